@@ -0,0 +1,112 @@
+//! `fsck`: reads every SST file of each table with `EmbedDb::verify_table`, and with `--repair`
+//! sets the unreadable ones aside with `EmbedDb::repair_table`.
+
+use std::fmt::Write as _;
+
+use anyhow::{anyhow, Result};
+use embeddb::{EmbedDb, TableVerification};
+
+/// Checks `table`, or every table, repairing them when `repair` is set.
+pub fn run(db: &EmbedDb, table: Option<String>, repair: bool) -> Result<Vec<TableVerification>> {
+    let tables = match table {
+        Some(table) => vec![table],
+        None => db.list_tables()?,
+    };
+    tables
+        .iter()
+        .map(|table| {
+            if repair {
+                db.repair_table(table)
+            } else {
+                db.verify_table(table)
+            }
+        })
+        .collect()
+}
+
+/// One line per table, followed by its unreadable files and what repair did about them.
+pub fn render(reports: &[TableVerification]) -> String {
+    let mut out = String::new();
+    for report in reports {
+        let unreadable = report.unreadable().count();
+        let _ = write!(out, "{}: {} sst files", report.table, report.files.len());
+        if unreadable == 0 {
+            let _ = writeln!(out, ", ok");
+            continue;
+        }
+        let _ = writeln!(out, ", {unreadable} unreadable");
+        for check in report.unreadable() {
+            let _ = writeln!(
+                out,
+                "  {}  {}",
+                check.path.display(),
+                check.error.as_deref().unwrap_or_default()
+            );
+        }
+        for path in &report.quarantined {
+            let _ = writeln!(out, "  quarantined as {}", path.display());
+        }
+        if let Some(rows) = report.rows_rebuilt {
+            let _ = writeln!(out, "  rebuilt {rows} rows from the WAL history");
+        }
+        if let Some(reason) = &report.rebuild_error {
+            let _ = writeln!(out, "  rows of quarantined files not rebuilt: {reason}");
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Fails when a table has unreadable files still in use.
+pub fn check(reports: &[TableVerification]) -> Result<()> {
+    let damaged = reports.iter().filter(|report| !report.is_clean()).count();
+    if damaged == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{damaged} of {} tables have unreadable sst files (rerun with --repair)",
+            reports.len()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use embeddb::{Column, Config, DataType, TableSchema, Value};
+
+    use super::*;
+
+    #[test]
+    fn fsck_reports_and_repairs_a_corrupt_sst() {
+        let dir = tempfile::tempdir().unwrap();
+        let db =
+            EmbedDb::open(Config::new(dir.path().to_path_buf()).with_wal_archive(true)).unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, None).unwrap();
+        let fields = BTreeMap::from([("title".to_string(), Value::String("a".into()))]);
+        db.insert_row("notes", fields).unwrap();
+        db.flush_table("notes").unwrap();
+
+        let reports = run(&db, None, false).unwrap();
+        assert_eq!(render(&reports), "notes: 1 sst files, ok");
+        assert!(check(&reports).is_ok());
+
+        fs::write(&reports[0].files[0].path, b"EDBSST").unwrap();
+        let reports = run(&db, Some("notes".to_string()), false).unwrap();
+        let text = render(&reports);
+        assert!(text.contains("1 unreadable"), "{text}");
+        assert!(check(&reports).is_err());
+
+        let reports = run(&db, None, true).unwrap();
+        let text = render(&reports);
+        assert!(text.contains("quarantined as"), "{text}");
+        assert!(text.contains("rebuilt 1 rows"), "{text}");
+        assert!(check(&reports).is_ok());
+        assert_eq!(
+            render(&run(&db, None, false).unwrap()),
+            "notes: 1 sst files, ok"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use embeddb::{DataType, EmbedDb, TableSchema, Value};
+use serde::Serialize;
+
+use crate::json_to_value;
+
+/// Rejections listed individually in the report; `rows_rejected` counts all of them.
+pub(crate) const MAX_REPORTED_REJECTIONS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// One JSON object per line, keyed by column name.
+    Jsonl,
+    /// A header row of column names followed by one record per row. Empty cells are null.
+    Csv,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedRecord {
+    /// Line number for JSONL, record number (not counting the header) for CSV.
+    pub record: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub records_read: usize,
+    pub rows_inserted: usize,
+    pub rows_rejected: usize,
+    pub batches: usize,
+    pub first_row_id: Option<u64>,
+    pub last_row_id: Option<u64>,
+    pub rejected: Vec<RejectedRecord>,
+}
+
+/// Streams records from `path` into `table`, converting values to the column types of the table
+/// schema. Records that fail conversion or validation are rejected and reported; the rest are
+/// inserted `batch_size` at a time with one WAL sync per batch. `progress` runs after each batch.
+pub fn import_file(
+    db: &EmbedDb,
+    table: &str,
+    path: &Path,
+    format: ImportFormat,
+    batch_size: usize,
+    mut progress: impl FnMut(&ImportReport),
+) -> Result<ImportReport> {
+    if batch_size == 0 {
+        return Err(anyhow!("--batch-size must be at least 1"));
+    }
+    let schema = db.describe_table(table)?.schema;
+    let file = File::open(path)?;
+    let mut importer = Importer {
+        db,
+        table,
+        schema: &schema,
+        batch_size,
+        batch: Vec::with_capacity(batch_size),
+        report: ImportReport::default(),
+    };
+
+    match format {
+        ImportFormat::Jsonl => {
+            for (idx, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let fields = parse_json_record(&schema, &line);
+                importer.push(idx + 1, fields, &mut progress)?;
+            }
+        }
+        ImportFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(file);
+            let headers = reader.headers()?.clone();
+            let mut columns = Vec::with_capacity(headers.len());
+            for name in &headers {
+                let column = schema
+                    .columns
+                    .iter()
+                    .find(|col| col.name == name)
+                    .ok_or_else(|| anyhow!("CSV column '{name}' is not in table '{table}'"))?;
+                columns.push(column);
+            }
+            for (idx, record) in reader.records().enumerate() {
+                let fields = record.map_err(anyhow::Error::from).and_then(|record| {
+                    let mut fields = BTreeMap::new();
+                    for (column, cell) in columns.iter().zip(record.iter()) {
+                        if !cell.is_empty() {
+                            let value = parse_cell(&column.data_type, cell)
+                                .map_err(|err| anyhow!("column '{}': {err}", column.name))?;
+                            fields.insert(column.name.clone(), value);
+                        }
+                    }
+                    Ok(fields)
+                });
+                importer.push(idx + 1, fields, &mut progress)?;
+            }
+        }
+    }
+
+    importer.flush(&mut progress)?;
+    Ok(importer.report)
+}
+
+struct Importer<'a> {
+    db: &'a EmbedDb,
+    table: &'a str,
+    schema: &'a TableSchema,
+    batch_size: usize,
+    batch: Vec<BTreeMap<String, Value>>,
+    report: ImportReport,
+}
+
+impl Importer<'_> {
+    fn push(
+        &mut self,
+        record: usize,
+        fields: Result<BTreeMap<String, Value>>,
+        progress: &mut impl FnMut(&ImportReport),
+    ) -> Result<()> {
+        self.report.records_read += 1;
+        // Validate up front so a bad record is rejected on its own instead of failing its batch.
+        let checked = fields.and_then(|fields| {
+            let mut generated = fields.clone();
+            self.schema.apply_defaults(&mut generated, 0);
+            self.schema.apply_generated(&mut generated)?;
+            self.schema.validate_row(&generated)?;
+            Ok(fields)
+        });
+        match checked {
+            Ok(fields) => {
+                self.batch.push(fields);
+                if self.batch.len() >= self.batch_size {
+                    self.flush(progress)?;
+                }
+            }
+            Err(err) => {
+                self.report.rows_rejected += 1;
+                if self.report.rejected.len() < MAX_REPORTED_REJECTIONS {
+                    self.report.rejected.push(RejectedRecord {
+                        record,
+                        reason: err.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, progress: &mut impl FnMut(&ImportReport)) -> Result<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        let row_ids = self.db.insert_rows(self.table, rows)?;
+        if let Some(first) = row_ids.first() {
+            self.report.first_row_id.get_or_insert(*first);
+        }
+        self.report.last_row_id = row_ids.last().copied().or(self.report.last_row_id);
+        self.report.rows_inserted += row_ids.len();
+        self.report.batches += 1;
+        progress(&self.report);
+        Ok(())
+    }
+}
+
+fn parse_json_record(schema: &TableSchema, line: &str) -> Result<BTreeMap<String, Value>> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow!("record must be a JSON object"))?;
+    let mut fields = BTreeMap::new();
+    for (key, val) in object {
+        let value = match (json_to_value(val)?, column_type(schema, key)) {
+            (Value::Int(v), Some(DataType::Float)) => Value::Float(v as f64),
+            (Value::String(v), Some(DataType::Bytes)) => decode_bytes(&v)?,
+            (value, _) => value,
+        };
+        fields.insert(key.clone(), value);
+    }
+    Ok(fields)
+}
+
+fn column_type<'a>(schema: &'a TableSchema, name: &str) -> Option<&'a DataType> {
+    schema
+        .columns
+        .iter()
+        .find(|col| col.name == name)
+        .map(|col| &col.data_type)
+}
+
+fn parse_cell(data_type: &DataType, cell: &str) -> Result<Value> {
+    Ok(match data_type {
+        DataType::Int => Value::Int(cell.trim().parse()?),
+        DataType::Float => Value::Float(cell.trim().parse()?),
+        DataType::Bool => match cell.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Value::Bool(true),
+            "false" | "0" => Value::Bool(false),
+            other => return Err(anyhow!("invalid bool '{other}'")),
+        },
+        DataType::String => Value::String(cell.to_string()),
+        DataType::Bytes => decode_bytes(cell)?,
+    })
+}
+
+/// Bytes columns are imported from base64, the encoding `Value::as_string` produces.
+fn decode_bytes(text: &str) -> Result<Value> {
+    let bytes = general_purpose::STANDARD
+        .decode(text.trim())
+        .map_err(|err| anyhow!("invalid base64: {err}"))?;
+    Ok(Value::Bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use embeddb::{Column, Config};
+
+    use super::*;
+
+    fn open_db(dir: &Path) -> EmbedDb {
+        let db = EmbedDb::open(Config::new(dir.join("data"))).unwrap();
+        let schema = TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("score", DataType::Float, true),
+            Column::new("done", DataType::Bool, true),
+        ]);
+        db.create_table("notes", schema, None).unwrap();
+        db
+    }
+
+    #[test]
+    fn jsonl_import_batches_rows_and_reports_rejections() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open_db(dir.path());
+        let path = dir.path().join("notes.jsonl");
+        fs::write(
+            &path,
+            concat!(
+                "{\"title\": \"a\", \"score\": 1}\n",
+                "\n",
+                "{\"title\": \"b\", \"score\": 0.5, \"done\": true}\n",
+                "{\"score\": 2.0}\n",
+                "not json\n",
+                "{\"title\": \"c\"}\n",
+            ),
+        )
+        .unwrap();
+
+        let mut batches_seen = 0;
+        let report = import_file(&db, "notes", &path, ImportFormat::Jsonl, 2, |_| {
+            batches_seen += 1
+        })
+        .unwrap();
+        assert_eq!(report.records_read, 5);
+        assert_eq!(report.rows_inserted, 3);
+        assert_eq!(report.rows_rejected, 2);
+        assert_eq!(report.batches, 2);
+        assert_eq!(batches_seen, 2);
+        assert_eq!(
+            report.rejected.iter().map(|r| r.record).collect::<Vec<_>>(),
+            [4, 5]
+        );
+        assert!(report.rejected[0].reason.contains("title"));
+
+        let first = db.get_row("notes", report.first_row_id.unwrap()).unwrap();
+        assert_eq!(first.unwrap().fields["score"], Value::Float(1.0));
+        let last = db.get_row("notes", report.last_row_id.unwrap()).unwrap();
+        assert_eq!(last.unwrap().fields["title"], Value::String("c".into()));
+    }
+
+    #[test]
+    fn csv_import_maps_columns_by_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = open_db(dir.path());
+        let path = dir.path().join("notes.csv");
+        fs::write(
+            &path,
+            "done,title,score\ntrue,\"hello, world\",0.25\n,plain,\nmaybe,bad,1\n",
+        )
+        .unwrap();
+
+        let report = import_file(&db, "notes", &path, ImportFormat::Csv, 100, |_| {}).unwrap();
+        assert_eq!(report.rows_inserted, 2);
+        assert_eq!(report.rows_rejected, 1);
+        assert_eq!(report.rejected[0].record, 3);
+        assert!(report.rejected[0].reason.contains("done"));
+
+        let row = db.get_row("notes", report.first_row_id.unwrap()).unwrap();
+        let fields = row.unwrap().fields;
+        assert_eq!(fields["title"], Value::String("hello, world".into()));
+        assert_eq!(fields["done"], Value::Bool(true));
+        let row = db.get_row("notes", report.last_row_id.unwrap()).unwrap();
+        assert!(!row.unwrap().fields.contains_key("score"));
+
+        fs::write(&path, "title,missing\nx,y\n").unwrap();
+        let err = import_file(&db, "notes", &path, ImportFormat::Csv, 100, |_| {}).unwrap_err();
+        assert!(err.to_string().contains("'missing'"));
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use embeddb::{Column, DataType, EmbedDb, EmbeddingSpec, TableSchema, Value};
+use serde::Serialize;
+
+/// Column names used for ingested chunks. A missing table is created with all three, embedding the
+/// text column; an existing table must have the text and source columns, and gets chunk indexes
+/// only if it has the chunk column.
+#[derive(Debug, Clone)]
+pub struct IngestColumns {
+    pub text: String,
+    pub source: String,
+    pub chunk: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestReport {
+    pub table_created: bool,
+    pub files_ingested: usize,
+    pub chunks_inserted: usize,
+    pub first_row_id: Option<u64>,
+    pub last_row_id: Option<u64>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Walks `root`, chunks every file whose relative path matches `glob`, and inserts one row per
+/// chunk. Embedding jobs are enqueued by the table's embedding spec as rows are inserted.
+pub fn ingest_dir(
+    db: &EmbedDb,
+    table: &str,
+    root: &Path,
+    glob: &str,
+    chunk_chars: usize,
+    columns: &IngestColumns,
+) -> Result<IngestReport> {
+    if chunk_chars == 0 {
+        return Err(anyhow!("--chunk must be at least 1"));
+    }
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a directory", root.display()));
+    }
+
+    let mut report = IngestReport::default();
+    let with_chunk_index = prepare_table(db, table, columns, &mut report)?;
+
+    let mut files = Vec::new();
+    walk(root, root, glob, &mut files)?;
+
+    for (path, relative) in files {
+        let text = match extract_text(&path) {
+            Ok(text) => text,
+            Err(err) => {
+                report.skipped.push(SkippedFile {
+                    path: relative,
+                    reason: err.to_string(),
+                });
+                continue;
+            }
+        };
+        let chunks = chunk_text(&text, chunk_chars);
+        if chunks.is_empty() {
+            report.skipped.push(SkippedFile {
+                path: relative,
+                reason: "no text".to_string(),
+            });
+            continue;
+        }
+
+        for (idx, chunk) in chunks.into_iter().enumerate() {
+            let mut fields = BTreeMap::new();
+            fields.insert(columns.text.clone(), Value::String(chunk));
+            fields.insert(columns.source.clone(), Value::String(relative.clone()));
+            if with_chunk_index {
+                fields.insert(columns.chunk.clone(), Value::Int(idx as i64));
+            }
+            let row_id = db.insert_row(table, fields)?;
+            report.first_row_id.get_or_insert(row_id);
+            report.last_row_id = Some(row_id);
+            report.chunks_inserted += 1;
+        }
+        report.files_ingested += 1;
+    }
+
+    Ok(report)
+}
+
+/// Creates the table if needed and returns whether rows should carry a chunk index.
+fn prepare_table(
+    db: &EmbedDb,
+    table: &str,
+    columns: &IngestColumns,
+    report: &mut IngestReport,
+) -> Result<bool> {
+    if !db.list_tables()?.iter().any(|name| name == table) {
+        let schema = TableSchema::new(vec![
+            Column::new(columns.source.clone(), DataType::String, false),
+            Column::new(columns.chunk.clone(), DataType::Int, false),
+            Column::new(columns.text.clone(), DataType::String, false),
+        ]);
+        let spec = EmbeddingSpec::new(vec![columns.text.clone()]);
+        db.create_table(table, schema, Some(spec))?;
+        report.table_created = true;
+        return Ok(true);
+    }
+
+    let desc = db.describe_table(table)?;
+    let has_column = |name: &str| desc.schema.columns.iter().any(|col| col.name == name);
+    for name in [&columns.text, &columns.source] {
+        if !has_column(name) {
+            return Err(anyhow!("table '{table}' has no column '{name}'"));
+        }
+    }
+    if desc.embedding_spec.is_none() {
+        tracing::warn!(
+            table,
+            "table has no embedding spec; no embedding jobs will be enqueued"
+        );
+    }
+    Ok(has_column(&columns.chunk))
+}
+
+/// Collects matching files in a stable order as `(path, relative path with '/' separators)`.
+/// Hidden files and directories are skipped and symlinks are not followed.
+fn walk(root: &Path, dir: &Path, glob: &str, out: &mut Vec<(PathBuf, String)>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(root, &path, glob, out)?;
+        } else if file_type.is_file() {
+            let relative = path
+                .strip_prefix(root)?
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if glob_match(glob, &relative) {
+                out.push((path, relative));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Matches a '/'-separated relative path against a glob. `*` and `?` stay within one path
+/// segment and `**` spans any number of segments; a pattern without '/' matches the file name at
+/// any depth.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let mut pattern_parts: Vec<&str> = pattern.split('/').filter(|p| !p.is_empty()).collect();
+    if !pattern.contains('/') {
+        pattern_parts.insert(0, "**");
+    }
+    let path_parts: Vec<&str> = path.split('/').collect();
+    match_parts(&pattern_parts, &path_parts)
+}
+
+fn match_parts(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_parts(rest, &path[skip..])),
+        Some((part, rest)) => path
+            .split_first()
+            .is_some_and(|(name, tail)| match_segment(part, name) && match_parts(rest, tail)),
+    }
+}
+
+fn match_segment(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position to resume from after the last `*`: (pattern index, name index).
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(&c) if c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn extract_text(path: &Path) -> Result<String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("pdf") => extract_pdf(path),
+        Some("md") | Some("markdown") => Ok(strip_front_matter(&read_utf8(path)?).to_string()),
+        _ => read_utf8(path),
+    }
+}
+
+fn read_utf8(path: &Path) -> Result<String> {
+    String::from_utf8(fs::read(path)?).map_err(|_| anyhow!("not valid UTF-8 text"))
+}
+
+#[cfg(feature = "pdf")]
+fn extract_pdf(path: &Path) -> Result<String> {
+    pdf_extract::extract_text(path).map_err(|err| anyhow!("pdf extraction failed: {err}"))
+}
+
+#[cfg(not(feature = "pdf"))]
+fn extract_pdf(_path: &Path) -> Result<String> {
+    Err(anyhow!("PDF support requires the `pdf` feature"))
+}
+
+/// Drops a leading YAML front matter block (`---` ... `---`).
+fn strip_front_matter(text: &str) -> &str {
+    let Some(body) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return text;
+    };
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            return &body[offset..];
+        }
+    }
+    text
+}
+
+/// Splits text into chunks of at most `max_chars` characters, preferring to break at a paragraph,
+/// then a line, then a word boundary in the second half of each window.
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let Some((end, _)) = rest.char_indices().nth(max_chars) else {
+            chunks.push(rest.to_string());
+            break;
+        };
+        let window = &rest[..end];
+        let min = window.len() / 2;
+        let cut = window
+            .rfind("\n\n")
+            .filter(|&idx| idx >= min)
+            .or_else(|| window.rfind('\n').filter(|&idx| idx >= min))
+            .or_else(|| window.rfind(char::is_whitespace).filter(|&idx| idx >= min))
+            .filter(|&idx| idx > 0)
+            .unwrap_or(end);
+        let chunk = rest[..cut].trim_end();
+        if !chunk.is_empty() {
+            chunks.push(chunk.to_string());
+        }
+        rest = rest[cut..].trim_start();
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_segments() {
+        assert!(glob_match("**/*.md", "a.md"));
+        assert!(glob_match("**/*.md", "guides/deep/a.md"));
+        assert!(glob_match("*.md", "guides/a.md"));
+        assert!(glob_match("guides/*.md", "guides/a.md"));
+        assert!(!glob_match("guides/*.md", "guides/deep/a.md"));
+        assert!(glob_match("guides/**/a?.txt", "guides/x/y/ab.txt"));
+        assert!(!glob_match("**/*.md", "notes.txt"));
+        assert!(glob_match("**/*", "anything/at/all"));
+    }
+
+    #[test]
+    fn chunks_prefer_boundaries_and_respect_limit() {
+        let text = "first paragraph here\n\nsecond paragraph that is longer";
+        let chunks = chunk_text(text, 30);
+        assert_eq!(chunks[0], "first paragraph here");
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 30));
+        assert_eq!(chunks.join(" ").split_whitespace().count(), 8);
+
+        // No boundaries: hard split on character (not byte) counts.
+        assert_eq!(chunk_text("ééééé", 2), vec!["éé", "éé", "é"]);
+        assert!(chunk_text("  \n ", 10).is_empty());
+    }
+
+    #[test]
+    fn strips_markdown_front_matter() {
+        assert_eq!(strip_front_matter("---\ntitle: x\n---\n# Body"), "# Body");
+        assert_eq!(strip_front_matter("# No front matter"), "# No front matter");
+        assert_eq!(strip_front_matter("---\nunterminated"), "---\nunterminated");
+    }
+}
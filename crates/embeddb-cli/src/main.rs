@@ -1,16 +1,33 @@
 use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use clap::{Parser, Subcommand, ValueEnum};
 use embeddb::{
-    Column, Config, DistanceMetric, EmbedDb, Embedder, EmbeddingSpec, FilterCondition, FilterOp,
-    TableSchema, Value,
+    parse_filter, AggregateFn, Aggregation, AlterTableOp, Column, Config, DataType, DistanceMetric,
+    EmbedDb, Embedder, EmbeddingSpec, EmbeddingStatus, FilterCondition, FilterOp, Fusion,
+    IndexSpec, JobListOptions, JobSort, RowCodecKind, ScoreMode, SearchCursor, SearchOptions,
+    SparseVector, TableSchema, Value, VectorEncoding, WalEncoding,
 };
 use serde::Deserialize;
 use tracing_subscriber::EnvFilter;
 
+mod fsck;
+mod import;
+mod ingest;
+#[cfg(feature = "parquet")]
+mod parquet_export;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod wal;
+
+use import::ImportFormat;
+use ingest::IngestColumns;
+use stats::StatsTarget;
+
 #[derive(Parser, Debug)]
 #[command(name = "embeddb")]
 #[command(about = "EmbedDB CLI")]
@@ -21,17 +38,128 @@ struct Cli {
     #[arg(long)]
     wal_autocheckpoint_bytes: Option<u64>,
 
+    /// Seal `wal.log` into a new segment once it reaches this many bytes.
+    #[arg(long)]
+    wal_segment_bytes: Option<u64>,
+
+    /// Encoding for rows in newly written SST files.
+    #[arg(long, value_enum, default_value_t = RowCodecArg::Json)]
+    row_codec: RowCodecArg,
+
+    /// Encoding for WAL records; replay reads either.
+    #[arg(long, value_enum, default_value_t = WalEncodingArg::Json)]
+    wal_encoding: WalEncodingArg,
+
+    /// Keep WAL segments replaced by checkpoints under `wal_archive/` for `get --at-lsn`.
+    #[arg(long)]
+    wal_archive: bool,
+
+    /// Open without the data directory lock and without writing, e.g. to inspect a directory a
+    /// running server holds. Commands that write fail.
+    #[arg(long)]
+    read_only: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(clap::Args, Debug)]
+struct StatsOutput {
+    /// Refresh every interval (e.g. `2s`, `500ms`) until interrupted, reopening the data
+    /// directory read-only each time.
+    #[arg(long, value_parser = stats::parse_interval)]
+    watch: Option<Duration>,
+    /// Print the full stats as JSON (one line per refresh with `--watch`).
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotCommand {
+    /// Checkpoint and copy the database into an empty or missing directory.
+    Create { dest_dir: PathBuf },
+    /// Rebuild `--data-dir` (empty or missing) from a snapshot directory.
+    Restore { snapshot_dir: PathBuf },
+}
+
+#[derive(Subcommand, Debug)]
+enum WalCommand {
+    /// List every WAL record: offset, size, checksum status, type, table, and row id.
+    Inspect {
+        /// Inspect this WAL file instead of the files of `--data-dir`.
+        #[arg(long)]
+        file: Option<PathBuf>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report where replay would stop and how many records it would drop; exits non-zero if
+    /// any file would not replay in full.
+    Verify {
+        /// Verify this WAL file instead of the files of `--data-dir`.
+        #[arg(long)]
+        file: Option<PathBuf>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AlterCommand {
+    /// Add a column; existing rows read `--default` (a JSON value) for it.
+    #[command(name = "add-column")]
+    Add {
+        name: String,
+        #[arg(long = "type", value_enum)]
+        data_type: ColumnTypeArg,
+        #[arg(long)]
+        nullable: bool,
+        #[arg(long)]
+        default: Option<String>,
+    },
+    #[command(name = "drop-column")]
+    Drop { name: String },
+    #[command(name = "rename-column")]
+    Rename { from: String, to: String },
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
-    DbStats,
+    /// Database-wide stats: WAL size, checkpoints, and embedding totals.
+    DbStats {
+        #[command(flatten)]
+        output: StatsOutput,
+    },
     Checkpoint,
+    /// Back up or restore the data directory.
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+    /// Read every SST file and report the ones that can't be read; exits non-zero while any are
+    /// still in use.
+    Fsck {
+        /// Check only this table.
+        #[arg(long)]
+        table: Option<String>,
+        /// Quarantine unreadable files and rebuild their tables from the WAL history when it is
+        /// retained (`--wal-archive`).
+        #[arg(long)]
+        repair: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Inspect or verify the WAL without opening the database.
+    Wal {
+        #[command(subcommand)]
+        command: WalCommand,
+    },
+    /// Same as `snapshot create`.
+    #[command(hide = true)]
     SnapshotExport {
         dest_dir: PathBuf,
     },
+    /// Same as `snapshot restore`.
+    #[command(hide = true)]
     SnapshotRestore {
         snapshot_dir: PathBuf,
     },
@@ -39,8 +167,15 @@ enum Commands {
     DescribeTable {
         table: String,
     },
+    /// A table's rows, tombstones, embeddings, and SST files.
+    #[command(visible_alias = "stats")]
     TableStats {
         table: String,
+        #[command(flatten)]
+        output: StatsOutput,
+    },
+    IndexStatus {
+        table: String,
     },
     CreateTable {
         table: String,
@@ -48,25 +183,190 @@ enum Commands {
         schema: PathBuf,
         #[arg(long)]
         embed_fields: Option<String>,
+        /// Metric the table's embeddings are intended for (cosine tables store unit vectors).
+        #[arg(long, value_enum)]
+        embed_metric: Option<MetricArg>,
+        /// In-memory vector representation (`f16` halves memory at a small accuracy cost).
+        #[arg(long, value_enum, default_value_t = VectorEncodingArg::F32)]
+        vector_encoding: VectorEncodingArg,
+        /// Vector index for kNN search (`hnsw` uses default parameters).
+        #[arg(long, value_enum, default_value_t = IndexArg::Flat)]
+        index: IndexArg,
+        /// Expected embedding length; embeddings of any other length fail their job.
+        #[arg(long)]
+        embed_dimensions: Option<usize>,
+    },
+    /// Replace a table's embedding spec and re-enqueue rows whose content hash changes.
+    SetEmbeddingSpec {
+        table: String,
+        #[arg(long)]
+        embed_fields: String,
+        #[arg(long, value_enum)]
+        embed_metric: Option<MetricArg>,
+        #[arg(long, value_enum, default_value_t = VectorEncodingArg::F32)]
+        vector_encoding: VectorEncodingArg,
+        #[arg(long, value_enum, default_value_t = IndexArg::Flat)]
+        index: IndexArg,
+        #[arg(long)]
+        embed_dimensions: Option<usize>,
+        /// Report the affected rows without changing anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Give a table an embedding spec (or keep its current one) and embed every row that needs
+    /// it, in batches, reporting progress on stderr.
+    BackfillEmbeddings {
+        table: String,
+        /// Comma-separated source fields of the new spec; omit to backfill under the current one.
+        #[arg(long)]
+        embed_fields: Option<String>,
+        #[arg(long, value_enum)]
+        embed_metric: Option<MetricArg>,
+        /// Rows embedded per batch.
+        #[arg(long, default_value_t = 256)]
+        batch_size: usize,
+    },
+    /// Add, drop, or rename a column without rewriting the table.
+    AlterTable {
+        table: String,
+        #[command(subcommand)]
+        change: AlterCommand,
+    },
+    /// Rename a table, keeping its rows, embeddings, and jobs.
+    RenameTable {
+        table: String,
+        new_name: String,
     },
     Insert {
         table: String,
         #[arg(long)]
         row: String,
+        /// Delete the row once this many seconds have passed (at the next flush or compaction).
+        #[arg(long)]
+        ttl_seconds: Option<u64>,
+        /// JSON array holding the row's precomputed embedding, stored instead of queueing a job.
+        #[arg(long, conflicts_with = "ttl_seconds")]
+        embedding: Option<String>,
+    },
+    /// Chunk every matching text file under a directory into rows and enqueue their embeddings.
+    /// Creates the table (source, chunk, content) when it does not exist.
+    IngestDir {
+        table: String,
+        #[arg(long)]
+        path: PathBuf,
+        /// Glob matched against paths relative to `--path`; without a `/` it matches file names
+        /// at any depth.
+        #[arg(long, default_value = "**/*")]
+        glob: String,
+        /// Maximum chunk length in characters.
+        #[arg(long, default_value_t = 800)]
+        chunk: usize,
+        #[arg(long, default_value = "content")]
+        text_column: String,
+        #[arg(long, default_value = "source")]
+        source_column: String,
+        #[arg(long, default_value = "chunk")]
+        chunk_column: String,
+    },
+    /// Stream rows from a JSONL or CSV file into an existing table, converting values to the
+    /// schema's column types. Invalid records are skipped and reported.
+    Import {
+        table: String,
+        #[arg(long)]
+        file: PathBuf,
+        /// Defaults to the file extension (`.jsonl`/`.ndjson` or `.csv`).
+        #[arg(long, value_enum)]
+        format: Option<ImportFormatArg>,
+        /// Rows inserted per WAL sync.
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+    },
+    /// Write a table and its ready embeddings to `<out>/<table>.parquet`, with the embeddings as
+    /// a fixed-size list column.
+    #[cfg(feature = "parquet")]
+    ExportParquet {
+        table: String,
+        #[arg(long)]
+        out: PathBuf,
+        /// Rows per Parquet row group.
+        #[arg(long, default_value_t = 65536)]
+        batch_size: usize,
+    },
+    /// Write a table and its ready embeddings to a SQLite database file.
+    #[cfg(feature = "sqlite")]
+    ExportSqlite {
+        table: String,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Read a table written by `export-sqlite` (or any SQLite table) into a table, creating it
+    /// if needed. Exported embeddings are restored instead of recomputed.
+    #[cfg(feature = "sqlite")]
+    ImportSqlite {
+        table: String,
+        #[arg(long)]
+        file: PathBuf,
+        /// SQLite table to read; defaults to `table`.
+        #[arg(long)]
+        source_table: Option<String>,
+        /// Rows inserted per WAL sync.
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
     },
     Get {
         table: String,
         row_id: u64,
+        /// Read the row as of a past LSN (see `db-stats` for the current one).
+        #[arg(long)]
+        at_lsn: Option<u64>,
     },
     Delete {
         table: String,
         row_id: u64,
+        /// Only delete the row if it is still at this version (see `get`).
+        #[arg(long)]
+        expected_version: Option<u64>,
     },
-    Jobs {
+    /// Bring back a row deleted from a soft-delete table.
+    Restore {
         table: String,
+        row_id: u64,
     },
-    ProcessJobs {
+    /// Delete the hidden rows of a soft-delete table for good.
+    PurgeHidden {
         table: String,
+    },
+    /// List rows in row id order.
+    Scan {
+        table: String,
+        /// Only list rows with a row id greater than this.
+        #[arg(long)]
+        after: Option<u64>,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// List embedding jobs of one table, or of every table when no table is given.
+    Jobs {
+        table: Option<String>,
+        #[arg(long, value_enum)]
+        status: Option<JobStatusArg>,
+        /// Only list jobs with a row id greater than this (less than it with `--desc`).
+        #[arg(long)]
+        after: Option<u64>,
+        /// Skip this many matching jobs; pages orders other than `row-id`.
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long)]
+        limit: Option<usize>,
+        #[arg(long, value_enum, default_value_t = JobSortArg::RowId)]
+        sort: JobSortArg,
+        /// Sort in descending order.
+        #[arg(long)]
+        desc: bool,
+    },
+    /// Embed pending jobs of one table, or of every table in turn when no table is given.
+    ProcessJobs {
+        table: Option<String>,
         #[arg(long)]
         limit: Option<usize>,
     },
@@ -81,12 +381,35 @@ enum Commands {
         query: String,
         #[arg(long, default_value_t = 5)]
         k: usize,
-        #[arg(long, value_enum, default_value_t = MetricArg::Cosine)]
-        metric: MetricArg,
-        /// JSON array of filter conditions.
+        /// Defaults to the metric declared on the table's embedding spec.
+        #[arg(long, value_enum)]
+        metric: Option<MetricArg>,
+        /// JSON array of filter conditions (ops: Eq, Neq, Lt, Lte, Gt, Gte, Contains).
         /// Example: `[{"column":"age","op":"Gte","value":21},{"column":"score","op":"Lt","value":0.5}]`
         #[arg(long)]
         filter: Option<String>,
+        /// Filter conditions as a where clause, ANDed with `--filter`.
+        /// Example: `score > 0.5 AND tag = "rust"`
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+        /// Allow a metric other than the one declared on the table's embedding spec.
+        #[arg(long)]
+        allow_metric_mismatch: bool,
+        /// Search this named vector of the embedding spec instead of its unnamed vector.
+        #[arg(long)]
+        vector: Option<String>,
+        /// Skip this many of the best hits.
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Return hits ranked after this cursor, as printed after a full page.
+        #[arg(long)]
+        after: Option<SearchCursor>,
+        /// `similarity` adds a 0..1 `score` (1 is an exact match) to each hit's distance.
+        #[arg(long, value_enum, default_value = "distance")]
+        score_mode: ScoreModeArg,
+        /// Print how the search would run instead of its results.
+        #[arg(long)]
+        explain: bool,
     },
     SearchText {
         table: String,
@@ -94,12 +417,132 @@ enum Commands {
         query_text: String,
         #[arg(long, default_value_t = 5)]
         k: usize,
-        #[arg(long, value_enum, default_value_t = MetricArg::Cosine)]
-        metric: MetricArg,
+        /// Defaults to the metric declared on the table's embedding spec.
+        #[arg(long, value_enum)]
+        metric: Option<MetricArg>,
         /// JSON array of filter conditions.
         /// Example: `[{"column":"title","op":"Eq","value":"Hello"}]`
         #[arg(long)]
         filter: Option<String>,
+        /// Filter conditions as a where clause, ANDed with `--filter`.
+        /// Example: `score > 0.5 AND tag = "rust"`
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+        /// Allow a metric other than the one declared on the table's embedding spec.
+        #[arg(long)]
+        allow_metric_mismatch: bool,
+        /// Search this named vector of the embedding spec instead of its unnamed vector.
+        #[arg(long)]
+        vector: Option<String>,
+        /// Skip this many of the best hits.
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// Return hits ranked after this cursor, as printed after a full page.
+        #[arg(long)]
+        after: Option<SearchCursor>,
+        /// `similarity` adds a 0..1 `score` (1 is an exact match) to each hit's distance.
+        #[arg(long, value_enum, default_value = "distance")]
+        score_mode: ScoreModeArg,
+    },
+    /// Fuse a BM25 keyword search over the table's String columns with a vector search.
+    SearchHybrid {
+        table: String,
+        #[arg(long)]
+        query_text: String,
+        /// Dense query (JSON array); defaults to embedding `--query-text` with the local embedder.
+        #[arg(long)]
+        query: Option<String>,
+        #[arg(long, default_value_t = 5)]
+        k: usize,
+        /// Weight of the keyword ranking; setting either weight switches to weighted fusion.
+        #[arg(long)]
+        keyword_weight: Option<f32>,
+        /// Weight of the vector ranking.
+        #[arg(long)]
+        vector_weight: Option<f32>,
+        /// JSON array of filter conditions.
+        #[arg(long)]
+        filter: Option<String>,
+        /// Filter conditions as a where clause, ANDed with `--filter`.
+        /// Example: `score > 0.5 AND tag = "rust"`
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+    },
+    /// Attach a sparse vector to a row.
+    PutSparse {
+        table: String,
+        row_id: u64,
+        /// Sparse vector as `index:value` pairs, comma-separated (e.g. `3:0.5,17:1.2`).
+        #[arg(long)]
+        sparse: String,
+    },
+    SearchSparse {
+        table: String,
+        /// Sparse query as `index:value` pairs, comma-separated.
+        #[arg(long)]
+        sparse: String,
+        /// Optional dense query (JSON array); fuses dense and sparse rankings when set.
+        #[arg(long)]
+        query: Option<String>,
+        #[arg(long, default_value_t = 5)]
+        k: usize,
+        /// Defaults to the metric declared on the table's embedding spec.
+        #[arg(long, value_enum)]
+        metric: Option<MetricArg>,
+        /// JSON array of filter conditions.
+        #[arg(long)]
+        filter: Option<String>,
+        /// Filter conditions as a where clause, ANDed with `--filter`.
+        /// Example: `score > 0.5 AND tag = "rust"`
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+    },
+    Similar {
+        table: String,
+        row_id: u64,
+        #[arg(long, default_value_t = 5)]
+        k: usize,
+        /// Keep the source row in the results.
+        #[arg(long)]
+        include_self: bool,
+    },
+    Recommend {
+        table: String,
+        /// Comma-separated row ids to move toward.
+        #[arg(long, value_delimiter = ',', required = true)]
+        positive: Vec<u64>,
+        /// Comma-separated row ids to move away from.
+        #[arg(long, value_delimiter = ',')]
+        negative: Vec<u64>,
+        #[arg(long, default_value_t = 5)]
+        k: usize,
+    },
+    /// Count live rows, or only those matching the filters.
+    Count {
+        table: String,
+        /// JSON array of filter conditions.
+        #[arg(long)]
+        filter: Option<String>,
+        /// Filter conditions as a where clause, ANDed with `--filter`.
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+    },
+    Aggregate {
+        table: String,
+        /// Columns to group by, comma-separated.
+        #[arg(long, value_delimiter = ',')]
+        group_by: Vec<String>,
+        /// Aggregations as `count`, `count:<col>`, `sum:<col>`, `avg:<col>`, `min:<col>`, or
+        /// `max:<col>`. Repeatable.
+        #[arg(long = "agg", required = true)]
+        aggs: Vec<String>,
+        /// JSON array of filter conditions.
+        #[arg(long)]
+        filter: Option<String>,
+        /// Filter conditions as a where clause, ANDed with `--filter`.
+        /// Example: `score > 0.5 AND tag = "rust"`
+        #[arg(long = "where")]
+        where_clause: Option<String>,
     },
     Flush {
         table: String,
@@ -124,6 +567,155 @@ impl From<MetricArg> for DistanceMetric {
     }
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+enum ScoreModeArg {
+    Distance,
+    Similarity,
+}
+
+impl From<ScoreModeArg> for ScoreMode {
+    fn from(value: ScoreModeArg) -> Self {
+        match value {
+            ScoreModeArg::Distance => ScoreMode::Distance,
+            ScoreModeArg::Similarity => ScoreMode::Similarity,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum ColumnTypeArg {
+    Int,
+    Float,
+    Bool,
+    String,
+    Bytes,
+}
+
+impl From<ColumnTypeArg> for DataType {
+    fn from(value: ColumnTypeArg) -> Self {
+        match value {
+            ColumnTypeArg::Int => DataType::Int,
+            ColumnTypeArg::Float => DataType::Float,
+            ColumnTypeArg::Bool => DataType::Bool,
+            ColumnTypeArg::String => DataType::String,
+            ColumnTypeArg::Bytes => DataType::Bytes,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum JobStatusArg {
+    Pending,
+    Ready,
+    Failed,
+}
+
+impl From<JobStatusArg> for EmbeddingStatus {
+    fn from(value: JobStatusArg) -> Self {
+        match value {
+            JobStatusArg::Pending => EmbeddingStatus::Pending,
+            JobStatusArg::Ready => EmbeddingStatus::Ready,
+            JobStatusArg::Failed => EmbeddingStatus::Failed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum JobSortArg {
+    RowId,
+    Attempts,
+    NextRetryAt,
+}
+
+impl From<JobSortArg> for JobSort {
+    fn from(value: JobSortArg) -> Self {
+        match value {
+            JobSortArg::RowId => JobSort::RowId,
+            JobSortArg::Attempts => JobSort::Attempts,
+            JobSortArg::NextRetryAt => JobSort::NextRetryAt,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum VectorEncodingArg {
+    F32,
+    F16,
+    Int8,
+}
+
+impl From<VectorEncodingArg> for VectorEncoding {
+    fn from(value: VectorEncodingArg) -> Self {
+        match value {
+            VectorEncodingArg::F32 => VectorEncoding::F32,
+            VectorEncodingArg::F16 => VectorEncoding::F16,
+            VectorEncodingArg::Int8 => VectorEncoding::Int8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum IndexArg {
+    Flat,
+    Hnsw,
+}
+
+impl From<IndexArg> for IndexSpec {
+    fn from(value: IndexArg) -> Self {
+        match value {
+            IndexArg::Flat => IndexSpec::Flat,
+            IndexArg::Hnsw => IndexSpec::hnsw(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum ImportFormatArg {
+    Jsonl,
+    Csv,
+}
+
+impl From<ImportFormatArg> for ImportFormat {
+    fn from(value: ImportFormatArg) -> Self {
+        match value {
+            ImportFormatArg::Jsonl => ImportFormat::Jsonl,
+            ImportFormatArg::Csv => ImportFormat::Csv,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum RowCodecArg {
+    Json,
+    Bincode,
+    Msgpack,
+}
+
+impl From<RowCodecArg> for RowCodecKind {
+    fn from(value: RowCodecArg) -> Self {
+        match value {
+            RowCodecArg::Json => RowCodecKind::Json,
+            RowCodecArg::Bincode => RowCodecKind::Bincode,
+            RowCodecArg::Msgpack => RowCodecKind::MessagePack,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+enum WalEncodingArg {
+    Json,
+    Bincode,
+}
+
+impl From<WalEncodingArg> for WalEncoding {
+    fn from(value: WalEncodingArg) -> Self {
+        match value {
+            WalEncodingArg::Json => WalEncoding::Json,
+            WalEncodingArg::Bincode => WalEncoding::Bincode,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct SchemaFile {
     columns: Vec<Column>,
@@ -161,27 +753,83 @@ fn main() -> Result<()> {
     let config = match cli.wal_autocheckpoint_bytes {
         Some(bytes) => Config::new(cli.data_dir).with_wal_autocheckpoint_bytes(bytes),
         None => Config::new(cli.data_dir),
+    }
+    .with_row_codec(cli.row_codec.into())
+    .with_wal_encoding(cli.wal_encoding.into())
+    .with_wal_archive(cli.wal_archive)
+    .with_read_only(cli.read_only);
+    let config = match cli.wal_segment_bytes {
+        Some(bytes) => config.with_wal_segment_bytes(bytes),
+        None => config,
     };
 
-    let command = cli.command;
+    let command = match cli.command {
+        Commands::SnapshotExport { dest_dir } => Commands::Snapshot {
+            command: SnapshotCommand::Create { dest_dir },
+        },
+        Commands::SnapshotRestore { snapshot_dir } => Commands::Snapshot {
+            command: SnapshotCommand::Restore { snapshot_dir },
+        },
+        command => command,
+    };
     match command {
-        Commands::SnapshotRestore { snapshot_dir } => {
+        Commands::Snapshot {
+            command: SnapshotCommand::Restore { snapshot_dir },
+        } => {
             let stats = EmbedDb::restore_snapshot(snapshot_dir, &config.data_dir)?;
             println!("{}", serde_json::to_string_pretty(&stats)?);
         }
+        Commands::Wal {
+            command: WalCommand::Inspect { file, json },
+        } => {
+            let inspections = EmbedDb::inspect_wal(file.unwrap_or(config.data_dir))?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&inspections)?);
+            } else {
+                println!("{}", wal::render_inspect(&inspections));
+            }
+        }
+        Commands::Wal {
+            command: WalCommand::Verify { file, json },
+        } => {
+            let inspections = EmbedDb::inspect_wal(file.unwrap_or(config.data_dir))?;
+            if json {
+                let report = wal::verify_json(&inspections)?;
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                println!("{}", wal::render_verify(&inspections));
+            }
+            wal::check(&inspections)?;
+        }
+        Commands::DbStats {
+            output:
+                StatsOutput {
+                    watch: Some(interval),
+                    json,
+                },
+        } => stats::watch(&config, &StatsTarget::Db, json, interval)?,
+        Commands::TableStats {
+            table,
+            output:
+                StatsOutput {
+                    watch: Some(interval),
+                    json,
+                },
+        } => stats::watch(&config, &StatsTarget::Table(table), json, interval)?,
         other => {
             let db = EmbedDb::open(config)?;
 
             match other {
-                Commands::DbStats => {
-                    let stats = db.db_stats()?;
-                    println!("{}", serde_json::to_string_pretty(&stats)?);
+                Commands::DbStats { output } => {
+                    println!("{}", stats::render(&db, &StatsTarget::Db, output.json)?);
                 }
                 Commands::Checkpoint => {
                     let stats = db.checkpoint()?;
                     println!("{}", serde_json::to_string_pretty(&stats)?);
                 }
-                Commands::SnapshotExport { dest_dir } => {
+                Commands::Snapshot {
+                    command: SnapshotCommand::Create { dest_dir },
+                } => {
                     let stats = db.export_snapshot(dest_dir)?;
                     println!("{}", serde_json::to_string_pretty(&stats)?);
                 }
@@ -195,14 +843,22 @@ fn main() -> Result<()> {
                     let desc = db.describe_table(&table)?;
                     println!("{}", serde_json::to_string_pretty(&desc)?);
                 }
-                Commands::TableStats { table } => {
-                    let stats = db.table_stats(&table)?;
-                    println!("{}", serde_json::to_string_pretty(&stats)?);
+                Commands::TableStats { table, output } => {
+                    let target = StatsTarget::Table(table);
+                    println!("{}", stats::render(&db, &target, output.json)?);
+                }
+                Commands::IndexStatus { table } => {
+                    let status = db.index_status(&table)?;
+                    println!("{}", serde_json::to_string_pretty(&status)?);
                 }
                 Commands::CreateTable {
                     table,
                     schema,
                     embed_fields,
+                    embed_metric,
+                    vector_encoding,
+                    index,
+                    embed_dimensions,
                 } => {
                     let schema = load_schema(schema)?;
                     let embed_spec = embed_fields.map(|fields| {
@@ -211,34 +867,317 @@ fn main() -> Result<()> {
                             .map(|s| s.trim().to_string())
                             .filter(|s| !s.is_empty())
                             .collect();
-                        EmbeddingSpec::new(parts)
+                        let mut spec = EmbeddingSpec::new(parts)
+                            .with_vector_encoding(vector_encoding.into())
+                            .with_index(index.into());
+                        if let Some(metric) = embed_metric {
+                            spec = spec.with_metric(metric.into());
+                        }
+                        if let Some(dimensions) = embed_dimensions {
+                            spec = spec.with_dimensions(dimensions);
+                        }
+                        spec
                     });
                     db.create_table(table, schema, embed_spec)?;
                     println!("ok");
                 }
-                Commands::Insert { table, row } => {
+                Commands::SetEmbeddingSpec {
+                    table,
+                    embed_fields,
+                    embed_metric,
+                    vector_encoding,
+                    index,
+                    embed_dimensions,
+                    dry_run,
+                } => {
+                    let parts: Vec<String> = embed_fields
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                    let mut spec = EmbeddingSpec::new(parts)
+                        .with_vector_encoding(vector_encoding.into())
+                        .with_index(index.into());
+                    if let Some(metric) = embed_metric {
+                        spec = spec.with_metric(metric.into());
+                    }
+                    if let Some(dimensions) = embed_dimensions {
+                        spec = spec.with_dimensions(dimensions);
+                    }
+                    let plan = if dry_run {
+                        db.plan_embedding_spec(&table, &spec)?
+                    } else {
+                        db.apply_embedding_spec(&table, spec)?
+                    };
+                    println!("{}", serde_json::to_string_pretty(&plan)?);
+                }
+                Commands::BackfillEmbeddings {
+                    table,
+                    embed_fields,
+                    embed_metric,
+                    batch_size,
+                } => {
+                    let enqueued = match embed_fields {
+                        Some(fields) => {
+                            let parts: Vec<String> = fields
+                                .split(',')
+                                .map(|s| s.trim().to_string())
+                                .filter(|s| !s.is_empty())
+                                .collect();
+                            let mut spec = EmbeddingSpec::new(parts);
+                            if let Some(metric) = embed_metric {
+                                spec = spec.with_metric(metric.into());
+                            }
+                            db.apply_embedding_spec(&table, spec)?.affected_rows.len()
+                        }
+                        None if embed_metric.is_some() => {
+                            return Err(anyhow!("--embed-metric needs --embed-fields"));
+                        }
+                        None => {
+                            if db.describe_table(&table)?.embedding_spec.is_none() {
+                                return Err(anyhow!(
+                                    "table '{table}' has no embedding spec; pass --embed-fields"
+                                ));
+                            }
+                            0
+                        }
+                    };
+                    let total = db.table_stats(&table)?.embeddings_pending;
+                    let mut embedded = 0;
+                    loop {
+                        let processed = db.process_pending_jobs_with_limit(
+                            &table,
+                            &LocalHashEmbedder,
+                            batch_size.max(1),
+                        )?;
+                        if processed == 0 {
+                            break;
+                        }
+                        embedded += processed;
+                        eprintln!("embedded {embedded}/{total} rows");
+                    }
+                    let failed = db.table_stats(&table)?.embeddings_failed;
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "enqueued": enqueued,
+                            "embedded": embedded,
+                            "failed": failed
+                        })
+                    );
+                }
+                Commands::AlterTable { table, change } => {
+                    let op = match change {
+                        AlterCommand::Add {
+                            name,
+                            data_type,
+                            nullable,
+                            default,
+                        } => {
+                            let data_type = DataType::from(data_type);
+                            let default = match default {
+                                Some(raw) => match json_to_value(&serde_json::from_str(&raw)?)? {
+                                    Value::Int(v) if data_type == DataType::Float => {
+                                        Value::Float(v as f64)
+                                    }
+                                    value => value,
+                                },
+                                None => Value::Null,
+                            };
+                            AlterTableOp::AddColumn {
+                                column: Column::new(name, data_type, nullable),
+                                default,
+                            }
+                        }
+                        AlterCommand::Drop { name } => AlterTableOp::DropColumn { name },
+                        AlterCommand::Rename { from, to } => {
+                            AlterTableOp::RenameColumn { from, to }
+                        }
+                    };
+                    db.alter_table(&table, op)?;
+                    let schema = db.describe_table(&table)?.schema;
+                    println!("{}", serde_json::to_string_pretty(&schema)?);
+                }
+                Commands::RenameTable { table, new_name } => {
+                    db.rename_table(&table, new_name)?;
+                    println!("ok");
+                }
+                Commands::Insert {
+                    table,
+                    row,
+                    ttl_seconds,
+                    embedding,
+                } => {
                     let fields = parse_row(&row)?;
-                    let row_id = db.insert_row(&table, fields)?;
+                    let row_id = match (ttl_seconds, embedding) {
+                        (Some(ttl), _) => {
+                            db.insert_row_with_ttl(&table, fields, Duration::from_secs(ttl))?
+                        }
+                        (None, Some(raw)) => {
+                            db.insert_row_with_embedding(&table, fields, parse_vector(&raw)?)?
+                        }
+                        (None, None) => db.insert_row(&table, fields)?,
+                    };
                     println!("{}", row_id);
                 }
-                Commands::Get { table, row_id } => {
-                    let row = db.get_row(&table, row_id)?;
+                Commands::IngestDir {
+                    table,
+                    path,
+                    glob,
+                    chunk,
+                    text_column,
+                    source_column,
+                    chunk_column,
+                } => {
+                    let columns = IngestColumns {
+                        text: text_column,
+                        source: source_column,
+                        chunk: chunk_column,
+                    };
+                    let report = ingest::ingest_dir(&db, &table, &path, &glob, chunk, &columns)?;
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                Commands::Import {
+                    table,
+                    file,
+                    format,
+                    batch_size,
+                } => {
+                    let format = match format {
+                        Some(format) => format.into(),
+                        None => ImportFormat::from_path(&file).ok_or_else(|| {
+                            anyhow!(
+                                "cannot infer the format of {}; pass --format",
+                                file.display()
+                            )
+                        })?,
+                    };
+                    let report =
+                        import::import_file(&db, &table, &file, format, batch_size, |report| {
+                            eprintln!(
+                                "imported {} rows ({} rejected)",
+                                report.rows_inserted, report.rows_rejected
+                            );
+                        })?;
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                #[cfg(feature = "parquet")]
+                Commands::ExportParquet {
+                    table,
+                    out,
+                    batch_size,
+                } => {
+                    let report = parquet_export::export_parquet(&db, &table, &out, batch_size)?;
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                #[cfg(feature = "sqlite")]
+                Commands::ExportSqlite { table, out } => {
+                    let report = sqlite::export_sqlite(&db, &table, &out)?;
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                #[cfg(feature = "sqlite")]
+                Commands::ImportSqlite {
+                    table,
+                    file,
+                    source_table,
+                    batch_size,
+                } => {
+                    let report = sqlite::import_sqlite(
+                        &db,
+                        &table,
+                        &file,
+                        source_table.as_deref(),
+                        batch_size,
+                    )?;
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                Commands::Get {
+                    table,
+                    row_id,
+                    at_lsn,
+                } => {
+                    let row = match at_lsn {
+                        Some(lsn) => db.read_at_lsn(lsn)?.get_row(&table, row_id)?,
+                        None => db.get_row(&table, row_id)?,
+                    };
                     println!("{}", serde_json::to_string_pretty(&row)?);
                 }
-                Commands::Delete { table, row_id } => {
-                    db.delete_row(&table, row_id)?;
+                Commands::Delete {
+                    table,
+                    row_id,
+                    expected_version,
+                } => {
+                    match expected_version {
+                        Some(expected) => db.delete_row_if_version(&table, row_id, expected)?,
+                        None => db.delete_row(&table, row_id)?,
+                    }
                     println!("ok");
                 }
-                Commands::Jobs { table } => {
-                    let jobs = db.list_embedding_jobs(&table)?;
+                Commands::Restore { table, row_id } => {
+                    let row = db.restore_row(&table, row_id)?;
+                    println!("{}", serde_json::to_string_pretty(&row)?);
+                }
+                Commands::PurgeHidden { table } => {
+                    let purged = db.purge_hidden_rows(&table)?;
+                    println!("{}", serde_json::json!({ "purged": purged }));
+                }
+                Commands::Scan {
+                    table,
+                    after,
+                    limit,
+                } => {
+                    let page = db.scan_rows(&table, after, limit)?;
+                    if let Some(next) = page.next_cursor {
+                        eprintln!("more rows remain; continue with --after {next}");
+                    }
+                    println!("{}", serde_json::to_string_pretty(&page.items)?);
+                }
+                Commands::Jobs {
+                    table,
+                    status,
+                    after,
+                    offset,
+                    limit,
+                    sort,
+                    desc,
+                } => {
+                    let jobs = match table {
+                        Some(table) => {
+                            let options = JobListOptions {
+                                status: status.map(Into::into),
+                                cursor: after,
+                                offset,
+                                limit,
+                                sort: sort.into(),
+                                descending: desc,
+                            };
+                            let page = db.list_embedding_jobs_with_options(&table, &options)?;
+                            if let Some(next) = page.next_cursor {
+                                eprintln!("more jobs match; continue with --after {next}");
+                            } else if offset + page.items.len() < page.total {
+                                let next = offset + page.items.len();
+                                eprintln!("more jobs match; continue with --offset {next}");
+                            }
+                            page.items
+                        }
+                        None if after.is_some() || limit.is_some() || offset > 0 => {
+                            return Err(anyhow!("--after, --offset, and --limit need a table"));
+                        }
+                        None => db.list_embedding_jobs_all(status.map(Into::into))?,
+                    };
                     println!("{}", serde_json::to_string_pretty(&jobs)?);
                 }
                 Commands::ProcessJobs { table, limit } => {
-                    let processed = match limit {
-                        Some(limit) => {
+                    let processed = match (table, limit) {
+                        (Some(table), Some(limit)) => {
                             db.process_pending_jobs_with_limit(&table, &LocalHashEmbedder, limit)?
                         }
-                        None => db.process_pending_jobs(&table, &LocalHashEmbedder)?,
+                        (Some(table), None) => {
+                            db.process_pending_jobs(&table, &LocalHashEmbedder)?
+                        }
+                        (None, limit) => {
+                            db.process_all_pending(&LocalHashEmbedder, limit.unwrap_or(usize::MAX))?
+                        }
                     };
                     println!("{}", processed);
                 }
@@ -252,16 +1191,46 @@ fn main() -> Result<()> {
                     k,
                     metric,
                     filter,
+                    where_clause,
+                    allow_metric_mismatch,
+                    vector,
+                    offset,
+                    after,
+                    score_mode,
+                    explain,
                 } => {
                     let query_vec = parse_vector(&query)?;
-                    let hits = match filter.as_deref() {
-                        Some(raw) => {
-                            let filters = parse_filters(raw)?;
-                            db.search_knn_filtered(&table, &query_vec, k, metric.into(), &filters)?
-                        }
-                        None => db.search_knn(&table, &query_vec, k, metric.into())?,
+                    let filters = resolve_filters(filter.as_deref(), where_clause.as_deref())?;
+                    let options = SearchOptions {
+                        allow_metric_mismatch,
+                        vector,
+                        offset,
+                        after,
+                        score_mode: score_mode.into(),
+                        ..SearchOptions::default()
                     };
-                    println!("{}", serde_json::to_string_pretty(&hits)?);
+                    if explain {
+                        let plan = db.explain_search(
+                            &table,
+                            &query_vec,
+                            k,
+                            metric.map(DistanceMetric::from),
+                            &filters,
+                            &options,
+                        )?;
+                        println!("{}", serde_json::to_string_pretty(&plan)?);
+                    } else {
+                        let hits = db.search_knn_with_options(
+                            &table,
+                            &query_vec,
+                            k,
+                            metric.map(DistanceMetric::from),
+                            &filters,
+                            &options,
+                        )?;
+                        println!("{}", serde_json::to_string_pretty(&hits)?);
+                        print_next_search_page(&hits, k);
+                    }
                 }
                 Commands::SearchText {
                     table,
@@ -269,27 +1238,161 @@ fn main() -> Result<()> {
                     k,
                     metric,
                     filter,
+                    where_clause,
+                    allow_metric_mismatch,
+                    vector,
+                    offset,
+                    after,
+                    score_mode,
                 } => {
                     let embedder = LocalHashEmbedder;
                     let query_vec = embedder.embed(&query_text)?;
-                    let hits = match filter.as_deref() {
-                        Some(raw) => {
-                            let filters = parse_filters(raw)?;
-                            db.search_knn_filtered(&table, &query_vec, k, metric.into(), &filters)?
-                        }
-                        None => db.search_knn(&table, &query_vec, k, metric.into())?,
+                    let filters = resolve_filters(filter.as_deref(), where_clause.as_deref())?;
+                    let options = SearchOptions {
+                        allow_metric_mismatch,
+                        vector,
+                        offset,
+                        after,
+                        score_mode: score_mode.into(),
+                        ..SearchOptions::default()
+                    };
+                    let hits = db.search_knn_with_options(
+                        &table,
+                        &query_vec,
+                        k,
+                        metric.map(DistanceMetric::from),
+                        &filters,
+                        &options,
+                    )?;
+                    println!("{}", serde_json::to_string_pretty(&hits)?);
+                    print_next_search_page(&hits, k);
+                }
+                Commands::SearchHybrid {
+                    table,
+                    query_text,
+                    query,
+                    k,
+                    keyword_weight,
+                    vector_weight,
+                    filter,
+                    where_clause,
+                } => {
+                    let query_vec = match query.as_deref() {
+                        Some(raw) => parse_vector(raw)?,
+                        None => LocalHashEmbedder.embed(&query_text)?,
                     };
+                    let fusion = match (keyword_weight, vector_weight) {
+                        (None, None) => Fusion::ReciprocalRank,
+                        (keyword_weight, vector_weight) => Fusion::WeightedRank {
+                            keyword_weight: keyword_weight.unwrap_or(1.0),
+                            vector_weight: vector_weight.unwrap_or(1.0),
+                        },
+                    };
+                    let filters = resolve_filters(filter.as_deref(), where_clause.as_deref())?;
+                    let hits =
+                        db.search_hybrid(&table, &query_text, &query_vec, k, fusion, &filters)?;
                     println!("{}", serde_json::to_string_pretty(&hits)?);
                 }
+                Commands::PutSparse {
+                    table,
+                    row_id,
+                    sparse,
+                } => {
+                    db.put_sparse_vector(&table, row_id, parse_sparse_vector(&sparse)?)?;
+                    println!("ok");
+                }
+                Commands::SearchSparse {
+                    table,
+                    sparse,
+                    query,
+                    k,
+                    metric,
+                    filter,
+                    where_clause,
+                } => {
+                    let sparse = parse_sparse_vector(&sparse)?;
+                    let filters = resolve_filters(filter.as_deref(), where_clause.as_deref())?;
+                    let hits = match query.as_deref() {
+                        Some(raw) => db.search_hybrid_sparse(
+                            &table,
+                            &parse_vector(raw)?,
+                            &sparse,
+                            k,
+                            metric.map(DistanceMetric::from),
+                            &filters,
+                        )?,
+                        None => db.search_sparse(&table, &sparse, k, &filters)?,
+                    };
+                    println!("{}", serde_json::to_string_pretty(&hits)?);
+                }
+                Commands::Similar {
+                    table,
+                    row_id,
+                    k,
+                    include_self,
+                } => {
+                    let hits = db.search_similar(&table, row_id, k, !include_self)?;
+                    println!("{}", serde_json::to_string_pretty(&hits)?);
+                }
+                Commands::Recommend {
+                    table,
+                    positive,
+                    negative,
+                    k,
+                } => {
+                    let hits = db.recommend(&table, &positive, &negative, k)?;
+                    println!("{}", serde_json::to_string_pretty(&hits)?);
+                }
+                Commands::Count {
+                    table,
+                    filter,
+                    where_clause,
+                } => {
+                    let filters = resolve_filters(filter.as_deref(), where_clause.as_deref())?;
+                    println!("{}", db.count_rows(&table, &filters)?);
+                }
+                Commands::Aggregate {
+                    table,
+                    group_by,
+                    aggs,
+                    filter,
+                    where_clause,
+                } => {
+                    let filters = resolve_filters(filter.as_deref(), where_clause.as_deref())?;
+                    let aggs = aggs
+                        .iter()
+                        .map(|raw| parse_aggregation(raw))
+                        .collect::<Result<Vec<_>>>()?;
+                    let rows = db.aggregate(&table, &filters, &group_by, &aggs)?;
+                    println!("{}", serde_json::to_string_pretty(&rows)?);
+                }
                 Commands::Flush { table } => {
                     db.flush_table(&table)?;
                     println!("ok");
                 }
                 Commands::Compact { table } => {
-                    db.compact_table(&table)?;
-                    println!("ok");
+                    let stats = db.compact_table(&table)?;
+                    println!("{}", serde_json::to_string_pretty(&stats)?);
+                }
+                Commands::Fsck {
+                    table,
+                    repair,
+                    json,
+                } => {
+                    let reports = fsck::run(&db, table, repair)?;
+                    if json {
+                        println!("{}", serde_json::to_string_pretty(&reports)?);
+                    } else {
+                        println!("{}", fsck::render(&reports));
+                    }
+                    fsck::check(&reports)?;
+                }
+                Commands::Snapshot {
+                    command: SnapshotCommand::Restore { .. },
                 }
-                Commands::SnapshotRestore { .. } => unreachable!("handled above"),
+                | Commands::Wal { .. }
+                | Commands::SnapshotExport { .. }
+                | Commands::SnapshotRestore { .. } => unreachable!("handled above"),
             }
         }
     }
@@ -344,6 +1447,30 @@ fn json_to_value(value: &serde_json::Value) -> Result<Value> {
     })
 }
 
+/// A full page may have more hits after it, so print the cursor to continue from.
+fn print_next_search_page(hits: &[embeddb::SearchHit], k: usize) {
+    if let Some(last) = hits.last().filter(|_| k > 0 && hits.len() == k) {
+        eprintln!(
+            "more hits may follow; continue with --after {}",
+            SearchCursor::from(last)
+        );
+    }
+}
+
+fn resolve_filters(
+    filter: Option<&str>,
+    where_clause: Option<&str>,
+) -> Result<Vec<FilterCondition>> {
+    let mut filters = match filter {
+        Some(raw) => parse_filters(raw)?,
+        None => Vec::new(),
+    };
+    if let Some(clause) = where_clause {
+        filters.extend(parse_filter(clause)?);
+    }
+    Ok(filters)
+}
+
 fn parse_filters(input: &str) -> Result<Vec<FilterCondition>> {
     let raw: Vec<FilterConditionJson> = serde_json::from_str(input)
         .map_err(|err| anyhow!("filter must be a JSON array of conditions: {err}"))?;
@@ -359,6 +1486,41 @@ fn parse_filters(input: &str) -> Result<Vec<FilterCondition>> {
     Ok(out)
 }
 
+fn parse_aggregation(input: &str) -> Result<Aggregation> {
+    let (op, column) = match input.split_once(':') {
+        Some((op, column)) => (op, Some(column)),
+        None => (input, None),
+    };
+    let op = match op.to_ascii_lowercase().as_str() {
+        "count" => AggregateFn::Count,
+        "sum" => AggregateFn::Sum,
+        "avg" => AggregateFn::Avg,
+        "min" => AggregateFn::Min,
+        "max" => AggregateFn::Max,
+        other => return Err(anyhow!("unknown aggregation '{other}'")),
+    };
+    Ok(match column {
+        Some(column) => Aggregation::new(op, column),
+        None if op == AggregateFn::Count => Aggregation::count(),
+        None => {
+            return Err(anyhow!(
+                "aggregation '{input}' requires a column (e.g. {input}:score)"
+            ))
+        }
+    })
+}
+
+fn parse_sparse_vector(input: &str) -> Result<SparseVector> {
+    let mut pairs = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (index, value) = part
+            .split_once(':')
+            .ok_or_else(|| anyhow!("sparse entries must be index:value, got '{part}'"))?;
+        pairs.push((index.trim().parse::<u32>()?, value.trim().parse::<f32>()?));
+    }
+    SparseVector::from_pairs(pairs)
+}
+
 fn parse_vector(input: &str) -> Result<Vec<f32>> {
     let value: serde_json::Value = serde_json::from_str(input)?;
     let arr = value
@@ -0,0 +1,126 @@
+//! Parquet export (feature `parquet`). A table is written to `<out>/<table>.parquet` with the
+//! columns of `EmbedDb::export_arrow`: `row_id`, one column per schema column, and an `embedding`
+//! fixed-size list column when the table has embeddings, so the file loads straight into pandas,
+//! polars, or DuckDB.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use embeddb::{EmbedDb, EMBEDDING_COLUMN};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParquetExportReport {
+    pub path: PathBuf,
+    pub rows: usize,
+    pub embeddings: usize,
+}
+
+/// Writes `table` to `<out_dir>/<table>.parquet`, zstd-compressed, in row groups of
+/// `batch_size` rows. Creates `out_dir` if needed; fails if the file already exists.
+pub fn export_parquet(
+    db: &EmbedDb,
+    table: &str,
+    out_dir: &Path,
+    batch_size: usize,
+) -> Result<ParquetExportReport> {
+    if batch_size == 0 {
+        return Err(anyhow!("--batch-size must be at least 1"));
+    }
+    let batches = db.export_arrow(table, batch_size)?;
+    fs::create_dir_all(out_dir)?;
+    let path = out_dir.join(format!("{table}.parquet"));
+    let file = File::create_new(&path)
+        .map_err(|err| anyhow!("cannot create {}: {err}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_max_row_group_row_count(Some(batch_size))
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batches.schema(), Some(props))?;
+
+    let mut report = ParquetExportReport {
+        path,
+        rows: 0,
+        embeddings: 0,
+    };
+    for batch in batches {
+        let batch = batch?;
+        report.rows += batch.num_rows();
+        if let Some(embeddings) = batch.column_by_name(EMBEDDING_COLUMN) {
+            report.embeddings += embeddings.len() - embeddings.null_count();
+        }
+        writer.write(&batch)?;
+    }
+    writer.close()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use embeddb::{Column, Config, DataType, Embedder, EmbeddingSpec, TableSchema, Value};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+
+    struct LengthEmbedder;
+
+    impl Embedder for LengthEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            Ok(vec![input.len() as f32, 1.0])
+        }
+    }
+
+    #[test]
+    fn export_writes_rows_and_embeddings_in_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = EmbedDb::open(Config::new(dir.path().join("data"))).unwrap();
+        let schema = TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("score", DataType::Float, true),
+        ]);
+        db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+            .unwrap();
+        let rows = (0..5)
+            .map(|idx| {
+                let mut fields = BTreeMap::new();
+                fields.insert("title".to_string(), Value::String("x".repeat(idx + 1)));
+                fields
+            })
+            .collect();
+        let ids = db.insert_rows("notes", rows).unwrap();
+        db.process_pending_jobs_with_limit("notes", &LengthEmbedder, 3)
+            .unwrap();
+        db.delete_row("notes", ids[0]).unwrap();
+
+        let out = dir.path().join("export");
+        let report = export_parquet(&db, "notes", &out, 2).unwrap();
+        assert_eq!(report.path, out.join("notes.parquet"));
+        assert_eq!((report.rows, report.embeddings), (4, 2));
+        assert!(export_parquet(&db, "notes", &out, 2).is_err());
+
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(&report.path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        let schema = reader.schema().clone();
+        let names: Vec<&str> = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect();
+        assert_eq!(names, ["row_id", "title", "score", "embedding"]);
+        let batches: Vec<_> = reader.build().unwrap().collect::<Result<_, _>>().unwrap();
+        let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        assert_eq!(rows, 4);
+        let nulls: usize = batches
+            .iter()
+            .map(|batch| batch.column_by_name("embedding").unwrap().null_count())
+            .sum();
+        assert_eq!(nulls, 2);
+    }
+}
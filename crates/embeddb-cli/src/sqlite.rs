@@ -0,0 +1,483 @@
+//! SQLite interop (feature `sqlite`). An export holds one SQLite table per EmbedDB table with a
+//! `row_id INTEGER PRIMARY KEY` column, a companion `<table>_embeddings` table of little-endian
+//! f32 vectors, and an `_embeddb_tables` table recording the schema and embedding spec so an
+//! import can recreate the table exactly.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use embeddb::{Column, DataType, EmbedDb, EmbeddingSpec, TableSchema, Value};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::import::{RejectedRecord, MAX_REPORTED_REJECTIONS};
+
+const METADATA_TABLE: &str = "_embeddb_tables";
+const SCAN_PAGE: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportReport {
+    pub rows: usize,
+    pub embeddings: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SqliteImportReport {
+    /// True when the table did not exist and was created from the export metadata or the
+    /// SQLite column declarations.
+    pub created_table: bool,
+    pub records_read: usize,
+    pub rows_inserted: usize,
+    pub rows_rejected: usize,
+    pub embeddings_restored: usize,
+    pub rejected: Vec<RejectedRecord>,
+}
+
+/// Writes `table` and its ready embeddings into the SQLite database at `out`, in a single
+/// transaction. Fails if `out` already holds a table of the same name.
+pub fn export_sqlite(db: &EmbedDb, table: &str, out: &Path) -> Result<ExportReport> {
+    let descriptor = db.describe_table(table)?;
+    let mut conn = Connection::open(out)?;
+    let tx = conn.transaction()?;
+    let mut report = ExportReport::default();
+
+    let columns = &descriptor.schema.columns;
+    let mut ddl = format!("CREATE TABLE {} (row_id INTEGER PRIMARY KEY", quote(table));
+    for column in columns {
+        ddl.push_str(&format!(
+            ", {} {}{}",
+            quote(&column.name),
+            sqlite_type(&column.data_type),
+            if column.nullable { "" } else { " NOT NULL" }
+        ));
+    }
+    ddl.push(')');
+    tx.execute(&ddl, [])?;
+    tx.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {METADATA_TABLE} \
+             (name TEXT PRIMARY KEY, schema TEXT NOT NULL, embedding_spec TEXT)"
+        ),
+        [],
+    )?;
+    tx.execute(
+        &format!("INSERT INTO {METADATA_TABLE} (name, schema, embedding_spec) VALUES (?1, ?2, ?3)"),
+        params![
+            table,
+            serde_json::to_string(&descriptor.schema)?,
+            descriptor
+                .embedding_spec
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+        ],
+    )?;
+
+    {
+        let placeholders: String = (0..=columns.len())
+            .map(|idx| format!("?{}", idx + 1))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let names: String = std::iter::once("row_id".to_string())
+            .chain(columns.iter().map(|col| quote(&col.name)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} ({names}) VALUES ({placeholders})",
+            quote(table)
+        ))?;
+        let mut cursor = None;
+        loop {
+            let page = db.scan_rows(table, cursor, SCAN_PAGE)?;
+            for row in &page.items {
+                let mut values = vec![rusqlite::types::Value::Integer(row.id as i64)];
+                for column in columns {
+                    values.push(to_sqlite(row.fields.get(&column.name)));
+                }
+                insert.execute(rusqlite::params_from_iter(values))?;
+                report.rows += 1;
+            }
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+    }
+
+    if descriptor.embedding_spec.is_some() {
+        let companion = embeddings_table(table);
+        tx.execute(
+            &format!(
+                "CREATE TABLE {} (row_id INTEGER PRIMARY KEY, vector BLOB NOT NULL)",
+                quote(&companion)
+            ),
+            [],
+        )?;
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} (row_id, vector) VALUES (?1, ?2)",
+            quote(&companion)
+        ))?;
+        let mut cursor = None;
+        loop {
+            let page = db.scroll_embeddings(table, cursor, SCAN_PAGE)?;
+            for record in &page.items {
+                if let Some(vector) = &record.vector {
+                    let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+                    insert.execute(params![record.row_id as i64, bytes])?;
+                    report.embeddings += 1;
+                }
+            }
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Reads `source_table` (default: `table`) from the SQLite database at `path` into `table`,
+/// creating it if needed. Rows get new row ids; exported embeddings are attached to the new ids
+/// instead of being recomputed. Rows that do not fit the schema are rejected and reported.
+pub fn import_sqlite(
+    db: &EmbedDb,
+    table: &str,
+    path: &Path,
+    source_table: Option<&str>,
+    batch_size: usize,
+) -> Result<SqliteImportReport> {
+    if batch_size == 0 {
+        return Err(anyhow!("--batch-size must be at least 1"));
+    }
+    let source = source_table.unwrap_or(table);
+    let conn = Connection::open(path)?;
+    let mut report = SqliteImportReport::default();
+
+    let declared = declared_columns(&conn, source)?;
+    if declared.is_empty() {
+        return Err(anyhow!("SQLite table '{source}' not found"));
+    }
+    let schema = match db.describe_table(table) {
+        Ok(descriptor) => descriptor.schema,
+        Err(_) => {
+            let (schema, spec) = match exported_metadata(&conn, source)? {
+                Some(metadata) => metadata,
+                None => (derive_schema(&declared)?, None),
+            };
+            db.create_table(table, schema.clone(), spec)?;
+            report.created_table = true;
+            schema
+        }
+    };
+
+    let has_row_id = declared.iter().any(|(name, _, _)| name == "row_id");
+    let mut columns = Vec::new();
+    for (name, _, _) in &declared {
+        if name == "row_id" {
+            continue;
+        }
+        let column = schema
+            .columns
+            .iter()
+            .find(|col| &col.name == name)
+            .ok_or_else(|| anyhow!("SQLite column '{name}' is not in table '{table}'"))?;
+        columns.push(column);
+    }
+    let selected: Vec<String> = has_row_id
+        .then(|| "row_id".to_string())
+        .into_iter()
+        .chain(columns.iter().map(|col| quote(&col.name)))
+        .collect();
+    let order = if has_row_id { " ORDER BY row_id" } else { "" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM {}{order}",
+        selected.join(", "),
+        quote(source)
+    ))?;
+    let offset = usize::from(has_row_id);
+
+    let mut id_map = HashMap::new();
+    let mut batch: Vec<(Option<i64>, BTreeMap<String, Value>)> = Vec::with_capacity(batch_size);
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        report.records_read += 1;
+        let old_id: Option<i64> = if has_row_id { row.get(0)? } else { None };
+        let fields = columns.iter().enumerate().try_fold(
+            BTreeMap::new(),
+            |mut fields, (idx, column)| -> Result<_> {
+                // Generated columns are recomputed on insert.
+                if column.generated.is_none() {
+                    let value = from_sqlite(&column.data_type, row.get_ref(idx + offset)?)
+                        .map_err(|err| anyhow!("column '{}': {err}", column.name))?;
+                    if value != Value::Null {
+                        fields.insert(column.name.clone(), value);
+                    }
+                }
+                Ok(fields)
+            },
+        );
+        let checked = fields.and_then(|fields| {
+            let mut generated = fields.clone();
+            schema.apply_defaults(&mut generated, 0);
+            schema.apply_generated(&mut generated)?;
+            schema.validate_row(&generated)?;
+            Ok(fields)
+        });
+        match checked {
+            Ok(fields) => {
+                batch.push((old_id, fields));
+                if batch.len() >= batch_size {
+                    insert_batch(db, table, &mut batch, &mut id_map, &mut report)?;
+                }
+            }
+            Err(err) => {
+                report.rows_rejected += 1;
+                if report.rejected.len() < MAX_REPORTED_REJECTIONS {
+                    report.rejected.push(RejectedRecord {
+                        record: report.records_read,
+                        reason: err.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    insert_batch(db, table, &mut batch, &mut id_map, &mut report)?;
+
+    let companion = embeddings_table(source);
+    if db.describe_table(table)?.embedding_spec.is_some()
+        && !declared_columns(&conn, &companion)?.is_empty()
+    {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT row_id, vector FROM {} ORDER BY row_id",
+            quote(&companion)
+        ))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let old_id: i64 = row.get(0)?;
+            let Some(&new_id) = id_map.get(&old_id) else {
+                continue;
+            };
+            let bytes: Vec<u8> = row.get(1)?;
+            if !bytes.len().is_multiple_of(4) {
+                return Err(anyhow!(
+                    "embedding for row {old_id} is not a whole number of f32s"
+                ));
+            }
+            let vector = bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                .collect();
+            db.put_embedding(table, new_id, vector)?;
+            report.embeddings_restored += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+fn insert_batch(
+    db: &EmbedDb,
+    table: &str,
+    batch: &mut Vec<(Option<i64>, BTreeMap<String, Value>)>,
+    id_map: &mut HashMap<i64, u64>,
+    report: &mut SqliteImportReport,
+) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let (old_ids, rows): (Vec<_>, Vec<_>) = std::mem::take(batch).into_iter().unzip();
+    let new_ids = db.insert_rows(table, rows)?;
+    for (old_id, new_id) in old_ids.into_iter().zip(&new_ids) {
+        if let Some(old_id) = old_id {
+            id_map.insert(old_id, *new_id);
+        }
+    }
+    report.rows_inserted += new_ids.len();
+    Ok(())
+}
+
+/// `(name, declared type, not null)` for each column of `table`; empty if it does not exist.
+fn declared_columns(conn: &Connection, table: &str) -> Result<Vec<(String, String, bool)>> {
+    let mut stmt = conn.prepare("SELECT name, type, \"notnull\" FROM pragma_table_info(?1)")?;
+    let columns = stmt
+        .query_map([table], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(columns)
+}
+
+fn exported_metadata(
+    conn: &Connection,
+    table: &str,
+) -> Result<Option<(TableSchema, Option<EmbeddingSpec>)>> {
+    if declared_columns(conn, METADATA_TABLE)?.is_empty() {
+        return Ok(None);
+    }
+    let row: Option<(String, Option<String>)> = conn
+        .query_row(
+            &format!("SELECT schema, embedding_spec FROM {METADATA_TABLE} WHERE name = ?1"),
+            [table],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((schema, spec)) = row else {
+        return Ok(None);
+    };
+    let spec = spec.map(|spec| serde_json::from_str(&spec)).transpose()?;
+    Ok(Some((serde_json::from_str(&schema)?, spec)))
+}
+
+/// Maps declared SQLite types to columns using SQLite's own affinity rules, with `BOOLEAN` read
+/// as a bool column.
+fn derive_schema(declared: &[(String, String, bool)]) -> Result<TableSchema> {
+    let mut columns = Vec::new();
+    for (name, declared_type, not_null) in declared {
+        if name == "row_id" {
+            continue;
+        }
+        let upper = declared_type.to_ascii_uppercase();
+        let data_type = if upper.contains("BOOL") {
+            DataType::Bool
+        } else if upper.contains("INT") {
+            DataType::Int
+        } else if upper.contains("CHAR") || upper.contains("CLOB") || upper.contains("TEXT") {
+            DataType::String
+        } else if upper.is_empty() || upper.contains("BLOB") {
+            DataType::Bytes
+        } else {
+            DataType::Float
+        };
+        columns.push(Column::new(name.clone(), data_type, !not_null));
+    }
+    Ok(TableSchema::new(columns))
+}
+
+fn sqlite_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Int => "INTEGER",
+        DataType::Float => "REAL",
+        DataType::Bool => "BOOLEAN",
+        DataType::String => "TEXT",
+        DataType::Bytes => "BLOB",
+    }
+}
+
+fn to_sqlite(value: Option<&Value>) -> rusqlite::types::Value {
+    use rusqlite::types::Value as Sql;
+    match value {
+        None | Some(Value::Null) => Sql::Null,
+        Some(Value::Int(v)) => Sql::Integer(*v),
+        Some(Value::Float(v)) => Sql::Real(*v),
+        Some(Value::Bool(v)) => Sql::Integer(i64::from(*v)),
+        Some(Value::String(v)) => Sql::Text(v.clone()),
+        Some(Value::Bytes(v)) => Sql::Blob(v.clone()),
+    }
+}
+
+fn from_sqlite(data_type: &DataType, value: ValueRef<'_>) -> Result<Value> {
+    Ok(match (data_type, value) {
+        (_, ValueRef::Null) => Value::Null,
+        (DataType::Int, ValueRef::Integer(v)) => Value::Int(v),
+        (DataType::Float, ValueRef::Integer(v)) => Value::Float(v as f64),
+        (DataType::Float, ValueRef::Real(v)) => Value::Float(v),
+        (DataType::Bool, ValueRef::Integer(0)) => Value::Bool(false),
+        (DataType::Bool, ValueRef::Integer(1)) => Value::Bool(true),
+        (DataType::String, ValueRef::Text(v)) => Value::String(String::from_utf8(v.to_vec())?),
+        (DataType::Bytes, ValueRef::Blob(v)) => Value::Bytes(v.to_vec()),
+        (data_type, value) => {
+            return Err(anyhow!(
+                "cannot store SQLite {:?} in a {data_type:?} column",
+                value.data_type()
+            ))
+        }
+    })
+}
+
+fn embeddings_table(table: &str) -> String {
+    format!("{table}_embeddings")
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use embeddb::{Config, Embedder};
+
+    use super::*;
+
+    struct LengthEmbedder;
+
+    impl Embedder for LengthEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            Ok(vec![input.len() as f32, 1.0])
+        }
+    }
+
+    #[test]
+    fn export_and_import_round_trip_rows_and_embeddings() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = EmbedDb::open(Config::new(dir.path().join("data"))).unwrap();
+        let schema = TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("score", DataType::Float, true),
+            Column::new("done", DataType::Bool, true),
+            Column::new("blob", DataType::Bytes, true),
+        ]);
+        let spec = EmbeddingSpec::new(vec!["title"]);
+        db.create_table("notes", schema, Some(spec)).unwrap();
+        let mut first = BTreeMap::new();
+        first.insert("title".to_string(), Value::String("hello".into()));
+        first.insert("score".to_string(), Value::Float(0.5));
+        first.insert("done".to_string(), Value::Bool(true));
+        first.insert("blob".to_string(), Value::Bytes(vec![0, 1, 2]));
+        let mut second = BTreeMap::new();
+        second.insert("title".to_string(), Value::String("skipped".into()));
+        let ids = db
+            .insert_rows("notes", vec![first.clone(), second])
+            .unwrap();
+        db.delete_row("notes", ids[1]).unwrap();
+        let mut third = BTreeMap::new();
+        third.insert("title".to_string(), Value::String("later".into()));
+        db.insert_row("notes", third).unwrap();
+        db.process_pending_jobs("notes", &LengthEmbedder).unwrap();
+
+        let out = dir.path().join("notes.sqlite");
+        let report = export_sqlite(&db, "notes", &out).unwrap();
+        assert_eq!((report.rows, report.embeddings), (2, 2));
+
+        let err = import_sqlite(&db, "copy", &out, None, 10).unwrap_err();
+        assert!(err.to_string().contains("'copy' not found"));
+        let report = import_sqlite(&db, "copy", &out, Some("notes"), 1).unwrap();
+        assert!(report.created_table);
+        assert_eq!(report.rows_inserted, 2);
+        assert_eq!(report.embeddings_restored, 2);
+
+        let copy = db.describe_table("copy").unwrap();
+        assert!(copy.embedding_spec.is_some());
+        let rows = db.scan_rows("copy", None, 10).unwrap().items;
+        assert_eq!(rows[0].fields, first);
+        let embeddings = db.scroll_embeddings("copy", None, 10).unwrap().items;
+        assert_eq!(embeddings[0].vector, Some(vec![5.0, 1.0]));
+        assert_eq!(embeddings[1].vector, Some(vec![5.0, 1.0]));
+
+        // Without metadata the schema comes from the declared SQLite types.
+        let conn = Connection::open(&out).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE plain (name VARCHAR(20) NOT NULL, n INT, flag BOOLEAN);
+             INSERT INTO plain VALUES ('a', 1, 0), ('b', 'x', 1);",
+        )
+        .unwrap();
+        let report = import_sqlite(&db, "plain", &out, None, 10).unwrap();
+        assert_eq!((report.rows_inserted, report.rows_rejected), (1, 1));
+        assert!(report.rejected[0].reason.contains("column 'n'"));
+        let columns = db.describe_table("plain").unwrap().schema.columns;
+        assert_eq!(columns[0].data_type, DataType::String);
+        assert!(!columns[0].nullable);
+        assert_eq!(columns[2].data_type, DataType::Bool);
+    }
+}
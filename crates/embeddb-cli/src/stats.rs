@@ -0,0 +1,214 @@
+//! `stats <table>` and `db-stats`: a short summary of `EmbedDb::table_stats` or
+//! `EmbedDb::db_stats` (the full struct with `--json`), optionally redrawn every `--watch`
+//! interval.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use embeddb::{Config, DbStats, EmbedDb, TableStats};
+
+/// What a stats command reports on.
+#[derive(Debug, Clone)]
+pub enum StatsTarget {
+    Db,
+    Table(String),
+}
+
+/// Parses a `--watch` interval: a number followed by `ms`, `s`, or `m` (`s` when omitted).
+pub fn parse_interval(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid interval '{input}' (expected e.g. 500ms, 2s, or 1m)"))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => return Err(anyhow!("unknown interval unit '{unit}' (use ms, s, or m)")),
+    };
+    if seconds < 0.1 {
+        return Err(anyhow!("interval '{input}' is shorter than 100ms"));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// The target's stats as pretty JSON, or as the text summary.
+pub fn render(db: &EmbedDb, target: &StatsTarget, json: bool) -> Result<String> {
+    if json {
+        return Ok(serde_json::to_string_pretty(&stats_json(db, target)?)?);
+    }
+    match target {
+        StatsTarget::Table(table) => Ok(table_summary(&db.table_stats(table)?)),
+        StatsTarget::Db => {
+            let stats = db.db_stats()?;
+            let mut pending = 0;
+            for table in db.list_tables()? {
+                pending += db.table_stats(&table)?.embeddings_pending;
+            }
+            Ok(db_summary(&stats, pending))
+        }
+    }
+}
+
+/// Prints the target's stats every `interval` until interrupted. Each refresh reopens the data
+/// directory read-only, so it follows a directory that a running server holds; counters that
+/// start at open (appends, flushes, cache hits) therefore only cover the refresh itself. Text
+/// output redraws the screen, and JSON output prints one compact object per line.
+pub fn watch(config: &Config, target: &StatsTarget, json: bool, interval: Duration) -> Result<()> {
+    let config = config.clone().with_read_only(true);
+    let mut stdout = std::io::stdout();
+    loop {
+        let db = EmbedDb::open(config.clone())?;
+        if json {
+            writeln!(stdout, "{}", stats_json(&db, target)?)?;
+        } else {
+            let output = render(&db, target, false)?;
+            write!(
+                stdout,
+                "\x1b[2J\x1b[H{output}\n\nevery {interval:?} (Ctrl-C to stop)\n"
+            )?;
+        }
+        stdout.flush()?;
+        drop(db);
+        std::thread::sleep(interval);
+    }
+}
+
+fn stats_json(db: &EmbedDb, target: &StatsTarget) -> Result<serde_json::Value> {
+    Ok(match target {
+        StatsTarget::Table(table) => serde_json::to_value(db.table_stats(table)?)?,
+        StatsTarget::Db => serde_json::to_value(db.db_stats()?)?,
+    })
+}
+
+fn table_summary(stats: &TableStats) -> String {
+    let mut out = String::new();
+    let per_level: Vec<String> = stats
+        .sst_files_per_level
+        .iter()
+        .map(usize::to_string)
+        .collect();
+    let _ = writeln!(out, "table        {}", stats.name);
+    let _ = writeln!(
+        out,
+        "rows         {} total, {} in memtable, {} hidden",
+        stats.total_rows, stats.rows_mem, stats.hidden_rows
+    );
+    let _ = writeln!(
+        out,
+        "tombstones   {} total, {} in memtable",
+        stats.total_tombstones, stats.tombstones_mem
+    );
+    let _ = writeln!(out, "memtable     {}", bytes(stats.memtable_bytes));
+    let _ = writeln!(
+        out,
+        "embeddings   {} pending, {} ready, {} failed",
+        stats.embeddings_pending, stats.embeddings_ready, stats.embeddings_failed
+    );
+    if stats.sst_files == 0 {
+        let _ = writeln!(out, "sst files    0");
+    } else {
+        let _ = writeln!(
+            out,
+            "sst files    {} ({} per level, {})",
+            stats.sst_files,
+            per_level.join("/"),
+            bytes(stats.sst_bytes_per_level.iter().sum())
+        );
+    }
+    let _ = write!(
+        out,
+        "vectors      {} segments, {} resident",
+        stats.vector_segments,
+        bytes(stats.embedding_memory_bytes)
+    );
+    out
+}
+
+fn db_summary(stats: &DbStats, embeddings_pending: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "tables       {}", stats.tables);
+    let _ = writeln!(out, "lsn          {}", stats.lsn);
+    let _ = writeln!(
+        out,
+        "wal          {} ({} sealed segments, {} unsynced records)",
+        bytes(stats.wal_bytes),
+        stats.wal_segments,
+        stats.wal_unsynced_records
+    );
+    let _ = writeln!(
+        out,
+        "checkpoints  {} ({} automatic)",
+        stats.checkpoints, stats.auto_checkpoints
+    );
+    let _ = write!(
+        out,
+        "embeddings   {} pending, {} processed, {} failed",
+        embeddings_pending, stats.embeddings_processed_total, stats.embeddings_failed_total
+    );
+    out
+}
+
+fn bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{n} B");
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use embeddb::{Column, DataType, EmbeddingSpec, TableSchema, Value};
+
+    use super::*;
+
+    #[test]
+    fn intervals_and_summaries() {
+        assert_eq!(parse_interval("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_interval("3").unwrap(), Duration::from_secs(3));
+        assert_eq!(parse_interval("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_interval("1.5m").unwrap(), Duration::from_secs(90));
+        assert!(parse_interval("10ms").is_err());
+        assert!(parse_interval("2h").is_err());
+        assert!(parse_interval("soon").is_err());
+        assert_eq!(bytes(512), "512 B");
+        assert_eq!(bytes(3 * 1024 * 1024 / 2), "1.5 MiB");
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+            .unwrap();
+        for title in ["a", "b"] {
+            let fields = BTreeMap::from([("title".to_string(), Value::String(title.into()))]);
+            db.insert_row("notes", fields).unwrap();
+        }
+
+        let table = StatsTarget::Table("notes".to_string());
+        let text = render(&db, &table, false).unwrap();
+        assert!(text.starts_with("table        notes\n"), "{text}");
+        assert!(text.contains("embeddings   2 pending, 0 ready"), "{text}");
+        let text = render(&db, &StatsTarget::Db, false).unwrap();
+        assert!(text.contains("tables       1\n"), "{text}");
+        assert!(text.contains("embeddings   2 pending"), "{text}");
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&db, &table, true).unwrap()).unwrap();
+        assert_eq!(json["embeddings_pending"], 2);
+        assert!(render(&db, &StatsTarget::Table("missing".into()), false).is_err());
+    }
+}
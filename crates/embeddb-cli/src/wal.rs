@@ -0,0 +1,156 @@
+//! `wal inspect` and `wal verify`: what replay makes of the WAL files, read with
+//! `EmbedDb::inspect_wal` so a directory that fails to open can still be examined.
+
+use std::fmt::Write as _;
+
+use anyhow::{anyhow, Result};
+use embeddb::{WalFrame, WalInspection};
+
+/// Every frame of each file as a table, followed by the file's verdict.
+pub fn render_inspect(inspections: &[WalInspection]) -> String {
+    let mut out = String::new();
+    for inspection in inspections {
+        let version = inspection
+            .format_version
+            .map_or_else(|| "-".to_string(), |version| version.to_string());
+        let _ = writeln!(
+            out,
+            "== {} (format {version}, {} bytes)",
+            inspection.path.display(),
+            inspection.file_bytes
+        );
+        let _ = writeln!(
+            out,
+            "{:>10}  {:>8}  {:<8}  {:<22}  {:<16}  row_id",
+            "offset", "size", "checksum", "record", "table"
+        );
+        for frame in &inspection.frames {
+            let _ = writeln!(out, "{}", frame_line(frame));
+        }
+        let _ = writeln!(out, "{}", verdict(inspection));
+    }
+    out.trim_end().to_string()
+}
+
+/// One verdict line per file.
+pub fn render_verify(inspections: &[WalInspection]) -> String {
+    inspections
+        .iter()
+        .map(|inspection| format!("{}: {}", inspection.path.display(), verdict(inspection)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `wal verify --json`: the inspections without their frames.
+pub fn verify_json(inspections: &[WalInspection]) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(inspections)?;
+    if let Some(files) = value.as_array_mut() {
+        for file in files {
+            if let Some(file) = file.as_object_mut() {
+                file.remove("frames");
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Fails when replay would stop early or drop records in any file.
+pub fn check(inspections: &[WalInspection]) -> Result<()> {
+    let damaged = inspections
+        .iter()
+        .filter(|inspection| !inspection.is_clean())
+        .count();
+    if damaged == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{damaged} of {} WAL files would not replay in full",
+            inspections.len()
+        ))
+    }
+}
+
+fn frame_line(frame: &WalFrame) -> String {
+    let record = match frame.record {
+        Some(record) if frame.compressed => format!("{record} (zstd)"),
+        Some(record) => record.to_string(),
+        None => "?".to_string(),
+    };
+    let mut line = format!(
+        "{:>10}  {:>8}  {:<8}  {:<22}  {:<16}  {}",
+        frame.offset,
+        frame.len,
+        if frame.checksum_ok { "ok" } else { "BAD" },
+        record,
+        frame.table.as_deref().unwrap_or("-"),
+        frame
+            .row_id
+            .map_or_else(|| "-".to_string(), |row_id| row_id.to_string())
+    );
+    if let Some(error) = &frame.error {
+        let _ = write!(line, "  {error}");
+    }
+    line.trim_end().to_string()
+}
+
+fn verdict(inspection: &WalInspection) -> String {
+    let mut out = format!("{} records replayed", inspection.records_replayed);
+    match (inspection.first_corrupt_offset, &inspection.corruption) {
+        (Some(offset), Some(reason)) => {
+            let _ = write!(out, ", corrupt at offset {offset}: {reason}");
+        }
+        (Some(offset), None) => {
+            let _ = write!(out, ", corrupt at offset {offset}");
+        }
+        _ => {}
+    }
+    if inspection.records_dropped > 0 {
+        let _ = write!(out, ", {} dropped", inspection.records_dropped);
+    }
+    if inspection.is_clean() {
+        out.push_str(", ok");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use embeddb::{Column, Config, DataType, EmbedDb, TableSchema, Value};
+
+    use super::*;
+
+    #[test]
+    fn inspect_and_verify_report_a_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, None).unwrap();
+        let fields = BTreeMap::from([("title".to_string(), Value::String("a".into()))]);
+        db.insert_row("notes", fields).unwrap();
+        drop(db);
+
+        let inspections = EmbedDb::inspect_wal(dir.path()).unwrap();
+        assert!(check(&inspections).is_ok());
+        let text = render_inspect(&inspections);
+        assert!(text.contains("PutRow"), "{text}");
+        assert!(text.contains("notes"), "{text}");
+        assert!(render_verify(&inspections).ends_with(", ok"));
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join("wal.log"))
+            .unwrap();
+        file.write_all(&[40, 0, 0, 0, 1]).unwrap();
+        let inspections = EmbedDb::inspect_wal(dir.path()).unwrap();
+        let text = render_verify(&inspections);
+        assert!(text.contains("corrupt at offset"), "{text}");
+        assert!(check(&inspections).is_err());
+        let json = verify_json(&inspections).unwrap();
+        assert!(json[0].get("frames").is_none());
+        assert!(json[0]["first_corrupt_offset"].is_u64());
+    }
+}
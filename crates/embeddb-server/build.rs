@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The proto is compiled in-process by protox, so building the `grpc` feature needs no protoc.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/embeddb.proto");
+        let descriptors = protox::compile(["proto/embeddb.proto"], ["proto"])?;
+        tonic_build::configure().compile_fds(descriptors)?;
+    }
+    Ok(())
+}
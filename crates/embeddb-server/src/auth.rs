@@ -0,0 +1,288 @@
+//! API key authentication. When keys are configured, every request except `GET /health` and the
+//! UI's static files must present one, as `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+//! Requests without a known key get `401`; `read` keys get `403` on anything that writes.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::ApiError;
+
+/// What a key may do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Scope {
+    /// Reads and searches only.
+    Read,
+    #[default]
+    #[serde(alias = "write")]
+    ReadWrite,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiKey {
+    key: String,
+    #[serde(default)]
+    scope: Scope,
+    /// Who holds the key, recorded as the actor of the audit entries for its writes.
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// The server's API keys, read from the JSON file named by `EMBEDDB_API_KEYS_FILE`
+/// (`{"keys": [{"key": "...", "scope": "read", "name": "..."}]}`) plus the comma-separated
+/// `key[:scope]` entries of `EMBEDDB_API_KEYS`.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ApiKeys {
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+// Written by hand so keys never reach logs.
+impl fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scopes: Vec<Scope> = self.keys.iter().map(|key| key.scope).collect();
+        f.debug_struct("ApiKeys").field("scopes", &scopes).finish()
+    }
+}
+
+/// Why a request was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Denied {
+    /// No key, or one the server doesn't know.
+    Unauthenticated,
+    /// A `read` key on a request that writes.
+    ReadOnly,
+}
+
+impl ApiKeys {
+    /// The configured keys, or `None` when there are none and the server runs without auth.
+    pub(crate) fn from_env() -> Result<Option<Arc<Self>>> {
+        let keys = Self::from_lookup(|name| std::env::var(name).ok())?;
+        Ok((!keys.keys.is_empty()).then(|| Arc::new(keys)))
+    }
+
+    pub(crate) fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut keys: Self = match lookup("EMBEDDB_API_KEYS_FILE") {
+            Some(path) => {
+                let data = std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading EMBEDDB_API_KEYS_FILE {path}"))?;
+                serde_json::from_str(&data)
+                    .with_context(|| format!("parsing EMBEDDB_API_KEYS_FILE {path}"))?
+            }
+            None => Self::default(),
+        };
+        if let Some(raw) = lookup("EMBEDDB_API_KEYS") {
+            for entry in raw
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+            {
+                let (key, scope) = match entry.rsplit_once(':') {
+                    Some((key, "read")) => (key, Scope::Read),
+                    Some((key, "read_write" | "write")) => (key, Scope::ReadWrite),
+                    _ => (entry, Scope::ReadWrite),
+                };
+                keys.keys.push(ApiKey {
+                    key: key.to_string(),
+                    scope,
+                    name: None,
+                });
+            }
+        }
+        if keys.keys.iter().any(|key| key.key.is_empty()) {
+            return Err(anyhow!("API keys must not be empty"));
+        }
+        Ok(keys)
+    }
+
+    /// Checks a presented key against the configured ones for a request that reads or writes.
+    pub(crate) fn authorize(&self, presented: Option<&str>, write: bool) -> Result<(), Denied> {
+        let presented = presented.ok_or(Denied::Unauthenticated)?;
+        // Every key is compared in full so timing doesn't reveal which one, or how much, matched.
+        let scope = self.keys.iter().fold(None, |found, key| {
+            match constant_time_eq(key.key.as_bytes(), presented.as_bytes()) {
+                true => Some(key.scope),
+                false => found,
+            }
+        });
+        match scope {
+            None => Err(Denied::Unauthenticated),
+            Some(Scope::Read) if write => Err(Denied::ReadOnly),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Who `presented` belongs to: the key's `name`, or else `key #<n>` for the `n`th configured
+    /// key (file keys first), so the key itself never reaches the audit log.
+    pub(crate) fn actor(&self, presented: &str) -> Option<String> {
+        let (index, key) = self
+            .keys
+            .iter()
+            .enumerate()
+            .find(|(_, key)| constant_time_eq(key.key.as_bytes(), presented.as_bytes()))?;
+        Some(
+            key.name
+                .clone()
+                .unwrap_or_else(|| format!("key #{}", index + 1)),
+        )
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The key a request carries, from `Authorization: Bearer` or else `X-API-Key`. gRPC metadata
+/// uses the same header names.
+pub(crate) fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| {
+            headers
+                .get("x-api-key")
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(str::trim)
+}
+
+fn is_public(method: &Method, route: &str) -> bool {
+    method == Method::GET
+        && matches!(
+            route,
+            "/health" | "/" | "/assets/app.js" | "/assets/styles.css" | "/favicon.svg"
+        )
+}
+
+/// The routes that query a table with a `POST` body: searches, recommendations, duplicate
+/// detection, and aggregates. They read, and they are what `EMBEDDB_MAX_CONCURRENT_SEARCHES`
+/// limits.
+pub(crate) const QUERY_ROUTES: [&str; 9] = [
+    "/tables/:table/search",
+    "/tables/:table/search/explain",
+    "/tables/:table/search-text",
+    "/tables/:table/search-sparse",
+    "/tables/:table/search-hybrid",
+    "/tables/:table/recommend",
+    "/tables/:table/duplicates",
+    "/tables/:table/aggregate",
+    "/tables/:table/rows/:row_id/similar",
+];
+
+/// Whether a request changes state. Everything but `GET` and the `QUERY_ROUTES` `POST`s does.
+fn writes(method: &Method, route: &str) -> bool {
+    match *method {
+        Method::GET | Method::HEAD => false,
+        Method::POST => !QUERY_ROUTES.contains(&route),
+        _ => true,
+    }
+}
+
+pub(crate) async fn require_api_key(
+    State(keys): State<Arc<ApiKeys>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(req.uri().path(), MatchedPath::as_str);
+    if is_public(req.method(), route) {
+        return next.run(req).await;
+    }
+    // Namespaced requests are checked again, route by route, by the namespace's own router; here
+    // they only need a known key.
+    let write = route != "/namespaces/:namespace/*rest" && writes(req.method(), route);
+    match keys.authorize(presented_key(req.headers()), write) {
+        Ok(()) => next.run(req).await,
+        Err(Denied::Unauthenticated) => (
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            ApiError::unauthorized("missing or unknown API key"),
+        )
+            .into_response(),
+        Err(Denied::ReadOnly) => ApiError::forbidden("API key is read-only").into_response(),
+    }
+}
+
+/// Makes the caller the actor of the audit entries for the request's writes: the holder of its
+/// API key, or its peer IP address when the server has no keys.
+pub(crate) async fn audit_actor(
+    State(keys): State<Option<Arc<ApiKeys>>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let actor = match &keys {
+        Some(keys) => presented_key(req.headers()).and_then(|key| keys.actor(key)),
+        None => req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip().to_string()),
+    };
+    match actor {
+        Some(actor) => embeddb::audit_actor_scope(actor, next.run(req)).await,
+        None => next.run(req).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_load_from_file_and_env_with_scopes() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("keys.json");
+        std::fs::write(
+            &path,
+            r#"{"keys": [{"key": "file-key", "scope": "read", "name": "reports"}]}"#,
+        )
+        .expect("write keys");
+        let path = path.to_str().expect("path").to_string();
+        let keys = ApiKeys::from_lookup(|name| match name {
+            "EMBEDDB_API_KEYS_FILE" => Some(path.clone()),
+            "EMBEDDB_API_KEYS" => Some("admin, reader:read ,,writer:write".to_string()),
+            _ => None,
+        })
+        .expect("keys");
+
+        assert_eq!(keys.authorize(Some("file-key"), false), Ok(()));
+        assert_eq!(
+            keys.authorize(Some("file-key"), true),
+            Err(Denied::ReadOnly)
+        );
+        assert_eq!(keys.authorize(Some("reader"), true), Err(Denied::ReadOnly));
+        assert_eq!(keys.authorize(Some("admin"), true), Ok(()));
+        assert_eq!(keys.authorize(Some("writer"), true), Ok(()));
+        assert_eq!(
+            keys.authorize(Some("admin2"), false),
+            Err(Denied::Unauthenticated)
+        );
+        assert_eq!(keys.authorize(None, false), Err(Denied::Unauthenticated));
+        assert!(!format!("{keys:?}").contains("admin"));
+        assert_eq!(keys.actor("file-key").as_deref(), Some("reports"));
+        assert_eq!(keys.actor("reader").as_deref(), Some("key #3"));
+        assert_eq!(keys.actor("admin2"), None);
+
+        assert!(
+            ApiKeys::from_lookup(|name| (name == "EMBEDDB_API_KEYS").then(|| ":read".into()))
+                .is_err()
+        );
+    }
+}
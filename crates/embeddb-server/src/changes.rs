@@ -0,0 +1,113 @@
+//! `GET /tables/:table/changes`: a server-sent event stream of a table's inserts, updates, and
+//! deletes as they are committed, read from a `ChangeSubscription` to the table. The last event
+//! of each write has its feed `seq` as the `id`, so a client that reconnects with
+//! `Last-Event-ID` picks up where it left off.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use embeddb::{ChangeSubscription, ChangesLagged};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{shutdown_signal, ApiError, AppState};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangesQuery {
+    /// Feed position to stream from; without it (or `Last-Event-ID`) only new changes are sent.
+    after: Option<u64>,
+}
+
+pub(crate) async fn stream_changes(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(query): Query<ChangesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    let after = last_event_id
+        .or(query.after)
+        .unwrap_or_else(|| state.db.last_change_seq());
+    // Subscribed up front, so a stale position fails the request instead of ending the stream.
+    let subscription = state
+        .db
+        .subscribe_after(&table, after)
+        .await
+        .map_err(subscribe_error)?;
+
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(follow(subscription, tx));
+    Ok(Sse::new(ReceiverStream::new(rx))
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+fn subscribe_error(err: anyhow::Error) -> ApiError {
+    if let Some(lagged) = err.downcast_ref::<ChangesLagged>() {
+        ApiError::gone(lagged.to_string())
+    } else if err.to_string() == "table not found" {
+        ApiError::not_found("table not found")
+    } else {
+        ApiError::internal(err.to_string())
+    }
+}
+
+/// Sends the table's changes until the client goes away, the database closes, or the server
+/// shuts down. A client that falls more than the feed's capacity behind gets a `lagged` event
+/// carrying the feed's position, and the stream ends.
+async fn follow(mut subscription: ChangeSubscription, tx: mpsc::Sender<Result<Event, Infallible>>) {
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    // A write's changes are published together, so whatever follows a change is already read.
+    let mut next = None;
+    loop {
+        let change = match next.take() {
+            Some(change) => change,
+            None => tokio::select! {
+                change = subscription.recv_async() => change,
+                () = tx.closed() => return,
+                () = &mut shutdown => return,
+            },
+        };
+        let event = match change {
+            Ok(change) => {
+                let event =
+                    Event::default().data(serde_json::to_string(&change).unwrap_or_default());
+                // The changes of one write share its seq, and only the last carries it as the
+                // `id`, so a client that reconnects partway through a write gets all of it again.
+                let following = subscription.try_recv().transpose();
+                let last_of_write = !matches!(&following, Some(Ok(next)) if next.seq == change.seq);
+                next = following;
+                if last_of_write {
+                    event.id(change.seq.to_string())
+                } else {
+                    event
+                }
+            }
+            Err(err) => {
+                if let Some(lagged) = err.downcast_ref::<ChangesLagged>() {
+                    let event = Event::default()
+                        .event("lagged")
+                        .data(serde_json::json!({ "last_seq": lagged.last_seq }).to_string());
+                    let _ = tx.send(Ok(event)).await;
+                }
+                return;
+            }
+        };
+        if tx.send(Ok(event)).await.is_err() {
+            return;
+        }
+    }
+}
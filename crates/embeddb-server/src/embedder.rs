@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use embeddb::Embedder;
+use serde::Deserialize;
+
+const OPENAI_EMBEDDINGS_URL: &str = "https://api.openai.com/v1/embeddings";
+const OPENAI_DEFAULT_MODEL: &str = "text-embedding-3-small";
+const DEFAULT_REMOTE_BATCH_SIZE: usize = 64;
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+/// Embedder settings, read from the JSON file named by `EMBEDDB_EMBEDDER_CONFIG` and overridden
+/// field by field by the `EMBEDDB_EMBEDDER_*` env vars.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct EmbedderConfig {
+    pub(crate) provider: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) api_key: Option<String>,
+    pub(crate) api_key_file: Option<PathBuf>,
+    pub(crate) batch_size: Option<usize>,
+    pub(crate) timeout_ms: Option<u64>,
+    /// Fallback key for the `openai` provider, taken from `OPENAI_API_KEY`.
+    #[serde(skip)]
+    openai_api_key: Option<String>,
+}
+
+// Written by hand so API keys never reach logs.
+impl fmt::Debug for EmbedderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |key: &Option<String>| key.as_ref().map(|_| "<redacted>");
+        f.debug_struct("EmbedderConfig")
+            .field("provider", &self.provider)
+            .field("url", &self.url)
+            .field("model", &self.model)
+            .field("api_key", &redacted(&self.api_key))
+            .field("api_key_file", &self.api_key_file)
+            .field("batch_size", &self.batch_size)
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+impl EmbedderConfig {
+    pub(crate) fn from_env() -> Result<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = match lookup("EMBEDDB_EMBEDDER_CONFIG") {
+            Some(path) => {
+                let data = std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading EMBEDDB_EMBEDDER_CONFIG {path}"))?;
+                serde_json::from_str(&data)
+                    .with_context(|| format!("parsing EMBEDDB_EMBEDDER_CONFIG {path}"))?
+            }
+            None => Self::default(),
+        };
+
+        let parse = |name: &str| -> Result<Option<u64>> {
+            lookup(name)
+                .map(|raw| raw.parse::<u64>().map_err(|_| anyhow!("invalid {name}")))
+                .transpose()
+        };
+        if let Some(provider) = lookup("EMBEDDB_EMBEDDER") {
+            config.provider = Some(provider);
+        }
+        if let Some(url) = lookup("EMBEDDB_EMBEDDER_URL") {
+            config.url = Some(url);
+        }
+        if let Some(model) = lookup("EMBEDDB_EMBEDDER_MODEL") {
+            config.model = Some(model);
+        }
+        if let Some(key) = lookup("EMBEDDB_EMBEDDER_API_KEY") {
+            config.api_key = Some(key);
+        }
+        if let Some(path) = lookup("EMBEDDB_EMBEDDER_API_KEY_FILE") {
+            config.api_key_file = Some(PathBuf::from(path));
+        }
+        if let Some(size) = parse("EMBEDDB_EMBEDDER_BATCH_SIZE")? {
+            config.batch_size = Some(size as usize);
+        }
+        if let Some(timeout) = parse("EMBEDDB_EMBEDDER_TIMEOUT_MS")? {
+            config.timeout_ms = Some(timeout);
+        }
+        config.openai_api_key = lookup("OPENAI_API_KEY");
+        Ok(config)
+    }
+
+    pub(crate) fn provider(&self) -> &str {
+        self.provider.as_deref().unwrap_or("local-hash")
+    }
+
+    /// The configured API key: `api_key`, else the trimmed contents of `api_key_file`.
+    fn api_key(&self) -> Result<Option<String>> {
+        if let Some(key) = &self.api_key {
+            return Ok(Some(key.clone()));
+        }
+        match &self.api_key_file {
+            Some(path) => {
+                let key = std::fs::read_to_string(path)
+                    .with_context(|| format!("reading API key file {}", path.display()))?;
+                Ok(Some(key.trim().to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS))
+    }
+
+    fn batch_size(&self) -> Result<usize> {
+        match self.batch_size {
+            Some(0) => Err(anyhow!("embedder batch_size must be at least 1")),
+            Some(size) => Ok(size),
+            None => Ok(DEFAULT_REMOTE_BATCH_SIZE),
+        }
+    }
+}
+
+pub(crate) type EmbedderFactory = fn(&EmbedderConfig) -> Result<Arc<dyn Embedder>>;
+
+/// Embedder providers selectable by name through `EmbedderConfig::provider`.
+pub(crate) struct EmbedderRegistry {
+    factories: BTreeMap<&'static str, EmbedderFactory>,
+}
+
+impl EmbedderRegistry {
+    /// A registry with the built-in `local-hash`, `openai`, and `http` providers.
+    pub(crate) fn with_builtins() -> Self {
+        let mut registry = Self {
+            factories: BTreeMap::new(),
+        };
+        registry.register("local-hash", |_| Ok(Arc::new(LocalHashEmbedder)));
+        registry.register("openai", |config| {
+            let api_key = config
+                .api_key()?
+                .or_else(|| config.openai_api_key.clone())
+                .ok_or_else(|| {
+                    anyhow!("the openai embedder needs EMBEDDB_EMBEDDER_API_KEY or OPENAI_API_KEY")
+                })?;
+            Ok(Arc::new(HttpEmbedder::new(
+                config,
+                ApiFormat::OpenAi,
+                config.url.as_deref().unwrap_or(OPENAI_EMBEDDINGS_URL),
+                Some(api_key),
+                Some(config.model.as_deref().unwrap_or(OPENAI_DEFAULT_MODEL)),
+            )?))
+        });
+        registry.register("http", |config| {
+            let url = config
+                .url
+                .as_deref()
+                .ok_or_else(|| anyhow!("the http embedder needs EMBEDDB_EMBEDDER_URL"))?;
+            Ok(Arc::new(HttpEmbedder::new(
+                config,
+                ApiFormat::Generic,
+                url,
+                config.api_key()?,
+                config.model.as_deref(),
+            )?))
+        });
+        registry
+    }
+
+    pub(crate) fn register(&mut self, name: &'static str, factory: EmbedderFactory) {
+        self.factories.insert(name, factory);
+    }
+
+    pub(crate) fn build(&self, config: &EmbedderConfig) -> Result<Arc<dyn Embedder>> {
+        let provider = config.provider();
+        let factory = self.factories.get(provider).ok_or_else(|| {
+            let known: Vec<&str> = self.factories.keys().copied().collect();
+            anyhow!(
+                "unknown embedder provider '{provider}' (expected {})",
+                known.join("|")
+            )
+        })?;
+        factory(config)
+    }
+}
+
+/// Deterministic 4-dimensional hash embedding for local testing; not semantically meaningful.
+pub(crate) struct LocalHashEmbedder;
+
+impl Embedder for LocalHashEmbedder {
+    fn embed(&self, input: &str) -> Result<Vec<f32>> {
+        let mut hash = 0u64;
+        for byte in input.as_bytes() {
+            hash = hash.wrapping_mul(31).wrapping_add(*byte as u64);
+        }
+        let a = (hash & 0xFFFF) as f32;
+        let b = ((hash >> 16) & 0xFFFF) as f32;
+        let c = ((hash >> 32) & 0xFFFF) as f32;
+        let d = ((hash >> 48) & 0xFFFF) as f32;
+        Ok(vec![a, b, c, d])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ApiFormat {
+    /// `{"model", "input": [..]}` in, `{"data": [{"index", "embedding"}]}` out.
+    OpenAi,
+    /// `{"inputs": [..], "model"?}` in, `{"embeddings": [[..]]}` out.
+    Generic,
+}
+
+/// Embedder backed by a remote HTTP endpoint, called synchronously with up to `batch_size`
+/// inputs per request.
+struct HttpEmbedder {
+    agent: ureq::Agent,
+    format: ApiFormat,
+    url: String,
+    api_key: Option<String>,
+    model: Option<String>,
+    batch_size: usize,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct GenericResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl HttpEmbedder {
+    fn new(
+        config: &EmbedderConfig,
+        format: ApiFormat,
+        url: &str,
+        api_key: Option<String>,
+        model: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self {
+            agent: ureq::AgentBuilder::new().timeout(config.timeout()).build(),
+            format,
+            url: url.to_string(),
+            api_key,
+            model: model.map(str::to_string),
+            batch_size: config.batch_size()?,
+        })
+    }
+
+    fn post(&self, body: serde_json::Value) -> Result<serde_json::Value> {
+        let mut request = self.agent.post(&self.url);
+        if let Some(key) = &self.api_key {
+            request = request.set("Authorization", &format!("Bearer {key}"));
+        }
+        match request.send_json(body) {
+            Ok(response) => response
+                .into_json()
+                .map_err(|err| anyhow!("invalid embedder response: {err}")),
+            Err(ureq::Error::Status(code, response)) => {
+                let mut detail = response.into_string().unwrap_or_default();
+                detail.truncate(512);
+                Err(anyhow!("embedder returned HTTP {code}: {detail}"))
+            }
+            Err(err) => Err(anyhow!("embedder request failed: {err}")),
+        }
+    }
+}
+
+impl Embedder for HttpEmbedder {
+    fn embed(&self, input: &str) -> Result<Vec<f32>> {
+        self.embed_batch(&[input.to_string()])?
+            .pop()
+            .ok_or_else(|| anyhow!("embedder returned no vectors"))
+    }
+
+    fn embed_batch(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        let vectors = match self.format {
+            ApiFormat::OpenAi => {
+                let body = serde_json::json!({ "model": self.model, "input": inputs });
+                let response: OpenAiResponse = serde_json::from_value(self.post(body)?)
+                    .map_err(|err| anyhow!("invalid embedder response: {err}"))?;
+                let mut data = response.data;
+                data.sort_by_key(|item| item.index);
+                data.into_iter().map(|item| item.embedding).collect()
+            }
+            ApiFormat::Generic => {
+                let mut body = serde_json::json!({ "inputs": inputs });
+                if let Some(model) = &self.model {
+                    body["model"] = serde_json::Value::String(model.clone());
+                }
+                let response: GenericResponse = serde_json::from_value(self.post(body)?)
+                    .map_err(|err| anyhow!("invalid embedder response: {err}"))?;
+                response.embeddings
+            }
+        };
+        Ok(vectors)
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Serves one HTTP request with `body` and returns the raw request it received.
+    fn serve_once(body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!("http://{}/embed", listener.local_addr().expect("addr"));
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("accept");
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).expect("header");
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().expect("length");
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut payload = vec![0; content_length];
+            reader.read_exact(&mut payload).expect("body");
+            request.push_str(&String::from_utf8(payload).expect("utf8"));
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            reader
+                .get_mut()
+                .write_all(response.as_bytes())
+                .expect("write");
+            request
+        });
+        (url, handle)
+    }
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: BTreeMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn config_defaults_to_local_hash_and_rejects_unknown_providers() {
+        let registry = EmbedderRegistry::with_builtins();
+        let config = EmbedderConfig::from_lookup(lookup(&[])).expect("config");
+        assert_eq!(config.provider(), "local-hash");
+        let embedder = registry.build(&config).expect("embedder");
+        assert_eq!(embedder.embed("hi").expect("embed").len(), 4);
+
+        let config =
+            EmbedderConfig::from_lookup(lookup(&[("EMBEDDB_EMBEDDER", "nope")])).expect("config");
+        let err = registry.build(&config).err().expect("unknown provider");
+        assert!(err.to_string().contains("http|local-hash|openai"));
+
+        let config =
+            EmbedderConfig::from_lookup(lookup(&[("EMBEDDB_EMBEDDER", "openai")])).expect("config");
+        assert!(registry.build(&config).is_err());
+    }
+
+    #[test]
+    fn config_file_is_overridden_by_env_and_keys_are_redacted() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("embedder.json");
+        std::fs::write(
+            &path,
+            r#"{"provider": "http", "url": "http://file", "api_key": "sk-secret", "batch_size": 8}"#,
+        )
+        .expect("write");
+        let config = EmbedderConfig::from_lookup(lookup(&[
+            ("EMBEDDB_EMBEDDER_CONFIG", path.to_str().expect("path")),
+            ("EMBEDDB_EMBEDDER_URL", "http://env"),
+        ]))
+        .expect("config");
+        assert_eq!(config.provider(), "http");
+        assert_eq!(config.url.as_deref(), Some("http://env"));
+        assert_eq!(config.batch_size, Some(8));
+        assert!(!format!("{config:?}").contains("sk-secret"));
+    }
+
+    #[test]
+    fn http_provider_batches_inputs_and_sends_the_api_key() {
+        let (url, server) = serve_once(r#"{"embeddings": [[1.0, 2.0], [3.0, 4.0]]}"#);
+        let config = EmbedderConfig::from_lookup(lookup(&[
+            ("EMBEDDB_EMBEDDER", "http"),
+            ("EMBEDDB_EMBEDDER_URL", &url),
+            ("EMBEDDB_EMBEDDER_API_KEY", "token-1"),
+            ("EMBEDDB_EMBEDDER_BATCH_SIZE", "2"),
+        ]))
+        .expect("config");
+        let embedder = EmbedderRegistry::with_builtins()
+            .build(&config)
+            .expect("embedder");
+        assert_eq!(embedder.batch_size(), 2);
+
+        let vectors = embedder
+            .embed_batch(&["a".to_string(), "b".to_string()])
+            .expect("embed");
+        assert_eq!(vectors, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let request = server.join().expect("server");
+        assert!(request.contains("Authorization: Bearer token-1"));
+        assert!(request.contains(r#""inputs":["a","b"]"#));
+    }
+
+    #[test]
+    fn openai_provider_orders_vectors_by_index() {
+        let (url, server) = serve_once(
+            r#"{"data": [{"index": 1, "embedding": [2.0]}, {"index": 0, "embedding": [1.0]}]}"#,
+        );
+        let config = EmbedderConfig::from_lookup(lookup(&[
+            ("EMBEDDB_EMBEDDER", "openai"),
+            ("EMBEDDB_EMBEDDER_URL", &url),
+            ("OPENAI_API_KEY", "sk-test"),
+        ]))
+        .expect("config");
+        let embedder = EmbedderRegistry::with_builtins()
+            .build(&config)
+            .expect("embedder");
+        let vectors = embedder
+            .embed_batch(&["x".to_string(), "y".to_string()])
+            .expect("embed");
+        assert_eq!(vectors, vec![vec![1.0], vec![2.0]]);
+        let request = server.join().expect("server");
+        assert!(request.contains(r#""model":"text-embedding-3-small""#));
+    }
+}
@@ -0,0 +1,103 @@
+//! Bulk table export (feature `arrow`): `GET /tables/:table/export?format=arrow` streams a table as
+//! an Arrow IPC stream, one record batch at a time, so large tables are read without paying for
+//! JSON.
+
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_ipc::writer::StreamWriter;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use embeddb::ArrowBatches;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{ApiError, AppState};
+
+const ARROW_STREAM_CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+const DEFAULT_BATCH_ROWS: usize = 8192;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ExportQuery {
+    format: Option<String>,
+    /// Include the `embedding` column (default true).
+    embeddings: Option<bool>,
+    batch_rows: Option<usize>,
+}
+
+pub(crate) async fn export_table(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    match query.format.as_deref() {
+        None | Some("arrow") => {}
+        Some(other) => {
+            return Err(ApiError::bad_request(format!(
+                "unsupported export format '{other}' (expected 'arrow')"
+            )))
+        }
+    }
+    let batch_rows = query.batch_rows.unwrap_or(DEFAULT_BATCH_ROWS);
+    let mut batches = state
+        .db
+        .export_arrow(&table, batch_rows)
+        .await
+        .map_err(|err| {
+            if err.to_string() == "table not found" {
+                ApiError::not_found("table not found")
+            } else {
+                ApiError::bad_request(err.to_string())
+            }
+        })?;
+    if query.embeddings == Some(false) {
+        batches = batches.without_embeddings();
+    }
+
+    // Batches are encoded on the blocking pool as the client reads them; a client that goes away
+    // closes the channel, which stops the encoder.
+    let (tx, rx) = mpsc::channel(4);
+    let errors = tx.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = write_stream(batches, ChannelWriter(tx)) {
+            tracing::warn!("arrow export of '{table}' failed: {err:#}");
+            let _ = errors.blocking_send(Err(io::Error::other(err.to_string())));
+        }
+    });
+    Ok((
+        [(header::CONTENT_TYPE, ARROW_STREAM_CONTENT_TYPE)],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+fn write_stream(batches: ArrowBatches, out: ChannelWriter) -> anyhow::Result<()> {
+    let mut writer = StreamWriter::try_new_buffered(out, &batches.schema())?;
+    for batch in batches {
+        writer.write(&batch?)?;
+    }
+    writer.finish()?;
+    writer.into_inner()?.flush()?;
+    Ok(())
+}
+
+/// Sends everything written to it down the response body channel.
+struct ChannelWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,603 @@
+// Every tonic handler returns `Result<_, Status>`, so helpers producing a `Status` follow suit.
+#![allow(clippy::result_large_err)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::Result;
+use embeddb::{Column, DataType, DistanceMetric, EmbeddingSpec, RowData, TableSchema, Value};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::auth::{self, Denied};
+use crate::AppState;
+
+#[allow(clippy::enum_variant_names)]
+pub mod proto {
+    tonic::include_proto!("embeddb.v1");
+}
+
+use proto::embed_db_server::{EmbedDb as EmbedDbRpc, EmbedDbServer};
+use proto::value::Kind;
+
+/// Rows fetched per `scan_rows` call while streaming a scan.
+const SCAN_PAGE_ROWS: usize = 256;
+
+/// The gRPC service, backed by the same state as the HTTP handlers.
+pub struct GrpcService {
+    state: Arc<AppState>,
+}
+
+pub fn service(state: Arc<AppState>) -> EmbedDbServer<GrpcService> {
+    EmbedDbServer::new(GrpcService { state })
+}
+
+pub async fn serve(
+    addr: SocketAddr,
+    state: Arc<AppState>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(service(state))
+        .serve_with_shutdown(addr, shutdown)
+        .await?;
+    Ok(())
+}
+
+impl GrpcService {
+    /// Applies the server's API keys to a call, reading the key from the same headers as HTTP,
+    /// and returns the actor of the audit entries for its writes: the holder of its key, or its
+    /// peer IP address when the server has no keys.
+    fn authorize<T>(&self, request: &Request<T>, write: bool) -> Result<Option<String>, Status> {
+        let Some(keys) = &self.state.api_keys else {
+            return Ok(request.remote_addr().map(|addr| addr.ip().to_string()));
+        };
+        let headers = request.metadata().clone().into_headers();
+        let presented = auth::presented_key(&headers);
+        keys.authorize(presented, write)
+            .map_err(|denied| match denied {
+                Denied::Unauthenticated => Status::unauthenticated("missing or unknown API key"),
+                Denied::ReadOnly => Status::permission_denied("API key is read-only"),
+            })?;
+        Ok(presented.and_then(|key| keys.actor(key)))
+    }
+}
+
+/// Runs `future` with `actor` as the actor of its writes' audit entries.
+async fn as_actor<F: Future>(actor: Option<String>, future: F) -> F::Output {
+    match actor {
+        Some(actor) => embeddb::audit_actor_scope(actor, future).await,
+        None => future.await,
+    }
+}
+
+/// `as_actor` for a closure run on the blocking pool, which `audit_actor_scope` doesn't reach.
+fn blocking_as_actor<T>(actor: Option<String>, f: impl FnOnce() -> T) -> T {
+    match actor {
+        Some(actor) => embeddb::with_audit_actor(actor, f),
+        None => f(),
+    }
+}
+
+fn invalid(err: anyhow::Error) -> Status {
+    Status::invalid_argument(err.to_string())
+}
+
+fn internal(err: tokio::task::JoinError) -> Status {
+    Status::internal(err.to_string())
+}
+
+fn value_from_proto(value: proto::Value) -> Value {
+    match value.kind {
+        Some(Kind::IntValue(v)) => Value::Int(v),
+        Some(Kind::FloatValue(v)) => Value::Float(v),
+        Some(Kind::BoolValue(v)) => Value::Bool(v),
+        Some(Kind::StringValue(v)) => Value::String(v),
+        Some(Kind::BytesValue(v)) => Value::Bytes(v),
+        None => Value::Null,
+    }
+}
+
+fn value_to_proto(value: Value) -> proto::Value {
+    let kind = match value {
+        Value::Int(v) => Some(Kind::IntValue(v)),
+        Value::Float(v) => Some(Kind::FloatValue(v)),
+        Value::Bool(v) => Some(Kind::BoolValue(v)),
+        Value::String(v) => Some(Kind::StringValue(v)),
+        Value::Bytes(v) => Some(Kind::BytesValue(v)),
+        Value::Null => None,
+    };
+    proto::Value { kind }
+}
+
+fn fields_from_proto(fields: HashMap<String, proto::Value>) -> BTreeMap<String, Value> {
+    fields
+        .into_iter()
+        .map(|(name, value)| (name, value_from_proto(value)))
+        .collect()
+}
+
+fn row_to_proto(row: RowData) -> proto::Row {
+    proto::Row {
+        id: row.id,
+        version: row.version,
+        created_at_ms: row.created_at_ms,
+        updated_at_ms: row.updated_at_ms,
+        fields: row
+            .fields
+            .into_iter()
+            .map(|(name, value)| (name, value_to_proto(value)))
+            .collect(),
+    }
+}
+
+fn data_type_from_proto(raw: i32) -> Result<DataType, Status> {
+    match proto::DataType::try_from(raw) {
+        Ok(proto::DataType::Int) => Ok(DataType::Int),
+        Ok(proto::DataType::Float) => Ok(DataType::Float),
+        Ok(proto::DataType::Bool) => Ok(DataType::Bool),
+        Ok(proto::DataType::String) => Ok(DataType::String),
+        Ok(proto::DataType::Bytes) => Ok(DataType::Bytes),
+        Ok(proto::DataType::Unspecified) | Err(_) => {
+            Err(Status::invalid_argument("column data_type is required"))
+        }
+    }
+}
+
+fn metric_from_proto(raw: i32) -> Result<Option<DistanceMetric>, Status> {
+    match proto::Metric::try_from(raw) {
+        Ok(proto::Metric::Unspecified) => Ok(None),
+        Ok(proto::Metric::Cosine) => Ok(Some(DistanceMetric::Cosine)),
+        Ok(proto::Metric::L2) => Ok(Some(DistanceMetric::L2)),
+        Err(_) => Err(Status::invalid_argument(format!("unknown metric {raw}"))),
+    }
+}
+
+#[tonic::async_trait]
+impl EmbedDbRpc for GrpcService {
+    async fn create_table(
+        &self,
+        request: Request<proto::CreateTableRequest>,
+    ) -> Result<Response<proto::CreateTableResponse>, Status> {
+        let actor = self.authorize(&request, true)?;
+        let req = request.into_inner();
+        let columns = req
+            .columns
+            .into_iter()
+            .map(|col| {
+                Ok(Column::new(
+                    col.name,
+                    data_type_from_proto(col.data_type)?,
+                    col.nullable,
+                ))
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+        let embed_spec = if req.embedding_fields.is_empty() {
+            None
+        } else {
+            let mut spec = EmbeddingSpec::new(req.embedding_fields);
+            if let Some(metric) = metric_from_proto(req.embedding_metric)? {
+                spec = spec.with_metric(metric);
+            }
+            if let Some(dimensions) = req.embedding_dimensions {
+                spec = spec.with_dimensions(dimensions as usize);
+            }
+            Some(spec)
+        };
+        let create = self
+            .state
+            .db
+            .create_table(req.name, TableSchema::new(columns), embed_spec);
+        as_actor(actor, create).await.map_err(invalid)?;
+        Ok(Response::new(proto::CreateTableResponse {}))
+    }
+
+    async fn insert(
+        &self,
+        request: Request<proto::InsertRequest>,
+    ) -> Result<Response<proto::InsertResponse>, Status> {
+        let actor = self.authorize(&request, true)?;
+        let req = request.into_inner();
+        let db = self.state.db.blocking().clone();
+        // Each row is a durable WAL append, so large batches run off the async workers.
+        let row_ids = tokio::task::spawn_blocking(move || {
+            blocking_as_actor(actor, || {
+                req.rows
+                    .into_iter()
+                    .map(|row| db.insert_row(&req.table, fields_from_proto(row.fields)))
+                    .collect::<Result<Vec<u64>>>()
+            })
+        })
+        .await
+        .map_err(internal)?
+        .map_err(invalid)?;
+        Ok(Response::new(proto::InsertResponse { row_ids }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetRequest>,
+    ) -> Result<Response<proto::Row>, Status> {
+        self.authorize(&request, false)?;
+        let req = request.into_inner();
+        match self
+            .state
+            .db
+            .get_row(&req.table, req.row_id)
+            .await
+            .map_err(invalid)?
+        {
+            Some(row) => Ok(Response::new(row_to_proto(row))),
+            None => Err(Status::not_found("row not found")),
+        }
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteRequest>,
+    ) -> Result<Response<proto::DeleteResponse>, Status> {
+        let actor = self.authorize(&request, true)?;
+        let req = request.into_inner();
+        as_actor(actor, self.state.db.delete_row(&req.table, req.row_id))
+            .await
+            .map_err(invalid)?;
+        Ok(Response::new(proto::DeleteResponse {}))
+    }
+
+    async fn search(
+        &self,
+        request: Request<proto::SearchRequest>,
+    ) -> Result<Response<proto::SearchResponse>, Status> {
+        self.authorize(&request, false)?;
+        let req = request.into_inner();
+        let query = match (req.query.is_empty(), req.query_text.is_empty()) {
+            (false, true) => req.query,
+            (true, false) => {
+                let embedder = self.state.embedder.clone();
+                let text = req.query_text;
+                tokio::task::spawn_blocking(move || embedder.embed(&text))
+                    .await
+                    .map_err(internal)?
+                    .map_err(|err| Status::unavailable(format!("embedding query failed: {err}")))?
+            }
+            _ => {
+                return Err(Status::invalid_argument(
+                    "set exactly one of query and query_text",
+                ))
+            }
+        };
+        let k = if req.k == 0 { 5 } else { req.k as usize };
+        let metric = metric_from_proto(req.metric)?;
+        let hits = self
+            .state
+            .db
+            .search_knn(&req.table, &query, k, metric)
+            .await
+            .map_err(invalid)?;
+        Ok(Response::new(proto::SearchResponse {
+            hits: hits
+                .into_iter()
+                .map(|hit| proto::SearchHit {
+                    row_id: hit.row_id,
+                    distance: hit.distance,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn process_jobs(
+        &self,
+        request: Request<proto::ProcessJobsRequest>,
+    ) -> Result<Response<proto::ProcessJobsResponse>, Status> {
+        let actor = self.authorize(&request, true)?;
+        let req = request.into_inner();
+        let db = self.state.db.blocking().clone();
+        let embedder = self.state.embedder.clone();
+        let processed = tokio::task::spawn_blocking(move || {
+            blocking_as_actor(actor, || match req.limit {
+                Some(limit) => db.process_pending_jobs_with_limit(
+                    &req.table,
+                    embedder.as_ref(),
+                    limit as usize,
+                ),
+                None => db.process_pending_jobs(&req.table, embedder.as_ref()),
+            })
+        })
+        .await
+        .map_err(internal)?
+        .map_err(invalid)?;
+        Ok(Response::new(proto::ProcessJobsResponse {
+            processed: processed as u64,
+        }))
+    }
+
+    type ScanStream = Pin<Box<dyn Stream<Item = Result<proto::Row, Status>> + Send>>;
+
+    async fn scan(
+        &self,
+        request: Request<proto::ScanRequest>,
+    ) -> Result<Response<Self::ScanStream>, Status> {
+        self.authorize(&request, false)?;
+        let req = request.into_inner();
+        // Fail fast on an unknown table instead of in the middle of the stream.
+        self.state
+            .db
+            .scan_rows(&req.table, req.after, 0)
+            .await
+            .map_err(invalid)?;
+
+        let db = self.state.db.blocking().clone();
+        let (tx, rx) = mpsc::channel(SCAN_PAGE_ROWS);
+        tokio::task::spawn_blocking(move || {
+            let mut remaining = req.limit.unwrap_or(u64::MAX);
+            let mut after = req.after;
+            while remaining > 0 {
+                let page_rows = remaining.min(SCAN_PAGE_ROWS as u64) as usize;
+                let page = match db.scan_rows(&req.table, after, page_rows) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        let _ = tx.blocking_send(Err(invalid(err)));
+                        return;
+                    }
+                };
+                for row in page.items {
+                    remaining -= 1;
+                    after = Some(row.id);
+                    if tx.blocking_send(Ok(row_to_proto(row))).is_err() {
+                        // The client went away.
+                        return;
+                    }
+                }
+                if page.next_cursor.is_none() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::embed_db_client::EmbedDbClient;
+    use super::*;
+
+    use tempfile::tempdir;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tokio_stream::StreamExt;
+
+    use crate::http_smoke_tests::test_state;
+
+    fn text(value: &str) -> proto::Value {
+        proto::Value {
+            kind: Some(Kind::StringValue(value.to_string())),
+        }
+    }
+
+    fn create_notes() -> proto::CreateTableRequest {
+        proto::CreateTableRequest {
+            name: "notes".to_string(),
+            columns: vec![proto::Column {
+                name: "title".to_string(),
+                data_type: proto::DataType::String as i32,
+                nullable: false,
+            }],
+            embedding_fields: vec!["title".to_string()],
+            embedding_metric: proto::Metric::Cosine as i32,
+            embedding_dimensions: None,
+        }
+    }
+
+    /// Serves `state` over gRPC on a local port and connects a client to it.
+    async fn connect(state: Arc<AppState>) -> EmbedDbClient<tonic::transport::Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(service(state))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        EmbedDbClient::connect(format!("http://{addr}"))
+            .await
+            .expect("connect")
+    }
+
+    #[tokio::test]
+    async fn grpc_round_trip() {
+        let dir = tempdir().expect("tempdir");
+        let db = embeddb::EmbedDb::open(embeddb::Config::new(dir.path().to_path_buf()))
+            .expect("open db");
+        let mut client = connect(Arc::new(test_state(db))).await;
+
+        client
+            .create_table(create_notes())
+            .await
+            .expect("create table");
+
+        let rows = ["alpha", "beta", "gamma"]
+            .into_iter()
+            .map(|title| proto::Fields {
+                fields: HashMap::from([("title".to_string(), text(title))]),
+            })
+            .collect();
+        let row_ids = client
+            .insert(proto::InsertRequest {
+                table: "notes".to_string(),
+                rows,
+            })
+            .await
+            .expect("insert")
+            .into_inner()
+            .row_ids;
+        assert_eq!(row_ids.len(), 3);
+
+        let row = client
+            .get(proto::GetRequest {
+                table: "notes".to_string(),
+                row_id: row_ids[1],
+            })
+            .await
+            .expect("get")
+            .into_inner();
+        assert_eq!(row.fields["title"], text("beta"));
+
+        let processed = client
+            .process_jobs(proto::ProcessJobsRequest {
+                table: "notes".to_string(),
+                limit: None,
+            })
+            .await
+            .expect("process jobs")
+            .into_inner()
+            .processed;
+        assert_eq!(processed, 3);
+
+        let hits = client
+            .search(proto::SearchRequest {
+                table: "notes".to_string(),
+                query_text: "gamma".to_string(),
+                k: 1,
+                ..Default::default()
+            })
+            .await
+            .expect("search")
+            .into_inner()
+            .hits;
+        assert_eq!(hits[0].row_id, row_ids[2]);
+
+        client
+            .delete(proto::DeleteRequest {
+                table: "notes".to_string(),
+                row_id: row_ids[0],
+            })
+            .await
+            .expect("delete");
+        let status = client
+            .get(proto::GetRequest {
+                table: "notes".to_string(),
+                row_id: row_ids[0],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let mut stream = client
+            .scan(proto::ScanRequest {
+                table: "notes".to_string(),
+                after: None,
+                limit: None,
+            })
+            .await
+            .expect("scan")
+            .into_inner();
+        let mut scanned = Vec::new();
+        while let Some(row) = stream.next().await {
+            scanned.push(row.expect("row").id);
+        }
+        assert_eq!(scanned, row_ids[1..]);
+
+        let status = client
+            .scan(proto::ScanRequest {
+                table: "missing".to_string(),
+                after: None,
+                limit: None,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn grpc_writes_are_audited_as_their_caller() {
+        let dir = tempdir().expect("tempdir");
+        let config = embeddb::Config::new(dir.path().join("keys"))
+            .with_audit_log(embeddb::AuditConfig::default());
+        let db = embeddb::EmbedDb::open(config).expect("open db");
+        let keys = auth::ApiKeys::from_lookup(|name| {
+            (name == "EMBEDDB_API_KEYS").then(|| "admin,reader:read".to_string())
+        })
+        .expect("keys");
+        let state = Arc::new(crate::AppState {
+            api_keys: Some(Arc::new(keys)),
+            ..test_state(db)
+        });
+        let mut client = connect(state.clone()).await;
+        fn as_admin<T>(message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request
+                .metadata_mut()
+                .insert("authorization", "Bearer admin".parse().expect("metadata"));
+            request
+        }
+
+        client
+            .create_table(as_admin(create_notes()))
+            .await
+            .expect("create table");
+        // Inserts and job processing run on the blocking pool.
+        let rows = ["alpha", "beta"]
+            .into_iter()
+            .map(|title| proto::Fields {
+                fields: HashMap::from([("title".to_string(), text(title))]),
+            })
+            .collect();
+        let row_ids = client
+            .insert(as_admin(proto::InsertRequest {
+                table: "notes".to_string(),
+                rows,
+            }))
+            .await
+            .expect("insert")
+            .into_inner()
+            .row_ids;
+        client
+            .process_jobs(as_admin(proto::ProcessJobsRequest {
+                table: "notes".to_string(),
+                limit: None,
+            }))
+            .await
+            .expect("process jobs");
+        client
+            .delete(as_admin(proto::DeleteRequest {
+                table: "notes".to_string(),
+                row_id: row_ids[0],
+            }))
+            .await
+            .expect("delete");
+
+        let entries = state.db.audit_log(0).await.expect("audit log");
+        let seen: Vec<(embeddb::AuditOp, Option<u64>, Option<&str>)> = entries
+            .iter()
+            .map(|entry| (entry.op, entry.row_id, entry.actor.as_deref()))
+            .collect();
+        assert_eq!(
+            seen,
+            vec![
+                (embeddb::AuditOp::CreateTable, None, Some("key #1")),
+                (embeddb::AuditOp::Insert, Some(row_ids[0]), Some("key #1")),
+                (embeddb::AuditOp::Insert, Some(row_ids[1]), Some("key #1")),
+                (embeddb::AuditOp::Delete, Some(row_ids[0]), Some("key #1")),
+            ]
+        );
+
+        // Without keys, the caller is its peer address.
+        let config = embeddb::Config::new(dir.path().join("open"))
+            .with_audit_log(embeddb::AuditConfig::default());
+        let state = Arc::new(test_state(embeddb::EmbedDb::open(config).expect("open db")));
+        let mut client = connect(state.clone()).await;
+        client
+            .create_table(create_notes())
+            .await
+            .expect("create table");
+        let entries = state.db.audit_log(0).await.expect("audit log");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor.as_deref(), Some("127.0.0.1"));
+    }
+}
@@ -2,31 +2,71 @@ use anyhow::Result;
 use tracing_subscriber::EnvFilter;
 
 #[cfg(feature = "http")]
-use std::collections::BTreeMap;
+mod auth;
+#[cfg(feature = "http")]
+mod changes;
+#[cfg(feature = "http")]
+mod embedder;
+#[cfg(feature = "arrow")]
+mod export;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "http")]
+mod maintenance;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "http")]
+mod namespaces;
+#[cfg(feature = "http")]
+mod ratelimit;
+#[cfg(feature = "http")]
+mod snapshots;
+#[cfg(feature = "tls")]
+mod tls;
+
+#[cfg(feature = "http")]
+use std::collections::{BTreeMap, BTreeSet};
 #[cfg(feature = "http")]
 use std::net::SocketAddr;
 #[cfg(feature = "http")]
 use std::path::PathBuf;
 #[cfg(feature = "http")]
 use std::sync::Arc;
+#[cfg(feature = "http")]
+use std::time::Duration;
 
 #[cfg(feature = "http")]
 use anyhow::anyhow;
 #[cfg(feature = "http")]
+use auth::ApiKeys;
+#[cfg(feature = "http")]
 use embeddb::{
-    Config, DistanceMetric, EmbedDb, Embedder, EmbeddingJob, EmbeddingSpec, FilterCondition,
-    FilterOp, TableSchema, Value,
+    parse_filter, Aggregation, AlterTableOp, AsyncEmbedDb, AuditConfig, AuditEntry, Column,
+    CompactionPolicy, Config, DataType, DistanceMetric, Durability, EmbedDb, EmbedDbManager,
+    Embedder, EmbeddingModel, EmbeddingPage, EmbeddingSpec, EmbeddingStatus, FilterCondition,
+    FilterOp, Fusion, IndexSpec, JobListOptions, JobSort, NamedVectorSpec, RetryPolicy,
+    RowCodecKind, RowData, ScoreMode, SearchCursor, SearchOptions, SparseVector, TableSchema,
+    Value, VectorEncoding, VersionConflict, WalEncoding, WriteOp, WriteStall,
 };
 #[cfg(feature = "http")]
-use serde::Deserialize;
+use embedder::{EmbedderConfig, EmbedderRegistry};
+#[cfg(feature = "http")]
+use maintenance::Maintenance;
+#[cfg(feature = "http")]
+use namespaces::Namespaces;
+#[cfg(feature = "http")]
+use ratelimit::RateLimits;
+#[cfg(feature = "http")]
+use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "http")]
 use axum::{
     extract::Query,
     extract::{Path, State},
-    http::{header, StatusCode},
+    http::{header, HeaderValue, StatusCode},
+    middleware,
     response::{Html, IntoResponse, Response},
-    routing::{get, post},
+    routing::{any, get, post, put},
     Json, Router,
 };
 
@@ -120,7 +160,9 @@ mod contract_tests {
                             { "type": "null" }
                         ]
                     }
-                }
+                },
+                "ttl_seconds": { "type": "integer", "minimum": 0 },
+                "embedding": { "type": "array", "minItems": 1, "items": { "type": "number" } }
             }
         });
 
@@ -133,14 +175,26 @@ mod contract_tests {
                 "bytes": [1, 2, 3],
                 "ok": true,
                 "optional": null
-            }
+            },
+            "ttl_seconds": 3600
         });
         assert!(validator.is_valid(&valid));
 
+        let imported = serde_json::json!({
+            "fields": { "title": "Hello" },
+            "embedding": [0.1, 0.2, 0.3]
+        });
+        assert!(validator.is_valid(&imported));
+
         let invalid = serde_json::json!({
             "fields": []
         });
         assert!(!validator.is_valid(&invalid));
+        let invalid = serde_json::json!({
+            "fields": { "title": "Hello" },
+            "embedding": []
+        });
+        assert!(!validator.is_valid(&invalid));
     }
 
     #[test]
@@ -163,7 +217,7 @@ mod contract_tests {
                         "required": ["column", "op", "value"],
                         "properties": {
                             "column": { "type": "string", "minLength": 1 },
-                            "op": { "type": "string", "enum": ["Eq", "Neq", "Lt", "Lte", "Gt", "Gte"] },
+                            "op": { "type": "string", "enum": ["Eq", "Neq", "Ne", "Lt", "Lte", "Gt", "Gte", "Contains"] },
                             "value": {
                                 "anyOf": [
                                     { "type": "integer" },
@@ -220,7 +274,7 @@ mod contract_tests {
                         "required": ["column", "op", "value"],
                         "properties": {
                             "column": { "type": "string", "minLength": 1 },
-                            "op": { "type": "string", "enum": ["Eq", "Neq", "Lt", "Lte", "Gt", "Gte"] },
+                            "op": { "type": "string", "enum": ["Eq", "Neq", "Ne", "Lt", "Lte", "Gt", "Gte", "Contains"] },
                             "value": {
                                 "anyOf": [
                                     { "type": "integer" },
@@ -280,6 +334,7 @@ mod contract_tests {
             "type": "object",
             "required": [
                 "tables",
+                "lsn",
                 "wal_bytes",
                 "wal_durable_appends",
                 "wal_sync_ops",
@@ -292,10 +347,13 @@ mod contract_tests {
                 "compact_total_ms",
                 "embeddings_processed_total",
                 "embeddings_failed_total",
-                "embeddings_retried_total"
+                "embeddings_retried_total",
+                "search_cache_hits",
+                "search_cache_misses"
             ],
             "properties": {
                 "tables": { "type": "integer", "minimum": 0 },
+                "lsn": { "type": "integer", "minimum": 0 },
                 "wal_bytes": { "type": "integer", "minimum": 0 },
                 "wal_durable_appends": { "type": "integer", "minimum": 0 },
                 "wal_sync_ops": { "type": "integer", "minimum": 0 },
@@ -308,12 +366,15 @@ mod contract_tests {
                 "compact_total_ms": { "type": "integer", "minimum": 0 },
                 "embeddings_processed_total": { "type": "integer", "minimum": 0 },
                 "embeddings_failed_total": { "type": "integer", "minimum": 0 },
-                "embeddings_retried_total": { "type": "integer", "minimum": 0 }
+                "embeddings_retried_total": { "type": "integer", "minimum": 0 },
+                "search_cache_hits": { "type": "integer", "minimum": 0 },
+                "search_cache_misses": { "type": "integer", "minimum": 0 }
             }
         });
         let validator = compile_schema(schema);
         let ok = serde_json::json!({
             "tables": 2,
+            "lsn": 40,
             "wal_bytes": 1234,
             "wal_durable_appends": 12,
             "wal_sync_ops": 13,
@@ -326,7 +387,9 @@ mod contract_tests {
             "compact_total_ms": 3,
             "embeddings_processed_total": 5,
             "embeddings_failed_total": 1,
-            "embeddings_retried_total": 1
+            "embeddings_retried_total": 1,
+            "search_cache_hits": 4,
+            "search_cache_misses": 2
         });
         assert!(validator.is_valid(&ok));
     }
@@ -352,12 +415,55 @@ mod contract_tests {
             "type": "object",
             "required": ["ok"],
             "properties": {
-                "ok": { "type": "boolean" }
+                "ok": { "type": "boolean" },
+                "compaction": {
+                    "type": "object",
+                    "required": ["merges", "files_per_level", "elapsed_ms"],
+                    "properties": {
+                        "merges": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": [
+                                    "from_level",
+                                    "to_level",
+                                    "files_in",
+                                    "files_out",
+                                    "bytes_in",
+                                    "bytes_out",
+                                    "tombstones_dropped"
+                                ]
+                            }
+                        },
+                        "files_per_level": {
+                            "type": "array",
+                            "items": { "type": "integer", "minimum": 0 }
+                        },
+                        "elapsed_ms": { "type": "integer", "minimum": 0 }
+                    }
+                }
             }
         });
         let validator = compile_schema(schema);
         let ok = serde_json::json!({ "ok": true });
         assert!(validator.is_valid(&ok));
+        let compacted = serde_json::json!({
+            "ok": true,
+            "compaction": {
+                "merges": [{
+                    "from_level": 0,
+                    "to_level": 1,
+                    "files_in": 2,
+                    "files_out": 1,
+                    "bytes_in": 512,
+                    "bytes_out": 300,
+                    "tombstones_dropped": 1
+                }],
+                "files_per_level": [0, 1],
+                "elapsed_ms": 2
+            }
+        });
+        assert!(validator.is_valid(&compacted));
     }
 
     #[test]
@@ -426,10 +532,16 @@ mod contract_tests {
                                 "source_fields": {
                                     "type": "array",
                                     "items": { "type": "string", "minLength": 1 }
+                                },
+                                "dimensions": {
+                                    "anyOf": [{ "type": "integer", "minimum": 1 }, { "type": "null" }]
                                 }
                             }
                         }
                     ]
+                },
+                "dimension": {
+                    "anyOf": [{ "type": "integer", "minimum": 1 }, { "type": "null" }]
                 }
             }
         });
@@ -442,8 +554,10 @@ mod contract_tests {
                 ]
             },
             "embedding_spec": {
-                "source_fields": ["title"]
-            }
+                "source_fields": ["title"],
+                "dimensions": 384
+            },
+            "dimension": 384
         });
         assert!(validator.is_valid(&ok));
         let invalid = serde_json::json!({
@@ -466,15 +580,19 @@ mod contract_tests {
                 "embeddings_ready",
                 "embeddings_failed",
                 "sst_files",
+                "sst_files_per_level",
+                "vector_segments",
                 "next_row_id",
                 "wal_durable_appends",
                 "embeddings_processed_total",
                 "embeddings_failed_total",
                 "embeddings_retried_total",
+                "skipped_unchanged",
                 "flush_count",
                 "flush_total_ms",
                 "compact_count",
-                "compact_total_ms"
+                "compact_total_ms",
+                "index"
             ],
             "properties": {
                 "name": { "type": "string", "minLength": 1 },
@@ -485,15 +603,32 @@ mod contract_tests {
                 "embeddings_ready": { "type": "integer", "minimum": 0 },
                 "embeddings_failed": { "type": "integer", "minimum": 0 },
                 "sst_files": { "type": "integer", "minimum": 0 },
+                "sst_files_per_level": {
+                    "type": "array",
+                    "items": { "type": "integer", "minimum": 0 }
+                },
+                "vector_segments": { "type": "integer", "minimum": 0 },
                 "next_row_id": { "type": "integer", "minimum": 1 },
                 "wal_durable_appends": { "type": "integer", "minimum": 0 },
                 "embeddings_processed_total": { "type": "integer", "minimum": 0 },
                 "embeddings_failed_total": { "type": "integer", "minimum": 0 },
                 "embeddings_retried_total": { "type": "integer", "minimum": 0 },
+                "skipped_unchanged": { "type": "integer", "minimum": 0 },
                 "flush_count": { "type": "integer", "minimum": 0 },
                 "flush_total_ms": { "type": "integer", "minimum": 0 },
                 "compact_count": { "type": "integer", "minimum": 0 },
-                "compact_total_ms": { "type": "integer", "minimum": 0 }
+                "compact_total_ms": { "type": "integer", "minimum": 0 },
+                "index": {
+                    "type": "object",
+                    "required": ["kind", "state", "indexed_vectors", "total_vectors", "eta_ms"],
+                    "properties": {
+                        "kind": { "type": "string", "enum": ["Flat", "Hnsw"] },
+                        "state": { "type": "string", "enum": ["Ready", "Building"] },
+                        "indexed_vectors": { "type": "integer", "minimum": 0 },
+                        "total_vectors": { "type": "integer", "minimum": 0 },
+                        "eta_ms": { "type": ["integer", "null"], "minimum": 0 }
+                    }
+                }
             }
         });
         let validator = compile_schema(schema);
@@ -506,15 +641,25 @@ mod contract_tests {
             "embeddings_ready": 0,
             "embeddings_failed": 0,
             "sst_files": 0,
+            "sst_files_per_level": [],
+            "vector_segments": 0,
             "next_row_id": 2,
             "wal_durable_appends": 3,
             "embeddings_processed_total": 1,
             "embeddings_failed_total": 0,
             "embeddings_retried_total": 0,
+            "skipped_unchanged": 0,
             "flush_count": 0,
             "flush_total_ms": 0,
             "compact_count": 0,
-            "compact_total_ms": 0
+            "compact_total_ms": 0,
+            "index": {
+                "kind": "Flat",
+                "state": "Ready",
+                "indexed_vectors": 0,
+                "total_vectors": 0,
+                "eta_ms": null
+            }
         });
         assert!(validator.is_valid(&ok));
     }
@@ -633,6 +778,29 @@ mod contract_tests {
         assert!(validator.is_valid(&ok));
     }
 
+    #[test]
+    fn search_with_fields_response_schema() {
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["row_id", "distance", "fields"],
+                "properties": {
+                    "row_id": { "type": "integer", "minimum": 1 },
+                    "distance": { "type": "number" },
+                    "fields": { "type": "object" }
+                }
+            }
+        });
+        let validator = compile_schema(schema);
+        let ok = serde_json::json!([
+            { "row_id": 1, "distance": 0.1, "fields": { "title": "Hello", "body": "World" } }
+        ]);
+        assert!(validator.is_valid(&ok));
+        let missing_fields = serde_json::json!([{ "row_id": 1, "distance": 0.1 }]);
+        assert!(!validator.is_valid(&missing_fields));
+    }
+
     #[test]
     fn process_jobs_response_schema() {
         let schema = serde_json::json!({
@@ -651,14 +819,33 @@ mod contract_tests {
     fn insert_row_response_schema() {
         let schema = serde_json::json!({
             "type": "object",
-            "required": ["row_id"],
+            "required": ["row_id", "version"],
+            "properties": {
+                "row_id": { "type": "integer", "minimum": 1 },
+                "version": { "type": "integer", "minimum": 1 }
+            }
+        });
+        let validator = compile_schema(schema);
+        let ok = serde_json::json!({ "row_id": 1, "version": 1 });
+        assert!(validator.is_valid(&ok));
+    }
+
+    #[test]
+    fn update_row_response_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["row_id", "version"],
             "properties": {
-                "row_id": { "type": "integer", "minimum": 1 }
+                "row_id": { "type": "integer", "minimum": 1 },
+                "version": { "type": "integer", "minimum": 1 }
             }
         });
         let validator = compile_schema(schema);
-        let ok = serde_json::json!({ "row_id": 1 });
+        let ok = serde_json::json!({ "row_id": 1, "version": 2 });
         assert!(validator.is_valid(&ok));
+        let conflict =
+            serde_json::json!({ "error": "row 1 is at version 3, not the expected version 2" });
+        assert!(!validator.is_valid(&conflict));
     }
 
     #[test]
@@ -679,9 +866,10 @@ mod contract_tests {
     fn get_row_response_schema() {
         let schema = serde_json::json!({
             "type": "object",
-            "required": ["id", "fields"],
+            "required": ["id", "version", "fields"],
             "properties": {
                 "id": { "type": "integer", "minimum": 1 },
+                "version": { "type": "integer", "minimum": 0 },
                 "fields": {
                     "type": "object",
                     "additionalProperties": {
@@ -700,6 +888,7 @@ mod contract_tests {
         let validator = compile_schema(schema);
         let ok = serde_json::json!({
             "id": 1,
+            "version": 1,
             "fields": {
                 "title": "Hello",
                 "score": 4.2,
@@ -724,23 +913,104 @@ mod contract_tests {
         let ok = serde_json::json!({ "ok": true });
         assert!(validator.is_valid(&ok));
     }
-}
 
-#[cfg(feature = "http")]
-struct LocalHashEmbedder;
+    #[test]
+    fn scroll_embeddings_response_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["items", "next_cursor"],
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["row_id", "vector", "status"],
+                        "properties": {
+                            "row_id": { "type": "integer", "minimum": 1 },
+                            "vector": {
+                                "anyOf": [
+                                    { "type": "array", "items": { "type": "number" } },
+                                    { "type": "null" }
+                                ]
+                            },
+                            "status": { "type": "string", "enum": ["Pending", "Ready", "Failed"] }
+                        }
+                    }
+                },
+                "next_cursor": { "anyOf": [{ "type": "integer" }, { "type": "null" }] }
+            }
+        });
+        let validator = compile_schema(schema);
+        let ok = serde_json::json!({
+            "items": [
+                { "row_id": 1, "vector": [0.5, 0.25], "status": "Ready" },
+                { "row_id": 2, "vector": null, "status": "Pending" }
+            ],
+            "next_cursor": 2
+        });
+        assert!(validator.is_valid(&ok));
+    }
 
-#[cfg(feature = "http")]
-impl Embedder for LocalHashEmbedder {
-    fn embed(&self, input: &str) -> Result<Vec<f32>> {
-        let mut hash = 0u64;
-        for byte in input.as_bytes() {
-            hash = hash.wrapping_mul(31).wrapping_add(*byte as u64);
-        }
-        let a = (hash & 0xFFFF) as f32;
-        let b = ((hash >> 16) & 0xFFFF) as f32;
-        let c = ((hash >> 32) & 0xFFFF) as f32;
-        let d = ((hash >> 48) & 0xFFFF) as f32;
-        Ok(vec![a, b, c, d])
+    #[test]
+    fn scan_rows_response_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["items", "next_cursor"],
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "fields"],
+                        "properties": {
+                            "id": { "type": "integer", "minimum": 1 },
+                            "fields": { "type": "object" }
+                        }
+                    }
+                },
+                "next_cursor": { "anyOf": [{ "type": "integer" }, { "type": "null" }] }
+            }
+        });
+        let validator = compile_schema(schema);
+        let ok = serde_json::json!({
+            "items": [
+                { "id": 1, "fields": { "title": "Hello" } },
+                { "id": 3, "fields": { "title": "World" } }
+            ],
+            "next_cursor": 3
+        });
+        assert!(validator.is_valid(&ok));
+    }
+
+    #[test]
+    fn aggregate_response_schema() {
+        let scalar = serde_json::json!({
+            "anyOf": [
+                { "type": "integer" },
+                { "type": "number" },
+                { "type": "boolean" },
+                { "type": "string" },
+                { "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } },
+                { "type": "null" }
+            ]
+        });
+        let schema = serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "required": ["group", "values"],
+                "properties": {
+                    "group": { "type": "array", "items": scalar },
+                    "values": { "type": "array", "items": scalar }
+                }
+            }
+        });
+        let validator = compile_schema(schema);
+        let ok = serde_json::json!([
+            { "group": ["web"], "values": [2, 1.5, null] },
+            { "group": ["pdf"], "values": [1, 3.0, 3.0] }
+        ]);
+        assert!(validator.is_valid(&ok));
     }
 }
 
@@ -768,6 +1038,23 @@ fn main() -> Result<()> {
     }
 }
 
+/// `always`, `checkpoint`, or a sync interval in milliseconds such as `100ms`.
+#[cfg(feature = "http")]
+fn parse_durability(raw: &str) -> Result<Durability> {
+    match raw.trim() {
+        "always" => Ok(Durability::Always),
+        "checkpoint" => Ok(Durability::OnCheckpointOnly),
+        other => other
+            .strip_suffix("ms")
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .filter(|ms| *ms > 0)
+            .map(|ms| Durability::Interval(Duration::from_millis(ms)))
+            .ok_or_else(|| {
+                anyhow!("invalid EMBEDDB_DURABILITY (use always, checkpoint, or e.g. 100ms)")
+            }),
+    }
+}
+
 #[cfg(feature = "http")]
 fn run_http() -> Result<()> {
     let addr: SocketAddr = std::env::var("EMBEDDB_ADDR")
@@ -785,62 +1072,511 @@ fn run_http() -> Result<()> {
         })
         .transpose()?;
 
+    let wal_segment_bytes = std::env::var("EMBEDDB_WAL_SEGMENT_BYTES")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_WAL_SEGMENT_BYTES"))
+        })
+        .transpose()?;
+
+    let wal_group_commit_us = std::env::var("EMBEDDB_WAL_GROUP_COMMIT_US")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_WAL_GROUP_COMMIT_US"))
+        })
+        .transpose()?;
+
+    let durability = std::env::var("EMBEDDB_DURABILITY")
+        .ok()
+        .map(|raw| parse_durability(&raw))
+        .transpose()?;
+
+    let wal_compression = std::env::var("EMBEDDB_WAL_COMPRESSION")
+        .ok()
+        .map(|raw| {
+            raw.parse::<i32>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_WAL_COMPRESSION"))
+        })
+        .transpose()?;
+
+    let row_codec = match std::env::var("EMBEDDB_ROW_CODEC").ok().as_deref() {
+        None | Some("json") => RowCodecKind::Json,
+        Some("bincode") => RowCodecKind::Bincode,
+        Some("msgpack") => RowCodecKind::MessagePack,
+        Some(_) => {
+            return Err(anyhow!(
+                "invalid EMBEDDB_ROW_CODEC (expected json|bincode|msgpack)"
+            ))
+        }
+    };
+
+    let wal_encoding = match std::env::var("EMBEDDB_WAL_ENCODING").ok().as_deref() {
+        None | Some("json") => WalEncoding::Json,
+        Some("bincode") => WalEncoding::Bincode,
+        Some(_) => {
+            return Err(anyhow!(
+                "invalid EMBEDDB_WAL_ENCODING (expected json|bincode)"
+            ))
+        }
+    };
+
+    let search_cache_capacity = std::env::var("EMBEDDB_SEARCH_CACHE_CAPACITY")
+        .ok()
+        .map(|raw| {
+            raw.parse::<usize>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_SEARCH_CACHE_CAPACITY"))
+        })
+        .transpose()?
+        .unwrap_or(0);
+
+    let change_feed_capacity = std::env::var("EMBEDDB_CHANGE_FEED_CAPACITY")
+        .ok()
+        .map(|raw| {
+            raw.parse::<usize>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_CHANGE_FEED_CAPACITY"))
+        })
+        .transpose()?;
+
+    let rescore_oversample = std::env::var("EMBEDDB_RESCORE_OVERSAMPLE")
+        .ok()
+        .map(|raw| {
+            raw.parse::<usize>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_RESCORE_OVERSAMPLE"))
+        })
+        .transpose()?;
+
+    let memtable_max_bytes = std::env::var("EMBEDDB_MEMTABLE_MAX_BYTES")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_MEMTABLE_MAX_BYTES"))
+        })
+        .transpose()?;
+
+    let compaction_l0_trigger = std::env::var("EMBEDDB_COMPACTION_L0_TRIGGER")
+        .ok()
+        .map(|raw| {
+            raw.parse::<usize>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_COMPACTION_L0_TRIGGER"))
+        })
+        .transpose()?
+        .unwrap_or(0);
+
+    let compaction_l0_trigger_bytes = std::env::var("EMBEDDB_COMPACTION_L0_TRIGGER_BYTES")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .map_err(|_| anyhow!("invalid EMBEDDB_COMPACTION_L0_TRIGGER_BYTES"))
+        })
+        .transpose()?
+        .unwrap_or(0);
+
+    let compaction_interval_ms = std::env::var("EMBEDDB_COMPACTION_INTERVAL_MS")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .ok_or_else(|| anyhow!("invalid EMBEDDB_COMPACTION_INTERVAL_MS"))
+        })
+        .transpose()?;
+
     let config = match wal_autocheckpoint_bytes {
         Some(bytes) => Config::new(data_dir).with_wal_autocheckpoint_bytes(bytes),
         None => Config::new(data_dir),
+    }
+    .with_row_codec(row_codec)
+    .with_wal_encoding(wal_encoding)
+    .with_search_cache_capacity(search_cache_capacity)
+    .with_compaction_policy(CompactionPolicy {
+        l0_trigger_files: compaction_l0_trigger,
+        l0_trigger_bytes: compaction_l0_trigger_bytes,
+        ..CompactionPolicy::default()
+    })
+    .with_wal_archive(matches!(
+        std::env::var("EMBEDDB_WAL_ARCHIVE").ok().as_deref(),
+        Some("1" | "true")
+    ))
+    .with_read_only(matches!(
+        std::env::var("EMBEDDB_READ_ONLY").ok().as_deref(),
+        Some("1" | "true")
+    ))
+    .with_warm_on_open(matches!(
+        std::env::var("EMBEDDB_WARM_ON_OPEN").ok().as_deref(),
+        Some("1" | "true")
+    ));
+    let config = match change_feed_capacity {
+        Some(capacity) => config.with_change_feed_capacity(capacity),
+        None => config,
+    };
+    let config = match rescore_oversample {
+        Some(oversample) => config.with_rescore_oversample(oversample),
+        None => config,
+    };
+    let config = match wal_segment_bytes {
+        Some(bytes) => config.with_wal_segment_bytes(bytes),
+        None => config,
+    };
+    let config = match memtable_max_bytes {
+        Some(bytes) => config.with_memtable_max_bytes(bytes),
+        None => config,
+    };
+    let config = match wal_group_commit_us {
+        Some(us) => config.with_wal_group_commit(Duration::from_micros(us)),
+        None => config,
+    };
+    let config = match wal_compression {
+        Some(level) => config.with_wal_compression(level),
+        None => config,
+    };
+    let config = match durability {
+        Some(durability) => config.with_durability(durability),
+        None => config,
+    };
+    let config = match compaction_interval_ms {
+        Some(ms) => config.with_background_compaction(Duration::from_millis(ms)),
+        None => config,
+    };
+    let config = match audit_config()? {
+        Some(audit) => config.with_audit_log(audit),
+        None => config,
+    };
+    let maintenance = match std::env::var("EMBEDDB_MAINTENANCE_SCHEDULE").ok() {
+        Some(spec) => {
+            let tasks = maintenance::parse_tasks(
+                &std::env::var("EMBEDDB_MAINTENANCE_TASKS")
+                    .unwrap_or_else(|_| "flush,compact,checkpoint".to_string()),
+            )?;
+            Some(Arc::new(Maintenance::new(&spec, tasks)?))
+        }
+        None => None,
+    };
+
+    let embedder_config = EmbedderConfig::from_env()?;
+    let embedder = EmbedderRegistry::with_builtins().build(&embedder_config)?;
+    tracing::info!(provider = embedder_config.provider(), "embedder configured");
+
+    let background_interval_ms = std::env::var("EMBEDDB_BACKGROUND_EMBEDDING_MS")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .ok_or_else(|| anyhow!("invalid EMBEDDB_BACKGROUND_EMBEDDING_MS"))
+        })
+        .transpose()?;
+    let config = match background_interval_ms {
+        Some(ms) => config.with_background_embedding(embedder.clone(), Duration::from_millis(ms)),
+        None => config,
     };
-    let db = EmbedDb::open(config)?;
-    let state = Arc::new(AppState { db });
+
+    #[cfg(feature = "grpc")]
+    let grpc_addr: Option<SocketAddr> = std::env::var("EMBEDDB_GRPC_ADDR")
+        .ok()
+        .map(|raw| {
+            raw.parse()
+                .map_err(|_| anyhow!("invalid EMBEDDB_GRPC_ADDR"))
+        })
+        .transpose()?;
+
+    #[cfg(feature = "tls")]
+    let tls = match tls::TlsConfig::from_env()? {
+        Some(config) => Some((config.server_config()?, config.redirect_addr)),
+        None => None,
+    };
+    #[cfg(not(feature = "tls"))]
+    if std::env::var_os("EMBEDDB_TLS_CERT").is_some()
+        || std::env::var_os("EMBEDDB_TLS_CONFIG").is_some()
+    {
+        return Err(anyhow!(
+            "TLS settings need embeddb-server built with the tls feature"
+        ));
+    }
+
+    let api_keys = ApiKeys::from_env()?;
+    if api_keys.is_none() && !addr.ip().is_loopback() {
+        tracing::warn!(%addr, "no API keys configured; every request is allowed");
+    }
+    let rate_limits = RateLimits::from_env(api_keys.is_some())?;
+
+    let namespaces = std::env::var("EMBEDDB_NAMESPACES_DIR").ok().map(|root| {
+        Arc::new(Namespaces::new(EmbedDbManager::new(
+            root.into(),
+            config.clone(),
+        )))
+    });
+
+    let db = Arc::new(EmbedDb::open(config)?);
+    let state = Arc::new(AppState {
+        db: db.clone().into(),
+        embedder,
+        maintenance: maintenance.clone(),
+        api_keys,
+        namespaces: namespaces.clone(),
+        snapshots_dir: std::env::var_os("EMBEDDB_SNAPSHOTS_DIR").map(PathBuf::from),
+        rate_limits,
+    });
+    #[cfg(feature = "grpc")]
+    let grpc_state = state.clone();
     let app = build_router(state);
 
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
 
+    let maintenance_db = db.clone();
     runtime.block_on(async move {
+        if let Some(maintenance) = maintenance {
+            tokio::spawn(maintenance::run_forever(maintenance, maintenance_db));
+        }
         tracing::info!(%addr, "embeddb-server listening");
         let listener = tokio::net::TcpListener::bind(addr).await?;
-        axum::serve(listener, app).await?;
-        Ok::<(), anyhow::Error>(())
+        let http = async {
+            #[cfg(feature = "tls")]
+            if let Some((server_config, redirect_addr)) = tls {
+                let https = tls::serve(listener, app, server_config, shutdown_signal());
+                match redirect_addr {
+                    Some(redirect_addr) => {
+                        tracing::info!(%redirect_addr, "embeddb-server redirecting HTTP to HTTPS");
+                        let redirect = tokio::net::TcpListener::bind(redirect_addr).await?;
+                        let redirect =
+                            tls::serve_redirect(redirect, addr.port(), shutdown_signal());
+                        tokio::try_join!(https, redirect)?;
+                    }
+                    None => https.await?,
+                }
+                return Ok(());
+            }
+            // Peer addresses identify clients for rate limits when no API keys are configured.
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+            Ok::<(), anyhow::Error>(())
+        };
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_addr) = grpc_addr {
+            tracing::info!(%grpc_addr, "embeddb-server gRPC listening");
+            tokio::try_join!(http, grpc::serve(grpc_addr, grpc_state, shutdown_signal()))?;
+            return Ok(());
+        }
+        http.await
     })?;
 
+    // Requests have drained; checkpoint so the next start has little WAL to replay.
+    tracing::info!("embeddb-server shutting down");
+    if let Some(namespaces) = namespaces {
+        namespaces.close_all()?;
+    }
+    db.close()?;
     Ok(())
 }
 
 #[cfg(feature = "http")]
-struct AppState {
-    db: EmbedDb,
-}
-
-#[cfg(feature = "http")]
-fn build_router(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/", get(ui_index))
-        .route("/assets/app.js", get(ui_app_js))
-        .route("/assets/styles.css", get(ui_styles))
-        .route("/favicon.svg", get(ui_favicon))
-        .route("/health", get(health))
-        .route("/stats", get(db_stats))
-        .route("/checkpoint", post(checkpoint))
+/// The audit log settings: `EMBEDDB_AUDIT_LOG=1` turns it on, and
+/// `EMBEDDB_AUDIT_MAX_FILE_BYTES`/`EMBEDDB_AUDIT_MAX_FILES` tune its rotation.
+fn audit_config() -> Result<Option<AuditConfig>> {
+    let enabled = matches!(
+        std::env::var("EMBEDDB_AUDIT_LOG").ok().as_deref(),
+        Some("1" | "true")
+    );
+    let max_file_bytes = std::env::var("EMBEDDB_AUDIT_MAX_FILE_BYTES")
+        .ok()
+        .map(|raw| {
+            raw.parse::<u64>()
+                .ok()
+                .filter(|bytes| *bytes > 0)
+                .ok_or_else(|| anyhow!("invalid EMBEDDB_AUDIT_MAX_FILE_BYTES"))
+        })
+        .transpose()?;
+    let max_files = std::env::var("EMBEDDB_AUDIT_MAX_FILES")
+        .ok()
+        .map(|raw| {
+            raw.parse::<usize>()
+                .ok()
+                .filter(|files| *files > 0)
+                .ok_or_else(|| anyhow!("invalid EMBEDDB_AUDIT_MAX_FILES"))
+        })
+        .transpose()?;
+    if !enabled {
+        if max_file_bytes.is_some() || max_files.is_some() {
+            return Err(anyhow!(
+                "EMBEDDB_AUDIT_MAX_FILE_BYTES and EMBEDDB_AUDIT_MAX_FILES require EMBEDDB_AUDIT_LOG=1"
+            ));
+        }
+        return Ok(None);
+    }
+    let mut audit = AuditConfig::default();
+    if let Some(bytes) = max_file_bytes {
+        audit = audit.with_max_file_bytes(bytes);
+    }
+    if let Some(files) = max_files {
+        audit = audit.with_max_files(files);
+    }
+    Ok(Some(audit))
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+#[cfg(feature = "http")]
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("listening for Ctrl-C failed: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("listening for SIGTERM failed: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
+
+#[cfg(feature = "http")]
+struct AppState {
+    db: AsyncEmbedDb,
+    /// Used by job processing and text search; remote providers block, so call it off the
+    /// async workers.
+    embedder: Arc<dyn Embedder>,
+    maintenance: Option<Arc<Maintenance>>,
+    /// Keys requests must present; `None` serves every request.
+    api_keys: Option<Arc<ApiKeys>>,
+    /// Databases served under `/namespaces/:namespace`; `None` when `EMBEDDB_NAMESPACES_DIR` is
+    /// unset.
+    namespaces: Option<Arc<Namespaces>>,
+    /// Where `POST /snapshots` writes; `None` when `EMBEDDB_SNAPSHOTS_DIR` is unset.
+    snapshots_dir: Option<PathBuf>,
+    /// Per-client request limits, enforced at the top level only so namespaced requests are
+    /// counted once; `None` when none are configured.
+    rate_limits: Option<Arc<RateLimits>>,
+}
+
+#[cfg(feature = "http")]
+fn build_router(state: Arc<AppState>) -> Router {
+    let router = data_routes()
+        .route("/", get(ui_index))
+        .route("/assets/app.js", get(ui_app_js))
+        .route("/assets/styles.css", get(ui_styles))
+        .route("/favicon.svg", get(ui_favicon))
+        .route("/health", get(health))
         .route("/snapshot/export", post(snapshot_export))
         .route("/snapshot/restore", post(snapshot_restore))
+        .route(
+            "/snapshots",
+            get(snapshots::list_snapshots).post(snapshots::create_snapshot),
+        )
+        .route(
+            "/namespaces",
+            get(namespaces::list_namespaces).post(namespaces::create_namespace),
+        )
+        .route("/namespaces/:namespace/*rest", any(namespaces::dispatch));
+    #[cfg(feature = "metrics")]
+    let router = metrics::instrument(router);
+    let router = router.layer(middleware::from_fn_with_state(
+        state.api_keys.clone(),
+        auth::audit_actor,
+    ));
+    // Auth wraps the limits, so requests with unknown keys never get a bucket.
+    let router = match state.rate_limits.clone() {
+        Some(limits) => router.layer(middleware::from_fn_with_state(
+            limits,
+            ratelimit::enforce_limits,
+        )),
+        None => router,
+    };
+    with_auth(router, &state)
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+/// A namespace's routes: everything at the top level except the UI, health, snapshots, and
+/// namespaces themselves.
+#[cfg(feature = "http")]
+fn data_router(state: Arc<AppState>) -> Router {
+    with_auth(data_routes(), &state).with_state(state)
+}
+
+#[cfg(feature = "http")]
+fn with_auth(router: Router<Arc<AppState>>, state: &AppState) -> Router<Arc<AppState>> {
+    match state.api_keys.clone() {
+        Some(keys) => router.layer(middleware::from_fn_with_state(keys, auth::require_api_key)),
+        None => router,
+    }
+}
+
+#[cfg(feature = "http")]
+fn data_routes() -> Router<Arc<AppState>> {
+    let router = Router::new()
+        .route("/stats", get(db_stats))
+        .route("/audit", get(audit_log))
+        .route("/checkpoint", post(checkpoint))
+        .route("/jobs", get(list_all_jobs))
+        .route("/jobs/process", post(process_all_jobs))
+        .route("/batch", post(write_batch))
         .route("/tables", get(list_tables).post(create_table))
         .route("/tables/:table", get(describe_table))
         .route("/tables/:table/stats", get(table_stats))
-        .route("/tables/:table/rows", post(insert_row))
+        .route("/tables/:table/embedding-spec", post(set_embedding_spec))
+        .route("/tables/:table/reembed", post(reembed_table))
+        .route("/tables/:table/alter", post(alter_table))
+        .route("/tables/:table/rename", post(rename_table))
+        .route("/tables/:table/rows", get(scan_rows).post(insert_row))
+        // `rows:delete`: the router takes everything after `rows` in the segment as a parameter.
+        .route("/tables/:table/rows:action", post(delete_rows))
         .route(
             "/tables/:table/rows/:row_id",
-            get(get_row).delete(delete_row),
+            get(get_row)
+                .put(update_row)
+                .patch(patch_row)
+                .delete(delete_row),
+        )
+        .route("/tables/:table/rows/:row_id/restore", post(restore_row))
+        .route("/tables/:table/rows/:row_id/similar", post(search_similar))
+        .route(
+            "/tables/:table/rows/:row_id/embedding",
+            put(put_embedding).get(get_embedding),
+        )
+        .route(
+            "/tables/:table/rows/:row_id/sparse",
+            put(put_sparse_vector).get(get_sparse_vector),
         )
         .route("/tables/:table/jobs", get(list_jobs))
+        .route("/tables/:table/embeddings", get(scroll_embeddings))
         .route("/tables/:table/search", post(search))
+        .route("/tables/:table/search/explain", post(explain_search))
         .route("/tables/:table/search-text", post(search_text))
+        .route("/tables/:table/search-sparse", post(search_sparse))
+        .route("/tables/:table/search-hybrid", post(search_hybrid))
+        .route("/tables/:table/recommend", post(recommend))
+        .route("/tables/:table/duplicates", post(find_duplicates))
+        .route("/tables/:table/count", get(count_rows))
+        .route("/tables/:table/changes", get(changes::stream_changes))
+        .route("/tables/:table/aggregate", post(aggregate))
         .route("/tables/:table/jobs/process", post(process_jobs))
         .route("/tables/:table/jobs/retry-failed", post(retry_failed_jobs))
         .route("/tables/:table/flush", post(flush_table))
         .route("/tables/:table/compact", post(compact_table))
-        .layer(TraceLayer::new_for_http())
-        .with_state(state)
+        .route("/tables/:table/purge-hidden", post(purge_hidden_rows));
+    #[cfg(feature = "arrow")]
+    let router = router.route("/tables/:table/export", get(export::export_table));
+    router
 }
 
 #[cfg(feature = "http")]
@@ -859,12 +1595,68 @@ impl ApiError {
         }
     }
 
+    fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            message: message.into(),
+        }
+    }
+
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.into(),
+        }
+    }
+
     fn not_found(message: impl Into<String>) -> Self {
         Self {
             status: StatusCode::NOT_FOUND,
             message: message.into(),
         }
     }
+
+    fn conflict(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            message: message.into(),
+        }
+    }
+
+    fn too_many_requests(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            message: message.into(),
+        }
+    }
+
+    fn service_unavailable(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: message.into(),
+        }
+    }
+
+    fn gone(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::GONE,
+            message: message.into(),
+        }
+    }
+
+    fn bad_gateway(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            message: message.into(),
+        }
+    }
+
+    fn internal(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: message.into(),
+        }
+    }
 }
 
 #[cfg(feature = "http")]
@@ -882,11 +1674,21 @@ async fn health() -> impl IntoResponse {
 
 #[cfg(feature = "http")]
 async fn db_stats(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, ApiError> {
-    state
+    let stats = state
         .db
         .db_stats()
-        .map(Json)
-        .map_err(|err| ApiError::bad_request(err.to_string()))
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let mut out =
+        serde_json::to_value(stats).map_err(|err| ApiError::bad_request(err.to_string()))?;
+    if let (Some(maintenance), Some(obj)) = (&state.maintenance, out.as_object_mut()) {
+        obj.insert(
+            "maintenance".to_string(),
+            serde_json::to_value(maintenance.status())
+                .map_err(|err| ApiError::bad_request(err.to_string()))?,
+        );
+    }
+    Ok(Json(out))
 }
 
 #[cfg(feature = "http")]
@@ -894,6 +1696,7 @@ async fn checkpoint(State(state): State<Arc<AppState>>) -> Result<impl IntoRespo
     state
         .db
         .checkpoint()
+        .await
         .map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
@@ -916,6 +1719,7 @@ async fn snapshot_export(
     state
         .db
         .export_snapshot(path)
+        .await
         .map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
@@ -939,7 +1743,8 @@ async fn snapshot_restore(
     }
     let snapshot_dir = PathBuf::from(req.snapshot_dir);
     let data_dir = PathBuf::from(req.data_dir);
-    EmbedDb::restore_snapshot(snapshot_dir, data_dir)
+    AsyncEmbedDb::restore_snapshot(snapshot_dir, data_dir)
+        .await
         .map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
@@ -987,6 +1792,7 @@ async fn list_tables(State(state): State<Arc<AppState>>) -> Result<Json<Vec<Stri
     state
         .db
         .list_tables()
+        .await
         .map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
@@ -997,6 +1803,17 @@ struct CreateTableRequest {
     name: String,
     schema: TableSchema,
     embedding_fields: Option<Vec<String>>,
+    embedding_metric: Option<DistanceMetric>,
+    #[serde(default)]
+    embedding_vector_encoding: VectorEncoding,
+    #[serde(default)]
+    embedding_index: IndexSpec,
+    embedding_dimensions: Option<usize>,
+    #[serde(default)]
+    embedding_vectors: Vec<NamedVectorSpec>,
+    #[serde(default)]
+    embedding_retry: RetryPolicy,
+    embedding_model: Option<EmbeddingModel>,
 }
 
 #[cfg(feature = "http")]
@@ -1004,599 +1821,3826 @@ async fn create_table(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateTableRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let embed_spec = req
-        .embedding_fields
-        .map(|fields| EmbeddingSpec::new(fields));
+    let embed_spec = req.embedding_fields.map(|fields| {
+        let mut spec = EmbeddingSpec::new(fields)
+            .with_vector_encoding(req.embedding_vector_encoding)
+            .with_index(req.embedding_index)
+            .with_retry(req.embedding_retry);
+        if let Some(metric) = req.embedding_metric {
+            spec = spec.with_metric(metric);
+        }
+        if let Some(dimensions) = req.embedding_dimensions {
+            spec = spec.with_dimensions(dimensions);
+        }
+        spec.vectors = req.embedding_vectors;
+        spec.model = req.embedding_model;
+        spec
+    });
     state
         .db
         .create_table(req.name, req.schema, embed_spec)
+        .await
         .map_err(|err| ApiError::bad_request(err.to_string()))?;
     Ok((StatusCode::CREATED, Json(serde_json::json!({ "ok": true }))))
 }
 
 #[cfg(feature = "http")]
-async fn describe_table(
+#[derive(Debug, Deserialize)]
+struct SetEmbeddingSpecRequest {
+    embedding_fields: Vec<String>,
+    embedding_metric: Option<DistanceMetric>,
+    #[serde(default)]
+    embedding_vector_encoding: VectorEncoding,
+    #[serde(default)]
+    embedding_index: IndexSpec,
+    embedding_dimensions: Option<usize>,
+    #[serde(default)]
+    embedding_vectors: Vec<NamedVectorSpec>,
+    #[serde(default)]
+    embedding_retry: RetryPolicy,
+    embedding_model: Option<EmbeddingModel>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[cfg(feature = "http")]
+async fn set_embedding_spec(
     State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
+    Json(req): Json<SetEmbeddingSpecRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    state
-        .db
-        .describe_table(&table)
-        .map(Json)
+    let mut spec = EmbeddingSpec::new(req.embedding_fields)
+        .with_vector_encoding(req.embedding_vector_encoding)
+        .with_index(req.embedding_index)
+        .with_retry(req.embedding_retry);
+    if let Some(metric) = req.embedding_metric {
+        spec = spec.with_metric(metric);
+    }
+    if let Some(dimensions) = req.embedding_dimensions {
+        spec = spec.with_dimensions(dimensions);
+    }
+    spec.vectors = req.embedding_vectors;
+    spec.model = req.embedding_model;
+    let plan = if req.dry_run {
+        state.db.plan_embedding_spec(&table, &spec).await
+    } else {
+        state.db.apply_embedding_spec(&table, spec).await
+    };
+    plan.map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
 
 #[cfg(feature = "http")]
-async fn table_stats(
+#[derive(Debug, Deserialize)]
+struct ReembedRequest {
+    model: EmbeddingModel,
+}
+
+#[cfg(feature = "http")]
+async fn reembed_table(
     State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
+    Json(req): Json<ReembedRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
     state
         .db
-        .table_stats(&table)
+        .reembed_table(&table, req.model)
+        .await
         .map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
 
 #[cfg(feature = "http")]
-async fn list_jobs(
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op")]
+enum AlterTableRequest {
+    #[serde(rename = "add_column")]
+    Add {
+        column: Column,
+        #[serde(default)]
+        default: serde_json::Value,
+    },
+    #[serde(rename = "drop_column")]
+    Drop { name: String },
+    #[serde(rename = "rename_column")]
+    Rename { from: String, to: String },
+}
+
+#[cfg(feature = "http")]
+async fn alter_table(
     State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
-) -> Result<Json<Vec<EmbeddingJob>>, ApiError> {
+    Json(req): Json<AlterTableRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let op = match req {
+        AlterTableRequest::Add { column, default } => {
+            let default = match json_value_to_embeddb(default)
+                .map_err(|err| ApiError::bad_request(err.to_string()))?
+            {
+                Value::Int(v) if column.data_type == DataType::Float => Value::Float(v as f64),
+                value => value,
+            };
+            AlterTableOp::AddColumn { column, default }
+        }
+        AlterTableRequest::Drop { name } => AlterTableOp::DropColumn { name },
+        AlterTableRequest::Rename { from, to } => AlterTableOp::RenameColumn { from, to },
+    };
     state
         .db
-        .list_embedding_jobs(&table)
+        .alter_table(&table, op)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    state
+        .db
+        .describe_table(&table)
+        .await
         .map(Json)
         .map_err(|err| ApiError::bad_request(err.to_string()))
 }
 
 #[cfg(feature = "http")]
 #[derive(Debug, Deserialize)]
-struct InsertRowRequest {
-    fields: BTreeMap<String, serde_json::Value>,
+struct RenameTableRequest {
+    name: String,
 }
 
 #[cfg(feature = "http")]
-async fn insert_row(
+async fn rename_table(
     State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
-    Json(req): Json<InsertRowRequest>,
+    Json(req): Json<RenameTableRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let fields: BTreeMap<String, Value> = req
-        .fields
-        .into_iter()
-        .map(|(key, value)| {
-            json_value_to_embeddb(value)
-                .map(|parsed| (key, parsed))
-                .map_err(|err| ApiError::bad_request(err.to_string()))
-        })
-        .collect::<Result<_, _>>()?;
-
-    let row_id = state
+    state
         .db
-        .insert_row(&table, fields)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    Ok((
-        StatusCode::CREATED,
-        Json(serde_json::json!({ "row_id": row_id })),
-    ))
+        .rename_table(&table, req.name.clone())
+        .await
+        .map_err(|err| match err.to_string().as_str() {
+            "table not found" => ApiError::not_found("table not found"),
+            "table already exists" => ApiError::conflict("table already exists"),
+            _ => ApiError::bad_request(err.to_string()),
+        })?;
+    state
+        .db
+        .describe_table(&req.name)
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
 }
 
 #[cfg(feature = "http")]
-async fn get_row(
+async fn describe_table(
     State(state): State<Arc<AppState>>,
-    Path((table, row_id)): Path<(String, u64)>,
+    Path(table): Path<String>,
 ) -> Result<impl IntoResponse, ApiError> {
-    match state
+    state
         .db
-        .get_row(&table, row_id)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?
-    {
-        Some(row) => {
-            let mut fields = serde_json::Map::new();
-            for (key, value) in row.fields {
-                fields.insert(key, embeddb_value_to_json(value));
-            }
-            Ok(Json(serde_json::json!({
-                "id": row.id,
-                "fields": fields
-            })))
-        }
-        None => Err(ApiError::not_found("row not found")),
-    }
+        .describe_table(&table)
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
 }
 
 #[cfg(feature = "http")]
-async fn delete_row(
+async fn table_stats(
     State(state): State<Arc<AppState>>,
-    Path((table, row_id)): Path<(String, u64)>,
+    Path(table): Path<String>,
 ) -> Result<impl IntoResponse, ApiError> {
     state
         .db
-        .delete_row(&table, row_id)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    Ok(Json(serde_json::json!({ "ok": true })))
+        .table_stats(&table)
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
 }
 
 #[cfg(feature = "http")]
 #[derive(Debug, Deserialize)]
-struct FilterConditionJson {
-    column: String,
-    op: FilterOp,
-    value: serde_json::Value,
+struct ListJobsQuery {
+    status: Option<String>,
+    after: Option<u64>,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    sort: Option<String>,
+    order: Option<String>,
 }
 
 #[cfg(feature = "http")]
-fn parse_filters(raw: Vec<FilterConditionJson>) -> Result<Vec<FilterCondition>> {
-    let mut out = Vec::with_capacity(raw.len());
-    for cond in raw {
-        let value = json_value_to_embeddb(cond.value)?;
-        out.push(FilterCondition {
-            column: cond.column,
-            op: cond.op,
-            value,
-        });
+fn parse_job_status(raw: &str) -> Result<EmbeddingStatus, ApiError> {
+    match raw.to_ascii_lowercase().as_str() {
+        "pending" => Ok(EmbeddingStatus::Pending),
+        "ready" => Ok(EmbeddingStatus::Ready),
+        "failed" => Ok(EmbeddingStatus::Failed),
+        _ => Err(ApiError::bad_request(format!(
+            "unknown job status '{raw}' (expected pending, ready, or failed)"
+        ))),
     }
-    Ok(out)
 }
 
 #[cfg(feature = "http")]
-#[derive(Debug, Deserialize)]
-struct SearchRequest {
-    query: Vec<f32>,
-    k: Option<usize>,
-    metric: Option<DistanceMetric>,
-    filter: Option<Vec<FilterConditionJson>>,
+fn parse_job_sort(raw: &str) -> Result<JobSort, ApiError> {
+    match raw.to_ascii_lowercase().as_str() {
+        "row_id" => Ok(JobSort::RowId),
+        "attempts" => Ok(JobSort::Attempts),
+        "next_retry_at" => Ok(JobSort::NextRetryAt),
+        _ => Err(ApiError::bad_request(format!(
+            "unknown job sort '{raw}' (expected row_id, attempts, or next_retry_at)"
+        ))),
+    }
 }
 
+/// Returns the job array directly. `x-total-count` carries the number of matching jobs before
+/// `offset` and `limit`; when `limit` cuts a row id ordered listing short, `x-next-after` carries
+/// the row id to pass as `after` for the next page.
 #[cfg(feature = "http")]
-async fn search(
+async fn list_jobs(
     State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
-    Json(req): Json<SearchRequest>,
-) -> Result<impl IntoResponse, ApiError> {
-    let k = req.k.unwrap_or(5);
-    let metric = req.metric.unwrap_or(DistanceMetric::Cosine);
-    let filters = req
-        .filter
-        .map(parse_filters)
-        .transpose()
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    state
+    Query(query): Query<ListJobsQuery>,
+) -> Result<Response, ApiError> {
+    let descending = match query
+        .order
+        .as_deref()
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        None | Some("asc") => false,
+        Some("desc") => true,
+        Some(other) => {
+            return Err(ApiError::bad_request(format!(
+                "unknown order '{other}' (expected asc or desc)"
+            )))
+        }
+    };
+    let options = JobListOptions {
+        status: query.status.as_deref().map(parse_job_status).transpose()?,
+        cursor: query.after,
+        offset: query.offset,
+        limit: Some(query.limit.map_or(usize::MAX, |limit| limit.min(10_000))),
+        sort: query
+            .sort
+            .as_deref()
+            .map(parse_job_sort)
+            .transpose()?
+            .unwrap_or_default(),
+        descending,
+    };
+    let page = state
         .db
-        .search_knn_filtered(
-            &table,
-            &req.query,
-            k,
-            metric,
-            filters.as_deref().unwrap_or(&[]),
-        )
-        .map(Json)
-        .map_err(|err| ApiError::bad_request(err.to_string()))
+        .list_embedding_jobs_with_options(&table, &options)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let mut response = Json(page.items).into_response();
+    response
+        .headers_mut()
+        .insert("x-total-count", HeaderValue::from(page.total));
+    if let Some(next) = page.next_cursor {
+        response
+            .headers_mut()
+            .insert("x-next-after", HeaderValue::from(next));
+    }
+    Ok(response)
 }
 
 #[cfg(feature = "http")]
 #[derive(Debug, Deserialize)]
-struct SearchTextRequest {
-    query_text: String,
-    k: Option<usize>,
-    metric: Option<DistanceMetric>,
-    filter: Option<Vec<FilterConditionJson>>,
+struct ListAllJobsQuery {
+    status: Option<String>,
 }
 
 #[cfg(feature = "http")]
-async fn search_text(
+async fn list_all_jobs(
     State(state): State<Arc<AppState>>,
-    Path(table): Path<String>,
-    Json(req): Json<SearchTextRequest>,
+    Query(query): Query<ListAllJobsQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let k = req.k.unwrap_or(5);
-    let metric = req.metric.unwrap_or(DistanceMetric::Cosine);
-    let embedder = LocalHashEmbedder;
-    let query = embedder
-        .embed(&req.query_text)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    let filters = req
-        .filter
-        .map(parse_filters)
-        .transpose()
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    state
+    let status = query.status.as_deref().map(parse_job_status).transpose()?;
+    let jobs = state
         .db
-        .search_knn_filtered(&table, &query, k, metric, filters.as_deref().unwrap_or(&[]))
-        .map(Json)
-        .map_err(|err| ApiError::bad_request(err.to_string()))
+        .list_embedding_jobs_all(status)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(jobs))
 }
 
 #[cfg(feature = "http")]
-async fn process_jobs(
-    State(state): State<Arc<AppState>>,
+#[derive(Debug, Deserialize)]
+struct ScrollEmbeddingsQuery {
+    cursor: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[cfg(feature = "http")]
+async fn scroll_embeddings(
+    State(state): State<Arc<AppState>>,
     Path(table): Path<String>,
-    Query(query): Query<ProcessJobsQuery>,
+    Query(query): Query<ScrollEmbeddingsQuery>,
+) -> Result<Json<EmbeddingPage>, ApiError> {
+    let limit = query.limit.unwrap_or(100).min(10_000);
+    state
+        .db
+        .scroll_embeddings(&table, query.cursor, limit)
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    /// Unix milliseconds; entries before this are skipped.
+    since: Option<u64>,
+    /// The `next_cursor` of the previous page: an entry's LSN.
+    after: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[cfg(feature = "http")]
+async fn audit_log(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = query.limit.unwrap_or(1000).clamp(1, 10_000);
+    let entries = state
+        .db
+        .audit_log(query.since.unwrap_or(0))
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    let mut items: Vec<AuditEntry> = entries
+        .into_iter()
+        .filter(|entry| query.after.is_none_or(|after| entry.lsn > after))
+        .take(limit + 1)
+        .collect();
+    let next_cursor = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(|entry| entry.lsn)
+    } else {
+        None
+    };
+    Ok(Json(serde_json::json!({
+        "items": items,
+        "next_cursor": next_cursor
+    })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct ScanRowsQuery {
+    after: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[cfg(feature = "http")]
+async fn scan_rows(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(query): Query<ScanRowsQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = query.limit.unwrap_or(100).min(10_000);
+    let page = state
+        .db
+        .scan_rows(&table, query.after, limit)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let items: Vec<serde_json::Value> = page.items.into_iter().map(row_to_json).collect();
+    Ok(Json(serde_json::json!({
+        "items": items,
+        "next_cursor": page.next_cursor
+    })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct InsertRowRequest {
+    fields: BTreeMap<String, serde_json::Value>,
+    /// Delete the row once this many seconds have passed.
+    ttl_seconds: Option<u64>,
+    /// A precomputed embedding, stored with the row instead of queueing a job.
+    embedding: Option<Vec<f32>>,
+}
+
+#[cfg(feature = "http")]
+async fn insert_row(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<InsertRowRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let embedder = LocalHashEmbedder;
-    let processed = match query.limit {
-        Some(limit) => state
+    let fields: BTreeMap<String, Value> = req
+        .fields
+        .into_iter()
+        .map(|(key, value)| {
+            json_value_to_embeddb(value)
+                .map(|parsed| (key, parsed))
+                .map_err(|err| ApiError::bad_request(err.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let row_id = match (req.ttl_seconds, req.embedding) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "ttl_seconds cannot be combined with embedding",
+            ))
+        }
+        (Some(ttl), None) => {
+            state
+                .db
+                .insert_row_with_ttl(&table, fields, Duration::from_secs(ttl))
+                .await
+        }
+        (None, Some(embedding)) => {
+            state
+                .db
+                .insert_row_with_embedding(&table, fields, embedding)
+                .await
+        }
+        (None, None) => state.db.insert_row(&table, fields).await,
+    }
+    .map_err(write_error)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "row_id": row_id, "version": 1 })),
+    ))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct UpdateRowRequest {
+    fields: BTreeMap<String, serde_json::Value>,
+    /// Fail with 409 unless the row is still at this version.
+    expected_version: Option<u64>,
+}
+
+#[cfg(feature = "http")]
+async fn update_row(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+    Json(req): Json<UpdateRowRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let fields: BTreeMap<String, Value> = req
+        .fields
+        .into_iter()
+        .map(|(key, value)| {
+            json_value_to_embeddb(value)
+                .map(|parsed| (key, parsed))
+                .map_err(|err| ApiError::bad_request(err.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let version = match req.expected_version {
+        Some(expected) => {
+            state
+                .db
+                .update_row_if_version(&table, row_id, fields, expected)
+                .await
+        }
+        None => state.db.update_row(&table, row_id, fields).await,
+    }
+    .map_err(row_write_error)?;
+    Ok(Json(
+        serde_json::json!({ "row_id": row_id, "version": version }),
+    ))
+}
+
+/// Merges the provided fields into the row; columns left out keep their values.
+#[cfg(feature = "http")]
+async fn patch_row(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+    Json(req): Json<UpdateRowRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let fields: BTreeMap<String, Value> = req
+        .fields
+        .into_iter()
+        .map(|(key, value)| {
+            json_value_to_embeddb(value)
+                .map(|parsed| (key, parsed))
+                .map_err(|err| ApiError::bad_request(err.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let row = match req.expected_version {
+        Some(expected) => {
+            state
+                .db
+                .patch_row_if_version(&table, row_id, fields, expected)
+                .await
+        }
+        None => state.db.patch_row(&table, row_id, fields).await,
+    }
+    .map_err(row_write_error)?;
+    Ok(Json(row_to_json(row)))
+}
+
+#[cfg(feature = "http")]
+fn row_write_error(err: anyhow::Error) -> ApiError {
+    if err.downcast_ref::<VersionConflict>().is_some() {
+        ApiError::conflict(err.to_string())
+    } else if err.to_string() == "row not found" {
+        ApiError::not_found("row not found")
+    } else {
+        write_error(err)
+    }
+}
+
+/// `503` for a `WriteStall`, which clears once the table can be flushed again; `400` otherwise.
+#[cfg(feature = "http")]
+fn write_error(err: anyhow::Error) -> ApiError {
+    if err.downcast_ref::<WriteStall>().is_some() {
+        ApiError::service_unavailable(err.to_string())
+    } else {
+        ApiError::bad_request(err.to_string())
+    }
+}
+
+#[cfg(feature = "http")]
+async fn get_row(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+) -> Result<impl IntoResponse, ApiError> {
+    match state
+        .db
+        .get_row(&table, row_id)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?
+    {
+        Some(row) => Ok(Json(row_to_json(row))),
+        None => Err(ApiError::not_found("row not found")),
+    }
+}
+
+#[cfg(feature = "http")]
+async fn delete_row(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+    Query(query): Query<DeleteRowQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    match query.expected_version {
+        Some(expected) => state
             .db
-            .process_pending_jobs_with_limit(&table, &embedder, limit)
-            .map_err(|err| ApiError::bad_request(err.to_string()))?,
+            .delete_row_if_version(&table, row_id, expected)
+            .await
+            .map_err(row_write_error)?,
         None => state
             .db
-            .process_pending_jobs(&table, &embedder)
-            .map_err(|err| ApiError::bad_request(err.to_string()))?,
-    };
-    Ok(Json(serde_json::json!({ "processed": processed })))
+            .delete_row(&table, row_id)
+            .await
+            .map_err(write_error)?,
+    }
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct DeleteRowQuery {
+    expected_version: Option<u64>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct DeleteRowsRequest {
+    #[serde(alias = "filters")]
+    filter: Option<Vec<FilterConditionJson>>,
+    #[serde(rename = "where")]
+    where_clause: Option<String>,
+}
+
+#[cfg(feature = "http")]
+async fn delete_rows(
+    State(state): State<Arc<AppState>>,
+    Path((table, action)): Path<(String, String)>,
+    Json(req): Json<DeleteRowsRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if action != ":delete" {
+        return Err(ApiError::not_found(format!("unknown route rows{action}")));
+    }
+    let filters = request_filters(req.filter, req.where_clause.as_deref())?;
+    // An empty filter would match every row.
+    if filters.is_empty() {
+        return Err(ApiError::bad_request(
+            "delete needs at least one filter condition",
+        ));
+    }
+    let deleted = state
+        .db
+        .delete_rows_where(&table, &filters)
+        .await
+        .map_err(write_error)?;
+    Ok(Json(serde_json::json!({ "deleted": deleted })))
+}
+
+#[cfg(feature = "http")]
+async fn restore_row(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let row = state.db.restore_row(&table, row_id).await.map_err(|err| {
+        match err.to_string().as_str() {
+            "table not found" | "row not found" => ApiError::not_found(err.to_string()),
+            "row is not deleted" => ApiError::conflict(err.to_string()),
+            _ => ApiError::bad_request(err.to_string()),
+        }
+    })?;
+    Ok(Json(row_to_json(row)))
+}
+
+#[cfg(feature = "http")]
+async fn purge_hidden_rows(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let purged = state
+        .db
+        .purge_hidden_rows(&table)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "purged": purged })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct WriteBatchRequest {
+    ops: Vec<WriteOpJson>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WriteOpJson {
+    Insert {
+        table: String,
+        fields: BTreeMap<String, serde_json::Value>,
+    },
+    Update {
+        table: String,
+        row_id: u64,
+        fields: BTreeMap<String, serde_json::Value>,
+        expected_version: Option<u64>,
+    },
+    Delete {
+        table: String,
+        row_id: u64,
+        expected_version: Option<u64>,
+    },
+}
+
+#[cfg(feature = "http")]
+fn json_fields(fields: BTreeMap<String, serde_json::Value>) -> Result<BTreeMap<String, Value>> {
+    fields
+        .into_iter()
+        .map(|(key, value)| json_value_to_embeddb(value).map(|parsed| (key, parsed)))
+        .collect()
+}
+
+#[cfg(feature = "http")]
+async fn write_batch(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<WriteBatchRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let ops = req
+        .ops
+        .into_iter()
+        .enumerate()
+        .map(|(idx, op)| {
+            let op = match op {
+                WriteOpJson::Insert { table, fields } => WriteOp::Insert {
+                    table,
+                    fields: json_fields(fields).map_err(|err| anyhow!("op {idx}: {err}"))?,
+                },
+                WriteOpJson::Update {
+                    table,
+                    row_id,
+                    fields,
+                    expected_version,
+                } => WriteOp::Update {
+                    table,
+                    row_id,
+                    fields: json_fields(fields).map_err(|err| anyhow!("op {idx}: {err}"))?,
+                    expected_version,
+                },
+                WriteOpJson::Delete {
+                    table,
+                    row_id,
+                    expected_version,
+                } => WriteOp::Delete {
+                    table,
+                    row_id,
+                    expected_version,
+                },
+            };
+            Ok(op)
+        })
+        .collect::<Result<Vec<_>>>()
+        .map_err(|err: anyhow::Error| ApiError::bad_request(err.to_string()))?;
+    let results = state.db.write_batch(ops).await.map_err(|err| {
+        let message = format!("{err:#}");
+        if err.downcast_ref::<VersionConflict>().is_some() {
+            ApiError::conflict(message)
+        } else if err.downcast_ref::<WriteStall>().is_some() {
+            ApiError::service_unavailable(message)
+        } else {
+            ApiError::bad_request(message)
+        }
+    })?;
+    Ok(Json(serde_json::json!({ "results": results })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct FilterConditionJson {
+    column: String,
+    op: FilterOp,
+    value: serde_json::Value,
+}
+
+#[cfg(feature = "http")]
+fn parse_filters(raw: Vec<FilterConditionJson>) -> Result<Vec<FilterCondition>> {
+    let mut out = Vec::with_capacity(raw.len());
+    for cond in raw {
+        let value = json_value_to_embeddb(cond.value)?;
+        out.push(FilterCondition {
+            column: cond.column,
+            op: cond.op,
+            value,
+        });
+    }
+    Ok(out)
+}
+
+/// Combines a request's JSON `filter` array with its `where` clause.
+#[cfg(feature = "http")]
+fn request_filters(
+    filter: Option<Vec<FilterConditionJson>>,
+    where_clause: Option<&str>,
+) -> Result<Vec<FilterCondition>, ApiError> {
+    let mut filters = filter
+        .map(parse_filters)
+        .transpose()
+        .map_err(|err| ApiError::bad_request(err.to_string()))?
+        .unwrap_or_default();
+    if let Some(clause) = where_clause {
+        filters.extend(parse_filter(clause).map_err(|err| ApiError::bad_request(err.to_string()))?);
+    }
+    Ok(filters)
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct SearchRequest {
+    query: Vec<f32>,
+    k: Option<usize>,
+    metric: Option<DistanceMetric>,
+    #[serde(alias = "filters")]
+    filter: Option<Vec<FilterConditionJson>>,
+    /// Where clause (e.g. `score > 0.5 AND tag = "rust"`), ANDed with `filter`.
+    #[serde(rename = "where")]
+    where_clause: Option<String>,
+    #[serde(default)]
+    allow_metric_mismatch: bool,
+    /// Named vector of the table's embedding spec to search instead of its unnamed vector.
+    #[serde(default)]
+    vector: Option<String>,
+    /// Number of best hits to skip before the `k` returned.
+    #[serde(default)]
+    offset: usize,
+    /// Return hits ranked after this cursor, taken from a previous page's `x-next-after` header.
+    after: Option<String>,
+    /// `Similarity` adds a 0..1 `score` to each hit next to its distance.
+    #[serde(default)]
+    score_mode: ScoreMode,
+    /// Only these row ids may be returned, e.g. the documents a permission check allows.
+    #[serde(default)]
+    include_ids: Option<BTreeSet<u64>>,
+    /// Row ids never to return.
+    #[serde(default)]
+    exclude_ids: BTreeSet<u64>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Default, Deserialize)]
+struct SearchQuery {
+    /// Comma-separated extras to return with each hit; only `fields` is supported.
+    include: Option<String>,
+}
+
+#[cfg(feature = "http")]
+impl SearchQuery {
+    fn include_fields(&self) -> Result<bool, ApiError> {
+        let mut fields = false;
+        for part in self.include.iter().flat_map(|raw| raw.split(',')) {
+            match part.trim() {
+                "" => {}
+                "fields" => fields = true,
+                other => return Err(ApiError::bad_request(format!("unknown include '{other}'"))),
+            }
+        }
+        Ok(fields)
+    }
 }
 
-#[cfg(feature = "http")]
-#[derive(Debug, Deserialize)]
-struct ProcessJobsQuery {
-    limit: Option<usize>,
-}
+/// Builds search options from a request's fields, parsing its `after` cursor.
+#[cfg(feature = "http")]
+fn search_options(
+    allow_metric_mismatch: bool,
+    vector: Option<String>,
+    offset: usize,
+    after: Option<&str>,
+    score_mode: ScoreMode,
+    include_ids: Option<BTreeSet<u64>>,
+    exclude_ids: BTreeSet<u64>,
+) -> Result<SearchOptions, ApiError> {
+    let after = after
+        .map(str::parse::<SearchCursor>)
+        .transpose()
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(SearchOptions {
+        allow_metric_mismatch,
+        vector,
+        offset,
+        after,
+        score_mode,
+        include_ids,
+        exclude_ids,
+    })
+}
+
+/// Returns the hit array directly. When the page is full, `x-next-after` carries the cursor of
+/// its last hit, to pass as `after` for the next page.
+#[cfg(feature = "http")]
+fn search_page<T: Serialize>(hits: Vec<T>, k: usize, last: Option<SearchCursor>) -> Response {
+    let full = k > 0 && hits.len() == k;
+    let mut response = Json(hits).into_response();
+    if let Some(cursor) = last.filter(|_| full) {
+        if let Ok(value) = HeaderValue::from_str(&cursor.to_string()) {
+            response.headers_mut().insert("x-next-after", value);
+        }
+    }
+    response
+}
+
+#[cfg(feature = "http")]
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(params): Query<SearchQuery>,
+    Json(req): Json<SearchRequest>,
+) -> Result<Response, ApiError> {
+    let k = req.k.unwrap_or(5);
+    let metric = req.metric;
+    let filters = request_filters(req.filter, req.where_clause.as_deref())?;
+    let filters = filters.as_slice();
+    let options = search_options(
+        req.allow_metric_mismatch,
+        req.vector,
+        req.offset,
+        req.after.as_deref(),
+        req.score_mode,
+        req.include_ids,
+        req.exclude_ids,
+    )?;
+    if !params.include_fields()? {
+        let hits = state
+            .db
+            .search_knn_with_options(&table, &req.query, k, metric, filters, &options)
+            .await
+            .map_err(|err| ApiError::bad_request(err.to_string()))?;
+        let last = hits.last().map(SearchCursor::from);
+        return Ok(search_page(hits, k, last));
+    }
+
+    let hits = state
+        .db
+        .search_knn_with_rows(&table, &req.query, k, metric, filters, &options)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let last = hits.last().map(|hit| SearchCursor {
+        row_id: hit.row_id,
+        distance: hit.distance,
+    });
+    let hits: Vec<serde_json::Value> = hits
+        .into_iter()
+        .map(|hit| {
+            let fields: serde_json::Map<String, serde_json::Value> = hit
+                .fields
+                .into_iter()
+                .map(|(key, value)| (key, embeddb_value_to_json(value)))
+                .collect();
+            let mut json = serde_json::json!({
+                "row_id": hit.row_id,
+                "distance": hit.distance,
+                "fields": fields
+            });
+            if let Some(score) = hit.score {
+                json["score"] = serde_json::json!(score);
+            }
+            json
+        })
+        .collect();
+    Ok(search_page(hits, k, last))
+}
+
+#[cfg(feature = "http")]
+async fn explain_search(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<SearchRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let filters = request_filters(req.filter, req.where_clause.as_deref())?;
+    state
+        .db
+        .explain_search(
+            &table,
+            &req.query,
+            req.k.unwrap_or(5),
+            req.metric,
+            &filters,
+            &search_options(
+                req.allow_metric_mismatch,
+                req.vector,
+                req.offset,
+                req.after.as_deref(),
+                req.score_mode,
+                req.include_ids,
+                req.exclude_ids,
+            )?,
+        )
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct PutEmbeddingRequest {
+    vector: Vec<f32>,
+}
+
+#[cfg(feature = "http")]
+async fn put_embedding(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+    Json(req): Json<PutEmbeddingRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .db
+        .put_embedding(&table, row_id, req.vector)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+#[cfg(feature = "http")]
+async fn get_embedding(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+) -> Result<impl IntoResponse, ApiError> {
+    match state
+        .db
+        .get_embedding(&table, row_id)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?
+    {
+        Some(record) => Ok(Json(record)),
+        None => Err(ApiError::not_found("embedding not found")),
+    }
+}
+
+#[cfg(feature = "http")]
+async fn put_sparse_vector(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+    Json(vector): Json<SparseVector>,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .db
+        .put_sparse_vector(&table, row_id, vector)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+#[cfg(feature = "http")]
+async fn get_sparse_vector(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+) -> Result<impl IntoResponse, ApiError> {
+    match state
+        .db
+        .get_sparse_vector(&table, row_id)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?
+    {
+        Some(vector) => Ok(Json(vector)),
+        None => Err(ApiError::not_found("sparse vector not found")),
+    }
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct SearchSparseRequest {
+    sparse: SparseVector,
+    /// Optional dense query; when set, dense and sparse rankings are fused with RRF.
+    query: Option<Vec<f32>>,
+    k: Option<usize>,
+    metric: Option<DistanceMetric>,
+    #[serde(alias = "filters")]
+    filter: Option<Vec<FilterConditionJson>>,
+}
+
+#[cfg(feature = "http")]
+async fn search_sparse(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<SearchSparseRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let k = req.k.unwrap_or(5);
+    let filters = req
+        .filter
+        .map(parse_filters)
+        .transpose()
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let filters = filters.as_deref().unwrap_or(&[]);
+    let hits = match req.query {
+        Some(query) => {
+            state
+                .db
+                .search_hybrid_sparse(&table, &query, &req.sparse, k, req.metric, filters)
+                .await
+        }
+        None => {
+            state
+                .db
+                .search_sparse(&table, &req.sparse, k, filters)
+                .await
+        }
+    };
+    hits.map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct SearchTextRequest {
+    query_text: String,
+    k: Option<usize>,
+    metric: Option<DistanceMetric>,
+    #[serde(alias = "filters")]
+    filter: Option<Vec<FilterConditionJson>>,
+    /// Where clause (e.g. `score > 0.5 AND tag = "rust"`), ANDed with `filter`.
+    #[serde(rename = "where")]
+    where_clause: Option<String>,
+    #[serde(default)]
+    allow_metric_mismatch: bool,
+    /// Named vector of the table's embedding spec to search instead of its unnamed vector.
+    #[serde(default)]
+    vector: Option<String>,
+    /// Number of best hits to skip before the `k` returned.
+    #[serde(default)]
+    offset: usize,
+    /// Return hits ranked after this cursor, taken from a previous page's `x-next-after` header.
+    after: Option<String>,
+    #[serde(default)]
+    score_mode: ScoreMode,
+    #[serde(default)]
+    include_ids: Option<BTreeSet<u64>>,
+    #[serde(default)]
+    exclude_ids: BTreeSet<u64>,
+}
+
+#[cfg(feature = "http")]
+async fn search_text(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<SearchTextRequest>,
+) -> Result<Response, ApiError> {
+    let k = req.k.unwrap_or(5);
+    let metric = req.metric;
+    let options = search_options(
+        req.allow_metric_mismatch,
+        req.vector,
+        req.offset,
+        req.after.as_deref(),
+        req.score_mode,
+        req.include_ids,
+        req.exclude_ids,
+    )?;
+    let embedder = state.embedder.clone();
+    let query = tokio::task::spawn_blocking(move || embedder.embed(&req.query_text))
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .map_err(|err| ApiError::bad_gateway(format!("embedding query failed: {err}")))?;
+    let filters = request_filters(req.filter, req.where_clause.as_deref())?;
+    let hits = state
+        .db
+        .search_knn_with_options(&table, &query, k, metric, &filters, &options)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let last = hits.last().map(SearchCursor::from);
+    Ok(search_page(hits, k, last))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct SearchHybridRequest {
+    query_text: String,
+    /// Dense query; when omitted, `query_text` is embedded with the server's embedder.
+    query: Option<Vec<f32>>,
+    k: Option<usize>,
+    #[serde(default)]
+    fusion: Fusion,
+    #[serde(alias = "filters")]
+    filter: Option<Vec<FilterConditionJson>>,
+}
+
+#[cfg(feature = "http")]
+async fn search_hybrid(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<SearchHybridRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let k = req.k.unwrap_or(5);
+    let query = match req.query {
+        Some(query) => query,
+        None => {
+            let embedder = state.embedder.clone();
+            let text = req.query_text.clone();
+            tokio::task::spawn_blocking(move || embedder.embed(&text))
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?
+                .map_err(|err| ApiError::bad_gateway(format!("embedding query failed: {err}")))?
+        }
+    };
+    let filters = req
+        .filter
+        .map(parse_filters)
+        .transpose()
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    state
+        .db
+        .search_hybrid(
+            &table,
+            &req.query_text,
+            &query,
+            k,
+            req.fusion,
+            filters.as_deref().unwrap_or(&[]),
+        )
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Default, Deserialize)]
+struct SearchSimilarRequest {
+    k: Option<usize>,
+    exclude_self: Option<bool>,
+}
+
+#[cfg(feature = "http")]
+async fn search_similar(
+    State(state): State<Arc<AppState>>,
+    Path((table, row_id)): Path<(String, u64)>,
+    body: Option<Json<SearchSimilarRequest>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let k = req.k.unwrap_or(5);
+    state
+        .db
+        .search_similar(&table, row_id, k, req.exclude_self.unwrap_or(true))
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct RecommendRequest {
+    positive: Vec<u64>,
+    #[serde(default)]
+    negative: Vec<u64>,
+    k: Option<usize>,
+}
+
+#[cfg(feature = "http")]
+async fn recommend(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<RecommendRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let k = req.k.unwrap_or(5);
+    state
+        .db
+        .recommend(&table, &req.positive, &req.negative, k)
+        .await
+        .map(Json)
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct DuplicatesRequest {
+    threshold: f32,
+    metric: Option<DistanceMetric>,
+}
+
+#[cfg(feature = "http")]
+async fn find_duplicates(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<DuplicatesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let clusters = state
+        .db
+        .find_duplicates(&table, req.threshold, req.metric)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "clusters": clusters })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct CountQuery {
+    /// Where clause (e.g. `score > 0.5 AND tag = "rust"`); all live rows when omitted.
+    #[serde(rename = "where")]
+    where_clause: Option<String>,
+}
+
+#[cfg(feature = "http")]
+async fn count_rows(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(query): Query<CountQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let filters = request_filters(None, query.where_clause.as_deref())?;
+    let count = state
+        .db
+        .count_rows(&table, &filters)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "count": count })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct AggregateRequest {
+    #[serde(alias = "filters")]
+    filter: Option<Vec<FilterConditionJson>>,
+    #[serde(default)]
+    group_by: Vec<String>,
+    aggs: Vec<Aggregation>,
+}
+
+#[cfg(feature = "http")]
+async fn aggregate(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Json(req): Json<AggregateRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let filters = req
+        .filter
+        .map(parse_filters)
+        .transpose()
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let rows = state
+        .db
+        .aggregate(
+            &table,
+            filters.as_deref().unwrap_or(&[]),
+            &req.group_by,
+            &req.aggs,
+        )
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let out: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            serde_json::json!({
+                "group": row.group.into_iter().map(embeddb_value_to_json).collect::<Vec<_>>(),
+                "values": row.values.into_iter().map(embeddb_value_to_json).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    Ok(Json(out))
+}
+
+#[cfg(feature = "http")]
+async fn process_jobs(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(query): Query<ProcessJobsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let embedder = state.embedder.clone();
+    let processed = match query.limit {
+        Some(limit) => {
+            state
+                .db
+                .process_pending_jobs_with_limit(&table, embedder, limit)
+                .await
+        }
+        None => state.db.process_pending_jobs(&table, embedder).await,
+    }
+    .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "processed": processed })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct ProcessJobsQuery {
+    limit: Option<usize>,
+}
+
+#[cfg(feature = "http")]
+async fn process_all_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ProcessJobsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let processed = state
+        .db
+        .process_all_pending(state.embedder.clone(), limit)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "processed": processed })))
+}
+
+#[cfg(feature = "http")]
+#[derive(Debug, Deserialize)]
+struct RetryFailedQuery {
+    row_id: Option<u64>,
+}
+
+#[cfg(feature = "http")]
+async fn retry_failed_jobs(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+    Query(query): Query<RetryFailedQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let retried = state
+        .db
+        .retry_failed_jobs(&table, query.row_id)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "retried": retried })))
+}
+
+#[cfg(feature = "http")]
+async fn flush_table(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .db
+        .flush_table(&table)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+#[cfg(feature = "http")]
+async fn compact_table(
+    State(state): State<Arc<AppState>>,
+    Path(table): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let stats = state
+        .db
+        .compact_table(&table)
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "ok": true, "compaction": stats })))
+}
+
+#[cfg(feature = "http")]
+fn json_value_to_embeddb(value: serde_json::Value) -> Result<Value> {
+    Ok(match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(v) => Value::Bool(v),
+        serde_json::Value::Number(v) => {
+            if let Some(i) = v.as_i64() {
+                Value::Int(i)
+            } else if let Some(f) = v.as_f64() {
+                Value::Float(f)
+            } else {
+                return Err(anyhow!("invalid number"));
+            }
+        }
+        serde_json::Value::String(v) => Value::String(v),
+        serde_json::Value::Array(values) => {
+            let bytes: Result<Vec<u8>> = values
+                .into_iter()
+                .map(|item| {
+                    item.as_u64()
+                        .ok_or_else(|| anyhow!("bytes must be u8"))
+                        .and_then(|b| u8::try_from(b).map_err(|_| anyhow!("byte out of range")))
+                })
+                .collect();
+            Value::Bytes(bytes?)
+        }
+        serde_json::Value::Object(_) => return Err(anyhow!("nested objects not supported")),
+    })
+}
+
+#[cfg(feature = "http")]
+fn row_to_json(row: RowData) -> serde_json::Value {
+    let fields: serde_json::Map<String, serde_json::Value> = row
+        .fields
+        .into_iter()
+        .map(|(key, value)| (key, embeddb_value_to_json(value)))
+        .collect();
+    serde_json::json!({
+        "id": row.id,
+        "version": row.version,
+        "created_at_ms": row.created_at_ms,
+        "updated_at_ms": row.updated_at_ms,
+        "fields": fields
+    })
+}
+
+#[cfg(feature = "http")]
+fn embeddb_value_to_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Int(v) => serde_json::json!(v),
+        Value::Float(v) => serde_json::json!(v),
+        Value::Bool(v) => serde_json::json!(v),
+        Value::String(v) => serde_json::json!(v),
+        Value::Bytes(v) => serde_json::json!(v),
+        Value::Null => serde_json::Value::Null,
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod http_smoke_tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{HeaderMap, Request, StatusCode};
+    use embedder::LocalHashEmbedder;
+    use tempfile::tempdir;
+    use tower::util::ServiceExt;
+
+    /// A server's state over `db` with no keys, limits, namespaces, or snapshot directory; tests
+    /// override the fields they exercise.
+    pub(crate) fn test_state(db: impl Into<AsyncEmbedDb>) -> AppState {
+        AppState {
+            db: db.into(),
+            embedder: Arc::new(LocalHashEmbedder),
+            maintenance: None,
+            api_keys: None,
+            namespaces: None,
+            snapshots_dir: None,
+            rate_limits: None,
+        }
+    }
+
+    fn test_app(db: impl Into<AsyncEmbedDb>) -> Router {
+        build_router(Arc::new(test_state(db)))
+    }
+
+    /// Sends `body`, as JSON when given, and returns the response's status with its JSON body,
+    /// or `Null` when it has none.
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let req = Request::builder().method(method).uri(uri);
+        let req = match body {
+            Some(body) => req
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => req.body(Body::empty()),
+        };
+        let res = app
+            .clone()
+            .oneshot(req.expect("request"))
+            .await
+            .expect("response");
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    /// A server over a new database in `dir` whose `notes` table embeds its `title` and `body`,
+    /// holding row 1, "Hello"/"World", with its embedding job still pending.
+    async fn notes_app(dir: &std::path::Path) -> Router {
+        let db = EmbedDb::open(Config::new(dir.to_path_buf())).expect("open db");
+        let app = test_app(db);
+        let create = serde_json::json!({
+            "name": "notes",
+            "schema": {
+                "columns": [
+                    { "name": "title", "data_type": "String", "nullable": false },
+                    { "name": "body", "data_type": "String", "nullable": false }
+                ]
+            },
+            "embedding_fields": ["title", "body"]
+        });
+        let (status, _) = call(&app, "POST", "/tables", Some(create)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let insert = serde_json::json!({ "fields": { "title": "Hello", "body": "World" } });
+        let (status, row) = call(&app, "POST", "/tables/notes/rows", Some(insert)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(row["row_id"], 1);
+        app
+    }
+
+    /// Inserts a `notes` row titled `title` whose embedding is `vector` rather than a pending
+    /// job, and returns its id.
+    async fn insert_embedded(app: &Router, title: &str, vector: [f32; 4]) -> u64 {
+        let body = serde_json::json!({
+            "fields": { "title": title, "body": "" },
+            "embedding": vector
+        });
+        let (status, row) = call(app, "POST", "/tables/notes/rows", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        row["row_id"].as_u64().expect("row id")
+    }
+
+    /// The row ids of a JSON array of hits, in order.
+    fn hit_ids(hits: &serde_json::Value) -> Vec<u64> {
+        hits.as_array()
+            .expect("hits")
+            .iter()
+            .filter_map(|hit| hit["row_id"].as_u64())
+            .collect()
+    }
+
+    /// Adds embedded rows 2 "Hello"/"World", 3 "Hello"/"Mars", and 4 "Bye"/"World" to
+    /// `notes_app`'s table, ranked in that order for the query `[1, 0, 0, 0]`.
+    async fn insert_greetings(app: &Router) {
+        for (row_id, title, body, vector) in [
+            (2, "Hello", "World", [1.0, 0.0, 0.0, 0.0]),
+            (3, "Hello", "Mars", [0.9, 0.1, 0.0, 0.0]),
+            (4, "Bye", "World", [0.8, 0.2, 0.0, 0.0]),
+        ] {
+            let insert = serde_json::json!({
+                "fields": { "title": title, "body": body },
+                "embedding": vector
+            });
+            let (status, row) = call(app, "POST", "/tables/notes/rows", Some(insert)).await;
+            assert_eq!(status, StatusCode::CREATED);
+            assert_eq!(row["row_id"], row_id);
+        }
+    }
+
+    /// Embeds like `LocalHashEmbedder`, but fails every input that mentions "fail".
+    struct FlakyEmbedder;
+
+    impl Embedder for FlakyEmbedder {
+        fn embed(&self, input: &str) -> anyhow::Result<Vec<f32>> {
+            if input.contains("fail") {
+                anyhow::bail!("embedding refused");
+            }
+            LocalHashEmbedder.embed(input)
+        }
+    }
+
+    /// A server whose `jobs` table gives up on a job after two failures, holding rows 1 and 4
+    /// `ready`, 2 `failed` after two attempts, 3 `pending` after one, and 5 `pending` untried.
+    async fn jobs_app(dir: &std::path::Path) -> Router {
+        let db = EmbedDb::open(Config::new(dir.to_path_buf())).expect("open db");
+        let app = build_router(Arc::new(AppState {
+            embedder: Arc::new(FlakyEmbedder),
+            ..test_state(db)
+        }));
+        let create = serde_json::json!({
+            "name": "jobs",
+            "schema": { "columns": [{ "name": "title", "data_type": "String", "nullable": false }] },
+            "embedding_fields": ["title"],
+            "embedding_retry": { "max_attempts": 2, "base_ms": 0, "jitter": 0.0 }
+        });
+        let (status, _) = call(&app, "POST", "/tables", Some(create)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let insert = |title: &str| {
+            let body = serde_json::json!({ "fields": { "title": title } });
+            call(&app, "POST", "/tables/jobs/rows", Some(body))
+        };
+        for title in ["ok", "fail"] {
+            assert_eq!(insert(title).await.0, StatusCode::CREATED);
+        }
+        assert_eq!(
+            call(&app, "POST", "/tables/jobs/jobs/process", None)
+                .await
+                .0,
+            StatusCode::OK
+        );
+        for title in ["fail again", "ok"] {
+            assert_eq!(insert(title).await.0, StatusCode::CREATED);
+        }
+        assert_eq!(
+            call(&app, "POST", "/tables/jobs/jobs/process", None)
+                .await
+                .0,
+            StatusCode::OK
+        );
+        assert_eq!(insert("untried").await.0, StatusCode::CREATED);
+        app
+    }
+
+    /// Lists `jobs_app`'s jobs with `query`, returning the status, the listed row ids, and the
+    /// response headers.
+    async fn list_jobs(app: &Router, query: &str) -> (StatusCode, Vec<u64>, HeaderMap) {
+        let req = Request::builder()
+            .uri(format!("/tables/jobs/jobs?{query}"))
+            .body(Body::empty())
+            .expect("request");
+        let res = app.clone().oneshot(req).await.expect("response");
+        let (status, headers) = (res.status(), res.headers().clone());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let jobs: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+        let row_ids = jobs
+            .as_array()
+            .map(|jobs| {
+                jobs.iter()
+                    .filter_map(|job| job["row_id"].as_u64())
+                    .collect()
+            })
+            .unwrap_or_default();
+        (status, row_ids, headers)
+    }
+
+    #[tokio::test]
+    async fn http_smoke_flow() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let app = test_app(db);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/health")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/stats")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let create_body = serde_json::json!({
+            "name": "notes",
+            "schema": {
+                "columns": [
+                    { "name": "title", "data_type": "String", "nullable": false },
+                    { "name": "body", "data_type": "String", "nullable": false }
+                ]
+            },
+            "embedding_fields": ["title", "body"]
+        });
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/tables")
+                    .header("content-type", "application/json")
+                    .body(Body::from(create_body.to_string()))
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let insert_body = serde_json::json!({
+            "fields": {
+                "title": "Hello",
+                "body": "World"
+            }
+        });
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/tables/notes/rows")
+                    .header("content-type", "application/json")
+                    .body(Body::from(insert_body.to_string()))
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/tables/notes/jobs/process")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/tables/notes/jobs")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let jobs: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+
+        let attempts = jobs
+            .as_array()
+            .and_then(|v| v.first())
+            .and_then(|v| v.get("attempts"))
+            .and_then(|v| v.as_u64())
+            .expect("attempts");
+        assert_eq!(attempts, 0);
+
+        let search_body = serde_json::json!({
+            "query_text": "Hello\nWorld",
+            "k": 1
+        });
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/tables/notes/search-text")
+                    .header("content-type", "application/json")
+                    .body(Body::from(search_body.to_string()))
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let hits: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        let row_id = hits
+            .as_array()
+            .and_then(|v| v.first())
+            .and_then(|v| v.get("row_id"))
+            .and_then(|v| v.as_u64())
+            .expect("row_id");
+        assert_eq!(row_id, 1);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/tables/notes/flush")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/tables/notes/compact")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/checkpoint")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/tables/notes/rows/1")
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn batches_apply_every_op_or_none() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let batch = |expected_version: u64| {
+            serde_json::json!({ "ops": [
+                { "op": "insert", "table": "notes", "fields": { "title": "Batch", "body": "x" } },
+                { "op": "delete", "table": "notes", "row_id": 2 },
+                {
+                    "op": "update",
+                    "table": "notes",
+                    "row_id": 1,
+                    "fields": { "title": "Hello", "body": "Batched" },
+                    "expected_version": expected_version
+                }
+            ] })
+        };
+
+        // The stale update fails the whole batch, so the insert before it is not written either.
+        let (status, _) = call(&app, "POST", "/batch", Some(batch(2))).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = call(&app, "GET", "/tables/notes/rows/2", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (_, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(row["version"], 1);
+
+        let (status, body) = call(&app, "POST", "/batch", Some(batch(1))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["results"],
+            serde_json::json!([
+                { "row_id": 2, "version": 1 },
+                { "row_id": 2, "version": null },
+                { "row_id": 1, "version": 2 }
+            ])
+        );
+        let (_, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(row["fields"]["body"], "Batched");
+    }
+
+    #[tokio::test]
+    async fn version_checked_writes_conflict_when_stale() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let update = serde_json::json!({
+            "fields": { "title": "Hello", "body": "Again" },
+            "expected_version": 1
+        });
+
+        // The first update succeeds; retrying it with the same expected version conflicts.
+        let (status, updated) =
+            call(&app, "PUT", "/tables/notes/rows/1", Some(update.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["version"], 2);
+        let (status, _) = call(&app, "PUT", "/tables/notes/rows/1", Some(update)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, _) = call(
+            &app,
+            "DELETE",
+            "/tables/notes/rows/1?expected_version=1",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(row["version"], 2);
+        let (status, _) = call(
+            &app,
+            "DELETE",
+            "/tables/notes/rows/1?expected_version=2",
+            None,
+        )
+        .await;
+        assert!(status.is_success(), "{status}");
+    }
+
+    #[tokio::test]
+    async fn jobs_are_listed_and_drained_across_tables() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let create = serde_json::json!({
+            "name": "docs",
+            "schema": { "columns": [{ "name": "title", "data_type": "String", "nullable": false }] },
+            "embedding_fields": ["title"]
+        });
+        let (status, _) = call(&app, "POST", "/tables", Some(create)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let insert = serde_json::json!({ "fields": { "title": "Guide" } });
+        let (status, _) = call(&app, "POST", "/tables/docs/rows", Some(insert)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let keys = |jobs: &serde_json::Value| -> Vec<String> {
+            jobs.as_array()
+                .expect("jobs")
+                .iter()
+                .map(|job| format!("{}/{}", job["table"].as_str().unwrap_or(""), job["row_id"]))
+                .collect()
+        };
+        let (status, pending) = call(&app, "GET", "/jobs?status=pending", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(keys(&pending), ["docs/1", "notes/1"]);
+
+        // The limit caps the jobs processed across every table.
+        for expected in [1, 1, 0] {
+            let (status, drained) = call(&app, "POST", "/jobs/process?limit=1", None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(drained["processed"], expected);
+        }
+        let (_, ready) = call(&app, "GET", "/jobs?status=ready", None).await;
+        assert_eq!(keys(&ready), ["docs/1", "notes/1"]);
+        let (_, pending) = call(&app, "GET", "/jobs?status=pending", None).await;
+        assert_eq!(pending, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn patch_merges_fields_into_the_stored_row() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let patch = |fields: serde_json::Value| Some(serde_json::json!({ "fields": fields }));
+
+        let (status, patched) = call(
+            &app,
+            "PATCH",
+            "/tables/notes/rows/1",
+            patch(serde_json::json!({ "body": "Patched" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(patched["fields"]["title"], "Hello");
+        assert_eq!(patched["fields"]["body"], "Patched");
+        assert_eq!(patched["version"], 2);
+
+        for (uri, fields, expected) in [
+            (
+                "/tables/notes/rows/1",
+                serde_json::json!({ "body": 5 }),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/tables/notes/rows/1",
+                serde_json::json!({ "views": 5 }),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/tables/notes/rows/999",
+                serde_json::json!({ "body": "x" }),
+                StatusCode::NOT_FOUND,
+            ),
+        ] {
+            let (status, _) = call(&app, "PATCH", uri, patch(fields)).await;
+            assert_eq!(status, expected, "{uri}");
+        }
+        let (_, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(row["fields"]["body"], "Patched");
+        assert_eq!(row["version"], 2);
+    }
+
+    #[tokio::test]
+    async fn row_embeddings_can_be_read_and_replaced() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let (status, _) = call(&app, "POST", "/tables/notes/jobs/process", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, embedding) = call(&app, "GET", "/tables/notes/rows/1/embedding", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(embedding["status"], "Ready");
+        assert_eq!(embedding["vector"].as_array().map(Vec::len), Some(4));
+
+        for (vector, expected) in [
+            (serde_json::json!([0.0, 1.0, 0.0, 0.0]), StatusCode::OK),
+            (serde_json::json!([1.0]), StatusCode::BAD_REQUEST),
+        ] {
+            let body = serde_json::json!({ "vector": vector });
+            let (status, _) = call(&app, "PUT", "/tables/notes/rows/1/embedding", Some(body)).await;
+            assert_eq!(status, expected, "{vector}");
+        }
+        let (_, embedding) = call(&app, "GET", "/tables/notes/rows/1/embedding", None).await;
+        assert_eq!(embedding["vector"], serde_json::json!([0.0, 1.0, 0.0, 0.0]));
+        let search = serde_json::json!({ "query": [0.0, 1.0, 0.0, 0.0], "k": 1 });
+        let (status, hits) = call(&app, "POST", "/tables/notes/search", Some(search)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hits[0]["row_id"], 1, "{hits}");
+
+        let (status, _) = call(&app, "GET", "/tables/notes/rows/999/embedding", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reembed_moves_rows_to_a_new_model() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let (status, _) = call(&app, "POST", "/tables/notes/jobs/process", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let model = serde_json::json!({ "name": "mini", "version": "2" });
+        let (status, plan) = call(
+            &app,
+            "POST",
+            "/tables/notes/reembed",
+            Some(serde_json::json!({ "model": model })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(plan["affected_rows"], serde_json::json!([1]), "{plan}");
+
+        let (status, _) = call(&app, "POST", "/tables/notes/jobs/process", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, embedding) = call(&app, "GET", "/tables/notes/rows/1/embedding", None).await;
+        assert_eq!(embedding["status"], "Ready");
+        assert_eq!(embedding["model"], model);
+    }
+
+    #[tokio::test]
+    async fn count_returns_the_rows_matching_a_where_clause() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let insert = serde_json::json!({ "fields": { "title": "Other", "body": "World" } });
+        let (status, _) = call(&app, "POST", "/tables/notes/rows", Some(insert)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = call(&app, "DELETE", "/tables/notes/rows/1", None).await;
+        assert!(status.is_success(), "{status}");
+        let insert = serde_json::json!({ "fields": { "title": "Hello", "body": "Again" } });
+        let (status, _) = call(&app, "POST", "/tables/notes/rows", Some(insert)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        for (uri, status, expected) in [
+            ("/tables/notes/count", StatusCode::OK, Some(2)),
+            (
+                "/tables/notes/count?where=title%20%3D%20%27Hello%27",
+                StatusCode::OK,
+                Some(1),
+            ),
+            (
+                "/tables/notes/count?where=body%20%3D%20%27World%27",
+                StatusCode::OK,
+                Some(1),
+            ),
+            (
+                "/tables/notes/count?where=title%20%3D%20%27Missing%27",
+                StatusCode::OK,
+                Some(0),
+            ),
+            (
+                "/tables/notes/count?where=nope%20%3D%201",
+                StatusCode::BAD_REQUEST,
+                None,
+            ),
+        ] {
+            let (actual, body) = call(&app, "GET", uri, None).await;
+            assert_eq!(actual, status, "{uri}");
+            assert_eq!(body["count"].as_u64(), expected, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn rows_can_be_inserted_with_a_precomputed_embedding() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let fields = serde_json::json!({ "title": "Imported", "body": "ETL" });
+        let vector = serde_json::json!([1.0, 0.0, 0.0, 0.0]);
+
+        // The imported row is stored with its vector and never waits for a job.
+        let body = serde_json::json!({ "fields": fields, "embedding": vector });
+        let (status, created) = call(&app, "POST", "/tables/notes/rows", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let row_id = created["row_id"].as_u64().expect("row id");
+        let uri = format!("/tables/notes/rows/{row_id}/embedding");
+        let (status, embedding) = call(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(embedding["status"], "Ready");
+        assert_eq!(embedding["vector"], vector);
+
+        // The first vector set the table's dimension, and imported rows can't expire.
+        for body in [
+            serde_json::json!({ "fields": fields, "embedding": [1.0] }),
+            serde_json::json!({ "fields": fields, "embedding": vector, "ttl_seconds": 60 }),
+        ] {
+            let (status, _) = call(&app, "POST", "/tables/notes/rows", Some(body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        let (_, count) = call(&app, "GET", "/tables/notes/count", None).await;
+        assert_eq!(count["count"], 2);
+        let (_, pending) = call(&app, "GET", "/jobs?status=pending", None).await;
+        assert_eq!(pending.as_array().map(Vec::len), Some(1), "{pending}");
+        assert_eq!(pending[0]["row_id"], 1);
+    }
+
+    #[tokio::test]
+    async fn recommend_ranks_rows_near_the_positive_examples() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let liked = insert_embedded(&app, "liked", [1.0, 0.0, 0.0, 0.0]).await;
+        let near = insert_embedded(&app, "near", [0.7, 0.7, 0.0, 0.0]).await;
+        let off_axis = insert_embedded(&app, "off axis", [0.6, 0.0, 0.8, 0.0]).await;
+        let disliked = insert_embedded(&app, "disliked", [0.0, 1.0, 0.0, 0.0]).await;
+        insert_embedded(&app, "unrelated", [0.0, 0.0, 0.0, 1.0]).await;
+
+        // The examples themselves are never recommended.
+        let body = serde_json::json!({ "positive": [liked], "k": 2 });
+        let (status, hits) = call(&app, "POST", "/tables/notes/recommend", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [near, off_axis], "{hits}");
+
+        // A negative example pushes rows that share its direction down the ranking.
+        let body = serde_json::json!({ "positive": [liked], "negative": [disliked], "k": 2 });
+        let (status, hits) = call(&app, "POST", "/tables/notes/recommend", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [off_axis, near], "{hits}");
+
+        // Row 1's embedding job hasn't run, so it can't be an example.
+        for body in [
+            serde_json::json!({ "positive": [], "k": 2 }),
+            serde_json::json!({ "positive": [1], "k": 2 }),
+        ] {
+            let (status, _) = call(&app, "POST", "/tables/notes/recommend", Some(body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicates_cluster_only_near_identical_rows() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let original = insert_embedded(&app, "original", [1.0, 0.0, 0.0, 0.0]).await;
+        let copy = insert_embedded(&app, "copy", [0.99, 0.02, 0.0, 0.0]).await;
+        insert_embedded(&app, "distant", [0.0, 0.0, 1.0, 0.0]).await;
+
+        for body in [
+            serde_json::json!({ "threshold": 0.01 }),
+            serde_json::json!({ "threshold": 0.05, "metric": "L2" }),
+        ] {
+            let (status, found) = call(&app, "POST", "/tables/notes/duplicates", Some(body)).await;
+            assert_eq!(status, StatusCode::OK);
+            let clusters = found["clusters"].as_array().expect("clusters");
+            assert_eq!(clusters.len(), 1, "{found}");
+            assert_eq!(clusters[0]["row_ids"], serde_json::json!([original, copy]));
+            assert!(clusters[0]["max_distance"].as_f64().expect("distance") > 0.0);
+        }
+
+        let body = serde_json::json!({ "threshold": 0.000001 });
+        let (status, found) = call(&app, "POST", "/tables/notes/duplicates", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found["clusters"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn search_returns_only_included_and_never_excluded_ids() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let first = insert_embedded(&app, "first", [1.0, 0.0, 0.0, 0.0]).await;
+        let second = insert_embedded(&app, "second", [0.9, 0.1, 0.0, 0.0]).await;
+        let third = insert_embedded(&app, "third", [0.5, 0.5, 0.0, 0.0]).await;
+
+        for (ids, expected) in [
+            (serde_json::json!({}), vec![first, second, third]),
+            // Ids that aren't in the table are ignored.
+            (
+                serde_json::json!({ "include_ids": [first, third, 99] }),
+                vec![first, third],
+            ),
+            (serde_json::json!({ "include_ids": [] }), vec![]),
+            (
+                serde_json::json!({ "exclude_ids": [first] }),
+                vec![second, third],
+            ),
+            (
+                serde_json::json!({ "include_ids": [first, second], "exclude_ids": [first] }),
+                vec![second],
+            ),
+        ] {
+            let mut body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 10 });
+            body.as_object_mut()
+                .expect("object")
+                .extend(ids.as_object().expect("object").clone());
+            let (status, hits) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+            assert_eq!(status, StatusCode::OK, "{ids}");
+            assert_eq!(hit_ids(&hits), expected, "{ids}");
+        }
+    }
+
+    #[tokio::test]
+    async fn flush_sweeps_rows_whose_ttl_has_passed() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        let mut uris = Vec::new();
+        for ttl_seconds in [0, 3600] {
+            let body = serde_json::json!({
+                "fields": { "title": "Session", "body": "Scratch" },
+                "ttl_seconds": ttl_seconds
+            });
+            let (status, row) = call(&app, "POST", "/tables/notes/rows", Some(body)).await;
+            assert_eq!(status, StatusCode::CREATED);
+            uris.push(format!("/tables/notes/rows/{}", row["row_id"]));
+        }
+
+        let (status, _) = call(&app, "POST", "/tables/notes/flush", None).await;
+        assert_eq!(status, StatusCode::OK);
+        // Only the row inserted with a zero TTL is gone; row 1 never had one.
+        for (uri, expected) in [
+            (uris[0].as_str(), StatusCode::NOT_FOUND),
+            (uris[1].as_str(), StatusCode::OK),
+            ("/tables/notes/rows/1", StatusCode::OK),
+        ] {
+            let (status, _) = call(&app, "GET", uri, None).await;
+            assert_eq!(status, expected, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn search_where_clauses_filter_hits() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+
+        for (clause, expected) in [
+            (r#"title = "Hello" AND body CONTAINS 'orl'"#, vec![2]),
+            (r#"title != "Hello""#, vec![4]),
+            ("body = 'World'", vec![2, 4]),
+            ("title = 'Missing'", vec![]),
+        ] {
+            let body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "where": clause });
+            let (status, hits) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+            assert_eq!(status, StatusCode::OK, "{clause}");
+            assert_eq!(hit_ids(&hits), expected, "{clause}");
+        }
+
+        // OR isn't supported, and unknown columns are rejected rather than matching nothing.
+        for clause in ["title = 'Hello' OR body = 'x'", "nope = 1"] {
+            let body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "where": clause });
+            let (status, _) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{clause}");
+        }
+    }
+
+    #[tokio::test]
+    async fn search_filters_match_substrings_with_contains() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+
+        // `filters` is accepted as an alias of `filter`.
+        for key in ["filter", "filters"] {
+            for (needle, expected) in [("orl", vec![2, 4]), ("ar", vec![3]), ("nope", vec![])] {
+                let body = serde_json::json!({
+                    "query": [1.0, 0.0, 0.0, 0.0],
+                    key: [{ "column": "body", "op": "Contains", "value": needle }]
+                });
+                let (status, hits) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+                assert_eq!(status, StatusCode::OK, "{key} {needle}");
+                assert_eq!(hit_ids(&hits), expected, "{key} {needle}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn search_hits_carry_row_fields_on_request() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+        let body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 2 });
+
+        let uri = "/tables/notes/search?include=fields";
+        let (status, hits) = call(&app, "POST", uri, Some(body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [2, 3], "{hits}");
+        assert_eq!(
+            hits[0]["fields"],
+            serde_json::json!({ "title": "Hello", "body": "World" })
+        );
+        assert_eq!(
+            hits[1]["fields"],
+            serde_json::json!({ "title": "Hello", "body": "Mars" })
+        );
+        assert!(hits[0]["distance"].is_number());
+
+        let (_, hits) = call(&app, "POST", "/tables/notes/search", Some(body.clone())).await;
+        assert!(hits[0].get("fields").is_none(), "{hits}");
+        let uri = "/tables/notes/search?include=vectors";
+        let (status, _) = call(&app, "POST", uri, Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn similarity_scores_accompany_distances() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+
+        for (metric, score_of) in [
+            (
+                "Cosine",
+                (|distance| 1.0 - distance / 2.0) as fn(f64) -> f64,
+            ),
+            ("L2", |distance| 1.0 / (1.0 + distance)),
+        ] {
+            let body = serde_json::json!({
+                "query": [1.0, 0.0, 0.0, 0.0],
+                "k": 3,
+                "metric": metric,
+                "score_mode": "Similarity"
+            });
+            let (status, hits) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+            assert_eq!(status, StatusCode::OK, "{metric}");
+            // Scores leave the ranking alone, and the exact match scores 1.
+            assert_eq!(hit_ids(&hits), [2, 3, 4], "{metric}: {hits}");
+            assert_eq!(hits[0]["score"].as_f64(), Some(1.0), "{metric}: {hits}");
+            for hit in hits.as_array().expect("hits") {
+                let distance = hit["distance"].as_f64().expect("distance");
+                let score = hit["score"].as_f64().expect("score");
+                assert!((score - score_of(distance)).abs() < 1e-6, "{metric}: {hit}");
+            }
+        }
+
+        let body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 3 });
+        let (_, hits) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+        assert!(hits[0].get("score").is_none(), "{hits}");
+    }
+
+    #[tokio::test]
+    async fn search_pages_follow_the_next_after_cursor() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+        let page = |after: Option<String>| {
+            let mut body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 2 });
+            if let Some(after) = after {
+                body["after"] = serde_json::json!(after);
+            }
+            let req = Request::builder()
+                .method("POST")
+                .uri("/tables/notes/search")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request");
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.expect("response");
+                assert_eq!(res.status(), StatusCode::OK);
+                let next = res
+                    .headers()
+                    .get("x-next-after")
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                    .await
+                    .expect("body");
+                let hits: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+                (hit_ids(&hits), next)
+            }
+        };
 
-#[cfg(feature = "http")]
-#[derive(Debug, Deserialize)]
-struct RetryFailedQuery {
-    row_id: Option<u64>,
-}
+        // Only a full page carries a cursor.
+        let (ids, next) = page(None).await;
+        assert_eq!(ids, [2, 3]);
+        let (ids, next) = page(Some(next.expect("a full page carries a cursor"))).await;
+        assert_eq!(ids, [4]);
+        assert_eq!(next, None);
 
-#[cfg(feature = "http")]
-async fn retry_failed_jobs(
-    State(state): State<Arc<AppState>>,
-    Path(table): Path<String>,
-    Query(query): Query<RetryFailedQuery>,
-) -> Result<impl IntoResponse, ApiError> {
-    let retried = state
-        .db
-        .retry_failed_jobs(&table, query.row_id)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    Ok(Json(serde_json::json!({ "retried": retried })))
-}
+        let body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 2, "offset": 1 });
+        let (status, hits) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [3, 4]);
+        let body = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "after": "garbage" });
+        let (status, _) = call(&app, "POST", "/tables/notes/search", Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
 
-#[cfg(feature = "http")]
-async fn flush_table(
-    State(state): State<Arc<AppState>>,
-    Path(table): Path<String>,
-) -> Result<impl IntoResponse, ApiError> {
-    state
-        .db
-        .flush_table(&table)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    Ok(Json(serde_json::json!({ "ok": true })))
-}
+    #[tokio::test]
+    async fn job_listings_filter_by_status_and_page_by_row_id() {
+        let dir = tempdir().expect("tempdir");
+        let app = jobs_app(dir.path()).await;
 
-#[cfg(feature = "http")]
-async fn compact_table(
-    State(state): State<Arc<AppState>>,
-    Path(table): Path<String>,
-) -> Result<impl IntoResponse, ApiError> {
-    state
-        .db
-        .compact_table(&table)
-        .map_err(|err| ApiError::bad_request(err.to_string()))?;
-    Ok(Json(serde_json::json!({ "ok": true })))
-}
+        for (query, expected) in [
+            ("", vec![1, 2, 3, 4, 5]),
+            ("status=ready", vec![1, 4]),
+            ("status=failed", vec![2]),
+            ("status=pending", vec![3, 5]),
+        ] {
+            let (status, row_ids, _) = list_jobs(&app, query).await;
+            assert_eq!(status, StatusCode::OK, "{query}");
+            assert_eq!(row_ids, expected, "{query}");
+        }
+        let (status, _, _) = list_jobs(&app, "status=bogus").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
 
-#[cfg(feature = "http")]
-fn json_value_to_embeddb(value: serde_json::Value) -> Result<Value> {
-    Ok(match value {
-        serde_json::Value::Null => Value::Null,
-        serde_json::Value::Bool(v) => Value::Bool(v),
-        serde_json::Value::Number(v) => {
-            if let Some(i) = v.as_i64() {
-                Value::Int(i)
-            } else if let Some(f) = v.as_f64() {
-                Value::Float(f)
-            } else {
-                return Err(anyhow!("invalid number"));
-            }
+        // Each full page names the row id the next one starts after.
+        let mut pages = Vec::new();
+        let mut query = "limit=2".to_string();
+        loop {
+            let (status, row_ids, headers) = list_jobs(&app, &query).await;
+            assert_eq!(status, StatusCode::OK, "{query}");
+            pages.push(row_ids);
+            let Some(next) = headers.get("x-next-after") else {
+                break;
+            };
+            query = format!("limit=2&after={}", next.to_str().expect("header"));
         }
-        serde_json::Value::String(v) => Value::String(v),
-        serde_json::Value::Array(values) => {
-            let bytes: Result<Vec<u8>> = values
-                .into_iter()
-                .map(|item| {
-                    item.as_u64()
-                        .ok_or_else(|| anyhow!("bytes must be u8"))
-                        .and_then(|b| u8::try_from(b).map_err(|_| anyhow!("byte out of range")))
+        assert_eq!(pages, [vec![1, 2], vec![3, 4], vec![5]]);
+        let (_, row_ids, headers) = list_jobs(&app, "status=pending&limit=1").await;
+        assert_eq!(row_ids, [3]);
+        assert_eq!(headers["x-next-after"], "3");
+    }
+
+    #[tokio::test]
+    async fn job_listings_sort_page_by_offset_and_count_matches() {
+        let dir = tempdir().expect("tempdir");
+        let app = jobs_app(dir.path()).await;
+
+        // Ties are broken by ascending row id whatever the order, and the total counts every
+        // job matching the status before `offset` and `limit` apply.
+        for (query, expected, total) in [
+            ("sort=attempts&order=desc", vec![2, 3, 1, 4, 5], "5"),
+            ("sort=attempts", vec![1, 4, 5, 3, 2], "5"),
+            ("sort=attempts&order=desc&offset=1&limit=2", vec![3, 1], "5"),
+            ("status=pending&sort=attempts&order=desc", vec![3, 5], "2"),
+            ("order=desc&limit=2", vec![5, 4], "5"),
+            ("sort=attempts&offset=9", vec![], "5"),
+        ] {
+            let (status, row_ids, headers) = list_jobs(&app, query).await;
+            assert_eq!(status, StatusCode::OK, "{query}");
+            assert_eq!(row_ids, expected, "{query}");
+            assert_eq!(headers["x-total-count"], total, "{query}");
+        }
+        // Cursors only follow row id order.
+        let (status, _, _) = list_jobs(&app, "sort=attempts&after=1").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn sparse_vectors_rank_rows_by_dot_product() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+        for (row_id, sparse) in [
+            (
+                2,
+                serde_json::json!({ "indices": [3, 7], "values": [0.5, 1.0] }),
+            ),
+            (3, serde_json::json!({ "indices": [7], "values": [2.0] })),
+            (4, serde_json::json!({ "indices": [1], "values": [1.0] })),
+        ] {
+            let uri = format!("/tables/notes/rows/{row_id}/sparse");
+            let (status, _) = call(&app, "PUT", &uri, Some(sparse.clone())).await;
+            assert_eq!(status, StatusCode::OK);
+            let (status, stored) = call(&app, "GET", &uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(stored, sparse);
+        }
+        let bad = serde_json::json!({ "indices": [1, 2], "values": [1.0] });
+        let (status, _) = call(&app, "PUT", "/tables/notes/rows/2/sparse", Some(bad)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = serde_json::json!({ "sparse": { "indices": [7], "values": [2.0] }, "k": 3 });
+        let (status, hits) = call(&app, "POST", "/tables/notes/search-sparse", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [3, 2], "{hits}");
+        assert_eq!(hits[0]["distance"].as_f64(), Some(-4.0));
+        assert_eq!(hits[1]["distance"].as_f64(), Some(-2.0));
+
+        // With a dense query too, row 4 leads both rankings' fusion despite being the dense
+        // ranking's last.
+        let body = serde_json::json!({
+            "sparse": { "indices": [1], "values": [1.0] },
+            "query": [1.0, 0.0, 0.0, 0.0],
+            "k": 3
+        });
+        let (status, hits) = call(&app, "POST", "/tables/notes/search-sparse", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [4, 2, 3], "{hits}");
+    }
+
+    #[tokio::test]
+    async fn hybrid_search_fuses_keyword_and_vector_rankings() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+        let search = |fusion: Option<serde_json::Value>| {
+            let mut body = serde_json::json!({
+                "query_text": "mars",
+                "query": [1.0, 0.0, 0.0, 0.0],
+                "k": 3
+            });
+            if let Some(fusion) = fusion {
+                body["fusion"] = fusion;
+            }
+            call(&app, "POST", "/tables/notes/search-hybrid", Some(body))
+        };
+
+        // Only row 3 mentions Mars, which lifts it over row 2, the nearer vector.
+        let (status, hits) = search(None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [3, 2, 4], "{hits}");
+        let fused = |hit: &serde_json::Value| hit["distance"].as_f64().expect("distance");
+        assert!(fused(&hits[0]) < fused(&hits[1]), "{hits}");
+
+        // Without keyword weight, the vector ranking stands.
+        let vector_only = serde_json::json!({
+            "WeightedRank": { "keyword_weight": 0.0, "vector_weight": 1.0 }
+        });
+        let (status, hits) = search(Some(vector_only)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [2, 3, 4], "{hits}");
+    }
+
+    #[tokio::test]
+    async fn explain_reports_how_a_search_would_run() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+
+        // Row 1's embedding is still pending, so it isn't a candidate.
+        for (body, candidates) in [
+            (
+                serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 1 }),
+                3,
+            ),
+            (
+                serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "include_ids": [2, 4] }),
+                2,
+            ),
+        ] {
+            let uri = "/tables/notes/search/explain";
+            let (status, explain) = call(&app, "POST", uri, Some(body)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                explain,
+                serde_json::json!({
+                    "index": "Flat",
+                    "degraded": false,
+                    "candidates": candidates,
+                    "rescore_candidates": null
                 })
-                .collect();
-            Value::Bytes(bytes?)
+            );
         }
-        serde_json::Value::Object(_) => return Err(anyhow!("nested objects not supported")),
-    })
-}
 
-#[cfg(feature = "http")]
-fn embeddb_value_to_json(value: Value) -> serde_json::Value {
-    match value {
-        Value::Int(v) => serde_json::json!(v),
-        Value::Float(v) => serde_json::json!(v),
-        Value::Bool(v) => serde_json::json!(v),
-        Value::String(v) => serde_json::json!(v),
-        Value::Bytes(v) => serde_json::json!(v),
-        Value::Null => serde_json::Value::Null,
+        let body = serde_json::json!({ "query": [1.0, 0.0], "k": 1 });
+        let (status, _) = call(&app, "POST", "/tables/notes/search/explain", Some(body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
     }
-}
 
-#[cfg(all(test, feature = "http"))]
-mod http_smoke_tests {
-    use super::*;
+    #[tokio::test]
+    async fn similar_rows_rank_neighbours_of_a_row() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
 
-    use axum::body::Body;
-    use axum::http::{Request, StatusCode};
-    use tempfile::tempdir;
-    use tower::util::ServiceExt;
+        for (body, expected) in [
+            // The body is optional, and the row itself is left out by default.
+            (None, vec![3, 4]),
+            (Some(serde_json::json!({ "k": 1 })), vec![3]),
+            (
+                Some(serde_json::json!({ "k": 2, "exclude_self": false })),
+                vec![2, 3],
+            ),
+        ] {
+            let (status, hits) = call(&app, "POST", "/tables/notes/rows/2/similar", body).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(hit_ids(&hits), expected, "{hits}");
+        }
+        let (status, hits) = call(&app, "POST", "/tables/notes/rows/4/similar", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(hit_ids(&hits), [3, 2], "{hits}");
+
+        // Row 1 has no embedding yet.
+        let (status, _) = call(&app, "POST", "/tables/notes/rows/1/similar", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
 
     #[tokio::test]
-    async fn http_smoke_flow() {
+    async fn alter_adds_renames_and_drops_columns() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        // Row 1 is read back from an SST file written before the schema changed.
+        let (status, _) = call(&app, "POST", "/tables/notes/flush", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let alter = |op: serde_json::Value| call(&app, "POST", "/tables/notes/alter", Some(op));
+        let columns = |table: &serde_json::Value| -> Vec<String> {
+            table["schema"]["columns"]
+                .as_array()
+                .expect("columns")
+                .iter()
+                .filter_map(|column| column["name"].as_str().map(str::to_string))
+                .collect()
+        };
+
+        let (status, table) = alter(serde_json::json!({
+            "op": "add_column",
+            "column": { "name": "views", "data_type": "Int", "nullable": false },
+            "default": 0
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(columns(&table), ["title", "body", "views"], "{table}");
+        let (_, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(row["fields"]["views"], 0);
+
+        let (status, table) = alter(serde_json::json!({
+            "op": "rename_column",
+            "from": "views",
+            "to": "reads"
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(columns(&table), ["title", "body", "reads"], "{table}");
+        let (_, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(row["fields"]["reads"], 0);
+        assert!(row["fields"].get("views").is_none(), "{row}");
+
+        let (status, table) =
+            alter(serde_json::json!({ "op": "drop_column", "name": "reads" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(columns(&table), ["title", "body"], "{table}");
+        let (_, row) = call(&app, "GET", "/tables/notes/rows/1", None).await;
+        assert_eq!(
+            row["fields"],
+            serde_json::json!({ "title": "Hello", "body": "World" })
+        );
+
+        // Existing rows need a value for a required column, and the embedding spec reads `body`.
+        for op in [
+            serde_json::json!({
+                "op": "add_column",
+                "column": { "name": "views", "data_type": "Int", "nullable": false }
+            }),
+            serde_json::json!({ "op": "drop_column", "name": "body" }),
+            serde_json::json!({ "op": "rename_column", "from": "body", "to": "content" }),
+        ] {
+            let (status, _) = alter(op.clone()).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{op}");
+        }
+    }
+
+    #[tokio::test]
+    async fn embedding_scroll_pages_follow_the_next_cursor() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+
+        let (status, page) = call(&app, "GET", "/tables/notes/embeddings?limit=2", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let items = page["items"].as_array().expect("items");
+        assert_eq!(items[0]["row_id"], 1);
+        assert_eq!(items[0]["status"], "Pending");
+        assert!(items[0]["vector"].is_null());
+        assert_eq!(items[1]["row_id"], 2);
+        assert_eq!(items[1]["status"], "Ready");
+        assert_eq!(items[1]["vector"], serde_json::json!([1.0, 0.0, 0.0, 0.0]));
+        let cursor = page["next_cursor"]
+            .as_u64()
+            .expect("a full page carries a cursor");
+
+        let uri = format!("/tables/notes/embeddings?limit=2&cursor={cursor}");
+        let (status, page) = call(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<u64> = page["items"]
+            .as_array()
+            .expect("items")
+            .iter()
+            .map(|item| item["row_id"].as_u64().expect("row_id"))
+            .collect();
+        assert_eq!(ids, [3, 4]);
+        assert!(page["items"]
+            .as_array()
+            .expect("items")
+            .iter()
+            .all(|item| item["status"] == "Ready"));
+
+        assert!(page["next_cursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn row_scan_pages_follow_the_next_cursor() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+        let (status, _) = call(&app, "DELETE", "/tables/notes/rows/3", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids = |page: &serde_json::Value| -> Vec<u64> {
+            page["items"]
+                .as_array()
+                .expect("items")
+                .iter()
+                .map(|item| item["id"].as_u64().expect("id"))
+                .collect()
+        };
+
+        let (status, page) = call(&app, "GET", "/tables/notes/rows?limit=2", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&page), [1, 2]);
+        assert_eq!(page["items"][0]["fields"]["title"], "Hello");
+        assert_eq!(page["items"][1]["fields"]["body"], "World");
+        let cursor = page["next_cursor"].as_u64().expect("more rows follow");
+
+        // The deleted row 3 is skipped, and the last page ends the scan.
+        let uri = format!("/tables/notes/rows?limit=2&after={cursor}");
+        let (status, page) = call(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&page), [4]);
+        assert_eq!(page["items"][0]["fields"]["title"], "Bye");
+        assert!(page["next_cursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn aggregate_counts_rows_per_group() {
+        let dir = tempdir().expect("tempdir");
+        let app = notes_app(dir.path()).await;
+        insert_greetings(&app).await;
+
+        let body = serde_json::json!({ "group_by": ["title"], "aggs": [{ "op": "Count" }] });
+        let (status, groups) = call(&app, "POST", "/tables/notes/aggregate", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            groups,
+            serde_json::json!([
+                { "group": ["Bye"], "values": [1] },
+                { "group": ["Hello"], "values": [3] }
+            ])
+        );
+
+        let body = serde_json::json!({
+            "group_by": ["title"],
+            "aggs": [{ "op": "Count" }],
+            "filter": [{ "column": "body", "op": "Eq", "value": "World" }]
+        });
+        let (status, groups) = call(&app, "POST", "/tables/notes/aggregate", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            groups,
+            serde_json::json!([
+                { "group": ["Bye"], "values": [1] },
+                { "group": ["Hello"], "values": [2] }
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_endpoints_work() {
         let dir = tempdir().expect("tempdir");
         let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
-        let app = build_router(Arc::new(AppState { db }));
+        let app = test_app(db);
 
-        let res = app
+        let create_body = serde_json::json!({
+            "name": "notes",
+            "schema": {
+                "columns": [
+                    { "name": "title", "data_type": "String", "nullable": false }
+                ]
+            },
+            "embedding_fields": ["title"]
+        });
+        let _ = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("GET")
-                    .uri("/health")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/tables")
+                    .header("content-type", "application/json")
+                    .body(Body::from(create_body.to_string()))
                     .expect("request"),
             )
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::OK);
 
+        let snapshot_dir = tempdir().expect("snapshot dir");
+        let export_body =
+            serde_json::json!({ "dest_dir": snapshot_dir.path().join("snapshot-copy") });
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("GET")
-                    .uri("/stats")
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/snapshot/export")
+                    .header("content-type", "application/json")
+                    .body(Body::from(export_body.to_string()))
                     .expect("request"),
             )
             .await
             .expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let exported: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        assert!(
+            exported
+                .get("files_copied")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+                >= 1
+        );
 
-        let create_body = serde_json::json!({
-            "name": "notes",
-            "schema": {
-                "columns": [
-                    { "name": "title", "data_type": "String", "nullable": false },
-                    { "name": "body", "data_type": "String", "nullable": false }
-                ]
-            },
-            "embedding_fields": ["title", "body"]
+        let restore_target = snapshot_dir.path().join("restore-target");
+        let restore_body = serde_json::json!({
+            "snapshot_dir": snapshot_dir.path().join("snapshot-copy"),
+            "data_dir": restore_target
         });
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/tables")
+                    .uri("/snapshot/restore")
                     .header("content-type", "application/json")
-                    .body(Body::from(create_body.to_string()))
+                    .body(Body::from(restore_body.to_string()))
                     .expect("request"),
             )
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
 
-        let insert_body = serde_json::json!({
-            "fields": {
-                "title": "Hello",
-                "body": "World"
+    #[tokio::test]
+    async fn rate_limited_requests_get_429_with_retry_after() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().join("data"))).expect("open db");
+        let limits = RateLimits::from_lookup(
+            |name| (name == "EMBEDDB_RATE_LIMIT_RPS").then(|| "0.1".to_string()),
+            false,
+        )
+        .expect("limits");
+        let app = build_router(Arc::new(AppState {
+            rate_limits: Some(Arc::new(limits)),
+            ..test_state(Arc::new(db))
+        }));
+        let get = |uri: &str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .body(Body::empty())
+                    .expect("request"),
+            )
+        };
+
+        assert_eq!(
+            get("/stats").await.expect("response").status(),
+            StatusCode::OK
+        );
+        let res = get("/tables").await.expect("response");
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            res.headers()
+                .get(header::RETRY_AFTER)
+                .map(HeaderValue::as_bytes),
+            Some(&b"10"[..])
+        );
+        assert_eq!(
+            get("/health").await.expect("response").status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn managed_snapshots_are_created_and_listed() {
+        let dir = tempdir().expect("tempdir");
+        let db = Arc::new(EmbedDb::open(Config::new(dir.path().join("data"))).expect("open db"));
+        let state = |snapshots_dir| {
+            Arc::new(AppState {
+                snapshots_dir,
+                ..test_state(db.clone())
+            })
+        };
+        let send = |app: Router, method: &str, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri("/snapshots")
+                .header("content-type", "application/json");
+            let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+            async move {
+                let res = app
+                    .oneshot(request.body(body).expect("request"))
+                    .await
+                    .expect("response");
+                let status = res.status();
+                let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                    .await
+                    .expect("body");
+                let json: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+                (status, json)
             }
+        };
+
+        let disabled = build_router(state(None));
+        let (status, _) = send(disabled, "GET", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let root = dir.path().join("snapshots");
+        let app = build_router(state(Some(root.clone())));
+        let (status, listed) = send(app.clone(), "GET", None).await;
+        assert_eq!(
+            (status, listed["snapshots"].clone()),
+            (StatusCode::OK, serde_json::json!([]))
+        );
+
+        let named = serde_json::json!({ "name": "nightly" });
+        let (status, created) = send(app.clone(), "POST", Some(named.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["name"], "nightly");
+        assert!(created["files_copied"].as_u64().expect("files") >= 1);
+        assert!(root.join("nightly").join("wal.log").exists());
+        let (status, _) = send(app.clone(), "POST", Some(named)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let bad = serde_json::json!({ "name": "../escape" });
+        let (status, _) = send(app.clone(), "POST", Some(bad)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, created) = send(app.clone(), "POST", None).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let generated = created["name"].as_str().expect("name").to_string();
+        assert!(generated.starts_with("snapshot-"), "{generated}");
+
+        let (status, listed) = send(app, "GET", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut names: Vec<&str> = listed["snapshots"]
+            .as_array()
+            .expect("snapshots")
+            .iter()
+            .map(|snapshot| snapshot["name"].as_str().expect("name"))
+            .collect();
+        names.sort();
+        assert_eq!(names, ["nightly", generated.as_str()]);
+        assert!(listed["snapshots"][0]["bytes"].as_u64().expect("bytes") > 0);
+    }
+
+    #[tokio::test]
+    async fn api_keys_gate_requests_by_scope() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let keys = ApiKeys::from_lookup(|name| {
+            (name == "EMBEDDB_API_KEYS").then(|| "admin,reader:read".to_string())
+        })
+        .expect("keys");
+        let app = build_router(Arc::new(AppState {
+            api_keys: Some(Arc::new(keys)),
+            ..test_state(db)
+        }));
+
+        let create = serde_json::json!({
+            "name": "notes",
+            "schema": { "columns": [{ "name": "title", "data_type": "String", "nullable": false }] },
+            "embedding_fields": ["title"]
         });
+        let search = serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 1 });
+        let cases = [
+            ("GET", "/health", None, None, StatusCode::OK),
+            ("GET", "/tables", None, None, StatusCode::UNAUTHORIZED),
+            (
+                "GET",
+                "/tables",
+                Some("Bearer nope"),
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "POST",
+                "/tables",
+                Some("Bearer reader"),
+                Some(&create),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "POST",
+                "/tables",
+                Some("Bearer admin"),
+                Some(&create),
+                StatusCode::CREATED,
+            ),
+            (
+                "GET",
+                "/tables",
+                Some("Bearer reader"),
+                None,
+                StatusCode::OK,
+            ),
+            (
+                "POST",
+                "/tables/notes/search",
+                Some("Bearer reader"),
+                Some(&search),
+                StatusCode::OK,
+            ),
+        ];
+        for (method, uri, auth, body, expected) in cases {
+            let mut req = Request::builder().method(method).uri(uri);
+            if let Some(auth) = auth {
+                req = req.header("authorization", auth);
+            }
+            let req = match body {
+                Some(body) => req
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string())),
+                None => req.body(Body::empty()),
+            };
+            let res = app
+                .clone()
+                .oneshot(req.expect("request"))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), expected, "{method} {uri} with {auth:?}");
+        }
+
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/tables/notes/rows")
-                    .header("content-type", "application/json")
-                    .body(Body::from(insert_body.to_string()))
+                    .method("GET")
+                    .uri("/tables")
+                    .header("x-api-key", "reader")
+                    .body(Body::empty())
                     .expect("request"),
             )
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn audit_log_records_the_api_key_holder() {
+        let dir = tempdir().expect("tempdir");
+        let config = Config::new(dir.path().to_path_buf()).with_audit_log(AuditConfig::default());
+        let db = EmbedDb::open(config).expect("open db");
+        let keys = ApiKeys::from_lookup(|name| {
+            (name == "EMBEDDB_API_KEYS").then(|| "admin,reader:read".to_string())
+        })
+        .expect("keys");
+        let app = build_router(Arc::new(AppState {
+            api_keys: Some(Arc::new(keys)),
+            ..test_state(db)
+        }));
+
+        let create = serde_json::json!({
+            "name": "notes",
+            "schema": { "columns": [{ "name": "title", "data_type": "String", "nullable": false }] }
+        });
+        let insert = serde_json::json!({ "fields": { "title": "Hello" } });
+        for (uri, body) in [("/tables", &create), ("/tables/notes/rows", &insert)] {
+            let res = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(uri)
+                        .header("authorization", "Bearer admin")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .expect("request"),
+                )
+                .await
+                .expect("response");
+            assert!(res.status().is_success(), "POST {uri}: {}", res.status());
+        }
 
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/tables/notes/jobs/process")
+                    .method("GET")
+                    .uri("/audit?limit=1")
+                    .header("authorization", "Bearer reader")
                     .body(Body::empty())
                     .expect("request"),
             )
             .await
             .expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let page: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        assert_eq!(page["items"][0]["op"], "CreateTable");
+        assert_eq!(page["items"][0]["actor"], "key #1");
+        let cursor = page["next_cursor"].as_u64().expect("cursor");
 
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
                     .method("GET")
-                    .uri("/tables/notes/jobs")
+                    .uri(format!("/audit?after={cursor}"))
+                    .header("authorization", "Bearer reader")
                     .body(Body::empty())
                     .expect("request"),
             )
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::OK);
         let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
             .await
             .expect("body");
-        let jobs: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
-        let attempts = jobs
-            .as_array()
-            .and_then(|v| v.first())
-            .and_then(|v| v.get("attempts"))
-            .and_then(|v| v.as_u64())
-            .expect("attempts");
-        assert_eq!(attempts, 0);
+        let page: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        let items = page["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["op"], "Insert");
+        assert_eq!(items[0]["table"], "notes");
+        assert_eq!(items[0]["actor"], "key #1");
+        assert!(page["next_cursor"].is_null());
+    }
 
-        let search_body = serde_json::json!({
-            "query_text": "Hello\nWorld",
-            "k": 1
-        });
-        let res = app
-            .clone()
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/tables/notes/search-text")
+    #[tokio::test]
+    async fn changes_stream_committed_writes_as_server_sent_events() {
+        use tokio_stream::StreamExt;
+
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let app = test_app(db);
+        let send = |method: &str, uri: &str, body: Option<serde_json::Value>| {
+            let req = Request::builder().method(method).uri(uri);
+            let req = match body {
+                Some(body) => req
                     .header("content-type", "application/json")
-                    .body(Body::from(search_body.to_string()))
-                    .expect("request"),
-            )
+                    .body(Body::from(body.to_string())),
+                None => req.body(Body::empty()),
+            };
+            app.clone().oneshot(req.expect("request"))
+        };
+        let create = serde_json::json!({
+            "name": "notes",
+            "schema": { "columns": [{ "name": "title", "data_type": "String", "nullable": false }] }
+        });
+        let res = send("POST", "/tables", Some(create))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let res = send("GET", "/tables/missing/changes", None)
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = send("GET", "/tables/notes/changes?after=5", None)
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::GONE);
+
+        let res = send("GET", "/tables/notes/changes", None)
             .await
             .expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()["content-type"]
+                .to_str()
+                .expect("content type"),
+            "text/event-stream"
+        );
+        let mut events = res.into_body().into_data_stream();
 
-        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+        let insert = serde_json::json!({ "fields": { "title": "Hello" } });
+        let res = send("POST", "/tables/notes/rows", Some(insert))
             .await
-            .expect("body");
-        let hits: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
-        let row_id = hits
-            .as_array()
-            .and_then(|v| v.first())
-            .and_then(|v| v.get("row_id"))
-            .and_then(|v| v.as_u64())
-            .expect("row_id");
-        assert_eq!(row_id, 1);
+            .expect("response");
+        assert!(res.status().is_success());
+        let batch = serde_json::json!({ "ops": [
+            { "op": "insert", "table": "notes", "fields": { "title": "a" } },
+            { "op": "insert", "table": "notes", "fields": { "title": "b" } }
+        ] });
+        let res = send("POST", "/batch", Some(batch)).await.expect("response");
+        assert!(res.status().is_success());
+        let res = send("DELETE", "/tables/notes/rows/1", None)
+            .await
+            .expect("response");
+        assert!(res.status().is_success());
+
+        let mut text = String::new();
+        while !text.contains("Delete") {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), events.next())
+                .await
+                .expect("event in time")
+                .expect("open stream")
+                .expect("chunk");
+            text.push_str(std::str::from_utf8(&chunk).expect("utf8"));
+        }
+        // Seqs are the WAL positions the writes committed at, and only the last change of the
+        // batch carries its id.
+        let ids: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("id: "))
+            .collect();
+        assert_eq!(ids, ["2", "6", "7"], "{text}");
+        assert!(
+            text.contains(r#"data: {"seq":2,"table":"notes","op":"Insert","row_id":1}"#),
+            "{text}"
+        );
+        assert!(
+            text.contains(r#"data: {"seq":6,"table":"notes","op":"Insert","row_id":2}"#),
+            "{text}"
+        );
+
+        // A restarted server numbers changes on from the WAL, so a client that saw every change
+        // keeps its place while an older position is gone.
+        drop(events);
+        drop(app);
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("reopen db");
+        let app = test_app(db);
+        for (last_event_id, status) in [("7", StatusCode::OK), ("6", StatusCode::GONE)] {
+            let res = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri("/tables/notes/changes")
+                        .header("last-event-id", last_event_id)
+                        .body(Body::empty())
+                        .expect("request"),
+                )
+                .await
+                .expect("response");
+            assert_eq!(res.status(), status, "{last_event_id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn namespaces_serve_isolated_databases() {
+        let dir = tempdir().expect("tempdir");
+        let config = Config::new(dir.path().join("default"));
+        let manager = EmbedDbManager::new(dir.path().join("namespaces"), config.clone());
+        let db = EmbedDb::open(config).expect("open db");
+        let keys = ApiKeys::from_lookup(|name| {
+            (name == "EMBEDDB_API_KEYS").then(|| "admin,reader:read".to_string())
+        })
+        .expect("keys");
+        let app = build_router(Arc::new(AppState {
+            api_keys: Some(Arc::new(keys)),
+            namespaces: Some(Arc::new(Namespaces::new(manager))),
+            ..test_state(db)
+        }));
+
+        let acme = serde_json::json!({ "name": "acme" });
+        let create = serde_json::json!({
+            "name": "notes",
+            "schema": { "columns": [{ "name": "title", "data_type": "String", "nullable": false }] }
+        });
+        let cases = [
+            (
+                "POST",
+                "/namespaces",
+                "admin",
+                Some(&acme),
+                StatusCode::CREATED,
+            ),
+            (
+                "POST",
+                "/namespaces",
+                "admin",
+                Some(&acme),
+                StatusCode::CONFLICT,
+            ),
+            (
+                "POST",
+                "/namespaces",
+                "reader",
+                Some(&acme),
+                StatusCode::FORBIDDEN,
+            ),
+            ("GET", "/namespaces", "reader", None, StatusCode::OK),
+            (
+                "POST",
+                "/namespaces/acme/tables",
+                "reader",
+                Some(&create),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "POST",
+                "/namespaces/acme/tables",
+                "admin",
+                Some(&create),
+                StatusCode::CREATED,
+            ),
+            (
+                "GET",
+                "/namespaces/acme/tables/notes",
+                "reader",
+                None,
+                StatusCode::OK,
+            ),
+            (
+                "GET",
+                "/tables/notes",
+                "reader",
+                None,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "GET",
+                "/namespaces/globex/tables",
+                "reader",
+                None,
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "GET",
+                "/namespaces/acme/tables",
+                "nope",
+                None,
+                StatusCode::UNAUTHORIZED,
+            ),
+        ];
+        for (method, uri, key, body, expected) in cases {
+            let req = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {key}"));
+            let req = match body {
+                Some(body) => req
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string())),
+                None => req.body(Body::empty()),
+            };
+            let res = app
+                .clone()
+                .oneshot(req.expect("request"))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), expected, "{method} {uri} with {key}");
+        }
 
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/tables/notes/flush")
+                    .method("GET")
+                    .uri("/namespaces/acme/tables?unused=1")
+                    .header("x-api-key", "reader")
                     .body(Body::empty())
                     .expect("request"),
             )
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let tables: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        assert_eq!(tables, serde_json::json!(["notes"]));
+    }
+
+    #[tokio::test]
+    async fn search_text_can_target_a_named_vector() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let app = test_app(db);
+
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request")
+        };
+        let setup = [
+            post(
+                "/tables",
+                serde_json::json!({
+                    "name": "notes",
+                    "schema": {
+                        "columns": [
+                            { "name": "title", "data_type": "String", "nullable": false },
+                            { "name": "body", "data_type": "String", "nullable": false }
+                        ]
+                    },
+                    "embedding_fields": ["title"],
+                    "embedding_vectors": [{ "name": "body", "source_fields": ["body"] }]
+                }),
+            ),
+            post(
+                "/tables/notes/rows",
+                serde_json::json!({ "fields": { "title": "Hello", "body": "World" } }),
+            ),
+            post("/tables/notes/jobs/process", serde_json::json!({})),
+        ];
+        for req in setup {
+            let res = app.clone().oneshot(req).await.expect("response");
+            assert!(res.status().is_success(), "{}", res.status());
+        }
 
         let res = app
             .clone()
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/tables/notes/compact")
-                    .body(Body::empty())
-                    .expect("request"),
-            )
+            .oneshot(post(
+                "/tables/notes/search-text",
+                serde_json::json!({ "query_text": "World", "k": 1, "vector": "body" }),
+            ))
             .await
             .expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let hits: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        assert_eq!(hits[0]["row_id"], 1);
+        assert!(hits[0]["distance"].as_f64().expect("distance").abs() < 1e-5);
 
         let res = app
             .clone()
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/checkpoint")
-                    .body(Body::empty())
-                    .expect("request"),
-            )
+            .oneshot(post(
+                "/tables/notes/search-text",
+                serde_json::json!({ "query_text": "World", "vector": "summary" }),
+            ))
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rename_table_moves_the_table_to_its_new_name() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let app = test_app(db);
+
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request")
+        };
+        let table = |name: &str| {
+            serde_json::json!({
+                "name": name,
+                "schema": {
+                    "columns": [{ "name": "title", "data_type": "String", "nullable": false }]
+                }
+            })
+        };
+        let setup = [
+            post("/tables", table("notes")),
+            post("/tables", table("taken")),
+            post(
+                "/tables/notes/rows",
+                serde_json::json!({ "fields": { "title": "Hello" } }),
+            ),
+        ];
+        for req in setup {
+            let res = app.clone().oneshot(req).await.expect("response");
+            assert!(res.status().is_success(), "{}", res.status());
+        }
+
+        for (uri, name, status) in [
+            ("/tables/missing/rename", "docs", StatusCode::NOT_FOUND),
+            ("/tables/notes/rename", "taken", StatusCode::CONFLICT),
+            ("/tables/notes/rename", "docs", StatusCode::OK),
+        ] {
+            let res = app
+                .clone()
+                .oneshot(post(uri, serde_json::json!({ "name": name })))
+                .await
+                .expect("response");
+            assert_eq!(res.status(), status, "{uri} -> {name}");
+        }
 
+        let get = |uri: &str| {
+            Request::builder()
+                .method("GET")
+                .uri(uri)
+                .body(Body::empty())
+                .expect("request")
+        };
         let res = app
             .clone()
-            .oneshot(
-                Request::builder()
-                    .method("GET")
-                    .uri("/tables/notes/rows/1")
-                    .body(Body::empty())
-                    .expect("request"),
-            )
+            .oneshot(get("/tables/docs/rows/1"))
             .await
             .expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        let res = app
+            .clone()
+            .oneshot(get("/tables/notes"))
+            .await
+            .expect("response");
+        assert!(!res.status().is_success());
     }
 
     #[tokio::test]
-    async fn snapshot_endpoints_work() {
+    async fn rows_matching_a_filter_are_deleted_together() {
         let dir = tempdir().expect("tempdir");
         let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
-        let app = build_router(Arc::new(AppState { db }));
+        let app = test_app(db);
 
-        let create_body = serde_json::json!({
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request")
+        };
+        let create = serde_json::json!({
             "name": "notes",
             "schema": {
                 "columns": [
-                    { "name": "title", "data_type": "String", "nullable": false }
+                    { "name": "tenant", "data_type": "String", "nullable": false },
+                    { "name": "n", "data_type": "Int", "nullable": false }
                 ]
-            },
-            "embedding_fields": ["title"]
+            }
         });
-        let _ = app
+        let res = app
             .clone()
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/tables")
-                    .header("content-type", "application/json")
-                    .body(Body::from(create_body.to_string()))
-                    .expect("request"),
-            )
+            .oneshot(post("/tables", create))
             .await
             .expect("response");
+        assert!(res.status().is_success());
+        for (tenant, n) in [("acme", 1), ("globex", 2), ("acme", 3), ("acme", 4)] {
+            let row = serde_json::json!({ "fields": { "tenant": tenant, "n": n } });
+            let res = app
+                .clone()
+                .oneshot(post("/tables/notes/rows", row))
+                .await
+                .expect("response");
+            assert!(res.status().is_success());
+        }
+
+        let deleted = |body: serde_json::Value| {
+            let app = app.clone();
+            async move {
+                let res = app
+                    .oneshot(post("/tables/notes/rows:delete", body))
+                    .await
+                    .expect("response");
+                let status = res.status();
+                let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                    .await
+                    .expect("body");
+                let json: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+                (status, json)
+            }
+        };
+        let (status, json) = deleted(serde_json::json!({
+            "filter": [{ "column": "tenant", "op": "Eq", "value": "acme" }],
+            "where": "n > 1"
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["deleted"], 2);
+        let (status, json) = deleted(serde_json::json!({ "where": "tenant = \"acme\"" })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["deleted"], 1);
+        let (status, _) = deleted(serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = deleted(serde_json::json!({ "where": "missing = 1" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let res = app
+            .clone()
+            .oneshot(post(
+                "/tables/notes/rows:purge",
+                serde_json::json!({ "where": "n > 0" }),
+            ))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
 
-        let snapshot_dir = tempdir().expect("snapshot dir");
-        let export_body =
-            serde_json::json!({ "dest_dir": snapshot_dir.path().join("snapshot-copy") });
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/snapshot/export")
-                    .header("content-type", "application/json")
-                    .body(Body::from(export_body.to_string()))
+                    .method("GET")
+                    .uri("/tables/notes/rows")
+                    .body(Body::empty())
                     .expect("request"),
             )
             .await
             .expect("response");
-        assert_eq!(res.status(), StatusCode::OK);
         let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
             .await
             .expect("body");
-        let exported: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
-        assert!(
-            exported
-                .get("files_copied")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0)
-                >= 1
-        );
+        let json: serde_json::Value = serde_json::from_slice(&bytes).expect("json");
+        let rows = json["items"].as_array().expect("items");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["fields"]["tenant"], "globex");
+    }
+
+    #[tokio::test]
+    async fn soft_deleted_rows_can_be_restored_or_purged() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let app = test_app(db);
+
+        let request = |method: &str, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request")
+        };
+        let empty = serde_json::json!({});
+        let steps = [
+            (
+                request(
+                    "POST",
+                    "/tables",
+                    serde_json::json!({
+                        "name": "notes",
+                        "schema": {
+                            "columns": [{ "name": "title", "data_type": "String", "nullable": false }],
+                            "soft_delete": {}
+                        }
+                    }),
+                ),
+                StatusCode::CREATED,
+            ),
+            (
+                request(
+                    "POST",
+                    "/tables/notes/rows",
+                    serde_json::json!({ "fields": { "title": "Hello" } }),
+                ),
+                StatusCode::CREATED,
+            ),
+            (
+                request("DELETE", "/tables/notes/rows/1", empty.clone()),
+                StatusCode::OK,
+            ),
+            (
+                request("GET", "/tables/notes/rows/1", empty.clone()),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                request("POST", "/tables/notes/rows/1/restore", empty.clone()),
+                StatusCode::OK,
+            ),
+            (
+                request("POST", "/tables/notes/rows/1/restore", empty.clone()),
+                StatusCode::CONFLICT,
+            ),
+            (
+                request("GET", "/tables/notes/rows/1", empty.clone()),
+                StatusCode::OK,
+            ),
+            (
+                request("DELETE", "/tables/notes/rows/1", empty.clone()),
+                StatusCode::OK,
+            ),
+        ];
+        for (req, status) in steps {
+            let uri = req.uri().to_string();
+            let res = app.clone().oneshot(req).await.expect("response");
+            assert_eq!(res.status(), status, "{uri}");
+        }
+
+        let res = app
+            .clone()
+            .oneshot(request("POST", "/tables/notes/purge-hidden", empty.clone()))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(json["purged"], 1);
+        let res = app
+            .clone()
+            .oneshot(request("POST", "/tables/notes/rows/1/restore", empty))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[tokio::test]
+    async fn export_streams_rows_as_arrow_ipc() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+            .expect("create table");
+        for title in ["a", "b", "c"] {
+            let mut fields = BTreeMap::new();
+            fields.insert("title".to_string(), Value::String(title.to_string()));
+            db.insert_row("notes", fields).expect("insert");
+        }
+        db.process_pending_jobs("notes", &LocalHashEmbedder)
+            .expect("embed");
+        let app = test_app(db);
+
+        let get = |uri: &str| {
+            Request::builder()
+                .method("GET")
+                .uri(uri)
+                .body(Body::empty())
+                .expect("request")
+        };
+        for (uri, embedding) in [
+            ("/tables/notes/export?format=arrow&batch_rows=2", true),
+            ("/tables/notes/export?embeddings=false", false),
+        ] {
+            let res = app.clone().oneshot(get(uri)).await.expect("response");
+            assert_eq!(res.status(), StatusCode::OK, "{uri}");
+            assert_eq!(
+                res.headers()["content-type"],
+                "application/vnd.apache.arrow.stream"
+            );
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            let reader =
+                arrow_ipc::reader::StreamReader::try_new(std::io::Cursor::new(bytes), None)
+                    .expect("arrow stream");
+            assert_eq!(
+                reader.schema().column_with_name("embedding").is_some(),
+                embedding
+            );
+            let batches = reader.collect::<Result<Vec<_>, _>>().expect("batches");
+            let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+            assert_eq!(rows, 3, "{uri}");
+            if embedding {
+                assert_eq!(batches.len(), 2);
+            }
+        }
+
+        let res = app
+            .clone()
+            .oneshot(get("/tables/missing/export"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = app
+            .oneshot(get("/tables/notes/export?format=csv"))
+            .await
+            .expect("response");
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn metrics_endpoint_counts_requests() {
+        let dir = tempdir().expect("tempdir");
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).expect("open db");
+        let app = test_app(db);
+
+        let requests = [
+            (
+                "/tables",
+                serde_json::json!({
+                    "name": "notes",
+                    "schema": {
+                        "columns": [{ "name": "title", "data_type": "String", "nullable": false }]
+                    },
+                    "embedding_fields": ["title"]
+                }),
+            ),
+            (
+                "/tables/notes/rows",
+                serde_json::json!({ "fields": { "title": "Hello" } }),
+            ),
+            (
+                "/tables/notes/search",
+                serde_json::json!({ "query": [1.0, 0.0, 0.0, 0.0], "k": 1 }),
+            ),
+        ];
+        for (uri, body) in requests {
+            let res = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .expect("request"),
+                )
+                .await
+                .expect("response");
+            assert!(res.status().is_success(), "{uri}: {}", res.status());
+        }
 
-        let restore_target = snapshot_dir.path().join("restore-target");
-        let restore_body = serde_json::json!({
-            "snapshot_dir": snapshot_dir.path().join("snapshot-copy"),
-            "data_dir": restore_target
-        });
         let res = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/snapshot/restore")
-                    .header("content-type", "application/json")
-                    .body(Body::from(restore_body.to_string()))
+                    .method("GET")
+                    .uri("/metrics")
+                    .body(Body::empty())
                     .expect("request"),
             )
             .await
             .expect("response");
         assert_eq!(res.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let text = String::from_utf8(bytes.to_vec()).expect("utf8");
+        assert!(text.contains("embeddb_inserts_total 1\n"));
+        assert!(text.contains("embeddb_searches_total 1\n"));
+        assert!(text.contains("embeddb_search_duration_seconds_count 1\n"));
+        assert!(text.contains("embeddb_embedding_jobs_pending{table=\"notes\"} 1\n"));
     }
 }
@@ -0,0 +1,360 @@
+//! Scheduled maintenance (`EMBEDDB_MAINTENANCE_SCHEDULE`): runs flush/compact/checkpoint on a
+//! cron-like schedule inside the server process and records the outcome for `GET /stats`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use embeddb::EmbedDb;
+use serde::Serialize;
+
+/// Five-field cron expression (`minute hour day-of-month month day-of-week`, UTC). Each field
+/// accepts `*`, numbers, ranges (`1-5`), lists (`1,15`), and steps (`*/15`, `0-30/10`). Day of
+/// week is 0-6 with 0 = Sunday (7 is also accepted for Sunday).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Standard cron semantics: when both day fields are restricted, either may match.
+    dom_any: bool,
+    dow_any: bool,
+}
+
+impl Schedule {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            return Err(anyhow!(
+                "maintenance schedule must have 5 fields (minute hour day month weekday), got '{spec}'"
+            ));
+        };
+        let mut days_of_week = parse_field(dow, 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(dom, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            dom_any: *dom == "*",
+            dow_any: *dow == "*",
+        })
+    }
+
+    fn matches(&self, epoch_minute: u64) -> bool {
+        let minute = epoch_minute % 60;
+        let hour = (epoch_minute / 60) % 24;
+        let days = (epoch_minute / (60 * 24)) as i64;
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as u64;
+
+        let dom = self.days_of_month & (1 << day) != 0;
+        let dow = self.days_of_week & (1 << weekday) != 0;
+        let day_matches = match (self.dom_any, self.dow_any) {
+            (true, true) => true,
+            (false, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        };
+        self.minutes & (1 << minute) != 0
+            && self.hours & (1 << hour) != 0
+            && self.months & (1 << month) != 0
+            && day_matches
+    }
+
+    /// First matching minute strictly after `now_secs` (Unix seconds), searched up to ~4 years
+    /// ahead so schedules like `0 0 29 2 *` still resolve.
+    pub fn next_after(&self, now_secs: u64) -> Option<u64> {
+        let start = now_secs / 60 + 1;
+        (start..start + 4 * 366 * 24 * 60)
+            .find(|minute| self.matches(*minute))
+            .map(|minute| minute * 60)
+    }
+}
+
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().ok().filter(|s| *s > 0)),
+            None => (part, Some(1)),
+        };
+        let step = step.ok_or_else(|| anyhow!("invalid step in schedule field '{field}'"))?;
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (parse_value(lo, field)?, parse_value(hi, field)?)
+        } else {
+            let value = parse_value(range, field)?;
+            // `5/10` means "from 5 to the end in steps of 10".
+            if part.contains('/') {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(anyhow!(
+                "schedule field '{field}' is out of range {min}-{max}"
+            ));
+        }
+        let mut value = lo;
+        while value <= hi {
+            mask |= 1 << value;
+            value += step;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(raw: &str, field: &str) -> Result<u64> {
+    raw.parse::<u64>()
+        .map_err(|_| anyhow!("invalid value '{raw}' in schedule field '{field}'"))
+}
+
+/// Converts days since the Unix epoch to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u64;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u64;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceTask {
+    Flush,
+    Compact,
+    Checkpoint,
+}
+
+/// Parses `EMBEDDB_MAINTENANCE_TASKS`, a comma-separated list run in the given order. `vacuum`
+/// is accepted as an alias for `compact`, which is what drops deleted rows from SSTs.
+pub fn parse_tasks(raw: &str) -> Result<Vec<MaintenanceTask>> {
+    let mut tasks = Vec::new();
+    for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let task = match name {
+            "flush" => MaintenanceTask::Flush,
+            "compact" | "vacuum" => MaintenanceTask::Compact,
+            "checkpoint" => MaintenanceTask::Checkpoint,
+            _ => {
+                return Err(anyhow!(
+                    "invalid maintenance task '{name}' (expected flush|compact|vacuum|checkpoint)"
+                ))
+            }
+        };
+        if !tasks.contains(&task) {
+            tasks.push(task);
+        }
+    }
+    if tasks.is_empty() {
+        return Err(anyhow!(
+            "EMBEDDB_MAINTENANCE_TASKS must name at least one task"
+        ));
+    }
+    Ok(tasks)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MaintenanceStatus {
+    pub schedule: String,
+    pub tasks: Vec<MaintenanceTask>,
+    pub runs_total: u64,
+    pub failures_total: u64,
+    pub last_started_ms: Option<u64>,
+    pub last_finished_ms: Option<u64>,
+    pub last_duration_ms: Option<u64>,
+    /// Error from the most recent run; cleared by the next successful run.
+    pub last_error: Option<String>,
+    pub next_run_ms: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct Maintenance {
+    schedule: Schedule,
+    tasks: Vec<MaintenanceTask>,
+    status: Mutex<MaintenanceStatus>,
+}
+
+impl Maintenance {
+    pub fn new(spec: &str, tasks: Vec<MaintenanceTask>) -> Result<Self> {
+        let schedule = Schedule::parse(spec)?;
+        let status = MaintenanceStatus {
+            schedule: spec.to_string(),
+            tasks: tasks.clone(),
+            next_run_ms: schedule.next_after(now_ms() / 1000).map(|s| s * 1000),
+            ..MaintenanceStatus::default()
+        };
+        Ok(Self {
+            schedule,
+            tasks,
+            status: Mutex::new(status),
+        })
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        self.lock_status().clone()
+    }
+
+    fn lock_status(&self) -> std::sync::MutexGuard<'_, MaintenanceStatus> {
+        self.status
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Runs every configured task once and records the outcome. Stops at the first failing task.
+    pub fn run_once(&self, db: &EmbedDb) -> Result<()> {
+        let started = now_ms();
+        self.lock_status().last_started_ms = Some(started);
+
+        let result = self.run_tasks(db);
+
+        let finished = now_ms();
+        let mut status = self.lock_status();
+        status.runs_total += 1;
+        status.last_finished_ms = Some(finished);
+        status.last_duration_ms = Some(finished.saturating_sub(started));
+        status.next_run_ms = self.schedule.next_after(finished / 1000).map(|s| s * 1000);
+        match &result {
+            Ok(()) => status.last_error = None,
+            Err(err) => {
+                status.failures_total += 1;
+                status.last_error = Some(format!("{err:#}"));
+            }
+        }
+        result
+    }
+
+    fn run_tasks(&self, db: &EmbedDb) -> Result<()> {
+        for task in &self.tasks {
+            match task {
+                MaintenanceTask::Flush => {
+                    for table in db.list_tables()? {
+                        db.flush_table(&table)?;
+                    }
+                }
+                MaintenanceTask::Compact => {
+                    for table in db.list_tables()? {
+                        db.compact_table(&table)?;
+                    }
+                }
+                MaintenanceTask::Checkpoint => {
+                    db.checkpoint()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Sleeps until each scheduled time and runs maintenance on a blocking thread, forever.
+pub async fn run_forever(maintenance: Arc<Maintenance>, db: Arc<EmbedDb>) {
+    loop {
+        let Some(next_ms) = maintenance.status().next_run_ms else {
+            tracing::warn!("maintenance schedule has no upcoming run; stopping scheduler");
+            return;
+        };
+        let wait = Duration::from_millis(next_ms.saturating_sub(now_ms()));
+        tokio::time::sleep(wait).await;
+
+        let job = maintenance.clone();
+        let db = db.clone();
+        let outcome = tokio::task::spawn_blocking(move || job.run_once(&db)).await;
+        match outcome {
+            Ok(Ok(())) => tracing::info!("scheduled maintenance completed"),
+            Ok(Err(err)) => tracing::warn!("scheduled maintenance failed: {err:#}"),
+            Err(err) => tracing::warn!("scheduled maintenance panicked: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01T00:00:00Z, a Monday.
+    const JAN_1_2024: u64 = 1_704_067_200;
+
+    #[test]
+    fn parses_fields_and_finds_next_run() {
+        let nightly = Schedule::parse("30 3 * * *").unwrap();
+        assert_eq!(
+            nightly.next_after(JAN_1_2024),
+            Some(JAN_1_2024 + 3 * 3600 + 30 * 60)
+        );
+
+        let quarter_hour = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter_hour.next_after(JAN_1_2024),
+            Some(JAN_1_2024 + 15 * 60)
+        );
+
+        // Weekends only: the first match after Monday is Saturday 2024-01-06 at 02:00.
+        let weekends = Schedule::parse("0 2 * * 6,7").unwrap();
+        assert_eq!(
+            weekends.next_after(JAN_1_2024),
+            Some(JAN_1_2024 + 5 * 86_400 + 2 * 3600)
+        );
+
+        let leap_day = Schedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(JAN_1_2024),
+            Some(JAN_1_2024 + 59 * 86_400)
+        );
+
+        assert!(Schedule::parse("0 3 * *").is_err());
+        assert!(Schedule::parse("61 3 * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn parses_task_lists() {
+        assert_eq!(
+            parse_tasks("flush, vacuum,checkpoint,compact").unwrap(),
+            vec![
+                MaintenanceTask::Flush,
+                MaintenanceTask::Compact,
+                MaintenanceTask::Checkpoint
+            ]
+        );
+        assert!(parse_tasks("defrag").is_err());
+        assert!(parse_tasks("").is_err());
+    }
+
+    #[test]
+    fn run_once_records_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = EmbedDb::open(embeddb::Config::new(dir.path().to_path_buf())).unwrap();
+        let maintenance = Maintenance::new(
+            "0 3 * * *",
+            parse_tasks("flush,compact,checkpoint").unwrap(),
+        )
+        .unwrap();
+        maintenance.run_once(&db).unwrap();
+
+        let status = maintenance.status();
+        assert_eq!(status.runs_total, 1);
+        assert_eq!(status.failures_total, 0);
+        assert!(status.last_error.is_none());
+        assert!(status.next_run_ms.unwrap() > status.last_finished_ms.unwrap());
+    }
+}
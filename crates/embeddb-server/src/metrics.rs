@@ -0,0 +1,327 @@
+//! Prometheus metrics (feature `metrics`): `GET /metrics` serves HTTP request counters and a
+//! search latency histogram recorded by a middleware layer, plus database counters and per-table
+//! gauges read at scrape time, in the text exposition format.
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, Method},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use embeddb::{DbStats, TableStats};
+
+use crate::{ApiError, AppState};
+
+/// Upper bounds, in seconds, of the search latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; anything above the last bound only counts
+    /// towards `count`.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(idx) = LATENCY_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Counters for successful HTTP requests, shared by the middleware and the `/metrics` handler.
+#[derive(Default)]
+pub struct ServerMetrics {
+    inserts: AtomicU64,
+    deletes: AtomicU64,
+    searches: AtomicU64,
+    search_latency: Histogram,
+}
+
+enum Op {
+    Insert,
+    Delete,
+    Search,
+}
+
+fn classify(method: &Method, route: &str) -> Option<Op> {
+    match (method.as_str(), route) {
+        ("POST", "/tables/:table/rows") => Some(Op::Insert),
+        ("DELETE", "/tables/:table/rows/:row_id") => Some(Op::Delete),
+        (
+            "POST",
+            "/tables/:table/search"
+            | "/tables/:table/search-text"
+            | "/tables/:table/search-sparse"
+            | "/tables/:table/search-hybrid"
+            | "/tables/:table/recommend"
+            | "/tables/:table/rows/:row_id/similar",
+        ) => Some(Op::Search),
+        _ => None,
+    }
+}
+
+/// Adds `GET /metrics` to `router` and the layer that records request metrics for it.
+pub fn instrument(router: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+    let metrics = Arc::new(ServerMetrics::default());
+    router
+        .route("/metrics", get(scrape))
+        .layer(middleware::from_fn_with_state(metrics.clone(), track))
+        .layer(Extension(metrics))
+}
+
+async fn track(State(metrics): State<Arc<ServerMetrics>>, req: Request, next: Next) -> Response {
+    let op = req
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| classify(req.method(), route.as_str()));
+    let started = Instant::now();
+    let response = next.run(req).await;
+    if response.status().is_success() {
+        match op {
+            Some(Op::Insert) => {
+                metrics.inserts.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(Op::Delete) => {
+                metrics.deletes.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(Op::Search) => {
+                metrics.searches.fetch_add(1, Ordering::Relaxed);
+                metrics.search_latency.observe(started.elapsed());
+            }
+            None => {}
+        }
+    }
+    response
+}
+
+async fn scrape(
+    State(state): State<Arc<AppState>>,
+    Extension(metrics): Extension<Arc<ServerMetrics>>,
+) -> Result<Response, ApiError> {
+    let db_stats = state
+        .db
+        .db_stats()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    let names = state
+        .db
+        .list_tables()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    // A table dropped since the listing is simply left out of this scrape.
+    let mut tables: Vec<TableStats> = Vec::with_capacity(names.len());
+    for name in &names {
+        if let Ok(stats) = state.db.table_stats(name).await {
+            tables.push(stats);
+        }
+    }
+    let body = render(&metrics, &db_stats, &tables);
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
+
+fn render(metrics: &ServerMetrics, db: &DbStats, tables: &[TableStats]) -> String {
+    let mut out = String::new();
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    for (name, help, value) in [
+        (
+            "embeddb_inserts_total",
+            "Rows inserted through the HTTP API.",
+            load(&metrics.inserts),
+        ),
+        (
+            "embeddb_deletes_total",
+            "Rows deleted through the HTTP API.",
+            load(&metrics.deletes),
+        ),
+        (
+            "embeddb_searches_total",
+            "Searches served through the HTTP API.",
+            load(&metrics.searches),
+        ),
+    ] {
+        write_header(&mut out, name, help, "counter");
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    let latency = &metrics.search_latency;
+    let name = "embeddb_search_duration_seconds";
+    write_header(
+        &mut out,
+        name,
+        "Latency of successful HTTP searches.",
+        "histogram",
+    );
+    let mut cumulative = 0;
+    for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&latency.buckets) {
+        cumulative += load(bucket);
+        let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+    }
+    let count = load(&latency.count);
+    let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+    let _ = writeln!(
+        out,
+        "{name}_sum {}",
+        load(&latency.sum_micros) as f64 / 1_000_000.0
+    );
+    let _ = writeln!(out, "{name}_count {count}");
+
+    for (name, help, kind, value) in [
+        (
+            "embeddb_embedding_jobs_processed_total",
+            "Embedding jobs completed since open.",
+            "counter",
+            db.embeddings_processed_total as f64,
+        ),
+        (
+            "embeddb_embedding_jobs_failed_total",
+            "Embedding jobs that failed terminally since open.",
+            "counter",
+            db.embeddings_failed_total as f64,
+        ),
+        (
+            "embeddb_wal_bytes",
+            "Size of the WAL including sealed segments.",
+            "gauge",
+            db.wal_bytes as f64,
+        ),
+        (
+            "embeddb_checkpoints_total",
+            "Checkpoints since open.",
+            "counter",
+            db.checkpoints as f64,
+        ),
+        (
+            "embeddb_checkpoint_seconds_total",
+            "Time spent in checkpoints since open.",
+            "counter",
+            db.checkpoint_total_ms as f64 / 1000.0,
+        ),
+    ] {
+        write_header(&mut out, name, help, kind);
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    let name = "embeddb_embedding_jobs_pending";
+    write_header(&mut out, name, "Pending embedding jobs per table.", "gauge");
+    for table in tables {
+        let label = escape_label(&table.name);
+        let _ = writeln!(
+            out,
+            "{name}{{table=\"{label}\"}} {}",
+            table.embeddings_pending
+        );
+    }
+
+    let name = "embeddb_sst_files";
+    write_header(&mut out, name, "SST files per table and level.", "gauge");
+    for table in tables {
+        let label = escape_label(&table.name);
+        for (level, files) in table.sst_files_per_level.iter().enumerate() {
+            let _ = writeln!(out, "{name}{{table=\"{label}\",level=\"{level}\"}} {files}");
+        }
+    }
+
+    let name = "embeddb_sst_bytes";
+    write_header(
+        &mut out,
+        name,
+        "On-disk SST bytes per table and level.",
+        "gauge",
+    );
+    for table in tables {
+        let label = escape_label(&table.name);
+        for (level, bytes) in table.sst_bytes_per_level.iter().enumerate() {
+            let _ = writeln!(out, "{name}{{table=\"{label}\",level=\"{level}\"}} {bytes}");
+        }
+    }
+
+    let name = "embeddb_table_rows";
+    write_header(&mut out, name, "Rows per table.", "gauge");
+    for table in tables {
+        let label = escape_label(&table.name);
+        let _ = writeln!(out, "{name}{{table=\"{label}\"}} {}", table.total_rows);
+    }
+
+    let name = "embeddb_embedding_memory_bytes";
+    write_header(
+        &mut out,
+        name,
+        "Memory held by resident vectors per table.",
+        "gauge",
+    );
+    for table in tables {
+        let label = escape_label(&table.name);
+        let _ = writeln!(
+            out,
+            "{name}{{table=\"{label}\"}} {}",
+            table.embedding_memory_bytes
+        );
+    }
+
+    out
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_render_cumulatively() {
+        let metrics = ServerMetrics::default();
+        metrics.search_latency.observe(Duration::from_micros(500));
+        metrics.search_latency.observe(Duration::from_millis(20));
+        metrics.search_latency.observe(Duration::from_secs(10));
+        metrics.inserts.fetch_add(3, Ordering::Relaxed);
+
+        let db: DbStats = serde_json::from_value(serde_json::json!({
+            "tables": 1, "lsn": 9, "wal_bytes": 4096, "wal_segments": 0, "wal_rotations": 0,
+            "wal_durable_appends": 9, "wal_sync_ops": 9, "checkpoints": 2, "auto_checkpoints": 0,
+            "checkpoint_total_ms": 1500, "flush_count_total": 0, "flush_total_ms": 0,
+            "compact_count_total": 0, "compact_total_ms": 0, "embeddings_processed_total": 5,
+            "embeddings_failed_total": 1, "embeddings_retried_total": 0, "search_cache_hits": 0,
+            "search_cache_misses": 0
+        }))
+        .unwrap();
+        let text = render(&metrics, &db, &[]);
+
+        assert!(text.contains("embeddb_inserts_total 3\n"));
+        assert!(text.contains("embeddb_search_duration_seconds_bucket{le=\"0.001\"} 1\n"));
+        assert!(text.contains("embeddb_search_duration_seconds_bucket{le=\"0.025\"} 2\n"));
+        assert!(text.contains("embeddb_search_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("embeddb_search_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("embeddb_search_duration_seconds_count 3\n"));
+        assert!(text.contains("embeddb_checkpoint_seconds_total 1.5\n"));
+        assert!(text.contains("# TYPE embeddb_wal_bytes gauge\n"));
+        assert_eq!(escape_label("a\"b"), "a\\\"b");
+    }
+}
@@ -0,0 +1,155 @@
+//! Namespaces (`EMBEDDB_NAMESPACES_DIR`): isolated databases hosted next to the default one, each
+//! in its own subdirectory and served under `/namespaces/:namespace/...` with the same table,
+//! job, and stats routes as the top level.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use embeddb::EmbedDbManager;
+use serde::Deserialize;
+use tower::util::ServiceExt;
+
+use crate::{data_router, ApiError, AppState};
+
+pub(crate) struct Namespaces {
+    manager: Arc<EmbedDbManager>,
+    // Routers for opened namespaces, built once so each keeps its `AsyncEmbedDb`.
+    routers: Mutex<HashMap<String, Router>>,
+}
+
+impl Namespaces {
+    pub(crate) fn new(manager: EmbedDbManager) -> Self {
+        Self {
+            manager: Arc::new(manager),
+            routers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn router(&self, state: &AppState, name: &str) -> Result<Router, ApiError> {
+        if let Some(router) = self.lock().get(name) {
+            return Ok(router.clone());
+        }
+        let manager = self.manager.clone();
+        let owned = name.to_string();
+        let db = tokio::task::spawn_blocking(move || manager.namespace(&owned))
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?
+            .map_err(namespace_error)?;
+        let router = self
+            .lock()
+            .entry(name.to_string())
+            .or_insert_with(|| data_router(namespace_state(state, db)))
+            .clone();
+        Ok(router)
+    }
+
+    /// Closes every opened namespace; see `EmbedDbManager::close_all`.
+    pub(crate) fn close_all(&self) -> anyhow::Result<()> {
+        self.lock().clear();
+        self.manager.close_all()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Router>> {
+        self.routers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A namespace shares the server's embedder and API keys; scheduled maintenance only covers the
+/// default database.
+fn namespace_state(state: &AppState, db: Arc<embeddb::EmbedDb>) -> Arc<AppState> {
+    Arc::new(AppState {
+        db: db.into(),
+        embedder: state.embedder.clone(),
+        maintenance: None,
+        api_keys: state.api_keys.clone(),
+        namespaces: None,
+        snapshots_dir: None,
+        rate_limits: None,
+    })
+}
+
+fn namespace_error(err: anyhow::Error) -> ApiError {
+    if err.to_string().ends_with("not found") {
+        ApiError::not_found(err.to_string())
+    } else {
+        ApiError::bad_request(err.to_string())
+    }
+}
+
+fn enabled(state: &AppState) -> Result<&Namespaces, ApiError> {
+    state.namespaces.as_deref().ok_or_else(|| {
+        ApiError::not_found("namespaces are not enabled (set EMBEDDB_NAMESPACES_DIR)")
+    })
+}
+
+pub(crate) async fn list_namespaces(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let manager = enabled(&state)?.manager.clone();
+    let names = tokio::task::spawn_blocking(move || manager.list_namespaces())
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "namespaces": names })))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CreateNamespaceRequest {
+    name: String,
+}
+
+pub(crate) async fn create_namespace(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateNamespaceRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let manager = enabled(&state)?.manager.clone();
+    let name = req.name.clone();
+    tokio::task::spawn_blocking(move || manager.create_namespace(&name))
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .map_err(|err| {
+            if err.to_string().ends_with("already exists") {
+                ApiError::conflict(err.to_string())
+            } else {
+                ApiError::bad_request(err.to_string())
+            }
+        })?;
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "name": req.name })),
+    ))
+}
+
+/// Serves `/namespaces/:namespace/<rest>` with the namespace's router at `/<rest>`.
+pub(crate) async fn dispatch(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, rest)): Path<(String, String)>,
+    req: Request,
+) -> Result<Response, ApiError> {
+    let router = enabled(&state)?.router(&state, &namespace).await?;
+    let path = format!("/{}", rest.trim_start_matches('/'));
+    let uri = match req.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+    // A fresh request, so the outer route's matched path and params don't leak into the inner
+    // router's extractors.
+    let (parts, body) = req.into_parts();
+    let mut inner = Request::builder()
+        .method(parts.method)
+        .uri(uri)
+        .version(parts.version)
+        .body(body)
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    *inner.headers_mut() = parts.headers;
+    let response = router.oneshot(inner).await.into_response();
+    Ok(response)
+}
@@ -0,0 +1,317 @@
+//! Per-client rate limits, so one noisy client can't starve the others of the shared database.
+//! `EMBEDDB_RATE_LIMIT_RPS` refills each client's token bucket of `EMBEDDB_RATE_LIMIT_BURST`
+//! requests, and `EMBEDDB_MAX_CONCURRENT_SEARCHES` caps the client's `QUERY_ROUTES` requests in
+//! flight. Clients are told apart by API key when keys are configured and by peer IP otherwise.
+//! Requests over a limit get `429` with a `Retry-After` header; `GET /health` is never limited.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::auth::{presented_key, QUERY_ROUTES};
+use crate::ApiError;
+
+// Idle clients are forgotten once this many are tracked.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Key(String),
+    Ip(IpAddr),
+}
+
+#[derive(Debug)]
+struct ClientState {
+    tokens: f64,
+    refilled_at: Instant,
+    searches: usize,
+}
+
+/// Why a request was turned away, and when to try again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Limited {
+    Rate { retry_after: Duration },
+    Searches,
+}
+
+pub(crate) struct RateLimits {
+    /// Tokens added per second, and the bucket size; `None` leaves the request rate unlimited.
+    rate: Option<(f64, f64)>,
+    max_searches: Option<usize>,
+    by_key: bool,
+    clients: Mutex<HashMap<Client, ClientState>>,
+}
+
+impl RateLimits {
+    /// The configured limits, or `None` when there are none. `by_key` identifies clients by API
+    /// key instead of IP, for servers that require keys.
+    pub(crate) fn from_env(by_key: bool) -> Result<Option<Arc<Self>>> {
+        let limits = Self::from_lookup(|name| std::env::var(name).ok(), by_key)?;
+        Ok((limits.rate.is_some() || limits.max_searches.is_some()).then(|| Arc::new(limits)))
+    }
+
+    pub(crate) fn from_lookup(
+        lookup: impl Fn(&str) -> Option<String>,
+        by_key: bool,
+    ) -> Result<Self> {
+        let rps = lookup("EMBEDDB_RATE_LIMIT_RPS")
+            .map(|raw| {
+                raw.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|rps| rps.is_finite() && *rps > 0.0)
+                    .ok_or_else(|| anyhow!("invalid EMBEDDB_RATE_LIMIT_RPS"))
+            })
+            .transpose()?;
+        let burst = lookup("EMBEDDB_RATE_LIMIT_BURST")
+            .map(|raw| {
+                raw.trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|burst| *burst > 0)
+                    .ok_or_else(|| anyhow!("invalid EMBEDDB_RATE_LIMIT_BURST"))
+            })
+            .transpose()?;
+        if burst.is_some() && rps.is_none() {
+            return Err(anyhow!(
+                "EMBEDDB_RATE_LIMIT_BURST requires EMBEDDB_RATE_LIMIT_RPS"
+            ));
+        }
+        let max_searches = lookup("EMBEDDB_MAX_CONCURRENT_SEARCHES")
+            .map(|raw| {
+                raw.trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|max| *max > 0)
+                    .ok_or_else(|| anyhow!("invalid EMBEDDB_MAX_CONCURRENT_SEARCHES"))
+            })
+            .transpose()?;
+        Ok(Self {
+            // A second's worth of requests by default, and at least one.
+            rate: rps.map(|rps| (rps, burst.map_or(rps.ceil().max(1.0), f64::from))),
+            max_searches,
+            by_key,
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Client, ClientState>> {
+        self.clients
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Takes a token from the client's bucket and, for a search, one of its search slots, which
+    /// the returned permit gives back when dropped.
+    fn admit(
+        self: &Arc<Self>,
+        client: Client,
+        search: bool,
+        now: Instant,
+    ) -> Result<SearchPermit, Limited> {
+        let mut clients = self.lock();
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&client) {
+            clients.retain(|_, state| state.searches > 0 || !self.refills_by(state, now));
+        }
+        let burst = self.rate.map_or(0.0, |(_, burst)| burst);
+        let state = clients.entry(client.clone()).or_insert(ClientState {
+            tokens: burst,
+            refilled_at: now,
+            searches: 0,
+        });
+        if search && self.max_searches.is_some_and(|max| state.searches >= max) {
+            return Err(Limited::Searches);
+        }
+        if let Some((rps, burst)) = self.rate {
+            let elapsed = now.saturating_duration_since(state.refilled_at);
+            state.tokens = (state.tokens + elapsed.as_secs_f64() * rps).min(burst);
+            state.refilled_at = now;
+            if state.tokens < 1.0 {
+                let retry_after = Duration::from_secs_f64((1.0 - state.tokens) / rps);
+                return Err(Limited::Rate { retry_after });
+            }
+            state.tokens -= 1.0;
+        }
+        let search = search && self.max_searches.is_some();
+        if search {
+            state.searches += 1;
+        }
+        Ok(SearchPermit {
+            limits: self.clone(),
+            client: search.then_some(client),
+        })
+    }
+
+    /// Whether the client's bucket is full again by `now`, so forgetting it changes nothing.
+    fn refills_by(&self, state: &ClientState, now: Instant) -> bool {
+        match self.rate {
+            Some((rps, burst)) => {
+                let elapsed = now.saturating_duration_since(state.refilled_at);
+                state.tokens + elapsed.as_secs_f64() * rps >= burst
+            }
+            None => true,
+        }
+    }
+
+    fn client(&self, req: &Request) -> Client {
+        if self.by_key {
+            if let Some(key) = presented_key(req.headers()) {
+                return Client::Key(key.to_string());
+            }
+        }
+        let ip = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |info| info.0.ip());
+        Client::Ip(ip)
+    }
+}
+
+/// Holds a search slot until the response is ready.
+pub(crate) struct SearchPermit {
+    limits: Arc<RateLimits>,
+    client: Option<Client>,
+}
+
+impl Drop for SearchPermit {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            if let Some(state) = self.limits.lock().get_mut(&client) {
+                state.searches = state.searches.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Whether `path` (top-level or under `/namespaces/:namespace`) is one of `QUERY_ROUTES`.
+fn is_query_path(path: &str) -> bool {
+    let mut segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if segments.len() > 2 && segments[0] == "namespaces" {
+        segments.drain(..2);
+    }
+    if segments.len() < 3 || segments[0] != "tables" {
+        return false;
+    }
+    segments[1] = ":table";
+    if segments.len() > 3 && segments[2] == "rows" {
+        segments[3] = ":row_id";
+    }
+    let route = format!("/{}", segments.join("/"));
+    QUERY_ROUTES.contains(&route.as_str())
+}
+
+pub(crate) async fn enforce_limits(
+    State(limits): State<Arc<RateLimits>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if req.method() == Method::GET && req.uri().path() == "/health" {
+        return next.run(req).await;
+    }
+    let search = req.method() == Method::POST && is_query_path(req.uri().path());
+    match limits.admit(limits.client(&req), search, Instant::now()) {
+        Ok(permit) => {
+            let response = next.run(req).await;
+            drop(permit);
+            response
+        }
+        Err(Limited::Rate { retry_after }) => {
+            // Whole seconds, rounded up so a retry at that time finds a token.
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            (
+                [(header::RETRY_AFTER, seconds.max(1).to_string())],
+                ApiError::too_many_requests("rate limit exceeded"),
+            )
+                .into_response()
+        }
+        Err(Limited::Searches) => (
+            [(header::RETRY_AFTER, "1".to_string())],
+            ApiError::too_many_requests("too many concurrent searches"),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(vars: &[(&str, &str)]) -> Arc<RateLimits> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Arc::new(RateLimits::from_lookup(|name| vars.get(name).cloned(), true).expect("limits"))
+    }
+
+    #[test]
+    fn buckets_refill_per_client_and_searches_are_capped() {
+        let limits = limits(&[
+            ("EMBEDDB_RATE_LIMIT_RPS", "2"),
+            ("EMBEDDB_RATE_LIMIT_BURST", "3"),
+            ("EMBEDDB_MAX_CONCURRENT_SEARCHES", "1"),
+        ]);
+        let start = Instant::now();
+        let a = Client::Key("a".to_string());
+        for _ in 0..3 {
+            assert!(limits.admit(a.clone(), false, start).is_ok());
+        }
+        let Err(Limited::Rate { retry_after }) = limits.admit(a.clone(), false, start) else {
+            panic!("expected the bucket to be empty");
+        };
+        assert_eq!(retry_after, Duration::from_millis(500));
+        // Other clients have their own buckets.
+        let b = Client::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert!(limits.admit(b.clone(), false, start).is_ok());
+        assert!(limits
+            .admit(a.clone(), false, start + Duration::from_millis(500))
+            .is_ok());
+
+        let later = start + Duration::from_secs(10);
+        let permit = limits.admit(b.clone(), true, later).expect("first search");
+        assert_eq!(
+            limits.admit(b.clone(), true, later).err(),
+            Some(Limited::Searches)
+        );
+        assert!(limits.admit(b.clone(), false, later).is_ok());
+        drop(permit);
+        assert!(limits.admit(b, true, later).is_ok());
+    }
+
+    #[test]
+    fn config_and_query_paths() {
+        let limits = limits(&[("EMBEDDB_RATE_LIMIT_RPS", "0.5")]);
+        assert_eq!(limits.rate, Some((0.5, 1.0)));
+        assert_eq!(limits.max_searches, None);
+        let lookup = |vars: &'static [(&'static str, &'static str)]| {
+            RateLimits::from_lookup(
+                |name| {
+                    vars.iter()
+                        .find(|(var, _)| *var == name)
+                        .map(|(_, value)| value.to_string())
+                },
+                false,
+            )
+        };
+        assert!(lookup(&[("EMBEDDB_RATE_LIMIT_RPS", "0")]).is_err());
+        assert!(lookup(&[("EMBEDDB_RATE_LIMIT_BURST", "5")]).is_err());
+        assert!(lookup(&[("EMBEDDB_MAX_CONCURRENT_SEARCHES", "none")]).is_err());
+
+        assert!(is_query_path("/tables/notes/search"));
+        assert!(is_query_path("/tables/notes/search/explain"));
+        assert!(is_query_path("/tables/notes/rows/7/similar"));
+        assert!(is_query_path("/namespaces/acme/tables/notes/search-hybrid"));
+        assert!(!is_query_path("/tables/notes/rows"));
+        assert!(!is_query_path("/tables/notes/rows/7"));
+        assert!(!is_query_path("/namespaces/acme/stats"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Managed snapshots (`EMBEDDB_SNAPSHOTS_DIR`): `POST /snapshots` checkpoints the default
+//! database into a new named subdirectory and `GET /snapshots` lists them, so backups can be taken
+//! remotely without passing server paths around.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{ApiError, AppState};
+
+#[derive(Debug, Serialize)]
+struct SnapshotInfo {
+    name: String,
+    path: PathBuf,
+    /// When the snapshot directory was renamed into place.
+    created_at_ms: u64,
+    bytes: u64,
+}
+
+fn enabled(state: &AppState) -> Result<PathBuf, ApiError> {
+    state.snapshots_dir.clone().ok_or_else(|| {
+        ApiError::not_found("managed snapshots are not enabled (set EMBEDDB_SNAPSHOTS_DIR)")
+    })
+}
+
+pub(crate) async fn list_snapshots(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root = enabled(&state)?;
+    let snapshots = tokio::task::spawn_blocking(move || list(&root))
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(serde_json::json!({ "snapshots": snapshots })))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct CreateSnapshotRequest {
+    /// Defaults to `snapshot-<epoch ms>`.
+    name: Option<String>,
+}
+
+pub(crate) async fn create_snapshot(
+    State(state): State<Arc<AppState>>,
+    req: Option<Json<CreateSnapshotRequest>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let root = enabled(&state)?;
+    let name = match req.and_then(|Json(req)| req.name) {
+        Some(name) => name,
+        None => format!("snapshot-{}", now_ms()),
+    };
+    validate_name(&name)?;
+    let path = root.join(&name);
+    if path.exists() {
+        return Err(ApiError::conflict(format!(
+            "snapshot '{name}' already exists"
+        )));
+    }
+    let stats = state
+        .db
+        .export_snapshot(path.clone())
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "name": name,
+            "path": path,
+            "files_copied": stats.files_copied,
+            "bytes_copied": stats.bytes_copied,
+        })),
+    ))
+}
+
+/// Snapshot names become directory names: 1-64 ASCII letters, digits, `-`, or `_`. That leaves
+/// out the `.partial` staging directories of snapshots still being copied.
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ApiError::bad_request(format!(
+            "invalid snapshot name '{name}' (use 1-64 letters, digits, '-' or '_')"
+        )))
+    }
+}
+
+/// The snapshots under `root`, oldest first.
+fn list(root: &Path) -> std::io::Result<Vec<SnapshotInfo>> {
+    let mut snapshots = Vec::new();
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(snapshots),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !entry.file_type()?.is_dir() || validate_name(&name).is_err() {
+            continue;
+        }
+        let created_at_ms = entry
+            .metadata()?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        snapshots.push(SnapshotInfo {
+            bytes: dir_bytes(&entry.path())?,
+            path: entry.path(),
+            name,
+            created_at_ms,
+        });
+    }
+    snapshots.sort_by(|a, b| (a.created_at_ms, &a.name).cmp(&(b.created_at_ms, &b.name)));
+    Ok(snapshots)
+}
+
+fn dir_bytes(dir: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += dir_bytes(&entry.path())?;
+        } else if file_type.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+fn now_ms() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis())
+}
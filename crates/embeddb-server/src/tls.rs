@@ -0,0 +1,393 @@
+//! HTTPS (`tls` feature). `EMBEDDB_TLS_CERT` and `EMBEDDB_TLS_KEY` name PEM files with the
+//! server's certificate chain and private key; `EMBEDDB_TLS_CLIENT_CA` additionally requires every
+//! client to present a certificate issued by one of the CAs in that PEM file (mutual TLS), and
+//! `EMBEDDB_TLS_REDIRECT_ADDR` serves plain HTTP on a second address that only redirects to HTTPS.
+//! `EMBEDDB_TLS_CONFIG` names a JSON file with the same settings, which the env vars override.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+
+/// TLS settings, read from the JSON file named by `EMBEDDB_TLS_CONFIG` and overridden field by
+/// field by the `EMBEDDB_TLS_*` env vars.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct TlsConfig {
+    pub(crate) cert: Option<PathBuf>,
+    pub(crate) key: Option<PathBuf>,
+    pub(crate) client_ca: Option<PathBuf>,
+    pub(crate) redirect_addr: Option<SocketAddr>,
+}
+
+impl TlsConfig {
+    /// The configured settings, or `None` when TLS is not configured.
+    pub(crate) fn from_env() -> Result<Option<Self>> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Option<Self>> {
+        let mut config = match lookup("EMBEDDB_TLS_CONFIG") {
+            Some(path) => {
+                let data = std::fs::read_to_string(&path)
+                    .with_context(|| format!("reading EMBEDDB_TLS_CONFIG {path}"))?;
+                serde_json::from_str(&data)
+                    .with_context(|| format!("parsing EMBEDDB_TLS_CONFIG {path}"))?
+            }
+            None => Self::default(),
+        };
+        if let Some(path) = lookup("EMBEDDB_TLS_CERT") {
+            config.cert = Some(PathBuf::from(path));
+        }
+        if let Some(path) = lookup("EMBEDDB_TLS_KEY") {
+            config.key = Some(PathBuf::from(path));
+        }
+        if let Some(path) = lookup("EMBEDDB_TLS_CLIENT_CA") {
+            config.client_ca = Some(PathBuf::from(path));
+        }
+        if let Some(raw) = lookup("EMBEDDB_TLS_REDIRECT_ADDR") {
+            config.redirect_addr = Some(
+                raw.parse()
+                    .map_err(|_| anyhow!("invalid EMBEDDB_TLS_REDIRECT_ADDR"))?,
+            );
+        }
+        match (&config.cert, &config.key) {
+            (Some(_), Some(_)) => Ok(Some(config)),
+            (None, None) if config.client_ca.is_none() && config.redirect_addr.is_none() => {
+                Ok(None)
+            }
+            _ => Err(anyhow!(
+                "TLS needs both a certificate (EMBEDDB_TLS_CERT) and a key (EMBEDDB_TLS_KEY)"
+            )),
+        }
+    }
+
+    /// Reads the certificates and key into a rustls config serving HTTP/1.1.
+    pub(crate) fn server_config(&self) -> Result<Arc<ServerConfig>> {
+        let (Some(cert), Some(key)) = (&self.cert, &self.key) else {
+            return Err(anyhow!("TLS certificate and key are not configured"));
+        };
+        let chain = read_certs(cert)?;
+        let key = PrivateKeyDer::from_pem_file(key)
+            .map_err(|err| anyhow!("reading TLS key {}: {err}", key.display()))?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path)? {
+                    roots
+                        .add(cert)
+                        .with_context(|| format!("adding client CA {}", path.display()))?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder
+            .with_single_cert(chain, key)
+            .context("TLS certificate does not match the key")?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| anyhow!("reading certificates {}: {err}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// Serves `app` over TLS on `listener` until `shutdown` resolves, then waits for open
+/// connections to finish their requests. Like `axum::serve` with `ConnectInfo<SocketAddr>`,
+/// handlers see the peer address.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    app: Router,
+    config: Arc<ServerConfig>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::warn!("accepting a connection failed: {err}");
+                    continue;
+                }
+            },
+            () = &mut shutdown => break,
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            // Handshake failures (including rejected client certificates) only affect this peer.
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::debug!(%peer, "TLS handshake failed: {err}");
+                    return;
+                }
+            };
+            let service = app.map_request(move |mut req: Request<Incoming>| {
+                req.extensions_mut().insert(ConnectInfo(peer));
+                req
+            });
+            let conn = hyper::server::conn::http1::Builder::new()
+                .timer(TokioTimer::new())
+                .serve_connection(TokioIo::new(stream), TowerToHyperService::new(service));
+            if let Err(err) = watcher.watch(conn).await {
+                tracing::debug!(%peer, "connection ended with an error: {err}");
+            }
+        });
+    }
+    drop(listener);
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// Serves plain HTTP on `listener`, redirecting every request to the same path over HTTPS on
+/// `https_port`.
+pub(crate) async fn serve_redirect(
+    listener: TcpListener,
+    https_port: u16,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let app = Router::new().fallback(move |req: Request| async move { redirect(&req, https_port) });
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+fn redirect(req: &Request, https_port: u16) -> Response {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok());
+    match https_location(host, req.uri(), https_port) {
+        Some(location) => Redirect::permanent(&location).into_response(),
+        None => (StatusCode::BAD_REQUEST, "missing Host header").into_response(),
+    }
+}
+
+/// `https://` URL of the request's host on `https_port`, keeping its path and query.
+fn https_location(host: Option<&str>, uri: &Uri, https_port: u16) -> Option<String> {
+    let authority = host
+        .and_then(|host| host.parse::<axum::http::uri::Authority>().ok())
+        .or_else(|| uri.authority().cloned())?;
+    let host = authority.host();
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    Some(if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::routing::get;
+    use rustls::pki_types::ServerName;
+    use rustls::ClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    use super::*;
+
+    fn lookup(vars: &[(&str, &str)]) -> Result<Option<TlsConfig>> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        TlsConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn config_and_redirect_locations() {
+        assert!(lookup(&[]).unwrap().is_none());
+        assert!(lookup(&[("EMBEDDB_TLS_CERT", "cert.pem")]).is_err());
+        assert!(lookup(&[("EMBEDDB_TLS_CLIENT_CA", "ca.pem")]).is_err());
+        assert!(lookup(&[
+            ("EMBEDDB_TLS_CERT", "cert.pem"),
+            ("EMBEDDB_TLS_KEY", "key.pem"),
+            ("EMBEDDB_TLS_REDIRECT_ADDR", "nowhere"),
+        ])
+        .is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("tls.json");
+        std::fs::write(
+            &file,
+            r#"{"cert": "a.pem", "key": "b.pem", "redirect_addr": "0.0.0.0:80"}"#,
+        )
+        .unwrap();
+        let config = lookup(&[
+            ("EMBEDDB_TLS_CONFIG", file.to_str().unwrap()),
+            ("EMBEDDB_TLS_KEY", "c.pem"),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.cert, Some(PathBuf::from("a.pem")));
+        assert_eq!(config.key, Some(PathBuf::from("c.pem")));
+        assert_eq!(config.redirect_addr, Some("0.0.0.0:80".parse().unwrap()));
+
+        let uri: Uri = "/tables?limit=2".parse().unwrap();
+        assert_eq!(
+            https_location(Some("db.example:8080"), &uri, 8443).as_deref(),
+            Some("https://db.example:8443/tables?limit=2")
+        );
+        assert_eq!(
+            https_location(Some("db.example"), &uri, 443).as_deref(),
+            Some("https://db.example/tables?limit=2")
+        );
+        assert_eq!(https_location(None, &uri, 443), None);
+    }
+
+    struct Pki {
+        ca: rcgen::Certificate,
+        ca_key: rcgen::KeyPair,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            let ca_key = rcgen::KeyPair::generate().unwrap();
+            let mut params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+            params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            let ca = params.self_signed(&ca_key).unwrap();
+            Self { ca, ca_key }
+        }
+
+        /// A certificate for `localhost` signed by the CA, and its key, as PEM.
+        fn issue(&self) -> (String, String) {
+            let key = rcgen::KeyPair::generate().unwrap();
+            let params = rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+            let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        }
+    }
+
+    fn client(pki: &Pki, identity: Option<(String, String)>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add(pki.ca.der().clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = match identity {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                    PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        TlsConnector::from(Arc::new(config))
+    }
+
+    async fn get_health(addr: SocketAddr, connector: &TlsConnector) -> std::io::Result<String> {
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector.connect(name, stream).await?;
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn serves_https_and_requires_client_certificates() {
+        let pki = Pki::new();
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = pki.issue();
+        let write = |name: &str, pem: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, pem).unwrap();
+            path
+        };
+        let mut config = TlsConfig {
+            cert: Some(write("cert.pem", &cert)),
+            key: Some(write("key.pem", &key)),
+            ..TlsConfig::default()
+        };
+        let app = Router::new().route(
+            "/health",
+            get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move {
+                format!("ok from {}", peer.ip())
+            }),
+        );
+        let start = |config: &TlsConfig| {
+            let server_config = config.server_config().unwrap();
+            let app = app.clone();
+            async move {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = listener.local_addr().unwrap();
+                let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+                let server = tokio::spawn(serve(listener, app, server_config, async {
+                    let _ = stopped.await;
+                }));
+                (addr, stop, server)
+            }
+        };
+
+        let (addr, stop, server) = start(&config).await;
+        let response = get_health(addr, &client(&pki, None)).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("ok from 127.0.0.1"), "{response}");
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+
+        config.client_ca = Some(write("ca.pem", &pki.ca.pem()));
+        let (addr, stop, server) = start(&config).await;
+        assert!(get_health(addr, &client(&pki, None)).await.is_err());
+        let response = get_health(addr, &client(&pki, Some(pki.issue())))
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        // A certificate from another CA is turned away too.
+        let stranger = Pki::new();
+        assert!(get_health(addr, &client(&pki, Some(stranger.issue())))
+            .await
+            .is_err());
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -322,6 +322,7 @@ function renderStats() {
   if (!state.stats) return;
   const stats = state.stats;
   const items = [
+    ["Rows", stats.total_rows],
     ["Rows (mem)", stats.rows_mem],
     ["Embeddings", stats.embeddings_total],
     ["Pending", stats.embeddings_pending],
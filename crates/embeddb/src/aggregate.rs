@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::{DataType, RowData, TableSchema, Value};
+use crate::value_as_f64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregateFn {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// One aggregate to compute per group. `Count` without a column counts rows; with a column it
+/// counts non-null values. The other functions require a numeric column and skip nulls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Aggregation {
+    pub op: AggregateFn,
+    #[serde(default)]
+    pub column: Option<String>,
+}
+
+impl Aggregation {
+    pub fn count() -> Self {
+        Self {
+            op: AggregateFn::Count,
+            column: None,
+        }
+    }
+
+    pub fn new(op: AggregateFn, column: impl Into<String>) -> Self {
+        Self {
+            op,
+            column: Some(column.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateRow {
+    /// Group key values, in `group_by` order.
+    pub group: Vec<Value>,
+    /// Aggregate results, in `aggs` order. `Null` when a group has no values for the column.
+    pub values: Vec<Value>,
+}
+
+pub(crate) fn validate(
+    schema: &TableSchema,
+    group_by: &[String],
+    aggs: &[Aggregation],
+) -> Result<()> {
+    let column_type = |name: &str| {
+        schema
+            .columns
+            .iter()
+            .find(|col| col.name == name)
+            .map(|col| col.data_type.clone())
+    };
+
+    for column in group_by {
+        column_type(column).ok_or_else(|| anyhow!("unknown group_by column '{column}'"))?;
+    }
+    if aggs.is_empty() {
+        return Err(anyhow!("at least one aggregation is required"));
+    }
+    for agg in aggs {
+        match (&agg.column, agg.op) {
+            (None, AggregateFn::Count) => {}
+            (None, op) => return Err(anyhow!("{op:?} requires a column")),
+            (Some(column), op) => {
+                let data_type = column_type(column)
+                    .ok_or_else(|| anyhow!("unknown aggregate column '{column}'"))?;
+                let numeric = matches!(data_type, DataType::Int | DataType::Float);
+                if op != AggregateFn::Count && !numeric {
+                    return Err(anyhow!(
+                        "{op:?} requires a numeric column, '{column}' is {data_type:?}"
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default)]
+struct Accumulator {
+    count: u64,
+    sum: f64,
+    min: Option<Value>,
+    max: Option<Value>,
+}
+
+impl Accumulator {
+    fn add(&mut self, value: &Value) {
+        self.count += 1;
+        let Some(x) = value_as_f64(value) else {
+            return;
+        };
+        self.sum += x;
+        if self
+            .min
+            .as_ref()
+            .and_then(value_as_f64)
+            .is_none_or(|min| x < min)
+        {
+            self.min = Some(value.clone());
+        }
+        if self
+            .max
+            .as_ref()
+            .and_then(value_as_f64)
+            .is_none_or(|max| x > max)
+        {
+            self.max = Some(value.clone());
+        }
+    }
+
+    fn finish(self, op: AggregateFn) -> Value {
+        match op {
+            AggregateFn::Count => Value::Int(self.count as i64),
+            _ if self.count == 0 => Value::Null,
+            AggregateFn::Sum => Value::Float(self.sum),
+            AggregateFn::Avg => Value::Float(self.sum / self.count as f64),
+            AggregateFn::Min => self.min.unwrap_or(Value::Null),
+            AggregateFn::Max => self.max.unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Groups rows and folds each aggregation; callers validate inputs with `validate` first.
+pub(crate) struct Aggregator<'a> {
+    group_by: &'a [String],
+    aggs: &'a [Aggregation],
+    // Keyed by the serialized group values so output order is deterministic.
+    groups: BTreeMap<String, (Vec<Value>, Vec<Accumulator>)>,
+}
+
+impl<'a> Aggregator<'a> {
+    pub(crate) fn new(group_by: &'a [String], aggs: &'a [Aggregation]) -> Self {
+        Self {
+            group_by,
+            aggs,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, row: &RowData) -> Result<()> {
+        let group: Vec<Value> = self
+            .group_by
+            .iter()
+            .map(|column| row.fields.get(column).cloned().unwrap_or(Value::Null))
+            .collect();
+        let key = serde_json::to_string(&group)?;
+        let (_, accumulators) = self
+            .groups
+            .entry(key)
+            .or_insert_with(|| (group, vec![Accumulator::default(); self.aggs.len()]));
+
+        for (agg, acc) in self.aggs.iter().zip(accumulators.iter_mut()) {
+            match &agg.column {
+                None => acc.count += 1,
+                Some(column) => match row.fields.get(column) {
+                    None | Some(Value::Null) => {}
+                    Some(value) => acc.add(value),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Vec<AggregateRow> {
+        let aggs = self.aggs;
+        self.groups
+            .into_values()
+            .map(|(group, accumulators)| AggregateRow {
+                group,
+                values: aggs
+                    .iter()
+                    .zip(accumulators)
+                    .map(|(agg, acc)| acc.finish(agg.op))
+                    .collect(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,870 @@
+//! Async handle onto an [`EmbedDb`] (feature `async`).
+//!
+//! Every `EmbedDb` call takes the database lock and may read files or sync the WAL, which stalls
+//! the calling thread. [`AsyncEmbedDb`] runs each call on tokio's blocking thread pool
+//! (`spawn_blocking`) so async tasks, such as HTTP handlers, keep their worker threads free. Its
+//! methods mirror `EmbedDb`'s; borrowed arguments are copied into the blocking task.
+//!
+//! ```no_run
+//! # use embeddb::{AsyncEmbedDb, Config};
+//! # async fn run() -> anyhow::Result<()> {
+//! let db = AsyncEmbedDb::open(Config::new("./data".into())).await?;
+//! let hits = db.search_knn("notes", &[0.1, 0.2], 5, None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+#[cfg(feature = "arrow")]
+use crate::ArrowBatches;
+use crate::{
+    audit, AggregateRow, Aggregation, AlterTableOp, AuditEntry, ChangePage, ChangeSubscription,
+    CheckpointStats, CompactionStats, Config, DbStats, DistanceFn, DistanceMetric,
+    DuplicateCluster, EmbedDb, Embedder, EmbeddingJob, EmbeddingJobPage, EmbeddingModel,
+    EmbeddingPage, EmbeddingRecord, EmbeddingSpec, EmbeddingStatus, FilterCondition, Fusion,
+    HistoricalView, IndexStatus, JobListOptions, ReembedPlan, RowData, RowPage, RowTrigger,
+    SearchExplain, SearchHit, SearchHitWithRow, SearchOptions, SnapshotStats, SparseVector,
+    TableDescriptor, TableSchema, TableStats, TableVerification, Value, WalEntry, WarmStats,
+    WriteOp, WriteOpResult,
+};
+
+/// Clonable async handle; clones share one database.
+#[derive(Debug, Clone)]
+pub struct AsyncEmbedDb {
+    db: Arc<EmbedDb>,
+}
+
+impl From<EmbedDb> for AsyncEmbedDb {
+    fn from(db: EmbedDb) -> Self {
+        Self { db: Arc::new(db) }
+    }
+}
+
+impl From<Arc<EmbedDb>> for AsyncEmbedDb {
+    fn from(db: Arc<EmbedDb>) -> Self {
+        Self { db }
+    }
+}
+
+tokio::task_local! {
+    static AUDIT_ACTOR: String;
+}
+
+/// Runs `future` with `actor` recorded as the actor of the audit entries for the writes its
+/// `AsyncEmbedDb` calls make, the async counterpart of `with_audit_actor`.
+pub async fn audit_actor_scope<F: Future>(actor: impl Into<String>, future: F) -> F::Output {
+    AUDIT_ACTOR.scope(actor.into(), future).await
+}
+
+/// Runs `f` on the blocking pool.
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| anyhow!("blocking task failed: {err}"))?
+}
+
+impl AsyncEmbedDb {
+    pub async fn open(config: Config) -> Result<Self> {
+        let db = blocking(move || EmbedDb::open(config)).await?;
+        Ok(db.into())
+    }
+
+    pub async fn restore_snapshot(
+        snapshot_dir: impl AsRef<Path>,
+        data_dir: impl AsRef<Path>,
+    ) -> Result<SnapshotStats> {
+        let snapshot_dir = snapshot_dir.as_ref().to_path_buf();
+        let data_dir = data_dir.as_ref().to_path_buf();
+        blocking(move || EmbedDb::restore_snapshot(snapshot_dir, data_dir)).await
+    }
+
+    /// The synchronous database, for callers that are already off the async workers.
+    pub fn blocking(&self) -> &Arc<EmbedDb> {
+        &self.db
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&EmbedDb) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.db.clone();
+        let actor = AUDIT_ACTOR.try_with(String::clone).ok();
+        blocking(move || audit::scoped(actor, || f(&db))).await
+    }
+
+    // Registrations only touch in-memory registries, so they run inline.
+    pub fn register_trigger(&self, trigger: Arc<dyn RowTrigger>) {
+        self.db.register_trigger(trigger);
+    }
+
+    pub fn register_metric(&self, name: &str, metric: Arc<dyn DistanceFn>) -> Result<()> {
+        self.db.register_metric(name, metric)
+    }
+
+    // So does reading the change feed.
+    pub fn changes_since(&self, table: &str, after: u64) -> Result<ChangePage> {
+        self.db.changes_since(table, after)
+    }
+
+    pub fn last_change_seq(&self) -> u64 {
+        self.db.last_change_seq()
+    }
+
+    /// Waits until a row change after feed position `after` is committed, without holding a
+    /// thread of the blocking pool.
+    pub async fn wait_for_change(&self, after: u64) {
+        self.db.changes.changed(after).await
+    }
+
+    /// See `EmbedDb::subscribe`; read the subscription with `ChangeSubscription::recv_async`.
+    pub async fn subscribe(&self, table: &str) -> Result<ChangeSubscription> {
+        let table = table.to_string();
+        self.run(move |db| db.subscribe(&table)).await
+    }
+
+    pub async fn subscribe_after(&self, table: &str, after: u64) -> Result<ChangeSubscription> {
+        let table = table.to_string();
+        self.run(move |db| db.subscribe_after(&table, after)).await
+    }
+
+    pub async fn db_stats(&self) -> Result<DbStats> {
+        self.run(|db| db.db_stats()).await
+    }
+
+    pub async fn list_tables(&self) -> Result<Vec<String>> {
+        self.run(|db| db.list_tables()).await
+    }
+
+    pub async fn describe_table(&self, table: &str) -> Result<TableDescriptor> {
+        let table = table.to_string();
+        self.run(move |db| db.describe_table(&table)).await
+    }
+
+    pub async fn table_stats(&self, table: &str) -> Result<TableStats> {
+        let table = table.to_string();
+        self.run(move |db| db.table_stats(&table)).await
+    }
+
+    pub async fn index_status(&self, table: &str) -> Result<IndexStatus> {
+        let table = table.to_string();
+        self.run(move |db| db.index_status(&table)).await
+    }
+
+    pub async fn warm_table(&self, table: &str) -> Result<WarmStats> {
+        let table = table.to_string();
+        self.run(move |db| db.warm_table(&table)).await
+    }
+
+    pub async fn explain_search(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<SearchExplain> {
+        let (table, query, metric) = (table.to_string(), query.to_vec(), metric.into());
+        let (filters, options) = (filters.to_vec(), options.clone());
+        self.run(move |db| db.explain_search(&table, &query, k, metric, &filters, &options))
+            .await
+    }
+
+    pub async fn create_table(
+        &self,
+        name: impl Into<String>,
+        schema: TableSchema,
+        embedding_spec: Option<EmbeddingSpec>,
+    ) -> Result<()> {
+        let name = name.into();
+        self.run(move |db| db.create_table(name, schema, embedding_spec))
+            .await
+    }
+
+    pub async fn insert_row(&self, table: &str, fields: BTreeMap<String, Value>) -> Result<u64> {
+        let table = table.to_string();
+        self.run(move |db| db.insert_row(&table, fields)).await
+    }
+
+    pub async fn insert_rows(
+        &self,
+        table: &str,
+        rows: Vec<BTreeMap<String, Value>>,
+    ) -> Result<Vec<u64>> {
+        let table = table.to_string();
+        self.run(move |db| db.insert_rows(&table, rows)).await
+    }
+
+    pub async fn insert_row_with_ttl(
+        &self,
+        table: &str,
+        fields: BTreeMap<String, Value>,
+        ttl: Duration,
+    ) -> Result<u64> {
+        let table = table.to_string();
+        self.run(move |db| db.insert_row_with_ttl(&table, fields, ttl))
+            .await
+    }
+
+    pub async fn insert_rows_with_ttl(
+        &self,
+        table: &str,
+        rows: Vec<BTreeMap<String, Value>>,
+        ttl: Duration,
+    ) -> Result<Vec<u64>> {
+        let table = table.to_string();
+        self.run(move |db| db.insert_rows_with_ttl(&table, rows, ttl))
+            .await
+    }
+
+    pub async fn insert_row_with_embedding(
+        &self,
+        table: &str,
+        fields: BTreeMap<String, Value>,
+        embedding: Vec<f32>,
+    ) -> Result<u64> {
+        let table = table.to_string();
+        self.run(move |db| db.insert_row_with_embedding(&table, fields, embedding))
+            .await
+    }
+
+    pub async fn insert_rows_with_embeddings(
+        &self,
+        table: &str,
+        rows: Vec<(BTreeMap<String, Value>, Vec<f32>)>,
+    ) -> Result<Vec<u64>> {
+        let table = table.to_string();
+        self.run(move |db| db.insert_rows_with_embeddings(&table, rows))
+            .await
+    }
+
+    pub async fn update_row(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+    ) -> Result<u64> {
+        let table = table.to_string();
+        self.run(move |db| db.update_row(&table, row_id, fields))
+            .await
+    }
+
+    pub async fn update_row_if_version(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+        expected_version: u64,
+    ) -> Result<u64> {
+        let table = table.to_string();
+        self.run(move |db| db.update_row_if_version(&table, row_id, fields, expected_version))
+            .await
+    }
+
+    pub async fn patch_row(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+    ) -> Result<RowData> {
+        let table = table.to_string();
+        self.run(move |db| db.patch_row(&table, row_id, fields))
+            .await
+    }
+
+    pub async fn patch_row_if_version(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+        expected_version: u64,
+    ) -> Result<RowData> {
+        let table = table.to_string();
+        self.run(move |db| db.patch_row_if_version(&table, row_id, fields, expected_version))
+            .await
+    }
+
+    pub async fn delete_row(&self, table: &str, row_id: u64) -> Result<()> {
+        let table = table.to_string();
+        self.run(move |db| db.delete_row(&table, row_id)).await
+    }
+
+    pub async fn delete_row_if_version(
+        &self,
+        table: &str,
+        row_id: u64,
+        expected_version: u64,
+    ) -> Result<()> {
+        let table = table.to_string();
+        self.run(move |db| db.delete_row_if_version(&table, row_id, expected_version))
+            .await
+    }
+
+    pub async fn write_batch(&self, ops: Vec<WriteOp>) -> Result<Vec<WriteOpResult>> {
+        self.run(move |db| db.write_batch(ops)).await
+    }
+
+    pub async fn delete_rows_where(
+        &self,
+        table: &str,
+        filters: &[FilterCondition],
+    ) -> Result<usize> {
+        let (table, filters) = (table.to_string(), filters.to_vec());
+        self.run(move |db| db.delete_rows_where(&table, &filters))
+            .await
+    }
+
+    pub async fn delete_range(&self, table: &str, range: Range<u64>) -> Result<usize> {
+        let table = table.to_string();
+        self.run(move |db| db.delete_range(&table, range)).await
+    }
+
+    pub async fn restore_row(&self, table: &str, row_id: u64) -> Result<RowData> {
+        let table = table.to_string();
+        self.run(move |db| db.restore_row(&table, row_id)).await
+    }
+
+    pub async fn purge_hidden_rows(&self, table: &str) -> Result<usize> {
+        let table = table.to_string();
+        self.run(move |db| db.purge_hidden_rows(&table)).await
+    }
+
+    pub async fn plan_embedding_spec(
+        &self,
+        table: &str,
+        spec: &EmbeddingSpec,
+    ) -> Result<ReembedPlan> {
+        let (table, spec) = (table.to_string(), spec.clone());
+        self.run(move |db| db.plan_embedding_spec(&table, &spec))
+            .await
+    }
+
+    pub async fn apply_embedding_spec(
+        &self,
+        table: &str,
+        spec: EmbeddingSpec,
+    ) -> Result<ReembedPlan> {
+        let table = table.to_string();
+        self.run(move |db| db.apply_embedding_spec(&table, spec))
+            .await
+    }
+
+    pub async fn reembed_table(&self, table: &str, model: EmbeddingModel) -> Result<ReembedPlan> {
+        let table = table.to_string();
+        self.run(move |db| db.reembed_table(&table, model)).await
+    }
+
+    pub async fn alter_table(&self, table: &str, op: AlterTableOp) -> Result<()> {
+        let table = table.to_string();
+        self.run(move |db| db.alter_table(&table, op)).await
+    }
+
+    pub async fn rename_table(&self, table: &str, new_name: impl Into<String>) -> Result<()> {
+        let table = table.to_string();
+        let new_name = new_name.into();
+        self.run(move |db| db.rename_table(&table, new_name)).await
+    }
+
+    pub async fn get_row(&self, table: &str, row_id: u64) -> Result<Option<RowData>> {
+        let table = table.to_string();
+        self.run(move |db| db.get_row(&table, row_id)).await
+    }
+
+    pub async fn scan_rows(
+        &self,
+        table: &str,
+        start_after: Option<u64>,
+        limit: usize,
+    ) -> Result<RowPage> {
+        let table = table.to_string();
+        self.run(move |db| db.scan_rows(&table, start_after, limit))
+            .await
+    }
+
+    #[cfg(feature = "arrow")]
+    pub async fn export_arrow(&self, table: &str, batch_rows: usize) -> Result<ArrowBatches> {
+        let table = table.to_string();
+        self.run(move |db| db.export_arrow(&table, batch_rows))
+            .await
+    }
+
+    pub async fn list_embedding_jobs(&self, table: &str) -> Result<Vec<EmbeddingJob>> {
+        let table = table.to_string();
+        self.run(move |db| db.list_embedding_jobs(&table)).await
+    }
+
+    pub async fn list_embedding_jobs_all(
+        &self,
+        status: Option<EmbeddingStatus>,
+    ) -> Result<Vec<EmbeddingJob>> {
+        self.run(move |db| db.list_embedding_jobs_all(status)).await
+    }
+
+    pub async fn list_embedding_jobs_page(
+        &self,
+        table: &str,
+        status: Option<EmbeddingStatus>,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EmbeddingJobPage> {
+        let table = table.to_string();
+        self.run(move |db| db.list_embedding_jobs_page(&table, status, cursor, limit))
+            .await
+    }
+
+    pub async fn list_embedding_jobs_with_options(
+        &self,
+        table: &str,
+        options: &JobListOptions,
+    ) -> Result<EmbeddingJobPage> {
+        let (table, options) = (table.to_string(), options.clone());
+        self.run(move |db| db.list_embedding_jobs_with_options(&table, &options))
+            .await
+    }
+
+    pub async fn scroll_embeddings(
+        &self,
+        table: &str,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EmbeddingPage> {
+        let table = table.to_string();
+        self.run(move |db| db.scroll_embeddings(&table, cursor, limit))
+            .await
+    }
+
+    pub async fn get_embedding(&self, table: &str, row_id: u64) -> Result<Option<EmbeddingRecord>> {
+        let table = table.to_string();
+        self.run(move |db| db.get_embedding(&table, row_id)).await
+    }
+
+    pub async fn retry_failed_jobs(&self, table: &str, row_id: Option<u64>) -> Result<usize> {
+        let table = table.to_string();
+        self.run(move |db| db.retry_failed_jobs(&table, row_id))
+            .await
+    }
+
+    /// Embedders are shared with the blocking task, so they are passed as `Arc`s here.
+    pub async fn process_pending_jobs(
+        &self,
+        table: &str,
+        embedder: Arc<dyn Embedder>,
+    ) -> Result<usize> {
+        let table = table.to_string();
+        self.run(move |db| db.process_pending_jobs(&table, embedder.as_ref()))
+            .await
+    }
+
+    pub async fn process_pending_jobs_with_limit(
+        &self,
+        table: &str,
+        embedder: Arc<dyn Embedder>,
+        limit: usize,
+    ) -> Result<usize> {
+        let table = table.to_string();
+        self.run(move |db| db.process_pending_jobs_with_limit(&table, embedder.as_ref(), limit))
+            .await
+    }
+
+    pub async fn process_all_pending(
+        &self,
+        embedder: Arc<dyn Embedder>,
+        limit: usize,
+    ) -> Result<usize> {
+        self.run(move |db| db.process_all_pending(embedder.as_ref(), limit))
+            .await
+    }
+
+    pub async fn put_embedding(&self, table: &str, row_id: u64, vector: Vec<f32>) -> Result<()> {
+        let table = table.to_string();
+        self.run(move |db| db.put_embedding(&table, row_id, vector))
+            .await
+    }
+
+    pub async fn search_knn(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query, metric) = (table.to_string(), query.to_vec(), metric.into());
+        self.run(move |db| db.search_knn(&table, &query, k, metric))
+            .await
+    }
+
+    pub async fn search_knn_filtered(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query, metric) = (table.to_string(), query.to_vec(), metric.into());
+        let filters = filters.to_vec();
+        self.run(move |db| db.search_knn_filtered(&table, &query, k, metric, &filters))
+            .await
+    }
+
+    pub async fn search_knn_with_options(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query, metric) = (table.to_string(), query.to_vec(), metric.into());
+        let (filters, options) = (filters.to_vec(), options.clone());
+        self.run(move |db| {
+            db.search_knn_with_options(&table, &query, k, metric, &filters, &options)
+        })
+        .await
+    }
+
+    pub async fn search_knn_with_rows(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHitWithRow>> {
+        let (table, query, metric) = (table.to_string(), query.to_vec(), metric.into());
+        let (filters, options) = (filters.to_vec(), options.clone());
+        self.run(move |db| db.search_knn_with_rows(&table, &query, k, metric, &filters, &options))
+            .await
+    }
+
+    pub async fn put_sparse_vector(
+        &self,
+        table: &str,
+        row_id: u64,
+        vector: SparseVector,
+    ) -> Result<()> {
+        let table = table.to_string();
+        self.run(move |db| db.put_sparse_vector(&table, row_id, vector))
+            .await
+    }
+
+    pub async fn get_sparse_vector(
+        &self,
+        table: &str,
+        row_id: u64,
+    ) -> Result<Option<SparseVector>> {
+        let table = table.to_string();
+        self.run(move |db| db.get_sparse_vector(&table, row_id))
+            .await
+    }
+
+    pub async fn search_sparse(
+        &self,
+        table: &str,
+        query: &SparseVector,
+        k: usize,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query, filters) = (table.to_string(), query.clone(), filters.to_vec());
+        self.run(move |db| db.search_sparse(&table, &query, k, &filters))
+            .await
+    }
+
+    pub async fn search_hybrid_sparse(
+        &self,
+        table: &str,
+        dense_query: &[f32],
+        sparse_query: &SparseVector,
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let (table, dense_query, metric) = (table.to_string(), dense_query.to_vec(), metric.into());
+        let (sparse_query, filters) = (sparse_query.clone(), filters.to_vec());
+        self.run(move |db| {
+            db.search_hybrid_sparse(&table, &dense_query, &sparse_query, k, metric, &filters)
+        })
+        .await
+    }
+
+    pub async fn search_keyword(
+        &self,
+        table: &str,
+        query_text: &str,
+        k: usize,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query_text, filters) =
+            (table.to_string(), query_text.to_string(), filters.to_vec());
+        self.run(move |db| db.search_keyword(&table, &query_text, k, &filters))
+            .await
+    }
+
+    pub async fn search_hybrid(
+        &self,
+        table: &str,
+        query_text: &str,
+        query_vec: &[f32],
+        k: usize,
+        fusion: Fusion,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query_text, query_vec) = (
+            table.to_string(),
+            query_text.to_string(),
+            query_vec.to_vec(),
+        );
+        let filters = filters.to_vec();
+        self.run(move |db| db.search_hybrid(&table, &query_text, &query_vec, k, fusion, &filters))
+            .await
+    }
+
+    pub async fn search_knn_batch(
+        &self,
+        table: &str,
+        queries: &[Vec<f32>],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+    ) -> Result<Vec<Vec<SearchHit>>> {
+        let (table, queries, metric) = (table.to_string(), queries.to_vec(), metric.into());
+        self.run(move |db| db.search_knn_batch(&table, &queries, k, metric))
+            .await
+    }
+
+    pub async fn search_knn_named(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: &str,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let (table, query, metric) = (table.to_string(), query.to_vec(), metric.to_string());
+        let (filters, options) = (filters.to_vec(), options.clone());
+        self.run(move |db| db.search_knn_named(&table, &query, k, &metric, &filters, &options))
+            .await
+    }
+
+    pub async fn search_similar(
+        &self,
+        table: &str,
+        row_id: u64,
+        k: usize,
+        exclude_self: bool,
+    ) -> Result<Vec<SearchHit>> {
+        let table = table.to_string();
+        self.run(move |db| db.search_similar(&table, row_id, k, exclude_self))
+            .await
+    }
+
+    pub async fn recommend(
+        &self,
+        table: &str,
+        positive: &[u64],
+        negative: &[u64],
+        k: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let (table, positive, negative) = (table.to_string(), positive.to_vec(), negative.to_vec());
+        self.run(move |db| db.recommend(&table, &positive, &negative, k))
+            .await
+    }
+
+    pub async fn find_duplicates(
+        &self,
+        table: &str,
+        threshold: f32,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<DuplicateCluster>> {
+        let table = table.to_string();
+        self.run(move |db| db.find_duplicates(&table, threshold, metric))
+            .await
+    }
+
+    pub async fn count_rows(&self, table: &str, filters: &[FilterCondition]) -> Result<u64> {
+        let (table, filters) = (table.to_string(), filters.to_vec());
+        self.run(move |db| db.count_rows(&table, &filters)).await
+    }
+
+    pub async fn aggregate(
+        &self,
+        table: &str,
+        filters: &[FilterCondition],
+        group_by: &[String],
+        aggs: &[Aggregation],
+    ) -> Result<Vec<AggregateRow>> {
+        let (table, filters) = (table.to_string(), filters.to_vec());
+        let (group_by, aggs) = (group_by.to_vec(), aggs.to_vec());
+        self.run(move |db| db.aggregate(&table, &filters, &group_by, &aggs))
+            .await
+    }
+
+    pub async fn expire_rows(&self, table: &str) -> Result<usize> {
+        let table = table.to_string();
+        self.run(move |db| db.expire_rows(&table)).await
+    }
+
+    pub async fn flush_table(&self, table: &str) -> Result<()> {
+        let table = table.to_string();
+        self.run(move |db| db.flush_table(&table)).await
+    }
+
+    pub async fn compact_table(&self, table: &str) -> Result<CompactionStats> {
+        let table = table.to_string();
+        self.run(move |db| db.compact_table(&table)).await
+    }
+
+    pub async fn compact_table_if_due(&self, table: &str) -> Result<Option<CompactionStats>> {
+        let table = table.to_string();
+        self.run(move |db| db.compact_table_if_due(&table)).await
+    }
+
+    pub async fn verify_table(&self, table: &str) -> Result<TableVerification> {
+        let table = table.to_string();
+        self.run(move |db| db.verify_table(&table)).await
+    }
+
+    pub async fn repair_table(&self, table: &str) -> Result<TableVerification> {
+        let table = table.to_string();
+        self.run(move |db| db.repair_table(&table)).await
+    }
+
+    pub async fn current_lsn(&self) -> Result<u64> {
+        self.run(|db| db.current_lsn()).await
+    }
+
+    pub async fn audit_log(&self, since_ms: u64) -> Result<Vec<AuditEntry>> {
+        self.run(move |db| db.audit_log(since_ms)).await
+    }
+
+    pub async fn read_at_lsn(&self, lsn: u64) -> Result<HistoricalView> {
+        self.run(move |db| db.read_at_lsn(lsn)).await
+    }
+
+    pub async fn snapshot(&self) -> Result<HistoricalView> {
+        self.run(|db| db.snapshot()).await
+    }
+
+    pub async fn wal_since(&self, lsn: u64) -> Result<Vec<WalEntry>> {
+        self.run(move |db| db.wal_since(lsn)).await
+    }
+
+    pub async fn apply_wal_records(&self, entries: Vec<WalEntry>) -> Result<u64> {
+        self.run(move |db| db.apply_wal_records(&entries)).await
+    }
+
+    pub async fn checkpoint(&self) -> Result<CheckpointStats> {
+        self.run(|db| db.checkpoint()).await
+    }
+
+    pub async fn close(&self) -> Result<()> {
+        self.run(|db| db.close()).await
+    }
+
+    pub async fn export_snapshot(&self, dest_dir: impl AsRef<Path>) -> Result<SnapshotStats> {
+        let dest_dir: PathBuf = dest_dir.as_ref().to_path_buf();
+        self.run(move |db| db.export_snapshot(dest_dir)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Column, DataType};
+
+    struct LenEmbedder;
+
+    impl Embedder for LenEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            Ok(vec![input.len() as f32])
+        }
+    }
+
+    #[tokio::test]
+    async fn async_handle_runs_calls_off_the_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = AsyncEmbedDb::open(Config::new(dir.path().to_path_buf()))
+            .await
+            .unwrap();
+        db.create_table(
+            "notes",
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2)),
+        )
+        .await
+        .unwrap();
+        let mut ids = Vec::new();
+        for title in ["a", "abc"] {
+            let mut fields = BTreeMap::new();
+            fields.insert("title".to_string(), Value::String(title.to_string()));
+            ids.push(db.insert_row("notes", fields).await.unwrap());
+        }
+        assert_eq!(
+            db.process_pending_jobs("notes", Arc::new(LenEmbedder))
+                .await
+                .unwrap(),
+            2
+        );
+
+        let hits = db.search_knn("notes", &[3.0], 1, None).await.unwrap();
+        assert_eq!(hits[0].row_id, ids[1]);
+        let row = db.get_row("notes", ids[0]).await.unwrap().unwrap();
+        assert_eq!(row.fields["title"], Value::String("a".to_string()));
+        assert!(db.describe_table("missing").await.is_err());
+        assert_eq!(db.blocking().list_tables().unwrap(), vec!["notes"]);
+    }
+
+    #[tokio::test]
+    async fn subscriptions_wake_async_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = AsyncEmbedDb::open(Config::new(dir.path().to_path_buf()))
+            .await
+            .unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, None).await.unwrap();
+        let mut subscription = db.subscribe("notes").await.unwrap();
+
+        let writer = db.clone();
+        let insert = tokio::spawn(async move {
+            let fields = BTreeMap::from([("title".to_string(), Value::String("a".to_string()))]);
+            writer.insert_row("notes", fields).await.unwrap()
+        });
+        let event = subscription.recv_async().await.unwrap();
+        assert_eq!(event.row_id, insert.await.unwrap());
+        assert_eq!(event.seq, db.last_change_seq());
+    }
+
+    #[tokio::test]
+    async fn audit_actor_scope_reaches_the_blocking_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf()).with_audit_log(Default::default());
+        let db = AsyncEmbedDb::open(config).await.unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, None).await.unwrap();
+        let fields = BTreeMap::from([("title".to_string(), Value::String("a".to_string()))]);
+        let row_id = audit_actor_scope("alice", db.insert_row("notes", fields))
+            .await
+            .unwrap();
+
+        let entries = db.audit_log(0).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].actor, None);
+        assert_eq!(entries[1].actor.as_deref(), Some("alice"));
+        assert_eq!(entries[1].row_id, Some(row_id));
+    }
+}
@@ -0,0 +1,335 @@
+//! The audit log: who changed which rows and tables, and when. Entries are JSON lines under
+//! `<data_dir>/audit/`, apart from the WAL, so checkpoints never truncate them and they rotate
+//! and expire on their own schedule.
+
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::fault;
+use crate::storage::wal::WalRecord;
+
+const ACTIVE_FILE: &str = "audit.log";
+
+thread_local! {
+    static ACTOR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `actor` recorded as the actor of the audit entries for the writes it makes on
+/// this thread. `AsyncEmbedDb` calls take theirs from `audit_actor_scope` instead.
+pub fn with_audit_actor<T>(actor: impl Into<String>, f: impl FnOnce() -> T) -> T {
+    scoped(Some(actor.into()), f)
+}
+
+pub(crate) fn scoped<T>(actor: Option<String>, f: impl FnOnce() -> T) -> T {
+    // Restores the outer actor even if `f` panics.
+    struct Restore(Option<String>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let outer = self.0.take();
+            ACTOR.with(|current| *current.borrow_mut() = outer);
+        }
+    }
+
+    let _restore = Restore(ACTOR.with(|current| current.replace(actor)));
+    f()
+}
+
+fn current_actor() -> Option<String> {
+    ACTOR.with(|current| current.borrow().clone())
+}
+
+fn default_max_file_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+/// Rotation and retention of the audit log, set with `Config::with_audit_log`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Start a new file once the current one would grow past this size.
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Delete the oldest rotated files beyond this many; `None` keeps them all.
+    #[serde(default)]
+    pub max_files: Option<usize>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: default_max_file_bytes(),
+            max_files: None,
+        }
+    }
+}
+
+impl AuditConfig {
+    pub fn with_max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    pub fn with_max_files(mut self, files: usize) -> Self {
+        self.max_files = Some(files);
+        self
+    }
+}
+
+/// What an audited write did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditOp {
+    CreateTable,
+    AlterTable,
+    RenameTable,
+    SetEmbeddingSpec,
+    Insert,
+    Update,
+    Delete,
+    /// A bulk delete of the row ids in `AuditEntry::ranges`.
+    DeleteRange,
+    /// A delete on a table with `TableSchema::soft_delete`, which hides the row.
+    SoftDelete,
+    Restore,
+    PutSparseVector,
+}
+
+/// One change recorded in the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// LSN of the WAL record that made the change.
+    pub lsn: u64,
+    /// Unix milliseconds.
+    pub at_ms: u64,
+    /// Who made the change, from `with_audit_actor`; `None` for writes made outside one, such as
+    /// TTL expiry by a background thread.
+    #[serde(default)]
+    pub actor: Option<String>,
+    pub op: AuditOp,
+    pub table: String,
+    #[serde(default)]
+    pub row_id: Option<u64>,
+    /// The row ids a `DeleteRange` removed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranges: Vec<Range<u64>>,
+    /// The table's new name, for `RenameTable`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_name: Option<String>,
+}
+
+impl AuditEntry {
+    /// The entry for `record`, or `None` for records that follow from other changes: embedding
+    /// jobs and vectors, row id allocation, expiry times, and checkpoint and batch markers.
+    fn for_record(
+        record: &WalRecord,
+        lsn: u64,
+        at_ms: u64,
+        actor: &Option<String>,
+    ) -> Option<Self> {
+        let mut ranges = Vec::new();
+        let mut new_name = None;
+        let op = match record {
+            WalRecord::CreateTable { .. } => AuditOp::CreateTable,
+            WalRecord::AlterTable { .. } => AuditOp::AlterTable,
+            WalRecord::RenameTable { new_name: name, .. } => {
+                new_name = Some(name.clone());
+                AuditOp::RenameTable
+            }
+            WalRecord::SetEmbeddingSpec { .. } => AuditOp::SetEmbeddingSpec,
+            // Inserts write a row's first version; updates bump it.
+            WalRecord::PutRow { row, .. } if row.version <= 1 => AuditOp::Insert,
+            WalRecord::PutRow { .. } => AuditOp::Update,
+            WalRecord::DeleteRow { .. } => AuditOp::Delete,
+            WalRecord::DeleteRanges {
+                ranges: deleted, ..
+            } => {
+                ranges = deleted.clone();
+                AuditOp::DeleteRange
+            }
+            WalRecord::HideRow { .. } => AuditOp::SoftDelete,
+            WalRecord::RestoreRow { .. } => AuditOp::Restore,
+            WalRecord::StoreSparseVector { .. } => AuditOp::PutSparseVector,
+            _ => return None,
+        };
+        Some(Self {
+            lsn,
+            at_ms,
+            actor: actor.clone(),
+            op,
+            table: record.table()?.to_string(),
+            row_id: record.row_id(),
+            ranges,
+            new_name,
+        })
+    }
+}
+
+fn audit_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("audit")
+}
+
+/// Rotated files are named by the Unix millisecond they were rotated at, so none of their
+/// entries is later than the name.
+fn rotated_path(dir: &Path, rotated_at_ms: u64) -> PathBuf {
+    dir.join(format!("audit_{rotated_at_ms:020}.log"))
+}
+
+/// The rotated files in `dir` with their rotation times, oldest first.
+fn list_rotated(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut files = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let rotated_at = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("audit_")?.strip_suffix(".log"))
+            .and_then(|ms| ms.parse::<u64>().ok());
+        if let Some(rotated_at) = rotated_at {
+            files.push((rotated_at, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The writer of `<data_dir>/audit/audit.log`, held in `Inner` so entries are appended in WAL
+/// order.
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    dir: PathBuf,
+    config: AuditConfig,
+    file: File,
+    bytes: u64,
+}
+
+impl AuditLog {
+    pub(crate) fn open(data_dir: &Path, config: AuditConfig) -> Result<Self> {
+        let dir = audit_dir(data_dir);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(ACTIVE_FILE);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(&path)
+            .with_context(|| format!("opening audit log {}", path.display()))?;
+        let mut bytes = file.metadata()?.len();
+        if bytes > 0 {
+            // End a line torn by a crash, so the next entry starts on its own.
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                fault::write_wal(&path, &mut file, b"\n")?;
+                bytes += 1;
+            }
+        }
+        Ok(Self {
+            dir,
+            config,
+            file,
+            bytes,
+        })
+    }
+
+    /// Records the changes `records` make, the first at `first_lsn`, rotating first if they
+    /// would take the file past `max_file_bytes`.
+    pub(crate) fn record(
+        &mut self,
+        records: &[WalRecord],
+        first_lsn: u64,
+        at_ms: u64,
+        sync: bool,
+    ) -> Result<()> {
+        let actor = current_actor();
+        let mut data = Vec::new();
+        for (lsn, record) in (first_lsn..).zip(records) {
+            if let Some(entry) = AuditEntry::for_record(record, lsn, at_ms, &actor) {
+                serde_json::to_writer(&mut data, &entry)?;
+                data.push(b'\n');
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.bytes > 0 && self.bytes + data.len() as u64 > self.config.max_file_bytes {
+            self.rotate(at_ms)?;
+        }
+        let path = self.dir.join(ACTIVE_FILE);
+        fault::write_wal(&path, &mut self.file, &data)?;
+        self.bytes += data.len() as u64;
+        if sync {
+            fault::sync_wal(&path, &self.file)?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, now_ms: u64) -> Result<()> {
+        let active = self.dir.join(ACTIVE_FILE);
+        fault::sync_wal(&active, &self.file)?;
+        let rotated = list_rotated(&self.dir)?;
+        // Keeps names unique, and ordered, when rotations come faster than once a millisecond.
+        let rotated_at = rotated
+            .last()
+            .map_or(now_ms, |(last, _)| now_ms.max(last + 1));
+        fault::rename(&active, &rotated_path(&self.dir, rotated_at))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(&active)?;
+        self.bytes = 0;
+        if let Some(max_files) = self.config.max_files {
+            let excess = (rotated.len() + 1).saturating_sub(max_files);
+            for (_, path) in rotated.iter().take(excess) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The entries at or after `since_ms` in every file under `<data_dir>/audit/`, oldest first.
+pub(crate) fn read_since(data_dir: &Path, since_ms: u64) -> Result<Vec<AuditEntry>> {
+    let dir = audit_dir(data_dir);
+    let mut files: Vec<PathBuf> = list_rotated(&dir)?
+        .into_iter()
+        .filter(|(rotated_at, _)| *rotated_at >= since_ms)
+        .map(|(_, path)| path)
+        .collect();
+    files.push(dir.join(ACTIVE_FILE));
+
+    let mut entries = Vec::new();
+    for path in files {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) if entry.at_ms >= since_ms => entries.push(entry),
+                Ok(_) => {}
+                // Only a crash mid-append leaves a partial line.
+                Err(err) => {
+                    tracing::warn!("skipping torn audit entry in {}: {err}", path.display())
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
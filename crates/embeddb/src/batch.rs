@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use crate::vector;
+use crate::DistanceMetric;
+
+/// Where `search_knn_batch` computes its distance matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ScoringBackend {
+    #[default]
+    Cpu,
+    /// Use a GPU adapter when the crate is built with the `gpu` feature and one is available.
+    /// Small batches and any GPU failure fall back to the CPU.
+    Gpu,
+}
+
+/// Below this many query/vector pairs the upload and readback cost outweighs the GPU speedup.
+#[cfg(feature = "gpu")]
+const GPU_MIN_PAIRS: usize = 1 << 16;
+
+/// Distances between every query and every candidate, row-major by query: entry
+/// `q * candidates_len + v`. Both inputs are flat arrays of `dim`-length vectors.
+pub(crate) fn distance_matrix(
+    queries: &[f32],
+    candidates: &[f32],
+    dim: usize,
+    metric: DistanceMetric,
+    backend: ScoringBackend,
+) -> Vec<f32> {
+    if dim == 0 {
+        return Vec::new();
+    }
+
+    #[cfg(feature = "gpu")]
+    if backend == ScoringBackend::Gpu {
+        let pairs = (queries.len() / dim).saturating_mul(candidates.len() / dim);
+        if pairs >= GPU_MIN_PAIRS {
+            match crate::gpu::distance_matrix(queries, candidates, dim, metric) {
+                Ok(out) => return out,
+                Err(err) => tracing::warn!("GPU batch scoring failed, using CPU: {err:#}"),
+            }
+        }
+    }
+    #[cfg(not(feature = "gpu"))]
+    let _ = backend;
+
+    cpu_distance_matrix(queries, candidates, dim, metric)
+}
+
+fn cpu_distance_matrix(
+    queries: &[f32],
+    candidates: &[f32],
+    dim: usize,
+    metric: DistanceMetric,
+) -> Vec<f32> {
+    let mut out = Vec::with_capacity((queries.len() / dim) * (candidates.len() / dim));
+    for query in queries.chunks_exact(dim) {
+        for candidate in candidates.chunks_exact(dim) {
+            out.push(vector::distance(query, candidate, metric));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_matrix_is_row_major_by_query() {
+        let queries = [0.0f32, 0.0, 1.0, 1.0];
+        let candidates = [1.0f32, 0.0, 0.0, 2.0, 1.0, 1.0];
+        let out = distance_matrix(
+            &queries,
+            &candidates,
+            2,
+            DistanceMetric::L2,
+            ScoringBackend::Gpu,
+        );
+        assert_eq!(out, vec![1.0, 4.0, 2.0, 1.0, 2.0, 0.0]);
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn gpu_matches_cpu_when_an_adapter_is_available() {
+        let dim = 16;
+        let queries: Vec<f32> = (0..dim * 64).map(|i| (i % 7) as f32 - 3.0).collect();
+        let candidates: Vec<f32> = (0..dim * 2048).map(|i| (i % 11) as f32 * 0.5).collect();
+        for metric in [DistanceMetric::L2, DistanceMetric::Cosine] {
+            let Ok(gpu) = crate::gpu::distance_matrix(&queries, &candidates, dim, metric) else {
+                // No adapter in this environment; the CPU path is covered above.
+                return;
+            };
+            let cpu = cpu_distance_matrix(&queries, &candidates, dim, metric);
+            for (a, b) in gpu.iter().zip(&cpu) {
+                assert!((a - b).abs() <= 1e-3 * b.abs().max(1.0), "{metric:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{DistanceMetric, FilterCondition, SearchHit, SearchOptions};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SearchCacheKey {
+    table: String,
+    // Exact bit patterns rather than a digest so distinct queries can never share an entry.
+    query: Vec<u32>,
+    k: usize,
+    metric: DistanceMetric,
+    filters: String,
+    allow_metric_mismatch: bool,
+    include_ids: Option<Vec<u64>>,
+    exclude_ids: Vec<u64>,
+}
+
+impl SearchCacheKey {
+    pub(crate) fn new(
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: DistanceMetric,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Self {
+        Self {
+            table: table.to_string(),
+            query: query.iter().map(|x| x.to_bits()).collect(),
+            k,
+            metric,
+            filters: serde_json::to_string(filters).unwrap_or_default(),
+            allow_metric_mismatch: options.allow_metric_mismatch,
+            include_ids: options
+                .include_ids
+                .as_ref()
+                .map(|ids| ids.iter().copied().collect()),
+            exclude_ids: options.exclude_ids.iter().copied().collect(),
+        }
+    }
+}
+
+/// LRU cache of search results. Entries for a table are dropped whenever that table is written.
+#[derive(Debug, Default)]
+pub(crate) struct SearchCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<SearchCacheKey, (u64, Vec<SearchHit>)>,
+    recency: BTreeMap<u64, SearchCacheKey>,
+    hits: u64,
+    misses: u64,
+}
+
+impl SearchCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Looks up `key`, counting the hit or miss.
+    pub(crate) fn get(&mut self, key: &SearchCacheKey) -> Option<Vec<SearchHit>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some((last_used, hits)) = self.entries.get_mut(key) else {
+            self.misses += 1;
+            return None;
+        };
+        self.hits += 1;
+        self.recency.remove(last_used);
+        self.recency.insert(tick, key.clone());
+        *last_used = tick;
+        Some(hits.clone())
+    }
+
+    pub(crate) fn insert(&mut self, key: SearchCacheKey, hits: Vec<SearchHit>) {
+        if !self.enabled() {
+            return;
+        }
+        self.tick += 1;
+        if let Some((last_used, _)) = self.entries.remove(&key) {
+            self.recency.remove(&last_used);
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (self.tick, hits));
+    }
+
+    pub(crate) fn invalidate_table(&mut self, table: &str) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let entries = &mut self.entries;
+        self.recency.retain(|_, key| {
+            if key.table == table {
+                entries.remove(key);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Lookups that found (`.0`) or missed (`.1`) an entry since open.
+    pub(crate) fn lookups(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(table: &str, x: f32) -> SearchCacheKey {
+        SearchCacheKey::new(
+            table,
+            &[x],
+            1,
+            DistanceMetric::L2,
+            &[],
+            &SearchOptions::default(),
+        )
+    }
+
+    fn hits(row_id: u64) -> Vec<SearchHit> {
+        vec![SearchHit {
+            row_id,
+            distance: 0.0,
+            score: None,
+        }]
+    }
+
+    #[test]
+    fn evicts_least_recently_used_and_invalidates_by_table() {
+        let mut cache = SearchCache::new(2);
+        cache.insert(key("a", 1.0), hits(1));
+        cache.insert(key("b", 2.0), hits(2));
+        // Touch "a" so "b" becomes the eviction candidate.
+        assert!(cache.get(&key("a", 1.0)).is_some());
+        cache.insert(key("a", 3.0), hits(3));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("b", 2.0)).is_none());
+        assert_eq!(cache.lookups(), (1, 1));
+
+        cache.invalidate_table("a");
+        assert_eq!(cache.len(), 0);
+        assert!(cache.get(&key("a", 1.0)).is_none());
+
+        let mut disabled = SearchCache::new(0);
+        disabled.insert(key("a", 1.0), hits(1));
+        assert!(disabled.get(&key("a", 1.0)).is_none());
+    }
+}
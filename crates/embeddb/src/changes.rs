@@ -0,0 +1,345 @@
+//! The change feed: the most recent committed row changes, numbered in the order they were
+//! committed, for readers that follow a table's writes instead of polling it. Writers stage their
+//! changes while they hold the write lock and publish them once durable, just before triggers
+//! see them. `ChangeSubscription` follows one table's share of it.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::trigger::{RowChange, RowChangeKind};
+
+pub(crate) fn default_change_feed_capacity() -> usize {
+    4096
+}
+
+/// One committed insert, update, or delete, as kept by the change feed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Position in the feed: the LSN the write that made the change committed at. Every change
+    /// of one write, such as a batch or a bulk delete, shares it; it counts up across all tables,
+    /// so a table's events can skip numbers, and it never repeats after a reopen.
+    pub seq: u64,
+    pub table: String,
+    pub op: RowChangeKind,
+    pub row_id: u64,
+}
+
+/// A table's changes read from the feed by `EmbedDb::changes_since`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangePage {
+    pub events: Vec<ChangeEvent>,
+    /// The feed position the events were read up to; pass it as `after` to read the next ones.
+    pub last_seq: u64,
+}
+
+/// Error from `EmbedDb::changes_since` when the changes after `after` are no longer held: more
+/// than `Config::change_feed_capacity` changes were committed since, or `after` is from before
+/// the database was last opened and later writes may have been missed. Reload whatever was
+/// derived from the table and follow the feed from `last_seq`. Recover it with
+/// `err.downcast_ref::<ChangesLagged>()`.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("changes after seq {after} are no longer in the change feed (now at seq {last_seq})")]
+pub struct ChangesLagged {
+    pub after: u64,
+    pub last_seq: u64,
+}
+
+#[derive(Debug)]
+struct FeedState {
+    events: VecDeque<ChangeEvent>,
+    // Every change after this seq is held: the LSN at open, then the last evicted seq.
+    floor: u64,
+    last_seq: u64,
+    // Writes staged by `stage`, by seq, waiting until every write staged before them is
+    // published or discarded.
+    staged: BTreeMap<u64, Staged>,
+    // Set by `EmbedDb::close`, which wakes every waiting subscription.
+    closed: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct ChangeFeed {
+    capacity: usize,
+    state: Mutex<FeedState>,
+    changed: Condvar,
+    #[cfg(feature = "async")]
+    notify: tokio::sync::Notify,
+}
+
+impl ChangeFeed {
+    /// A feed starting at `lsn`, the WAL's position at open, so a seq from an earlier open is at
+    /// most where this one starts.
+    pub(crate) fn new(capacity: usize, lsn: u64) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(FeedState {
+                events: VecDeque::new(),
+                floor: lsn,
+                last_seq: lsn,
+                staged: BTreeMap::new(),
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            #[cfg(feature = "async")]
+            notify: tokio::sync::Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FeedState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.lock().last_seq
+    }
+
+    pub(crate) fn since(&self, table: &str, after: u64) -> Result<ChangePage, ChangesLagged> {
+        let state = self.lock();
+        if after < state.floor || after > state.last_seq {
+            return Err(ChangesLagged {
+                after,
+                last_seq: state.last_seq,
+            });
+        }
+        // Seqs skip numbers and repeat within a write, so the first one after `after` is searched
+        // for.
+        let start = state.events.partition_point(|event| event.seq <= after);
+        let events = state
+            .events
+            .range(start..)
+            .filter(|event| event.table == table)
+            .cloned()
+            .collect();
+        Ok(ChangePage {
+            events,
+            last_seq: state.last_seq,
+        })
+    }
+
+    /// Numbers `changes` with `lsn`, the WAL position their write committed at. Call it under
+    /// the write lock that made them, so seqs follow commit order, and publish the result once
+    /// the write is durable; until then, readers see none of them or of any change staged later.
+    pub(crate) fn stage(&self, changes: &[RowChange], lsn: u64) -> StagedChanges<'_> {
+        let events: Vec<ChangeEvent> = changes
+            .iter()
+            .map(|change| ChangeEvent {
+                seq: lsn,
+                table: change.table.clone(),
+                op: change.kind,
+                row_id: change.row_id,
+            })
+            .collect();
+        if !events.is_empty() {
+            let staged = Staged {
+                events,
+                durable: false,
+            };
+            self.lock().staged.insert(lsn, staged);
+        }
+        StagedChanges {
+            feed: self,
+            seq: lsn,
+            settled: false,
+        }
+    }
+
+    /// Publishes or discards the write staged at `seq`, then moves every write at the front of
+    /// the queue that is durable into the feed.
+    fn settle(&self, seq: u64, durable: bool) {
+        let mut state = self.lock();
+        if durable {
+            if let Some(staged) = state.staged.get_mut(&seq) {
+                staged.durable = true;
+            }
+        } else {
+            state.staged.remove(&seq);
+        }
+        let mut published = false;
+        while let Some(entry) = state.staged.first_entry() {
+            if !entry.get().durable {
+                break;
+            }
+            for event in entry.remove().events {
+                state.last_seq = event.seq;
+                state.events.push_back(event);
+                published = true;
+            }
+        }
+        while state.events.len() > self.capacity {
+            if let Some(evicted) = state.events.pop_front() {
+                state.floor = evicted.seq;
+            }
+        }
+        drop(state);
+        if published {
+            self.wake();
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.wake();
+    }
+
+    fn wake(&self) {
+        self.changed.notify_all();
+        #[cfg(feature = "async")]
+        self.notify.notify_waiters();
+    }
+
+    /// Blocks until a change after `after` is committed, the feed is closed, or `timeout`
+    /// passes.
+    fn wait(&self, after: u64, timeout: Duration) {
+        let state = self.lock();
+        let _ = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| {
+                state.last_seq <= after && !state.closed
+            })
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// Resolves once a change after `after` is committed or the feed is closed.
+    #[cfg(feature = "async")]
+    pub(crate) async fn changed(&self, after: u64) {
+        loop {
+            // Registered before the check, so a change committed in between still wakes it.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let state = self.lock();
+                if state.last_seq > after || state.closed {
+                    return;
+                }
+            }
+            notified.await;
+        }
+    }
+}
+
+/// One table's changes, in commit order, from `EmbedDb::subscribe`. Events arrive once the
+/// write that made them, and every write committed before it, is durable in the WAL; the
+/// subscription only reads the shared feed, so an idle or forgotten one costs nothing but its
+/// handle.
+#[derive(Debug)]
+pub struct ChangeSubscription {
+    feed: Arc<ChangeFeed>,
+    table: String,
+    // The feed position read up to; events already read but not yet returned wait in `pending`.
+    after: u64,
+    pending: VecDeque<ChangeEvent>,
+}
+
+impl ChangeSubscription {
+    pub(crate) fn new(feed: Arc<ChangeFeed>, table: &str, after: u64) -> Self {
+        Self {
+            feed,
+            table: table.to_string(),
+            after,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// The next change if one is already committed. Fails with `ChangesLagged` when more than
+    /// `Config::change_feed_capacity` changes were committed since the last call; the
+    /// subscription then carries on from the feed's current position. Fails once the database
+    /// is closed and every earlier change has been returned.
+    pub fn try_recv(&mut self) -> Result<Option<ChangeEvent>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(Some(event));
+        }
+        let closed = self.feed.lock().closed;
+        match self.feed.since(&self.table, self.after) {
+            Ok(page) => {
+                self.after = page.last_seq;
+                self.pending.extend(page.events);
+            }
+            Err(lagged) => {
+                self.after = lagged.last_seq;
+                return Err(lagged.into());
+            }
+        }
+        match self.pending.pop_front() {
+            Some(event) => Ok(Some(event)),
+            None if closed => Err(anyhow!("database is closed")),
+            None => Ok(None),
+        }
+    }
+
+    /// Blocks until the next change is committed.
+    pub fn recv(&mut self) -> Result<ChangeEvent> {
+        loop {
+            if let Some(event) = self.try_recv()? {
+                return Ok(event);
+            }
+            self.feed.wait(self.after, Duration::from_secs(60));
+        }
+    }
+
+    /// Like `recv`, but gives up with `None` after `timeout`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<ChangeEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(event) = self.try_recv()? {
+                return Ok(Some(event));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            self.feed.wait(self.after, remaining);
+        }
+    }
+
+    /// Waits for the next change without blocking the thread, for async tasks.
+    #[cfg(feature = "async")]
+    pub async fn recv_async(&mut self) -> Result<ChangeEvent> {
+        loop {
+            if let Some(event) = self.try_recv()? {
+                return Ok(event);
+            }
+            self.feed.changed(self.after).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Staged {
+    events: Vec<ChangeEvent>,
+    durable: bool,
+}
+
+/// A write's changes, numbered by `ChangeFeed::stage` and held back until `publish`. Dropped
+/// unpublished, when the write fails to commit, they are discarded.
+#[must_use]
+pub(crate) struct StagedChanges<'a> {
+    feed: &'a ChangeFeed,
+    seq: u64,
+    settled: bool,
+}
+
+impl StagedChanges<'_> {
+    pub(crate) fn publish(mut self) {
+        self.settled = true;
+        self.feed.settle(self.seq, true);
+    }
+}
+
+impl Drop for StagedChanges<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.feed.settle(self.seq, false);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Arrow conversion of table rows (feature `arrow`), returned by `EmbedDb::export_arrow`.
+
+use std::collections::{btree_map, BTreeMap, HashMap};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use arrow_array::builder::{FixedSizeListBuilder, Float32Builder};
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+    UInt64Array,
+};
+use arrow_schema::{DataType as ArrowType, Field, FieldRef, Schema, SchemaRef};
+
+use crate::schema::{Column, DataType, RowData, TableSchema, Value};
+
+/// Name of the row id column of exported batches.
+pub const ROW_ID_COLUMN: &str = "row_id";
+/// Name of the embedding column of exported batches.
+pub const EMBEDDING_COLUMN: &str = "embedding";
+
+/// A table's rows as Arrow record batches, read by `EmbedDb::export_arrow`.
+///
+/// Every batch has a non-null `UInt64` `row_id` column, then one column per schema column (`Int`
+/// as `Int64`, `Float` as `Float64`, `Bool` as `Boolean`, `String` as `Utf8`, and `Bytes` as
+/// `Binary`). Tables whose embedding dimension is known end with an `embedding` column of
+/// `FixedSizeList<Float32>`, null for rows without a ready embedding.
+#[derive(Debug)]
+pub struct ArrowBatches {
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    item: Option<(FieldRef, usize)>,
+    rows: btree_map::IntoIter<u64, RowData>,
+    embeddings: HashMap<u64, Vec<f32>>,
+    batch_rows: usize,
+}
+
+impl ArrowBatches {
+    pub(crate) fn new(
+        schema: &TableSchema,
+        dimension: Option<usize>,
+        rows: BTreeMap<u64, RowData>,
+        embeddings: HashMap<u64, Vec<f32>>,
+        batch_rows: usize,
+    ) -> Result<Self> {
+        let mut reserved = vec![ROW_ID_COLUMN];
+        if dimension.is_some() {
+            reserved.push(EMBEDDING_COLUMN);
+        }
+        if let Some(column) = schema
+            .columns
+            .iter()
+            .find(|column| reserved.contains(&column.name.as_str()))
+        {
+            return Err(anyhow!(
+                "column '{}' clashes with the exported column of the same name",
+                column.name
+            ));
+        }
+
+        let mut fields = vec![Field::new(ROW_ID_COLUMN, ArrowType::UInt64, false)];
+        fields.extend(schema.columns.iter().map(|column| {
+            Field::new(
+                column.name.clone(),
+                arrow_type(&column.data_type),
+                column.nullable,
+            )
+        }));
+        let item = dimension.map(|dimension| {
+            let item: FieldRef = Arc::new(Field::new("item", ArrowType::Float32, false));
+            fields.push(Field::new(
+                EMBEDDING_COLUMN,
+                ArrowType::FixedSizeList(item.clone(), dimension as i32),
+                true,
+            ));
+            (item, dimension)
+        });
+        Ok(Self {
+            schema: Arc::new(Schema::new(fields)),
+            columns: schema.columns.clone(),
+            item,
+            rows: rows.into_iter(),
+            embeddings,
+            batch_rows,
+        })
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Leaves the `embedding` column out of the batches.
+    pub fn without_embeddings(mut self) -> Self {
+        if self.item.take().is_some() {
+            let fields = self.schema.fields();
+            let fields = fields[..fields.len() - 1].to_vec();
+            self.schema = Arc::new(Schema::new(fields));
+            self.embeddings.clear();
+        }
+        self
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let rows: Vec<RowData> = self
+            .rows
+            .by_ref()
+            .take(self.batch_rows)
+            .map(|(_, row)| row)
+            .collect();
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let mut arrays: Vec<ArrayRef> = vec![Arc::new(UInt64Array::from_iter_values(
+            rows.iter().map(|row| row.id),
+        ))];
+        for column in &self.columns {
+            arrays.push(column_array(column, &rows)?);
+        }
+        if let Some((item, dimension)) = &self.item {
+            let mut builder = FixedSizeListBuilder::with_capacity(
+                Float32Builder::with_capacity(rows.len() * dimension),
+                *dimension as i32,
+                rows.len(),
+            )
+            .with_field(item.clone());
+            for row in &rows {
+                match self.embeddings.remove(&row.id) {
+                    Some(vector) if vector.len() == *dimension => {
+                        builder.values().append_slice(&vector);
+                        builder.append(true);
+                    }
+                    Some(vector) => {
+                        return Err(anyhow!(
+                            "row {}: embedding has {} dimensions, expected {dimension}",
+                            row.id,
+                            vector.len()
+                        ))
+                    }
+                    None => {
+                        builder.values().append_value_n(0.0, *dimension);
+                        builder.append(false);
+                    }
+                }
+            }
+            arrays.push(Arc::new(builder.finish()));
+        }
+        Ok(Some(RecordBatch::try_new(self.schema.clone(), arrays)?))
+    }
+}
+
+impl Iterator for ArrowBatches {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
+    }
+}
+
+fn arrow_type(data_type: &DataType) -> ArrowType {
+    match data_type {
+        DataType::Int => ArrowType::Int64,
+        DataType::Float => ArrowType::Float64,
+        DataType::Bool => ArrowType::Boolean,
+        DataType::String => ArrowType::Utf8,
+        DataType::Bytes => ArrowType::Binary,
+    }
+}
+
+fn column_array(column: &Column, rows: &[RowData]) -> Result<ArrayRef> {
+    Ok(match column.data_type {
+        DataType::Int => Arc::new(Int64Array::from(values(
+            column,
+            rows,
+            |value| match value {
+                Value::Int(value) => Some(*value),
+                _ => None,
+            },
+        )?)),
+        DataType::Float => Arc::new(Float64Array::from(values(
+            column,
+            rows,
+            |value| match value {
+                Value::Float(value) => Some(*value),
+                Value::Int(value) => Some(*value as f64),
+                _ => None,
+            },
+        )?)),
+        DataType::Bool => Arc::new(BooleanArray::from(values(
+            column,
+            rows,
+            |value| match value {
+                Value::Bool(value) => Some(*value),
+                _ => None,
+            },
+        )?)),
+        DataType::String => Arc::new(StringArray::from(values(
+            column,
+            rows,
+            |value| match value {
+                Value::String(value) => Some(value.as_str()),
+                _ => None,
+            },
+        )?)),
+        DataType::Bytes => Arc::new(BinaryArray::from(values(
+            column,
+            rows,
+            |value| match value {
+                Value::Bytes(value) => Some(value.as_slice()),
+                _ => None,
+            },
+        )?)),
+    })
+}
+
+/// The column's value in each row, `None` where it is missing or null; `get` converts a value of
+/// the column's type and returns `None` for any other.
+fn values<'a, T>(
+    column: &Column,
+    rows: &'a [RowData],
+    get: impl Fn(&'a Value) -> Option<T>,
+) -> Result<Vec<Option<T>>> {
+    rows.iter()
+        .map(|row| match row.fields.get(&column.name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => get(value).map(Some).ok_or_else(|| {
+                anyhow!(
+                    "row {}: column '{}' holds {value:?}, not {:?}",
+                    row.id,
+                    column.name,
+                    column.data_type
+                )
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Array, FixedSizeListArray, Float32Array};
+
+    use super::*;
+
+    fn row(id: u64, fields: &[(&str, Value)]) -> RowData {
+        RowData {
+            id,
+            version: 1,
+            fields: fields
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            created_at_ms: 0,
+            updated_at_ms: 0,
+        }
+    }
+
+    #[test]
+    fn batches_hold_typed_columns_and_nullable_embeddings() {
+        let schema = TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("score", DataType::Float, true),
+            Column::new("blob", DataType::Bytes, true),
+        ]);
+        let rows: BTreeMap<u64, RowData> = [
+            row(
+                1,
+                &[
+                    ("title", Value::String("a".to_string())),
+                    ("score", Value::Int(2)),
+                ],
+            ),
+            row(
+                4,
+                &[
+                    ("title", Value::String("b".to_string())),
+                    ("blob", Value::Bytes(vec![7])),
+                ],
+            ),
+            row(5, &[("title", Value::String("c".to_string()))]),
+        ]
+        .into_iter()
+        .map(|row| (row.id, row))
+        .collect();
+        let embeddings = HashMap::from([(1, vec![1.0, 2.0]), (5, vec![3.0, 4.0])]);
+        let batches: Vec<RecordBatch> = ArrowBatches::new(&schema, Some(2), rows, embeddings, 2)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            batches
+                .iter()
+                .map(RecordBatch::num_rows)
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+
+        let first = &batches[0];
+        let ids = first.column(0).as_any().downcast_ref::<UInt64Array>();
+        assert_eq!(ids.unwrap().values(), &[1, 4]);
+        let scores = first.column_by_name("score").unwrap();
+        let scores = scores.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!((scores.value(0), scores.is_null(1)), (2.0, true));
+        let embedding = first.column_by_name(EMBEDDING_COLUMN).unwrap();
+        let embedding = embedding
+            .as_any()
+            .downcast_ref::<FixedSizeListArray>()
+            .unwrap();
+        assert!(embedding.is_valid(0) && embedding.is_null(1));
+        let vector = embedding.value(0);
+        let vector = vector.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(vector.values(), &[1.0, 2.0]);
+
+        let clash = TableSchema::new(vec![Column::new("embedding", DataType::Int, true)]);
+        let err = ArrowBatches::new(&clash, Some(2), BTreeMap::new(), HashMap::new(), 2)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("clashes"), "{err}");
+        assert!(ArrowBatches::new(&clash, None, BTreeMap::new(), HashMap::new(), 2).is_ok());
+
+        let rows = BTreeMap::from([(1, row(1, &[("title", Value::String("a".to_string()))]))]);
+        let embeddings = HashMap::from([(1, vec![1.0, 2.0])]);
+        let batches = ArrowBatches::new(&schema, Some(2), rows, embeddings, 2)
+            .unwrap()
+            .without_embeddings();
+        assert_eq!(batches.schema().fields().len(), 4);
+        for batch in batches {
+            assert!(batch.unwrap().column_by_name(EMBEDDING_COLUMN).is_none());
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::SchemaChange;
+use crate::storage::codec::RowCodecKind;
+use crate::storage::sst::{self, SstFile};
+
+/// When and how compaction moves SST data down the levels. Level 0 holds flushed memtables and
+/// may overlap; every deeper level is a set of non-overlapping files with a size budget that
+/// grows by `level_size_multiplier` per level.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompactionPolicy {
+    /// `flush_table` and the background compaction worker compact the table once it has this
+    /// many level-0 files. 0 leaves compaction to explicit `compact_table` calls.
+    pub l0_trigger_files: usize,
+    /// Likewise once the table's level-0 files add up to this many bytes. 0 disables the trigger.
+    pub l0_trigger_bytes: u64,
+    /// Approximate size of each SST file written by compaction.
+    pub target_file_bytes: u64,
+    /// Size budget of level 1; a level over budget pushes its oldest file into the next level.
+    pub level1_max_bytes: u64,
+    pub level_size_multiplier: u64,
+    /// Deepest level. It has no size budget, and merges into it drop tombstones.
+    pub max_level: u32,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            l0_trigger_files: 0,
+            l0_trigger_bytes: 0,
+            target_file_bytes: 4 * 1024 * 1024,
+            level1_max_bytes: 16 * 1024 * 1024,
+            level_size_multiplier: 10,
+            max_level: 6,
+        }
+    }
+}
+
+impl CompactionPolicy {
+    /// Whether level 0 of `files` reached `l0_trigger_files` or `l0_trigger_bytes`.
+    pub(crate) fn l0_due(&self, files: &[SstFile]) -> Result<bool> {
+        let level_zero: Vec<SstFile> = files.iter().filter(|f| f.level == 0).cloned().collect();
+        if level_zero.is_empty() {
+            return Ok(false);
+        }
+        if self.l0_trigger_files > 0 && level_zero.len() >= self.l0_trigger_files {
+            return Ok(true);
+        }
+        Ok(self.l0_trigger_bytes > 0 && sst::total_bytes(&level_zero)? >= self.l0_trigger_bytes)
+    }
+
+    fn level_max_bytes(&self, level: u32) -> u64 {
+        let growth = self
+            .level_size_multiplier
+            .max(1)
+            .saturating_pow(level.saturating_sub(1));
+        self.level1_max_bytes.saturating_mul(growth)
+    }
+}
+
+/// One merge of files from `from_level` (and the overlapping files of `to_level`) into
+/// `to_level`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelMerge {
+    pub from_level: u32,
+    pub to_level: u32,
+    pub files_in: usize,
+    pub files_out: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub tombstones_dropped: usize,
+}
+
+/// Result of `EmbedDb::compact_table`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactionStats {
+    /// Merges in the order they ran; empty when there was nothing to compact.
+    pub merges: Vec<LevelMerge>,
+    /// SST files per level afterwards, starting at level 0.
+    pub files_per_level: Vec<usize>,
+    pub elapsed_ms: u64,
+}
+
+pub(crate) fn files_per_level(files: &[SstFile]) -> Vec<usize> {
+    let depth = files
+        .iter()
+        .map(|f| f.level as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut counts = vec![0; depth];
+    for file in files {
+        counts[file.level as usize] += 1;
+    }
+    counts
+}
+
+/// On-disk bytes of `files` per level, starting at level 0.
+pub(crate) fn bytes_per_level(files: &[SstFile]) -> Result<Vec<u64>> {
+    let mut bytes = vec![0; files_per_level(files).len()];
+    for file in files {
+        bytes[file.level as usize] += std::fs::metadata(&file.path)?.len();
+    }
+    Ok(bytes)
+}
+
+/// Merges all of level 0 into level 1, then pushes files down from every level over its budget.
+/// `files` is updated in place and kept in read order; merged rows are upgraded past `changes`.
+pub(crate) fn compact_levels(
+    files: &mut Vec<SstFile>,
+    dir: &Path,
+    next_seq: &mut u64,
+    policy: &CompactionPolicy,
+    codec: RowCodecKind,
+    changes: &[SchemaChange],
+) -> Result<Vec<LevelMerge>> {
+    let max_level = policy.max_level.max(1);
+    let mut merges = Vec::new();
+
+    let level_zero: Vec<SstFile> = files.iter().filter(|f| f.level == 0).cloned().collect();
+    if !level_zero.is_empty() {
+        merges.push(merge_down(
+            files, level_zero, 0, max_level, dir, next_seq, policy, codec, changes,
+        )?);
+    }
+
+    for level in 1..max_level {
+        loop {
+            let in_level: Vec<SstFile> =
+                files.iter().filter(|f| f.level == level).cloned().collect();
+            if sst::total_bytes(&in_level)? <= policy.level_max_bytes(level) {
+                break;
+            }
+            // In read order, the first file of a level is its oldest.
+            let oldest = in_level[0].clone();
+            merges.push(merge_down(
+                files,
+                vec![oldest],
+                level,
+                max_level,
+                dir,
+                next_seq,
+                policy,
+                codec,
+                changes,
+            )?);
+        }
+    }
+
+    Ok(merges)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn merge_down(
+    files: &mut Vec<SstFile>,
+    picked: Vec<SstFile>,
+    from_level: u32,
+    max_level: u32,
+    dir: &Path,
+    next_seq: &mut u64,
+    policy: &CompactionPolicy,
+    codec: RowCodecKind,
+    changes: &[SchemaChange],
+) -> Result<LevelMerge> {
+    let to_level = from_level + 1;
+    let mut range: Option<(u64, u64)> = None;
+    for file in &picked {
+        if let Some((lo, hi)) = sst::row_id_range(&file.path)? {
+            range = Some(match range {
+                Some((min, max)) => (min.min(lo), max.max(hi)),
+                None => (lo, hi),
+            });
+        }
+    }
+
+    let mut inputs = Vec::new();
+    if let Some((lo, hi)) = range {
+        for file in files.iter().filter(|f| f.level == to_level) {
+            let overlaps = sst::row_id_range(&file.path)?
+                .is_some_and(|(file_lo, file_hi)| file_lo <= hi && lo <= file_hi);
+            if overlaps {
+                inputs.push(file.clone());
+            }
+        }
+    }
+    inputs.extend(picked);
+    sst::sort_oldest_first(&mut inputs);
+
+    // Rows only need tombstones while an older file below the output level could hold them.
+    let drop_tombstones = to_level >= max_level || !files.iter().any(|f| f.level > to_level);
+    let bytes_in = sst::total_bytes(&inputs)?;
+    let output = sst::merge_into_level(
+        &inputs,
+        dir,
+        to_level,
+        next_seq,
+        policy.target_file_bytes,
+        drop_tombstones,
+        codec,
+        changes,
+    )?;
+    let bytes_out = sst::total_bytes(&output.files)?;
+
+    sst::remove_files(&inputs)?;
+    files.retain(|f| !inputs.iter().any(|input| input.path == f.path));
+    let files_out = output.files.len();
+    files.extend(output.files);
+    sst::sort_oldest_first(files);
+
+    Ok(LevelMerge {
+        from_level,
+        to_level,
+        files_in: inputs.len(),
+        files_out,
+        bytes_in,
+        bytes_out,
+        tombstones_dropped: output.tombstones_dropped,
+    })
+}
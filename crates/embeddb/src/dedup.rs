@@ -0,0 +1,72 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+/// Rows linked by pairs of embeddings within the threshold of `EmbedDb::find_duplicates`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    /// At least two row ids, ascending.
+    pub row_ids: Vec<u64>,
+    /// Largest distance of the pairs that link the cluster. Members are linked transitively, so
+    /// two of them may be further apart than this.
+    pub max_distance: f32,
+}
+
+/// Union-find over row ids, built up one linked pair at a time.
+#[derive(Debug, Default)]
+pub(crate) struct Clusters {
+    parent: HashMap<u64, u64>,
+    max_distance: HashMap<u64, f32>,
+}
+
+impl Clusters {
+    fn root(&mut self, mut row_id: u64) -> u64 {
+        while let Some(&parent) = self.parent.get(&row_id) {
+            if parent == row_id {
+                break;
+            }
+            // Path halving: point at the grandparent on the way up.
+            let grandparent = self.parent.get(&parent).copied().unwrap_or(parent);
+            self.parent.insert(row_id, grandparent);
+            row_id = grandparent;
+        }
+        row_id
+    }
+
+    pub(crate) fn link(&mut self, a: u64, b: u64, distance: f32) {
+        for row_id in [a, b] {
+            self.parent.entry(row_id).or_insert(row_id);
+        }
+        let (root_a, root_b) = (self.root(a), self.root(b));
+        let max = [root_a, root_b]
+            .iter()
+            .filter_map(|root| self.max_distance.get(root))
+            .fold(distance, |max, &d| max.max(d));
+        let (root, child) = if root_a <= root_b {
+            (root_a, root_b)
+        } else {
+            (root_b, root_a)
+        };
+        self.parent.insert(child, root);
+        self.max_distance.remove(&child);
+        self.max_distance.insert(root, max);
+    }
+
+    /// The clusters ordered by their smallest row id.
+    pub(crate) fn into_clusters(mut self) -> Vec<DuplicateCluster> {
+        let mut members: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        let mut row_ids: Vec<u64> = self.parent.keys().copied().collect();
+        row_ids.sort_unstable();
+        for row_id in row_ids {
+            let root = self.root(row_id);
+            members.entry(root).or_default().push(row_id);
+        }
+        members
+            .into_iter()
+            .map(|(root, row_ids)| DuplicateCluster {
+                row_ids,
+                max_distance: self.max_distance.get(&root).copied().unwrap_or(0.0),
+            })
+            .collect()
+    }
+}
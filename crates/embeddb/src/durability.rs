@@ -0,0 +1,97 @@
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// When WAL appends are synced to disk, set with `Config::with_durability`.
+///
+/// `Always` is the only policy under which a write that returned survives a crash of the machine
+/// (not just the process). The others trade that for throughput on disks where a sync is slow: a
+/// crash loses the writes appended since the last sync, but never corrupts the WAL, since replay
+/// stops at the first torn record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Durability {
+    /// Every write is synced before it returns (shared between writers with
+    /// `Config::wal_group_commit`).
+    #[default]
+    Always,
+    /// A background thread syncs appended records at this interval, so at most about one
+    /// interval of acknowledged writes can be lost.
+    Interval(Duration),
+    /// The WAL is only synced by checkpoints, segment rotation, and `EmbedDb::close`.
+    OnCheckpointOnly,
+}
+
+struct Shared {
+    stop: Mutex<bool>,
+    wake: Condvar,
+}
+
+/// Thread running `sync` every interval for `Durability::Interval`, stopped and joined on drop.
+pub(crate) struct WalSyncer {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+fn lock(mutex: &Mutex<bool>) -> MutexGuard<'_, bool> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl fmt::Debug for WalSyncer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalSyncer").finish_non_exhaustive()
+    }
+}
+
+impl WalSyncer {
+    /// `sync` returns false once there is nothing left to sync for, which ends the thread.
+    pub(crate) fn spawn(
+        interval: Duration,
+        sync: impl Fn() -> bool + Send + 'static,
+    ) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            stop: Mutex::new(false),
+            wake: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("embeddb-wal-sync".to_string())
+            .spawn(move || loop {
+                let stop = lock(&thread_shared.stop);
+                let (stop, _) = thread_shared
+                    .wake
+                    .wait_timeout_while(stop, interval, |stop| !*stop)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                if *stop {
+                    return;
+                }
+                drop(stop);
+                if !sync() {
+                    return;
+                }
+            })?;
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Asks the thread to exit, without waiting for it.
+    pub(crate) fn stop(&self) {
+        *lock(&self.shared.stop) = true;
+        self.shared.wake.notify_all();
+    }
+}
+
+impl Drop for WalSyncer {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
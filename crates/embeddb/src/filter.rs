@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Result};
+
+use crate::schema::Value;
+use crate::{FilterCondition, FilterOp};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Op(FilterOp),
+    Literal(Value),
+    And,
+}
+
+/// Parses a where clause such as `score > 0.5 AND tag = "rust"` into filter conditions.
+///
+/// Each condition is `column op literal`, with `op` one of `=` (or `==`), `!=` (or `<>`), `<`,
+/// `<=`, `>`, `>=`, or `CONTAINS`. Literals are numbers, `"double"` or `'single'` quoted strings
+/// (backslash escapes `\"`, `\'`, and `\\`), `true`, `false`, or `null`. Conditions are joined
+/// with `AND`; keywords are case-insensitive, and a column name that isn't a plain identifier can
+/// be wrapped in backticks. `OR` and parentheses are rejected, since filters are a conjunction.
+pub fn parse_filter(input: &str) -> Result<Vec<FilterCondition>> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conditions = Vec::new();
+    let mut tokens = tokens.into_iter();
+    loop {
+        let column = match tokens.next() {
+            Some(Token::Ident(column)) => column,
+            other => return Err(unexpected(other, "a column name")),
+        };
+        let op = match tokens.next() {
+            Some(Token::Op(op)) => op,
+            other => return Err(unexpected(other, &format!("an operator after '{column}'"))),
+        };
+        let value = match tokens.next() {
+            Some(Token::Literal(value)) => value,
+            other => return Err(unexpected(other, &format!("a value after '{column}'"))),
+        };
+        conditions.push(FilterCondition { column, op, value });
+        match tokens.next() {
+            None => return Ok(conditions),
+            Some(Token::And) => {}
+            other => return Err(unexpected(other, "AND or the end of the clause")),
+        }
+    }
+}
+
+fn unexpected(token: Option<Token>, expected: &str) -> anyhow::Error {
+    match token {
+        None => anyhow!("where clause ended early: expected {expected}"),
+        Some(token) => anyhow!(
+            "where clause: expected {expected}, found {}",
+            describe(&token)
+        ),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("'{name}'"),
+        Token::Op(op) => format!("operator {op:?}"),
+        Token::Literal(value) => format!("value {value:?}"),
+        Token::And => "AND".to_string(),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let token = match c {
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let next = chars.peek().map(|&(_, next)| next);
+                let (op, two_chars) = match (c, next) {
+                    ('=', Some('=')) => (FilterOp::Eq, true),
+                    ('=', _) => (FilterOp::Eq, false),
+                    ('!', Some('=')) => (FilterOp::Neq, true),
+                    ('<', Some('>')) => (FilterOp::Neq, true),
+                    ('<', Some('=')) => (FilterOp::Lte, true),
+                    ('<', _) => (FilterOp::Lt, false),
+                    ('>', Some('=')) => (FilterOp::Gte, true),
+                    ('>', _) => (FilterOp::Gt, false),
+                    _ => return Err(anyhow!("where clause: unexpected '!' at offset {start}")),
+                };
+                if two_chars {
+                    chars.next();
+                }
+                Token::Op(op)
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) if chars.peek().is_some() => {
+                            text.extend(chars.next().map(|(_, escaped)| escaped));
+                        }
+                        Some((_, ch)) if ch == c => {
+                            tokens.push(Token::Literal(Value::String(text)));
+                            break;
+                        }
+                        Some((_, ch)) if ch != '\\' => text.push(ch),
+                        _ => {
+                            return Err(anyhow!(
+                                "where clause: unterminated string starting at offset {start}"
+                            ))
+                        }
+                    }
+                }
+                continue;
+            }
+            '`' => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '`')) => break,
+                        Some((_, ch)) => name.push(ch),
+                        None => {
+                            return Err(anyhow!(
+                                "where clause: unterminated column name starting at offset {start}"
+                            ))
+                        }
+                    }
+                }
+                Token::Ident(name)
+            }
+            '(' | ')' => {
+                return Err(anyhow!(
+                    "where clause: parentheses are not supported (offset {start})"
+                ))
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut end = start;
+                while let Some(&(idx, ch)) = chars.peek() {
+                    let sign_after_exponent =
+                        (ch == '-' || ch == '+') && input[start..idx].ends_with(['e', 'E']);
+                    if ch.is_ascii_alphanumeric()
+                        || ch == '.'
+                        || idx == start
+                        || sign_after_exponent
+                    {
+                        end = idx + ch.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                Token::Literal(parse_number(&input[start..end])?)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(idx, ch)) = chars.peek() {
+                    if ch.is_alphanumeric() || ch == '_' {
+                        end = idx + ch.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let word = &input[start..end];
+                match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => return Err(anyhow!("where clause: OR is not supported, only AND")),
+                    "contains" => Token::Op(FilterOp::Contains),
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    _ => Token::Ident(word.to_string()),
+                }
+            }
+            other => {
+                return Err(anyhow!(
+                    "where clause: unexpected '{other}' at offset {start}"
+                ))
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<Value> {
+    if let Ok(int) = text.parse::<i64>() {
+        return Ok(Value::Int(int));
+    }
+    text.parse::<f64>()
+        .ok()
+        .filter(|float| float.is_finite())
+        .map(Value::Float)
+        .ok_or_else(|| anyhow!("where clause: invalid number '{text}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cond(column: &str, op: FilterOp, value: Value) -> (String, FilterOp, Value) {
+        (column.to_string(), op, value)
+    }
+
+    fn parsed(input: &str) -> Vec<(String, FilterOp, Value)> {
+        parse_filter(input)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.column, c.op, c.value))
+            .collect()
+    }
+
+    #[test]
+    fn parses_conjunctions_of_comparisons() {
+        assert_eq!(
+            parsed(r#"score > 0.5 AND tag = "rust" and `first name` contains 'O\'Neil'"#),
+            vec![
+                cond("score", FilterOp::Gt, Value::Float(0.5)),
+                cond("tag", FilterOp::Eq, Value::String("rust".to_string())),
+                cond(
+                    "first name",
+                    FilterOp::Contains,
+                    Value::String("O'Neil".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            parsed("a>=-3 AND b<>1e3 AND c == true AND d != null AND e<=2 AND f<1.5e-2"),
+            vec![
+                cond("a", FilterOp::Gte, Value::Int(-3)),
+                cond("b", FilterOp::Neq, Value::Float(1000.0)),
+                cond("c", FilterOp::Eq, Value::Bool(true)),
+                cond("d", FilterOp::Neq, Value::Null),
+                cond("e", FilterOp::Lte, Value::Int(2)),
+                cond("f", FilterOp::Lt, Value::Float(0.015)),
+            ]
+        );
+        assert!(parse_filter("  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_clauses() {
+        for (input, needle) in [
+            ("a = 1 OR b = 2", "OR is not supported"),
+            ("(a = 1)", "parentheses"),
+            ("a = 'open", "unterminated string"),
+            ("a 1", "expected an operator after 'a'"),
+            ("a =", "ended early"),
+            ("a = 1 b = 2", "expected AND"),
+            ("a = 1 AND", "ended early"),
+            ("a = 1x", "invalid number"),
+            ("a = b", "expected a value after 'a'"),
+            ("a ! 1", "unexpected '!'"),
+        ] {
+            let err = parse_filter(input).unwrap_err().to_string();
+            assert!(err.contains(needle), "{input}: {err}");
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::SearchHit;
+
+/// Smoothing constant from the original RRF paper; damps the weight of the very top ranks.
+const RRF_K: f32 = 60.0;
+
+/// How `EmbedDb::search_hybrid` merges its keyword and vector rankings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Fusion {
+    /// Reciprocal rank fusion, both rankings counting equally.
+    #[default]
+    ReciprocalRank,
+    /// Reciprocal rank fusion with each ranking's contribution scaled by its weight.
+    WeightedRank {
+        keyword_weight: f32,
+        vector_weight: f32,
+    },
+}
+
+impl Fusion {
+    /// The keyword and vector weights.
+    pub(crate) fn weights(self) -> (f32, f32) {
+        match self {
+            Fusion::ReciprocalRank => (1.0, 1.0),
+            Fusion::WeightedRank {
+                keyword_weight,
+                vector_weight,
+            } => (keyword_weight, vector_weight),
+        }
+    }
+}
+
+/// Merges ranked result lists with reciprocal rank fusion: each row scores `sum(1 / (60 + rank))`
+/// over the lists it appears in. Fused hits carry the negated score as `distance` so that lower
+/// still means closer.
+pub(crate) fn reciprocal_rank_fusion(lists: &[&[SearchHit]], k: usize) -> Vec<SearchHit> {
+    let weighted: Vec<(&[SearchHit], f32)> = lists.iter().map(|list| (*list, 1.0)).collect();
+    weighted_rank_fusion(&weighted, k)
+}
+
+/// `reciprocal_rank_fusion` with each list's terms multiplied by its weight.
+pub(crate) fn weighted_rank_fusion(lists: &[(&[SearchHit], f32)], k: usize) -> Vec<SearchHit> {
+    let mut scores: HashMap<u64, f32> = HashMap::new();
+    for (list, weight) in lists {
+        for (rank, hit) in list.iter().enumerate() {
+            *scores.entry(hit.row_id).or_default() += weight / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+
+    let mut fused: Vec<SearchHit> = scores
+        .into_iter()
+        .map(|(row_id, score)| SearchHit {
+            row_id,
+            distance: -score,
+            score: None,
+        })
+        .collect();
+    fused.sort_by(|a, b| {
+        a.distance
+            .total_cmp(&b.distance)
+            .then(a.row_id.cmp(&b.row_id))
+    });
+    fused.truncate(k);
+    fused
+}
@@ -0,0 +1,289 @@
+//! wgpu compute backend for batch distance matrices (`gpu` feature).
+
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context, Result};
+use wgpu::util::DeviceExt;
+
+use crate::DistanceMetric;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+const SHADER: &str = r#"
+struct Params {
+    dim: u32,
+    n_queries: u32,
+    n_candidates: u32,
+    metric: u32,
+}
+
+@group(0) @binding(0) var<storage, read> queries: array<f32>;
+@group(0) @binding(1) var<storage, read> candidates: array<f32>;
+@group(0) @binding(2) var<storage, read_write> out: array<f32>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let v = gid.x;
+    let q = gid.y;
+    if (v >= params.n_candidates || q >= params.n_queries) {
+        return;
+    }
+    let qo = q * params.dim;
+    let vo = v * params.dim;
+    var acc = 0.0;
+    var norm_q = 0.0;
+    var norm_v = 0.0;
+    for (var i = 0u; i < params.dim; i = i + 1u) {
+        let a = queries[qo + i];
+        let b = candidates[vo + i];
+        if (params.metric == 0u) {
+            let d = a - b;
+            acc = acc + d * d;
+        } else {
+            acc = acc + a * b;
+            norm_q = norm_q + a * a;
+            norm_v = norm_v + b * b;
+        }
+    }
+    var result = acc;
+    if (params.metric != 0u) {
+        if (norm_q == 0.0 || norm_v == 0.0) {
+            result = 1.0;
+        } else {
+            result = 1.0 - acc / (sqrt(norm_q) * sqrt(norm_v));
+        }
+    }
+    out[q * params.n_candidates + v] = result;
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    dim: u32,
+    n_queries: u32,
+    n_candidates: u32,
+    metric: u32,
+}
+
+struct GpuContext {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    max_binding_bytes: u64,
+    max_dispatch: u32,
+}
+
+/// The adapter is probed once per process; `None` means no usable GPU.
+fn context() -> Option<&'static GpuContext> {
+    static CONTEXT: OnceLock<Option<GpuContext>> = OnceLock::new();
+    CONTEXT
+        .get_or_init(|| match pollster::block_on(GpuContext::new()) {
+            Ok(ctx) => Some(ctx),
+            Err(err) => {
+                tracing::info!("GPU batch scoring unavailable: {err:#}");
+                None
+            }
+        })
+        .as_ref()
+}
+
+impl GpuContext {
+    async fn new() -> Result<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .ok_or_else(|| anyhow!("no GPU adapter found"))?;
+        let limits = adapter.limits();
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("embeddb-batch"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: limits.clone(),
+                    memory_hints: wgpu::MemoryHints::Performance,
+                },
+                None,
+            )
+            .await
+            .context("request GPU device")?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("embeddb-distance"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("embeddb-distance"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            max_binding_bytes: u64::from(limits.max_storage_buffer_binding_size),
+            max_dispatch: limits.max_compute_workgroups_per_dimension,
+        })
+    }
+
+    /// Scores one chunk of queries against all candidates.
+    fn run(
+        &self,
+        queries: &[f32],
+        candidates: &wgpu::Buffer,
+        n_candidates: u32,
+        dim: u32,
+        metric: u32,
+    ) -> Result<Vec<f32>> {
+        let n_queries = (queries.len() / dim as usize) as u32;
+        let out_bytes = u64::from(n_queries) * u64::from(n_candidates) * 4;
+        let params = Params {
+            dim,
+            n_queries,
+            n_candidates,
+            metric,
+        };
+
+        let query_buf = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("queries"),
+                contents: bytemuck::cast_slice(queries),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let params_buf = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: bytemuck::bytes_of(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let out_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("distances"),
+            size: out_bytes,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: out_bytes,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: query_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: candidates.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: out_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buf.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(n_candidates.div_ceil(WORKGROUP_SIZE), n_queries, 1);
+        }
+        encoder.copy_buffer_to_buffer(&out_buf, 0, &readback, 0, out_bytes);
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            let _ = tx.send(res);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .context("GPU readback channel closed")?
+            .context("map GPU readback buffer")?;
+        let out = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        readback.unmap();
+        Ok(out)
+    }
+}
+
+/// GPU equivalent of `batch::cpu_distance_matrix`. Errors when no adapter is available or the
+/// candidate matrix exceeds the device's storage binding limit; callers fall back to the CPU.
+pub(crate) fn distance_matrix(
+    queries: &[f32],
+    candidates: &[f32],
+    dim: usize,
+    metric: DistanceMetric,
+) -> Result<Vec<f32>> {
+    let ctx = context().ok_or_else(|| anyhow!("no GPU adapter available"))?;
+    let n_candidates = candidates.len() / dim;
+    let candidate_bytes = (candidates.len() * 4) as u64;
+    if candidate_bytes > ctx.max_binding_bytes
+        || n_candidates.div_ceil(WORKGROUP_SIZE as usize) > ctx.max_dispatch as usize
+    {
+        return Err(anyhow!(
+            "candidate set is too large for the GPU device limits"
+        ));
+    }
+    let metric = match metric {
+        DistanceMetric::L2 => 0,
+        DistanceMetric::Cosine => 1,
+    };
+
+    let candidate_buf = ctx
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("candidates"),
+            contents: bytemuck::cast_slice(candidates),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+    // Chunk queries so each output buffer and dispatch stays within device limits.
+    let row_bytes = (n_candidates as u64 * 4).max(1);
+    let rows_per_chunk = (ctx.max_binding_bytes / row_bytes)
+        .min(u64::from(ctx.max_dispatch))
+        .max(1) as usize;
+    if row_bytes > ctx.max_binding_bytes {
+        return Err(anyhow!(
+            "candidate set is too large for the GPU device limits"
+        ));
+    }
+
+    let mut out = Vec::with_capacity((queries.len() / dim) * n_candidates);
+    for chunk in queries.chunks(rows_per_chunk * dim) {
+        out.extend(ctx.run(
+            chunk,
+            &candidate_buf,
+            n_candidates as u32,
+            dim as u32,
+            metric,
+        )?);
+    }
+    Ok(out)
+}
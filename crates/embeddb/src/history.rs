@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::RowData;
+use crate::storage::sst::SstFile;
+use crate::storage::wal::{self, Wal, WalRecord};
+use crate::{
+    apply_record, load_row, scan_rows_locked, scroll_embeddings_locked, search_locked, DbState,
+    DistanceMetric, EmbeddingPage, FilterCondition, RowPage, SearchHit, SearchOptions, TableState,
+};
+#[cfg(feature = "arrow")]
+use crate::{export_arrow_locked, ArrowBatches};
+
+/// Directory holding WAL segments retained by checkpoints when `Config::wal_archive` is set.
+pub(crate) fn archive_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("wal_archive")
+}
+
+/// Archived segments are named by the LSN they end at, so the newest one identifies the LSN the
+/// current `wal.log` snapshot starts from.
+pub(crate) fn archive_segment_path(data_dir: &Path, end_lsn: u64) -> PathBuf {
+    archive_dir(data_dir).join(format!("wal_{end_lsn:020}.log"))
+}
+
+/// Directory holding the links that keep the SST files of `EmbedDb::snapshot` views readable.
+fn pinned_files_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("read_snapshots")
+}
+
+/// Removes the links of views left open by a process that has since stopped.
+pub(crate) fn remove_pinned_files(data_dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(pinned_files_dir(data_dir)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// A view's hard links to the SST files it reads, so compactions and renames can't remove them
+/// from under it. The links are removed when it is dropped.
+#[derive(Debug)]
+pub(crate) struct PinnedFiles {
+    dir: PathBuf,
+}
+
+impl PinnedFiles {
+    pub(crate) fn create(data_dir: &Path) -> Result<Self> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        let dir = pinned_files_dir(data_dir).join(format!("view_{id}"));
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Links `file` of the table numbered `table_idx` into the view's directory.
+    pub(crate) fn pin(&self, table_idx: usize, file: &SstFile) -> Result<SstFile> {
+        let dir = self.dir.join(table_idx.to_string());
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(SstFile::filename(file.level, file.seq));
+        if fs::hard_link(&file.path, &path).is_err() {
+            fs::copy(&file.path, &path)?;
+        }
+        Ok(SstFile {
+            level: file.level,
+            seq: file.seq,
+            path,
+        })
+    }
+}
+
+impl Drop for PinnedFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Archived segments oldest first, then the segments sealed since the last checkpoint, followed by
+/// the live `wal.log`.
+fn wal_segments(data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut segments = wal::list_segments(&archive_dir(data_dir))?;
+    segments.extend(wal::list_segments(&wal::sealed_dir(data_dir))?);
+    segments.push(data_dir.join("wal.log"));
+    Ok(segments)
+}
+
+/// Rebuilds table state as of `target` by replaying the WAL from the beginning of history.
+///
+/// Segments written by a checkpoint start with a snapshot that is skipped, since the preceding
+/// segments already produced that state; segments sealed by size-based rotation have no snapshot
+/// and simply continue the previous one. The first segment must hold the full history (no
+/// checkpoint marker), because rows covered by a checkpoint only survive in SSTs, which reflect
+/// the present rather than the past.
+pub(crate) fn replay_to_lsn(data_dir: &Path, target: u64) -> Result<DbState> {
+    let mut state = DbState {
+        tables: HashMap::new(),
+    };
+    let mut lsn = 0u64;
+
+    for (idx, path) in wal_segments(data_dir)?.iter().enumerate() {
+        let mut records = Wal::replay_path(path)?;
+        let marker = records
+            .iter()
+            .position(|record| matches!(record, WalRecord::Checkpoint { .. }));
+        match marker {
+            None => {}
+            Some(pos) => {
+                let WalRecord::Checkpoint { lsn: base } = records[pos] else {
+                    unreachable!("position matched a checkpoint record");
+                };
+                if idx == 0 {
+                    return Err(anyhow!(
+                        "history before LSN {base} is not retained (enable wal_archive before checkpointing to keep it)"
+                    ));
+                }
+                if base != lsn {
+                    return Err(anyhow!(
+                        "WAL archive gap: segment {} starts at LSN {base}, expected {lsn}",
+                        path.display()
+                    ));
+                }
+                records.drain(..=pos);
+            }
+        }
+
+        for record in records {
+            if lsn == target {
+                return Ok(state);
+            }
+            apply_record(&mut state, record)?;
+            lsn += 1;
+        }
+    }
+
+    if lsn == target {
+        Ok(state)
+    } else {
+        Err(anyhow!("LSN {target} is ahead of the current LSN {lsn}"))
+    }
+}
+
+/// A WAL record returned by `EmbedDb::wal_since`, with the LSN it was written at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalEntry {
+    pub lsn: u64,
+    pub record: WalRecord,
+}
+
+/// The LSN a sealed or archived segment ends at, from its name; `None` for `wal.log`.
+fn segment_end_lsn(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("wal_")?
+        .strip_suffix(".log")?
+        .parse()
+        .ok()
+}
+
+/// The records written after `since`, oldest first. Segments ending at or before `since` are
+/// skipped unread; the checkpoint snapshot at the start of a segment is left out, like in
+/// `replay_to_lsn`.
+pub(crate) fn records_since(data_dir: &Path, since: u64) -> Result<Vec<WalEntry>> {
+    let mut entries = Vec::new();
+    // LSN of the last record passed, once known.
+    let mut lsn: Option<u64> = None;
+
+    for path in wal_segments(data_dir)? {
+        if let Some(end) = segment_end_lsn(&path).filter(|end| *end <= since) {
+            lsn = Some(end);
+            continue;
+        }
+        let mut records = Wal::replay_path(&path)?;
+        let marker = records
+            .iter()
+            .position(|record| matches!(record, WalRecord::Checkpoint { .. }));
+        if let Some(pos) = marker {
+            let WalRecord::Checkpoint { lsn: base } = records[pos] else {
+                unreachable!("position matched a checkpoint record");
+            };
+            // A gap only matters if it falls after `since`.
+            if since < base {
+                match lsn {
+                    None => {
+                        return Err(anyhow!(
+                            "WAL before LSN {base} is not retained (enable wal_archive, or start again from a snapshot)"
+                        ));
+                    }
+                    Some(lsn) if lsn != base => {
+                        return Err(anyhow!(
+                            "WAL archive gap: segment {} starts at LSN {base}, expected {lsn}",
+                            path.display()
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+            lsn = Some(base);
+            records.drain(..=pos);
+        }
+
+        let lsn = lsn.get_or_insert(0);
+        for record in records {
+            *lsn += 1;
+            if *lsn > since {
+                entries.push(WalEntry { lsn: *lsn, record });
+            }
+        }
+    }
+
+    match lsn {
+        Some(lsn) if since > lsn => Err(anyhow!("LSN {since} is ahead of the current LSN {lsn}")),
+        _ => Ok(entries),
+    }
+}
+
+/// Read-only view of the database as of one LSN: a past one rebuilt by `EmbedDb::read_at_lsn`, or
+/// the current one frozen by `EmbedDb::snapshot`.
+#[derive(Debug)]
+pub struct HistoricalView {
+    lsn: u64,
+    state: DbState,
+    _pinned: Option<PinnedFiles>,
+}
+
+impl HistoricalView {
+    pub(crate) fn new(lsn: u64, state: DbState) -> Self {
+        Self {
+            lsn,
+            state,
+            _pinned: None,
+        }
+    }
+
+    pub(crate) fn with_pinned(mut self, pinned: Option<PinnedFiles>) -> Self {
+        self._pinned = pinned;
+        self
+    }
+
+    fn table(&self, table: &str) -> Result<&TableState> {
+        self.state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))
+    }
+
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+
+    pub fn list_tables(&self) -> Vec<String> {
+        let mut out: Vec<String> = self.state.tables.keys().cloned().collect();
+        out.sort();
+        out
+    }
+
+    pub fn get_row(&self, table: &str, row_id: u64) -> Result<Option<RowData>> {
+        load_row(self.table(table)?, row_id)
+    }
+
+    /// Pages through the table's rows like `EmbedDb::scan_rows`.
+    pub fn scan_rows(
+        &self,
+        table: &str,
+        start_after: Option<u64>,
+        limit: usize,
+    ) -> Result<RowPage> {
+        scan_rows_locked(self.table(table)?, start_after, limit)
+    }
+
+    /// Pages through the table's embeddings like `EmbedDb::scroll_embeddings`.
+    pub fn scroll_embeddings(
+        &self,
+        table: &str,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EmbeddingPage> {
+        Ok(scroll_embeddings_locked(self.table(table)?, cursor, limit))
+    }
+
+    /// Reads the table like `EmbedDb::export_arrow`, except that quantized tables export their
+    /// stored vectors (feature `arrow`).
+    #[cfg(feature = "arrow")]
+    pub fn export_arrow(&self, table: &str, batch_rows: usize) -> Result<ArrowBatches> {
+        export_arrow_locked(self.table(table)?, batch_rows, |_| Ok(None))
+    }
+
+    pub fn search_knn(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: DistanceMetric,
+    ) -> Result<Vec<SearchHit>> {
+        self.search_knn_filtered(table, query, k, metric, &[])
+    }
+
+    pub fn search_knn_filtered(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: DistanceMetric,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        search_locked(
+            self.table(table)?,
+            query,
+            k,
+            metric,
+            filters,
+            &SearchOptions::default(),
+        )
+    }
+}
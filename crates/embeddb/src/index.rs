@@ -0,0 +1,561 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::DistanceMetric;
+
+/// Vector index backing a table's kNN search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexKind {
+    /// Exact brute-force scan over every `Ready` embedding; needs no build step.
+    Flat,
+    /// Approximate HNSW graph, maintained as embeddings are stored.
+    Hnsw,
+}
+
+/// Index a table's embeddings are kept in, set through `EmbeddingSpec::with_index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IndexSpec {
+    #[default]
+    Flat,
+    Hnsw(HnswSpec),
+}
+
+impl IndexSpec {
+    /// An HNSW index with default parameters.
+    pub fn hnsw() -> Self {
+        Self::Hnsw(HnswSpec::default())
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        match self {
+            Self::Flat => IndexKind::Flat,
+            Self::Hnsw(_) => IndexKind::Hnsw,
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Self::Hnsw(spec) = self {
+            if spec.m < 2 {
+                return Err(anyhow!("HNSW m must be at least 2"));
+            }
+            if spec.ef_construction == 0 || spec.ef_search == 0 {
+                return Err(anyhow!(
+                    "HNSW ef_construction and ef_search must be positive"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// HNSW build and search parameters. Larger values trade memory and latency for recall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HnswSpec {
+    /// Neighbors kept per node on upper layers; layer 0 keeps twice as many.
+    pub m: usize,
+    /// Candidate list size while inserting.
+    pub ef_construction: usize,
+    /// Candidate list size while searching; raised to `k` when smaller.
+    pub ef_search: usize,
+}
+
+impl Default for HnswSpec {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndexState {
+    Ready,
+    /// An index build is in progress; searches use the exact scan until it finishes.
+    Building,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub kind: IndexKind,
+    pub state: IndexState,
+    pub indexed_vectors: usize,
+    pub total_vectors: usize,
+    /// Estimated time until a running build completes, extrapolated from its rate so far.
+    pub eta_ms: Option<u64>,
+}
+
+impl IndexStatus {
+    pub(crate) fn flat(total_vectors: usize) -> Self {
+        Self::ready(IndexKind::Flat, total_vectors)
+    }
+
+    pub(crate) fn ready(kind: IndexKind, total_vectors: usize) -> Self {
+        Self {
+            kind,
+            state: IndexState::Ready,
+            indexed_vectors: total_vectors,
+            total_vectors,
+            eta_ms: None,
+        }
+    }
+}
+
+/// How a search would be executed, as reported by `EmbedDb::explain_search`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchExplain {
+    pub index: IndexKind,
+    /// Set when the table's configured index is unavailable (e.g. still building) and the
+    /// search falls back to an exact scan.
+    pub degraded: bool,
+    /// Ready embeddings the search would score (for HNSW, the initial candidate list size).
+    pub candidates: usize,
+    /// Candidates fetched for exact re-scoring on quantized tables, if re-scoring applies.
+    pub rescore_candidates: Option<usize>,
+}
+
+/// Distance paired with a node id, ordered by distance so it can sit in a `BinaryHeap`.
+#[derive(Debug, Clone, Copy)]
+struct Scored(f32, u32);
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+#[derive(Debug)]
+struct HnswNode {
+    row_id: u64,
+    /// Neighbor node ids per layer, from layer 0 up to the node's level.
+    neighbors: Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+/// In-memory HNSW graph over a table's embeddings.
+///
+/// The graph stores row ids only; callers supply distances through closures so vectors stay in
+/// `TableState`. Removed or replaced rows are tombstoned and still routed through, and the owner
+/// rebuilds the graph once tombstones outnumber live nodes (see `needs_rebuild`).
+#[derive(Debug)]
+pub(crate) struct Hnsw {
+    spec: HnswSpec,
+    metric: DistanceMetric,
+    nodes: Vec<HnswNode>,
+    by_row: HashMap<u64, u32>,
+    entry: Option<u32>,
+    max_level: usize,
+    deleted: usize,
+    rng: u64,
+}
+
+/// Hard cap on node levels; with m >= 2 higher levels are vanishingly unlikely.
+const MAX_LEVEL: usize = 16;
+
+impl Hnsw {
+    pub(crate) fn new(spec: HnswSpec, metric: DistanceMetric) -> Self {
+        Self {
+            spec,
+            metric,
+            nodes: Vec::new(),
+            by_row: HashMap::new(),
+            entry: None,
+            max_level: 0,
+            deleted: 0,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Metric the graph was built for; searches with another metric can't use it.
+    pub(crate) fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    pub(crate) fn ef_search(&self) -> usize {
+        self.spec.ef_search
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.by_row.len()
+    }
+
+    pub(crate) fn spec(&self) -> HnswSpec {
+        self.spec
+    }
+
+    pub(crate) fn contains(&self, row_id: u64) -> bool {
+        self.by_row.contains_key(&row_id)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn tombstones(&self) -> usize {
+        self.deleted
+    }
+
+    pub(crate) fn needs_rebuild(&self) -> bool {
+        self.deleted > 64 && self.deleted > self.by_row.len()
+    }
+
+    pub(crate) fn remove(&mut self, row_id: u64) {
+        if let Some(id) = self.by_row.remove(&row_id) {
+            self.nodes[id as usize].deleted = true;
+            self.deleted += 1;
+        }
+    }
+
+    /// Inserts (or replaces) `row_id`. `to_new` measures a stored row against the vector being
+    /// inserted and `between` measures two stored rows, for pruning neighbor lists.
+    pub(crate) fn insert(
+        &mut self,
+        row_id: u64,
+        to_new: &dyn Fn(u64) -> f32,
+        between: &dyn Fn(u64, u64) -> f32,
+    ) {
+        self.remove(row_id);
+        let level = self.random_level();
+        let id = self.nodes.len() as u32;
+        self.nodes.push(HnswNode {
+            row_id,
+            neighbors: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.by_row.insert(row_id, id);
+
+        let Some(mut entry) = self.entry else {
+            self.entry = Some(id);
+            self.max_level = level;
+            return;
+        };
+
+        for layer in (level + 1..=self.max_level).rev() {
+            entry = self.search_layer(to_new, entry, 1, layer, false)[0].1;
+        }
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let found = self.search_layer(to_new, entry, self.spec.ef_construction, layer, true);
+            if let Some(nearest) = found.first() {
+                entry = nearest.1;
+            }
+            let max = self.max_neighbors(layer);
+            let neighbors: Vec<u32> = found
+                .iter()
+                .filter(|scored| scored.1 != id)
+                .take(max)
+                .map(|scored| scored.1)
+                .collect();
+            for &neighbor in &neighbors {
+                let links = &mut self.nodes[neighbor as usize].neighbors[layer];
+                links.push(id);
+                if links.len() > max {
+                    self.prune(neighbor, layer, max, between);
+                }
+            }
+            self.nodes[id as usize].neighbors[layer] = neighbors;
+        }
+
+        if level > self.max_level {
+            self.entry = Some(id);
+            self.max_level = level;
+        }
+    }
+
+    /// Returns up to `ef` live rows nearest to the query measured by `distance`, nearest
+    /// first.
+    pub(crate) fn search(&self, distance: &dyn Fn(u64) -> f32, ef: usize) -> Vec<(u64, f32)> {
+        let Some(mut entry) = self.entry else {
+            return Vec::new();
+        };
+        for layer in (1..=self.max_level).rev() {
+            entry = self.search_layer(distance, entry, 1, layer, false)[0].1;
+        }
+        self.search_layer(distance, entry, ef.max(1), 0, true)
+            .into_iter()
+            .map(|Scored(dist, id)| (self.nodes[id as usize].row_id, dist))
+            .collect()
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.spec.m * 2
+        } else {
+            self.spec.m
+        }
+    }
+
+    /// Keeps the `max` closest neighbors of `node` on `layer`, dropping tombstoned ones first.
+    fn prune(&mut self, node: u32, layer: usize, max: usize, between: &dyn Fn(u64, u64) -> f32) {
+        let row_id = self.nodes[node as usize].row_id;
+        let mut scored: Vec<Scored> = self.nodes[node as usize].neighbors[layer]
+            .iter()
+            .filter(|&&other| !self.nodes[other as usize].deleted)
+            .map(|&other| Scored(between(row_id, self.nodes[other as usize].row_id), other))
+            .collect();
+        scored.sort();
+        scored.truncate(max);
+        self.nodes[node as usize].neighbors[layer] = scored.into_iter().map(|s| s.1).collect();
+    }
+
+    /// Best-first search of one layer from `entry`, returning up to `ef` nodes nearest first.
+    /// Tombstoned nodes are always traversed; with `live_only` they are left out of the result,
+    /// which may then be empty. Without it the result always holds at least `entry`.
+    fn search_layer(
+        &self,
+        distance: &dyn Fn(u64) -> f32,
+        entry: u32,
+        ef: usize,
+        layer: usize,
+        live_only: bool,
+    ) -> Vec<Scored> {
+        let keep = |id: u32| !live_only || !self.nodes[id as usize].deleted;
+        let start = Scored(distance(self.nodes[entry as usize].row_id), entry);
+        let mut visited = HashSet::from([entry]);
+        let mut candidates = BinaryHeap::from([Reverse(start)]);
+        let mut found = BinaryHeap::new();
+        if keep(entry) {
+            found.push(start);
+        }
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            let worst = found.peek().map_or(f32::INFINITY, |scored| scored.0);
+            if current.0 > worst && found.len() >= ef {
+                break;
+            }
+            let Some(links) = self.nodes[current.1 as usize].neighbors.get(layer) else {
+                continue;
+            };
+            for &next in links {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let scored = Scored(distance(self.nodes[next as usize].row_id), next);
+                let worst = found.peek().map_or(f32::INFINITY, |scored| scored.0);
+                if found.len() < ef || scored.0 < worst {
+                    candidates.push(Reverse(scored));
+                    if keep(next) {
+                        found.push(scored);
+                        if found.len() > ef {
+                            found.pop();
+                        }
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Serializes the graph, tombstones and level generator included, for `decode`. Integers
+    /// are little-endian; a node is `[row_id: u64][deleted: u8][levels: u8]`, then per layer
+    /// `[count: u32][count x u32]`.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for value in [self.spec.m, self.spec.ef_construction, self.spec.ef_search] {
+            out.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+        out.push(match self.metric {
+            DistanceMetric::Cosine => 0,
+            DistanceMetric::L2 => 1,
+        });
+        out.extend_from_slice(&self.entry.unwrap_or(u32::MAX).to_le_bytes());
+        out.extend_from_slice(&(self.max_level as u32).to_le_bytes());
+        out.extend_from_slice(&self.rng.to_le_bytes());
+        out.extend_from_slice(&(self.nodes.len() as u32).to_le_bytes());
+        for node in &self.nodes {
+            out.extend_from_slice(&node.row_id.to_le_bytes());
+            out.push(node.deleted as u8);
+            out.push(node.neighbors.len() as u8);
+            for links in &node.neighbors {
+                out.extend_from_slice(&(links.len() as u32).to_le_bytes());
+                for link in links {
+                    out.extend_from_slice(&link.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    /// Reads a graph written by `encode`, or `None` if `data` is not a well-formed one.
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data };
+        let mut usize_field = || usize::try_from(u64::from_le_bytes(reader.take()?)).ok();
+        let spec = HnswSpec {
+            m: usize_field()?,
+            ef_construction: usize_field()?,
+            ef_search: usize_field()?,
+        };
+        let metric = match reader.take::<1>()? {
+            [0] => DistanceMetric::Cosine,
+            [1] => DistanceMetric::L2,
+            _ => return None,
+        };
+        let entry = Some(u32::from_le_bytes(reader.take()?)).filter(|id| *id != u32::MAX);
+        let max_level = u32::from_le_bytes(reader.take()?) as usize;
+        let rng = u64::from_le_bytes(reader.take()?);
+        let count = u32::from_le_bytes(reader.take()?);
+
+        let mut nodes = Vec::new();
+        let mut by_row = HashMap::new();
+        let mut deleted = 0;
+        for id in 0..count {
+            let row_id = u64::from_le_bytes(reader.take()?);
+            let [tombstoned, levels] = reader.take()?;
+            let mut neighbors = Vec::with_capacity(levels as usize);
+            for _ in 0..levels {
+                let links = u32::from_le_bytes(reader.take()?);
+                let links = (0..links)
+                    .map(|_| reader.take().map(u32::from_le_bytes))
+                    .collect::<Option<Vec<u32>>>()?;
+                if links.iter().any(|link| *link >= count) {
+                    return None;
+                }
+                neighbors.push(links);
+            }
+            if tombstoned != 0 {
+                deleted += 1;
+            } else if by_row.insert(row_id, id).is_some() {
+                return None;
+            }
+            nodes.push(HnswNode {
+                row_id,
+                neighbors,
+                deleted: tombstoned != 0,
+            });
+        }
+        let entry_ok = match entry {
+            Some(id) => nodes
+                .get(id as usize)
+                .is_some_and(|node| node.neighbors.len() > max_level),
+            None => nodes.is_empty(),
+        };
+        if !reader.data.is_empty() || !entry_ok {
+            return None;
+        }
+        Some(Self {
+            spec,
+            metric,
+            nodes,
+            by_row,
+            entry,
+            max_level,
+            deleted,
+            rng,
+        })
+    }
+
+    /// Draws a level from the usual exponential distribution with a fixed-seed xorshift, so
+    /// replaying the same WAL rebuilds the same graph.
+    fn random_level(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let uniform = ((self.rng >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let scale = 1.0 / (self.spec.m as f64).ln();
+        ((-uniform.ln() * scale) as usize).min(MAX_LEVEL)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.data.split_first_chunk::<N>()?;
+        self.data = rest;
+        Some(*head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn l2(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+    }
+
+    #[test]
+    fn hnsw_finds_exact_neighbors_on_small_sets() {
+        let points: Vec<Vec<f32>> = (0..300)
+            .map(|i| vec![(i % 17) as f32, (i / 17) as f32])
+            .collect();
+        let mut index = Hnsw::new(HnswSpec::default(), DistanceMetric::L2);
+        for (i, point) in points.iter().enumerate() {
+            index.insert(
+                i as u64,
+                &|row| l2(&points[row as usize], point),
+                &|a, b| l2(&points[a as usize], &points[b as usize]),
+            );
+        }
+        assert_eq!(index.len(), 300);
+
+        let query = [3.2, 4.9];
+        let hits = index.search(&|row| l2(&points[row as usize], &query), 64);
+        let mut exact: Vec<(u64, f32)> = (0..300u64)
+            .map(|row| (row, l2(&points[row as usize], &query)))
+            .collect();
+        exact.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let top: Vec<u64> = hits.iter().take(5).map(|hit| hit.0).collect();
+        let expected: Vec<u64> = exact.iter().take(5).map(|hit| hit.0).collect();
+        assert_eq!(top, expected);
+    }
+
+    #[test]
+    fn hnsw_roundtrips_through_encode() {
+        let points: Vec<Vec<f32>> = (0..50).map(|i| vec![i as f32, (i % 7) as f32]).collect();
+        let mut index = Hnsw::new(HnswSpec::default(), DistanceMetric::L2);
+        for (i, point) in points.iter().enumerate() {
+            index.insert(
+                i as u64,
+                &|row| l2(&points[row as usize], point),
+                &|a, b| l2(&points[a as usize], &points[b as usize]),
+            );
+        }
+        index.remove(3);
+
+        let data = index.encode();
+        let decoded = Hnsw::decode(&data).unwrap();
+        assert_eq!(decoded.len(), 49);
+        assert_eq!(decoded.tombstones(), 1);
+        assert_eq!(decoded.encode(), data);
+        let query = |row: u64| l2(&points[row as usize], &[10.0, 3.0]);
+        assert_eq!(index.search(&query, 10), decoded.search(&query, 10));
+
+        assert!(Hnsw::decode(&data[..data.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn hnsw_skips_removed_rows() {
+        let points: Vec<Vec<f32>> = (0..20).map(|i| vec![i as f32]).collect();
+        let mut index = Hnsw::new(HnswSpec::default(), DistanceMetric::L2);
+        for (i, point) in points.iter().enumerate() {
+            index.insert(
+                i as u64,
+                &|row| l2(&points[row as usize], point),
+                &|a, b| l2(&points[a as usize], &points[b as usize]),
+            );
+        }
+        index.remove(5);
+        let hits = index.search(&|row| l2(&points[row as usize], &[5.0]), 3);
+        assert!(hits.iter().all(|hit| hit.0 != 5));
+        assert_eq!(hits.len(), 3);
+        assert_eq!(index.len(), 19);
+    }
+}
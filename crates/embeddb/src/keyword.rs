@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+// Standard BM25 parameters: term-frequency saturation and document-length normalization.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Inverted index over the String columns of a table's rows, scored with BM25.
+#[derive(Debug, Default)]
+pub(crate) struct KeywordIndex {
+    // Term -> row id -> occurrences of the term in the row.
+    postings: HashMap<String, HashMap<u64, u32>>,
+    // Row id -> (token count, distinct terms), so a row can be removed without re-tokenizing.
+    docs: HashMap<u64, (u32, Vec<String>)>,
+    total_len: u64,
+}
+
+/// Lowercased alphanumeric runs of `text`.
+pub(crate) fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
+
+impl KeywordIndex {
+    /// Indexes a row's text, replacing whatever was indexed for it before.
+    pub(crate) fn insert<'a>(&mut self, row_id: u64, texts: impl IntoIterator<Item = &'a str>) {
+        self.remove(row_id);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        let mut len = 0u32;
+        for token in texts.into_iter().flat_map(tokenize) {
+            *counts.entry(token).or_default() += 1;
+            len += 1;
+        }
+        if len == 0 {
+            return;
+        }
+        let mut terms = Vec::with_capacity(counts.len());
+        for (term, count) in counts {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(row_id, count);
+            terms.push(term);
+        }
+        self.docs.insert(row_id, (len, terms));
+        self.total_len += u64::from(len);
+    }
+
+    pub(crate) fn remove(&mut self, row_id: u64) {
+        let Some((len, terms)) = self.docs.remove(&row_id) else {
+            return;
+        };
+        self.total_len -= u64::from(len);
+        for term in terms {
+            if let Some(rows) = self.postings.get_mut(&term) {
+                rows.remove(&row_id);
+                if rows.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// BM25 score of every row containing at least one query term, best first.
+    pub(crate) fn search(&self, query: &str) -> Vec<(u64, f32)> {
+        if self.docs.is_empty() {
+            return Vec::new();
+        }
+        let mut terms: Vec<String> = tokenize(query).collect();
+        terms.sort();
+        terms.dedup();
+
+        let docs = self.docs.len() as f32;
+        let avg_len = self.total_len as f32 / docs;
+        let mut scores: HashMap<u64, f32> = HashMap::new();
+        for term in &terms {
+            let Some(rows) = self.postings.get(term) else {
+                continue;
+            };
+            let df = rows.len() as f32;
+            let idf = (1.0 + (docs - df + 0.5) / (df + 0.5)).ln();
+            for (row_id, tf) in rows {
+                let tf = *tf as f32;
+                let len = self.docs.get(row_id).map_or(0.0, |(len, _)| *len as f32);
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len);
+                *scores.entry(*row_id).or_default() += idf * tf * (BM25_K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut ranked: Vec<(u64, f32)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bm25_prefers_rare_terms_and_forgets_removed_rows() {
+        let mut index = KeywordIndex::default();
+        index.insert(1, ["The quick brown fox"]);
+        index.insert(2, ["the lazy dog", "The end."]);
+        index.insert(3, ["Quick, quick!"]);
+
+        let order = |index: &KeywordIndex, query: &str| -> Vec<u64> {
+            index
+                .search(query)
+                .iter()
+                .map(|(row_id, _)| *row_id)
+                .collect()
+        };
+        // Row 3 repeats "quick" in a shorter row.
+        assert_eq!(order(&index, "quick"), vec![3, 1]);
+        // "dog" is in one row and "quick" in two, so the rarer match ranks first.
+        assert_eq!(order(&index, "QUICK dog"), vec![2, 3, 1]);
+        assert!(index.search("missing").is_empty());
+
+        index.remove(3);
+        index.insert(1, ["a dog"]);
+        assert_eq!(order(&index, "quick dog"), vec![1, 2]);
+        index.remove(1);
+        index.remove(2);
+        assert!(index.postings.is_empty());
+        assert_eq!(index.total_len, 0);
+    }
+}
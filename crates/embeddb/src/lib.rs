@@ -2,31 +2,101 @@
 //!
 //! This crate provides the embedded database engine and public APIs.
 
+mod aggregate;
+#[cfg(feature = "async")]
+mod async_db;
+mod audit;
+mod batch;
+mod cache;
+mod changes;
+#[cfg(feature = "arrow")]
+mod columnar;
+mod compaction;
+mod dedup;
+mod durability;
+mod filter;
+mod fusion;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod history;
+mod index;
+mod keyword;
+mod manager;
+mod metric;
 mod schema;
 mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod trigger;
 mod vector;
+mod verify;
+mod worker;
 
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::cmp::Ordering;
+use std::collections::{hash_map, BTreeMap, BTreeSet, HashMap};
 use std::fs::{self, File, OpenOptions};
 use std::io::ErrorKind;
+use std::ops::{Deref, DerefMut, Range};
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::{Mutex, MutexGuard};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
-
-use anyhow::{anyhow, Result};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use audit::AuditLog;
+use cache::{SearchCache, SearchCacheKey};
+use changes::ChangeFeed;
+use dedup::Clusters;
+use durability::WalSyncer;
 use fs2::FileExt;
-use schema::EmbeddingMeta;
+use index::Hnsw;
+use keyword::KeywordIndex;
+use metric::MetricRegistry;
+use schema::{EmbeddingMeta, SchemaChange};
 use serde::{Deserialize, Serialize};
+use storage::fault;
+use storage::indexfile;
+use storage::rawvec::{self, RawVectorStore};
 use storage::sst::{self, SstEntry, SstFile};
-use storage::wal::{Wal, WalRecord};
-use vector::{distance, SearchResult};
-
-pub use schema::{Column, DataType, EmbeddingSpec, RowData, TableSchema, Value};
-
-const EMBEDDING_MAX_ATTEMPTS: u32 = 5;
-const EMBEDDING_BACKOFF_BASE_MS: u64 = 250;
-const EMBEDDING_BACKOFF_CAP_MS: u64 = 30_000;
+use storage::vecseg::{self, VectorEntry, VectorSegmentFile};
+use storage::wal::{self, GroupCommit, Wal};
+use trigger::TriggerSet;
+use vector::{distance_stored, distance_to_unit, SearchResult, StoredVector, TopK};
+use worker::{CompactionWorker, EmbeddingWorker};
+
+pub use aggregate::{AggregateFn, AggregateRow, Aggregation};
+#[cfg(feature = "async")]
+pub use async_db::{audit_actor_scope, AsyncEmbedDb};
+pub use audit::{with_audit_actor, AuditConfig, AuditEntry, AuditOp};
+pub use batch::ScoringBackend;
+pub use changes::{ChangeEvent, ChangePage, ChangeSubscription, ChangesLagged};
+#[cfg(feature = "arrow")]
+pub use columnar::{ArrowBatches, EMBEDDING_COLUMN, ROW_ID_COLUMN};
+pub use compaction::{CompactionPolicy, CompactionStats, LevelMerge};
+pub use dedup::DuplicateCluster;
+pub use durability::Durability;
+pub use filter::parse_filter;
+pub use fusion::Fusion;
+pub use history::{HistoricalView, WalEntry};
+pub use index::{HnswSpec, IndexKind, IndexSpec, IndexState, IndexStatus, SearchExplain};
+pub use manager::EmbedDbManager;
+pub use metric::DistanceFn;
+pub use schema::{
+    AlterTableOp, Collation, Column, ColumnConstraints, ColumnDefault, ColumnExpr, DataType,
+    EmbeddingModel, EmbeddingSpec, NamedVectorSpec, Pattern, RetryPolicy, RowData, SoftDelete,
+    TableSchema, Value, CREATED_AT_COLUMN, UPDATED_AT_COLUMN,
+};
+pub use storage::codec::RowCodecKind;
+pub use storage::wal::{WalEncoding, WalFrame, WalInspection, WalRecord};
+pub use trigger::{RowChange, RowChangeKind, RowTrigger};
+pub use vector::{SparseVector, VectorEncoding};
+pub use verify::{SstCheck, TableVerification};
+pub use worker::{BackgroundEmbedding, CompactionWorkerStatus, EmbeddingWorkerStatus};
+
+// Embedding jobs enqueued by `apply_embedding_spec` per WAL sync.
+const REEMBED_BATCH_ROWS: usize = 1024;
+// Neighbours `find_duplicates` fetches per row at first; doubled while all are within threshold.
+const DUPLICATE_NEIGHBORS: usize = 16;
 
 fn now_epoch_ms() -> u64 {
     SystemTime::now()
@@ -35,15 +105,9 @@ fn now_epoch_ms() -> u64 {
         .unwrap_or(0)
 }
 
-fn embedding_backoff_ms(attempts: u32) -> u64 {
-    if attempts <= 1 {
-        return EMBEDDING_BACKOFF_BASE_MS;
-    }
-    let exp = attempts.saturating_sub(1).min(20);
-    let mult = 1u64.checked_shl(exp).unwrap_or(u64::MAX);
-    EMBEDDING_BACKOFF_BASE_MS
-        .saturating_mul(mult)
-        .min(EMBEDDING_BACKOFF_CAP_MS)
+/// Unix milliseconds `ttl` from now.
+fn expiry_after(ttl: Duration) -> u64 {
+    now_epoch_ms().saturating_add(ttl.as_millis().try_into().unwrap_or(u64::MAX))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +115,93 @@ pub struct Config {
     pub data_dir: PathBuf,
     #[serde(default)]
     pub wal_autocheckpoint_bytes: Option<u64>,
+    /// Seal `wal.log` into `wal_segments/` once it reaches this size, so no single WAL file grows
+    /// unbounded between checkpoints. Sealed segments are replayed on open and retired (or
+    /// archived) by the next checkpoint.
+    #[serde(default)]
+    pub wal_segment_bytes: Option<u64>,
+    /// When set, writers append to the WAL without syncing and share one sync per group instead:
+    /// the first writer waiting in a group lets others append for up to this long, then syncs for
+    /// all of them. Writes still return only once durable. `None` syncs every write on its own.
+    #[serde(default)]
+    pub wal_group_commit: Option<Duration>,
+    /// When WAL appends are synced. Group commit only applies to `Durability::Always`.
+    #[serde(default)]
+    pub durability: Durability,
+    /// zstd level for compressing WAL records of at least a few hundred bytes; `None` stores them
+    /// uncompressed. Either way, vectors are written as raw f32 bytes rather than JSON.
+    #[serde(default)]
+    pub wal_compression: Option<i32>,
+    /// Encoding for WAL records. Replay reads either encoding, so this can be changed between
+    /// opens of the same data dir: an existing WAL file keeps its format until the next
+    /// checkpoint replaces it.
+    #[serde(default)]
+    pub wal_encoding: WalEncoding,
+    /// Encoding used for rows in newly written SST files. Existing files keep the codec recorded
+    /// in their header, so this can be changed between opens of the same data dir.
+    #[serde(default)]
+    pub row_codec: RowCodecKind,
+    /// Before a write, flush any table whose memtable (unflushed rows and tombstones, see
+    /// `TableStats::memtable_bytes`) has grown to this estimated size. If that flush fails, the
+    /// write fails with `WriteStall` instead of growing the memtable further. `None` leaves
+    /// flushing to the caller.
+    #[serde(default)]
+    pub memtable_max_bytes: Option<u64>,
+    /// Maximum number of cached search results; 0 disables the cache. Cached entries for a table
+    /// are invalidated by any write to that table.
+    #[serde(default)]
+    pub search_cache_capacity: usize,
+    /// Number of recent row changes the change feed holds for `EmbedDb::changes_since`; readers
+    /// further behind get `ChangesLagged`.
+    #[serde(default = "changes::default_change_feed_capacity")]
+    pub change_feed_capacity: usize,
+    /// Keep the WAL segment replaced by each checkpoint under `wal_archive/` so `read_at_lsn` can
+    /// reach back past checkpoints. Archived segments are never pruned automatically.
+    #[serde(default)]
+    pub wal_archive: bool,
+    /// Candidates fetched per requested result when searching a quantized table; the candidates
+    /// are re-scored against the exact f32 vectors before the top `k` are returned. 0 disables
+    /// re-scoring.
+    #[serde(default = "default_rescore_oversample")]
+    pub rescore_oversample: usize,
+    /// Backend for `search_knn_batch`. `Gpu` only takes effect with the `gpu` feature.
+    #[serde(default)]
+    pub scoring_backend: ScoringBackend,
+    /// Level sizes, output file sizes, and the level-0 file count and size that trigger
+    /// compaction after a flush or in the background compaction worker.
+    #[serde(default)]
+    pub compaction: CompactionPolicy,
+    /// Policies replacing `compaction` for the named tables.
+    #[serde(default)]
+    pub table_compaction: BTreeMap<String, CompactionPolicy>,
+    /// When set, `open` starts a thread that compacts, at this interval, every table whose level
+    /// 0 reached its policy's `l0_trigger_files` or `l0_trigger_bytes`. The thread stops when the
+    /// `EmbedDb` is dropped.
+    #[serde(default)]
+    pub background_compaction: Option<Duration>,
+    /// When set, `open` starts a thread that drains pending embedding jobs on every table at
+    /// this interval. The thread stops when the `EmbedDb` is dropped.
+    #[serde(skip)]
+    pub background_embedding: Option<BackgroundEmbedding>,
+    /// Open without taking the data directory lock and without changing any file, so the
+    /// directory can be read while another process has it open. Every write fails, and no
+    /// background worker is started. The view is the state as of open; a concurrent writer's
+    /// flushes and compactions can make later reads fail until the database is reopened.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Run `EmbedDb::close` when the handle is dropped, so the next open starts from a fresh
+    /// checkpoint. Errors can only be logged there; call `close` directly to see them.
+    #[serde(default)]
+    pub close_on_drop: bool,
+    /// Run `EmbedDb::warm_table` on every table at the end of `open`, so the first queries after
+    /// a restart don't pay for cold reads. Open takes longer by the time it spends reading.
+    #[serde(default)]
+    pub warm_on_open: bool,
+    /// When set, every write appends who made it, when, and to which table and rows to the
+    /// audit log under `audit/`, read back with `EmbedDb::audit_log`. Read-only opens record
+    /// nothing.
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
 }
 
 impl Config {
@@ -58,6 +209,26 @@ impl Config {
         Self {
             data_dir,
             wal_autocheckpoint_bytes: None,
+            wal_segment_bytes: None,
+            wal_group_commit: None,
+            durability: Durability::Always,
+            wal_compression: None,
+            wal_encoding: WalEncoding::Json,
+            row_codec: RowCodecKind::Json,
+            memtable_max_bytes: None,
+            search_cache_capacity: 0,
+            change_feed_capacity: changes::default_change_feed_capacity(),
+            wal_archive: false,
+            rescore_oversample: default_rescore_oversample(),
+            scoring_backend: ScoringBackend::Cpu,
+            compaction: CompactionPolicy::default(),
+            table_compaction: BTreeMap::new(),
+            background_compaction: None,
+            background_embedding: None,
+            read_only: false,
+            close_on_drop: false,
+            warm_on_open: false,
+            audit: None,
         }
     }
 
@@ -65,9 +236,126 @@ impl Config {
         self.wal_autocheckpoint_bytes = Some(bytes);
         self
     }
+
+    pub fn with_wal_segment_bytes(mut self, bytes: u64) -> Self {
+        self.wal_segment_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_wal_group_commit(mut self, max_latency: Duration) -> Self {
+        self.wal_group_commit = Some(max_latency);
+        self
+    }
+
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    pub fn with_wal_compression(mut self, level: i32) -> Self {
+        self.wal_compression = Some(level);
+        self
+    }
+
+    pub fn with_wal_encoding(mut self, encoding: WalEncoding) -> Self {
+        self.wal_encoding = encoding;
+        self
+    }
+
+    pub fn with_row_codec(mut self, codec: RowCodecKind) -> Self {
+        self.row_codec = codec;
+        self
+    }
+
+    pub fn with_memtable_max_bytes(mut self, bytes: u64) -> Self {
+        self.memtable_max_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_search_cache_capacity(mut self, capacity: usize) -> Self {
+        self.search_cache_capacity = capacity;
+        self
+    }
+
+    pub fn with_change_feed_capacity(mut self, capacity: usize) -> Self {
+        self.change_feed_capacity = capacity;
+        self
+    }
+
+    pub fn with_wal_archive(mut self, enabled: bool) -> Self {
+        self.wal_archive = enabled;
+        self
+    }
+
+    pub fn with_audit_log(mut self, audit: AuditConfig) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn with_close_on_drop(mut self, enabled: bool) -> Self {
+        self.close_on_drop = enabled;
+        self
+    }
+
+    pub fn with_warm_on_open(mut self, enabled: bool) -> Self {
+        self.warm_on_open = enabled;
+        self
+    }
+
+    pub fn with_rescore_oversample(mut self, oversample: usize) -> Self {
+        self.rescore_oversample = oversample;
+        self
+    }
+
+    pub fn with_scoring_backend(mut self, backend: ScoringBackend) -> Self {
+        self.scoring_backend = backend;
+        self
+    }
+
+    pub fn with_compaction_policy(mut self, policy: CompactionPolicy) -> Self {
+        self.compaction = policy;
+        self
+    }
+
+    pub fn with_table_compaction_policy(
+        mut self,
+        table: impl Into<String>,
+        policy: CompactionPolicy,
+    ) -> Self {
+        self.table_compaction.insert(table.into(), policy);
+        self
+    }
+
+    pub fn with_background_compaction(mut self, interval: Duration) -> Self {
+        self.background_compaction = Some(interval);
+        self
+    }
+
+    /// The policy `table` is compacted with.
+    pub fn compaction_policy(&self, table: &str) -> &CompactionPolicy {
+        self.table_compaction.get(table).unwrap_or(&self.compaction)
+    }
+
+    pub fn with_background_embedding(
+        mut self,
+        embedder: Arc<dyn Embedder>,
+        interval: Duration,
+    ) -> Self {
+        self.background_embedding = Some(BackgroundEmbedding { embedder, interval });
+        self
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+fn default_rescore_oversample() -> usize {
+    4
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DistanceMetric {
     Cosine,
     L2,
@@ -95,16 +383,210 @@ pub struct EmbeddingJob {
 pub struct SearchHit {
     pub row_id: u64,
     pub distance: f32,
+    /// The distance as a similarity in `0..=1` (1 is an exact match), with
+    /// `ScoreMode::Similarity`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+}
+
+impl From<SearchResult> for SearchHit {
+    fn from(res: SearchResult) -> Self {
+        Self {
+            row_id: res.row_id,
+            distance: res.distance,
+            score: None,
+        }
+    }
+}
+
+/// Error from `update_row_if_version` and `delete_row_if_version` when the row was changed since
+/// the caller read it. Recover it with `err.downcast_ref::<VersionConflict>()`.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("row {row_id} is at version {actual}, not the expected version {expected}")]
+pub struct VersionConflict {
+    pub row_id: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Error from a write when a table's memtable reached `Config::memtable_max_bytes` and flushing
+/// it failed, so the write was refused rather than let the memtable keep growing. Nothing was
+/// written; retry once the cause (often a full or failing disk) is fixed. Recover it with
+/// `err.downcast_ref::<WriteStall>()`.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error(
+    "write stalled: table '{table}' memtable holds {memtable_bytes} bytes (limit {limit_bytes}) \
+     and flushing it failed: {reason}"
+)]
+pub struct WriteStall {
+    pub table: String,
+    pub memtable_bytes: u64,
+    pub limit_bytes: u64,
+    pub reason: String,
+}
+
+/// One write in a batch applied atomically by `EmbedDb::write_batch`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum WriteOp {
+    Insert {
+        table: String,
+        fields: BTreeMap<String, Value>,
+    },
+    /// Replaces the fields of a row, failing the batch with a `VersionConflict` unless the row is
+    /// at `expected_version` when given.
+    Update {
+        table: String,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+        #[serde(default)]
+        expected_version: Option<u64>,
+    },
+    Delete {
+        table: String,
+        row_id: u64,
+        #[serde(default)]
+        expected_version: Option<u64>,
+    },
+}
+
+/// The row a `WriteOp` wrote and its new version; `None` for a delete.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteOpResult {
+    pub row_id: u64,
+    pub version: Option<u64>,
+}
+
+/// A search hit with the fields of its row, returned by `search_knn_with_rows`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHitWithRow {
+    pub row_id: u64,
+    pub distance: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    pub fields: BTreeMap<String, Value>,
+}
+
+/// Optional knobs for `search_knn_with_options`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchOptions {
+    /// Search with a metric other than the one declared on the table's `EmbeddingSpec`.
+    #[serde(default)]
+    pub allow_metric_mismatch: bool,
+    /// Search one of the spec's named vectors instead of the table's unnamed vector. Named
+    /// vector searches are exact scans and are not cached.
+    #[serde(default)]
+    pub vector: Option<String>,
+    /// Skip this many of the best hits, returning hits `offset..offset + k`.
+    #[serde(default)]
+    pub offset: usize,
+    /// Return only hits ranked after this one, such as the last hit of the previous page. Unlike
+    /// `offset`, a page stays put when rows are inserted ahead of it.
+    #[serde(default)]
+    pub after: Option<SearchCursor>,
+    #[serde(default)]
+    pub score_mode: ScoreMode,
+    /// Consider only these rows, such as the ones an external permission check found visible.
+    /// An empty set matches nothing; searches with a set skip the HNSW index and score just the
+    /// listed rows.
+    #[serde(default)]
+    pub include_ids: Option<BTreeSet<u64>>,
+    /// Never return these rows.
+    #[serde(default)]
+    pub exclude_ids: BTreeSet<u64>,
+}
+
+impl SearchOptions {
+    /// Whether `include_ids` and `exclude_ids` let `row_id` be a candidate.
+    pub fn allows(&self, row_id: u64) -> bool {
+        self.include_ids
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&row_id))
+            && !self.exclude_ids.contains(&row_id)
+    }
+}
+
+/// Whether search hits carry a `score` next to their raw distance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoreMode {
+    /// Distances only: squared L2, `1 - cos` for cosine, or a custom metric's own.
+    #[default]
+    Distance,
+    /// Also a similarity in `0..=1` from `vector::similarity`, comparable across metrics: `1 -
+    /// d / 2` for cosine and `1 / (1 + d)` for L2 and custom metrics.
+    Similarity,
+}
+
+impl ScoreMode {
+    /// Fills in the hits' scores from their distances with `similarity`, in `Similarity` mode.
+    fn apply(self, hits: &mut [SearchHit], similarity: impl Fn(f32) -> f32) {
+        if self == ScoreMode::Similarity {
+            for hit in hits {
+                hit.score = Some(similarity(hit.distance));
+            }
+        }
+    }
+}
+
+/// A position in ranked search results: hits are ordered by distance, then by row id.
+///
+/// Displays and parses as `<row_id>:<distance bits in hex>`, so it survives a round trip
+/// through a URL or header without losing precision.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SearchCursor {
+    pub row_id: u64,
+    pub distance: f32,
+}
+
+impl SearchCursor {
+    /// Whether `hit` ranks after the cursor.
+    pub fn admits(&self, hit: &SearchHit) -> bool {
+        hit.distance
+            .total_cmp(&self.distance)
+            .then(hit.row_id.cmp(&self.row_id))
+            .is_gt()
+    }
+}
+
+impl From<&SearchHit> for SearchCursor {
+    fn from(hit: &SearchHit) -> Self {
+        Self {
+            row_id: hit.row_id,
+            distance: hit.distance,
+        }
+    }
+}
+
+impl std::fmt::Display for SearchCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{:08x}", self.row_id, self.distance.to_bits())
+    }
+}
+
+impl std::str::FromStr for SearchCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid search cursor '{raw}'");
+        let (row_id, bits) = raw.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            row_id: row_id.parse().map_err(|_| invalid())?,
+            distance: f32::from_bits(u32::from_str_radix(bits, 16).map_err(|_| invalid())?),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FilterOp {
     Eq,
+    #[serde(alias = "Ne")]
     Neq,
     Lt,
     Lte,
     Gt,
     Gte,
+    /// Substring match on a string column.
+    Contains,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,6 +601,9 @@ pub struct TableDescriptor {
     pub name: String,
     pub schema: TableSchema,
     pub embedding_spec: Option<EmbeddingSpec>,
+    /// Length of the table's embeddings: declared by the spec, or learned from the first stored
+    /// embedding. `None` until either is known.
+    pub dimension: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -126,28 +611,68 @@ pub struct TableStats {
     pub name: String,
     pub rows_mem: usize,
     pub tombstones_mem: usize,
+    /// Estimated size of the memtable: the rows and tombstones not yet flushed to SST files.
+    #[serde(default)]
+    pub memtable_bytes: u64,
     pub embeddings_total: usize,
     pub embeddings_pending: usize,
     pub embeddings_ready: usize,
     pub embeddings_failed: usize,
     pub sst_files: usize,
+    /// SST files per level, starting at level 0.
+    pub sst_files_per_level: Vec<usize>,
+    /// On-disk bytes of the SST files per level, starting at level 0.
+    #[serde(default)]
+    pub sst_bytes_per_level: Vec<u64>,
+    /// Rows across the memtable and SST files, including rows past their TTL that have not been
+    /// purged yet. The first call after open (or after a range delete) counts them with a scan.
+    #[serde(default)]
+    pub total_rows: u64,
+    /// Point and range tombstones across the memtable and SST files, dropped by compaction into
+    /// the last level.
+    #[serde(default)]
+    pub total_tombstones: u64,
+    /// Soft-deleted rows waiting to be restored or purged; not counted in `total_rows`.
+    #[serde(default)]
+    pub hidden_rows: u64,
+    /// Memory held by the table's resident vectors (dense, named, and sparse, plus cosine norms).
+    #[serde(default)]
+    pub embedding_memory_bytes: u64,
+    /// Vector segment files holding the table's flushed embeddings.
+    pub vector_segments: usize,
     pub next_row_id: u64,
     pub wal_durable_appends: u64,
     pub embeddings_processed_total: u64,
     pub embeddings_failed_total: u64,
     pub embeddings_retried_total: u64,
+    /// Updates since open that left the embedding source unchanged, so no job was enqueued.
+    pub skipped_unchanged: u64,
     pub flush_count: u64,
     pub flush_total_ms: u64,
     pub compact_count: u64,
     pub compact_total_ms: u64,
+    /// When the table was last compacted, in epoch milliseconds; `None` when it hasn't been
+    /// since open.
+    #[serde(default)]
+    pub last_compacted_at_ms: Option<u64>,
+    pub index: IndexStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DbStats {
     pub tables: usize,
+    pub lsn: u64,
+    /// Total size of `wal.log` and any sealed segments.
     pub wal_bytes: u64,
+    /// Segments sealed by `Config::wal_segment_bytes` since the last checkpoint.
+    pub wal_segments: usize,
+    /// Times `wal.log` was sealed into a segment since open.
+    pub wal_rotations: u64,
     pub wal_durable_appends: u64,
     pub wal_sync_ops: u64,
+    /// WAL records appended since the last sync; always 0 under `Durability::Always`.
+    #[serde(default)]
+    pub wal_unsynced_records: u64,
     pub checkpoints: u64,
     pub auto_checkpoints: u64,
     pub checkpoint_total_ms: u64,
@@ -158,6 +683,91 @@ pub struct DbStats {
     pub embeddings_processed_total: u64,
     pub embeddings_failed_total: u64,
     pub embeddings_retried_total: u64,
+    pub search_cache_hits: u64,
+    pub search_cache_misses: u64,
+    /// Present when the background embedding worker is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_worker: Option<EmbeddingWorkerStatus>,
+    /// Present when the background compaction worker is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compaction_worker: Option<CompactionWorkerStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingRecord {
+    pub row_id: u64,
+    /// `None` while the row's embedding has not been computed yet.
+    pub vector: Option<Vec<f32>>,
+    pub status: EmbeddingStatus,
+    /// Model of the embedding spec the vector was stored under, when the spec named one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<EmbeddingModel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingJobPage {
+    pub items: Vec<EmbeddingJob>,
+    /// Pass back as `cursor` to fetch the next page; `None` once the matching jobs are exhausted,
+    /// and always `None` for listings not sorted by row id, which page by offset instead.
+    pub next_cursor: Option<u64>,
+    /// Jobs matching the listing's status and cursor, before its offset and limit.
+    #[serde(default)]
+    pub total: usize,
+}
+
+/// Order of `list_embedding_jobs_with_options` results. Ties are broken by ascending row id.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobSort {
+    #[default]
+    RowId,
+    Attempts,
+    NextRetryAt,
+}
+
+/// Filtering and paging for `list_embedding_jobs_with_options`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobListOptions {
+    /// Keep only jobs in this status.
+    #[serde(default)]
+    pub status: Option<EmbeddingStatus>,
+    /// Start after this row id in `JobSort::RowId` order; other sorts page with `offset`.
+    #[serde(default)]
+    pub cursor: Option<u64>,
+    /// Matching jobs to skip before the page starts.
+    #[serde(default)]
+    pub offset: usize,
+    /// Page size; unlimited when unset.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub sort: JobSort,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowPage {
+    pub items: Vec<RowData>,
+    /// Pass back as `start_after` to fetch the next page; `None` once the table is exhausted.
+    pub next_cursor: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingPage {
+    pub items: Vec<EmbeddingRecord>,
+    /// Pass back as `cursor` to fetch the next page; `None` once the table is exhausted.
+    pub next_cursor: Option<u64>,
+}
+
+/// Rows whose embeddings are stale under a proposed `EmbeddingSpec`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReembedPlan {
+    /// Rows whose content hash under the new spec differs from the stored one (or that have
+    /// never been embedded, or were embedded under a model other than the spec's); these are
+    /// re-enqueued on apply.
+    pub affected_rows: Vec<u64>,
+    pub unchanged_rows: usize,
+    pub applied: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -166,1161 +776,5636 @@ pub struct CheckpointStats {
     pub wal_bytes_after: u64,
 }
 
+/// What `EmbedDb::warm_table` loaded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WarmStats {
+    pub sst_files: usize,
+    /// Bytes read from SST files and, for quantized tables, the exact-vector store.
+    pub bytes_read: u64,
+    /// Visible rows, all of which are now in the keyword index.
+    pub rows: u64,
+    /// Resident embeddings, and so the vectors in the HNSW graph when the table has one.
+    pub vectors: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnapshotStats {
     pub files_copied: u64,
     pub bytes_copied: u64,
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+struct TombstoneCounts {
+    point: u64,
+    range: u64,
+}
+
 #[derive(Debug)]
 struct TableState {
     schema: TableSchema,
     next_row_id: u64,
     rows: BTreeMap<u64, RowData>,
     tombstones: BTreeSet<u64>,
-    embeddings: HashMap<u64, Vec<f32>>,
+    // Sorted, disjoint row id ranges deleted since the last flush. They hide rows in SST files;
+    // rows in `rows` are always newer.
+    range_tombstones: Vec<Range<u64>>,
+    // Estimated size of `rows`, `tombstones`, and `range_tombstones`, the table's memtable.
+    memtable_bytes: u64,
+    embeddings: HashMap<u64, StoredVector>,
+    // Original L2 norms for embeddings stored unit-normalized (cosine tables).
+    embedding_norms: HashMap<u64, f32>,
+    // Dimension of stored embeddings, learned from the first stored vector.
+    dimension: Option<usize>,
     embedding_meta: HashMap<u64, EmbeddingMeta>,
     embedding_spec: Option<EmbeddingSpec>,
+    // Application-supplied sparse embeddings, independent of the dense embedding pipeline.
+    sparse_vectors: HashMap<u64, SparseVector>,
+    // Unix milliseconds at which rows inserted with a TTL expire.
+    expirations: HashMap<u64, u64>,
+    // Rows deleted from a soft-delete table, with the Unix milliseconds they were hidden at. Their
+    // data stays in `rows` or the SSTs until they are restored or purged.
+    hidden: HashMap<u64, u64>,
+    // Vectors of the spec's named vector fields, by vector name and then row id.
+    named_embeddings: BTreeMap<String, HashMap<u64, Vec<f32>>>,
+    // BM25 index over the String columns of visible rows, built by the first keyword search and
+    // kept up to date by row writes after that.
+    keywords: OnceLock<KeywordIndex>,
+    // Visible rows and SST tombstones, counted by the first `table_stats` and kept up to date by
+    // writes, flushes, and compactions after that, or reset where they can't be.
+    row_count: OnceLock<u64>,
+    sst_tombstones: OnceLock<TombstoneCounts>,
+    // Approximate index over `embeddings`, present when the spec asks for one.
+    hnsw: Option<Hnsw>,
+    // Set while `open` replays the WAL: `hnsw` is left empty and loaded or rebuilt afterwards.
+    index_deferred: bool,
+    // Bumped whenever `embeddings` changes.
+    embedding_version: u64,
+    // `embedding_version` as of the last index file written for the table.
+    persisted_index_version: Option<u64>,
     sst_files: Vec<SstFile>,
+    // Schema changes not yet applied to the rows of older SST files, oldest first.
+    schema_changes: Vec<SchemaChange>,
+    // Shared by SST and vector segment files, so file names never collide across the two.
     next_sst_seq: u64,
+    vector_segments: Vec<VectorSegmentFile>,
+    // Rows whose embedding was stored or removed since it was last written to a vector segment.
+    vector_changes: BTreeSet<u64>,
     metrics: TableRuntimeMetrics,
 }
 
-#[derive(Debug)]
-struct DbState {
-    tables: HashMap<String, TableState>,
-}
-
-#[derive(Debug)]
-struct Inner {
-    wal: Wal,
-    state: DbState,
-    metrics: RuntimeMetrics,
-}
-
-#[derive(Debug, Default)]
-struct TableRuntimeMetrics {
-    wal_durable_appends: u64,
-    embeddings_processed_total: u64,
-    embeddings_failed_total: u64,
-    embeddings_retried_total: u64,
-    flush_count: u64,
-    flush_total_ms: u64,
-    compact_count: u64,
-    compact_total_ms: u64,
-}
+impl TableState {
+    fn new(schema: TableSchema, embedding_spec: Option<EmbeddingSpec>) -> Self {
+        let hnsw = new_hnsw(embedding_spec.as_ref());
+        Self {
+            schema,
+            next_row_id: 1,
+            rows: BTreeMap::new(),
+            tombstones: BTreeSet::new(),
+            range_tombstones: Vec::new(),
+            memtable_bytes: 0,
+            embeddings: HashMap::new(),
+            embedding_norms: HashMap::new(),
+            dimension: None,
+            embedding_meta: HashMap::new(),
+            embedding_spec,
+            sparse_vectors: HashMap::new(),
+            expirations: HashMap::new(),
+            hidden: HashMap::new(),
+            named_embeddings: BTreeMap::new(),
+            keywords: OnceLock::new(),
+            row_count: OnceLock::new(),
+            sst_tombstones: OnceLock::new(),
+            hnsw,
+            index_deferred: false,
+            embedding_version: 0,
+            persisted_index_version: None,
+            sst_files: Vec::new(),
+            schema_changes: Vec::new(),
+            next_sst_seq: 1,
+            vector_segments: Vec::new(),
+            vector_changes: BTreeSet::new(),
+            metrics: TableRuntimeMetrics::default(),
+        }
+    }
 
-#[derive(Debug, Default)]
-struct RuntimeMetrics {
-    wal_durable_appends: u64,
-    wal_sync_ops: u64,
-    checkpoints: u64,
-    auto_checkpoints: u64,
-    checkpoint_total_ms: u64,
-    flush_count_total: u64,
-    flush_total_ms: u64,
-    compact_count_total: u64,
-    compact_total_ms: u64,
-    embeddings_processed_total: u64,
-    embeddings_failed_total: u64,
-    embeddings_retried_total: u64,
-}
+    /// A frozen copy for `EmbedDb::snapshot`, reading its rows from `sst_files` and its own
+    /// copies of the memtable and embeddings. It has no vector index, so searches scan every
+    /// embedding.
+    fn snapshot_copy(&self, sst_files: Vec<SstFile>) -> Self {
+        let mut copy = Self::new(self.schema.clone(), self.embedding_spec.clone());
+        copy.hnsw = None;
+        copy.next_row_id = self.next_row_id;
+        copy.rows = self.rows.clone();
+        copy.tombstones = self.tombstones.clone();
+        copy.range_tombstones = self.range_tombstones.clone();
+        copy.memtable_bytes = self.memtable_bytes;
+        copy.embeddings = self.embeddings.clone();
+        copy.embedding_norms = self.embedding_norms.clone();
+        copy.dimension = self.dimension;
+        copy.embedding_meta = self.embedding_meta.clone();
+        copy.sparse_vectors = self.sparse_vectors.clone();
+        copy.expirations = self.expirations.clone();
+        copy.hidden = self.hidden.clone();
+        copy.named_embeddings = self.named_embeddings.clone();
+        copy.embedding_version = self.embedding_version;
+        copy.sst_files = sst_files;
+        copy.schema_changes = self.schema_changes.clone();
+        copy.next_sst_seq = self.next_sst_seq;
+        copy
+    }
 
-#[derive(Debug)]
-pub struct EmbedDb {
-    config: Config,
-    // Held for the lifetime of the EmbedDb handle so the exclusive directory lock is released on
-    // drop.
-    _dir_lock: File,
-    inner: Mutex<Inner>,
-}
+    /// The declared embedding length, or else the length of the embeddings already stored.
+    fn expected_dimension(&self) -> Option<usize> {
+        self.embedding_spec
+            .as_ref()
+            .and_then(|spec| spec.dimensions)
+            .or(self.dimension.filter(|_| !self.embeddings.is_empty()))
+    }
 
-impl EmbedDb {
-    pub fn open(config: Config) -> Result<Self> {
-        fs::create_dir_all(&config.data_dir)?;
+    fn normalizes_vectors(&self) -> bool {
+        self.embedding_spec
+            .as_ref()
+            .map(EmbeddingSpec::normalizes_vectors)
+            .unwrap_or(false)
+    }
 
-        // Prevent concurrent processes from opening the same data directory. EmbedDB is not
-        // multi-process safe; a second writer can corrupt WAL/SST state.
-        let lock_path = config.data_dir.join("embeddb.lock");
-        let lock_file = OpenOptions::new()
-            .create(true)
-            .truncate(false)
-            .read(true)
-            .write(true)
-            .open(&lock_path)?;
-        if let Err(e) = lock_file.try_lock_exclusive() {
-            if e.kind() == ErrorKind::WouldBlock {
-                return Err(anyhow!(
-                    "data_dir is already in use (lock held): {}",
-                    config.data_dir.display()
-                ));
+    /// Stores a vector, normalizing it first on cosine tables. `norm` is set when the vector
+    /// is already normalized (e.g. replayed from a checkpoint).
+    fn store_embedding(&mut self, row_id: u64, mut vector: Vec<f32>, norm: Option<f32>) {
+        if self.embeddings.is_empty() && !vector.is_empty() {
+            self.dimension = Some(vector.len());
+        }
+        match norm {
+            Some(norm) => {
+                self.embedding_norms.insert(row_id, norm);
+            }
+            None if self.normalizes_vectors() => {
+                let norm = vector::normalize(&mut vector);
+                self.embedding_norms.insert(row_id, norm);
+            }
+            None => {
+                self.embedding_norms.remove(&row_id);
             }
-            return Err(e.into());
         }
+        let encoding = self
+            .embedding_spec
+            .as_ref()
+            .map(|spec| spec.vector_encoding)
+            .unwrap_or_default();
+        self.embeddings
+            .insert(row_id, StoredVector::encode(vector, encoding));
+        self.vector_changes.insert(row_id);
+        self.embedding_version += 1;
+        self.index_embedding(row_id);
+    }
 
-        let wal_path = config.data_dir.join("wal.log");
-        let wal_prev_path = config.data_dir.join("wal.prev");
-        // Recover from an interrupted checkpoint where `wal.log` was moved aside but the new WAL
-        // was not promoted yet. In that case, prefer the previous WAL.
-        if !wal_path.exists() && wal_prev_path.exists() {
-            fs::rename(&wal_prev_path, &wal_path)?;
-        }
-        let wal = Wal::open(wal_path)?;
-
-        let mut state = DbState {
-            tables: HashMap::new(),
+    /// Applies a row write (`None` for a delete) to the keyword index, if it has been built.
+    fn index_keywords(&mut self, row_id: u64, row: Option<&RowData>) {
+        let Some(index) = self.keywords.get_mut() else {
+            return;
         };
-
-        let records = wal.replay()?;
-        for record in records {
-            apply_record(&mut state, record)?;
+        match row {
+            Some(row) => index.insert(row_id, string_fields(&self.schema, &row.fields)),
+            None => index.remove(row_id),
         }
+    }
 
-        for (name, table_state) in state.tables.iter_mut() {
-            let dir = sst::table_dir(&config.data_dir, name);
-            let files = sst::list_sst_files(&dir)?;
-            table_state.next_sst_seq = sst::max_seq(&files) + 1;
-            table_state.sst_files = files;
+    /// The keyword index, built from every visible row on first use.
+    fn keyword_index(&self) -> Result<&KeywordIndex> {
+        if let Some(index) = self.keywords.get() {
+            return Ok(index);
+        }
+        let mut index = KeywordIndex::default();
+        for (row_id, row) in scan_visible_rows(self)? {
+            index.insert(row_id, string_fields(&self.schema, &row.fields));
         }
+        Ok(self.keywords.get_or_init(|| index))
+    }
 
-        Ok(Self {
-            config,
-            _dir_lock: lock_file,
-            inner: Mutex::new(Inner {
-                wal,
-                state,
-                metrics: RuntimeMetrics::default(),
-            }),
-        })
+    fn store_named_embedding(&mut self, name: String, row_id: u64, vector: Vec<f32>) {
+        self.named_embeddings
+            .entry(name)
+            .or_default()
+            .insert(row_id, vector);
+    }
+
+    /// The stored vectors of named vector `name` for rows searches may return.
+    fn ready_named_vectors<'a>(&'a self, name: &str) -> impl Iterator<Item = (u64, &'a [f32])> {
+        self.named_embeddings
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|(row_id, _)| self.searchable(**row_id))
+            .map(|(row_id, vector)| (*row_id, vector.as_slice()))
     }
 
-    fn lock_inner(&self) -> Result<MutexGuard<'_, Inner>> {
-        self.inner.lock().map_err(|_| anyhow!("lock poisoned"))
+    /// The declared length of named vector `name`, or else the length of those already stored.
+    fn named_dimension(&self, name: &str) -> Option<usize> {
+        self.embedding_spec
+            .as_ref()
+            .and_then(|spec| spec.vector(name))
+            .and_then(|vector| vector.dimensions)
+            .or_else(|| {
+                self.named_embeddings
+                    .get(name)
+                    .and_then(|vectors| vectors.values().next())
+                    .map(Vec::len)
+            })
     }
 
-    fn preflight_wal_autocheckpoint(&self) -> Result<()> {
-        let threshold = match self.config.wal_autocheckpoint_bytes {
-            Some(bytes) if bytes > 0 => bytes,
-            _ => return Ok(()),
+    /// Adds a just-stored embedding to the HNSW graph, if the table has one.
+    fn index_embedding(&mut self, row_id: u64) {
+        if self.index_deferred {
+            return;
+        }
+        let Some(mut hnsw) = self.hnsw.take() else {
+            return;
         };
+        if let Some(query) = self.original_vector(row_id) {
+            let metric = hnsw.metric();
+            let mut query_unit = query.clone();
+            vector::normalize(&mut query_unit);
+            let to_new = |other: u64| self.distance_to(other, &query, &query_unit, metric);
+            let between = |a: u64, b: u64| match self.original_vector(a) {
+                Some(vector) => {
+                    let mut unit = vector.clone();
+                    vector::normalize(&mut unit);
+                    self.distance_to(b, &vector, &unit, metric)
+                }
+                None => f32::INFINITY,
+            };
+            hnsw.insert(row_id, &to_new, &between);
+        }
+        self.hnsw = Some(hnsw);
+    }
 
-        let wal_path = self.config.data_dir.join("wal.log");
-        let wal_bytes = fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
-        if wal_bytes >= threshold {
-            // Preflight checkpoint before the caller appends additional WAL records, so an
-            // auto-checkpoint failure does not occur after a successful write.
-            let _ = self.checkpoint_internal(true)?;
+    /// Re-inserts every embedding into a fresh graph, dropping tombstoned nodes.
+    fn rebuild_index(&mut self) {
+        self.hnsw = new_hnsw(self.embedding_spec.as_ref());
+        if self.hnsw.is_none() {
+            return;
         }
+        let mut row_ids: Vec<u64> = self.embeddings.keys().copied().collect();
+        row_ids.sort_unstable();
+        for row_id in row_ids {
+            self.index_embedding(row_id);
+        }
+    }
 
-        Ok(())
+    /// Whether a graph read back from an index file can serve as this table's: built with the
+    /// spec's parameters and holding exactly the stored embeddings.
+    fn index_matches(&self, hnsw: &Hnsw) -> bool {
+        new_hnsw(self.embedding_spec.as_ref())
+            .is_some_and(|fresh| fresh.spec() == hnsw.spec() && fresh.metric() == hnsw.metric())
+            && hnsw.len() == self.embeddings.len()
+            && self.embeddings.keys().all(|row_id| hnsw.contains(*row_id))
     }
 
-    fn checkpoint_internal(&self, auto: bool) -> Result<CheckpointStats> {
-        let mut inner = self.lock_inner()?;
-        checkpoint_locked(&self.config.data_dir, &mut inner, auto)
+    fn distance_to(
+        &self,
+        row_id: u64,
+        query: &[f32],
+        query_unit: &[f32],
+        metric: DistanceMetric,
+    ) -> f32 {
+        match self.embeddings.get(&row_id) {
+            Some(vector) => self.embedding_distance(row_id, vector, query, query_unit, metric),
+            None => f32::INFINITY,
+        }
     }
 
-    pub fn db_stats(&self) -> Result<DbStats> {
-        let (
-            tables,
-            wal_durable_appends,
-            wal_sync_ops,
-            checkpoints,
-            auto_checkpoints,
-            checkpoint_total_ms,
-            flush_count_total,
-            flush_total_ms,
-            compact_count_total,
-            compact_total_ms,
-            embeddings_processed_total,
-            embeddings_failed_total,
-            embeddings_retried_total,
-        ) = {
-            let inner = self.lock_inner()?;
-            (
-                inner.state.tables.len(),
-                inner.metrics.wal_durable_appends,
-                inner.metrics.wal_sync_ops,
-                inner.metrics.checkpoints,
-                inner.metrics.auto_checkpoints,
-                inner.metrics.checkpoint_total_ms,
-                inner.metrics.flush_count_total,
-                inner.metrics.flush_total_ms,
-                inner.metrics.compact_count_total,
-                inner.metrics.compact_total_ms,
-                inner.metrics.embeddings_processed_total,
-                inner.metrics.embeddings_failed_total,
-                inner.metrics.embeddings_retried_total,
-            )
-        };
+    /// Applies a schema change: in-memory rows are rewritten now, and rows in SST files numbered
+    /// below `before_seq` as they are read.
+    fn alter(&mut self, op: AlterTableOp, before_seq: u64) -> Result<()> {
+        self.schema = op.apply_to_schema(&self.schema)?;
+        self.keywords = OnceLock::new();
+        for row in self.rows.values_mut() {
+            op.apply_to_row(&mut row.fields);
+        }
+        self.recount_memtable_bytes();
+        self.schema_changes.push(SchemaChange { before_seq, op });
+        Ok(())
+    }
 
-        let wal_path = self.config.data_dir.join("wal.log");
-        let wal_bytes = fs::metadata(wal_path).map(|m| m.len()).unwrap_or(0);
+    /// Deletes every row with an id in `ranges` (sorted and disjoint): in-memory rows and their
+    /// embeddings are dropped now, and range tombstones hide the rest in SST files.
+    /// Tombstones a row and drops its embeddings and expiry.
+    fn delete_row(&mut self, row_id: u64) {
+        // A hidden row was already left out of the count.
+        if self.hidden.remove(&row_id).is_none() {
+            if let Some(count) = self.row_count.get_mut() {
+                *count = count.saturating_sub(1);
+            }
+        }
+        if let Some(row) = self.rows.remove(&row_id) {
+            self.memtable_bytes = self.memtable_bytes.saturating_sub(memtable_row_bytes(&row));
+        }
+        if self.tombstones.insert(row_id) {
+            self.memtable_bytes += MEMTABLE_TOMBSTONE_BYTES;
+        }
+        self.expirations.remove(&row_id);
+        self.remove_embedding(row_id);
+        self.index_keywords(row_id, None);
+    }
 
-        Ok(DbStats {
-            tables,
-            wal_bytes,
-            wal_durable_appends,
-            wal_sync_ops,
-            checkpoints,
-            auto_checkpoints,
-            checkpoint_total_ms,
-            flush_count_total,
-            flush_total_ms,
-            compact_count_total,
-            compact_total_ms,
-            embeddings_processed_total,
-            embeddings_failed_total,
-            embeddings_retried_total,
-        })
+    /// Soft-deletes a row: it stays stored, with its embeddings, but reads and searches skip it.
+    fn hide_row(&mut self, row_id: u64, hidden_at_ms: u64) {
+        if self.hidden.insert(row_id, hidden_at_ms).is_none() {
+            if let Some(count) = self.row_count.get_mut() {
+                *count = count.saturating_sub(1);
+            }
+        }
+        self.index_keywords(row_id, None);
     }
 
-    pub fn list_tables(&self) -> Result<Vec<String>> {
-        let inner = self.lock_inner()?;
-        let mut out: Vec<String> = inner.state.tables.keys().cloned().collect();
-        out.sort();
-        Ok(out)
+    /// Makes a hidden row visible again.
+    fn unhide_row(&mut self, row_id: u64) -> Result<()> {
+        if self.hidden.remove(&row_id).is_none() {
+            return Ok(());
+        }
+        if let Some(count) = self.row_count.get_mut() {
+            *count += 1;
+        }
+        if self.keywords.get().is_some() {
+            let row = load_row(self, row_id)?;
+            self.index_keywords(row_id, row.as_ref());
+        }
+        Ok(())
     }
 
-    pub fn describe_table(&self, table: &str) -> Result<TableDescriptor> {
-        let inner = self.lock_inner()?;
-        let table_state = inner
-            .state
-            .tables
-            .get(table)
-            .ok_or_else(|| anyhow!("table not found"))?;
-        Ok(TableDescriptor {
-            name: table.to_string(),
-            schema: table_state.schema.clone(),
-            embedding_spec: table_state.embedding_spec.clone(),
-        })
+    /// Whether searches may return `row_id`: it isn't hidden, and its embedding job (if any) is
+    /// `Ready`.
+    fn searchable(&self, row_id: u64) -> bool {
+        !self.hidden.contains_key(&row_id)
+            && self
+                .embedding_meta
+                .get(&row_id)
+                .is_none_or(|meta| meta.status == EmbeddingStatus::Ready)
     }
 
-    pub fn table_stats(&self, table: &str) -> Result<TableStats> {
-        let inner = self.lock_inner()?;
-        let table_state = inner
-            .state
-            .tables
-            .get(table)
-            .ok_or_else(|| anyhow!("table not found"))?;
+    fn delete_ranges(&mut self, ranges: &[Range<u64>]) {
+        let covered = |row_id: &u64| sst::covers(ranges, *row_id);
+        self.rows.retain(|row_id, _| !covered(row_id));
+        self.tombstones.retain(|row_id| !covered(row_id));
+        self.expirations.retain(|row_id, _| !covered(row_id));
+        self.hidden.retain(|row_id, _| !covered(row_id));
+        let mut with_embeddings: BTreeSet<u64> = self
+            .embedding_meta
+            .keys()
+            .chain(self.embeddings.keys())
+            .chain(self.sparse_vectors.keys())
+            .chain(self.named_embeddings.values().flat_map(HashMap::keys))
+            .copied()
+            .filter(covered)
+            .collect();
+        while let Some(row_id) = with_embeddings.pop_first() {
+            self.remove_embedding(row_id);
+        }
+        sst::add_ranges(&mut self.range_tombstones, ranges.iter().cloned());
+        self.recount_memtable_bytes();
+        self.keywords = OnceLock::new();
+        self.row_count = OnceLock::new();
+    }
 
-        let mut pending = 0usize;
-        let mut ready = 0usize;
-        let mut failed = 0usize;
-        for meta in table_state.embedding_meta.values() {
-            match meta.status {
-                EmbeddingStatus::Pending => pending += 1,
-                EmbeddingStatus::Ready => ready += 1,
-                EmbeddingStatus::Failed => failed += 1,
-            }
+    /// Swaps the embedding spec and re-encodes resident vectors, since normalization and the
+    /// in-memory encoding both follow the spec.
+    fn set_embedding_spec(&mut self, embedding_spec: Option<EmbeddingSpec>) {
+        let vectors: Vec<(u64, Vec<f32>)> = self
+            .embeddings
+            .keys()
+            .filter_map(|row_id| Some((*row_id, self.original_vector(*row_id)?)))
+            .collect();
+        self.hnsw = new_hnsw(embedding_spec.as_ref());
+        self.named_embeddings.retain(|name, _| {
+            embedding_spec
+                .as_ref()
+                .is_some_and(|spec| spec.vector(name).is_some())
+        });
+        self.embedding_spec = embedding_spec;
+        self.embedding_version += 1;
+        self.embeddings.clear();
+        self.embedding_norms.clear();
+        for (row_id, vector) in vectors {
+            self.store_embedding(row_id, vector, None);
         }
+    }
 
-        Ok(TableStats {
-            name: table.to_string(),
-            rows_mem: table_state.rows.len(),
-            tombstones_mem: table_state.tombstones.len(),
-            embeddings_total: table_state.embedding_meta.len(),
-            embeddings_pending: pending,
-            embeddings_ready: ready,
-            embeddings_failed: failed,
-            sst_files: table_state.sst_files.len(),
-            next_row_id: table_state.next_row_id,
-            wal_durable_appends: table_state.metrics.wal_durable_appends,
-            embeddings_processed_total: table_state.metrics.embeddings_processed_total,
-            embeddings_failed_total: table_state.metrics.embeddings_failed_total,
-            embeddings_retried_total: table_state.metrics.embeddings_retried_total,
-            flush_count: table_state.metrics.flush_count,
-            flush_total_ms: table_state.metrics.flush_total_ms,
-            compact_count: table_state.metrics.compact_count,
-            compact_total_ms: table_state.metrics.compact_total_ms,
-        })
+    fn remove_embedding(&mut self, row_id: u64) {
+        if self.embeddings.remove(&row_id).is_some() {
+            self.vector_changes.insert(row_id);
+            self.embedding_version += 1;
+        }
+        self.embedding_norms.remove(&row_id);
+        self.embedding_meta.remove(&row_id);
+        self.sparse_vectors.remove(&row_id);
+        for vectors in self.named_embeddings.values_mut() {
+            vectors.remove(&row_id);
+        }
+        if self.index_deferred {
+            return;
+        }
+        if let Some(hnsw) = self.hnsw.as_mut() {
+            hnsw.remove(row_id);
+            if hnsw.needs_rebuild() {
+                self.rebuild_index();
+            }
+        }
     }
 
-    pub fn create_table(
+    /// Distance from `query` to a stored embedding. `query_unit` is the normalized query, used
+    /// for embeddings stored unit-normalized.
+    fn embedding_distance(
         &self,
-        name: impl Into<String>,
-        schema: TableSchema,
-        embedding_spec: Option<EmbeddingSpec>,
-    ) -> Result<()> {
-        self.preflight_wal_autocheckpoint()?;
-        let name = name.into();
-        let mut inner = self.lock_inner()?;
-        if inner.state.tables.contains_key(&name) {
-            return Err(anyhow!("table already exists"));
+        row_id: u64,
+        vector: &StoredVector,
+        query: &[f32],
+        query_unit: &[f32],
+        metric: DistanceMetric,
+    ) -> f32 {
+        match self.embedding_norms.get(&row_id) {
+            Some(norm) => distance_to_unit(query, query_unit, vector, *norm, metric),
+            None => distance_stored(query, vector, metric),
         }
+    }
 
-        schema.validate_schema()?;
-        let dir = sst::table_dir(&self.config.data_dir, &name);
-        sst::ensure_dir(&dir)?;
-
-        let record = WalRecord::CreateTable {
-            name: name.clone(),
-            schema: schema.clone(),
-            embedding_spec: embedding_spec.clone(),
-        };
-        append_durable_wal(&mut inner, Some(&name), &record)?;
+    fn row_count(&self) -> Result<u64> {
+        if let Some(count) = self.row_count.get() {
+            return Ok(*count);
+        }
+        let count = scan_visible_rows(self)?.len() as u64;
+        Ok(*self.row_count.get_or_init(|| count))
+    }
 
-        inner.state.tables.insert(
-            name,
-            TableState {
-                schema,
-                next_row_id: 1,
-                rows: BTreeMap::new(),
-                tombstones: BTreeSet::new(),
-                embeddings: HashMap::new(),
-                embedding_meta: HashMap::new(),
-                embedding_spec,
-                sst_files: Vec::new(),
-                next_sst_seq: 1,
-                metrics: TableRuntimeMetrics::default(),
-            },
-        );
+    fn sst_tombstone_counts(&self) -> Result<TombstoneCounts> {
+        if let Some(counts) = self.sst_tombstones.get() {
+            return Ok(*counts);
+        }
+        let mut counts = TombstoneCounts::default();
+        for file in &self.sst_files {
+            let loaded = sst::LoadedSst::load(&file.path)?;
+            counts.point += loaded.tombstone_count() as u64;
+            counts.range += loaded.range_tombstones().len() as u64;
+        }
+        Ok(*self.sst_tombstones.get_or_init(|| counts))
+    }
 
-        Ok(())
+    /// Makes `row` the visible version of its row id; an id at or past `next_row_id` is a new row.
+    fn put_row(&mut self, row: RowData) {
+        if row.id >= self.next_row_id {
+            self.next_row_id = row.id + 1;
+            if let Some(count) = self.row_count.get_mut() {
+                *count += 1;
+            }
+        }
+        self.index_keywords(row.id, Some(&row));
+        if self.tombstones.remove(&row.id) {
+            self.memtable_bytes = self.memtable_bytes.saturating_sub(MEMTABLE_TOMBSTONE_BYTES);
+        }
+        self.memtable_bytes += memtable_row_bytes(&row);
+        if let Some(old) = self.rows.insert(row.id, row) {
+            self.memtable_bytes = self.memtable_bytes.saturating_sub(memtable_row_bytes(&old));
+        }
     }
 
-    pub fn insert_row(&self, table: &str, fields: BTreeMap<String, Value>) -> Result<u64> {
-        self.preflight_wal_autocheckpoint()?;
-        let mut inner = self.lock_inner()?;
-        let (row_id, embedding_spec) = {
-            let table_state = inner
-                .state
-                .tables
-                .get(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
-            table_state.schema.validate_row(&fields)?;
-            (table_state.next_row_id, table_state.embedding_spec.clone())
-        };
+    fn recount_memtable_bytes(&mut self) {
+        self.memtable_bytes = self.rows.values().map(memtable_row_bytes).sum::<u64>()
+            + (self.tombstones.len() as u64) * MEMTABLE_TOMBSTONE_BYTES
+            + (self.range_tombstones.len() as u64) * MEMTABLE_TOMBSTONE_BYTES * 2;
+    }
 
-        let row = RowData {
-            id: row_id,
-            fields: fields.clone(),
-        };
+    fn embedding_memory_bytes(&self) -> u64 {
+        let dense: usize = self.embeddings.values().map(StoredVector::heap_bytes).sum();
+        let named: usize = self
+            .named_embeddings
+            .values()
+            .flat_map(HashMap::values)
+            .map(|vector| vector.len() * 4)
+            .sum();
+        let sparse: usize = self
+            .sparse_vectors
+            .values()
+            .map(|vector| vector.indices().len() * 8)
+            .sum();
+        (dense + named + sparse + self.embedding_norms.len() * 4) as u64
+    }
 
-        let record = WalRecord::PutRow {
-            table: table.to_string(),
-            row_id,
-            row: row.clone(),
-        };
-        // Primary write: durable first.
-        append_durable_wal(&mut inner, Some(table), &record)?;
+    /// The embedding for `row_id` as originally stored (un-normalized), if searches may return
+    /// the row.
+    fn ready_vector(&self, row_id: u64) -> Option<Vec<f32>> {
+        if !self.searchable(row_id) {
+            return None;
+        }
+        self.original_vector(row_id)
+    }
 
-        if let Some(table_state) = inner.state.tables.get_mut(table) {
-            if table_state.next_row_id <= row_id {
-                table_state.next_row_id = row_id + 1;
+    /// The stored embedding for `row_id` with any cosine normalization undone.
+    fn original_vector(&self, row_id: u64) -> Option<Vec<f32>> {
+        let mut vector = self.embeddings.get(&row_id)?.to_f32();
+        if let Some(norm) = self.embedding_norms.get(&row_id) {
+            for x in vector.iter_mut() {
+                *x *= norm;
             }
-            table_state.rows.insert(row_id, row);
-            table_state.tombstones.remove(&row_id);
         }
+        Some(vector)
+    }
 
-        if let Some(spec) = embedding_spec {
-            let content_hash = spec.content_hash(&fields)?;
-            let job_record = WalRecord::EnqueueEmbedding {
-                table: table.to_string(),
-                row_id,
-                content_hash: content_hash.clone(),
-            };
-            append_durable_wal(&mut inner, Some(table), &job_record)?;
+    fn ready_embedding_count(&self) -> usize {
+        self.embeddings
+            .keys()
+            .filter(|row_id| {
+                self.embedding_meta
+                    .get(row_id)
+                    .is_none_or(|meta| meta.status == EmbeddingStatus::Ready)
+            })
+            .count()
+    }
 
-            if let Some(table_state) = inner.state.tables.get_mut(table) {
-                table_state.embedding_meta.insert(
-                    row_id,
-                    EmbeddingMeta {
-                        status: EmbeddingStatus::Pending,
-                        content_hash,
-                        last_error: None,
-                        attempts: 0,
-                        next_retry_at_ms: 0,
-                    },
-                );
-            }
+    fn index_status(&self) -> IndexStatus {
+        match &self.hnsw {
+            Some(_) => IndexStatus::ready(IndexKind::Hnsw, self.ready_embedding_count()),
+            None => IndexStatus::flat(self.ready_embedding_count()),
         }
+    }
 
-        Ok(row_id)
+    /// The HNSW graph, if the table has one built for `metric`.
+    fn hnsw_for(&self, metric: DistanceMetric) -> Option<&Hnsw> {
+        self.hnsw.as_ref().filter(|hnsw| hnsw.metric() == metric)
     }
 
-    pub fn update_row(
-        &self,
-        table: &str,
-        row_id: u64,
-        fields: BTreeMap<String, Value>,
-    ) -> Result<()> {
-        self.preflight_wal_autocheckpoint()?;
-        let mut inner = self.lock_inner()?;
-        let embedding_spec = {
-            let table_state = inner
-                .state
-                .tables
-                .get(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
-            if !row_exists(table_state, row_id)? {
-                return Err(anyhow!("row not found"));
-            }
-            table_state.schema.validate_row(&fields)?;
-            table_state.embedding_spec.clone()
-        };
-        let row = RowData {
-            id: row_id,
-            fields: fields.clone(),
-        };
+    /// The metric searches default to when the caller doesn't pick one.
+    fn default_metric(&self) -> DistanceMetric {
+        self.embedding_spec
+            .as_ref()
+            .and_then(|spec| spec.metric)
+            .unwrap_or(DistanceMetric::Cosine)
+    }
 
-        let record = WalRecord::PutRow {
-            table: table.to_string(),
-            row_id,
-            row: row.clone(),
-        };
-        append_durable_wal(&mut inner, Some(table), &record)?;
+    fn custom_metric(&self) -> Option<&str> {
+        self.embedding_spec
+            .as_ref()
+            .and_then(|spec| spec.custom_metric.as_deref())
+    }
 
-        if let Some(table_state) = inner.state.tables.get_mut(table) {
-            table_state.rows.insert(row_id, row);
-            table_state.tombstones.remove(&row_id);
+    /// The built-in metric a search runs with: the requested one, else the table default. A
+    /// table with a custom metric has no built-in default.
+    fn resolve_metric(&self, metric: Option<DistanceMetric>) -> Result<DistanceMetric> {
+        match (metric, self.custom_metric()) {
+            (Some(metric), _) => Ok(metric),
+            (None, Some(name)) => Err(anyhow!(
+                "table uses custom metric '{name}'; pass a metric or use search_knn_named"
+            )),
+            (None, None) => Ok(self.default_metric()),
         }
+    }
+}
 
-        if let Some(spec) = embedding_spec {
-            let content_hash = spec.content_hash(&fields)?;
-            let job_record = WalRecord::EnqueueEmbedding {
-                table: table.to_string(),
-                row_id,
-                content_hash: content_hash.clone(),
-            };
-            append_durable_wal(&mut inner, Some(table), &job_record)?;
+fn new_hnsw(spec: Option<&EmbeddingSpec>) -> Option<Hnsw> {
+    let spec = spec?;
+    match spec.index {
+        IndexSpec::Flat => None,
+        IndexSpec::Hnsw(params) => Some(Hnsw::new(
+            params,
+            spec.metric.unwrap_or(DistanceMetric::Cosine),
+        )),
+    }
+}
 
-            if let Some(table_state) = inner.state.tables.get_mut(table) {
-                table_state.embedding_meta.insert(
-                    row_id,
-                    EmbeddingMeta {
-                        status: EmbeddingStatus::Pending,
-                        content_hash,
-                        last_error: None,
-                        attempts: 0,
-                        next_retry_at_ms: 0,
-                    },
-                );
-            }
-        }
+#[derive(Debug)]
+struct DbState {
+    tables: HashMap<String, TableState>,
+}
 
-        Ok(())
+#[derive(Debug)]
+struct Inner {
+    wal: Wal,
+    state: DbState,
+    metrics: RuntimeMetrics,
+    // Locked separately so searches holding a shared `Inner` lock can still record results.
+    search_cache: Mutex<SearchCache>,
+    raw_vectors: RawVectors,
+    // Log sequence number of the last durable WAL record.
+    lsn: u64,
+    group_commit: Option<Arc<GroupCommit>>,
+    // Group commit ticket covering the records appended under the current write guard.
+    unsynced_ticket: Option<u64>,
+    durability: Durability,
+    // Records appended but not yet synced under a relaxed `Durability`.
+    unsynced_records: u64,
+    // Set by `EmbedDb::close`; every later operation fails.
+    closed: bool,
+    audit: Option<AuditLog>,
+}
+
+/// Exclusive `Inner` access for a write. With group commit, the records appended under the guard
+/// are synced after the lock is released: by `commit`, which reports a failed sync, or otherwise
+/// by the drop, which can only log it.
+struct WriteGuard<'a> {
+    guard: Option<RwLockWriteGuard<'a, Inner>>,
+}
+
+impl WriteGuard<'_> {
+    /// Releases the lock and waits until the guarded writes are durable.
+    fn commit(mut self) -> Result<()> {
+        self.release()
     }
 
-    pub fn delete_row(&self, table: &str, row_id: u64) -> Result<()> {
-        self.preflight_wal_autocheckpoint()?;
-        let mut inner = self.lock_inner()?;
-        let exists = {
-            let table_state = inner
-                .state
-                .tables
-                .get(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
-            row_exists(table_state, row_id)?
+    fn release(&mut self) -> Result<()> {
+        let Some(mut guard) = self.guard.take() else {
+            return Ok(());
         };
-        if !exists {
-            return Err(anyhow!("row not found"));
+        let pending = guard.unsynced_ticket.take().zip(guard.group_commit.clone());
+        drop(guard);
+        match pending {
+            Some((ticket, group)) => group.wait(ticket),
+            None => Ok(()),
         }
+    }
+}
 
-        let record = WalRecord::DeleteRow {
-            table: table.to_string(),
-            row_id,
-        };
-        append_durable_wal(&mut inner, Some(table), &record)?;
+impl Deref for WriteGuard<'_> {
+    type Target = Inner;
 
-        if let Some(table_state) = inner.state.tables.get_mut(table) {
-            table_state.rows.remove(&row_id);
-            table_state.tombstones.insert(row_id);
-            table_state.embeddings.remove(&row_id);
-            table_state.embedding_meta.remove(&row_id);
-        }
+    fn deref(&self) -> &Inner {
+        self.guard.as_ref().expect("write guard already released")
+    }
+}
 
-        Ok(())
+impl DerefMut for WriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Inner {
+        self.guard.as_mut().expect("write guard already released")
     }
+}
 
-    pub fn get_row(&self, table: &str, row_id: u64) -> Result<Option<RowData>> {
-        let inner = self.lock_inner()?;
-        let table_state = inner
-            .state
-            .tables
-            .get(table)
-            .ok_or_else(|| anyhow!("table not found"))?;
-        load_row(table_state, row_id)
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.release() {
+            tracing::error!("WAL group commit failed: {err:#}");
+        }
     }
+}
 
-    pub fn list_embedding_jobs(&self, table: &str) -> Result<Vec<EmbeddingJob>> {
-        let inner = self.lock_inner()?;
-        let table_state = inner
-            .state
-            .tables
-            .get(table)
-            .ok_or_else(|| anyhow!("table not found"))?;
+/// Exact-vector stores for quantized tables, kept in step with the WAL by `observe`.
+#[derive(Debug)]
+struct RawVectors {
+    data_dir: PathBuf,
+    tables: HashMap<String, RawVectorStore>,
+}
 
-        let mut jobs = Vec::new();
-        for (row_id, meta) in &table_state.embedding_meta {
-            jobs.push(EmbeddingJob {
-                table: table.to_string(),
-                row_id: *row_id,
-                status: meta.status,
-                content_hash: meta.content_hash.clone(),
-                last_error: meta.last_error.clone(),
-                attempts: meta.attempts,
-                next_retry_at_ms: meta.next_retry_at_ms,
-            });
+impl RawVectors {
+    fn new(data_dir: PathBuf) -> Self {
+        Self {
+            data_dir,
+            tables: HashMap::new(),
         }
-
-        // Deterministic output for CLI/HTTP consumers.
-        jobs.sort_by_key(|job| job.row_id);
-        Ok(jobs)
     }
 
-    pub fn retry_failed_jobs(&self, table: &str, row_id: Option<u64>) -> Result<usize> {
-        self.preflight_wal_autocheckpoint()?;
-        let to_retry: Vec<u64> = {
-            let inner = self.lock_inner()?;
-            let table_state = inner
-                .state
-                .tables
-                .get(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
+    fn create_store(&mut self, table: &str) -> Result<&mut RawVectorStore> {
+        let dir = sst::table_dir(&self.data_dir, table);
+        sst::ensure_dir(&dir)?;
+        let store = RawVectorStore::create(rawvec::raw_vectors_path(&dir))?;
+        Ok(self
+            .tables
+            .entry(table.to_string())
+            .insert_entry(store)
+            .into_mut())
+    }
 
-            let mut out = Vec::new();
-            for (id, meta) in &table_state.embedding_meta {
-                if meta.status != EmbeddingStatus::Failed {
-                    continue;
-                }
-                if let Some(filter) = row_id {
-                    if *id != filter {
-                        continue;
+    /// Mirrors `record` into the stores. Must run before `record` is applied to `state`.
+    fn observe(&mut self, state: &DbState, record: &WalRecord) -> Result<()> {
+        match record {
+            WalRecord::CreateTable {
+                name,
+                embedding_spec: Some(spec),
+                ..
+            } if spec.is_quantized() => {
+                self.create_store(name)?;
+            }
+            WalRecord::StoreEmbedding {
+                table,
+                row_id,
+                vector,
+                norm,
+            } => {
+                if let Some(store) = self.tables.get_mut(table) {
+                    match norm {
+                        Some(norm) => {
+                            let original: Vec<f32> = vector.iter().map(|x| x * norm).collect();
+                            store.append(*row_id, &original)?;
+                        }
+                        None => store.append(*row_id, vector)?,
                     }
                 }
-                if row_exists(table_state, *id)? {
-                    out.push(*id);
+            }
+            WalRecord::DeleteRow { table, row_id } => {
+                if let Some(store) = self.tables.get_mut(table) {
+                    store.remove(*row_id);
                 }
             }
-            out
-        };
-
-        let mut retried = 0usize;
-        for id in to_retry {
-            let mut inner = self.lock_inner()?;
-            let status_record = WalRecord::UpdateEmbeddingStatus {
-                table: table.to_string(),
-                row_id: id,
-                status: EmbeddingStatus::Pending,
-                last_error: None,
-                attempts: Some(0),
-                next_retry_at_ms: Some(0),
-            };
-            append_durable_wal(&mut inner, Some(table), &status_record)?;
-
-            if let Some(table_state) = inner.state.tables.get_mut(table) {
-                if let Some(meta) = table_state.embedding_meta.get_mut(&id) {
-                    meta.status = EmbeddingStatus::Pending;
-                    meta.last_error = None;
-                    meta.attempts = 0;
-                    meta.next_retry_at_ms = 0;
-                    table_state.metrics.embeddings_retried_total += 1;
+            WalRecord::DeleteRanges { table, ranges } => {
+                if let Some(store) = self.tables.get_mut(table) {
+                    store.remove_where(|row_id| sst::covers(ranges, row_id));
                 }
             }
-            inner.metrics.embeddings_retried_total += 1;
-
-            retried += 1;
+            WalRecord::RenameTable { table, new_name } => {
+                if let Some(mut store) = self.tables.remove(table) {
+                    let dir = sst::table_dir(&self.data_dir, new_name);
+                    store.relocate(rawvec::raw_vectors_path(&dir));
+                    self.tables.insert(new_name.clone(), store);
+                }
+            }
+            WalRecord::SetEmbeddingSpec {
+                table,
+                embedding_spec,
+            } => {
+                let Some(table_state) = state.tables.get(table) else {
+                    return Ok(());
+                };
+                let quantized = embedding_spec
+                    .as_ref()
+                    .is_some_and(EmbeddingSpec::is_quantized);
+                let previous = self.tables.remove(table);
+                if quantized {
+                    let mut vectors = Vec::new();
+                    for row_id in table_state.embeddings.keys() {
+                        let exact = match &previous {
+                            Some(store) => store.read(*row_id)?,
+                            None => None,
+                        };
+                        if let Some(vector) = exact.or_else(|| table_state.original_vector(*row_id))
+                        {
+                            vectors.push((*row_id, vector));
+                        }
+                    }
+                    drop(previous);
+                    let store = self.create_store(table)?;
+                    for (row_id, vector) in vectors {
+                        store.append(row_id, &vector)?;
+                    }
+                } else if let Some(store) = previous {
+                    let _ = fs::remove_file(store.path());
+                }
+            }
+            _ => {}
         }
-
-        Ok(retried)
+        Ok(())
     }
 
-    pub fn process_pending_jobs(&self, table: &str, embedder: &dyn Embedder) -> Result<usize> {
-        self.process_pending_jobs_internal(table, embedder, None)
+    fn exact_vector(&self, table: &str, row_id: u64) -> Result<Option<Vec<f32>>> {
+        match self.tables.get(table) {
+            Some(store) => store.read(row_id),
+            None => Ok(None),
+        }
     }
+}
 
-    pub fn process_pending_jobs_with_limit(
-        &self,
-        table: &str,
-        embedder: &dyn Embedder,
-        limit: usize,
-    ) -> Result<usize> {
-        self.process_pending_jobs_internal(table, embedder, Some(limit))
-    }
+#[derive(Debug, Default)]
+struct TableRuntimeMetrics {
+    wal_durable_appends: u64,
+    embeddings_processed_total: u64,
+    embeddings_failed_total: u64,
+    embeddings_retried_total: u64,
+    skipped_unchanged: u64,
+    flush_count: u64,
+    flush_total_ms: u64,
+    compact_count: u64,
+    compact_total_ms: u64,
+    last_compacted_at_ms: Option<u64>,
+}
 
-    fn process_pending_jobs_internal(
-        &self,
-        table: &str,
-        embedder: &dyn Embedder,
-        limit: Option<usize>,
-    ) -> Result<usize> {
-        self.process_pending_jobs_internal_at(table, embedder, limit, now_epoch_ms())
-    }
+#[derive(Debug, Default)]
+struct RuntimeMetrics {
+    wal_durable_appends: u64,
+    wal_rotations: u64,
+    wal_sync_ops: u64,
+    checkpoints: u64,
+    auto_checkpoints: u64,
+    checkpoint_total_ms: u64,
+    flush_count_total: u64,
+    flush_total_ms: u64,
+    compact_count_total: u64,
+    compact_total_ms: u64,
+    embeddings_processed_total: u64,
+    embeddings_failed_total: u64,
+    embeddings_retried_total: u64,
+}
 
-    fn process_pending_jobs_internal_at(
-        &self,
-        table: &str,
-        embedder: &dyn Embedder,
-        limit: Option<usize>,
-        now_ms: u64,
-    ) -> Result<usize> {
-        self.preflight_wal_autocheckpoint()?;
-        let pending_jobs: Vec<(u64, String)> = {
-            let inner = self.lock_inner()?;
-            let table_state = inner
-                .state
-                .tables
-                .get(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
+#[derive(Debug)]
+pub struct EmbedDb {
+    // Declared first so the threads are stopped and joined before anything else is dropped.
+    worker: Option<EmbeddingWorker>,
+    compactor: Option<CompactionWorker>,
+    syncer: Option<WalSyncer>,
+    config: Config,
+    // Held for the lifetime of the EmbedDb handle so the exclusive directory lock is released on
+    // drop; `None` when opened read-only. Shared state is reference-counted so the worker thread
+    // can hold its own handle.
+    _dir_lock: Option<Arc<File>>,
+    inner: Arc<RwLock<Inner>>,
+    triggers: Arc<TriggerSet>,
+    changes: Arc<ChangeFeed>,
+    distance_fns: Arc<MetricRegistry>,
+}
 
-            let spec = match &table_state.embedding_spec {
-                Some(spec) => spec.clone(),
-                None => return Ok(0),
-            };
+impl Drop for EmbedDb {
+    fn drop(&mut self) {
+        if !self.config.close_on_drop {
+            return;
+        }
+        // Join the workers first so they can't race the final flush and checkpoint.
+        drop(self.worker.take());
+        drop(self.compactor.take());
+        if let Err(err) = self.close() {
+            tracing::error!("closing EmbedDb on drop failed: {err:#}");
+        }
+    }
+}
 
-            let mut jobs = Vec::new();
+impl EmbedDb {
+    pub fn open(config: Config) -> Result<Self> {
+        // Prevent concurrent processes from opening the same data directory. EmbedDB is not
+        // multi-process safe; a second writer can corrupt WAL/SST state. Read-only opens change
+        // nothing on disk, so they skip the lock.
+        let lock_path = config.data_dir.join("embeddb.lock");
+        let dir_lock = if config.read_only {
+            None
+        } else {
+            fs::create_dir_all(&config.data_dir)?;
+            let lock_file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(&lock_path)?;
+            if let Err(e) = lock_file.try_lock_exclusive() {
+                if e.kind() == ErrorKind::WouldBlock {
+                    return Err(anyhow!(
+                        "data_dir {} is already in use by another EmbedDb instance (lock held on {}); \
+                         close it first, or open with Config::with_read_only",
+                        config.data_dir.display(),
+                        lock_path.display()
+                    ));
+                }
+                return Err(e.into());
+            }
+            // Views still open when the last process holding the directory stopped.
+            history::remove_pinned_files(&config.data_dir)?;
+            Some(Arc::new(lock_file))
+        };
 
-            let mut pending_row_ids: Vec<u64> = table_state
-                .embedding_meta
-                .iter()
-                .filter_map(|(row_id, meta)| {
-                    if meta.status == EmbeddingStatus::Pending && meta.next_retry_at_ms <= now_ms {
-                        Some(*row_id)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            pending_row_ids.sort();
-            if let Some(limit) = limit {
-                pending_row_ids.truncate(limit);
-            }
-
-            for row_id in pending_row_ids {
-                if let Some(row) = load_row(table_state, row_id)? {
-                    let input = spec.input_string(&row.fields)?;
-                    jobs.push((row_id, input));
+        let wal_path = config.data_dir.join("wal.log");
+        let wal_prev_path = config.data_dir.join("wal.prev");
+        // Recover from an interrupted checkpoint where `wal.log` was moved aside but the new WAL
+        // was not promoted yet. In that case, prefer the previous WAL.
+        let wal = if config.read_only {
+            match (wal_path.exists(), wal_prev_path.exists()) {
+                (true, _) => Wal::open_read_only(wal_path)?,
+                (false, true) => Wal::open_read_only(wal_prev_path)?,
+                (false, false) => {
+                    return Err(anyhow!(
+                        "data_dir holds no database: {}",
+                        config.data_dir.display()
+                    ))
                 }
             }
-            jobs
+        } else {
+            if !wal_path.exists() && wal_prev_path.exists() {
+                fault::rename(&wal_prev_path, &wal_path)?;
+            }
+            Wal::open(wal_path, config.wal_encoding)?.with_compression(config.wal_compression)
         };
 
-        let mut processed = 0usize;
-        for (row_id, input) in pending_jobs {
-            match embedder.embed(&input) {
-                Ok(vector) => {
-                    let mut inner = self.lock_inner()?;
-                    let store_record = WalRecord::StoreEmbedding {
-                        table: table.to_string(),
-                        row_id,
-                        vector: vector.clone(),
-                    };
-                    append_durable_wal(&mut inner, Some(table), &store_record)?;
+        let mut state = DbState {
+            tables: HashMap::new(),
+        };
 
-                    if let Some(table_state) = inner.state.tables.get_mut(table) {
-                        table_state.embeddings.insert(row_id, vector);
+        let records = replay_wal_generation(&config, &wal)?;
+        rewind_table_renames(&config, &records)?;
+        // Read-only opens skip the exact-vector stores of quantized tables, which replay
+        // rewrites; their searches fall back to approximate distances.
+        let observe_raw = !config.read_only;
+        let mut lsn = 0u64;
+        let mut raw_vectors = RawVectors::new(config.data_dir.clone());
+        // Per table, the LSN of the last record that changed its embeddings. An index file is
+        // current when its generation is at least this and at most the final LSN.
+        let mut embeddings_lsn: HashMap<String, u64> = HashMap::new();
+        for record in records {
+            lsn = match &record {
+                WalRecord::Checkpoint { lsn } => *lsn,
+                _ => lsn + 1,
+            };
+            let Some(table) = record.table().map(str::to_string) else {
+                // The snapshot before a checkpoint marker reproduces state as of its LSN.
+                if matches!(record, WalRecord::Checkpoint { .. }) {
+                    for changed in embeddings_lsn.values_mut() {
+                        *changed = lsn;
+                    }
+                }
+                continue;
+            };
+            let version = state.tables.get(&table).map(|t| t.embedding_version);
+            if let WalRecord::RenameTable { table, new_name } = &record {
+                move_table_dir(&config.data_dir, table, new_name)?;
+                if let Some(changed) = embeddings_lsn.remove(table) {
+                    embeddings_lsn.insert(new_name.clone(), changed);
+                }
+            }
+            if let WalRecord::VectorSegments { table } = &record {
+                for record in vector_segment_records(&config.data_dir, table)? {
+                    if observe_raw {
+                        raw_vectors.observe(&state, &record)?;
                     }
+                    apply_record(&mut state, record)?;
+                }
+                // Loaded vectors are already in segments and need not be flushed again.
+                if let Some(table_state) = state.tables.get_mut(table) {
+                    table_state.vector_changes.clear();
+                }
+            } else {
+                if observe_raw {
+                    raw_vectors.observe(&state, &record)?;
+                }
+                apply_record(&mut state, record)?;
+            }
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.index_deferred = true;
+                if version != Some(table_state.embedding_version) {
+                    embeddings_lsn.insert(table, lsn);
+                }
+            }
+        }
 
-                    let status_record = WalRecord::UpdateEmbeddingStatus {
-                        table: table.to_string(),
-                        row_id,
-                        status: EmbeddingStatus::Ready,
-                        last_error: None,
-                        attempts: Some(0),
-                        next_retry_at_ms: Some(0),
-                    };
-                    append_durable_wal(&mut inner, Some(table), &status_record)?;
+        for (name, table_state) in state.tables.iter_mut() {
+            let dir = sst::table_dir(&config.data_dir, name);
+            let files = sst::list_sst_files(&dir)?;
+            let segments = vecseg::list_segments(&dir)?;
+            let max_segment_seq = segments.iter().map(|f| f.seq).max().unwrap_or(0);
+            table_state.next_sst_seq = sst::max_seq(&files).max(max_segment_seq) + 1;
+            table_state.sst_files = files;
+            table_state.vector_segments = segments;
+            let changed = embeddings_lsn.get(name).copied().unwrap_or(0);
+            restore_index(
+                &config.data_dir,
+                name,
+                table_state,
+                changed,
+                lsn,
+                !config.read_only,
+            )?;
+        }
 
-                    if let Some(table_state) = inner.state.tables.get_mut(table) {
-                        if let Some(meta) = table_state.embedding_meta.get_mut(&row_id) {
-                            meta.status = EmbeddingStatus::Ready;
-                            meta.last_error = None;
-                            meta.attempts = 0;
-                            meta.next_retry_at_ms = 0;
-                            table_state.metrics.embeddings_processed_total += 1;
-                        }
-                    }
-                    inner.metrics.embeddings_processed_total += 1;
-                }
-                Err(err) => {
-                    let mut inner = self.lock_inner()?;
-                    let (new_attempts, next_retry, new_status) =
-                        if let Some(table_state) = inner.state.tables.get(table) {
-                            if let Some(meta) = table_state.embedding_meta.get(&row_id) {
-                                let attempts = meta.attempts.saturating_add(1);
-                                if attempts >= EMBEDDING_MAX_ATTEMPTS {
-                                    (attempts, 0u64, EmbeddingStatus::Failed)
-                                } else {
-                                    (
-                                        attempts,
-                                        now_ms.saturating_add(embedding_backoff_ms(attempts)),
-                                        EmbeddingStatus::Pending,
-                                    )
-                                }
-                            } else {
-                                (
-                                    1u32,
-                                    now_ms.saturating_add(embedding_backoff_ms(1)),
-                                    EmbeddingStatus::Pending,
-                                )
-                            }
-                        } else {
-                            (
-                                1u32,
-                                now_ms.saturating_add(embedding_backoff_ms(1)),
-                                EmbeddingStatus::Pending,
-                            )
-                        };
-                    let status_record = WalRecord::UpdateEmbeddingStatus {
-                        table: table.to_string(),
-                        row_id,
-                        status: new_status,
-                        last_error: Some(err.to_string()),
-                        attempts: Some(new_attempts),
-                        next_retry_at_ms: Some(next_retry),
+        let group_commit = match config.wal_group_commit.filter(|_| !config.read_only) {
+            Some(max_latency) => {
+                let group = GroupCommit::new(max_latency);
+                group.attach(&wal)?;
+                Some(Arc::new(group))
+            }
+            None => None,
+        };
+        let search_cache = SearchCache::new(config.search_cache_capacity);
+        let durability = config.durability;
+        let change_feed_capacity = config.change_feed_capacity;
+        let audit = match config.audit.clone().filter(|_| !config.read_only) {
+            Some(audit) => Some(AuditLog::open(&config.data_dir, audit)?),
+            None => None,
+        };
+        let mut db = Self {
+            worker: None,
+            compactor: None,
+            syncer: None,
+            config,
+            _dir_lock: dir_lock,
+            inner: Arc::new(RwLock::new(Inner {
+                wal,
+                state,
+                metrics: RuntimeMetrics::default(),
+                search_cache: Mutex::new(search_cache),
+                raw_vectors,
+                lsn,
+                group_commit,
+                unsynced_ticket: None,
+                durability,
+                unsynced_records: 0,
+                closed: false,
+                audit,
+            })),
+            triggers: Arc::new(TriggerSet::default()),
+            changes: Arc::new(ChangeFeed::new(change_feed_capacity, lsn)),
+            distance_fns: Arc::new(MetricRegistry::default()),
+        };
+        if db.config.warm_on_open {
+            for table in db.list_tables()? {
+                db.warm_table(&table)?;
+            }
+        }
+        if let Some(settings) = db.config.background_embedding.clone() {
+            if !db.config.read_only {
+                db.worker = Some(EmbeddingWorker::spawn(db.shared_handle(), settings)?);
+            }
+        }
+        if let Some(interval) = db.config.background_compaction {
+            if !db.config.read_only {
+                db.compactor = Some(CompactionWorker::spawn(db.shared_handle(), interval)?);
+            }
+        }
+        if let Durability::Interval(interval) = db.config.durability {
+            if !db.config.read_only {
+                let inner = Arc::downgrade(&db.inner);
+                db.syncer = Some(WalSyncer::spawn(interval, move || {
+                    let Some(inner) = inner.upgrade() else {
+                        return false;
                     };
-                    append_durable_wal(&mut inner, Some(table), &status_record)?;
-
-                    if let Some(table_state) = inner.state.tables.get_mut(table) {
-                        if let Some(meta) = table_state.embedding_meta.get_mut(&row_id) {
-                            meta.status = new_status;
-                            meta.last_error = Some(err.to_string());
-                            meta.attempts = new_attempts;
-                            meta.next_retry_at_ms = next_retry;
-                            table_state.metrics.embeddings_failed_total += 1;
-                        }
+                    let Ok(mut inner) = inner.write() else {
+                        return false;
+                    };
+                    if inner.closed {
+                        return false;
                     }
-                    inner.metrics.embeddings_failed_total += 1;
-                }
+                    if let Err(err) = sync_pending_wal(&mut inner) {
+                        tracing::warn!("periodic WAL sync failed: {err:#}");
+                    }
+                    true
+                })?);
             }
+        }
+        Ok(db)
+    }
 
-            processed += 1;
+    /// A second handle onto the same database state, without workers of its own.
+    fn shared_handle(&self) -> EmbedDb {
+        EmbedDb {
+            worker: None,
+            compactor: None,
+            syncer: None,
+            config: Config {
+                close_on_drop: false,
+                ..self.config.clone()
+            },
+            _dir_lock: self._dir_lock.clone(),
+            inner: self.inner.clone(),
+            triggers: self.triggers.clone(),
+            changes: self.changes.clone(),
+            distance_fns: self.distance_fns.clone(),
         }
+    }
 
-        Ok(processed)
+    /// Shared access for reads; any number of readers run concurrently.
+    fn read_inner(&self) -> Result<RwLockReadGuard<'_, Inner>> {
+        let guard = self.inner.read().map_err(|_| anyhow!("lock poisoned"))?;
+        if guard.closed {
+            return Err(anyhow!("database is closed"));
+        }
+        Ok(guard)
     }
 
-    pub fn search_knn(
-        &self,
-        table: &str,
-        query: &[f32],
-        k: usize,
-        metric: DistanceMetric,
-    ) -> Result<Vec<SearchHit>> {
-        let inner = self.lock_inner()?;
-        let table_state = inner
-            .state
-            .tables
-            .get(table)
-            .ok_or_else(|| anyhow!("table not found"))?;
+    /// Exclusive access for writes, which also excludes all readers.
+    fn write_inner(&self) -> Result<WriteGuard<'_>> {
+        if self.config.read_only {
+            return Err(anyhow!("database is open read-only"));
+        }
+        let guard = self.inner.write().map_err(|_| anyhow!("lock poisoned"))?;
+        if guard.closed {
+            return Err(anyhow!("database is closed"));
+        }
+        Ok(WriteGuard { guard: Some(guard) })
+    }
 
-        let mut results: Vec<SearchResult> = Vec::new();
-        for (row_id, vector) in &table_state.embeddings {
-            if let Some(meta) = table_state.embedding_meta.get(row_id) {
-                if meta.status != EmbeddingStatus::Ready {
-                    continue;
+    /// Shuts the database down cleanly: stops the background worker, flushes every table's
+    /// memtable, checkpoints the WAL so the next open has almost nothing to replay, and releases
+    /// the data directory lock. Every later call on this handle (or its clones) fails with
+    /// "database is closed"; closing again is a no-op. A read-only database is only marked closed.
+    pub fn close(&self) -> Result<()> {
+        if let Some(worker) = &self.worker {
+            worker.stop();
+        }
+        if let Some(compactor) = &self.compactor {
+            compactor.stop();
+        }
+        if let Some(syncer) = &self.syncer {
+            syncer.stop();
+        }
+        if self
+            .inner
+            .read()
+            .map_err(|_| anyhow!("lock poisoned"))?
+            .closed
+        {
+            return Ok(());
+        }
+        if !self.config.read_only {
+            for table in self.list_tables()? {
+                self.flush_table(&table)?;
+            }
+        }
+
+        let mut guard = self.inner.write().map_err(|_| anyhow!("lock poisoned"))?;
+        if guard.closed {
+            return Ok(());
+        }
+        if !self.config.read_only {
+            checkpoint_locked(&self.config, &mut guard, false)?;
+        }
+        guard.closed = true;
+        drop(guard);
+        self.changes.close();
+        if let Some(lock) = &self._dir_lock {
+            FileExt::unlock(&**lock)?;
+        }
+        Ok(())
+    }
+
+    fn preflight_write_limits(&self) -> Result<()> {
+        if let Some(threshold) = self
+            .config
+            .wal_autocheckpoint_bytes
+            .filter(|bytes| *bytes > 0)
+        {
+            if wal_bytes_total(&self.config.data_dir) >= threshold {
+                // Preflight checkpoint before the caller appends additional WAL records, so an
+                // auto-checkpoint failure does not occur after a successful write.
+                let _ = self.checkpoint_internal(true)?;
+                return Ok(());
+            }
+        }
+
+        if let Some(limit) = self.config.wal_segment_bytes.filter(|bytes| *bytes > 0) {
+            let mut inner = self.write_inner()?;
+            if inner.wal.len() >= limit {
+                rotate_wal_segment(&self.config, &mut inner)?;
+            }
+        }
+
+        if let Some(limit) = self.config.memtable_max_bytes.filter(|bytes| *bytes > 0) {
+            let full: Vec<(String, u64)> = self
+                .read_inner()?
+                .state
+                .tables
+                .iter()
+                .filter(|(_, table_state)| table_state.memtable_bytes >= limit)
+                .map(|(name, table_state)| (name.clone(), table_state.memtable_bytes))
+                .collect();
+            for (table, memtable_bytes) in full {
+                if let Err(err) = self.flush_table(&table) {
+                    tracing::warn!("automatic flush of '{table}' failed: {err:#}");
+                    return Err(WriteStall {
+                        table,
+                        memtable_bytes,
+                        limit_bytes: limit,
+                        reason: format!("{err:#}"),
+                    }
+                    .into());
                 }
             }
-            let dist = distance(query, vector, metric);
-            results.push(SearchResult {
-                row_id: *row_id,
-                distance: dist,
-            });
         }
 
-        results.sort_by(|a, b| a.distance.total_cmp(&b.distance));
-        let hits = results
-            .into_iter()
-            .take(k)
-            .map(|res| SearchHit {
-                row_id: res.row_id,
-                distance: res.distance,
-            })
-            .collect();
+        Ok(())
+    }
 
-        Ok(hits)
+    fn checkpoint_internal(&self, auto: bool) -> Result<CheckpointStats> {
+        let mut inner = self.write_inner()?;
+        checkpoint_locked(&self.config, &mut inner, auto)
     }
 
-    pub fn search_knn_filtered(
-        &self,
-        table: &str,
-        query: &[f32],
-        k: usize,
-        metric: DistanceMetric,
-        filters: &[FilterCondition],
-    ) -> Result<Vec<SearchHit>> {
-        let inner = self.lock_inner()?;
+    pub fn db_stats(&self) -> Result<DbStats> {
+        let (
+            tables,
+            lsn,
+            wal_rotations,
+            wal_durable_appends,
+            wal_sync_ops,
+            wal_unsynced_records,
+            checkpoints,
+            auto_checkpoints,
+            checkpoint_total_ms,
+            flush_count_total,
+            flush_total_ms,
+            compact_count_total,
+            compact_total_ms,
+            embeddings_processed_total,
+            embeddings_failed_total,
+            embeddings_retried_total,
+            search_cache_hits,
+            search_cache_misses,
+        ) = {
+            let inner = self.read_inner()?;
+            let cache_lookups = lock_cache(&inner.search_cache).lookups();
+            (
+                inner.state.tables.len(),
+                inner.lsn,
+                inner.metrics.wal_rotations,
+                inner.metrics.wal_durable_appends,
+                inner.metrics.wal_sync_ops
+                    + inner.group_commit.as_ref().map_or(0, |group| group.syncs()),
+                inner.unsynced_records,
+                inner.metrics.checkpoints,
+                inner.metrics.auto_checkpoints,
+                inner.metrics.checkpoint_total_ms,
+                inner.metrics.flush_count_total,
+                inner.metrics.flush_total_ms,
+                inner.metrics.compact_count_total,
+                inner.metrics.compact_total_ms,
+                inner.metrics.embeddings_processed_total,
+                inner.metrics.embeddings_failed_total,
+                inner.metrics.embeddings_retried_total,
+                cache_lookups.0,
+                cache_lookups.1,
+            )
+        };
+
+        let wal_bytes = wal_bytes_total(&self.config.data_dir);
+        let wal_segments = wal::list_segments(&wal::sealed_dir(&self.config.data_dir))?.len();
+
+        Ok(DbStats {
+            tables,
+            lsn,
+            wal_bytes,
+            wal_segments,
+            wal_rotations,
+            wal_durable_appends,
+            wal_sync_ops,
+            wal_unsynced_records,
+            checkpoints,
+            auto_checkpoints,
+            checkpoint_total_ms,
+            flush_count_total,
+            flush_total_ms,
+            compact_count_total,
+            compact_total_ms,
+            embeddings_processed_total,
+            embeddings_failed_total,
+            embeddings_retried_total,
+            search_cache_hits,
+            search_cache_misses,
+            embedding_worker: self.worker.as_ref().map(EmbeddingWorker::status),
+            compaction_worker: self.compactor.as_ref().map(CompactionWorker::status),
+        })
+    }
+
+    pub fn list_tables(&self) -> Result<Vec<String>> {
+        let inner = self.read_inner()?;
+        let mut out: Vec<String> = inner.state.tables.keys().cloned().collect();
+        out.sort();
+        Ok(out)
+    }
+
+    pub fn describe_table(&self, table: &str) -> Result<TableDescriptor> {
+        let inner = self.read_inner()?;
         let table_state = inner
             .state
             .tables
             .get(table)
             .ok_or_else(|| anyhow!("table not found"))?;
+        Ok(TableDescriptor {
+            name: table.to_string(),
+            schema: table_state.schema.clone(),
+            embedding_spec: table_state.embedding_spec.clone(),
+            dimension: table_state.expected_dimension(),
+        })
+    }
 
-        validate_filters(&table_state.schema, filters)?;
-
-        let mut results: Vec<SearchResult> = Vec::new();
-        for (row_id, vector) in &table_state.embeddings {
-            if let Some(meta) = table_state.embedding_meta.get(row_id) {
-                if meta.status != EmbeddingStatus::Ready {
-                    continue;
-                }
-            }
+    pub fn table_stats(&self, table: &str) -> Result<TableStats> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
 
-            if !filters.is_empty() {
-                let row = match load_row(table_state, *row_id)? {
-                    Some(row) => row,
-                    None => continue,
-                };
-                if !row_matches_filters(&row, filters) {
-                    continue;
-                }
+        let mut pending = 0usize;
+        let mut ready = 0usize;
+        let mut failed = 0usize;
+        for meta in table_state.embedding_meta.values() {
+            match meta.status {
+                EmbeddingStatus::Pending => pending += 1,
+                EmbeddingStatus::Ready => ready += 1,
+                EmbeddingStatus::Failed => failed += 1,
             }
-
-            let dist = distance(query, vector, metric);
-            results.push(SearchResult {
-                row_id: *row_id,
-                distance: dist,
-            });
         }
 
-        results.sort_by(|a, b| a.distance.total_cmp(&b.distance));
-        let hits = results
-            .into_iter()
-            .take(k)
-            .map(|res| SearchHit {
-                row_id: res.row_id,
-                distance: res.distance,
-            })
-            .collect();
-
-        Ok(hits)
-    }
+        let sst_tombstones = table_state.sst_tombstone_counts()?;
+        let total_tombstones = sst_tombstones.point
+            + sst_tombstones.range
+            + (table_state.tombstones.len() + table_state.range_tombstones.len()) as u64;
 
-    pub fn flush_table(&self, table: &str) -> Result<()> {
-        let mut inner = self.lock_inner()?;
-        let elapsed_ms = {
-            let table_state = inner
-                .state
-                .tables
-                .get_mut(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
-            let started = Instant::now();
-            let flushed = flush_table_state(&self.config.data_dir, table, table_state)?;
-            if flushed {
-                let elapsed_ms = started.elapsed().as_millis() as u64;
-                table_state.metrics.flush_count += 1;
-                table_state.metrics.flush_total_ms = table_state
-                    .metrics
-                    .flush_total_ms
-                    .saturating_add(elapsed_ms);
-                Some(elapsed_ms)
+        Ok(TableStats {
+            name: table.to_string(),
+            rows_mem: table_state.rows.len(),
+            tombstones_mem: table_state.tombstones.len(),
+            memtable_bytes: table_state.memtable_bytes,
+            embeddings_total: table_state.embedding_meta.len(),
+            embeddings_pending: pending,
+            embeddings_ready: ready,
+            embeddings_failed: failed,
+            sst_files: table_state.sst_files.len(),
+            sst_files_per_level: compaction::files_per_level(&table_state.sst_files),
+            sst_bytes_per_level: compaction::bytes_per_level(&table_state.sst_files)?,
+            total_rows: table_state.row_count()?,
+            total_tombstones,
+            hidden_rows: table_state.hidden.len() as u64,
+            embedding_memory_bytes: table_state.embedding_memory_bytes(),
+            vector_segments: table_state.vector_segments.len(),
+            next_row_id: table_state.next_row_id,
+            wal_durable_appends: table_state.metrics.wal_durable_appends,
+            embeddings_processed_total: table_state.metrics.embeddings_processed_total,
+            embeddings_failed_total: table_state.metrics.embeddings_failed_total,
+            embeddings_retried_total: table_state.metrics.embeddings_retried_total,
+            skipped_unchanged: table_state.metrics.skipped_unchanged,
+            flush_count: table_state.metrics.flush_count,
+            flush_total_ms: table_state.metrics.flush_total_ms,
+            compact_count: table_state.metrics.compact_count,
+            compact_total_ms: table_state.metrics.compact_total_ms,
+            last_compacted_at_ms: table_state.metrics.last_compacted_at_ms,
+            index: table_state.index_status(),
+        })
+    }
+
+    /// Build state of the table's vector index.
+    pub fn index_status(&self, table: &str) -> Result<IndexStatus> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        Ok(table_state.index_status())
+    }
+
+    /// Does the work a table's first queries would otherwise pay for: reads every SST file (and
+    /// a quantized table's exact-vector store) into the OS page cache, and builds the keyword
+    /// index and row and tombstone counts that are otherwise built on first use. Embeddings and
+    /// the HNSW graph are always loaded by `open`. Runs under the shared lock, so it doesn't
+    /// block other readers.
+    pub fn warm_table(&self, table: &str) -> Result<WarmStats> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+
+        let mut stats = WarmStats {
+            sst_files: table_state.sst_files.len(),
+            vectors: table_state.embeddings.len(),
+            ..WarmStats::default()
+        };
+        let mut counts = TombstoneCounts::default();
+        for file in &table_state.sst_files {
+            stats.bytes_read += fs::metadata(&file.path)?.len();
+            let loaded = sst::LoadedSst::load(&file.path)?;
+            counts.point += loaded.tombstone_count() as u64;
+            counts.range += loaded.range_tombstones().len() as u64;
+        }
+        let _ = table_state.sst_tombstones.set(counts);
+        if let Some(store) = inner.raw_vectors.tables.get(table) {
+            match fs::read(store.path()) {
+                Ok(data) => stats.bytes_read += data.len() as u64,
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        table_state.keyword_index()?;
+        stats.rows = table_state.row_count()?;
+        Ok(stats)
+    }
+
+    /// Validates a search like `search_knn_with_options` and reports how it would run, without
+    /// scoring any vectors.
+    pub fn explain_search(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<SearchExplain> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let metric = table_state.resolve_metric(metric.into())?;
+        if let Some(name) = options.vector.as_deref() {
+            check_metric_against_table(table_state, metric, options)?;
+            check_named_query(table_state, name, query)?;
+            validate_filters(&table_state.schema, filters)?;
+            return Ok(SearchExplain {
+                index: IndexKind::Flat,
+                degraded: false,
+                candidates: table_state
+                    .ready_named_vectors(name)
+                    .filter(|(row_id, _)| options.allows(*row_id))
+                    .count(),
+                rescore_candidates: None,
+            });
+        }
+        check_query_against_table(table_state, query, metric, options)?;
+        validate_filters(&table_state.schema, filters)?;
+
+        let status = table_state.index_status();
+        let rescore = self.config.rescore_oversample > 0
+            && table_state
+                .embedding_spec
+                .as_ref()
+                .is_some_and(EmbeddingSpec::is_quantized);
+        let fetch = if rescore {
+            k.saturating_mul(self.config.rescore_oversample)
+        } else {
+            k
+        };
+        let ready = match &options.include_ids {
+            Some(_) => search_candidates(table_state, options)
+                .filter(|(row_id, _)| table_state.searchable(**row_id) && options.allows(**row_id))
+                .count(),
+            None => table_state.ready_embedding_count(),
+        };
+        let built = table_state.hnsw_for(metric);
+        // Searches restricted to `include_ids` scan just those rows instead.
+        let hnsw = built.filter(|_| options.include_ids.is_none());
+        Ok(SearchExplain {
+            index: if hnsw.is_some() {
+                IndexKind::Hnsw
             } else {
-                None
+                IndexKind::Flat
+            },
+            // An index built for another metric can't serve this search.
+            degraded: status.state == IndexState::Building
+                || (status.kind != IndexKind::Flat && built.is_none()),
+            candidates: hnsw.map_or(ready, |hnsw| hnsw.ef_search().max(fetch).min(ready)),
+            rescore_candidates: rescore.then_some(fetch),
+        })
+    }
+
+    pub fn create_table(
+        &self,
+        name: impl Into<String>,
+        schema: TableSchema,
+        embedding_spec: Option<EmbeddingSpec>,
+    ) -> Result<()> {
+        self.preflight_write_limits()?;
+        let name = name.into();
+        let mut inner = self.write_inner()?;
+        if inner.state.tables.contains_key(&name) {
+            return Err(anyhow!("table already exists"));
+        }
+
+        schema.validate_schema()?;
+        if let Some(spec) = &embedding_spec {
+            spec.validate()?;
+        }
+        let dir = sst::table_dir(&self.config.data_dir, &name);
+        sst::ensure_dir(&dir)?;
+
+        let record = WalRecord::CreateTable {
+            name: name.clone(),
+            schema: schema.clone(),
+            embedding_spec: embedding_spec.clone(),
+        };
+        append_durable_wal(&mut inner, Some(&name), &record)?;
+
+        inner
+            .state
+            .tables
+            .insert(name, TableState::new(schema, embedding_spec));
+
+        inner.commit()
+    }
+
+    pub fn insert_row(&self, table: &str, fields: BTreeMap<String, Value>) -> Result<u64> {
+        self.insert_row_internal(table, fields, None)
+    }
+
+    /// Inserts a row that `expire_rows` deletes once `ttl` has passed.
+    pub fn insert_row_with_ttl(
+        &self,
+        table: &str,
+        fields: BTreeMap<String, Value>,
+        ttl: Duration,
+    ) -> Result<u64> {
+        self.insert_row_internal(table, fields, Some(ttl))
+    }
+
+    fn insert_row_internal(
+        &self,
+        table: &str,
+        mut fields: BTreeMap<String, Value>,
+        ttl: Option<Duration>,
+    ) -> Result<u64> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let now_ms = now_epoch_ms();
+        let (row_id, embedding_spec) = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            table_state.schema.apply_defaults(&mut fields, now_ms);
+            table_state.schema.apply_generated(&mut fields)?;
+            table_state.schema.validate_row(&fields)?;
+            (table_state.next_row_id, table_state.embedding_spec.clone())
+        };
+
+        let row = RowData {
+            id: row_id,
+            version: 1,
+            created_at_ms: now_ms,
+            updated_at_ms: now_ms,
+            fields: fields.clone(),
+        };
+
+        let expires_at_ms = ttl.map(expiry_after);
+        let mut records = vec![WalRecord::PutRow {
+            table: table.to_string(),
+            row_id,
+            row: row.clone(),
+        }];
+        records.extend(expires_at_ms.map(|expires_at_ms| WalRecord::SetRowExpiry {
+            table: table.to_string(),
+            row_id,
+            expires_at_ms,
+        }));
+        // Primary write: durable first.
+        append_durable_wal_batch(&mut inner, Some(table), &records)?;
+
+        if let Some(table_state) = inner.state.tables.get_mut(table) {
+            if let Some(expires_at_ms) = expires_at_ms {
+                table_state.expirations.insert(row_id, expires_at_ms);
+            }
+            table_state.put_row(row.clone());
+        }
+
+        if let Some(spec) = embedding_spec {
+            let content_hash = spec.content_hash(&fields)?;
+            let job_record = WalRecord::EnqueueEmbedding {
+                table: table.to_string(),
+                row_id,
+                content_hash: content_hash.clone(),
+            };
+            append_durable_wal(&mut inner, Some(table), &job_record)?;
+
+            if let Some(table_state) = inner.state.tables.get_mut(table) {
+                table_state.embedding_meta.insert(
+                    row_id,
+                    EmbeddingMeta {
+                        status: EmbeddingStatus::Pending,
+                        content_hash,
+                        last_error: None,
+                        attempts: 0,
+                        next_retry_at_ms: 0,
+                        model: None,
+                    },
+                );
             }
+        }
+
+        let change = RowChange {
+            table: table.to_string(),
+            row_id,
+            kind: RowChangeKind::Insert,
+            old: None,
+            new: Some(row),
         };
-        if let Some(elapsed_ms) = elapsed_ms {
-            inner.metrics.flush_count_total += 1;
-            inner.metrics.flush_total_ms = inner.metrics.flush_total_ms.saturating_add(elapsed_ms);
+        self.commit_changes(inner, vec![change])?;
+        Ok(row_id)
+    }
+
+    /// Inserts `rows` under consecutive row ids with a single WAL sync for the whole batch. Every
+    /// row is validated before anything is written, so one invalid row fails the batch and none
+    /// are inserted.
+    pub fn insert_rows(&self, table: &str, rows: Vec<BTreeMap<String, Value>>) -> Result<Vec<u64>> {
+        self.insert_rows_internal(table, rows, None, None)
+    }
+
+    /// `insert_rows` for rows that `expire_rows` deletes once `ttl` has passed.
+    pub fn insert_rows_with_ttl(
+        &self,
+        table: &str,
+        rows: Vec<BTreeMap<String, Value>>,
+        ttl: Duration,
+    ) -> Result<Vec<u64>> {
+        self.insert_rows_internal(table, rows, Some(ttl), None)
+    }
+
+    /// Inserts a row together with an embedding computed outside the database, e.g. in bulk by
+    /// an ETL job. The row is never queued: its job is `Ready` from the start, with the vector
+    /// recorded under the spec's `model` as by `put_embedding`, and row, vector, and status are
+    /// logged as one atomic batch.
+    pub fn insert_row_with_embedding(
+        &self,
+        table: &str,
+        fields: BTreeMap<String, Value>,
+        embedding: Vec<f32>,
+    ) -> Result<u64> {
+        let row_ids =
+            self.insert_rows_internal(table, vec![fields], None, Some(vec![embedding]))?;
+        Ok(row_ids[0])
+    }
+
+    /// `insert_rows` for rows that come with their embeddings, as `insert_row_with_embedding`
+    /// does. Every vector must have the same, expected dimension.
+    pub fn insert_rows_with_embeddings(
+        &self,
+        table: &str,
+        rows: Vec<(BTreeMap<String, Value>, Vec<f32>)>,
+    ) -> Result<Vec<u64>> {
+        let (rows, embeddings) = rows.into_iter().unzip();
+        self.insert_rows_internal(table, rows, None, Some(embeddings))
+    }
+
+    /// `embeddings`, when set, holds one vector per row.
+    fn insert_rows_internal(
+        &self,
+        table: &str,
+        rows: Vec<BTreeMap<String, Value>>,
+        ttl: Option<Duration>,
+        embeddings: Option<Vec<Vec<f32>>>,
+    ) -> Result<Vec<u64>> {
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let imported = embeddings.is_some();
+        if imported && table_state.embedding_spec.is_none() {
+            return Err(anyhow!("table has no embedding spec"));
+        }
+        let mut embeddings = embeddings.map(Vec::into_iter);
+        let mut dimensions = table_state.expected_dimension();
+        let first_row_id = table_state.next_row_id;
+        let now_ms = now_epoch_ms();
+        let mut prepared = Vec::with_capacity(rows.len());
+        for (offset, mut fields) in rows.into_iter().enumerate() {
+            table_state.schema.apply_defaults(&mut fields, now_ms);
+            table_state
+                .schema
+                .apply_generated(&mut fields)
+                .and_then(|_| table_state.schema.validate_row(&fields))
+                .map_err(|err| anyhow!("row {offset}: {err}"))?;
+            let content_hash = table_state
+                .embedding_spec
+                .as_ref()
+                .map(|spec| spec.content_hash(&fields))
+                .transpose()?;
+            let embedding = embeddings.as_mut().and_then(Iterator::next);
+            if let Some(vector) = &embedding {
+                if vector.is_empty() || !vector.iter().all(|value| value.is_finite()) {
+                    return Err(anyhow!(
+                        "row {offset}: vector must be non-empty with finite values"
+                    ));
+                }
+                // The first vector fixes the dimension of a table that has none yet.
+                let expected = *dimensions.get_or_insert(vector.len());
+                if vector.len() != expected {
+                    return Err(anyhow!(
+                        "row {offset}: vector has {} dimensions but the table expects {expected}",
+                        vector.len()
+                    ));
+                }
+            }
+            let row = RowData {
+                id: first_row_id + offset as u64,
+                version: 1,
+                created_at_ms: now_ms,
+                updated_at_ms: now_ms,
+                fields,
+            };
+            prepared.push((row, content_hash, embedding));
+        }
+        let model = table_state
+            .embedding_spec
+            .as_ref()
+            .and_then(|spec| spec.model.clone());
+
+        let expires_at_ms = ttl.map(expiry_after);
+        let mut records = Vec::with_capacity(prepared.len() * 2);
+        // Replay drops the whole batch if it was cut short, so no row is left queued for an
+        // embedder that would disagree with the imported vectors.
+        if imported {
+            records.push(WalRecord::BeginBatch);
+        }
+        for (row, content_hash, embedding) in &prepared {
+            records.push(WalRecord::PutRow {
+                table: table.to_string(),
+                row_id: row.id,
+                row: row.clone(),
+            });
+            if let Some(expires_at_ms) = expires_at_ms {
+                records.push(WalRecord::SetRowExpiry {
+                    table: table.to_string(),
+                    row_id: row.id,
+                    expires_at_ms,
+                });
+            }
+            if let Some(content_hash) = content_hash {
+                records.push(WalRecord::EnqueueEmbedding {
+                    table: table.to_string(),
+                    row_id: row.id,
+                    content_hash: content_hash.clone(),
+                });
+            }
+            if let Some(vector) = embedding {
+                records.push(WalRecord::StoreEmbedding {
+                    table: table.to_string(),
+                    row_id: row.id,
+                    vector: vector.clone(),
+                    norm: None,
+                });
+                records.push(WalRecord::UpdateEmbeddingStatus {
+                    table: table.to_string(),
+                    row_id: row.id,
+                    status: EmbeddingStatus::Ready,
+                    last_error: None,
+                    attempts: Some(0),
+                    next_retry_at_ms: Some(0),
+                    model: model.clone(),
+                });
+            }
+        }
+        if imported {
+            records.push(WalRecord::CommitBatch);
+        }
+        append_durable_wal_batch(&mut inner, Some(table), &records)?;
+
+        let table_state = inner
+            .state
+            .tables
+            .get_mut(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let mut row_ids = Vec::with_capacity(prepared.len());
+        let mut inserted = Vec::with_capacity(prepared.len());
+        for (row, content_hash, embedding) in prepared {
+            if let Some(expires_at_ms) = expires_at_ms {
+                table_state.expirations.insert(row.id, expires_at_ms);
+            }
+            table_state.put_row(row.clone());
+            if let Some(content_hash) = content_hash {
+                let ready = embedding.is_some();
+                table_state.embedding_meta.insert(
+                    row.id,
+                    EmbeddingMeta {
+                        status: if ready {
+                            EmbeddingStatus::Ready
+                        } else {
+                            EmbeddingStatus::Pending
+                        },
+                        content_hash,
+                        last_error: None,
+                        attempts: 0,
+                        next_retry_at_ms: 0,
+                        model: if ready { model.clone() } else { None },
+                    },
+                );
+            }
+            if let Some(vector) = embedding {
+                table_state.store_embedding(row.id, vector, None);
+            }
+            row_ids.push(row.id);
+            inserted.push(row);
+        }
+
+        let changes = inserted
+            .into_iter()
+            .map(|row| RowChange {
+                table: table.to_string(),
+                row_id: row.id,
+                kind: RowChangeKind::Insert,
+                old: None,
+                new: Some(row),
+            })
+            .collect();
+        self.commit_changes(inner, changes)?;
+        Ok(row_ids)
+    }
+
+    /// Replaces the fields of a row and returns its new version.
+    pub fn update_row(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+    ) -> Result<u64> {
+        self.update_row_internal(table, row_id, fields, None, false)
+            .map(|row| row.version)
+    }
+
+    /// Replaces the row only if it is still at `expected_version`, for read-modify-write cycles
+    /// built on `get_row`. Returns the new version; a row that changed in the meantime fails with
+    /// a `VersionConflict` error.
+    pub fn update_row_if_version(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+        expected_version: u64,
+    ) -> Result<u64> {
+        self.update_row_internal(table, row_id, fields, Some(expected_version), false)
+            .map(|row| row.version)
+    }
+
+    /// Merges `fields` into the row, keeping every column they don't mention, and returns the
+    /// updated row. Generated columns are recomputed from the merged fields.
+    pub fn patch_row(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+    ) -> Result<RowData> {
+        self.update_row_internal(table, row_id, fields, None, true)
+    }
+
+    /// `patch_row` that only applies if the row is still at `expected_version`; otherwise fails
+    /// with a `VersionConflict` error.
+    pub fn patch_row_if_version(
+        &self,
+        table: &str,
+        row_id: u64,
+        fields: BTreeMap<String, Value>,
+        expected_version: u64,
+    ) -> Result<RowData> {
+        self.update_row_internal(table, row_id, fields, Some(expected_version), true)
+    }
+
+    fn update_row_internal(
+        &self,
+        table: &str,
+        row_id: u64,
+        mut fields: BTreeMap<String, Value>,
+        expected_version: Option<u64>,
+        merge: bool,
+    ) -> Result<RowData> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let (embedding_spec, old) = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            let old = load_row(table_state, row_id)?.ok_or_else(|| anyhow!("row not found"))?;
+            check_row_version(&old, expected_version)?;
+            if merge {
+                let mut merged = old.fields.clone();
+                for col in &table_state.schema.columns {
+                    if col.generated.is_some() {
+                        merged.remove(&col.name);
+                    }
+                }
+                merged.append(&mut fields);
+                fields = merged;
+            }
+            table_state.schema.apply_generated(&mut fields)?;
+            table_state.schema.validate_row(&fields)?;
+            (table_state.embedding_spec.clone(), old)
+        };
+        let row = RowData {
+            id: row_id,
+            version: old.version + 1,
+            created_at_ms: old.created_at_ms,
+            updated_at_ms: now_epoch_ms(),
+            fields: fields.clone(),
+        };
+
+        let record = WalRecord::PutRow {
+            table: table.to_string(),
+            row_id,
+            row: row.clone(),
+        };
+        append_durable_wal(&mut inner, Some(table), &record)?;
+
+        if let Some(table_state) = inner.state.tables.get_mut(table) {
+            table_state.put_row(row.clone());
+        }
+
+        if let Some(spec) = embedding_spec {
+            let content_hash = spec.content_hash(&fields)?;
+            let table_state = inner
+                .state
+                .tables
+                .get_mut(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            // A ready embedding of the same source content stays valid, so no job is enqueued.
+            let unchanged = table_state.embeddings.contains_key(&row_id)
+                && table_state.embedding_meta.get(&row_id).is_some_and(|meta| {
+                    meta.status == EmbeddingStatus::Ready && meta.content_hash == content_hash
+                });
+            if unchanged {
+                table_state.metrics.skipped_unchanged += 1;
+            } else {
+                let job_record = WalRecord::EnqueueEmbedding {
+                    table: table.to_string(),
+                    row_id,
+                    content_hash: content_hash.clone(),
+                };
+                append_durable_wal(&mut inner, Some(table), &job_record)?;
+
+                if let Some(table_state) = inner.state.tables.get_mut(table) {
+                    table_state.embedding_meta.insert(
+                        row_id,
+                        EmbeddingMeta {
+                            status: EmbeddingStatus::Pending,
+                            content_hash,
+                            last_error: None,
+                            attempts: 0,
+                            next_retry_at_ms: 0,
+                            model: None,
+                        },
+                    );
+                }
+            }
+        }
+
+        let change = RowChange {
+            table: table.to_string(),
+            row_id,
+            kind: RowChangeKind::Update,
+            old: Some(old),
+            new: Some(row.clone()),
+        };
+        self.commit_changes(inner, vec![change])?;
+        Ok(row)
+    }
+
+    pub fn delete_row(&self, table: &str, row_id: u64) -> Result<()> {
+        self.delete_row_internal(table, row_id, None)
+    }
+
+    /// Deletes the row only if it is still at `expected_version`; otherwise fails with a
+    /// `VersionConflict` error.
+    pub fn delete_row_if_version(
+        &self,
+        table: &str,
+        row_id: u64,
+        expected_version: u64,
+    ) -> Result<()> {
+        self.delete_row_internal(table, row_id, Some(expected_version))
+    }
+
+    fn delete_row_internal(
+        &self,
+        table: &str,
+        row_id: u64,
+        expected_version: Option<u64>,
+    ) -> Result<()> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let (old, soft) = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            let old = load_row(table_state, row_id)?.ok_or_else(|| anyhow!("row not found"))?;
+            check_row_version(&old, expected_version)?;
+            (old, table_state.schema.soft_delete.is_some())
+        };
+
+        let record = delete_record(table, row_id, soft);
+        append_durable_wal(&mut inner, Some(table), &record)?;
+        apply_record(&mut inner.state, record)?;
+
+        let change = RowChange {
+            table: table.to_string(),
+            row_id,
+            kind: RowChangeKind::Delete,
+            old: Some(old),
+            new: None,
+        };
+        self.commit_changes(inner, vec![change])
+    }
+
+    /// Applies `ops` atomically, across any number of tables: every op is validated against the
+    /// state left by the ops before it, then all of their records are logged between batch
+    /// markers with a single WAL sync. One failing op fails the batch and nothing is written, and
+    /// replay discards a batch whose commit marker never reached the WAL.
+    pub fn write_batch(&self, ops: Vec<WriteOp>) -> Result<Vec<WriteOpResult>> {
+        if ops.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+
+        // The rows the batch has written so far (`None` once deleted), and the next row id of
+        // each table it inserted into.
+        let mut written: HashMap<(String, u64), Option<RowData>> = HashMap::new();
+        let mut next_row_ids: HashMap<String, u64> = HashMap::new();
+        let mut records = vec![WalRecord::BeginBatch];
+        let mut changes = Vec::with_capacity(ops.len());
+        let mut skipped_unchanged: HashMap<String, u64> = HashMap::new();
+        let now_ms = now_epoch_ms();
+        for (idx, op) in ops.into_iter().enumerate() {
+            let table = match &op {
+                WriteOp::Insert { table, .. }
+                | WriteOp::Update { table, .. }
+                | WriteOp::Delete { table, .. } => table.clone(),
+            };
+            let table_state = inner
+                .state
+                .tables
+                .get(&table)
+                .ok_or_else(|| anyhow!("table not found"))
+                .with_context(|| format!("op {idx}"))?;
+            let current = |row_id: u64| -> Result<Option<RowData>> {
+                match written.get(&(table.clone(), row_id)) {
+                    Some(row) => Ok(row.clone()),
+                    None => load_row(table_state, row_id),
+                }
+            };
+            let (kind, old, new) = match op {
+                WriteOp::Insert { mut fields, .. } => {
+                    let next_row_id = next_row_ids
+                        .entry(table.clone())
+                        .or_insert(table_state.next_row_id);
+                    table_state.schema.apply_defaults(&mut fields, now_ms);
+                    table_state
+                        .schema
+                        .apply_generated(&mut fields)
+                        .and_then(|_| table_state.schema.validate_row(&fields))
+                        .with_context(|| format!("op {idx}"))?;
+                    let row = RowData {
+                        id: *next_row_id,
+                        version: 1,
+                        created_at_ms: now_ms,
+                        updated_at_ms: now_ms,
+                        fields,
+                    };
+                    *next_row_id += 1;
+                    (RowChangeKind::Insert, None, Some(row))
+                }
+                WriteOp::Update {
+                    row_id,
+                    mut fields,
+                    expected_version,
+                    ..
+                } => {
+                    let old = current(row_id)
+                        .and_then(|row| row.ok_or_else(|| anyhow!("row not found")))
+                        .and_then(|old| check_row_version(&old, expected_version).map(|_| old))
+                        .and_then(|old| {
+                            table_state.schema.apply_generated(&mut fields)?;
+                            table_state.schema.validate_row(&fields)?;
+                            Ok(old)
+                        })
+                        .with_context(|| format!("op {idx}"))?;
+                    let row = RowData {
+                        id: row_id,
+                        version: old.version + 1,
+                        created_at_ms: old.created_at_ms,
+                        updated_at_ms: now_ms,
+                        fields,
+                    };
+                    (RowChangeKind::Update, Some(old), Some(row))
+                }
+                WriteOp::Delete {
+                    row_id,
+                    expected_version,
+                    ..
+                } => {
+                    let old = current(row_id)
+                        .and_then(|row| row.ok_or_else(|| anyhow!("row not found")))
+                        .and_then(|old| check_row_version(&old, expected_version).map(|_| old))
+                        .with_context(|| format!("op {idx}"))?;
+                    (RowChangeKind::Delete, Some(old), None)
+                }
+            };
+
+            let row_id = old.as_ref().or(new.as_ref()).map_or(0, |row| row.id);
+            match &new {
+                Some(row) => {
+                    records.push(WalRecord::PutRow {
+                        table: table.clone(),
+                        row_id,
+                        row: row.clone(),
+                    });
+                    if let Some(spec) = &table_state.embedding_spec {
+                        let content_hash = spec
+                            .content_hash(&row.fields)
+                            .with_context(|| format!("op {idx}"))?;
+                        // As in `update_row`, a ready embedding of the same content stays valid
+                        // unless the batch already changed the row.
+                        let unchanged = kind == RowChangeKind::Update
+                            && !written.contains_key(&(table.clone(), row_id))
+                            && table_state.embeddings.contains_key(&row_id)
+                            && table_state.embedding_meta.get(&row_id).is_some_and(|meta| {
+                                meta.status == EmbeddingStatus::Ready
+                                    && meta.content_hash == content_hash
+                            });
+                        if unchanged {
+                            *skipped_unchanged.entry(table.clone()).or_default() += 1;
+                        } else {
+                            records.push(WalRecord::EnqueueEmbedding {
+                                table: table.clone(),
+                                row_id,
+                                content_hash,
+                            });
+                        }
+                    }
+                }
+                None => records.push(delete_record(
+                    &table,
+                    row_id,
+                    table_state.schema.soft_delete.is_some(),
+                )),
+            }
+            written.insert((table.clone(), row_id), new.clone());
+            changes.push(RowChange {
+                table,
+                row_id,
+                kind,
+                old,
+                new,
+            });
+        }
+        records.push(WalRecord::CommitBatch);
+
+        let start = inner.wal.len();
+        if let Err(err) = append_durable_wal_batch(&mut inner, None, &records) {
+            // Cut off the partial batch so later appends don't land inside it on replay.
+            if let Err(truncate_err) = inner.wal.truncate(start) {
+                tracing::warn!("failed to discard partial write batch: {truncate_err:#}");
+            }
+            return Err(err);
+        }
+        for record in records {
+            if let Some(table) = record.table() {
+                lock_cache(&inner.search_cache).invalidate_table(table);
+                if let Some(table_state) = inner.state.tables.get_mut(table) {
+                    table_state.metrics.wal_durable_appends += 1;
+                }
+            }
+            apply_record(&mut inner.state, record)?;
+        }
+        for (table, skipped) in skipped_unchanged {
+            if let Some(table_state) = inner.state.tables.get_mut(&table) {
+                table_state.metrics.skipped_unchanged += skipped;
+            }
+        }
+
+        let results = changes
+            .iter()
+            .map(|change| WriteOpResult {
+                row_id: change.row_id,
+                version: change.new.as_ref().map(|row| row.version),
+            })
+            .collect();
+        self.commit_changes(inner, changes)?;
+        Ok(results)
+    }
+
+    /// Deletes every row matching all of `filters` (every row when empty) with a single WAL
+    /// record, returning the number of rows deleted.
+    pub fn delete_rows_where(&self, table: &str, filters: &[FilterCondition]) -> Result<usize> {
+        self.delete_rows_internal(table, 0..u64::MAX, filters)
+    }
+
+    /// Deletes every row with an id in `range` with a single WAL record, returning the number of
+    /// rows deleted.
+    pub fn delete_range(&self, table: &str, range: Range<u64>) -> Result<usize> {
+        self.delete_rows_internal(table, range, &[])
+    }
+
+    /// Logs the matching rows as range tombstones: each run of matches not interrupted by a live
+    /// non-matching row becomes one range, whatever ids were deleted before inside it.
+    fn delete_rows_internal(
+        &self,
+        table: &str,
+        bounds: Range<u64>,
+        filters: &[FilterCondition],
+    ) -> Result<usize> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let (ranges, deleted) = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            validate_filters(&table_state.schema, filters)?;
+            let end = bounds.end.min(table_state.next_row_id);
+            let mut ranges = Vec::new();
+            let mut deleted = Vec::new();
+            let mut run: Option<Range<u64>> = None;
+            if bounds.start < end {
+                for (row_id, row) in scan_visible_rows(table_state)?.range(bounds.start..end) {
+                    if row_matches_filters(&table_state.schema, row, filters) {
+                        let start = run.take().map_or(*row_id, |run| run.start);
+                        run = Some(start..row_id + 1);
+                        deleted.push(row.clone());
+                    } else {
+                        ranges.extend(run.take());
+                    }
+                }
+            }
+            ranges.extend(run);
+            (ranges, deleted)
+        };
+        if deleted.is_empty() {
+            return Ok(0);
+        }
+
+        let soft = inner
+            .state
+            .tables
+            .get(table)
+            .is_some_and(|table_state| table_state.schema.soft_delete.is_some());
+        if soft {
+            // Hidden rows keep their data, so each is logged on its own rather than as a range.
+            let records: Vec<WalRecord> = deleted
+                .iter()
+                .map(|row| delete_record(table, row.id, true))
+                .collect();
+            append_durable_wal_batch(&mut inner, Some(table), &records)?;
+            for record in records {
+                apply_record(&mut inner.state, record)?;
+            }
+        } else {
+            let record = WalRecord::DeleteRanges {
+                table: table.to_string(),
+                ranges: ranges.clone(),
+            };
+            append_durable_wal(&mut inner, Some(table), &record)?;
+            if let Some(table_state) = inner.state.tables.get_mut(table) {
+                table_state.delete_ranges(&ranges);
+            }
+        }
+
+        let count = deleted.len();
+        self.commit_changes(inner, deleted_changes(table, deleted))?;
+        Ok(count)
+    }
+
+    /// Makes a row hidden by a delete on a soft-delete table visible again, and returns it.
+    /// Fires an `Insert` trigger, since the delete fired a `Delete` one.
+    pub fn restore_row(&self, table: &str, row_id: u64) -> Result<RowData> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let row = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            if table_state.schema.soft_delete.is_none() {
+                return Err(anyhow!("table does not use soft delete"));
+            }
+            if !table_state.hidden.contains_key(&row_id) {
+                return Err(if row_exists(table_state, row_id)? {
+                    anyhow!("row is not deleted")
+                } else {
+                    anyhow!("row not found")
+                });
+            }
+            load_stored_row(table_state, row_id)?.ok_or_else(|| anyhow!("row not found"))?
+        };
+
+        let record = WalRecord::RestoreRow {
+            table: table.to_string(),
+            row_id,
+        };
+        append_durable_wal(&mut inner, Some(table), &record)?;
+        apply_record(&mut inner.state, record)?;
+
+        let change = RowChange {
+            table: table.to_string(),
+            row_id,
+            kind: RowChangeKind::Insert,
+            old: None,
+            new: Some(row.clone()),
+        };
+        self.commit_changes(inner, vec![change])?;
+        Ok(row)
+    }
+
+    /// Deletes the table's hidden rows for good, returning how many: they become tombstones
+    /// like hard-deleted rows, and their data is dropped from SST files by compaction.
+    pub fn purge_hidden_rows(&self, table: &str) -> Result<usize> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let purged = purge_hidden_rows_locked(&mut inner, table, u64::MAX)?;
+        inner.commit()?;
+        Ok(purged)
+    }
+
+    /// Dry run of `apply_embedding_spec`: reports which rows would be re-embedded.
+    pub fn plan_embedding_spec(&self, table: &str, spec: &EmbeddingSpec) -> Result<ReembedPlan> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let (plan, _) = plan_reembed(table_state, spec, false)?;
+        Ok(plan)
+    }
+
+    /// Replaces the table's embedding spec and enqueues re-embedding for every row whose content
+    /// hash changes under it, or whose embedding was stored under a model other than the one the
+    /// spec names. Giving a table without a spec its first one backfills every row.
+    ///
+    /// Jobs are logged in batches, one WAL sync each. If a crash cuts the enqueueing short,
+    /// applying the same spec again enqueues the rows that were missed.
+    pub fn apply_embedding_spec(&self, table: &str, spec: EmbeddingSpec) -> Result<ReembedPlan> {
+        self.apply_spec_and_reembed(table, |_| Ok(spec), false)
+    }
+
+    /// Switches the table to another embedding model: records `model` in its embedding spec and
+    /// enqueues re-embedding for every row whose embedding was stored under a different model,
+    /// or before the spec named one. Pending rows are embedded under `model` when processed.
+    /// The new model's vectors must have the table's dimension.
+    pub fn reembed_table(&self, table: &str, model: EmbeddingModel) -> Result<ReembedPlan> {
+        self.apply_spec_and_reembed(
+            table,
+            |table_state| {
+                let spec = table_state
+                    .embedding_spec
+                    .clone()
+                    .ok_or_else(|| anyhow!("table has no embedding spec"))?;
+                Ok(spec.with_model(model))
+            },
+            true,
+        )
+    }
+
+    /// Sets the spec built from the table's current state and enqueues the rows `plan_reembed`
+    /// finds stale under it.
+    fn apply_spec_and_reembed(
+        &self,
+        table: &str,
+        build_spec: impl FnOnce(&TableState) -> Result<EmbeddingSpec>,
+        untracked_stale: bool,
+    ) -> Result<ReembedPlan> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let (spec, mut plan, hashes) = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            let spec = build_spec(table_state)?;
+            let (plan, hashes) = plan_reembed(table_state, &spec, untracked_stale)?;
+            (spec, plan, hashes)
+        };
+
+        let record = WalRecord::SetEmbeddingSpec {
+            table: table.to_string(),
+            embedding_spec: Some(spec.clone()),
+        };
+        append_durable_wal(&mut inner, Some(table), &record)?;
+        if let Some(table_state) = inner.state.tables.get_mut(table) {
+            table_state.set_embedding_spec(Some(spec));
+        }
+
+        for chunk in hashes.chunks(REEMBED_BATCH_ROWS) {
+            let job_records: Vec<WalRecord> = chunk
+                .iter()
+                .map(|(row_id, content_hash)| WalRecord::EnqueueEmbedding {
+                    table: table.to_string(),
+                    row_id: *row_id,
+                    content_hash: content_hash.clone(),
+                })
+                .collect();
+            append_durable_wal_batch(&mut inner, Some(table), &job_records)?;
+            if let Some(table_state) = inner.state.tables.get_mut(table) {
+                for (row_id, content_hash) in chunk {
+                    table_state.embedding_meta.insert(
+                        *row_id,
+                        EmbeddingMeta {
+                            status: EmbeddingStatus::Pending,
+                            content_hash: content_hash.clone(),
+                            last_error: None,
+                            attempts: 0,
+                            next_retry_at_ms: 0,
+                            model: None,
+                        },
+                    );
+                }
+            }
+        }
+
+        plan.applied = true;
+        inner.commit()?;
+        Ok(plan)
+    }
+
+    /// Adds, drops, or renames a column without rewriting the table: rows already flushed to SST
+    /// files are brought up to date as they are read, and for good once compaction merges them.
+    /// Columns the embedding spec reads cannot be dropped or renamed.
+    pub fn alter_table(&self, table: &str, op: AlterTableOp) -> Result<()> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let before_seq = {
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            let removed = match &op {
+                AlterTableOp::AddColumn { .. } => None,
+                AlterTableOp::DropColumn { name } => Some(name),
+                AlterTableOp::RenameColumn { from, .. } => Some(from),
+            };
+            if let (Some(column), Some(spec)) = (removed, &table_state.embedding_spec) {
+                if spec.all_source_fields().any(|field| field == column) {
+                    return Err(anyhow!(
+                        "column '{column}' is an embedding source field; change the embedding spec first"
+                    ));
+                }
+            }
+            op.apply_to_schema(&table_state.schema)?;
+            table_state.next_sst_seq
+        };
+
+        let record = WalRecord::AlterTable {
+            table: table.to_string(),
+            op: op.clone(),
+            before_seq,
+        };
+        append_durable_wal(&mut inner, Some(table), &record)?;
+        if let Some(table_state) = inner.state.tables.get_mut(table) {
+            table_state.alter(op, before_seq)?;
+        }
+        inner.commit()?;
+        Ok(())
+    }
+
+    /// Renames a table in place: its directory is renamed rather than its rows copied, and the
+    /// WAL is checkpointed so nothing logged under the old name is left to replay.
+    pub fn rename_table(&self, table: &str, new_name: impl Into<String>) -> Result<()> {
+        self.preflight_write_limits()?;
+        let new_name = new_name.into();
+        if new_name.is_empty()
+            || new_name == "."
+            || new_name == ".."
+            || new_name.contains(['/', '\\'])
+        {
+            return Err(anyhow!("invalid table name '{new_name}'"));
+        }
+        let mut inner = self.write_inner()?;
+        if !inner.state.tables.contains_key(table) {
+            return Err(anyhow!("table not found"));
+        }
+        if inner.state.tables.contains_key(&new_name) {
+            return Err(anyhow!("table already exists"));
+        }
+        let dir = sst::table_dir(&self.config.data_dir, &new_name);
+        if dir.exists() {
+            return Err(anyhow!(
+                "cannot rename to '{new_name}': {} already exists",
+                dir.display()
+            ));
+        }
+
+        rename_table_locked(&self.config, &mut inner, table, &new_name)?;
+        inner.commit()
+    }
+
+    /// Registers a trigger that runs after every committed insert, update, and delete.
+    pub fn register_trigger(&self, trigger: Arc<dyn RowTrigger>) {
+        self.triggers.register(trigger);
+    }
+
+    /// The table's row changes committed after feed position `after`, oldest first. Start from
+    /// `last_change_seq` to follow only changes made from now on. Fails with `ChangesLagged`
+    /// when the feed no longer holds every change after `after`.
+    pub fn changes_since(&self, table: &str, after: u64) -> Result<ChangePage> {
+        Ok(self.changes.since(table, after)?)
+    }
+
+    /// The change feed's position: the seq of the last committed row change, or the WAL's LSN at
+    /// open when none has been committed since.
+    pub fn last_change_seq(&self) -> u64 {
+        self.changes.last_seq()
+    }
+
+    /// Follows the table's row changes committed from now on, for embedding workers, indexers,
+    /// and replication tools in the same process that would otherwise poll.
+    pub fn subscribe(&self, table: &str) -> Result<ChangeSubscription> {
+        self.subscribe_after(table, self.changes.last_seq())
+    }
+
+    /// Follows the table's row changes committed after feed position `after`, failing with
+    /// `ChangesLagged` when the feed no longer holds them all.
+    pub fn subscribe_after(&self, table: &str, after: u64) -> Result<ChangeSubscription> {
+        if !self.read_inner()?.state.tables.contains_key(table) {
+            return Err(anyhow!("table not found"));
+        }
+        self.changes.since(table, after)?;
+        Ok(ChangeSubscription::new(self.changes.clone(), table, after))
+    }
+
+    /// Registers a named custom metric for `search_knn_named` and `EmbeddingSpec::custom_metric`.
+    /// Registrations are not persisted; re-register after every open. `l2` and `cosine` are
+    /// reserved.
+    pub fn register_metric(&self, name: &str, metric: Arc<dyn DistanceFn>) -> Result<()> {
+        self.distance_fns.register(name, metric)
+    }
+
+    pub fn get_row(&self, table: &str, row_id: u64) -> Result<Option<RowData>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        load_row(table_state, row_id)
+    }
+
+    /// Pages through a table's live rows in row id order, starting after `start_after`. Rows are
+    /// merged from the SSTs and the memtable, so deleted rows never appear.
+    pub fn scan_rows(
+        &self,
+        table: &str,
+        start_after: Option<u64>,
+        limit: usize,
+    ) -> Result<RowPage> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+
+        scan_rows_locked(table_state, start_after, limit)
+    }
+
+    /// Reads a table's live rows, with their ready embeddings, as Arrow record batches of up to
+    /// `batch_rows` rows each (feature `arrow`); see `ArrowBatches` for the columns. Everything
+    /// is read up front under one lock, so the batches are a consistent snapshot and later
+    /// writes don't show up in them. Quantized tables export their exact vectors.
+    #[cfg(feature = "arrow")]
+    pub fn export_arrow(&self, table: &str, batch_rows: usize) -> Result<ArrowBatches> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        export_arrow_locked(table_state, batch_rows, |row_id| {
+            inner.raw_vectors.exact_vector(table, row_id)
+        })
+    }
+
+    pub fn list_embedding_jobs(&self, table: &str) -> Result<Vec<EmbeddingJob>> {
+        Ok(self
+            .list_embedding_jobs_page(table, None, None, usize::MAX)?
+            .items)
+    }
+
+    /// Embedding jobs of every table, ordered by table name and then row id, keeping only jobs in
+    /// `status` when it is set.
+    pub fn list_embedding_jobs_all(
+        &self,
+        status: Option<EmbeddingStatus>,
+    ) -> Result<Vec<EmbeddingJob>> {
+        let inner = self.read_inner()?;
+        let mut tables: Vec<(&String, &TableState)> = inner.state.tables.iter().collect();
+        tables.sort_by_key(|(name, _)| *name);
+
+        let mut jobs = Vec::new();
+        for (table, table_state) in tables {
+            let start = jobs.len();
+            for (row_id, meta) in &table_state.embedding_meta {
+                if status.is_some_and(|status| meta.status != status) {
+                    continue;
+                }
+                jobs.push(EmbeddingJob {
+                    table: table.clone(),
+                    row_id: *row_id,
+                    status: meta.status,
+                    content_hash: meta.content_hash.clone(),
+                    last_error: meta.last_error.clone(),
+                    attempts: meta.attempts,
+                    next_retry_at_ms: meta.next_retry_at_ms,
+                });
+            }
+            jobs[start..].sort_by_key(|job| job.row_id);
+        }
+        Ok(jobs)
+    }
+
+    /// Pages through a table's embedding jobs in row id order, starting after `cursor`, keeping
+    /// only jobs in `status` when it is set.
+    pub fn list_embedding_jobs_page(
+        &self,
+        table: &str,
+        status: Option<EmbeddingStatus>,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EmbeddingJobPage> {
+        self.list_embedding_jobs_with_options(
+            table,
+            &JobListOptions {
+                status,
+                cursor,
+                limit: Some(limit),
+                ..JobListOptions::default()
+            },
+        )
+    }
+
+    /// Lists a table's embedding jobs filtered, sorted, and paged as `options` asks. A `cursor`
+    /// (the previous page's `next_cursor`) needs `JobSort::RowId`; in descending order it starts
+    /// below that row id.
+    pub fn list_embedding_jobs_with_options(
+        &self,
+        table: &str,
+        options: &JobListOptions,
+    ) -> Result<EmbeddingJobPage> {
+        if options.cursor.is_some() && options.sort != JobSort::RowId {
+            return Err(anyhow!(
+                "a job cursor needs row id order; page other orders with an offset"
+            ));
+        }
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+
+        let past_cursor = |row_id: u64| match options.cursor {
+            None => true,
+            Some(cursor) if options.descending => row_id < cursor,
+            Some(cursor) => row_id > cursor,
+        };
+        let mut jobs = Vec::new();
+        for (row_id, meta) in &table_state.embedding_meta {
+            if !past_cursor(*row_id) || options.status.is_some_and(|status| meta.status != status) {
+                continue;
+            }
+            jobs.push(EmbeddingJob {
+                table: table.to_string(),
+                row_id: *row_id,
+                status: meta.status,
+                content_hash: meta.content_hash.clone(),
+                last_error: meta.last_error.clone(),
+                attempts: meta.attempts,
+                next_retry_at_ms: meta.next_retry_at_ms,
+            });
+        }
+
+        // Deterministic output for CLI/HTTP consumers.
+        jobs.sort_by(|a, b| {
+            let order = match options.sort {
+                JobSort::RowId => a.row_id.cmp(&b.row_id),
+                JobSort::Attempts => a.attempts.cmp(&b.attempts),
+                JobSort::NextRetryAt => a.next_retry_at_ms.cmp(&b.next_retry_at_ms),
+            };
+            let order = if options.descending {
+                order.reverse()
+            } else {
+                order
+            };
+            order.then(a.row_id.cmp(&b.row_id))
+        });
+        let total = jobs.len();
+        let limit = options.limit.unwrap_or(usize::MAX);
+        let mut jobs: Vec<EmbeddingJob> = jobs.into_iter().skip(options.offset).collect();
+        let has_more = jobs.len() > limit;
+        jobs.truncate(limit);
+        let next_cursor = if has_more && options.sort == JobSort::RowId {
+            jobs.last().map(|job| job.row_id)
+        } else {
+            None
+        };
+        Ok(EmbeddingJobPage {
+            items: jobs,
+            next_cursor,
+            total,
+        })
+    }
+
+    /// Pages through a table's embeddings in row id order, starting after `cursor`.
+    pub fn scroll_embeddings(
+        &self,
+        table: &str,
+        cursor: Option<u64>,
+        limit: usize,
+    ) -> Result<EmbeddingPage> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+
+        Ok(scroll_embeddings_locked(table_state, cursor, limit))
+    }
+
+    /// A row's embedding and job status, as `scroll_embeddings` reports them; `None` when the
+    /// row does not exist or has never had an embedding job.
+    pub fn get_embedding(&self, table: &str, row_id: u64) -> Result<Option<EmbeddingRecord>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let meta = table_state.embedding_meta.get(&row_id);
+        if meta.is_none() && !table_state.embeddings.contains_key(&row_id) {
+            return Ok(None);
+        }
+        if !row_exists(table_state, row_id)? {
+            return Ok(None);
+        }
+        Ok(Some(EmbeddingRecord {
+            row_id,
+            vector: table_state.original_vector(row_id),
+            status: meta.map_or(EmbeddingStatus::Ready, |meta| meta.status),
+            model: meta.and_then(|meta| meta.model.clone()),
+        }))
+    }
+
+    pub fn retry_failed_jobs(&self, table: &str, row_id: Option<u64>) -> Result<usize> {
+        self.preflight_write_limits()?;
+        let to_retry: Vec<u64> = {
+            let inner = self.read_inner()?;
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+
+            let mut out = Vec::new();
+            for (id, meta) in &table_state.embedding_meta {
+                if meta.status != EmbeddingStatus::Failed {
+                    continue;
+                }
+                if let Some(filter) = row_id {
+                    if *id != filter {
+                        continue;
+                    }
+                }
+                if row_exists(table_state, *id)? {
+                    out.push(*id);
+                }
+            }
+            out
+        };
+
+        let mut retried = 0usize;
+        let mut inner = self.write_inner()?;
+        for id in to_retry {
+            let status_record = WalRecord::UpdateEmbeddingStatus {
+                table: table.to_string(),
+                row_id: id,
+                status: EmbeddingStatus::Pending,
+                last_error: None,
+                attempts: Some(0),
+                next_retry_at_ms: Some(0),
+                model: None,
+            };
+            append_durable_wal(&mut inner, Some(table), &status_record)?;
+
+            if let Some(table_state) = inner.state.tables.get_mut(table) {
+                if let Some(meta) = table_state.embedding_meta.get_mut(&id) {
+                    meta.status = EmbeddingStatus::Pending;
+                    meta.last_error = None;
+                    meta.attempts = 0;
+                    meta.next_retry_at_ms = 0;
+                    table_state.metrics.embeddings_retried_total += 1;
+                }
+            }
+            inner.metrics.embeddings_retried_total += 1;
+
+            retried += 1;
+        }
+
+        inner.commit()?;
+        Ok(retried)
+    }
+
+    pub fn process_pending_jobs(&self, table: &str, embedder: &dyn Embedder) -> Result<usize> {
+        self.process_pending_jobs_internal(table, embedder, None)
+    }
+
+    pub fn process_pending_jobs_with_limit(
+        &self,
+        table: &str,
+        embedder: &dyn Embedder,
+        limit: usize,
+    ) -> Result<usize> {
+        self.process_pending_jobs_internal(table, embedder, Some(limit))
+    }
+
+    /// Embeds up to `limit` pending jobs across all tables. Tables take turns in name order, each
+    /// turn processing up to one embedder batch, so a large backlog in one table cannot starve the
+    /// others. Returns the number of jobs attempted.
+    pub fn process_all_pending(&self, embedder: &dyn Embedder, limit: usize) -> Result<usize> {
+        // One clock for the whole drain, so jobs that fail and back off are not picked up again.
+        let now_ms = now_epoch_ms();
+        let quantum = embedder.batch_size().max(1);
+        let mut tables = self.list_tables()?;
+        let mut processed = 0usize;
+        while processed < limit && !tables.is_empty() {
+            let mut drained = Vec::new();
+            for table in &tables {
+                let quota = quantum.min(limit - processed);
+                if quota == 0 {
+                    break;
+                }
+                let count = self
+                    .process_pending_jobs_internal_at(table, embedder, Some(quota), now_ms)
+                    .map_err(|err| anyhow!("{table}: {err}"))?;
+                processed += count;
+                if count < quota {
+                    drained.push(table.clone());
+                }
+            }
+            tables.retain(|table| !drained.contains(table));
+        }
+        Ok(processed)
+    }
+
+    fn process_pending_jobs_internal(
+        &self,
+        table: &str,
+        embedder: &dyn Embedder,
+        limit: Option<usize>,
+    ) -> Result<usize> {
+        self.process_pending_jobs_internal_at(table, embedder, limit, now_epoch_ms())
+    }
+
+    fn process_pending_jobs_internal_at(
+        &self,
+        table: &str,
+        embedder: &dyn Embedder,
+        limit: Option<usize>,
+        now_ms: u64,
+    ) -> Result<usize> {
+        self.preflight_write_limits()?;
+        // Per pending row, the input of the unnamed vector followed by one per named vector.
+        let (vector_names, pending_jobs): (Vec<String>, Vec<(u64, Vec<String>)>) = {
+            let inner = self.read_inner()?;
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+
+            let spec = match &table_state.embedding_spec {
+                Some(spec) => spec.clone(),
+                None => return Ok(0),
+            };
+
+            let mut jobs = Vec::new();
+
+            let mut pending_row_ids: Vec<u64> = table_state
+                .embedding_meta
+                .iter()
+                .filter_map(|(row_id, meta)| {
+                    if meta.status == EmbeddingStatus::Pending && meta.next_retry_at_ms <= now_ms {
+                        Some(*row_id)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            pending_row_ids.sort();
+            if let Some(limit) = limit {
+                pending_row_ids.truncate(limit);
+            }
+
+            for row_id in pending_row_ids {
+                if let Some(row) = load_stored_row(table_state, row_id)? {
+                    let mut inputs = vec![spec.input_string(&row.fields)?];
+                    for vector in &spec.vectors {
+                        inputs.push(vector.input_string(&row.fields)?);
+                    }
+                    jobs.push((row_id, inputs));
+                }
+            }
+            let names = spec
+                .vectors
+                .iter()
+                .map(|vector| vector.name.clone())
+                .collect();
+            (names, jobs)
+        };
+
+        let per_row = vector_names.len() + 1;
+        let mut processed = 0usize;
+        for batch in pending_jobs.chunks(embedder.batch_size().max(1)) {
+            let inputs: Vec<String> = batch
+                .iter()
+                .flat_map(|(_, inputs)| inputs.iter().cloned())
+                .collect();
+            let outcomes: Vec<std::result::Result<RowEmbeddings, String>> =
+                match embedder.embed_batch(&inputs) {
+                    Ok(vectors) if vectors.len() == inputs.len() => {
+                        let mut vectors = vectors.into_iter();
+                        (0..batch.len())
+                            .map(|_| {
+                                let vector = vectors.next().unwrap_or_default();
+                                let named = vector_names
+                                    .iter()
+                                    .cloned()
+                                    .zip(vectors.by_ref().take(per_row - 1))
+                                    .collect();
+                                Ok(RowEmbeddings { vector, named })
+                            })
+                            .collect()
+                    }
+                    Ok(vectors) => {
+                        let err = format!(
+                            "embedder returned {} vectors for {} inputs",
+                            vectors.len(),
+                            inputs.len()
+                        );
+                        vec![Err(err); batch.len()]
+                    }
+                    Err(err) => vec![Err(err.to_string()); batch.len()],
+                };
+            for ((row_id, _), outcome) in batch.iter().zip(outcomes) {
+                let row_id = *row_id;
+                self.record_embedding_outcome(table, row_id, outcome, now_ms)?;
+                processed += 1;
+            }
+        }
+
+        Ok(processed)
+    }
+
+    /// Stores an embedding computed outside the database (e.g. offline, or restored from an
+    /// export) for an existing row, marking its job `Ready` as if an embedder had produced it. The
+    /// first vector stored in a table without `EmbeddingSpec::dimensions` fixes its dimension, and
+    /// the vector is recorded under the spec's `model`. A later change to the row's embedded
+    /// fields queues the row to be embedded again.
+    pub fn put_embedding(&self, table: &str, row_id: u64, vector: Vec<f32>) -> Result<()> {
+        self.preflight_write_limits()?;
+        {
+            let inner = self.read_inner()?;
+            let table_state = inner
+                .state
+                .tables
+                .get(table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            if table_state.embedding_spec.is_none() {
+                return Err(anyhow!("table has no embedding spec"));
+            }
+            if load_row(table_state, row_id)?.is_none() {
+                return Err(anyhow!("row not found"));
+            }
+            if vector.is_empty() || !vector.iter().all(|value| value.is_finite()) {
+                return Err(anyhow!("vector must be non-empty with finite values"));
+            }
+            if let Some(dimensions) = table_state.expected_dimension() {
+                if vector.len() != dimensions {
+                    return Err(anyhow!(
+                        "vector has {} dimensions but the table expects {dimensions}",
+                        vector.len()
+                    ));
+                }
+            }
+        }
+        let embeddings = RowEmbeddings {
+            vector,
+            named: Vec::new(),
+        };
+        self.record_embedding_outcome(table, row_id, Ok(embeddings), now_epoch_ms())
+    }
+
+    /// Persists the result of embedding one pending row: the vector and `Ready` status on
+    /// success, or a retry (with backoff) or `Failed` status on error.
+    fn record_embedding_outcome(
+        &self,
+        table: &str,
+        row_id: u64,
+        outcome: std::result::Result<RowEmbeddings, String>,
+        now_ms: u64,
+    ) -> Result<()> {
+        let mut inner = self.write_inner()?;
+        let checked = match inner.state.tables.get(table) {
+            Some(table_state) => outcome.and_then(|embeddings| embeddings.check(table_state)),
+            None => outcome,
+        };
+        match checked {
+            Ok(RowEmbeddings { vector, named }) => {
+                let store_record = WalRecord::StoreEmbedding {
+                    table: table.to_string(),
+                    row_id,
+                    vector: vector.clone(),
+                    norm: None,
+                };
+                append_durable_wal(&mut inner, Some(table), &store_record)?;
+
+                if let Some(table_state) = inner.state.tables.get_mut(table) {
+                    table_state.store_embedding(row_id, vector, None);
+                }
+
+                for (name, vector) in named {
+                    let named_record = WalRecord::StoreNamedEmbedding {
+                        table: table.to_string(),
+                        row_id,
+                        name: name.clone(),
+                        vector: vector.clone(),
+                    };
+                    append_durable_wal(&mut inner, Some(table), &named_record)?;
+                    if let Some(table_state) = inner.state.tables.get_mut(table) {
+                        table_state.store_named_embedding(name, row_id, vector);
+                    }
+                }
+
+                let model = inner
+                    .state
+                    .tables
+                    .get(table)
+                    .and_then(|table_state| table_state.embedding_spec.as_ref()?.model.clone());
+                let status_record = WalRecord::UpdateEmbeddingStatus {
+                    table: table.to_string(),
+                    row_id,
+                    status: EmbeddingStatus::Ready,
+                    last_error: None,
+                    attempts: Some(0),
+                    next_retry_at_ms: Some(0),
+                    model: model.clone(),
+                };
+                append_durable_wal(&mut inner, Some(table), &status_record)?;
+
+                if let Some(table_state) = inner.state.tables.get_mut(table) {
+                    if let Some(meta) = table_state.embedding_meta.get_mut(&row_id) {
+                        meta.status = EmbeddingStatus::Ready;
+                        meta.last_error = None;
+                        meta.attempts = 0;
+                        meta.next_retry_at_ms = 0;
+                        meta.model = model;
+                        table_state.metrics.embeddings_processed_total += 1;
+                    }
+                }
+                inner.metrics.embeddings_processed_total += 1;
+            }
+            Err(err) => {
+                let table_state = inner.state.tables.get(table);
+                let retry = table_state
+                    .and_then(|table_state| table_state.embedding_spec.as_ref())
+                    .map(|spec| spec.retry)
+                    .unwrap_or_default();
+                let attempts = table_state
+                    .and_then(|table_state| table_state.embedding_meta.get(&row_id))
+                    .map_or(1, |meta| meta.attempts.saturating_add(1));
+                let (new_attempts, next_retry, new_status) = if attempts >= retry.max_attempts {
+                    (attempts, 0u64, EmbeddingStatus::Failed)
+                } else {
+                    let seed = row_id ^ now_ms.rotate_left(32);
+                    (
+                        attempts,
+                        now_ms.saturating_add(retry.backoff_ms(attempts, seed)),
+                        EmbeddingStatus::Pending,
+                    )
+                };
+                let status_record = WalRecord::UpdateEmbeddingStatus {
+                    table: table.to_string(),
+                    row_id,
+                    status: new_status,
+                    last_error: Some(err.to_string()),
+                    attempts: Some(new_attempts),
+                    next_retry_at_ms: Some(next_retry),
+                    model: None,
+                };
+                append_durable_wal(&mut inner, Some(table), &status_record)?;
+
+                if let Some(table_state) = inner.state.tables.get_mut(table) {
+                    if let Some(meta) = table_state.embedding_meta.get_mut(&row_id) {
+                        meta.status = new_status;
+                        meta.last_error = Some(err.to_string());
+                        meta.attempts = new_attempts;
+                        meta.next_retry_at_ms = next_retry;
+                        table_state.metrics.embeddings_failed_total += 1;
+                    }
+                }
+                inner.metrics.embeddings_failed_total += 1;
+            }
+        }
+        inner.commit()
+    }
+
+    pub fn search_knn(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+    ) -> Result<Vec<SearchHit>> {
+        self.search_knn_with_options(table, query, k, metric, &[], &SearchOptions::default())
+    }
+
+    pub fn search_knn_filtered(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        self.search_knn_with_options(table, query, k, metric, filters, &SearchOptions::default())
+    }
+
+    pub fn search_knn_with_options(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.read_inner()?;
+        self.search_knn_locked(&inner, table, query, k, metric.into(), filters, options)
+    }
+
+    /// `search_knn_with_options`, with each hit joined to its row under the same read lock, so
+    /// callers get the fields without a `get_row` per hit.
+    pub fn search_knn_with_rows(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHitWithRow>> {
+        let inner = self.read_inner()?;
+        let hits =
+            self.search_knn_locked(&inner, table, query, k, metric.into(), filters, options)?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let mut out = Vec::with_capacity(hits.len());
+        for hit in hits {
+            if let Some(row) = load_row(table_state, hit.row_id)? {
+                out.push(SearchHitWithRow {
+                    row_id: hit.row_id,
+                    distance: hit.distance,
+                    score: hit.score,
+                    fields: row.fields,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// Applies `options.offset` and `options.after` over `search_knn_top`. A cursor can't be
+    /// pushed down into every search path, so the best hits are fetched with a doubling limit
+    /// until `k` of them rank after it.
+    #[allow(clippy::too_many_arguments)]
+    fn search_knn_locked(
+        &self,
+        inner: &Inner,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: Option<DistanceMetric>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        if options.offset == 0 && options.after.is_none() {
+            return self.search_knn_top(inner, table, query, k, metric, filters, options);
+        }
+        let mut fetch = k.saturating_add(options.offset);
+        loop {
+            let hits = self.search_knn_top(inner, table, query, fetch, metric, filters, options)?;
+            let exhausted = hits.len() < fetch || fetch == usize::MAX;
+            let page: Vec<SearchHit> = hits
+                .into_iter()
+                .filter(|hit| options.after.is_none_or(|after| after.admits(hit)))
+                .skip(options.offset)
+                .take(k)
+                .collect();
+            if page.len() == k || exhausted {
+                return Ok(page);
+            }
+            fetch = fetch.saturating_mul(2);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search_knn_top(
+        &self,
+        inner: &Inner,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: Option<DistanceMetric>,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let metric = match (metric, table_state.custom_metric()) {
+            (Some(metric), _) => metric,
+            (None, Some(name)) => {
+                let distance_fn = self.distance_fns.get(name)?;
+                return search_custom_locked(
+                    table_state,
+                    query,
+                    k,
+                    name,
+                    distance_fn.as_ref(),
+                    filters,
+                    options,
+                );
+            }
+            (None, None) => table_state.default_metric(),
+        };
+        let score = |distance| vector::similarity(distance, metric);
+        if let Some(name) = options.vector.as_deref() {
+            check_metric_against_table(table_state, metric, options)?;
+            let mut hits = search_named_locked(
+                table_state,
+                name,
+                query,
+                k,
+                filters,
+                options,
+                |query, vector| vector::distance(query, vector, metric),
+            )?;
+            options.score_mode.apply(&mut hits, score);
+            return Ok(hits);
+        }
+
+        let cache_key = lock_cache(&inner.search_cache)
+            .enabled()
+            .then(|| SearchCacheKey::new(table, query, k, metric, filters, options));
+        if let Some(key) = &cache_key {
+            if let Some(mut hits) = lock_cache(&inner.search_cache).get(key) {
+                options.score_mode.apply(&mut hits, score);
+                return Ok(hits);
+            }
+        }
+
+        let rescore = self.config.rescore_oversample > 0
+            && table_state
+                .embedding_spec
+                .as_ref()
+                .is_some_and(EmbeddingSpec::is_quantized);
+        let mut hits = if rescore {
+            let fetch = k.saturating_mul(self.config.rescore_oversample);
+            let candidates = search_locked(table_state, query, fetch, metric, filters, options)?;
+            rescore_exact(&inner.raw_vectors, table, candidates, query, k, metric)?
+        } else {
+            search_locked(table_state, query, k, metric, filters, options)?
+        };
+        if let Some(key) = cache_key {
+            lock_cache(&inner.search_cache).insert(key, hits.clone());
+        }
+        options.score_mode.apply(&mut hits, score);
+        Ok(hits)
+    }
+
+    /// Attaches a sparse embedding to an existing row, replacing any previous one. Sparse vectors
+    /// are supplied by the caller and kept until the row is deleted.
+    pub fn put_sparse_vector(&self, table: &str, row_id: u64, vector: SparseVector) -> Result<()> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        if !row_exists(table_state, row_id)? {
+            return Err(anyhow!("row not found"));
+        }
+
+        let record = WalRecord::StoreSparseVector {
+            table: table.to_string(),
+            row_id,
+            vector: vector.clone(),
+        };
+        append_durable_wal(&mut inner, Some(table), &record)?;
+        if let Some(table_state) = inner.state.tables.get_mut(table) {
+            table_state.sparse_vectors.insert(row_id, vector);
+        }
+        inner.commit()
+    }
+
+    pub fn get_sparse_vector(&self, table: &str, row_id: u64) -> Result<Option<SparseVector>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        Ok(table_state.sparse_vectors.get(&row_id).cloned())
+    }
+
+    /// Top-k rows by sparse dot product. Hits carry the negated dot product as `distance`;
+    /// rows sharing no index with the query are not returned.
+    pub fn search_sparse(
+        &self,
+        table: &str,
+        query: &SparseVector,
+        k: usize,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        search_sparse_locked(table_state, query, k, filters)
+    }
+
+    /// Hybrid search fusing a dense kNN ranking and a sparse dot-product ranking with reciprocal
+    /// rank fusion. Each side contributes up to `4 * k` candidates before fusion.
+    pub fn search_hybrid_sparse(
+        &self,
+        table: &str,
+        dense_query: &[f32],
+        sparse_query: &SparseVector,
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let metric = table_state.resolve_metric(metric.into())?;
+        let fetch = k.saturating_mul(4);
+        let dense = search_locked(
+            table_state,
+            dense_query,
+            fetch,
+            metric,
+            filters,
+            &SearchOptions::default(),
+        )?;
+        let sparse = search_sparse_locked(table_state, sparse_query, fetch, filters)?;
+        Ok(fusion::reciprocal_rank_fusion(&[&dense, &sparse], k))
+    }
+
+    /// Top-k rows by BM25 score of `query_text` against the table's String columns. Hits carry
+    /// the negated score as `distance`. The keyword index is built in memory by the first keyword
+    /// or hybrid search after open, then kept up to date by writes.
+    pub fn search_keyword(
+        &self,
+        table: &str,
+        query_text: &str,
+        k: usize,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        search_keyword_locked(table_state, query_text, k, filters)
+    }
+
+    /// Hybrid search fusing a BM25 keyword ranking of `query_text` and a kNN ranking of
+    /// `query_vec` (with the table's default metric), so exact keyword matches surface even when
+    /// their embeddings are not the nearest. Hits carry the negated fused score as `distance`.
+    pub fn search_hybrid(
+        &self,
+        table: &str,
+        query_text: &str,
+        query_vec: &[f32],
+        k: usize,
+        fusion: Fusion,
+        filters: &[FilterCondition],
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.read_inner()?;
+        let fetch = k.saturating_mul(4);
+        let dense = self.search_knn_locked(
+            &inner,
+            table,
+            query_vec,
+            fetch,
+            None,
+            filters,
+            &SearchOptions::default(),
+        )?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let keyword = search_keyword_locked(table_state, query_text, fetch, filters)?;
+        let (keyword_weight, vector_weight) = fusion.weights();
+        Ok(fusion::weighted_rank_fusion(
+            &[(&keyword, keyword_weight), (&dense, vector_weight)],
+            k,
+        ))
+    }
+
+    /// Brute-force kNN for many queries at once, returning one hit list per query in input order.
+    /// The full query-by-vector distance matrix is computed in one pass, on the GPU when
+    /// `Config::scoring_backend` selects it. Results are not cached.
+    pub fn search_knn_batch(
+        &self,
+        table: &str,
+        queries: &[Vec<f32>],
+        k: usize,
+        metric: impl Into<Option<DistanceMetric>>,
+    ) -> Result<Vec<Vec<SearchHit>>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let metric = table_state.resolve_metric(metric.into())?;
+        for query in queries {
+            check_query_against_table(table_state, query, metric, &SearchOptions::default())?;
+        }
+        let Some(dim) = queries.first().map(Vec::len) else {
+            return Ok(Vec::new());
+        };
+        if queries.iter().any(|query| query.len() != dim) {
+            return Err(anyhow!("batch queries have mismatched dimensions"));
+        }
+
+        let mut row_ids = Vec::new();
+        let mut candidates = Vec::new();
+        for row_id in table_state.embeddings.keys() {
+            if let Some(vector) = table_state.ready_vector(*row_id) {
+                if vector.len() == dim {
+                    row_ids.push(*row_id);
+                    candidates.extend(vector);
+                }
+            }
+        }
+        drop(inner);
+        if row_ids.is_empty() {
+            return Ok(vec![Vec::new(); queries.len()]);
+        }
+
+        let flat: Vec<f32> = queries.iter().flatten().copied().collect();
+        let distances =
+            batch::distance_matrix(&flat, &candidates, dim, metric, self.config.scoring_backend);
+        Ok(distances
+            .chunks_exact(row_ids.len())
+            .map(|row| {
+                let mut top = TopK::new(k);
+                for (row_id, distance) in row_ids.iter().zip(row) {
+                    top.push(*row_id, *distance);
+                }
+                top.into_sorted().into_iter().map(SearchHit::from).collect()
+            })
+            .collect())
+    }
+
+    /// Searches with a metric referenced by name: `l2` or `cosine` for the built-ins, otherwise a
+    /// metric added with `register_metric`. Custom-metric results are not cached.
+    pub fn search_knn_named(
+        &self,
+        table: &str,
+        query: &[f32],
+        k: usize,
+        metric: &str,
+        filters: &[FilterCondition],
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>> {
+        if let Some(builtin) = metric::builtin_metric(metric) {
+            return self.search_knn_with_options(table, query, k, builtin, filters, options);
+        }
+        let distance_fn = self.distance_fns.get(metric)?;
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        search_custom_locked(
+            table_state,
+            query,
+            k,
+            metric,
+            distance_fn.as_ref(),
+            filters,
+            options,
+        )
+    }
+
+    /// Searches with the table's default metric: its custom metric when one is declared,
+    /// otherwise `TableState::default_metric`.
+    fn search_table_metric(
+        &self,
+        table_state: &TableState,
+        query: &[f32],
+        k: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let options = SearchOptions::default();
+        match table_state.custom_metric() {
+            Some(name) => {
+                let distance_fn = self.distance_fns.get(name)?;
+                search_custom_locked(
+                    table_state,
+                    query,
+                    k,
+                    name,
+                    distance_fn.as_ref(),
+                    &[],
+                    &options,
+                )
+            }
+            None => search_locked(
+                table_state,
+                query,
+                k,
+                table_state.default_metric(),
+                &[],
+                &options,
+            ),
+        }
+    }
+
+    /// More-like-this search: uses the `Ready` embedding of `row_id` as the query, scored with the
+    /// table's metric (cosine when none is declared).
+    pub fn search_similar(
+        &self,
+        table: &str,
+        row_id: u64,
+        k: usize,
+        exclude_self: bool,
+    ) -> Result<Vec<SearchHit>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let query = table_state
+            .ready_vector(row_id)
+            .ok_or_else(|| anyhow!("row {row_id} has no ready embedding"))?;
+        let fetch = if exclude_self { k.saturating_add(1) } else { k };
+        let mut hits = self.search_table_metric(table_state, &query, fetch)?;
+        if exclude_self {
+            hits.retain(|hit| hit.row_id != row_id);
+        }
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    /// Recommends rows from example rows using the average-vector strategy: the query is
+    /// `avg(positive) + (avg(positive) - avg(negative))`, or just `avg(positive)` without
+    /// negatives. Example rows are excluded from the results.
+    pub fn recommend(
+        &self,
+        table: &str,
+        positive: &[u64],
+        negative: &[u64],
+        k: usize,
+    ) -> Result<Vec<SearchHit>> {
+        if positive.is_empty() {
+            return Err(anyhow!("recommend requires at least one positive example"));
+        }
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+
+        let average = |ids: &[u64]| -> Result<Vec<f32>> {
+            let mut sum: Vec<f32> = Vec::new();
+            for row_id in ids {
+                let vector = table_state
+                    .ready_vector(*row_id)
+                    .ok_or_else(|| anyhow!("row {row_id} has no ready embedding"))?;
+                if sum.is_empty() {
+                    sum = vec![0.0; vector.len()];
+                } else if sum.len() != vector.len() {
+                    return Err(anyhow!("example embeddings have mismatched dimensions"));
+                }
+                for (acc, x) in sum.iter_mut().zip(vector) {
+                    *acc += x;
+                }
+            }
+            let count = ids.len() as f32;
+            Ok(sum.into_iter().map(|x| x / count).collect())
+        };
+
+        let mut query = average(positive)?;
+        if !negative.is_empty() {
+            let negative_avg = average(negative)?;
+            if negative_avg.len() != query.len() {
+                return Err(anyhow!("example embeddings have mismatched dimensions"));
+            }
+            for (q, n) in query.iter_mut().zip(negative_avg) {
+                *q += *q - n;
+            }
+        }
+
+        let examples: BTreeSet<u64> = positive.iter().chain(negative).copied().collect();
+        let mut hits =
+            self.search_table_metric(table_state, &query, k.saturating_add(examples.len()))?;
+        hits.retain(|hit| !examples.contains(&hit.row_id));
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    /// Clusters rows whose `Ready` embeddings are within `threshold` of each other under `metric`
+    /// (the table's metric when `None`), linking pairs transitively. Each row is searched for its
+    /// nearest neighbours rather than compared with every other row, so on a table with an HNSW
+    /// index the pass costs about one approximate search per row (and may miss pairs the index
+    /// misses); flat tables scan exactly. Rows without a near neighbour are left out.
+    pub fn find_duplicates(
+        &self,
+        table: &str,
+        threshold: f32,
+        metric: impl Into<Option<DistanceMetric>>,
+    ) -> Result<Vec<DuplicateCluster>> {
+        if !threshold.is_finite() {
+            return Err(anyhow!("threshold must be a finite number"));
+        }
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let metric = metric.into();
+        let custom = match (metric, table_state.custom_metric()) {
+            (None, Some(name)) => Some((name, self.distance_fns.get(name)?)),
+            _ => None,
+        };
+        let metric = metric.unwrap_or_else(|| table_state.default_metric());
+        let options = SearchOptions::default();
+        let row_ids: Vec<u64> = table_state
+            .embeddings
+            .keys()
+            .copied()
+            .filter(|row_id| table_state.searchable(*row_id))
+            .collect();
+
+        let mut clusters = Clusters::default();
+        for &row_id in &row_ids {
+            let Some(query) = table_state.ready_vector(row_id) else {
+                continue;
+            };
+            let mut k = DUPLICATE_NEIGHBORS.min(row_ids.len());
+            loop {
+                let hits = match &custom {
+                    Some((name, distance_fn)) => search_custom_locked(
+                        table_state,
+                        &query,
+                        k,
+                        name,
+                        distance_fn.as_ref(),
+                        &[],
+                        &options,
+                    )?,
+                    None => search_locked(table_state, &query, k, metric, &[], &options)?,
+                };
+                let within = hits
+                    .iter()
+                    .take_while(|hit| hit.distance <= threshold)
+                    .count();
+                if within == hits.len() && hits.len() == k && k < row_ids.len() {
+                    k = k.saturating_mul(2).min(row_ids.len());
+                    continue;
+                }
+                for hit in &hits[..within] {
+                    if hit.row_id != row_id {
+                        clusters.link(row_id, hit.row_id, hit.distance);
+                    }
+                }
+                break;
+            }
+        }
+        Ok(clusters.into_clusters())
+    }
+
+    /// Counts the table's live rows matching `filters`. Without filters this is the row count
+    /// `table_stats` reports, kept up to date by writes instead of recounted.
+    pub fn count_rows(&self, table: &str, filters: &[FilterCondition]) -> Result<u64> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        validate_filters(&table_state.schema, filters)?;
+        if filters.is_empty() {
+            return table_state.row_count();
+        }
+        let rows = scan_visible_rows(table_state)?;
+        Ok(rows
+            .values()
+            .filter(|row| row_matches_filters(&table_state.schema, row, filters))
+            .count() as u64)
+    }
+
+    /// Computes `aggs` over rows matching `filters`, grouped by the `group_by` columns (a single
+    /// group when empty). Groups are returned in a deterministic order.
+    pub fn aggregate(
+        &self,
+        table: &str,
+        filters: &[FilterCondition],
+        group_by: &[String],
+        aggs: &[Aggregation],
+    ) -> Result<Vec<AggregateRow>> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        validate_filters(&table_state.schema, filters)?;
+        aggregate::validate(&table_state.schema, group_by, aggs)?;
+
+        let mut aggregator = aggregate::Aggregator::new(group_by, aggs);
+        for row in scan_visible_rows(table_state)?.values() {
+            if row_matches_filters(&table_state.schema, row, filters) {
+                aggregator.add(row)?;
+            }
+        }
+        Ok(aggregator.finish())
+    }
+
+    /// Deletes the table's rows whose TTL has passed, or whose expiry column holds a time in the
+    /// past, and returns how many. `flush_table` and `compact_table` run this first.
+    pub fn expire_rows(&self, table: &str) -> Result<usize> {
+        let mut inner = self.write_inner()?;
+        let expired = expire_rows_locked(&mut inner, table, now_epoch_ms())?;
+        let count = expired.len();
+        self.commit_changes(inner, deleted_changes(table, expired))?;
+        Ok(count)
+    }
+
+    /// Releases the write lock and waits until the write is durable, then publishes `changes` to
+    /// the change feed and runs the triggers on them. The feed numbers them with the write's LSN
+    /// while the lock is still held, so its order is the order writes committed in.
+    fn commit_changes(&self, inner: WriteGuard<'_>, changes: Vec<RowChange>) -> Result<()> {
+        let staged = self.changes.stage(&changes, inner.lsn);
+        inner.commit()?;
+        staged.publish();
+        for change in changes {
+            self.triggers.fire(change);
+        }
+        Ok(())
+    }
+
+    /// Expires rows, writes the memtable to a level-0 SST, then compacts the table if that leaves
+    /// level 0 at `CompactionPolicy::l0_trigger_files` files or `l0_trigger_bytes` bytes.
+    pub fn flush_table(&self, table: &str) -> Result<()> {
+        let mut guard = self.write_inner()?;
+        let expired = expire_rows_locked(&mut guard, table, now_epoch_ms())?;
+        // The expiry is logged by now, so its deletes are reported even if the flush fails.
+        let flushed = flush_table_locked(&self.config, &mut guard, table);
+        self.commit_changes(guard, deleted_changes(table, expired))?;
+        flushed
+    }
+
+    /// Expires rows and purges hidden rows past their `SoftDelete::retention_secs`, then merges
+    /// every level-0 SST into level 1 and moves the oldest files of any level over its
+    /// `CompactionPolicy` budget into the next level. Vector segments are merged as well.
+    pub fn compact_table(&self, table: &str) -> Result<CompactionStats> {
+        let mut inner = self.write_inner()?;
+        let now_ms = now_epoch_ms();
+        let expired = expire_rows_locked(&mut inner, table, now_ms)?;
+        let retention_ms = inner
+            .state
+            .tables
+            .get(table)
+            .and_then(|table_state| table_state.schema.soft_delete.as_ref()?.retention_secs)
+            .map(|seconds| seconds.saturating_mul(1000));
+        // As in `flush_table`, the expiry's deletes are reported even if the rest fails.
+        let compacted = retention_ms
+            .map_or(Ok(0), |retention_ms| {
+                purge_hidden_rows_locked(&mut inner, table, now_ms.saturating_sub(retention_ms))
+            })
+            .and_then(|_| compact_table_locked(&self.config, &mut inner, table));
+        self.commit_changes(inner, deleted_changes(table, expired))?;
+        compacted
+    }
+
+    /// Compacts the table only if its level 0 reached `CompactionPolicy::l0_trigger_files` files
+    /// or `l0_trigger_bytes` bytes, as the background compaction worker does on every pass.
+    /// Returns `None` when it didn't.
+    pub fn compact_table_if_due(&self, table: &str) -> Result<Option<CompactionStats>> {
+        let mut inner = self.write_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        if !self
+            .config
+            .compaction_policy(table)
+            .l0_due(&table_state.sst_files)?
+        {
+            return Ok(None);
+        }
+        let stats = compact_table_locked(&self.config, &mut inner, table)?;
+        inner.commit()?;
+        Ok(Some(stats))
+    }
+
+    /// Reads every SST file of the table in full and reports the ones that can't be read. Any
+    /// read, scan, or compaction that reaches such a file fails until `repair_table` sets it
+    /// aside.
+    pub fn verify_table(&self, table: &str) -> Result<TableVerification> {
+        let inner = self.read_inner()?;
+        let table_state = inner
+            .state
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let files = table_state
+            .sst_files
+            .iter()
+            .map(verify::check_file)
+            .collect();
+        Ok(TableVerification::new(table, files))
+    }
+
+    /// Runs `verify_table`, then moves the unreadable files into the table's `quarantine/`
+    /// directory so reads go on without them. On its own that loses their rows, or brings back
+    /// the older versions deeper levels hold. When the WAL holds the table's full history (no
+    /// checkpoint has run yet, or every one ran with `Config::wal_archive`), the table's rows are
+    /// rebuilt from it instead, into one new SST file that replaces all the others.
+    pub fn repair_table(&self, table: &str) -> Result<TableVerification> {
+        let mut inner = self.write_inner()?;
+        let report = repair_table_locked(&self.config, &mut inner, table)?;
+        inner.commit()?;
+        Ok(report)
+    }
+
+    /// LSN of the most recent durable write; each WAL record advances it by one.
+    pub fn current_lsn(&self) -> Result<u64> {
+        Ok(self.read_inner()?.lsn)
+    }
+
+    /// The audit log's entries from `since_ms` (Unix milliseconds) on, oldest first. Empty when
+    /// `Config::audit` was never set; rotated files deleted by `AuditConfig::max_files` are gone.
+    pub fn audit_log(&self, since_ms: u64) -> Result<Vec<AuditEntry>> {
+        // The lock keeps writers from appending mid-read.
+        let _inner = self.read_inner()?;
+        audit::read_since(&self.config.data_dir, since_ms)
+    }
+
+    /// Opens a read-only view of the database as it was right after the write at `lsn`
+    /// (`0` is the empty database). Needs the full WAL history: either no checkpoint has run yet,
+    /// or every checkpoint ran with `Config::wal_archive` enabled.
+    pub fn read_at_lsn(&self, lsn: u64) -> Result<HistoricalView> {
+        // Hold the lock so a concurrent checkpoint can't rotate segments mid-replay.
+        let _inner = self.read_inner()?;
+        let state = history::replay_to_lsn(&self.config.data_dir, lsn)?;
+        Ok(HistoricalView::new(lsn, state))
+    }
+
+    /// Opens a read-only view of the database as it is now, unaffected by later writes, so a long
+    /// scan or export sees one consistent state instead of interleaving with concurrent inserts
+    /// and deletes. Memtables and embeddings are copied; SST files are shared through hard links
+    /// under `read_snapshots/` (copies where linking fails), which keep them readable after a
+    /// compaction deletes them and are removed when the view is dropped. A read-only database
+    /// links nothing, so its views rely on no other process compacting the directory.
+    pub fn snapshot(&self) -> Result<HistoricalView> {
+        let inner = self.read_inner()?;
+        let pinned = if self.config.read_only {
+            None
+        } else {
+            Some(history::PinnedFiles::create(&self.config.data_dir)?)
+        };
+        let mut state = DbState {
+            tables: HashMap::new(),
+        };
+        for (idx, (name, table_state)) in inner.state.tables.iter().enumerate() {
+            let sst_files = match &pinned {
+                Some(pinned) => table_state
+                    .sst_files
+                    .iter()
+                    .map(|file| pinned.pin(idx, file))
+                    .collect::<Result<_>>()?,
+                None => table_state.sst_files.clone(),
+            };
+            state
+                .tables
+                .insert(name.clone(), table_state.snapshot_copy(sst_files));
+        }
+        Ok(HistoricalView::new(inner.lsn, state).with_pinned(pinned))
+    }
+
+    /// The WAL records written after `lsn`, oldest first, for `apply_wal_records` on a replica or
+    /// for an incremental backup. Like `read_at_lsn`, this needs the WAL since `lsn`: it is kept
+    /// from the last checkpoint on, and across checkpoints with `Config::wal_archive`. Under a
+    /// relaxed `Durability` the records may include writes not yet synced.
+    pub fn wal_since(&self, lsn: u64) -> Result<Vec<WalEntry>> {
+        // Hold the lock so a concurrent checkpoint can't rotate segments mid-read.
+        let _inner = self.read_inner()?;
+        history::records_since(&self.config.data_dir, lsn)
+    }
+
+    /// Writes records from another database's `wal_since` to this one, keeping its `current_lsn`
+    /// equal to the source's, and returns the new LSN. Entries at or below `current_lsn` are
+    /// skipped so a failed shipment can be sent again; the rest must follow on from it without
+    /// gaps and must not end inside a batch. A database seeded from `export_snapshot` starts at
+    /// the source's LSN. It must take no writes of its own (including background embedding), or
+    /// its LSNs stop matching.
+    pub fn apply_wal_records(&self, entries: &[WalEntry]) -> Result<u64> {
+        self.preflight_write_limits()?;
+        let mut inner = self.write_inner()?;
+        let entries: Vec<&WalEntry> = entries
+            .iter()
+            .filter(|entry| entry.lsn > inner.lsn)
+            .collect();
+        let mut open_batch = false;
+        for (expected, entry) in (inner.lsn + 1..).zip(&entries) {
+            if entry.lsn != expected {
+                return Err(anyhow!(
+                    "WAL gap: expected LSN {expected}, got {}",
+                    entry.lsn
+                ));
+            }
+            match &entry.record {
+                WalRecord::BeginBatch => open_batch = true,
+                WalRecord::CommitBatch => open_batch = false,
+                WalRecord::Checkpoint { .. } | WalRecord::VectorSegments { .. } => {
+                    return Err(anyhow!(
+                        "LSN {}: {} records are not shipped",
+                        entry.lsn,
+                        entry.record.name()
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if open_batch {
+            return Err(anyhow!("WAL records end inside an uncommitted batch"));
+        }
+
+        // Records are appended in runs sharing one sync. A run ends before each record whose
+        // handling depends on the state the records before it leave behind.
+        let mut run = Vec::new();
+        for entry in entries {
+            let mut record = entry.record.clone();
+            if matches!(
+                record,
+                WalRecord::SetEmbeddingSpec { .. }
+                    | WalRecord::AlterTable { .. }
+                    | WalRecord::RenameTable { .. }
+            ) {
+                apply_shipped_records(&mut inner, &mut run)?;
+            }
+            match &mut record {
+                WalRecord::CreateTable { name, .. } => {
+                    sst::ensure_dir(&sst::table_dir(&self.config.data_dir, name))?;
+                }
+                // SST sequence numbers are local: the rewrite covers this database's older files.
+                WalRecord::AlterTable {
+                    table, before_seq, ..
+                } => {
+                    if let Some(table_state) = inner.state.tables.get(table.as_str()) {
+                        *before_seq = table_state.next_sst_seq;
+                    }
+                }
+                WalRecord::RenameTable { table, new_name } => {
+                    rename_table_locked(&self.config, &mut inner, table, new_name)?;
+                    continue;
+                }
+                _ => {}
+            }
+            run.push(record);
+        }
+        apply_shipped_records(&mut inner, &mut run)?;
+        let lsn = inner.lsn;
+        inner.commit()?;
+        Ok(lsn)
+    }
+
+    pub fn checkpoint(&self) -> Result<CheckpointStats> {
+        self.checkpoint_internal(false)
+    }
+
+    /// Checkpoints and copies the data directory into `dest_dir`, which must be missing or empty.
+    /// Writers wait for the copy; the snapshot only appears at `dest_dir` once it is complete.
+    pub fn export_snapshot(&self, dest_dir: impl AsRef<Path>) -> Result<SnapshotStats> {
+        let dest_dir = dest_dir.as_ref();
+        ensure_empty_or_missing_dir(dest_dir)?;
+
+        // Hold the DB lock for the entire operation so the snapshot is a consistent copy.
+        let mut inner = self.write_inner()?;
+        let _ = checkpoint_locked(&self.config, &mut inner, false)?;
+        let (files_copied, bytes_copied) =
+            copy_dir_atomically(&self.config.data_dir, dest_dir, should_skip_snapshot_entry)?;
+
+        Ok(SnapshotStats {
+            files_copied,
+            bytes_copied,
+        })
+    }
+
+    /// Reads the WAL frame by frame without opening the database, so it works on a directory
+    /// that will not open or that another process holds. `path` is a data directory, whose WAL
+    /// files are inspected in replay order, or a single WAL file.
+    pub fn inspect_wal(path: impl AsRef<Path>) -> Result<Vec<WalInspection>> {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            wal::replay_files(path)?
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.iter().map(|file| wal::inspect(file)).collect()
+    }
+
+    /// Copies a snapshot made by `export_snapshot` into `data_dir` (missing or empty), which can
+    /// then be opened like any data directory.
+    pub fn restore_snapshot(
+        snapshot_dir: impl AsRef<Path>,
+        data_dir: impl AsRef<Path>,
+    ) -> Result<SnapshotStats> {
+        let snapshot_dir = snapshot_dir.as_ref();
+        let data_dir = data_dir.as_ref();
+
+        if !snapshot_dir.exists() {
+            return Err(anyhow!(
+                "snapshot dir does not exist: {}",
+                snapshot_dir.display()
+            ));
+        }
+        ensure_empty_or_missing_dir(data_dir)?;
+
+        let (files_copied, bytes_copied) =
+            copy_dir_atomically(snapshot_dir, data_dir, should_skip_snapshot_entry)?;
+        Ok(SnapshotStats {
+            files_copied,
+            bytes_copied,
+        })
+    }
+}
+
+pub trait Embedder: Send + Sync {
+    fn embed(&self, input: &str) -> Result<Vec<f32>>;
+
+    /// Embeds several inputs in one call, returning one vector per input in order. Job
+    /// processing groups up to `batch_size` pending rows per call; an error fails the whole
+    /// group.
+    fn embed_batch(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        inputs.iter().map(|input| self.embed(input)).collect()
+    }
+
+    /// Largest group of inputs passed to `embed_batch`.
+    fn batch_size(&self) -> usize {
+        1
+    }
+}
+
+fn lock_cache(cache: &Mutex<SearchCache>) -> MutexGuard<'_, SearchCache> {
+    // The cache holds no invariants a panicking search could break.
+    cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn append_durable_wal(inner: &mut Inner, table: Option<&str>, record: &WalRecord) -> Result<()> {
+    append_durable_wal_batch(inner, table, std::slice::from_ref(record))
+}
+
+/// Appends `records` and makes them durable with one sync, or leaves them for a later sync under
+/// a relaxed `Durability`.
+fn append_durable_wal_batch(
+    inner: &mut Inner,
+    table: Option<&str>,
+    records: &[WalRecord],
+) -> Result<()> {
+    for record in records {
+        inner.wal.append(record, false)?;
+    }
+    match (inner.durability, &inner.group_commit) {
+        // Synced once the write guard is released; see `WriteGuard`.
+        (Durability::Always, Some(group)) => inner.unsynced_ticket = Some(group.register()),
+        (Durability::Always, None) => {
+            inner.wal.sync()?;
+            inner.metrics.wal_sync_ops += 1;
+        }
+        (Durability::Interval(_) | Durability::OnCheckpointOnly, _) => {
+            inner.unsynced_records += records.len() as u64;
+        }
+    }
+    // Recorded only once the records are in the WAL, so a failed append leaves no entry for a
+    // write that never happened. The write stands by now, so an audit failure is only logged.
+    if let Some(audit) = inner.audit.as_mut() {
+        let sync = inner.durability == Durability::Always;
+        if let Err(err) = audit.record(records, inner.lsn + 1, now_epoch_ms(), sync) {
+            tracing::error!("failed to record audit entries: {err:#}");
+        }
+    }
+    for record in records {
+        // The record is already durable, so a failure here must not abort the write; searches
+        // fall back to approximate distances for vectors missing from the raw store.
+        if let Err(err) = inner.raw_vectors.observe(&inner.state, record) {
+            tracing::warn!("failed to update raw vector store: {err:#}");
+        }
+    }
+    let appended = records.len() as u64;
+    inner.lsn += appended;
+    inner.metrics.wal_durable_appends += appended;
+    if let Some(table) = table {
+        lock_cache(&inner.search_cache).invalidate_table(table);
+        if let Some(table_state) = inner.state.tables.get_mut(table) {
+            table_state.metrics.wal_durable_appends += appended;
+        }
+    }
+    Ok(())
+}
+
+/// Appends `run` (emptying it) and applies it, for `EmbedDb::apply_wal_records`.
+fn apply_shipped_records(inner: &mut Inner, run: &mut Vec<WalRecord>) -> Result<()> {
+    if run.is_empty() {
+        return Ok(());
+    }
+    append_durable_wal_batch(inner, None, run)?;
+    for record in run.drain(..) {
+        if let Some(table) = record.table() {
+            lock_cache(&inner.search_cache).invalidate_table(table);
+        }
+        apply_record(&mut inner.state, record)?;
+    }
+    Ok(())
+}
+
+/// Logs and carries out the rename of `table` to `new_name`, whose directory must not exist.
+fn rename_table_locked(
+    config: &Config,
+    inner: &mut Inner,
+    table: &str,
+    new_name: &str,
+) -> Result<()> {
+    let record = WalRecord::RenameTable {
+        table: table.to_string(),
+        new_name: new_name.to_string(),
+    };
+    append_durable_wal(inner, Some(table), &record)?;
+    // The directory must not move before the record is on disk, whatever the durability
+    // policy; replay finishes or rewinds the move from there.
+    if inner.unsynced_ticket.is_some() || inner.unsynced_records > 0 {
+        inner.wal.sync()?;
+        inner.metrics.wal_sync_ops += 1;
+        inner.unsynced_records = 0;
+    }
+    move_table_dir(&config.data_dir, table, new_name)?;
+    apply_record(&mut inner.state, record)?;
+    let dir = sst::table_dir(&config.data_dir, new_name);
+    if let Some(table_state) = inner.state.tables.get_mut(new_name) {
+        table_state.sst_files = sst::list_sst_files(&dir)?;
+        table_state.vector_segments = vecseg::list_segments(&dir)?;
+    }
+    checkpoint_locked(config, inner, false)?;
+    Ok(())
+}
+
+fn checkpoint_locked(config: &Config, inner: &mut Inner, auto: bool) -> Result<CheckpointStats> {
+    let checkpoint_started = Instant::now();
+    let data_dir = config.data_dir.as_path();
+    let wal_path = data_dir.join("wal.log");
+    let wal_prev_path = data_dir.join("wal.prev");
+    let wal_new_path = data_dir.join("wal.log.new");
+    let wal_dummy_path = data_dir.join("wal.checkpoint.tmp");
+
+    let wal_bytes_before = wal_bytes_total(data_dir);
+
+    // Flush all tables so row data is durably in SSTs and the checkpoint WAL can be compact.
+    let table_names: Vec<String> = inner.state.tables.keys().cloned().collect();
+    for table in table_names {
+        let elapsed_ms = {
+            let table_state = inner
+                .state
+                .tables
+                .get_mut(&table)
+                .ok_or_else(|| anyhow!("table not found"))?;
+            let started = Instant::now();
+            let flushed = flush_table_state(
+                data_dir,
+                &table,
+                table_state,
+                &inner.raw_vectors,
+                config.row_codec,
+            )?;
+            compact_vector_segments(data_dir, &table, table_state)?;
+            // Written before the WAL is swapped for the snapshot, which replays as of `lsn`.
+            persist_index(data_dir, &table, table_state, inner.lsn)?;
+            if flushed {
+                let elapsed_ms = started.elapsed().as_millis() as u64;
+                table_state.metrics.flush_count += 1;
+                table_state.metrics.flush_total_ms = table_state
+                    .metrics
+                    .flush_total_ms
+                    .saturating_add(elapsed_ms);
+                Some(elapsed_ms)
+            } else {
+                None
+            }
+        };
+        if let Some(elapsed_ms) = elapsed_ms {
+            inner.metrics.flush_count_total += 1;
+            inner.metrics.flush_total_ms = inner.metrics.flush_total_ms.saturating_add(elapsed_ms);
+        }
+    }
+
+    let mut records: Vec<WalRecord> = Vec::new();
+    for (name, table_state) in inner.state.tables.iter() {
+        records.push(WalRecord::CreateTable {
+            name: name.clone(),
+            schema: table_state.schema.clone(),
+            embedding_spec: table_state.embedding_spec.clone(),
+        });
+        records.push(WalRecord::SetNextRowId {
+            table: name.clone(),
+            next_row_id: table_state.next_row_id,
+        });
+        if !table_state.schema_changes.is_empty() {
+            records.push(WalRecord::SchemaChanges {
+                table: name.clone(),
+                changes: table_state.schema_changes.clone(),
+            });
+        }
+
+        for (row_id, meta) in &table_state.embedding_meta {
+            records.push(WalRecord::EnqueueEmbedding {
+                table: name.clone(),
+                row_id: *row_id,
+                content_hash: meta.content_hash.clone(),
+            });
+            records.push(WalRecord::UpdateEmbeddingStatus {
+                table: name.clone(),
+                row_id: *row_id,
+                status: meta.status,
+                last_error: meta.last_error.clone(),
+                attempts: Some(meta.attempts),
+                next_retry_at_ms: Some(meta.next_retry_at_ms),
+                model: meta.model.clone(),
+            });
+        }
+
+        // The flush above wrote every embedding to the table's vector segments.
+        if !table_state.vector_segments.is_empty() {
+            records.push(WalRecord::VectorSegments {
+                table: name.clone(),
+            });
+        }
+
+        for (row_id, vector) in &table_state.sparse_vectors {
+            records.push(WalRecord::StoreSparseVector {
+                table: name.clone(),
+                row_id: *row_id,
+                vector: vector.clone(),
+            });
+        }
+
+        for (row_id, expires_at_ms) in &table_state.expirations {
+            records.push(WalRecord::SetRowExpiry {
+                table: name.clone(),
+                row_id: *row_id,
+                expires_at_ms: *expires_at_ms,
+            });
+        }
+
+        for (row_id, hidden_at_ms) in &table_state.hidden {
+            records.push(WalRecord::HideRow {
+                table: name.clone(),
+                row_id: *row_id,
+                hidden_at_ms: *hidden_at_ms,
+            });
+        }
+
+        for (vector_name, vectors) in &table_state.named_embeddings {
+            for (row_id, vector) in vectors {
+                records.push(WalRecord::StoreNamedEmbedding {
+                    table: name.clone(),
+                    row_id: *row_id,
+                    name: vector_name.clone(),
+                    vector: vector.clone(),
+                });
+            }
+        }
+    }
+    records.push(WalRecord::Checkpoint { lsn: inner.lsn });
+
+    // Write the new WAL snapshot.
+    {
+        let mut new_wal = Wal::create_new(wal_new_path.clone(), config.wal_encoding)?
+            .with_compression(config.wal_compression);
+        for record in &records {
+            new_wal.append(record, false)?;
+        }
+        new_wal.sync()?;
+        inner.metrics.wal_sync_ops += 1;
+    }
+
+    settle_group_commit(inner)?;
+    // Ensure `wal.log` is closed during rotation (important for Windows semantics).
+    inner.wal = Wal::create_new(wal_dummy_path.clone(), WalEncoding::Json)?;
+
+    // Rotate with a `wal.prev` fallback to tolerate crashes between renames.
+    if wal_prev_path.exists() {
+        let _ = fs::remove_file(&wal_prev_path);
+    }
+    if wal_path.exists() {
+        fault::rename(&wal_path, &wal_prev_path)?;
+    }
+    fault::rename(&wal_new_path, &wal_path)?;
+
+    let wal_bytes_after = fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
+
+    inner.wal = Wal::open(wal_path, config.wal_encoding)?.with_compression(config.wal_compression);
+    if let Some(group) = &inner.group_commit {
+        group.attach(&inner.wal)?;
+    }
+
+    let _ = fs::remove_file(&wal_dummy_path);
+    // The new snapshot supersedes every segment sealed since the previous checkpoint.
+    retire_sealed_segments(config, &wal::list_segments(&wal::sealed_dir(data_dir))?)?;
+    let archive_path = history::archive_segment_path(data_dir, inner.lsn);
+    // A segment ending at an already-archived LSN holds no new records.
+    if config.wal_archive && wal_prev_path.exists() && !archive_path.exists() {
+        fs::create_dir_all(history::archive_dir(data_dir))?;
+        fault::rename(&wal_prev_path, &archive_path)?;
+    } else {
+        let _ = fs::remove_file(&wal_prev_path);
+    }
+
+    let checkpoint_elapsed_ms = checkpoint_started.elapsed().as_millis() as u64;
+    inner.metrics.checkpoints += 1;
+    if auto {
+        inner.metrics.auto_checkpoints += 1;
+    }
+    inner.metrics.checkpoint_total_ms = inner
+        .metrics
+        .checkpoint_total_ms
+        .saturating_add(checkpoint_elapsed_ms);
+
+    Ok(CheckpointStats {
+        wal_bytes_before,
+        wal_bytes_after,
+    })
+}
+
+/// Records of the current WAL generation: the sealed segments followed by `wal.log`. A generation
+/// starts with a checkpoint snapshot, so sealed segments older than the newest snapshot are left
+/// over from an interrupted checkpoint; they are retired instead of replayed.
+fn replay_wal_generation(config: &Config, live: &Wal) -> Result<Vec<WalRecord>> {
+    let sealed = wal::list_segments(&wal::sealed_dir(&config.data_dir))?;
+    let mut segments = Vec::with_capacity(sealed.len() + 1);
+    for path in &sealed {
+        segments.push(Wal::replay_path(path)?);
+    }
+    segments.push(live.replay()?);
+
+    let start = segments
+        .iter()
+        .rposition(|records| {
+            records
+                .iter()
+                .any(|record| matches!(record, WalRecord::Checkpoint { .. }))
+        })
+        .unwrap_or(0);
+    if !config.read_only {
+        retire_sealed_segments(config, &sealed[..start])?;
+    }
+    Ok(segments.into_iter().skip(start).flatten().collect())
+}
+
+/// Moves a table's directory from `from`'s name to `to`'s, unless it has already moved or `to` is
+/// taken.
+fn move_table_dir(data_dir: &Path, from: &str, to: &str) -> Result<()> {
+    let from = sst::table_dir(data_dir, from);
+    let to = sst::table_dir(data_dir, to);
+    if from.exists() && !to.exists() {
+        fault::rename(&from, &to)?;
+    }
+    Ok(())
+}
+
+/// Moves the directories of tables renamed by `records` back to their old names, so the records
+/// before each rename find the table's files where they were when it was logged. Replay redoes
+/// the moves in order.
+fn rewind_table_renames(config: &Config, records: &[WalRecord]) -> Result<()> {
+    for record in records.iter().rev() {
+        let WalRecord::RenameTable { table, new_name } = record else {
+            continue;
+        };
+        if config.read_only {
+            return Err(anyhow!(
+                "renaming table '{table}' to '{new_name}' was interrupted; open the database for writing once to finish it"
+            ));
+        }
+        move_table_dir(&config.data_dir, new_name, table)?;
+    }
+    Ok(())
+}
+
+/// Seals the current `wal.log` as a segment ending at the current LSN and starts a fresh one.
+fn rotate_wal_segment(config: &Config, inner: &mut Inner) -> Result<()> {
+    let data_dir = config.data_dir.as_path();
+    let wal_path = data_dir.join("wal.log");
+    let wal_dummy_path = data_dir.join("wal.checkpoint.tmp");
+    let sealed_path = wal::sealed_segment_path(data_dir, inner.lsn);
+    if sealed_path.exists() {
+        // Nothing was appended since the last rotation.
+        return Ok(());
+    }
+
+    fs::create_dir_all(wal::sealed_dir(data_dir))?;
+    settle_group_commit(inner)?;
+    // Close `wal.log` before renaming it (important for Windows semantics). A crash before the
+    // new file is opened leaves no `wal.log`, which open recreates empty.
+    inner.wal = Wal::create_new(wal_dummy_path.clone(), WalEncoding::Json)?;
+    fault::rename(&wal_path, &sealed_path)?;
+    inner.wal = Wal::open(wal_path, config.wal_encoding)?.with_compression(config.wal_compression);
+    if let Some(group) = &inner.group_commit {
+        group.attach(&inner.wal)?;
+    }
+    let _ = fs::remove_file(&wal_dummy_path);
+    inner.metrics.wal_rotations += 1;
+    Ok(())
+}
+
+/// Syncs records left unsynced by a relaxed `Durability`.
+fn sync_pending_wal(inner: &mut Inner) -> Result<()> {
+    if inner.unsynced_records > 0 {
+        inner.wal.sync()?;
+        inner.metrics.wal_sync_ops += 1;
+        inner.unsynced_records = 0;
+    }
+    Ok(())
+}
+
+/// Syncs the live WAL before it is replaced, so neither group commit waiters nor the
+/// `Durability::Interval` thread need to sync the old file.
+fn settle_group_commit(inner: &mut Inner) -> Result<()> {
+    sync_pending_wal(inner)?;
+    if let Some(group) = &inner.group_commit {
+        inner.wal.sync()?;
+        inner.metrics.wal_sync_ops += 1;
+        group.detach();
+    }
+    Ok(())
+}
+
+/// Removes sealed segments, or moves them under `wal_archive/` when `Config::wal_archive` is set.
+fn retire_sealed_segments(config: &Config, segments: &[PathBuf]) -> Result<()> {
+    for path in segments {
+        let archived = match path.file_name() {
+            Some(name) if config.wal_archive => history::archive_dir(&config.data_dir).join(name),
+            _ => {
+                fs::remove_file(path)?;
+                continue;
+            }
+        };
+        if archived.exists() {
+            fs::remove_file(path)?;
+        } else {
+            fs::create_dir_all(history::archive_dir(&config.data_dir))?;
+            fault::rename(path, &archived)?;
+        }
+    }
+    Ok(())
+}
+
+/// Bytes in `wal.log` plus any sealed segments.
+fn wal_bytes_total(data_dir: &Path) -> u64 {
+    let sealed = wal::list_segments(&wal::sealed_dir(data_dir)).unwrap_or_default();
+    std::iter::once(data_dir.join("wal.log"))
+        .chain(sealed)
+        .map(|path| fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+        .sum()
+}
+
+fn ensure_empty_or_missing_dir(path: &Path) -> Result<()> {
+    if path.exists() {
+        if !path.is_dir() {
+            return Err(anyhow!(
+                "path exists and is not a directory: {}",
+                path.display()
+            ));
+        }
+        if fs::read_dir(path)?.next().is_some() {
+            return Err(anyhow!("directory must be empty: {}", path.display()));
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+fn should_skip_snapshot_entry(path: &Path) -> bool {
+    match path.file_name().and_then(|s| s.to_str()) {
+        // Transient/lock files should not be snapshotted.
+        Some(
+            "embeddb.lock" | "wal.prev" | "wal.log.new" | "wal.checkpoint.tmp" | "read_snapshots",
+        ) => true,
+        _ => false,
+    }
+}
+
+/// Copies `src` into a staging directory next to `dst` and renames it into place, so `dst` is
+/// either a complete copy or left untouched. `dst` must be missing or empty.
+fn copy_dir_atomically(
+    src: &Path,
+    dst: &Path,
+    should_skip: fn(&Path) -> bool,
+) -> Result<(u64, u64)> {
+    let name = dst
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid destination dir: {}", dst.display()))?;
+    let staging = dst.with_file_name(format!("{}.partial", name.to_string_lossy()));
+    if staging.exists() {
+        // Left behind by an interrupted copy.
+        fs::remove_dir_all(&staging)?;
+    }
+
+    let result = copy_dir_recursive_filtered(src, &staging, should_skip).and_then(|copied| {
+        if dst.exists() {
+            fs::remove_dir(dst)?;
+        }
+        fs::rename(&staging, dst)?;
+        Ok(copied)
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    result
+}
+
+fn copy_dir_recursive_filtered(
+    src: &Path,
+    dst: &Path,
+    should_skip: fn(&Path) -> bool,
+) -> Result<(u64, u64)> {
+    fs::create_dir_all(dst)?;
+
+    let mut files_copied = 0u64;
+    let mut bytes_copied = 0u64;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        if should_skip(&path) {
+            continue;
+        }
+        let dst_path = dst.join(entry.file_name());
+        let ty = entry.file_type()?;
+        if ty.is_dir() {
+            let (f, b) = copy_dir_recursive_filtered(&path, &dst_path, should_skip)?;
+            files_copied += f;
+            bytes_copied += b;
+        } else if ty.is_file() {
+            let bytes = fs::copy(&path, &dst_path)?;
+            files_copied += 1;
+            bytes_copied += bytes;
+        } else {
+            return Err(anyhow!("unsupported dir entry type: {}", path.display()));
+        }
+    }
+
+    Ok((files_copied, bytes_copied))
+}
+
+/// Writes the table's memtable to a level-0 SST, then compacts the table if that leaves level 0
+/// due; the body of `EmbedDb::flush_table` once rows are expired.
+fn flush_table_locked(config: &Config, inner: &mut Inner, table: &str) -> Result<()> {
+    let (elapsed_ms, due) = {
+        let table_state = inner
+            .state
+            .tables
+            .get_mut(table)
+            .ok_or_else(|| anyhow!("table not found"))?;
+        let started = Instant::now();
+        let flushed = flush_table_state(
+            &config.data_dir,
+            table,
+            table_state,
+            &inner.raw_vectors,
+            config.row_codec,
+        )?;
+        if table_state.persisted_index_version != Some(table_state.embedding_version) {
+            persist_index(&config.data_dir, table, table_state, inner.lsn)?;
+        }
+        let elapsed_ms = if flushed {
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+            table_state.metrics.flush_count += 1;
+            table_state.metrics.flush_total_ms = table_state
+                .metrics
+                .flush_total_ms
+                .saturating_add(elapsed_ms);
+            Some(elapsed_ms)
+        } else {
+            None
+        };
+        let due = config
+            .compaction_policy(table)
+            .l0_due(&table_state.sst_files)?;
+        (elapsed_ms, due)
+    };
+    if let Some(elapsed_ms) = elapsed_ms {
+        inner.metrics.flush_count_total += 1;
+        inner.metrics.flush_total_ms = inner.metrics.flush_total_ms.saturating_add(elapsed_ms);
+    }
+
+    if due {
+        compact_table_locked(config, inner, table)?;
+    }
+    Ok(())
+}
+
+/// The changes that deleting `rows` from `table` makes.
+fn deleted_changes(table: &str, rows: Vec<RowData>) -> Vec<RowChange> {
+    rows.into_iter()
+        .map(|old| RowChange {
+            table: table.to_string(),
+            row_id: old.id,
+            kind: RowChangeKind::Delete,
+            old: Some(old),
+            new: None,
+        })
+        .collect()
+}
+
+/// Logs deletes for the rows of `table` expired as of `now_ms` and returns them.
+fn expire_rows_locked(inner: &mut Inner, table: &str, now_ms: u64) -> Result<Vec<RowData>> {
+    let table_state = inner
+        .state
+        .tables
+        .get(table)
+        .ok_or_else(|| anyhow!("table not found"))?;
+    let mut expired = BTreeMap::new();
+    if table_state.schema.expiry_column.is_some() {
+        for (row_id, row) in scan_visible_rows(table_state)? {
+            if table_state
+                .schema
+                .expires_at_ms(&row.fields)
+                .is_some_and(|expires_at_ms| expires_at_ms <= now_ms)
+            {
+                expired.insert(row_id, row);
+            }
+        }
+    }
+    for (row_id, expires_at_ms) in &table_state.expirations {
+        if *expires_at_ms <= now_ms && !expired.contains_key(row_id) {
+            if let Some(row) = load_row(table_state, *row_id)? {
+                expired.insert(*row_id, row);
+            }
+        }
+    }
+    if expired.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let records: Vec<WalRecord> = expired
+        .keys()
+        .map(|row_id| WalRecord::DeleteRow {
+            table: table.to_string(),
+            row_id: *row_id,
+        })
+        .collect();
+    append_durable_wal_batch(inner, Some(table), &records)?;
+    if let Some(table_state) = inner.state.tables.get_mut(table) {
+        for row_id in expired.keys() {
+            table_state.delete_row(*row_id);
+        }
+    }
+    Ok(expired.into_values().collect())
+}
+
+/// Deletes the table's rows hidden at or before `cutoff_ms` for good, returning how many.
+fn purge_hidden_rows_locked(inner: &mut Inner, table: &str, cutoff_ms: u64) -> Result<usize> {
+    let table_state = inner
+        .state
+        .tables
+        .get(table)
+        .ok_or_else(|| anyhow!("table not found"))?;
+    let mut row_ids: Vec<u64> = table_state
+        .hidden
+        .iter()
+        .filter(|(_, hidden_at_ms)| **hidden_at_ms <= cutoff_ms)
+        .map(|(row_id, _)| *row_id)
+        .collect();
+    if row_ids.is_empty() {
+        return Ok(0);
+    }
+    row_ids.sort_unstable();
+
+    let records: Vec<WalRecord> = row_ids
+        .iter()
+        .map(|row_id| WalRecord::DeleteRow {
+            table: table.to_string(),
+            row_id: *row_id,
+        })
+        .collect();
+    append_durable_wal_batch(inner, Some(table), &records)?;
+    if let Some(table_state) = inner.state.tables.get_mut(table) {
+        for row_id in &row_ids {
+            table_state.delete_row(*row_id);
         }
-        Ok(())
     }
+    Ok(row_ids.len())
+}
 
-    pub fn compact_table(&self, table: &str) -> Result<()> {
-        let mut inner = self.lock_inner()?;
-        let elapsed_ms = {
-            let table_state = inner
-                .state
-                .tables
-                .get_mut(table)
-                .ok_or_else(|| anyhow!("table not found"))?;
+fn compact_table_locked(
+    config: &Config,
+    inner: &mut Inner,
+    table: &str,
+) -> Result<CompactionStats> {
+    let table_state = inner
+        .state
+        .tables
+        .get_mut(table)
+        .ok_or_else(|| anyhow!("table not found"))?;
+    compact_vector_segments(&config.data_dir, table, table_state)?;
+
+    let started = Instant::now();
+    let dir = sst::table_dir(&config.data_dir, table);
+    sst::ensure_dir(&dir)?;
+    let merges = compaction::compact_levels(
+        &mut table_state.sst_files,
+        &dir,
+        &mut table_state.next_sst_seq,
+        config.compaction_policy(table),
+        config.row_codec,
+        &table_state.schema_changes,
+    )?;
+    // Merges into the last level drop tombstones.
+    table_state.sst_tombstones = OnceLock::new();
+    // Merged rows were upgraded; keep only the changes older files still need.
+    let sst_files = &table_state.sst_files;
+    table_state
+        .schema_changes
+        .retain(|change| sst_files.iter().any(|file| file.seq < change.before_seq));
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    let stats = CompactionStats {
+        files_per_level: compaction::files_per_level(&table_state.sst_files),
+        merges,
+        elapsed_ms,
+    };
+    if stats.merges.is_empty() {
+        return Ok(stats);
+    }
 
-            let level_zero: Vec<SstFile> = table_state
-                .sst_files
-                .iter()
-                .filter(|file| file.level == 0)
-                .cloned()
-                .collect();
-            if level_zero.is_empty() {
-                return Ok(());
-            }
-            let started = Instant::now();
+    table_state.metrics.compact_count += 1;
+    table_state.metrics.compact_total_ms = table_state
+        .metrics
+        .compact_total_ms
+        .saturating_add(elapsed_ms);
+    table_state.metrics.last_compacted_at_ms = Some(now_epoch_ms());
+    inner.metrics.compact_count_total += 1;
+    inner.metrics.compact_total_ms = inner.metrics.compact_total_ms.saturating_add(elapsed_ms);
+    Ok(stats)
+}
+
+fn repair_table_locked(
+    config: &Config,
+    inner: &mut Inner,
+    table: &str,
+) -> Result<TableVerification> {
+    let lsn = inner.lsn;
+    let table_state = inner
+        .state
+        .tables
+        .get_mut(table)
+        .ok_or_else(|| anyhow!("table not found"))?;
+    let mut report = TableVerification::new(
+        table,
+        table_state
+            .sst_files
+            .iter()
+            .map(verify::check_file)
+            .collect(),
+    );
+    let unreadable: Vec<SstFile> = table_state
+        .sst_files
+        .iter()
+        .zip(&report.files)
+        .filter(|(_, check)| check.error.is_some())
+        .map(|(file, _)| file.clone())
+        .collect();
+    if unreadable.is_empty() {
+        return Ok(report);
+    }
+    for file in &unreadable {
+        report
+            .quarantined
+            .push(verify::quarantine(&config.data_dir, table, file)?);
+    }
+    table_state
+        .sst_files
+        .retain(|file| !unreadable.iter().any(|bad| bad.path == file.path));
 
-            let dir = sst::table_dir(&self.config.data_dir, table);
-            sst::ensure_dir(&dir)?;
+    let history = history::replay_to_lsn(&config.data_dir, lsn)
+        .and_then(|mut state| {
+            state
+                .tables
+                .remove(table)
+                .ok_or_else(|| anyhow!("table '{table}' is not in the WAL history"))
+        })
+        .map(|rebuilt| rebuilt.rows);
+    match history {
+        Ok(rows) => {
+            let entries: Vec<SstEntry> = rows
+                .into_values()
+                .map(|row| SstEntry {
+                    row_id: row.id,
+                    row: Some(row),
+                })
+                .collect();
+            // The range tombstone hides every older file, so the table reads right even if a
+            // crash leaves some of them behind.
+            let dir = sst::table_dir(&config.data_dir, table);
             let seq = table_state.next_sst_seq;
             table_state.next_sst_seq += 1;
+            let everything = 0..u64::MAX;
+            let path = sst::write_sst(
+                &dir,
+                0,
+                seq,
+                &entries,
+                std::slice::from_ref(&everything),
+                config.row_codec,
+            )?;
+            let replaced = std::mem::replace(
+                &mut table_state.sst_files,
+                vec![SstFile {
+                    level: 0,
+                    seq,
+                    path,
+                }],
+            );
+            sst::remove_files(&replaced)?;
+            // The rebuilt rows already have the current schema.
+            table_state.schema_changes.clear();
+            report.rows_rebuilt = Some(entries.len());
+        }
+        Err(err) => report.rebuild_error = Some(err.to_string()),
+    }
+    table_state.row_count = OnceLock::new();
+    table_state.sst_tombstones = OnceLock::new();
+    table_state.keywords = OnceLock::new();
+    lock_cache(&inner.search_cache).invalidate_table(table);
+    Ok(report)
+}
 
-            if let Some(new_file) = sst::compact_level_zero(&level_zero, &dir, seq)? {
-                sst::remove_files(&level_zero)?;
-                table_state.sst_files.retain(|file| file.level != 0);
-                table_state.sst_files.push(new_file);
-            }
-
-            let elapsed_ms = started.elapsed().as_millis() as u64;
-            table_state.metrics.compact_count += 1;
-            table_state.metrics.compact_total_ms = table_state
-                .metrics
-                .compact_total_ms
-                .saturating_add(elapsed_ms);
-            elapsed_ms
-        };
-        inner.metrics.compact_count_total += 1;
-        inner.metrics.compact_total_ms = inner.metrics.compact_total_ms.saturating_add(elapsed_ms);
-
-        Ok(())
+/// The record deleting a row: `HideRow` on a soft-delete table, `DeleteRow` otherwise.
+fn delete_record(table: &str, row_id: u64, soft: bool) -> WalRecord {
+    if soft {
+        WalRecord::HideRow {
+            table: table.to_string(),
+            row_id,
+            hidden_at_ms: now_epoch_ms(),
+        }
+    } else {
+        WalRecord::DeleteRow {
+            table: table.to_string(),
+            row_id,
+        }
     }
+}
 
-    pub fn checkpoint(&self) -> Result<CheckpointStats> {
-        self.checkpoint_internal(false)
+fn check_row_version(row: &RowData, expected: Option<u64>) -> Result<()> {
+    match expected {
+        Some(expected) if expected != row.version => Err(VersionConflict {
+            row_id: row.id,
+            expected,
+            actual: row.version,
+        }
+        .into()),
+        _ => Ok(()),
     }
+}
 
-    pub fn export_snapshot(&self, dest_dir: impl AsRef<Path>) -> Result<SnapshotStats> {
-        let dest_dir = dest_dir.as_ref();
-        ensure_empty_or_missing_dir(dest_dir)?;
+fn load_row(table_state: &TableState, row_id: u64) -> Result<Option<RowData>> {
+    if table_state.hidden.contains_key(&row_id) {
+        return Ok(None);
+    }
+    load_stored_row(table_state, row_id)
+}
 
-        // Hold the DB lock for the entire operation so the snapshot is a consistent copy.
-        let mut inner = self.lock_inner()?;
-        let _ = checkpoint_locked(&self.config.data_dir, &mut inner, false)?;
-        let (files_copied, bytes_copied) = copy_dir_recursive_filtered(
-            &self.config.data_dir,
-            dest_dir,
-            should_skip_snapshot_entry,
-        )?;
+/// Like `load_row`, but also returns hidden rows.
+fn load_stored_row(table_state: &TableState, row_id: u64) -> Result<Option<RowData>> {
+    if let Some(row) = table_state.rows.get(&row_id) {
+        return Ok(Some(row.clone()));
+    }
+    if table_state.tombstones.contains(&row_id)
+        || sst::covers(&table_state.range_tombstones, row_id)
+    {
+        return Ok(None);
+    }
 
-        Ok(SnapshotStats {
-            files_copied,
-            bytes_copied,
-        })
+    for file in table_state.sst_files.iter().rev() {
+        if let Some(entry) = sst::find_entry(&file.path, row_id)? {
+            return Ok(entry.row.map(|mut row| {
+                schema::upgrade_row(&table_state.schema_changes, file.seq, &mut row);
+                row
+            }));
+        }
     }
 
-    pub fn restore_snapshot(
-        snapshot_dir: impl AsRef<Path>,
-        data_dir: impl AsRef<Path>,
-    ) -> Result<SnapshotStats> {
-        let snapshot_dir = snapshot_dir.as_ref();
-        let data_dir = data_dir.as_ref();
+    Ok(None)
+}
 
-        if !snapshot_dir.exists() {
+/// Compares stored content hashes against `spec`, returning the plan plus the new hash of each
+/// affected row.
+/// The rows to re-embed under `spec`: those whose content hash changes, and, when `spec` names a
+/// model, those whose embedding was stored under another model (or, with `untracked_stale`, under
+/// none). Pending rows are embedded under `spec`'s model anyway.
+fn plan_reembed(
+    table_state: &TableState,
+    spec: &EmbeddingSpec,
+    untracked_stale: bool,
+) -> Result<(ReembedPlan, Vec<(u64, String)>)> {
+    spec.validate()?;
+    if let (Some(dimensions), Some(stored)) = (spec.dimensions, table_state.dimension) {
+        if dimensions != stored && !table_state.embeddings.is_empty() {
             return Err(anyhow!(
-                "snapshot dir does not exist: {}",
-                snapshot_dir.display()
+                "table stores {stored}-dimensional embeddings; cannot declare {dimensions} dimensions"
             ));
         }
-        ensure_empty_or_missing_dir(data_dir)?;
+    }
+    for field in spec.all_source_fields() {
+        if !table_state
+            .schema
+            .columns
+            .iter()
+            .any(|col| &col.name == field)
+        {
+            return Err(anyhow!("unknown embedding field '{field}'"));
+        }
+    }
 
-        let (files_copied, bytes_copied) =
-            copy_dir_recursive_filtered(snapshot_dir, data_dir, should_skip_snapshot_entry)?;
-        Ok(SnapshotStats {
-            files_copied,
-            bytes_copied,
+    // Hidden rows are re-embedded too, so they are up to date if restored.
+    let mut hashes = Vec::new();
+    let mut unchanged_rows = 0;
+    for (row_id, row) in scan_stored_rows(table_state)? {
+        let content_hash = spec.content_hash(&row.fields)?;
+        let unchanged = table_state.embedding_meta.get(&row_id).is_some_and(|meta| {
+            let model_stale = match (&spec.model, &meta.model) {
+                _ if meta.status == EmbeddingStatus::Pending => false,
+                (None, _) => false,
+                (Some(model), Some(stored)) => model != stored,
+                (Some(_), None) => untracked_stale,
+            };
+            meta.content_hash == content_hash && !model_stale
+        });
+        if unchanged {
+            unchanged_rows += 1;
+        } else {
+            hashes.push((row_id, content_hash));
+        }
+    }
+    let plan = ReembedPlan {
+        affected_rows: hashes.iter().map(|(row_id, _)| *row_id).collect(),
+        unchanged_rows,
+        applied: false,
+    };
+    Ok((plan, hashes))
+}
+
+/// All visible rows of a table.
+/// A page of `scan_visible_rows`, after the row id `start_after`.
+fn scan_rows_locked(
+    table_state: &TableState,
+    start_after: Option<u64>,
+    limit: usize,
+) -> Result<RowPage> {
+    let rows = scan_visible_rows(table_state)?;
+    let after = start_after.unwrap_or(0);
+    let mut remaining = rows
+        .into_iter()
+        .filter(|(row_id, _)| start_after.is_none() || *row_id > after);
+    let items: Vec<RowData> = remaining.by_ref().take(limit).map(|(_, row)| row).collect();
+    let next_cursor = if remaining.next().is_some() {
+        items.last().map(|row| row.id)
+    } else {
+        None
+    };
+    Ok(RowPage { items, next_cursor })
+}
+
+/// A page of the rows with an embedding or an embedding job, after the row id `cursor`.
+fn scroll_embeddings_locked(
+    table_state: &TableState,
+    cursor: Option<u64>,
+    limit: usize,
+) -> EmbeddingPage {
+    let after = cursor.unwrap_or(0);
+    let mut row_ids: Vec<u64> = table_state
+        .embedding_meta
+        .keys()
+        .chain(table_state.embeddings.keys())
+        .copied()
+        .filter(|row_id| cursor.is_none() || *row_id > after)
+        .collect::<BTreeSet<u64>>()
+        .into_iter()
+        .collect();
+    let has_more = row_ids.len() > limit;
+    row_ids.truncate(limit);
+
+    let items: Vec<EmbeddingRecord> = row_ids
+        .into_iter()
+        .map(|row_id| {
+            let meta = table_state.embedding_meta.get(&row_id);
+            EmbeddingRecord {
+                row_id,
+                vector: table_state.original_vector(row_id),
+                status: meta.map_or(EmbeddingStatus::Ready, |meta| meta.status),
+                model: meta.and_then(|meta| meta.model.clone()),
+            }
         })
+        .collect();
+    let next_cursor = if has_more {
+        items.last().map(|item| item.row_id)
+    } else {
+        None
+    };
+    EmbeddingPage { items, next_cursor }
+}
+
+/// The table's visible rows and ready embeddings as `ArrowBatches`. `exact` returns the exact
+/// vector of a row in a quantized table, if one is kept; other rows export their stored vector.
+#[cfg(feature = "arrow")]
+fn export_arrow_locked(
+    table_state: &TableState,
+    batch_rows: usize,
+    exact: impl Fn(u64) -> Result<Option<Vec<f32>>>,
+) -> Result<ArrowBatches> {
+    if batch_rows == 0 {
+        return Err(anyhow!("batch_rows must be at least 1"));
+    }
+    let rows = scan_visible_rows(table_state)?;
+    let mut embeddings = HashMap::new();
+    if table_state.embedding_spec.is_some() {
+        for row_id in rows.keys() {
+            if !table_state.searchable(*row_id) {
+                continue;
+            }
+            let vector = match exact(*row_id)? {
+                Some(vector) => Some(vector),
+                None => table_state.original_vector(*row_id),
+            };
+            if let Some(vector) = vector {
+                embeddings.insert(*row_id, vector);
+            }
+        }
     }
+    ArrowBatches::new(
+        &table_state.schema,
+        table_state.expected_dimension(),
+        rows,
+        embeddings,
+        batch_rows,
+    )
 }
 
-pub trait Embedder: Send + Sync {
-    fn embed(&self, input: &str) -> Result<Vec<f32>>;
+fn scan_visible_rows(table_state: &TableState) -> Result<BTreeMap<u64, RowData>> {
+    let mut rows = scan_stored_rows(table_state)?;
+    if !table_state.hidden.is_empty() {
+        rows.retain(|row_id, _| !table_state.hidden.contains_key(row_id));
+    }
+    Ok(rows)
 }
 
-fn append_durable_wal(inner: &mut Inner, table: Option<&str>, record: &WalRecord) -> Result<()> {
-    inner.wal.append(record, true)?;
-    inner.metrics.wal_durable_appends += 1;
-    inner.metrics.wal_sync_ops += 1;
-    if let Some(table) = table {
-        if let Some(table_state) = inner.state.tables.get_mut(table) {
-            table_state.metrics.wal_durable_appends += 1;
+/// All rows of a table, hidden ones included: SSTs applied oldest to newest, then the memtable
+/// on top.
+fn scan_stored_rows(table_state: &TableState) -> Result<BTreeMap<u64, RowData>> {
+    let mut rows = BTreeMap::new();
+    for file in &table_state.sst_files {
+        let loaded = sst::LoadedSst::load(&file.path)?;
+        for range in loaded.range_tombstones() {
+            sst::remove_range(&mut rows, range);
+        }
+        for entry in loaded.into_entries()? {
+            match entry.row {
+                Some(mut row) => {
+                    schema::upgrade_row(&table_state.schema_changes, file.seq, &mut row);
+                    rows.insert(entry.row_id, row);
+                }
+                None => {
+                    rows.remove(&entry.row_id);
+                }
+            }
         }
     }
-    Ok(())
+    for row_id in &table_state.tombstones {
+        rows.remove(row_id);
+    }
+    for range in &table_state.range_tombstones {
+        sst::remove_range(&mut rows, range);
+    }
+    for (row_id, row) in &table_state.rows {
+        rows.insert(*row_id, row.clone());
+    }
+    Ok(rows)
 }
 
-fn checkpoint_locked(data_dir: &Path, inner: &mut Inner, auto: bool) -> Result<CheckpointStats> {
-    let checkpoint_started = Instant::now();
-    let wal_path = data_dir.join("wal.log");
-    let wal_prev_path = data_dir.join("wal.prev");
-    let wal_new_path = data_dir.join("wal.log.new");
-    let wal_dummy_path = data_dir.join("wal.checkpoint.tmp");
+fn search_locked(
+    table_state: &TableState,
+    query: &[f32],
+    k: usize,
+    metric: DistanceMetric,
+    filters: &[FilterCondition],
+    options: &SearchOptions,
+) -> Result<Vec<SearchHit>> {
+    check_query_against_table(table_state, query, metric, options)?;
+    validate_filters(&table_state.schema, filters)?;
+
+    let mut resolver = RowResolver::new(table_state);
+    let mut query_unit = query.to_vec();
+    vector::normalize(&mut query_unit);
+    let hnsw = table_state
+        .hnsw_for(metric)
+        .filter(|_| options.include_ids.is_none());
+    if let Some(hnsw) = hnsw {
+        let hits = search_hnsw_locked(
+            table_state,
+            hnsw,
+            query,
+            &query_unit,
+            k,
+            metric,
+            filters,
+            options,
+            &mut resolver,
+        )?;
+        if let Some(hits) = hits {
+            return Ok(hits);
+        }
+    }
 
-    let wal_bytes_before = fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
+    let mut top = TopK::new(k);
+    for (row_id, vector) in search_candidates(table_state, options) {
+        if !table_state.searchable(*row_id) || !options.allows(*row_id) {
+            continue;
+        }
 
-    // Flush all tables so row data is durably in SSTs and the checkpoint WAL can be compact.
-    let table_names: Vec<String> = inner.state.tables.keys().cloned().collect();
-    for table in table_names {
-        let elapsed_ms = {
-            let table_state = inner
-                .state
-                .tables
-                .get_mut(&table)
-                .ok_or_else(|| anyhow!("table not found"))?;
-            let started = Instant::now();
-            let flushed = flush_table_state(data_dir, &table, table_state)?;
-            if flushed {
-                let elapsed_ms = started.elapsed().as_millis() as u64;
-                table_state.metrics.flush_count += 1;
-                table_state.metrics.flush_total_ms = table_state
-                    .metrics
-                    .flush_total_ms
-                    .saturating_add(elapsed_ms);
-                Some(elapsed_ms)
-            } else {
-                None
-            }
-        };
-        if let Some(elapsed_ms) = elapsed_ms {
-            inner.metrics.flush_count_total += 1;
-            inner.metrics.flush_total_ms = inner.metrics.flush_total_ms.saturating_add(elapsed_ms);
+        // Distance first: rows that can't make the top k never need loading for the filters.
+        let dist = table_state.embedding_distance(*row_id, vector, query, &query_unit, metric);
+        if !top.accepts(*row_id, dist) {
+            continue;
+        }
+        if !filters.is_empty() && resolver.load_matching(*row_id, filters)?.is_none() {
+            continue;
         }
+        top.push(*row_id, dist);
     }
 
-    let mut records: Vec<WalRecord> = Vec::new();
-    for (name, table_state) in inner.state.tables.iter() {
-        records.push(WalRecord::CreateTable {
-            name: name.clone(),
-            schema: table_state.schema.clone(),
-            embedding_spec: table_state.embedding_spec.clone(),
-        });
-        records.push(WalRecord::SetNextRowId {
-            table: name.clone(),
-            next_row_id: table_state.next_row_id,
-        });
+    Ok(top.into_sorted().into_iter().map(SearchHit::from).collect())
+}
 
-        for (row_id, meta) in &table_state.embedding_meta {
-            records.push(WalRecord::EnqueueEmbedding {
-                table: name.clone(),
-                row_id: *row_id,
-                content_hash: meta.content_hash.clone(),
-            });
-            records.push(WalRecord::UpdateEmbeddingStatus {
-                table: name.clone(),
-                row_id: *row_id,
-                status: meta.status,
-                last_error: meta.last_error.clone(),
-                attempts: Some(meta.attempts),
-                next_retry_at_ms: Some(meta.next_retry_at_ms),
+/// The embeddings an exact scan has to score: just those of `options.include_ids` when that's
+/// the smaller set.
+fn search_candidates<'a>(
+    table_state: &'a TableState,
+    options: &'a SearchOptions,
+) -> Box<dyn Iterator<Item = (&'a u64, &'a StoredVector)> + 'a> {
+    match &options.include_ids {
+        Some(ids) if ids.len() < table_state.embeddings.len() => Box::new(
+            ids.iter()
+                .filter_map(|row_id| table_state.embeddings.get_key_value(row_id)),
+        ),
+        _ => Box::new(table_state.embeddings.iter()),
+    }
+}
+
+/// Approximate search through the table's HNSW graph. The candidate list is doubled until `k`
+/// rows survive the status and filter checks; `None` means it grew to cover the whole graph
+/// without finding enough, and the caller should fall back to the exact scan.
+#[allow(clippy::too_many_arguments)]
+fn search_hnsw_locked(
+    table_state: &TableState,
+    hnsw: &Hnsw,
+    query: &[f32],
+    query_unit: &[f32],
+    k: usize,
+    metric: DistanceMetric,
+    filters: &[FilterCondition],
+    options: &SearchOptions,
+    resolver: &mut RowResolver<'_>,
+) -> Result<Option<Vec<SearchHit>>> {
+    let distance = |row_id: u64| table_state.distance_to(row_id, query, query_unit, metric);
+    let mut ef = hnsw.ef_search().max(k);
+    loop {
+        let mut hits = Vec::new();
+        for (row_id, distance) in hnsw.search(&distance, ef) {
+            if hits.len() == k {
+                break;
+            }
+            if !table_state.searchable(row_id) || !options.allows(row_id) {
+                continue;
+            }
+            if !filters.is_empty() && resolver.load_matching(row_id, filters)?.is_none() {
+                continue;
+            }
+            hits.push(SearchHit {
+                row_id,
+                distance,
+                score: None,
             });
         }
+        if hits.len() == k {
+            return Ok(Some(hits));
+        }
+        if ef >= hnsw.len() {
+            return Ok(None);
+        }
+        ef = ef.saturating_mul(2).min(hnsw.len());
+    }
+}
 
-        for (row_id, vector) in &table_state.embeddings {
-            records.push(WalRecord::StoreEmbedding {
-                table: name.clone(),
-                row_id: *row_id,
-                vector: vector.clone(),
-            });
+/// Second stage of quantized search: replaces candidate distances with exact ones computed from
+/// the raw f32 vectors and keeps the best `k`. Candidates without a raw vector keep their
+/// approximate distance.
+fn rescore_exact(
+    raw_vectors: &RawVectors,
+    table: &str,
+    mut candidates: Vec<SearchHit>,
+    query: &[f32],
+    k: usize,
+    metric: DistanceMetric,
+) -> Result<Vec<SearchHit>> {
+    for hit in candidates.iter_mut() {
+        if let Some(exact) = raw_vectors.exact_vector(table, hit.row_id)? {
+            hit.distance = vector::distance(query, &exact, metric);
         }
     }
+    candidates.sort_by(|a, b| {
+        a.distance
+            .total_cmp(&b.distance)
+            .then(a.row_id.cmp(&b.row_id))
+    });
+    candidates.truncate(k);
+    Ok(candidates)
+}
 
-    // Write the new WAL snapshot.
-    {
-        let mut new_wal = Wal::create_new(wal_new_path.clone())?;
-        for record in &records {
-            new_wal.append(record, false)?;
+fn search_sparse_locked(
+    table_state: &TableState,
+    query: &SparseVector,
+    k: usize,
+    filters: &[FilterCondition],
+) -> Result<Vec<SearchHit>> {
+    validate_filters(&table_state.schema, filters)?;
+
+    let mut resolver = RowResolver::new(table_state);
+    let mut top = TopK::new(k);
+    for (row_id, vector) in &table_state.sparse_vectors {
+        if table_state.hidden.contains_key(row_id) {
+            continue;
         }
-        new_wal.sync()?;
-        inner.metrics.wal_sync_ops += 1;
+        let Some(dot) = query.dot(vector) else {
+            continue;
+        };
+        if !top.accepts(*row_id, -dot) {
+            continue;
+        }
+        if !filters.is_empty() && resolver.load_matching(*row_id, filters)?.is_none() {
+            continue;
+        }
+        top.push(*row_id, -dot);
     }
 
-    // Ensure `wal.log` is closed during rotation (important for Windows semantics).
-    inner.wal = Wal::create_new(wal_dummy_path.clone())?;
+    Ok(top.into_sorted().into_iter().map(SearchHit::from).collect())
+}
 
-    // Rotate with a `wal.prev` fallback to tolerate crashes between renames.
-    if wal_prev_path.exists() {
-        let _ = fs::remove_file(&wal_prev_path);
+fn search_keyword_locked(
+    table_state: &TableState,
+    query_text: &str,
+    k: usize,
+    filters: &[FilterCondition],
+) -> Result<Vec<SearchHit>> {
+    validate_filters(&table_state.schema, filters)?;
+
+    let mut resolver = RowResolver::new(table_state);
+    let mut hits = Vec::new();
+    for (row_id, score) in table_state.keyword_index()?.search(query_text) {
+        if hits.len() == k {
+            break;
+        }
+        if !filters.is_empty() && resolver.load_matching(row_id, filters)?.is_none() {
+            continue;
+        }
+        hits.push(SearchHit {
+            row_id,
+            distance: -score,
+            score: None,
+        });
     }
-    if wal_path.exists() {
-        fs::rename(&wal_path, &wal_prev_path)?;
+    Ok(hits)
+}
+
+/// Estimated memtable size of a tombstone; a range tombstone counts as two.
+const MEMTABLE_TOMBSTONE_BYTES: u64 = 8;
+
+/// Estimated memtable size of a row: its id, version, and timestamps, field names, and values.
+fn memtable_row_bytes(row: &RowData) -> u64 {
+    let fields: usize = row
+        .fields
+        .iter()
+        .map(|(name, value)| {
+            name.len()
+                + match value {
+                    Value::Int(_) | Value::Float(_) => 8,
+                    Value::Bool(_) | Value::Null => 1,
+                    Value::String(text) => text.len(),
+                    Value::Bytes(bytes) => bytes.len(),
+                }
+        })
+        .sum();
+    (32 + fields) as u64
+}
+
+/// The values of a row's String columns, the text the keyword index covers.
+fn string_fields<'a>(
+    schema: &'a TableSchema,
+    fields: &'a BTreeMap<String, Value>,
+) -> impl Iterator<Item = &'a str> {
+    schema
+        .columns
+        .iter()
+        .filter(|column| column.data_type == DataType::String)
+        .filter_map(|column| match fields.get(&column.name) {
+            Some(Value::String(text)) => Some(text.as_str()),
+            _ => None,
+        })
+}
+
+fn search_custom_locked(
+    table_state: &TableState,
+    query: &[f32],
+    k: usize,
+    name: &str,
+    distance_fn: &dyn DistanceFn,
+    filters: &[FilterCondition],
+    options: &SearchOptions,
+) -> Result<Vec<SearchHit>> {
+    let conflict = table_state.embedding_spec.as_ref().and_then(|spec| {
+        match (&spec.custom_metric, spec.metric) {
+            (Some(table_metric), _) if table_metric != name => Some(format!("'{table_metric}'")),
+            (None, Some(table_metric)) => Some(format!("{table_metric:?}")),
+            _ => None,
+        }
+    });
+    if let Some(table_metric) = conflict {
+        if !options.allow_metric_mismatch {
+            return Err(anyhow!(
+                "requested metric '{name}' conflicts with table metric {table_metric} (set allow_metric_mismatch to override)"
+            ));
+        }
+    }
+    if let Some(vector) = options.vector.as_deref() {
+        let mut hits = search_named_locked(
+            table_state,
+            vector,
+            query,
+            k,
+            filters,
+            options,
+            |query, vector| distance_fn.distance(query, vector),
+        )?;
+        options
+            .score_mode
+            .apply(&mut hits, vector::inverse_distance);
+        return Ok(hits);
+    }
+    check_query_dimension(table_state, query)?;
+    validate_filters(&table_state.schema, filters)?;
+
+    let mut resolver = RowResolver::new(table_state);
+    let mut top = TopK::new(k);
+    for row_id in table_state.embeddings.keys() {
+        if !options.allows(*row_id) {
+            continue;
+        }
+        let Some(vector) = table_state.ready_vector(*row_id) else {
+            continue;
+        };
+        if vector.len() != query.len() {
+            continue;
+        }
+        let distance = distance_fn.distance(query, &vector);
+        if !top.accepts(*row_id, distance) {
+            continue;
+        }
+        if !filters.is_empty() && resolver.load_matching(*row_id, filters)?.is_none() {
+            continue;
+        }
+        top.push(*row_id, distance);
     }
-    fs::rename(&wal_new_path, &wal_path)?;
-
-    let wal_bytes_after = fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
 
-    inner.wal = Wal::open(wal_path)?;
+    let mut hits: Vec<SearchHit> = top.into_sorted().into_iter().map(SearchHit::from).collect();
+    options
+        .score_mode
+        .apply(&mut hits, vector::inverse_distance);
+    Ok(hits)
+}
 
-    let _ = fs::remove_file(&wal_dummy_path);
-    let _ = fs::remove_file(&wal_prev_path);
+fn check_query_against_table(
+    table_state: &TableState,
+    query: &[f32],
+    metric: DistanceMetric,
+    options: &SearchOptions,
+) -> Result<()> {
+    check_query_dimension(table_state, query)?;
+    check_metric_against_table(table_state, metric, options)
+}
 
-    let checkpoint_elapsed_ms = checkpoint_started.elapsed().as_millis() as u64;
-    inner.metrics.checkpoints += 1;
-    if auto {
-        inner.metrics.auto_checkpoints += 1;
+fn check_metric_against_table(
+    table_state: &TableState,
+    metric: DistanceMetric,
+    options: &SearchOptions,
+) -> Result<()> {
+    if let Some(spec) = &table_state.embedding_spec {
+        let conflict = match (spec.metric, &spec.custom_metric) {
+            (Some(table_metric), _) if table_metric != metric => Some(format!("{table_metric:?}")),
+            (_, Some(table_metric)) => Some(format!("'{table_metric}'")),
+            _ => None,
+        };
+        if let Some(table_metric) = conflict {
+            if !options.allow_metric_mismatch {
+                return Err(anyhow!(
+                    "requested metric {:?} conflicts with table metric {} (set allow_metric_mismatch to override)",
+                    metric,
+                    table_metric
+                ));
+            }
+        }
     }
-    inner.metrics.checkpoint_total_ms = inner
-        .metrics
-        .checkpoint_total_ms
-        .saturating_add(checkpoint_elapsed_ms);
-
-    Ok(CheckpointStats {
-        wal_bytes_before,
-        wal_bytes_after,
-    })
+    Ok(())
 }
 
-fn ensure_empty_or_missing_dir(path: &Path) -> Result<()> {
-    if path.exists() {
-        if !path.is_dir() {
+fn check_query_dimension(table_state: &TableState, query: &[f32]) -> Result<()> {
+    if let Some(dimension) = table_state.expected_dimension() {
+        if query.len() != dimension {
             return Err(anyhow!(
-                "path exists and is not a directory: {}",
-                path.display()
+                "query dimension {} does not match table embedding dimension {}",
+                query.len(),
+                dimension
             ));
         }
-        if fs::read_dir(path)?.next().is_some() {
-            return Err(anyhow!("directory must be empty: {}", path.display()));
-        }
-        return Ok(());
-    }
-
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
     }
     Ok(())
 }
 
-fn should_skip_snapshot_entry(path: &Path) -> bool {
-    match path.file_name().and_then(|s| s.to_str()) {
-        // Transient/lock files should not be snapshotted.
-        Some("embeddb.lock" | "wal.prev" | "wal.log.new" | "wal.checkpoint.tmp") => true,
-        _ => false,
+/// Exact scan over the named vector `name`, ranking rows by `distance` from the query.
+fn search_named_locked(
+    table_state: &TableState,
+    name: &str,
+    query: &[f32],
+    k: usize,
+    filters: &[FilterCondition],
+    options: &SearchOptions,
+    distance: impl Fn(&[f32], &[f32]) -> f32,
+) -> Result<Vec<SearchHit>> {
+    check_named_query(table_state, name, query)?;
+    validate_filters(&table_state.schema, filters)?;
+
+    let mut resolver = RowResolver::new(table_state);
+    let mut top = TopK::new(k);
+    for (row_id, vector) in table_state.ready_named_vectors(name) {
+        if !options.allows(row_id) {
+            continue;
+        }
+        let distance = distance(query, vector);
+        if !top.accepts(row_id, distance) {
+            continue;
+        }
+        if !filters.is_empty() && resolver.load_matching(row_id, filters)?.is_none() {
+            continue;
+        }
+        top.push(row_id, distance);
     }
+
+    Ok(top.into_sorted().into_iter().map(SearchHit::from).collect())
 }
 
-fn copy_dir_recursive_filtered(
-    src: &Path,
-    dst: &Path,
-    should_skip: fn(&Path) -> bool,
-) -> Result<(u64, u64)> {
-    fs::create_dir_all(dst)?;
+fn check_named_query(table_state: &TableState, name: &str, query: &[f32]) -> Result<()> {
+    let declared = table_state
+        .embedding_spec
+        .as_ref()
+        .is_some_and(|spec| spec.vector(name).is_some());
+    if !declared {
+        return Err(anyhow!("table has no vector named '{name}'"));
+    }
+    if let Some(dimension) = table_state.named_dimension(name) {
+        if query.len() != dimension {
+            return Err(anyhow!(
+                "query dimension {} does not match dimension {dimension} of vector '{name}'",
+                query.len()
+            ));
+        }
+    }
+    Ok(())
+}
 
-    let mut files_copied = 0u64;
-    let mut bytes_copied = 0u64;
+/// The vectors embedded for one row: the spec's unnamed vector, then one per named vector.
+#[derive(Clone)]
+struct RowEmbeddings {
+    vector: Vec<f32>,
+    named: Vec<(String, Vec<f32>)>,
+}
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let path = entry.path();
-        if should_skip(&path) {
-            continue;
+impl RowEmbeddings {
+    /// Rejects the row if any of its vectors differs in length from what the table expects.
+    fn check(self, table_state: &TableState) -> std::result::Result<Self, String> {
+        if let Some(dimensions) = table_state.expected_dimension() {
+            if self.vector.len() != dimensions {
+                return Err(format!(
+                    "embedder returned a {}-dimensional vector but the table expects {} dimensions",
+                    self.vector.len(),
+                    dimensions
+                ));
+            }
         }
-        let dst_path = dst.join(entry.file_name());
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            let (f, b) = copy_dir_recursive_filtered(&path, &dst_path, should_skip)?;
-            files_copied += f;
-            bytes_copied += b;
-        } else if ty.is_file() {
-            let bytes = fs::copy(&path, &dst_path)?;
-            files_copied += 1;
-            bytes_copied += bytes;
-        } else {
-            return Err(anyhow!("unsupported dir entry type: {}", path.display()));
+        for (name, vector) in &self.named {
+            if let Some(dimensions) = table_state.named_dimension(name) {
+                if vector.len() != dimensions {
+                    return Err(format!(
+                        "embedder returned a {}-dimensional vector for '{name}' but the table expects {dimensions} dimensions",
+                        vector.len()
+                    ));
+                }
+            }
         }
+        Ok(self)
     }
+}
 
-    Ok((files_copied, bytes_copied))
+/// Resolves rows for a single read pass, loading each SST file at most once.
+struct RowResolver<'a> {
+    table_state: &'a TableState,
+    loaded: HashMap<usize, sst::LoadedSst>,
 }
 
-fn load_row(table_state: &TableState, row_id: u64) -> Result<Option<RowData>> {
-    if let Some(row) = table_state.rows.get(&row_id) {
-        return Ok(Some(row.clone()));
-    }
-    if table_state.tombstones.contains(&row_id) {
-        return Ok(None);
+impl<'a> RowResolver<'a> {
+    fn new(table_state: &'a TableState) -> Self {
+        Self {
+            table_state,
+            loaded: HashMap::new(),
+        }
     }
 
-    for file in table_state.sst_files.iter().rev() {
-        if let Some(entry) = sst::find_entry(&file.path, row_id)? {
-            return Ok(entry.row);
+    /// Returns the visible row for `row_id` if it matches all `filters`. Equality filters on
+    /// dictionary-encoded SST columns are checked against codes before the row is decoded.
+    fn load_matching(
+        &mut self,
+        row_id: u64,
+        filters: &[FilterCondition],
+    ) -> Result<Option<RowData>> {
+        let table_state = self.table_state;
+        if table_state.hidden.contains_key(&row_id) {
+            return Ok(None);
+        }
+        if let Some(row) = table_state.rows.get(&row_id) {
+            return Ok(row_matches_filters(&table_state.schema, row, filters).then(|| row.clone()));
+        }
+        if table_state.tombstones.contains(&row_id)
+            || sst::covers(&table_state.range_tombstones, row_id)
+        {
+            return Ok(None);
         }
-    }
 
-    Ok(None)
+        for (idx, file) in table_state.sst_files.iter().enumerate().rev() {
+            let sst = match self.loaded.entry(idx) {
+                hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                hash_map::Entry::Vacant(entry) => entry.insert(sst::LoadedSst::load(&file.path)?),
+            };
+            let sst = &*sst;
+            let entry = match sst.find(row_id) {
+                Some(entry) => entry,
+                None if sst.covers(row_id) => return Ok(None),
+                None => continue,
+            };
+            if entry.fields.is_none() {
+                return Ok(None);
+            }
+            for filter in filters {
+                let wants_equal = match filter.op {
+                    FilterOp::Eq => true,
+                    FilterOp::Neq => false,
+                    _ => continue,
+                };
+                // Dictionary codes only tell byte-equal strings apart.
+                if column_collation(&table_state.schema, &filter.column) != Collation::Binary {
+                    continue;
+                }
+                if let Some(equal) = sst.code_equals(entry, &filter.column, &filter.value) {
+                    if equal != wants_equal {
+                        return Ok(None);
+                    }
+                }
+            }
+            let row = sst.decode(entry)?.row;
+            return Ok(row.filter(|row| row_matches_filters(&table_state.schema, row, filters)));
+        }
+
+        Ok(None)
+    }
 }
 
 fn row_exists(table_state: &TableState, row_id: u64) -> Result<bool> {
@@ -1341,11 +6426,15 @@ fn validate_filters(schema: &TableSchema, filters: &[FilterCondition]) -> Result
     }
 
     for filter in filters {
-        let col = schema
-            .columns
-            .iter()
-            .find(|col| col.name == filter.column)
-            .ok_or_else(|| anyhow!("unknown filter column '{}'", filter.column))?;
+        let system;
+        let col = match schema.columns.iter().find(|col| col.name == filter.column) {
+            Some(col) => col,
+            None if [CREATED_AT_COLUMN, UPDATED_AT_COLUMN].contains(&filter.column.as_str()) => {
+                system = Column::new(filter.column.clone(), DataType::Int, false);
+                &system
+            }
+            None => return Err(anyhow!("unknown filter column '{}'", filter.column)),
+        };
 
         let value = &filter.value;
         let is_numeric = matches!(col.data_type, DataType::Int | DataType::Float);
@@ -1367,21 +6456,44 @@ fn validate_filters(schema: &TableSchema, filters: &[FilterCondition]) -> Result
                     ));
                 }
             }
-            FilterOp::Lt | FilterOp::Lte | FilterOp::Gt | FilterOp::Gte => {
-                if !is_numeric {
+            FilterOp::Lt | FilterOp::Lte | FilterOp::Gt | FilterOp::Gte => match col.data_type {
+                DataType::Int | DataType::Float if !value_is_numeric => {
                     return Err(anyhow!(
-                        "filter op '{:?}' not supported for non-numeric column '{}'",
+                        "filter op '{:?}' requires numeric value for column '{}'",
                         filter.op,
                         filter.column
                     ));
                 }
-                if !value_is_numeric {
+                DataType::String | DataType::Bytes if !value.matches(&col.data_type) => {
                     return Err(anyhow!(
-                        "filter op '{:?}' requires numeric value for column '{}'",
+                        "filter op '{:?}' requires {:?} value for column '{}'",
+                        filter.op,
+                        col.data_type,
+                        filter.column
+                    ));
+                }
+                DataType::Bool => {
+                    return Err(anyhow!(
+                        "filter op '{:?}' not supported for Bool column '{}'",
                         filter.op,
                         filter.column
                     ));
                 }
+                _ => {}
+            },
+            FilterOp::Contains => {
+                if col.data_type != DataType::String {
+                    return Err(anyhow!(
+                        "filter op 'Contains' not supported for non-string column '{}'",
+                        filter.column
+                    ));
+                }
+                if !matches!(value, Value::String(_)) {
+                    return Err(anyhow!(
+                        "filter op 'Contains' requires string value for column '{}'",
+                        filter.column
+                    ));
+                }
             }
         }
     }
@@ -1389,41 +6501,42 @@ fn validate_filters(schema: &TableSchema, filters: &[FilterCondition]) -> Result
     Ok(())
 }
 
-fn row_matches_filters(row: &RowData, filters: &[FilterCondition]) -> bool {
+/// Whether `row` matches every filter, comparing `String` and `Bytes` values under the collation
+/// of their column in `schema`.
+fn row_matches_filters(schema: &TableSchema, row: &RowData, filters: &[FilterCondition]) -> bool {
     for filter in filters {
-        let actual = row.fields.get(&filter.column).unwrap_or(&Value::Null);
+        let system;
+        let actual = match row.fields.get(&filter.column) {
+            Some(value) => value,
+            None if !schema.columns.iter().any(|col| col.name == filter.column) => {
+                system = row.system_value(&filter.column).unwrap_or(Value::Null);
+                &system
+            }
+            None => &Value::Null,
+        };
         let expected = &filter.value;
+        let collation = column_collation(schema, &filter.column);
+        let ordering = match (value_as_f64(actual), value_as_f64(expected)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => collation.compare(actual, expected),
+        };
         let matches = match filter.op {
-            FilterOp::Eq => {
-                if let (Some(a), Some(b)) = (value_as_f64(actual), value_as_f64(expected)) {
-                    a == b
-                } else {
-                    actual == expected
-                }
-            }
-            FilterOp::Neq => {
-                if let (Some(a), Some(b)) = (value_as_f64(actual), value_as_f64(expected)) {
-                    a != b
-                } else {
-                    actual != expected
-                }
-            }
-            FilterOp::Lt => value_as_f64(actual)
-                .zip(value_as_f64(expected))
-                .map(|(a, b)| a < b)
-                .unwrap_or(false),
-            FilterOp::Lte => value_as_f64(actual)
-                .zip(value_as_f64(expected))
-                .map(|(a, b)| a <= b)
-                .unwrap_or(false),
-            FilterOp::Gt => value_as_f64(actual)
-                .zip(value_as_f64(expected))
-                .map(|(a, b)| a > b)
-                .unwrap_or(false),
-            FilterOp::Gte => value_as_f64(actual)
-                .zip(value_as_f64(expected))
-                .map(|(a, b)| a >= b)
-                .unwrap_or(false),
+            FilterOp::Eq => match ordering {
+                Some(ordering) => ordering.is_eq(),
+                None => actual == expected,
+            },
+            FilterOp::Neq => match ordering {
+                Some(ordering) => ordering.is_ne(),
+                None => actual != expected,
+            },
+            FilterOp::Lt => ordering.is_some_and(Ordering::is_lt),
+            FilterOp::Lte => ordering.is_some_and(Ordering::is_le),
+            FilterOp::Gt => ordering.is_some_and(Ordering::is_gt),
+            FilterOp::Gte => ordering.is_some_and(Ordering::is_ge),
+            FilterOp::Contains => match (actual, expected) {
+                (Value::String(a), Value::String(b)) => collation.contains(a, b),
+                _ => false,
+            },
         };
 
         if !matches {
@@ -1433,6 +6546,14 @@ fn row_matches_filters(row: &RowData, filters: &[FilterCondition]) -> bool {
     true
 }
 
+fn column_collation(schema: &TableSchema, column: &str) -> Collation {
+    schema
+        .columns
+        .iter()
+        .find(|col| col.name == column)
+        .map_or(Collation::Binary, |col| col.collation)
+}
+
 fn apply_record(state: &mut DbState, record: WalRecord) -> Result<()> {
     match record {
         WalRecord::CreateTable {
@@ -1440,42 +6561,33 @@ fn apply_record(state: &mut DbState, record: WalRecord) -> Result<()> {
             schema,
             embedding_spec,
         } => {
-            state.tables.insert(
-                name,
-                TableState {
-                    schema,
-                    next_row_id: 1,
-                    rows: BTreeMap::new(),
-                    tombstones: BTreeSet::new(),
-                    embeddings: HashMap::new(),
-                    embedding_meta: HashMap::new(),
-                    embedding_spec,
-                    sst_files: Vec::new(),
-                    next_sst_seq: 1,
-                    metrics: TableRuntimeMetrics::default(),
-                },
-            );
+            state
+                .tables
+                .insert(name, TableState::new(schema, embedding_spec));
         }
         WalRecord::SetNextRowId { table, next_row_id } => {
             if let Some(table_state) = state.tables.get_mut(&table) {
                 table_state.next_row_id = next_row_id;
             }
         }
+        WalRecord::RenameTable { table, new_name } => {
+            if let Some(table_state) = state.tables.remove(&table) {
+                state.tables.insert(new_name, table_state);
+            }
+        }
         WalRecord::PutRow { table, row_id, row } => {
             if let Some(table_state) = state.tables.get_mut(&table) {
-                table_state.rows.insert(row_id, row);
-                table_state.tombstones.remove(&row_id);
-                if row_id >= table_state.next_row_id {
-                    table_state.next_row_id = row_id + 1;
-                }
+                table_state.put_row(RowData { id: row_id, ..row });
             }
         }
         WalRecord::DeleteRow { table, row_id } => {
             if let Some(table_state) = state.tables.get_mut(&table) {
-                table_state.rows.remove(&row_id);
-                table_state.tombstones.insert(row_id);
-                table_state.embeddings.remove(&row_id);
-                table_state.embedding_meta.remove(&row_id);
+                table_state.delete_row(row_id);
+            }
+        }
+        WalRecord::DeleteRanges { table, ranges } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.delete_ranges(&ranges);
             }
         }
         WalRecord::EnqueueEmbedding {
@@ -1492,6 +6604,7 @@ fn apply_record(state: &mut DbState, record: WalRecord) -> Result<()> {
                         last_error: None,
                         attempts: 0,
                         next_retry_at_ms: 0,
+                        model: None,
                     },
                 );
             }
@@ -1503,9 +6616,13 @@ fn apply_record(state: &mut DbState, record: WalRecord) -> Result<()> {
             last_error,
             attempts,
             next_retry_at_ms,
+            model,
         } => {
             if let Some(table_state) = state.tables.get_mut(&table) {
                 if let Some(meta) = table_state.embedding_meta.get_mut(&row_id) {
+                    if status == EmbeddingStatus::Ready {
+                        meta.model = model;
+                    }
                     meta.status = status;
                     meta.last_error = last_error;
                     if let Some(attempts) = attempts {
@@ -1521,11 +6638,82 @@ fn apply_record(state: &mut DbState, record: WalRecord) -> Result<()> {
             table,
             row_id,
             vector,
+            norm,
+        } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.store_embedding(row_id, vector, norm);
+            }
+        }
+        WalRecord::StoreSparseVector {
+            table,
+            row_id,
+            vector,
+        } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.sparse_vectors.insert(row_id, vector);
+            }
+        }
+        WalRecord::StoreNamedEmbedding {
+            table,
+            row_id,
+            name,
+            vector,
+        } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.store_named_embedding(name, row_id, vector);
+            }
+        }
+        WalRecord::SetRowExpiry {
+            table,
+            row_id,
+            expires_at_ms,
+        } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.expirations.insert(row_id, expires_at_ms);
+            }
+        }
+        WalRecord::HideRow {
+            table,
+            row_id,
+            hidden_at_ms,
+        } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.hide_row(row_id, hidden_at_ms);
+            }
+        }
+        WalRecord::RestoreRow { table, row_id } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.unhide_row(row_id)?;
+            }
+        }
+        WalRecord::SetEmbeddingSpec {
+            table,
+            embedding_spec,
+        } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.set_embedding_spec(embedding_spec);
+            }
+        }
+        WalRecord::AlterTable {
+            table,
+            op,
+            before_seq,
         } => {
             if let Some(table_state) = state.tables.get_mut(&table) {
-                table_state.embeddings.insert(row_id, vector);
+                table_state.alter(op, before_seq)?;
             }
         }
+        WalRecord::SchemaChanges { table, changes } => {
+            if let Some(table_state) = state.tables.get_mut(&table) {
+                table_state.schema_changes = changes;
+            }
+        }
+        // Vector segments are loaded by `EmbedDb::open`; history replays skip snapshots. Replay
+        // already dropped any batch that never committed, so batch markers need nothing.
+        WalRecord::VectorSegments { .. }
+        | WalRecord::Checkpoint { .. }
+        | WalRecord::BeginBatch
+        | WalRecord::CommitBatch => {}
     }
 
     Ok(())
@@ -1535,9 +6723,15 @@ fn flush_table_state(
     root: &std::path::Path,
     table: &str,
     table_state: &mut TableState,
+    raw_vectors: &RawVectors,
+    codec: RowCodecKind,
 ) -> Result<bool> {
-    if table_state.rows.is_empty() && table_state.tombstones.is_empty() {
-        return Ok(false);
+    let vectors_flushed = flush_vector_changes(root, table, table_state, raw_vectors)?;
+    if table_state.rows.is_empty()
+        && table_state.tombstones.is_empty()
+        && table_state.range_tombstones.is_empty()
+    {
+        return Ok(vectors_flushed);
     }
 
     let dir = sst::table_dir(root, table);
@@ -1560,7 +6754,11 @@ fn flush_table_state(
 
     let seq = table_state.next_sst_seq;
     table_state.next_sst_seq += 1;
-    let path = sst::write_sst(&dir, 0, seq, &entries)?;
+    let path = sst::write_sst(&dir, 0, seq, &entries, &table_state.range_tombstones, codec)?;
+    if let Some(counts) = table_state.sst_tombstones.get_mut() {
+        counts.point += table_state.tombstones.len() as u64;
+        counts.range += table_state.range_tombstones.len() as u64;
+    }
     table_state.sst_files.push(SstFile {
         level: 0,
         seq,
@@ -1568,9 +6766,140 @@ fn flush_table_state(
     });
     table_state.rows.clear();
     table_state.tombstones.clear();
+    table_state.range_tombstones.clear();
+    table_state.memtable_bytes = 0;
+
+    Ok(true)
+}
+
+/// Writes embeddings stored or removed since the last flush to a new level-0 vector segment.
+/// Quantized tables persist their exact f32 vectors, matching what checkpoints used to log.
+fn flush_vector_changes(
+    root: &Path,
+    table: &str,
+    table_state: &mut TableState,
+    raw_vectors: &RawVectors,
+) -> Result<bool> {
+    if table_state.vector_changes.is_empty() {
+        return Ok(false);
+    }
+
+    let mut entries = Vec::with_capacity(table_state.vector_changes.len());
+    for row_id in &table_state.vector_changes {
+        let entry = match table_state.embeddings.get(row_id) {
+            Some(vector) => match raw_vectors.exact_vector(table, *row_id)? {
+                Some(exact) => VectorEntry {
+                    row_id: *row_id,
+                    vector: Some(exact),
+                    norm: None,
+                },
+                None => VectorEntry {
+                    row_id: *row_id,
+                    vector: Some(vector.to_f32()),
+                    norm: table_state.embedding_norms.get(row_id).copied(),
+                },
+            },
+            None => VectorEntry {
+                row_id: *row_id,
+                vector: None,
+                norm: None,
+            },
+        };
+        entries.push(entry);
+    }
 
+    let seq = table_state.next_sst_seq;
+    table_state.next_sst_seq += 1;
+    let file = vecseg::write_segment(&sst::table_dir(root, table), 0, seq, &entries)?;
+    table_state.vector_segments.push(file);
+    table_state.vector_changes.clear();
     Ok(true)
 }
 
+/// Merges a table's vector segments into a single level-1 segment without removals.
+/// Finishes the index build `open` deferred: loads the table's index file if it is current
+/// (its generation is within `changed_lsn..=lsn`), otherwise rebuilds the graph and, with
+/// `persist`, writes a new file for the next open.
+fn restore_index(
+    root: &Path,
+    table: &str,
+    table_state: &mut TableState,
+    changed_lsn: u64,
+    lsn: u64,
+    persist: bool,
+) -> Result<()> {
+    table_state.index_deferred = false;
+    if table_state.hnsw.is_none() {
+        return Ok(());
+    }
+    // An unreadable file is only a cache miss; the graph is rebuilt from the embeddings.
+    let loaded = indexfile::read_index(&sst::table_dir(root, table))
+        .ok()
+        .flatten()
+        .filter(|file| (changed_lsn..=lsn).contains(&file.generation))
+        .and_then(|file| Hnsw::decode(&file.payload))
+        .filter(|hnsw| table_state.index_matches(hnsw));
+    match loaded {
+        Some(hnsw) => {
+            table_state.hnsw = Some(hnsw);
+            table_state.persisted_index_version = Some(table_state.embedding_version);
+            Ok(())
+        }
+        None => {
+            table_state.rebuild_index();
+            match persist {
+                true => persist_index(root, table, table_state, lsn),
+                false => Ok(()),
+            }
+        }
+    }
+}
+
+/// Writes the table's HNSW graph to its index file with `lsn` as the generation; `lsn` must be
+/// the LSN `table_state` is current as of.
+fn persist_index(root: &Path, table: &str, table_state: &mut TableState, lsn: u64) -> Result<()> {
+    let Some(hnsw) = &table_state.hnsw else {
+        return Ok(());
+    };
+    indexfile::write_index(&sst::table_dir(root, table), lsn, &hnsw.encode())?;
+    table_state.persisted_index_version = Some(table_state.embedding_version);
+    Ok(())
+}
+
+fn compact_vector_segments(root: &Path, table: &str, table_state: &mut TableState) -> Result<()> {
+    let segments = &table_state.vector_segments;
+    if segments.len() <= 1 && segments.iter().all(|file| file.level > 0) {
+        return Ok(());
+    }
+
+    let entries: Vec<VectorEntry> = vecseg::merge_segments(&table_state.vector_segments)?
+        .into_values()
+        .filter(|entry| entry.vector.is_some())
+        .collect();
+    let seq = table_state.next_sst_seq;
+    table_state.next_sst_seq += 1;
+    // The merged segment has the highest seq, so it wins if a crash leaves the inputs behind.
+    let merged = vecseg::write_segment(&sst::table_dir(root, table), 1, seq, &entries)?;
+    vecseg::remove_files(&table_state.vector_segments)?;
+    table_state.vector_segments = vec![merged];
+    Ok(())
+}
+
+/// `StoreEmbedding` records reproducing the embeddings held in a table's vector segments.
+fn vector_segment_records(root: &Path, table: &str) -> Result<Vec<WalRecord>> {
+    let segments = vecseg::list_segments(&sst::table_dir(root, table))?;
+    Ok(vecseg::merge_segments(&segments)?
+        .into_values()
+        .filter_map(|entry| {
+            Some(WalRecord::StoreEmbedding {
+                table: table.to_string(),
+                row_id: entry.row_id,
+                vector: entry.vector?,
+                norm: entry.norm,
+            })
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests;
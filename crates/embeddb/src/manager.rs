@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+
+use crate::{Config, EmbedDb};
+
+/// Opens and caches one `EmbedDb` per namespace, each in its own subdirectory of a root
+/// directory, so one process can host isolated databases.
+///
+/// Every namespace is opened with a copy of the template config (WAL, codec, cache, and worker
+/// settings) whose `data_dir` points at `<root>/<namespace>`. Namespaces share nothing else: each
+/// has its own WAL, tables, and directory lock.
+pub struct EmbedDbManager {
+    root: PathBuf,
+    template: Config,
+    open: Mutex<HashMap<String, Arc<EmbedDb>>>,
+}
+
+impl EmbedDbManager {
+    /// `template.data_dir` is ignored; namespaces live under `root`.
+    pub fn new(root: PathBuf, template: Config) -> Self {
+        Self {
+            root,
+            template,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Names of the namespaces on disk, sorted.
+    pub fn list_namespaces(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str() {
+                if validate_namespace(name).is_ok() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Creates the namespace's directory and opens it. Fails if it already exists.
+    pub fn create_namespace(&self, name: &str) -> Result<Arc<EmbedDb>> {
+        validate_namespace(name)?;
+        if self.template.read_only {
+            return Err(anyhow!("database is open read-only"));
+        }
+        let mut open = self.lock();
+        let dir = self.root.join(name);
+        if open.contains_key(name) || dir.exists() {
+            return Err(anyhow!("namespace '{name}' already exists"));
+        }
+        fs::create_dir_all(&dir)?;
+        let db = Arc::new(EmbedDb::open(self.config_for(dir))?);
+        open.insert(name.to_string(), db.clone());
+        Ok(db)
+    }
+
+    /// Returns the namespace's database, opening it on first use. Fails if the namespace was
+    /// never created.
+    pub fn namespace(&self, name: &str) -> Result<Arc<EmbedDb>> {
+        validate_namespace(name)?;
+        let mut open = self.lock();
+        if let Some(db) = open.get(name) {
+            return Ok(db.clone());
+        }
+        let dir = self.root.join(name);
+        if !dir.is_dir() {
+            return Err(anyhow!("namespace '{name}' not found"));
+        }
+        let db = Arc::new(EmbedDb::open(self.config_for(dir))?);
+        open.insert(name.to_string(), db.clone());
+        Ok(db)
+    }
+
+    /// Closes every opened namespace (see `EmbedDb::close`) and forgets it, so a later
+    /// `namespace` call reopens it. Returns the first error after trying them all.
+    pub fn close_all(&self) -> Result<()> {
+        let mut first_err = None;
+        for (_, db) in self.lock().drain() {
+            if let Err(err) = db.close() {
+                first_err.get_or_insert(err);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+
+    fn config_for(&self, data_dir: PathBuf) -> Config {
+        Config {
+            data_dir,
+            ..self.template.clone()
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<EmbedDb>>> {
+        // The map only ever gains fully opened databases, so a panic can't leave it inconsistent.
+        self.open
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Namespace names become directory names: 1-64 ASCII letters, digits, `-`, or `_`.
+fn validate_namespace(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid namespace '{name}' (use 1-64 letters, digits, '-' or '_')"
+        ))
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Result};
+
+use crate::DistanceMetric;
+
+/// Application-defined distance between a query and a stored embedding; lower means closer.
+///
+/// Custom metrics are always given the original (un-normalized) f32 vectors, both of the same
+/// dimension.
+pub trait DistanceFn: Send + Sync {
+    fn distance(&self, query: &[f32], vector: &[f32]) -> f32;
+}
+
+impl<F> DistanceFn for F
+where
+    F: Fn(&[f32], &[f32]) -> f32 + Send + Sync,
+{
+    fn distance(&self, query: &[f32], vector: &[f32]) -> f32 {
+        self(query, vector)
+    }
+}
+
+/// Maps the names accepted by named searches onto built-in metrics.
+pub(crate) fn builtin_metric(name: &str) -> Option<DistanceMetric> {
+    match name.to_ascii_lowercase().as_str() {
+        "l2" => Some(DistanceMetric::L2),
+        "cosine" => Some(DistanceMetric::Cosine),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct MetricRegistry {
+    metrics: RwLock<HashMap<String, Arc<dyn DistanceFn>>>,
+}
+
+impl MetricRegistry {
+    pub(crate) fn register(&self, name: &str, metric: Arc<dyn DistanceFn>) -> Result<()> {
+        if name.trim().is_empty() {
+            return Err(anyhow!("metric name must not be empty"));
+        }
+        if builtin_metric(name).is_some() {
+            return Err(anyhow!(
+                "metric name '{name}' is reserved for a built-in metric"
+            ));
+        }
+        self.metrics
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(name.to_string(), metric);
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Result<Arc<dyn DistanceFn>> {
+        self.metrics
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("metric '{name}' is not registered"))
+    }
+}
+
+impl fmt::Debug for MetricRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self
+            .metrics
+            .read()
+            .map(|m| m.keys().cloned().collect())
+            .unwrap_or_default();
+        f.debug_struct("MetricRegistry")
+            .field("names", &names)
+            .finish()
+    }
+}
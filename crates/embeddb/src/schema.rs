@@ -1,11 +1,15 @@
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::EmbeddingStatus;
+use crate::index::IndexSpec;
+use crate::vector::VectorEncoding;
+use crate::{DistanceMetric, EmbeddingStatus};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum DataType {
@@ -21,6 +25,18 @@ pub struct Column {
     pub name: String,
     pub data_type: DataType,
     pub nullable: bool,
+    /// When set, the column's value is computed from other columns on every write; callers must
+    /// not supply it.
+    #[serde(default)]
+    pub generated: Option<ColumnExpr>,
+    /// Stored by inserts that leave the column out.
+    #[serde(default)]
+    pub default: Option<ColumnDefault>,
+    #[serde(default)]
+    pub constraints: ColumnConstraints,
+    /// How filters compare the column's values.
+    #[serde(default)]
+    pub collation: Collation,
 }
 
 impl Column {
@@ -29,18 +45,405 @@ impl Column {
             name: name.into(),
             data_type,
             nullable,
+            generated: None,
+            default: None,
+            constraints: ColumnConstraints::default(),
+            collation: Collation::default(),
         }
     }
+
+    pub fn with_generated(mut self, expr: ColumnExpr) -> Self {
+        self.generated = Some(expr);
+        self
+    }
+
+    pub fn with_default(mut self, default: ColumnDefault) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    pub fn with_constraints(mut self, constraints: ColumnConstraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+}
+
+/// How filter conditions compare a `String` or `Bytes` column's values: `=`, `!=`, and
+/// `CONTAINS`, and the ordering used by `<`, `<=`, `>`, and `>=`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Collation {
+    /// Byte-wise comparison (of UTF-8 for strings), so `"B" < "a"`.
+    #[default]
+    Binary,
+    /// Strings compare by their Unicode lowercase forms, so `name = "ALICE"` matches `"alice"`.
+    /// `String` columns only.
+    CaseInsensitive,
+}
+
+impl Collation {
+    pub(crate) fn compare(self, a: &Value, b: &Value) -> Option<Ordering> {
+        match (a, b) {
+            (Value::String(a), Value::String(b)) => Some(match self {
+                Collation::Binary => a.cmp(b),
+                Collation::CaseInsensitive => a.to_lowercase().cmp(&b.to_lowercase()),
+            }),
+            (Value::Bytes(a), Value::Bytes(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    /// Whether string `haystack` contains `needle` under this collation.
+    pub(crate) fn contains(self, haystack: &str, needle: &str) -> bool {
+        match self {
+            Collation::Binary => haystack.contains(needle),
+            Collation::CaseInsensitive => haystack.to_lowercase().contains(&needle.to_lowercase()),
+        }
+    }
+}
+
+/// Validation rules checked by `TableSchema::validate_row` for non-null values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnConstraints {
+    /// Maximum length in characters (`String`) or bytes (`Bytes`).
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    /// Regex a `String` value must match.
+    #[serde(default)]
+    pub pattern: Option<Pattern>,
+    /// Inclusive lower bound for `Int`/`Float` values.
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Inclusive upper bound for `Int`/`Float` values.
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// Closed set of accepted values.
+    #[serde(default)]
+    pub allowed_values: Option<Vec<Value>>,
+}
+
+impl ColumnConstraints {
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self> {
+        self.pattern = Some(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn with_range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn with_allowed_values(mut self, values: Vec<Value>) -> Self {
+        self.allowed_values = Some(values);
+        self
+    }
+
+    fn validate_for(&self, column: &str, data_type: &DataType) -> Result<()> {
+        let unsupported = |rule: &str| {
+            Err(anyhow!(
+                "column '{column}': {rule} constraint is not supported for {data_type:?}"
+            ))
+        };
+        if self.max_length.is_some() && !matches!(data_type, DataType::String | DataType::Bytes) {
+            return unsupported("max_length");
+        }
+        if self.pattern.is_some() && *data_type != DataType::String {
+            return unsupported("pattern");
+        }
+        if (self.min.is_some() || self.max.is_some())
+            && !matches!(data_type, DataType::Int | DataType::Float)
+        {
+            return unsupported("min/max");
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Err(anyhow!(
+                    "column '{column}': min {min} is greater than max {max}"
+                ));
+            }
+        }
+        if let Some(allowed) = &self.allowed_values {
+            if let Some(bad) = allowed
+                .iter()
+                .find(|v| **v == Value::Null || !v.matches(data_type))
+            {
+                return Err(anyhow!(
+                    "column '{column}': allowed value {bad:?} is not a {data_type:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, column: &str, value: &Value) -> Result<()> {
+        if *value == Value::Null {
+            return Ok(());
+        }
+        if let Some(max_length) = self.max_length {
+            let len = match value {
+                Value::String(v) => v.chars().count(),
+                Value::Bytes(v) => v.len(),
+                _ => 0,
+            };
+            if len > max_length {
+                return Err(anyhow!(
+                    "column '{column}' exceeds max length {max_length} (got {len})"
+                ));
+            }
+        }
+        if let (Some(pattern), Value::String(v)) = (&self.pattern, value) {
+            if !pattern.0.is_match(v) {
+                return Err(anyhow!(
+                    "column '{column}' value {v:?} does not match pattern '{}'",
+                    pattern.0.as_str()
+                ));
+            }
+        }
+        let number = match value {
+            Value::Int(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            _ => None,
+        };
+        if let Some(x) = number {
+            if let Some(min) = self.min.filter(|min| x < *min) {
+                return Err(anyhow!(
+                    "column '{column}' value {x} is below minimum {min}"
+                ));
+            }
+            if let Some(max) = self.max.filter(|max| x > *max) {
+                return Err(anyhow!(
+                    "column '{column}' value {x} is above maximum {max}"
+                ));
+            }
+        }
+        if let Some(allowed) = &self.allowed_values {
+            if !allowed.contains(value) {
+                return Err(anyhow!(
+                    "column '{column}' value {value:?} is not one of the allowed values"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A compiled regex that serializes as its source string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(Pattern)
+            .map_err(|err| anyhow!("invalid pattern '{pattern}': {err}"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Pattern::new(&value)
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(value: Pattern) -> Self {
+        value.0.as_str().to_string()
+    }
+}
+
+/// What an insert stores in a column it leaves out. Updates never apply defaults.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ColumnDefault {
+    /// A constant of the column's type.
+    Value(Value),
+    /// The insert time in Unix milliseconds, for `Int` columns.
+    Now,
+    /// A random version 4 UUID, hyphenated and lowercase, for `String` columns.
+    UuidV4,
+}
+
+impl ColumnDefault {
+    fn value(&self, now_ms: u64) -> Value {
+        match self {
+            ColumnDefault::Value(value) => value.clone(),
+            ColumnDefault::Now => Value::Int(now_ms as i64),
+            ColumnDefault::UuidV4 => Value::String(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    fn validate_for(&self, column: &Column) -> Result<()> {
+        let valid = match self {
+            ColumnDefault::Value(Value::Null) => false,
+            ColumnDefault::Value(value) => value.matches(&column.data_type),
+            ColumnDefault::Now => column.data_type == DataType::Int,
+            ColumnDefault::UuidV4 => column.data_type == DataType::String,
+        };
+        if !valid {
+            return Err(anyhow!(
+                "default {self:?} does not fit {:?} column '{}'",
+                column.data_type,
+                column.name
+            ));
+        }
+        if let ColumnDefault::Value(value) = self {
+            column.constraints.check(&column.name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Deterministic expression over non-generated columns of the same row.
+///
+/// Single-input expressions yield `Null` when their input is null or missing; `Concat` skips
+/// such inputs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ColumnExpr {
+    Lowercase(String),
+    Uppercase(String),
+    Trim(String),
+    /// Character count of a string, or byte count of bytes.
+    Length(String),
+    Concat {
+        columns: Vec<String>,
+        #[serde(default)]
+        separator: String,
+    },
+}
+
+impl ColumnExpr {
+    fn inputs(&self) -> Vec<&str> {
+        match self {
+            ColumnExpr::Lowercase(col)
+            | ColumnExpr::Uppercase(col)
+            | ColumnExpr::Trim(col)
+            | ColumnExpr::Length(col) => vec![col.as_str()],
+            ColumnExpr::Concat { columns, .. } => columns.iter().map(String::as_str).collect(),
+        }
+    }
+
+    fn output_type(&self) -> DataType {
+        match self {
+            ColumnExpr::Length(_) => DataType::Int,
+            _ => DataType::String,
+        }
+    }
+
+    fn accepts_input(&self, data_type: &DataType) -> bool {
+        match self {
+            ColumnExpr::Lowercase(_) | ColumnExpr::Uppercase(_) | ColumnExpr::Trim(_) => {
+                *data_type == DataType::String
+            }
+            ColumnExpr::Length(_) => matches!(data_type, DataType::String | DataType::Bytes),
+            ColumnExpr::Concat { .. } => true,
+        }
+    }
+
+    fn evaluate(&self, fields: &BTreeMap<String, Value>) -> Result<Value> {
+        let input = |col: &str| fields.get(col).unwrap_or(&Value::Null);
+        Ok(match self {
+            ColumnExpr::Lowercase(col) => match input(col) {
+                Value::String(v) => Value::String(v.to_lowercase()),
+                _ => Value::Null,
+            },
+            ColumnExpr::Uppercase(col) => match input(col) {
+                Value::String(v) => Value::String(v.to_uppercase()),
+                _ => Value::Null,
+            },
+            ColumnExpr::Trim(col) => match input(col) {
+                Value::String(v) => Value::String(v.trim().to_string()),
+                _ => Value::Null,
+            },
+            ColumnExpr::Length(col) => match input(col) {
+                Value::String(v) => Value::Int(v.chars().count() as i64),
+                Value::Bytes(v) => Value::Int(v.len() as i64),
+                _ => Value::Null,
+            },
+            ColumnExpr::Concat { columns, separator } => {
+                let mut parts = Vec::new();
+                for col in columns {
+                    match input(col) {
+                        Value::Null => {}
+                        value => parts.push(value.as_string()?),
+                    }
+                }
+                Value::String(parts.join(separator))
+            }
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TableSchema {
     pub columns: Vec<Column>,
+    /// `Int` column holding the Unix time, in seconds, at which a row expires; rows where it is
+    /// null never do. Expired rows are deleted by `EmbedDb::expire_rows`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry_column: Option<String>,
+    /// When set, deleting a row hides it instead of removing it: see `EmbedDb::restore_row` and
+    /// `EmbedDb::purge_hidden_rows`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft_delete: Option<SoftDelete>,
+}
+
+/// Soft-delete settings of a table. Hidden rows are left out of reads, scans, and searches but
+/// keep their data and embeddings until they are restored or purged.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoftDelete {
+    /// Seconds a hidden row stays restorable; after that `EmbedDb::compact_table` purges it.
+    /// Without one, hidden rows are only purged by `EmbedDb::purge_hidden_rows`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_secs: Option<u64>,
+}
+
+impl SoftDelete {
+    pub fn with_retention_secs(mut self, seconds: u64) -> Self {
+        self.retention_secs = Some(seconds);
+        self
+    }
 }
 
 impl TableSchema {
     pub fn new(columns: Vec<Column>) -> Self {
-        Self { columns }
+        Self {
+            columns,
+            expiry_column: None,
+            soft_delete: None,
+        }
+    }
+
+    pub fn with_expiry_column(mut self, column: impl Into<String>) -> Self {
+        self.expiry_column = Some(column.into());
+        self
+    }
+
+    pub fn with_soft_delete(mut self, soft_delete: SoftDelete) -> Self {
+        self.soft_delete = Some(soft_delete);
+        self
+    }
+
+    /// When the row with `fields` expires according to the expiry column, in Unix milliseconds.
+    pub(crate) fn expires_at_ms(&self, fields: &BTreeMap<String, Value>) -> Option<u64> {
+        match fields.get(self.expiry_column.as_ref()?)? {
+            Value::Int(seconds) => Some(u64::try_from(*seconds).unwrap_or(0).saturating_mul(1000)),
+            _ => None,
+        }
     }
 
     pub fn validate_schema(&self) -> Result<()> {
@@ -50,6 +453,99 @@ impl TableSchema {
                 return Err(anyhow!("duplicate column: {}", col.name));
             }
         }
+        for col in &self.columns {
+            col.constraints.validate_for(&col.name, &col.data_type)?;
+            if let Some(default) = &col.default {
+                if col.generated.is_some() {
+                    return Err(anyhow!(
+                        "generated column '{}' cannot have a default",
+                        col.name
+                    ));
+                }
+                default.validate_for(col)?;
+            }
+            if col.collation == Collation::CaseInsensitive && col.data_type != DataType::String {
+                return Err(anyhow!(
+                    "column '{}': CaseInsensitive collation is only supported for String",
+                    col.name
+                ));
+            }
+        }
+        if let Some(name) = &self.expiry_column {
+            match self.columns.iter().find(|col| &col.name == name) {
+                Some(col) if col.data_type == DataType::Int => {}
+                Some(_) => return Err(anyhow!("expiry column '{name}' must be Int")),
+                None => return Err(anyhow!("unknown expiry column '{name}'")),
+            }
+        }
+        for col in &self.columns {
+            let Some(expr) = &col.generated else {
+                continue;
+            };
+            if col.data_type != expr.output_type() {
+                return Err(anyhow!(
+                    "generated column '{}' must be {:?}",
+                    col.name,
+                    expr.output_type()
+                ));
+            }
+            for input in expr.inputs() {
+                let source = self
+                    .columns
+                    .iter()
+                    .find(|c| c.name == input)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "generated column '{}' references unknown column '{input}'",
+                            col.name
+                        )
+                    })?;
+                if source.generated.is_some() {
+                    return Err(anyhow!(
+                        "generated column '{}' cannot reference generated column '{input}'",
+                        col.name
+                    ));
+                }
+                if !expr.accepts_input(&source.data_type) {
+                    return Err(anyhow!(
+                        "generated column '{}' cannot use {:?} column '{input}'",
+                        col.name,
+                        source.data_type
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the defaults of columns an insert leaves out, as of `now_ms`.
+    pub fn apply_defaults(&self, fields: &mut BTreeMap<String, Value>, now_ms: u64) {
+        for col in &self.columns {
+            if let Some(default) = &col.default {
+                if !fields.contains_key(&col.name) {
+                    fields.insert(col.name.clone(), default.value(now_ms));
+                }
+            }
+        }
+    }
+
+    /// Fills in generated columns from the caller-supplied fields. Rejects writes that set a
+    /// generated column directly.
+    pub fn apply_generated(&self, fields: &mut BTreeMap<String, Value>) -> Result<()> {
+        for col in &self.columns {
+            if col.generated.is_some() && fields.contains_key(&col.name) {
+                return Err(anyhow!(
+                    "column '{}' is generated and cannot be set",
+                    col.name
+                ));
+            }
+        }
+        for col in &self.columns {
+            if let Some(expr) = &col.generated {
+                let value = expr.evaluate(fields)?;
+                fields.insert(col.name.clone(), value);
+            }
+        }
         Ok(())
     }
 
@@ -60,6 +556,7 @@ impl TableSchema {
                     if !value.matches(&col.data_type) {
                         return Err(anyhow!("column '{}' type mismatch", col.name));
                     }
+                    col.constraints.check(&col.name, value)?;
                 }
                 None => {
                     if !col.nullable {
@@ -77,6 +574,114 @@ impl TableSchema {
     }
 }
 
+/// A schema change made by `EmbedDb::alter_table`. Rows already on disk are not rewritten; they
+/// are brought up to date as they are read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AlterTableOp {
+    /// Adds a column that existing rows read as `default`. A `Null` default leaves them without a
+    /// value, so it needs a nullable column. Generated columns cannot be added.
+    AddColumn {
+        column: Column,
+        default: Value,
+    },
+    DropColumn {
+        name: String,
+    },
+    RenameColumn {
+        from: String,
+        to: String,
+    },
+}
+
+impl AlterTableOp {
+    /// The schema after this change, checked like a new table's schema.
+    pub(crate) fn apply_to_schema(&self, schema: &TableSchema) -> Result<TableSchema> {
+        let mut next = schema.clone();
+        let position = |name: &str| {
+            schema
+                .columns
+                .iter()
+                .position(|col| col.name == name)
+                .ok_or_else(|| anyhow!("unknown column '{name}'"))
+        };
+        match self {
+            AlterTableOp::AddColumn { column, default } => {
+                if column.generated.is_some() {
+                    return Err(anyhow!(
+                        "generated column '{}' cannot be added to an existing table",
+                        column.name
+                    ));
+                }
+                match default {
+                    Value::Null if !column.nullable => {
+                        return Err(anyhow!(
+                            "column '{}' is not nullable and needs a default",
+                            column.name
+                        ));
+                    }
+                    Value::Null => {}
+                    value if !value.matches(&column.data_type) => {
+                        return Err(anyhow!(
+                            "default for column '{}' type mismatch",
+                            column.name
+                        ));
+                    }
+                    value => column.constraints.check(&column.name, value)?,
+                }
+                next.columns.push(column.clone());
+            }
+            AlterTableOp::DropColumn { name } => {
+                next.columns.remove(position(name)?);
+            }
+            AlterTableOp::RenameColumn { from, to } => {
+                let idx = position(from)?;
+                next.columns[idx].name = to.clone();
+                if next.expiry_column.as_ref() == Some(from) {
+                    next.expiry_column = Some(to.clone());
+                }
+            }
+        }
+        // Also rejects dropping the expiry column, or a column a generated column reads, and
+        // renaming the latter.
+        next.validate_schema()?;
+        Ok(next)
+    }
+
+    /// Rewrites the fields of a row written before this change.
+    pub(crate) fn apply_to_row(&self, fields: &mut BTreeMap<String, Value>) {
+        match self {
+            AlterTableOp::AddColumn { column, default } => {
+                if *default != Value::Null && !fields.contains_key(&column.name) {
+                    fields.insert(column.name.clone(), default.clone());
+                }
+            }
+            AlterTableOp::DropColumn { name } => {
+                fields.remove(name);
+            }
+            AlterTableOp::RenameColumn { from, to } => {
+                if let Some(value) = fields.remove(from) {
+                    fields.insert(to.clone(), value);
+                }
+            }
+        }
+    }
+}
+
+/// An `AlterTableOp` still owed to rows in SST files numbered below `before_seq`, which were
+/// written before the change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaChange {
+    pub before_seq: u64,
+    pub op: AlterTableOp,
+}
+
+/// Brings a row read from SST file `seq` up to the current schema.
+pub(crate) fn upgrade_row(changes: &[SchemaChange], seq: u64, row: &mut RowData) {
+    for change in changes.iter().filter(|change| seq < change.before_seq) {
+        change.op.apply_to_row(&mut row.fields);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Value {
     Int(i64),
@@ -112,44 +717,344 @@ impl Value {
     }
 }
 
+/// Filter column for `RowData::created_at_ms`, an `Int`. A schema column of the same name takes
+/// its place.
+pub const CREATED_AT_COLUMN: &str = "created_at_ms";
+/// Filter column for `RowData::updated_at_ms`, like `CREATED_AT_COLUMN`.
+pub const UPDATED_AT_COLUMN: &str = "updated_at_ms";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RowData {
     pub id: u64,
+    /// Incremented by every update; rows start at 1. Rows persisted before versions were tracked
+    /// read as 0 until they are next written.
+    #[serde(default)]
+    pub version: u64,
+    /// Unix milliseconds at which the row was inserted. Rows written before timestamps were
+    /// tracked read as 0, and keep it through updates.
+    #[serde(default)]
+    pub created_at_ms: u64,
+    /// Unix milliseconds of the row's last insert or update; 0 like `created_at_ms`.
+    #[serde(default)]
+    pub updated_at_ms: u64,
     pub fields: BTreeMap<String, Value>,
 }
 
+impl RowData {
+    /// The row's value for system column `column`, if it names one.
+    pub(crate) fn system_value(&self, column: &str) -> Option<Value> {
+        let ms = match column {
+            CREATED_AT_COLUMN => self.created_at_ms,
+            UPDATED_AT_COLUMN => self.updated_at_ms,
+            _ => return None,
+        };
+        Some(Value::Int(i64::try_from(ms).unwrap_or(i64::MAX)))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingSpec {
     pub source_fields: Vec<String>,
+    /// Metric the table's embeddings are intended for. Cosine tables store unit-normalized
+    /// vectors so cosine search only needs a dot product.
+    #[serde(default)]
+    pub metric: Option<DistanceMetric>,
+    /// In-memory representation of resident vectors. WAL records always carry f32 values.
+    #[serde(default)]
+    pub vector_encoding: VectorEncoding,
+    /// Name of a metric registered with `EmbedDb::register_metric`, used as the table default
+    /// instead of a built-in `metric`.
+    #[serde(default)]
+    pub custom_metric: Option<String>,
+    /// Vector index maintained for kNN search. HNSW indexes are built for the table's default
+    /// built-in metric; searches with any other metric use the exact scan.
+    #[serde(default)]
+    pub index: IndexSpec,
+    /// Expected embedding length. Vectors of any other length fail their embedding job instead
+    /// of being stored; when unset, the first stored embedding sets the table's dimension.
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+    /// Further vectors embedded for each row alongside the one above, each from its own source
+    /// fields. Searches pick one by name; named vectors use the table's metric and are always
+    /// searched with an exact scan.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vectors: Vec<NamedVectorSpec>,
+    /// How the table's failed embedding jobs are retried.
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Model the table's embeddings are computed with. Each embedding records the model of the
+    /// spec it was stored under, and `EmbedDb::reembed_table` re-embeds the rows whose model
+    /// differs from a new one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<EmbeddingModel>,
+}
+
+/// An embedding model and, optionally, its version, as declared by `EmbeddingSpec::model`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModel {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl EmbeddingModel {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: None,
+        }
+    }
+
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+}
+
+impl std::fmt::Display for EmbeddingModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{version}", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// Retry schedule for failed embedding jobs, set per table with `EmbeddingSpec::with_retry`.
+///
+/// A job that fails waits `base_ms`, then twice as long after each further failure up to
+/// `cap_ms`, and is marked `Failed` once it has failed `max_attempts` times.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_ms: u64,
+    pub cap_ms: u64,
+    /// Fraction (0 to 1) of each delay that is randomized, so jobs that failed together (say,
+    /// during a provider outage) don't all retry at the same moment.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_ms: 250,
+            cap_ms: 30_000,
+            jitter: 0.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retrying a job that has failed `attempts` times. `seed` picks the jittered
+    /// part, so different jobs spread out.
+    pub(crate) fn backoff_ms(&self, attempts: u32, seed: u64) -> u64 {
+        let exp = attempts.saturating_sub(1).min(20);
+        let delay = self.base_ms.saturating_mul(1u64 << exp).min(self.cap_ms);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        // splitmix64 finalizer: spreads consecutive seeds over the whole range.
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        let unit = (z >> 11) as f64 / (1u64 << 53) as f64;
+        let jittered = delay as f64 * self.jitter.min(1.0);
+        (delay as f64 - jittered * unit).round() as u64
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            return Err(anyhow!("retry max_attempts must be at least 1"));
+        }
+        if self.cap_ms < self.base_ms {
+            return Err(anyhow!("retry cap_ms must be at least base_ms"));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(anyhow!("retry jitter must be between 0 and 1"));
+        }
+        Ok(())
+    }
+}
+
+/// A named vector field declared on an `EmbeddingSpec`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamedVectorSpec {
+    pub name: String,
+    pub source_fields: Vec<String>,
+    /// Expected length of this vector; when unset, the first one stored sets it.
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+}
+
+impl NamedVectorSpec {
+    pub fn new<S: Into<String>>(name: impl Into<String>, fields: Vec<S>) -> Self {
+        Self {
+            name: name.into(),
+            source_fields: fields.into_iter().map(Into::into).collect(),
+            dimensions: None,
+        }
+    }
+
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn input_string(&self, fields: &BTreeMap<String, Value>) -> Result<String> {
+        join_source_fields(&self.source_fields, fields)
+    }
 }
 
 impl EmbeddingSpec {
     pub fn new<S: Into<String>>(fields: Vec<S>) -> Self {
         Self {
             source_fields: fields.into_iter().map(Into::into).collect(),
+            metric: None,
+            vector_encoding: VectorEncoding::F32,
+            custom_metric: None,
+            index: IndexSpec::Flat,
+            dimensions: None,
+            vectors: Vec::new(),
+            retry: RetryPolicy::default(),
+            model: None,
         }
     }
 
-    pub fn input_string(&self, fields: &BTreeMap<String, Value>) -> Result<String> {
-        let mut parts = Vec::new();
-        for field in &self.source_fields {
-            let value = fields
-                .get(field)
-                .ok_or_else(|| anyhow!("missing embedding field '{}'", field))?;
-            parts.push(value.as_string()?);
+    pub fn with_vector_encoding(mut self, encoding: VectorEncoding) -> Self {
+        self.vector_encoding = encoding;
+        self
+    }
+
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    pub fn with_custom_metric(mut self, name: impl Into<String>) -> Self {
+        self.custom_metric = Some(name.into());
+        self
+    }
+
+    pub fn with_index(mut self, index: IndexSpec) -> Self {
+        self.index = index;
+        self
+    }
+
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn with_vector(mut self, vector: NamedVectorSpec) -> Self {
+        self.vectors.push(vector);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_model(mut self, model: EmbeddingModel) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// The named vector `name`, if the spec declares one.
+    pub fn vector(&self, name: &str) -> Option<&NamedVectorSpec> {
+        self.vectors.iter().find(|vector| vector.name == name)
+    }
+
+    /// Every column some vector of the spec is embedded from.
+    pub fn all_source_fields(&self) -> impl Iterator<Item = &String> {
+        self.source_fields
+            .iter()
+            .chain(self.vectors.iter().flat_map(|vector| &vector.source_fields))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (idx, vector) in self.vectors.iter().enumerate() {
+            if vector.name.is_empty() {
+                return Err(anyhow!("named vectors need a non-empty name"));
+            }
+            if self.vectors[..idx]
+                .iter()
+                .any(|other| other.name == vector.name)
+            {
+                return Err(anyhow!("duplicate named vector '{}'", vector.name));
+            }
+            if vector.dimensions == Some(0) {
+                return Err(anyhow!(
+                    "dimensions of vector '{}' must be at least 1",
+                    vector.name
+                ));
+            }
+        }
+        if self.metric.is_some() && self.custom_metric.is_some() {
+            return Err(anyhow!(
+                "embedding spec cannot set both a built-in metric and a custom metric"
+            ));
+        }
+        if self.dimensions == Some(0) {
+            return Err(anyhow!("embedding dimensions must be at least 1"));
         }
-        Ok(parts.join("\n"))
+        if self.custom_metric.is_some() && self.index != IndexSpec::Flat {
+            return Err(anyhow!(
+                "vector indexes require a built-in metric, not a custom metric"
+            ));
+        }
+        self.retry.validate()?;
+        self.index.validate()
+    }
+
+    /// Whether resident vectors are stored in a lossy encoding, so searches can re-score
+    /// candidates against the exact vectors.
+    pub(crate) fn is_quantized(&self) -> bool {
+        self.vector_encoding != VectorEncoding::F32
+    }
+
+    pub(crate) fn normalizes_vectors(&self) -> bool {
+        self.metric == Some(DistanceMetric::Cosine)
+    }
+
+    pub fn input_string(&self, fields: &BTreeMap<String, Value>) -> Result<String> {
+        join_source_fields(&self.source_fields, fields)
     }
 
+    /// Hash of every embedding input of a row, so a change to any vector's source fields
+    /// re-embeds it.
     pub fn content_hash(&self, fields: &BTreeMap<String, Value>) -> Result<String> {
         let input = self.input_string(fields)?;
         let mut hasher = Sha256::new();
         hasher.update(input.as_bytes());
+        for vector in &self.vectors {
+            hasher.update([0]);
+            hasher.update(vector.name.as_bytes());
+            hasher.update([0]);
+            hasher.update(vector.input_string(fields)?.as_bytes());
+        }
         let result = hasher.finalize();
         Ok(format!("{:x}", result))
     }
 }
 
+fn join_source_fields(
+    source_fields: &[String],
+    fields: &BTreeMap<String, Value>,
+) -> Result<String> {
+    let mut parts = Vec::new();
+    for field in source_fields {
+        let value = fields
+            .get(field)
+            .ok_or_else(|| anyhow!("missing embedding field '{}'", field))?;
+        parts.push(value.as_string()?);
+    }
+    Ok(parts.join("\n"))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingMeta {
     pub status: EmbeddingStatus,
@@ -160,4 +1065,7 @@ pub struct EmbeddingMeta {
     // Unix epoch millis when this job is eligible to be retried.
     // 0 means "retry immediately".
     pub next_retry_at_ms: u64,
+    // Model of the spec the row's embedding was stored under; `None` until it is embedded, and
+    // for embeddings stored before the spec named a model.
+    pub model: Option<EmbeddingModel>,
 }
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// On-disk encoding used for row payloads in SST files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RowCodecKind {
+    #[default]
+    Json,
+    Bincode,
+    MessagePack,
+}
+
+impl RowCodecKind {
+    pub fn id(self) -> u8 {
+        match self {
+            RowCodecKind::Json => 0,
+            RowCodecKind::Bincode => 1,
+            RowCodecKind::MessagePack => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(RowCodecKind::Json),
+            1 => Ok(RowCodecKind::Bincode),
+            2 => Ok(RowCodecKind::MessagePack),
+            other => Err(anyhow!("unknown row codec id {other}")),
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>> {
+        Ok(match self {
+            RowCodecKind::Json => serde_json::to_vec(value)?,
+            RowCodecKind::Bincode => bincode::serialize(value)?,
+            RowCodecKind::MessagePack => rmp_serde::to_vec(value)?,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T> {
+        Ok(match self {
+            RowCodecKind::Json => serde_json::from_slice(data)?,
+            RowCodecKind::Bincode => bincode::deserialize(data)?,
+            RowCodecKind::MessagePack => rmp_serde::from_slice(data)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{RowData, Value};
+    use crate::storage::sst::SstEntry;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn all_codecs_roundtrip_entries() {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String("hello".to_string()));
+        fields.insert("score".to_string(), Value::Float(0.5));
+        fields.insert("blob".to_string(), Value::Bytes(vec![1, 2, 3]));
+        fields.insert("missing".to_string(), Value::Null);
+        let entries = vec![
+            SstEntry {
+                row_id: 1,
+                row: Some(RowData {
+                    id: 1,
+                    version: 3,
+                    fields,
+                    created_at_ms: 0,
+                    updated_at_ms: 0,
+                }),
+            },
+            SstEntry {
+                row_id: 2,
+                row: None,
+            },
+        ];
+
+        for kind in [
+            RowCodecKind::Json,
+            RowCodecKind::Bincode,
+            RowCodecKind::MessagePack,
+        ] {
+            assert_eq!(RowCodecKind::from_id(kind.id()).unwrap(), kind);
+            let data = kind.encode(&entries).unwrap();
+            let decoded: Vec<SstEntry> = kind.decode(&data).unwrap();
+            assert_eq!(decoded.len(), 2);
+            let row = decoded[0].row.as_ref().unwrap();
+            assert_eq!(row.version, 3);
+            assert_eq!(row.fields.get("score"), Some(&Value::Float(0.5)));
+            assert_eq!(row.fields.get("blob"), Some(&Value::Bytes(vec![1, 2, 3])));
+            assert!(decoded[1].row.is_none());
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! The file operations of the WAL and SST writers, routed through `crate::testing` when fault
+//! injection is compiled in and an injector covers the path.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+pub fn write_wal(path: &Path, file: &mut File, data: &[u8]) -> io::Result<()> {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(injector) = crate::testing::injector_for(path) {
+        return injector.write_wal(path, file, data);
+    }
+    let _ = path;
+    file.write_all(data)
+}
+
+pub fn sync_wal(path: &Path, file: &File) -> io::Result<()> {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(injector) = crate::testing::injector_for(path) {
+        return injector.sync_wal(path, file);
+    }
+    let _ = path;
+    file.sync_data()
+}
+
+pub fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(injector) = crate::testing::injector_for(path) {
+        return injector.write_file(path, data);
+    }
+    fs::write(path, data)
+}
+
+pub fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(injector) = crate::testing::injector_for(from) {
+        return injector.rename(from, to);
+    }
+    fs::rename(from, to)
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use crc32fast::Hasher;
+
+// Index files start with `EDBIDX` and a format version byte, then the generation (`u64`, the LSN
+// the index is current as of), the encoded index, and a trailing CRC32 of everything before it.
+// All integers are little-endian.
+const INDEX_MAGIC: &[u8; 6] = b"EDBIDX";
+const INDEX_FORMAT_VERSION: u8 = 1;
+const INDEX_FILENAME: &str = "hnsw.bin";
+
+/// A persisted vector index and the LSN it reflects the table's embeddings as of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexFile {
+    pub generation: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Directory of a table's persisted indexes, under its table directory.
+pub fn index_dir(table_dir: &Path) -> PathBuf {
+    table_dir.join("index")
+}
+
+pub fn write_index(table_dir: &Path, generation: u64, payload: &[u8]) -> Result<PathBuf> {
+    let dir = index_dir(table_dir);
+    fs::create_dir_all(&dir)?;
+    let mut data = Vec::with_capacity(INDEX_MAGIC.len() + 13 + payload.len());
+    data.extend_from_slice(INDEX_MAGIC);
+    data.push(INDEX_FORMAT_VERSION);
+    data.extend_from_slice(&generation.to_le_bytes());
+    data.extend_from_slice(payload);
+    let mut hasher = Hasher::new();
+    hasher.update(&data);
+    data.extend_from_slice(&hasher.finalize().to_le_bytes());
+
+    // Renamed into place so a reader never sees a torn file.
+    let path = dir.join(INDEX_FILENAME);
+    let staging = path.with_extension("tmp");
+    fs::write(&staging, data)?;
+    fs::rename(&staging, &path)?;
+    Ok(path)
+}
+
+/// The table's persisted index, or `None` if it has none.
+pub fn read_index(table_dir: &Path) -> Result<Option<IndexFile>> {
+    let path = index_dir(table_dir).join(INDEX_FILENAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read(&path)?;
+    let corrupt = || anyhow!("corrupt index file {}", path.display());
+    let header_len = INDEX_MAGIC.len() + 1 + 8;
+    if data.len() < header_len + 4 || &data[..INDEX_MAGIC.len()] != INDEX_MAGIC {
+        return Err(corrupt());
+    }
+    if data[INDEX_MAGIC.len()] != INDEX_FORMAT_VERSION {
+        return Err(anyhow!(
+            "unsupported index file version {} in {}",
+            data[INDEX_MAGIC.len()],
+            path.display()
+        ));
+    }
+    let (body, checksum) = data.split_at(data.len() - 4);
+    let mut hasher = Hasher::new();
+    hasher.update(body);
+    if hasher.finalize().to_le_bytes() != checksum {
+        return Err(corrupt());
+    }
+    let generation = body[INDEX_MAGIC.len() + 1..header_len]
+        .try_into()
+        .map(u64::from_le_bytes)
+        .map_err(|_| corrupt())?;
+    Ok(Some(IndexFile {
+        generation,
+        payload: body[header_len..].to_vec(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_files_roundtrip_and_reject_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_index(dir.path()).unwrap(), None);
+
+        let path = write_index(dir.path(), 42, b"graph").unwrap();
+        assert_eq!(
+            read_index(dir.path()).unwrap(),
+            Some(IndexFile {
+                generation: 42,
+                payload: b"graph".to_vec(),
+            })
+        );
+
+        let mut data = fs::read(&path).unwrap();
+        data[INDEX_MAGIC.len() + 1] ^= 1;
+        fs::write(&path, data).unwrap();
+        assert!(read_index(dir.path()).is_err());
+    }
+}
@@ -1,2 +1,7 @@
+pub mod codec;
+pub mod fault;
+pub mod indexfile;
+pub mod rawvec;
 pub mod sst;
+pub mod vecseg;
 pub mod wal;
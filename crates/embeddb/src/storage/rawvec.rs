@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+
+pub fn raw_vectors_path(table_dir: &Path) -> PathBuf {
+    table_dir.join("raw_vectors.bin")
+}
+
+/// On-disk copy of the exact f32 embeddings of a table whose resident vectors are quantized, used
+/// to re-score search candidates.
+///
+/// Records are `[dim: u32 LE][dim x f32 LE]` and the file is append-only. It is a cache of what
+/// the WAL already holds: it is truncated and rebuilt during WAL replay on every open, so it is
+/// never fsynced and superseded records are only reclaimed then.
+#[derive(Debug)]
+pub struct RawVectorStore {
+    path: PathBuf,
+    // Reads seek the shared handle, so concurrent searches take turns on it.
+    file: Mutex<File>,
+    offsets: HashMap<u64, (u64, u32)>,
+    end: u64,
+}
+
+impl RawVectorStore {
+    pub fn create(path: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            offsets: HashMap::new(),
+            end: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Points the store at its file's new path after the table directory was renamed.
+    pub fn relocate(&mut self, path: PathBuf) {
+        self.path = path;
+    }
+
+    pub fn append(&mut self, row_id: u64, vector: &[f32]) -> Result<()> {
+        let dim = u32::try_from(vector.len()).map_err(|_| anyhow!("vector is too long"))?;
+        let mut buf = Vec::with_capacity(4 + vector.len() * 4);
+        buf.extend_from_slice(&dim.to_le_bytes());
+        for x in vector {
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+        let file = self
+            .file
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.seek(SeekFrom::Start(self.end))?;
+        file.write_all(&buf)?;
+        self.offsets.insert(row_id, (self.end, dim));
+        self.end += buf.len() as u64;
+        Ok(())
+    }
+
+    pub fn read(&self, row_id: u64) -> Result<Option<Vec<f32>>> {
+        let Some(&(offset, dim)) = self.offsets.get(&row_id) else {
+            return Ok(None);
+        };
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.seek(SeekFrom::Start(offset + 4))?;
+        let mut buf = vec![0u8; dim as usize * 4];
+        file.read_exact(&mut buf)?;
+        Ok(Some(
+            buf.chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        ))
+    }
+
+    pub fn remove(&mut self, row_id: u64) {
+        self.offsets.remove(&row_id);
+    }
+
+    pub fn remove_where(&mut self, mut deleted: impl FnMut(u64) -> bool) {
+        self.offsets.retain(|row_id, _| !deleted(*row_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_read_overwrite_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = RawVectorStore::create(raw_vectors_path(dir.path())).unwrap();
+        store.append(1, &[1.5, -2.0]).unwrap();
+        store.append(2, &[0.25]).unwrap();
+        store.append(1, &[3.0, 4.0, 5.0]).unwrap();
+
+        assert_eq!(store.read(1).unwrap(), Some(vec![3.0, 4.0, 5.0]));
+        assert_eq!(store.read(2).unwrap(), Some(vec![0.25]));
+        store.remove(2);
+        assert_eq!(store.read(2).unwrap(), None);
+
+        // Re-creating truncates the previous contents.
+        drop(store);
+        let store = RawVectorStore::create(raw_vectors_path(dir.path())).unwrap();
+        assert_eq!(store.read(1).unwrap(), None);
+        assert_eq!(fs_len(store.path()), 0);
+    }
+
+    fn fs_len(path: &Path) -> u64 {
+        std::fs::metadata(path).unwrap().len()
+    }
+}
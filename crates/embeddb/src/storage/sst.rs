@@ -1,10 +1,31 @@
-use std::fs::{self, File};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::schema::RowData;
+use crate::schema::{self, RowData, SchemaChange, Value};
+use crate::storage::codec::RowCodecKind;
+use crate::storage::fault;
+
+// SST files start with `EDBSST`, a format version byte, and the row codec id. Files written
+// before the header existed are headerless JSON arrays and are still readable.
+//
+// Version 1 payloads are a plain list of entries; version 2 payloads carry per-column string
+// dictionaries alongside entries whose values may reference dictionary codes. Version 3 entries
+// also record the row version; rows read from older files have version 0. Version 4 payloads add
+// range tombstones: sorted, disjoint row id ranges whose rows are deleted in every older file.
+// Entries in the same file are newer than its range tombstones. Version 5 entries also record the
+// row's created/updated timestamps; rows read from older files have 0 for both.
+const SST_MAGIC: &[u8; 6] = b"EDBSST";
+const SST_FORMAT_VERSION: u8 = 5;
+const SST_HEADER_LEN: usize = SST_MAGIC.len() + 2;
+
+// A string column is dictionary-encoded when it has at most this many distinct values and each
+// distinct value occurs at least twice on average.
+const DICT_MAX_DISTINCT: usize = 4096;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SstEntry {
@@ -12,6 +33,374 @@ pub struct SstEntry {
     pub row: Option<RowData>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SstPayload {
+    pub dictionaries: Vec<ColumnDictionary>,
+    pub entries: Vec<EncodedEntry>,
+    pub range_tombstones: Vec<Range<u64>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnDictionary {
+    pub column: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncodedEntry {
+    pub row_id: u64,
+    pub version: u64,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+    pub fields: Option<BTreeMap<String, EncodedValue>>,
+}
+
+/// Entry layout of version 1 payloads and headerless files.
+#[derive(Deserialize)]
+struct LegacyEntry {
+    row_id: u64,
+    row: Option<LegacyRow>,
+}
+
+#[derive(Deserialize)]
+struct LegacyRow {
+    id: u64,
+    fields: BTreeMap<String, Value>,
+}
+
+impl From<LegacyEntry> for SstEntry {
+    fn from(entry: LegacyEntry) -> Self {
+        SstEntry {
+            row_id: entry.row_id,
+            row: entry.row.map(|row| RowData {
+                id: row.id,
+                version: 0,
+                created_at_ms: 0,
+                updated_at_ms: 0,
+                fields: row.fields,
+            }),
+        }
+    }
+}
+
+/// Version 2 payload layout, before entries recorded row versions.
+#[derive(Deserialize)]
+struct LegacyPayload {
+    dictionaries: Vec<ColumnDictionary>,
+    entries: Vec<LegacyEncodedEntry>,
+}
+
+#[derive(Deserialize)]
+struct LegacyEncodedEntry {
+    row_id: u64,
+    fields: Option<BTreeMap<String, EncodedValue>>,
+}
+
+impl From<LegacyPayload> for SstPayload {
+    fn from(payload: LegacyPayload) -> Self {
+        SstPayload {
+            dictionaries: payload.dictionaries,
+            entries: payload
+                .entries
+                .into_iter()
+                .map(|entry| EncodedEntry {
+                    row_id: entry.row_id,
+                    version: 0,
+                    created_at_ms: 0,
+                    updated_at_ms: 0,
+                    fields: entry.fields,
+                })
+                .collect(),
+            range_tombstones: Vec::new(),
+        }
+    }
+}
+
+/// Entry layout of version 3 and 4 payloads, before entries recorded row timestamps.
+#[derive(Deserialize)]
+struct VersionedEntry {
+    row_id: u64,
+    version: u64,
+    fields: Option<BTreeMap<String, EncodedValue>>,
+}
+
+impl From<VersionedEntry> for EncodedEntry {
+    fn from(entry: VersionedEntry) -> Self {
+        EncodedEntry {
+            row_id: entry.row_id,
+            version: entry.version,
+            created_at_ms: 0,
+            updated_at_ms: 0,
+            fields: entry.fields,
+        }
+    }
+}
+
+/// Version 3 payload layout, before range tombstones.
+#[derive(Deserialize)]
+struct VersionedPayload {
+    dictionaries: Vec<ColumnDictionary>,
+    entries: Vec<VersionedEntry>,
+}
+
+impl From<VersionedPayload> for SstPayload {
+    fn from(payload: VersionedPayload) -> Self {
+        SstPayload {
+            dictionaries: payload.dictionaries,
+            entries: payload.entries.into_iter().map(Into::into).collect(),
+            range_tombstones: Vec::new(),
+        }
+    }
+}
+
+/// Version 4 payload layout, before entries recorded row timestamps.
+#[derive(Deserialize)]
+struct RangedPayload {
+    dictionaries: Vec<ColumnDictionary>,
+    entries: Vec<VersionedEntry>,
+    range_tombstones: Vec<Range<u64>>,
+}
+
+impl From<RangedPayload> for SstPayload {
+    fn from(payload: RangedPayload) -> Self {
+        SstPayload {
+            dictionaries: payload.dictionaries,
+            entries: payload.entries.into_iter().map(Into::into).collect(),
+            range_tombstones: payload.range_tombstones,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EncodedValue {
+    Plain(Value),
+    Code(u32),
+}
+
+impl SstPayload {
+    fn plain(entries: Vec<SstEntry>, range_tombstones: &[Range<u64>]) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|entry| EncodedEntry {
+                row_id: entry.row_id,
+                version: entry.row.as_ref().map_or(0, |row| row.version),
+                created_at_ms: entry.row.as_ref().map_or(0, |row| row.created_at_ms),
+                updated_at_ms: entry.row.as_ref().map_or(0, |row| row.updated_at_ms),
+                fields: entry.row.map(|row| {
+                    row.fields
+                        .into_iter()
+                        .map(|(key, value)| (key, EncodedValue::Plain(value)))
+                        .collect()
+                }),
+            })
+            .collect();
+        Self {
+            dictionaries: Vec::new(),
+            entries,
+            range_tombstones: range_tombstones.to_vec(),
+        }
+    }
+
+    fn dictionary_encoded(entries: &[SstEntry], range_tombstones: &[Range<u64>]) -> Self {
+        let mut occurrences: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut distinct: BTreeMap<&str, BTreeMap<&str, ()>> = BTreeMap::new();
+        for row in entries.iter().filter_map(|entry| entry.row.as_ref()) {
+            for (key, value) in &row.fields {
+                if let Value::String(text) = value {
+                    *occurrences.entry(key.as_str()).or_default() += 1;
+                    distinct
+                        .entry(key.as_str())
+                        .or_default()
+                        .insert(text.as_str(), ());
+                }
+            }
+        }
+
+        let mut dictionaries = Vec::new();
+        let mut codes: HashMap<&str, HashMap<&str, u32>> = HashMap::new();
+        for (column, values) in &distinct {
+            let count = occurrences.get(column).copied().unwrap_or(0);
+            if values.len() > DICT_MAX_DISTINCT || values.len() * 2 > count {
+                continue;
+            }
+            let column_codes = values
+                .keys()
+                .enumerate()
+                .map(|(idx, value)| (*value, idx as u32))
+                .collect();
+            codes.insert(column, column_codes);
+            dictionaries.push(ColumnDictionary {
+                column: column.to_string(),
+                values: values.keys().map(|value| value.to_string()).collect(),
+            });
+        }
+
+        let entries = entries
+            .iter()
+            .map(|entry| EncodedEntry {
+                row_id: entry.row_id,
+                version: entry.row.as_ref().map_or(0, |row| row.version),
+                created_at_ms: entry.row.as_ref().map_or(0, |row| row.created_at_ms),
+                updated_at_ms: entry.row.as_ref().map_or(0, |row| row.updated_at_ms),
+                fields: entry.row.as_ref().map(|row| {
+                    row.fields
+                        .iter()
+                        .map(|(key, value)| {
+                            let code = match value {
+                                Value::String(text) => codes
+                                    .get(key.as_str())
+                                    .and_then(|column| column.get(text.as_str())),
+                                _ => None,
+                            };
+                            let encoded = match code {
+                                Some(code) => EncodedValue::Code(*code),
+                                None => EncodedValue::Plain(value.clone()),
+                            };
+                            (key.clone(), encoded)
+                        })
+                        .collect()
+                }),
+            })
+            .collect();
+
+        Self {
+            dictionaries,
+            entries,
+            range_tombstones: range_tombstones.to_vec(),
+        }
+    }
+}
+
+/// An SST file loaded into memory, keeping dictionary-encoded values as codes until they are
+/// needed.
+#[derive(Debug)]
+pub struct LoadedSst {
+    payload: SstPayload,
+    dictionary_codes: HashMap<String, HashMap<String, u32>>,
+}
+
+impl LoadedSst {
+    /// Reads and decodes the file. Errors name the file, so a corrupt one can be found and
+    /// handed to `EmbedDb::repair_table`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+        let payload = decode_payload(&data)
+            .map_err(|err| anyhow!("unreadable sst file {}: {err}", path.display()))?;
+
+        let dictionary_codes = payload
+            .dictionaries
+            .iter()
+            .map(|dict| {
+                let codes = dict
+                    .values
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, value)| (value.clone(), idx as u32))
+                    .collect();
+                (dict.column.clone(), codes)
+            })
+            .collect();
+
+        Ok(Self {
+            payload,
+            dictionary_codes,
+        })
+    }
+
+    pub fn find(&self, row_id: u64) -> Option<&EncodedEntry> {
+        self.payload
+            .entries
+            .binary_search_by_key(&row_id, |entry| entry.row_id)
+            .ok()
+            .map(|idx| &self.payload.entries[idx])
+    }
+
+    /// Whether one of the file's range tombstones covers `row_id`. Only meaningful when the file
+    /// has no entry for it, since entries are newer than the file's range tombstones.
+    pub fn covers(&self, row_id: u64) -> bool {
+        covers(&self.payload.range_tombstones, row_id)
+    }
+
+    pub fn tombstone_count(&self) -> usize {
+        self.payload
+            .entries
+            .iter()
+            .filter(|entry| entry.fields.is_none())
+            .count()
+    }
+
+    pub fn range_tombstones(&self) -> &[Range<u64>] {
+        &self.payload.range_tombstones
+    }
+
+    pub fn decode(&self, entry: &EncodedEntry) -> Result<SstEntry> {
+        let row = match &entry.fields {
+            Some(fields) => {
+                let mut decoded = BTreeMap::new();
+                for (key, value) in fields {
+                    let value = match value {
+                        EncodedValue::Plain(value) => value.clone(),
+                        EncodedValue::Code(code) => {
+                            Value::String(self.dictionary_value(key, *code)?)
+                        }
+                    };
+                    decoded.insert(key.clone(), value);
+                }
+                Some(RowData {
+                    id: entry.row_id,
+                    version: entry.version,
+                    created_at_ms: entry.created_at_ms,
+                    updated_at_ms: entry.updated_at_ms,
+                    fields: decoded,
+                })
+            }
+            None => None,
+        };
+        Ok(SstEntry {
+            row_id: entry.row_id,
+            row,
+        })
+    }
+
+    /// Compares a column against a string using dictionary codes, without decoding the row.
+    /// Returns `None` when the value is not dictionary-encoded in this file.
+    pub fn code_equals(
+        &self,
+        entry: &EncodedEntry,
+        column: &str,
+        expected: &Value,
+    ) -> Option<bool> {
+        let expected = match expected {
+            Value::String(text) => text,
+            _ => return None,
+        };
+        let code = match entry.fields.as_ref()?.get(column)? {
+            EncodedValue::Code(code) => *code,
+            EncodedValue::Plain(_) => return None,
+        };
+        let codes = self.dictionary_codes.get(column)?;
+        Some(codes.get(expected.as_str()) == Some(&code))
+    }
+
+    pub fn into_entries(self) -> Result<Vec<SstEntry>> {
+        self.payload
+            .entries
+            .iter()
+            .map(|entry| self.decode(entry))
+            .collect()
+    }
+
+    fn dictionary_value(&self, column: &str, code: u32) -> Result<String> {
+        self.payload
+            .dictionaries
+            .iter()
+            .find(|dict| dict.column == column)
+            .and_then(|dict| dict.values.get(code as usize))
+            .cloned()
+            .ok_or_else(|| anyhow!("invalid dictionary code {code} for column '{column}'"))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SstFile {
     pub level: u32,
@@ -21,7 +410,7 @@ pub struct SstFile {
 
 impl SstFile {
     pub fn filename(level: u32, seq: u64) -> String {
-        format!("sst_L{}_{}.json", level, seq)
+        format!("sst_L{}_{}.sst", level, seq)
     }
 }
 
@@ -45,29 +434,130 @@ pub fn list_sst_files(dir: &Path) -> Result<Vec<SstFile>> {
         }
     }
 
-    files.sort_by_key(|f| (f.level, f.seq));
+    sort_oldest_first(&mut files);
     Ok(files)
 }
 
-pub fn write_sst(dir: &Path, level: u32, seq: u64, entries: &[SstEntry]) -> Result<PathBuf> {
+/// Orders files the way reads apply them, oldest first: compaction only moves data down, so a
+/// deeper level holds older rows, and within a level a higher sequence number is newer.
+pub fn sort_oldest_first(files: &mut [SstFile]) {
+    files.sort_by_key(|f| (std::cmp::Reverse(f.level), f.seq));
+}
+
+pub fn total_bytes(files: &[SstFile]) -> Result<u64> {
+    files
+        .iter()
+        .map(|file| Ok(fs::metadata(&file.path)?.len()))
+        .sum()
+}
+
+/// Smallest and largest row id stored in or deleted by the file, or `None` if it has neither
+/// entries nor range tombstones.
+pub fn row_id_range(path: &Path) -> Result<Option<(u64, u64)>> {
+    let sst = LoadedSst::load(path)?;
+    let entries = &sst.payload.entries;
+    let ranges = &sst.payload.range_tombstones;
+    let spans = [
+        entries
+            .first()
+            .zip(entries.last())
+            .map(|(first, last)| (first.row_id, last.row_id)),
+        ranges
+            .first()
+            .zip(ranges.last())
+            .map(|(first, last)| (first.start, last.end - 1)),
+    ];
+    Ok(spans
+        .into_iter()
+        .flatten()
+        .reduce(|(lo, hi), (other_lo, other_hi)| (lo.min(other_lo), hi.max(other_hi))))
+}
+
+/// Writes `entries` and `range_tombstones` (sorted and disjoint) as a new SST file.
+pub fn write_sst(
+    dir: &Path,
+    level: u32,
+    seq: u64,
+    entries: &[SstEntry],
+    range_tombstones: &[Range<u64>],
+    codec: RowCodecKind,
+) -> Result<PathBuf> {
+    let payload = SstPayload::plain(entries.to_vec(), range_tombstones);
+    write_payload(dir, level, seq, &payload, codec)
+}
+
+/// Like `write_sst`, but dictionary-encodes low-cardinality string columns.
+pub fn write_sst_dictionary_encoded(
+    dir: &Path,
+    level: u32,
+    seq: u64,
+    entries: &[SstEntry],
+    range_tombstones: &[Range<u64>],
+    codec: RowCodecKind,
+) -> Result<PathBuf> {
+    let payload = SstPayload::dictionary_encoded(entries, range_tombstones);
+    write_payload(dir, level, seq, &payload, codec)
+}
+
+fn write_payload(
+    dir: &Path,
+    level: u32,
+    seq: u64,
+    payload: &SstPayload,
+    codec: RowCodecKind,
+) -> Result<PathBuf> {
     fs::create_dir_all(dir)?;
     let path = dir.join(SstFile::filename(level, seq));
-    let file = File::create(&path)?;
-    serde_json::to_writer(file, &entries)?;
+    let payload = codec.encode(payload)?;
+    let mut data = Vec::with_capacity(SST_HEADER_LEN + payload.len());
+    data.extend_from_slice(SST_MAGIC);
+    data.push(SST_FORMAT_VERSION);
+    data.push(codec.id());
+    data.extend_from_slice(&payload);
+    // Written aside and renamed into place, so a crash mid-write never leaves a torn file under
+    // an SST name; `list_sst_files` ignores the `.tmp` leftovers.
+    let staging = path.with_extension("tmp");
+    if let Err(err) = fault::write_file(&staging, &data) {
+        let _ = fs::remove_file(&staging);
+        return Err(err.into());
+    }
+    fault::rename(&staging, &path)?;
     Ok(path)
 }
 
-pub fn read_sst(path: &Path) -> Result<Vec<SstEntry>> {
-    let file = File::open(path)?;
-    let entries: Vec<SstEntry> = serde_json::from_reader(file)?;
-    Ok(entries)
+fn decode_payload(data: &[u8]) -> Result<SstPayload> {
+    if !data.starts_with(SST_MAGIC) {
+        return Ok(SstPayload::plain(
+            decode_legacy_entries(RowCodecKind::Json, data)?,
+            &[],
+        ));
+    }
+    if data.len() < SST_HEADER_LEN {
+        return Err(anyhow!("truncated sst header"));
+    }
+    let version = data[SST_MAGIC.len()];
+    let codec = RowCodecKind::from_id(data[SST_MAGIC.len() + 1])?;
+    let body = &data[SST_HEADER_LEN..];
+    Ok(match version {
+        1 => SstPayload::plain(decode_legacy_entries(codec, body)?, &[]),
+        2 => codec.decode::<LegacyPayload>(body)?.into(),
+        3 => codec.decode::<VersionedPayload>(body)?.into(),
+        4 => codec.decode::<RangedPayload>(body)?.into(),
+        5 => codec.decode(body)?,
+        other => return Err(anyhow!("unsupported sst format version {other}")),
+    })
+}
+
+fn decode_legacy_entries(codec: RowCodecKind, data: &[u8]) -> Result<Vec<SstEntry>> {
+    let entries: Vec<LegacyEntry> = codec.decode(data)?;
+    Ok(entries.into_iter().map(SstEntry::from).collect())
 }
 
 pub fn parse_filename(name: &str) -> Option<(u32, u64)> {
-    if !name.starts_with("sst_L") || !name.ends_with(".json") {
-        return None;
-    }
-    let trimmed = name.trim_start_matches("sst_L").trim_end_matches(".json");
+    let stem = name.strip_prefix("sst_L")?;
+    let trimmed = stem
+        .strip_suffix(".sst")
+        .or_else(|| stem.strip_suffix(".json"))?;
     let mut parts = trimmed.split('_');
     let level = parts.next()?.parse::<u32>().ok()?;
     let seq = parts.next()?.parse::<u64>().ok()?;
@@ -78,35 +568,85 @@ pub fn max_seq(files: &[SstFile]) -> u64 {
     files.iter().map(|f| f.seq).max().unwrap_or(0)
 }
 
-pub fn compact_level_zero(
-    files: &[SstFile],
-    output_dir: &Path,
-    next_seq: u64,
-) -> Result<Option<SstFile>> {
-    if files.is_empty() {
-        return Ok(None);
-    }
-
-    let mut merged = std::collections::BTreeMap::<u64, SstEntry>::new();
-    let mut sorted = files.to_vec();
-    sorted.sort_by_key(|f| f.seq);
+/// Files written by `merge_into_level`.
+#[derive(Debug)]
+pub struct MergeOutput {
+    pub files: Vec<SstFile>,
+    pub tombstones_dropped: usize,
+}
 
-    for file in sorted.iter().rev() {
-        let entries = read_sst(&file.path)?;
-        for entry in entries {
-            merged.entry(entry.row_id).or_insert(entry);
+/// Merges `inputs`, ordered oldest first so later entries shadow earlier ones, into `level` as
+/// files of roughly `target_file_bytes` each, numbered from `next_seq`. Tombstones, including
+/// range tombstones, are dropped when `drop_tombstones` is set, which is only safe when no older
+/// file can still hold the row. Rows are upgraded past `changes` on the way, since the output
+/// files postdate them.
+#[allow(clippy::too_many_arguments)]
+pub fn merge_into_level(
+    inputs: &[SstFile],
+    output_dir: &Path,
+    level: u32,
+    next_seq: &mut u64,
+    target_file_bytes: u64,
+    drop_tombstones: bool,
+    codec: RowCodecKind,
+    changes: &[SchemaChange],
+) -> Result<MergeOutput> {
+    let mut merged = BTreeMap::<u64, SstEntry>::new();
+    let mut ranges = Vec::new();
+    for file in inputs {
+        let sst = LoadedSst::load(&file.path)?;
+        for range in sst.range_tombstones() {
+            remove_range(&mut merged, range);
         }
+        add_ranges(&mut ranges, sst.range_tombstones().iter().cloned());
+        for mut entry in sst.into_entries()? {
+            if let Some(row) = &mut entry.row {
+                schema::upgrade_row(changes, file.seq, row);
+            }
+            merged.insert(entry.row_id, entry);
+        }
+    }
+    let total = merged.len();
+    let entries: Vec<SstEntry> = merged
+        .into_values()
+        .filter(|entry| !drop_tombstones || entry.row.is_some())
+        .collect();
+    let mut tombstones_dropped = total - entries.len();
+    if drop_tombstones {
+        tombstones_dropped += ranges.len();
+        ranges.clear();
     }
 
-    let mut output_entries: Vec<SstEntry> = merged.into_values().collect();
-    output_entries.sort_by_key(|entry| entry.row_id);
+    // Input sizes include shadowed entries, so this slightly overestimates the rows per file.
+    let bytes_in = total_bytes(inputs)?.max(1);
+    let rows_per_file = (total as u128 * target_file_bytes as u128 / bytes_in as u128)
+        .clamp(1, usize::MAX as u128) as usize;
 
-    let path = write_sst(output_dir, 1, next_seq, &output_entries)?;
-    Ok(Some(SstFile {
-        level: 1,
-        seq: next_seq,
-        path,
-    }))
+    // Each output file takes the range tombstones between its first row id and the next file's,
+    // so the files stay disjoint; with no entries left, one file carries them all.
+    let chunks: Vec<&[SstEntry]> = if entries.is_empty() && !ranges.is_empty() {
+        vec![&[]]
+    } else {
+        entries.chunks(rows_per_file).collect()
+    };
+    let mut files = Vec::new();
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let lo = if idx == 0 { 0 } else { chunk[0].row_id };
+        let hi = chunks.get(idx + 1).map_or(u64::MAX, |next| next[0].row_id);
+        let clipped: Vec<Range<u64>> = ranges
+            .iter()
+            .map(|range| range.start.max(lo)..range.end.min(hi))
+            .filter(|range| !range.is_empty())
+            .collect();
+        let seq = *next_seq;
+        *next_seq += 1;
+        let path = write_sst_dictionary_encoded(output_dir, level, seq, chunk, &clipped, codec)?;
+        files.push(SstFile { level, seq, path });
+    }
+    Ok(MergeOutput {
+        files,
+        tombstones_dropped,
+    })
 }
 
 pub fn remove_files(files: &[SstFile]) -> Result<()> {
@@ -118,12 +658,41 @@ pub fn remove_files(files: &[SstFile]) -> Result<()> {
     Ok(())
 }
 
+/// The file's entry for `row_id`; a row id covered by one of its range tombstones reads as a
+/// tombstone entry.
 pub fn find_entry(path: &Path, row_id: u64) -> Result<Option<SstEntry>> {
-    let entries = read_sst(path)?;
-    if let Ok(idx) = entries.binary_search_by_key(&row_id, |entry| entry.row_id) {
-        return Ok(Some(entries[idx].clone()));
+    let sst = LoadedSst::load(path)?;
+    match sst.find(row_id) {
+        Some(entry) => sst.decode(entry).map(Some),
+        None => Ok(sst.covers(row_id).then_some(SstEntry { row_id, row: None })),
     }
-    Ok(None)
+}
+
+/// Whether `row_id` falls in one of `ranges`, which must be sorted and disjoint.
+pub fn covers(ranges: &[Range<u64>], row_id: u64) -> bool {
+    let idx = ranges.partition_point(|range| range.end <= row_id);
+    ranges.get(idx).is_some_and(|range| range.contains(&row_id))
+}
+
+/// Adds `new` to the sorted, disjoint `ranges`, coalescing overlapping and adjacent ranges.
+pub fn add_ranges(ranges: &mut Vec<Range<u64>>, new: impl IntoIterator<Item = Range<u64>>) {
+    ranges.extend(new.into_iter().filter(|range| !range.is_empty()));
+    ranges.sort_by_key(|range| range.start);
+    let mut coalesced: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match coalesced.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => coalesced.push(range),
+        }
+    }
+    *ranges = coalesced;
+}
+
+/// Removes the keys of `map` that fall in `range`.
+pub fn remove_range<V>(map: &mut BTreeMap<u64, V>, range: &Range<u64>) {
+    let mut covered = map.split_off(&range.start);
+    let mut after = covered.split_off(&range.end);
+    map.append(&mut after);
 }
 
 pub fn ensure_dir(path: &Path) -> Result<()> {
@@ -141,19 +710,32 @@ mod tests {
     use std::collections::BTreeMap;
     use tempfile::tempdir;
 
+    fn read_sst(path: &Path) -> Result<Vec<SstEntry>> {
+        LoadedSst::load(path)?.into_entries()
+    }
+
     #[test]
     fn find_entry_binary_search_roundtrip() {
         let dir = tempdir().unwrap();
         let table_dir = dir.path().join("table");
         let mut fields = BTreeMap::new();
         fields.insert("title".to_string(), Value::String("hello".to_string()));
-        let row = RowData { id: 3, fields };
+        let row = RowData {
+            id: 3,
+            version: 2,
+            fields,
+            created_at_ms: 10,
+            updated_at_ms: 20,
+        };
         let entries = vec![
             SstEntry {
                 row_id: 1,
                 row: Some(RowData {
                     id: 1,
+                    version: 1,
                     fields: BTreeMap::new(),
+                    created_at_ms: 0,
+                    updated_at_ms: 0,
                 }),
             },
             SstEntry {
@@ -165,15 +747,213 @@ mod tests {
                 row: Some(row.clone()),
             },
         ];
-        let path = write_sst(&table_dir, 0, 1, &entries).unwrap();
+        let path = write_sst(&table_dir, 0, 1, &entries, &[], RowCodecKind::Json).unwrap();
 
         let found = find_entry(&path, 3).unwrap().unwrap();
         let found_row = found.row.unwrap();
         assert_eq!(found_row.id, row.id);
+        assert_eq!(found_row.version, 2);
+        assert_eq!((found_row.created_at_ms, found_row.updated_at_ms), (10, 20));
         assert_eq!(
             found_row.fields.get("title"),
             Some(&Value::String("hello".to_string()))
         );
         assert!(find_entry(&path, 4).unwrap().is_none());
     }
+
+    #[test]
+    fn merge_applies_range_tombstones_and_splits_them_across_outputs() {
+        let dir = tempdir().unwrap();
+        let row = |id: u64| SstEntry {
+            row_id: id,
+            row: Some(RowData {
+                id,
+                version: 1,
+                fields: BTreeMap::new(),
+                created_at_ms: 0,
+                updated_at_ms: 0,
+            }),
+        };
+        let older: Vec<SstEntry> = (1..=6).map(row).collect();
+        let older = write_sst(dir.path(), 1, 1, &older, &[], RowCodecKind::Json).unwrap();
+        let deleted = 2..5;
+        let newer = write_sst(
+            dir.path(),
+            0,
+            2,
+            &[row(3)],
+            std::slice::from_ref(&deleted),
+            RowCodecKind::Json,
+        )
+        .unwrap();
+        assert_eq!(row_id_range(&newer).unwrap(), Some((2, 4)));
+        assert!(find_entry(&newer, 4).unwrap().unwrap().row.is_none());
+        assert!(find_entry(&newer, 3).unwrap().unwrap().row.is_some());
+        assert!(find_entry(&newer, 5).unwrap().is_none());
+
+        let inputs = [
+            SstFile {
+                level: 1,
+                seq: 1,
+                path: older,
+            },
+            SstFile {
+                level: 0,
+                seq: 2,
+                path: newer,
+            },
+        ];
+        let out_dir = dir.path().join("out");
+        let mut next_seq = 3;
+        let output = merge_into_level(
+            &inputs,
+            &out_dir,
+            1,
+            &mut next_seq,
+            1,
+            false,
+            RowCodecKind::Json,
+            &[],
+        )
+        .unwrap();
+        assert!(output.files.len() > 1);
+        let mut ids = Vec::new();
+        let mut ranges = Vec::new();
+        for file in &output.files {
+            let sst = LoadedSst::load(&file.path).unwrap();
+            ranges.extend(sst.range_tombstones().iter().cloned());
+            ids.extend(sst.into_entries().unwrap().iter().map(|entry| entry.row_id));
+        }
+        assert_eq!(ids, [1, 3, 5, 6]);
+        add_ranges(&mut ranges, []);
+        assert_eq!(ranges, [deleted]);
+
+        let merged = merge_into_level(
+            &inputs,
+            &out_dir,
+            2,
+            &mut next_seq,
+            u64::MAX,
+            true,
+            RowCodecKind::Json,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(merged.tombstones_dropped, 1);
+        let sst = LoadedSst::load(&merged.files[0].path).unwrap();
+        assert!(sst.range_tombstones().is_empty());
+        assert!(!sst.covers(4));
+    }
+
+    #[test]
+    fn read_sst_accepts_legacy_headerless_json() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sst_L0_1.json");
+        let entries = vec![SstEntry {
+            row_id: 7,
+            row: None,
+        }];
+        fs::write(&path, serde_json::to_vec(&entries).unwrap()).unwrap();
+
+        assert_eq!(parse_filename("sst_L0_1.json"), Some((0, 1)));
+        assert_eq!(parse_filename("sst_L2_9.sst"), Some((2, 9)));
+        let read = read_sst(&path).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].row_id, 7);
+
+        let new_path = write_sst(dir.path(), 0, 2, &entries, &[], RowCodecKind::Bincode).unwrap();
+        assert!(fs::read(&new_path).unwrap().starts_with(SST_MAGIC));
+        assert_eq!(read_sst(&new_path).unwrap()[0].row_id, 7);
+    }
+
+    #[test]
+    fn version_two_files_read_with_row_version_zero() {
+        #[derive(Serialize)]
+        struct V2Entry {
+            row_id: u64,
+            fields: Option<BTreeMap<String, EncodedValue>>,
+        }
+        #[derive(Serialize)]
+        struct V2Payload {
+            dictionaries: Vec<ColumnDictionary>,
+            entries: Vec<V2Entry>,
+        }
+
+        let dir = tempdir().unwrap();
+        let payload = V2Payload {
+            dictionaries: vec![ColumnDictionary {
+                column: "source".to_string(),
+                values: vec!["web".to_string()],
+            }],
+            entries: vec![V2Entry {
+                row_id: 4,
+                fields: Some(BTreeMap::from([(
+                    "source".to_string(),
+                    EncodedValue::Code(0),
+                )])),
+            }],
+        };
+        let mut data = SST_MAGIC.to_vec();
+        data.push(2);
+        data.push(RowCodecKind::Bincode.id());
+        data.extend(RowCodecKind::Bincode.encode(&payload).unwrap());
+        let path = dir.path().join(SstFile::filename(1, 1));
+        fs::write(&path, data).unwrap();
+
+        let row = read_sst(&path).unwrap()[0].row.clone().unwrap();
+        assert_eq!(row.version, 0);
+        assert_eq!(row.fields["source"], Value::String("web".to_string()));
+    }
+
+    #[test]
+    fn dictionary_encoding_roundtrips_and_compares_codes() {
+        let dir = tempdir().unwrap();
+        let mut entries = Vec::new();
+        for id in 1..=6u64 {
+            let mut fields = BTreeMap::new();
+            let source = if id % 2 == 0 { "web" } else { "mail" };
+            fields.insert("source".to_string(), Value::String(source.to_string()));
+            fields.insert("body".to_string(), Value::String(format!("unique-{id}")));
+            entries.push(SstEntry {
+                row_id: id,
+                row: Some(RowData {
+                    id,
+                    version: 1,
+                    fields,
+                    created_at_ms: 0,
+                    updated_at_ms: 0,
+                }),
+            });
+        }
+        for codec in [RowCodecKind::Json, RowCodecKind::Bincode] {
+            let path = write_sst_dictionary_encoded(
+                dir.path(),
+                1,
+                codec.id() as u64,
+                &entries,
+                &[],
+                codec,
+            )
+            .unwrap();
+            let sst = LoadedSst::load(&path).unwrap();
+            assert_eq!(sst.payload.dictionaries.len(), 1);
+            assert_eq!(sst.payload.dictionaries[0].column, "source");
+
+            let entry = sst.find(2).unwrap();
+            let web = Value::String("web".to_string());
+            let mail = Value::String("mail".to_string());
+            assert_eq!(sst.code_equals(entry, "source", &web), Some(true));
+            assert_eq!(sst.code_equals(entry, "source", &mail), Some(false));
+            assert_eq!(sst.code_equals(entry, "body", &web), None);
+
+            let decoded = read_sst(&path).unwrap();
+            assert_eq!(decoded.len(), 6);
+            let row = decoded[0].row.as_ref().unwrap();
+            assert_eq!(row.fields.get("source"), Some(&mail));
+            assert_eq!(
+                row.fields.get("body"),
+                Some(&Value::String("unique-1".to_string()))
+            );
+        }
+    }
 }
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use crc32fast::Hasher;
+
+// Vector segments start with `EDBVEC` and a format version byte, followed by the entries and a
+// trailing CRC32 of everything before it. Each entry is `[row_id: u64][dim: u32]`, then for a
+// stored vector `[has_norm: u8][norm: f32][dim x f32]`; a `dim` of `u32::MAX` marks a removal.
+// All integers and floats are little-endian.
+const VEC_MAGIC: &[u8; 6] = b"EDBVEC";
+const VEC_FORMAT_VERSION: u8 = 1;
+const TOMBSTONE_DIM: u32 = u32::MAX;
+
+/// One row's embedding as of the segment: the stored vector and, for vectors kept
+/// unit-normalized, the original norm. `vector` is `None` when the embedding was removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorEntry {
+    pub row_id: u64,
+    pub vector: Option<Vec<f32>>,
+    pub norm: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VectorSegmentFile {
+    pub level: u32,
+    pub seq: u64,
+    pub path: PathBuf,
+}
+
+impl VectorSegmentFile {
+    pub fn filename(level: u32, seq: u64) -> String {
+        format!("vec_L{}_{}.bin", level, seq)
+    }
+}
+
+pub fn parse_filename(name: &str) -> Option<(u32, u64)> {
+    let stem = name.strip_prefix("vec_L")?.strip_suffix(".bin")?;
+    let (level, seq) = stem.split_once('_')?;
+    Some((level.parse().ok()?, seq.parse().ok()?))
+}
+
+/// Segments in `dir`, oldest first.
+pub fn list_segments(dir: &Path) -> Result<Vec<VectorSegmentFile>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some((level, seq)) = path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .and_then(parse_filename)
+        {
+            files.push(VectorSegmentFile { level, seq, path });
+        }
+    }
+    files.sort_by_key(|f| f.seq);
+    Ok(files)
+}
+
+pub fn write_segment(
+    dir: &Path,
+    level: u32,
+    seq: u64,
+    entries: &[VectorEntry],
+) -> Result<VectorSegmentFile> {
+    fs::create_dir_all(dir)?;
+    let mut data = Vec::new();
+    data.extend_from_slice(VEC_MAGIC);
+    data.push(VEC_FORMAT_VERSION);
+    for entry in entries {
+        data.extend_from_slice(&entry.row_id.to_le_bytes());
+        let Some(vector) = &entry.vector else {
+            data.extend_from_slice(&TOMBSTONE_DIM.to_le_bytes());
+            continue;
+        };
+        let dim = u32::try_from(vector.len())
+            .ok()
+            .filter(|dim| *dim != TOMBSTONE_DIM)
+            .ok_or_else(|| anyhow!("vector is too long"))?;
+        data.extend_from_slice(&dim.to_le_bytes());
+        data.push(entry.norm.is_some() as u8);
+        data.extend_from_slice(&entry.norm.unwrap_or(0.0).to_le_bytes());
+        for x in vector {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+    let mut hasher = Hasher::new();
+    hasher.update(&data);
+    data.extend_from_slice(&hasher.finalize().to_le_bytes());
+
+    let path = dir.join(VectorSegmentFile::filename(level, seq));
+    fs::write(&path, data)?;
+    Ok(VectorSegmentFile { level, seq, path })
+}
+
+pub fn read_segment(path: &Path) -> Result<Vec<VectorEntry>> {
+    let data = fs::read(path)?;
+    let corrupt = || anyhow!("corrupt vector segment {}", path.display());
+    let header_len = VEC_MAGIC.len() + 1;
+    if data.len() < header_len + 4 || &data[..VEC_MAGIC.len()] != VEC_MAGIC {
+        return Err(corrupt());
+    }
+    if data[VEC_MAGIC.len()] != VEC_FORMAT_VERSION {
+        return Err(anyhow!(
+            "unsupported vector segment version {} in {}",
+            data[VEC_MAGIC.len()],
+            path.display()
+        ));
+    }
+    let (body, checksum) = data.split_at(data.len() - 4);
+    let mut hasher = Hasher::new();
+    hasher.update(body);
+    if hasher.finalize().to_le_bytes() != checksum {
+        return Err(corrupt());
+    }
+
+    let mut reader = Reader {
+        data: &body[header_len..],
+    };
+    let mut entries = Vec::new();
+    while !reader.data.is_empty() {
+        let row_id = u64::from_le_bytes(reader.take().ok_or_else(corrupt)?);
+        let dim = u32::from_le_bytes(reader.take().ok_or_else(corrupt)?);
+        if dim == TOMBSTONE_DIM {
+            entries.push(VectorEntry {
+                row_id,
+                vector: None,
+                norm: None,
+            });
+            continue;
+        }
+        let [has_norm] = reader.take().ok_or_else(corrupt)?;
+        let norm = f32::from_le_bytes(reader.take().ok_or_else(corrupt)?);
+        let vector = (0..dim)
+            .map(|_| reader.take().map(f32::from_le_bytes))
+            .collect::<Option<Vec<f32>>>()
+            .ok_or_else(corrupt)?;
+        entries.push(VectorEntry {
+            row_id,
+            vector: Some(vector),
+            norm: (has_norm != 0).then_some(norm),
+        });
+    }
+    Ok(entries)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.data.split_first_chunk::<N>()?;
+        self.data = rest;
+        Some(*head)
+    }
+}
+
+/// Folds `files` into the latest entry per row; later segments shadow earlier ones.
+pub fn merge_segments(files: &[VectorSegmentFile]) -> Result<BTreeMap<u64, VectorEntry>> {
+    let mut sorted = files.to_vec();
+    sorted.sort_by_key(|f| f.seq);
+    let mut merged = BTreeMap::new();
+    for file in &sorted {
+        for entry in read_segment(&file.path)? {
+            merged.insert(entry.row_id, entry);
+        }
+    }
+    Ok(merged)
+}
+
+pub fn remove_files(files: &[VectorSegmentFile]) -> Result<()> {
+    for file in files {
+        if file.path.exists() {
+            fs::remove_file(&file.path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_roundtrip_and_later_entries_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = write_segment(
+            dir.path(),
+            0,
+            1,
+            &[
+                VectorEntry {
+                    row_id: 1,
+                    vector: Some(vec![0.6, 0.8]),
+                    norm: Some(5.0),
+                },
+                VectorEntry {
+                    row_id: 2,
+                    vector: Some(vec![1.0, -2.0, 3.5]),
+                    norm: None,
+                },
+            ],
+        )
+        .unwrap();
+        let second = write_segment(
+            dir.path(),
+            0,
+            2,
+            &[VectorEntry {
+                row_id: 1,
+                vector: None,
+                norm: None,
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(read_segment(&first.path).unwrap().len(), 2);
+        let listed = list_segments(dir.path()).unwrap();
+        assert_eq!(listed.iter().map(|f| f.seq).collect::<Vec<_>>(), [1, 2]);
+
+        let merged = merge_segments(&listed).unwrap();
+        assert_eq!(merged[&1].vector, None);
+        assert_eq!(merged[&2].vector, Some(vec![1.0, -2.0, 3.5]));
+        assert_eq!(merged[&2].norm, None);
+
+        // A flipped byte fails the checksum instead of yielding a wrong vector.
+        let mut data = fs::read(&second.path).unwrap();
+        data[8] ^= 1;
+        fs::write(&second.path, data).unwrap();
+        assert!(read_segment(&second.path).is_err());
+    }
+}
@@ -1,14 +1,53 @@
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File, OpenOptions};
 use std::io::{BufReader, Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use crc32fast::Hasher;
 use serde::{Deserialize, Serialize};
 
-use crate::schema::{EmbeddingSpec, RowData, TableSchema};
+use crate::schema::{
+    AlterTableOp, EmbeddingModel, EmbeddingSpec, RowData, SchemaChange, TableSchema,
+};
+use crate::storage::fault;
+use crate::vector::SparseVector;
 use crate::EmbeddingStatus;
 
+// WAL files start with `EDBWAL` and a format version byte. Every record is framed as its length
+// (`u32`), the CRC32 of its data (`u32`), and the data. In version 2 the data starts with a kind
+// byte: JSON, or a binary encoding of the vector-carrying records, with `KIND_ZSTD` set when the
+// rest is zstd-compressed. Version 3 files may also hold bincode records; they are only written
+// with `WalEncoding::Bincode`, so builds that predate it refuse the file instead of stopping
+// replay at its first bincode record. Files without the header are version 1, whose records are
+// plain JSON. Older files are still replayed and appended to in their format until the next
+// checkpoint replaces them. All integers are little-endian.
+const WAL_MAGIC: &[u8; 6] = b"EDBWAL";
+const WAL_FORMAT_VERSION: u8 = 2;
+const WAL_FORMAT_VERSION_BINCODE: u8 = 3;
+const WAL_HEADER_LEN: u64 = WAL_MAGIC.len() as u64 + 1;
+const KIND_JSON: u8 = 0;
+const KIND_STORE_EMBEDDING: u8 = 1;
+const KIND_STORE_NAMED_EMBEDDING: u8 = 2;
+const KIND_BINCODE: u8 = 3;
+const KIND_ZSTD: u8 = 0x80;
+// Smaller records rarely shrink enough to pay for compressing them.
+const COMPRESS_MIN_BYTES: usize = 256;
+
+/// How WAL records are serialized, set with `Config::with_wal_encoding`. Embedding vectors are
+/// written as raw f32 bytes with either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WalEncoding {
+    #[default]
+    Json,
+    /// Smaller records that are faster to write and replay. Records carrying a table schema or
+    /// embedding spec are still written as JSON.
+    Bincode,
+}
+
+/// One logged change. Each record but a `Checkpoint` marker advances the LSN by one.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WalRecord {
     CreateTable {
@@ -30,6 +69,12 @@ pub enum WalRecord {
         table: String,
         row_id: u64,
     },
+    /// Deletes every row with an id in `ranges` (sorted and disjoint), as one record for a bulk
+    /// delete.
+    DeleteRanges {
+        table: String,
+        ranges: Vec<Range<u64>>,
+    },
     EnqueueEmbedding {
         table: String,
         row_id: u64,
@@ -45,71 +90,389 @@ pub enum WalRecord {
         attempts: Option<u32>,
         #[serde(default)]
         next_retry_at_ms: Option<u64>,
+        /// With `Ready`: the model the stored embedding came from.
+        #[serde(default)]
+        model: Option<EmbeddingModel>,
     },
     StoreEmbedding {
         table: String,
         row_id: u64,
         vector: Vec<f32>,
+        /// Set when `vector` is already unit-normalized; holds the original L2 norm.
+        #[serde(default)]
+        norm: Option<f32>,
+    },
+    StoreSparseVector {
+        table: String,
+        row_id: u64,
+        vector: SparseVector,
+    },
+    /// Stores the row's vector for one of the spec's named vector fields.
+    StoreNamedEmbedding {
+        table: String,
+        row_id: u64,
+        name: String,
+        vector: Vec<f32>,
+    },
+    /// Deletes the row once `expires_at_ms` (Unix milliseconds) has passed.
+    SetRowExpiry {
+        table: String,
+        row_id: u64,
+        expires_at_ms: u64,
+    },
+    /// Soft-deletes a row of a table with `TableSchema::soft_delete` set, at `hidden_at_ms` (Unix
+    /// milliseconds).
+    HideRow {
+        table: String,
+        row_id: u64,
+        hidden_at_ms: u64,
+    },
+    /// Makes a row hidden by `HideRow` visible again.
+    RestoreRow {
+        table: String,
+        row_id: u64,
+    },
+    /// Replaces a table's embedding spec; resident vectors are re-encoded for the new spec.
+    SetEmbeddingSpec {
+        table: String,
+        embedding_spec: Option<EmbeddingSpec>,
+    },
+    /// Changes a table's schema and rewrites its in-memory rows. Rows in SST files numbered below
+    /// `before_seq` are rewritten as they are read.
+    AlterTable {
+        table: String,
+        op: AlterTableOp,
+        before_seq: u64,
+    },
+    /// Written in a checkpoint snapshot after `CreateTable`, which has the current schema: the
+    /// changes still owed to rows in older SST files.
+    SchemaChanges {
+        table: String,
+        changes: Vec<SchemaChange>,
+    },
+    /// Written in a checkpoint snapshot in place of the table's `StoreEmbedding` records: its
+    /// embeddings are in the vector segment files under the table directory.
+    VectorSegments {
+        table: String,
+    },
+    /// Renames a table; its directory moves from `table`'s to `new_name`'s. `EmbedDb::rename_table`
+    /// checkpoints right after it, so it is only replayed after a crash.
+    RenameTable {
+        table: String,
+        new_name: String,
+    },
+    /// Written at the end of a checkpoint snapshot: the records before it reproduce the state as
+    /// of `lsn`, and the records after it continue from `lsn + 1`.
+    Checkpoint {
+        lsn: u64,
     },
+    /// Opens a write batch: the records up to the matching `CommitBatch` apply together or not
+    /// at all. A batch left open by a crash is cut off when the file is next opened.
+    BeginBatch,
+    CommitBatch,
+}
+
+impl WalRecord {
+    /// The table the record applies to; `None` for checkpoint and batch markers.
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            Self::CreateTable { name, .. } => Some(name),
+            Self::SetNextRowId { table, .. }
+            | Self::PutRow { table, .. }
+            | Self::DeleteRow { table, .. }
+            | Self::DeleteRanges { table, .. }
+            | Self::EnqueueEmbedding { table, .. }
+            | Self::UpdateEmbeddingStatus { table, .. }
+            | Self::StoreEmbedding { table, .. }
+            | Self::StoreSparseVector { table, .. }
+            | Self::StoreNamedEmbedding { table, .. }
+            | Self::SetRowExpiry { table, .. }
+            | Self::HideRow { table, .. }
+            | Self::RestoreRow { table, .. }
+            | Self::SetEmbeddingSpec { table, .. }
+            | Self::AlterTable { table, .. }
+            | Self::SchemaChanges { table, .. }
+            | Self::RenameTable { table, .. }
+            | Self::VectorSegments { table } => Some(table),
+            Self::Checkpoint { .. } | Self::BeginBatch | Self::CommitBatch => None,
+        }
+    }
+
+    /// The record's variant name, e.g. `PutRow`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CreateTable { .. } => "CreateTable",
+            Self::SetNextRowId { .. } => "SetNextRowId",
+            Self::PutRow { .. } => "PutRow",
+            Self::DeleteRow { .. } => "DeleteRow",
+            Self::DeleteRanges { .. } => "DeleteRanges",
+            Self::EnqueueEmbedding { .. } => "EnqueueEmbedding",
+            Self::UpdateEmbeddingStatus { .. } => "UpdateEmbeddingStatus",
+            Self::StoreEmbedding { .. } => "StoreEmbedding",
+            Self::StoreSparseVector { .. } => "StoreSparseVector",
+            Self::StoreNamedEmbedding { .. } => "StoreNamedEmbedding",
+            Self::SetRowExpiry { .. } => "SetRowExpiry",
+            Self::HideRow { .. } => "HideRow",
+            Self::RestoreRow { .. } => "RestoreRow",
+            Self::SetEmbeddingSpec { .. } => "SetEmbeddingSpec",
+            Self::AlterTable { .. } => "AlterTable",
+            Self::SchemaChanges { .. } => "SchemaChanges",
+            Self::VectorSegments { .. } => "VectorSegments",
+            Self::RenameTable { .. } => "RenameTable",
+            Self::Checkpoint { .. } => "Checkpoint",
+            Self::BeginBatch => "BeginBatch",
+            Self::CommitBatch => "CommitBatch",
+        }
+    }
+
+    /// The single row the record applies to, if any.
+    pub fn row_id(&self) -> Option<u64> {
+        match self {
+            Self::PutRow { row_id, .. }
+            | Self::DeleteRow { row_id, .. }
+            | Self::EnqueueEmbedding { row_id, .. }
+            | Self::UpdateEmbeddingStatus { row_id, .. }
+            | Self::StoreEmbedding { row_id, .. }
+            | Self::StoreSparseVector { row_id, .. }
+            | Self::StoreNamedEmbedding { row_id, .. }
+            | Self::SetRowExpiry { row_id, .. }
+            | Self::HideRow { row_id, .. }
+            | Self::RestoreRow { row_id, .. } => Some(*row_id),
+            _ => None,
+        }
+    }
+}
+
+/// Directory holding the segments sealed by `Config::wal_segment_bytes` since the last checkpoint.
+pub fn sealed_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("wal_segments")
+}
+
+/// Sealed segments are named by the LSN they end at, like archived ones.
+pub fn sealed_segment_path(data_dir: &Path, end_lsn: u64) -> PathBuf {
+    sealed_dir(data_dir).join(format!("wal_{end_lsn:020}.log"))
+}
+
+/// The `wal_*.log` segments in `dir`, oldest first; a missing directory has none.
+pub fn list_segments(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut segments = Vec::new();
+    if !dir.exists() {
+        return Ok(segments);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_segment = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("wal_") && name.ends_with(".log"));
+        if is_segment {
+            segments.push(path);
+        }
+    }
+    // Zero-padded names sort in LSN order.
+    segments.sort();
+    Ok(segments)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WalFormat {
+    /// Headerless file of JSON records, written before WAL format versions existed.
+    Legacy,
+    /// File with a header, holding its format version.
+    Versioned(u8),
+}
+
+impl WalFormat {
+    fn for_encoding(encoding: WalEncoding) -> Self {
+        match encoding {
+            WalEncoding::Json => WalFormat::Versioned(WAL_FORMAT_VERSION),
+            WalEncoding::Bincode => WalFormat::Versioned(WAL_FORMAT_VERSION_BINCODE),
+        }
+    }
+
+    /// The encoding appends use: `encoding` if the file's version allows it, JSON otherwise.
+    fn encoding(self, encoding: WalEncoding) -> WalEncoding {
+        match self {
+            WalFormat::Versioned(WAL_FORMAT_VERSION_BINCODE) => encoding,
+            _ => WalEncoding::Json,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Wal {
     path: PathBuf,
     file: File,
+    len: u64,
+    format: WalFormat,
+    encoding: WalEncoding,
+    compression: Option<i32>,
 }
 
 impl Wal {
-    pub fn open(path: PathBuf) -> Result<Self> {
+    /// Opens the WAL for appending records in `encoding`, first cutting off a torn record or
+    /// uncommitted batch left by a crash mid-append so later appends are not hidden behind it on
+    /// replay. A file written in an older format keeps taking JSON records until a checkpoint
+    /// replaces it.
+    pub fn open(path: PathBuf, encoding: WalEncoding) -> Result<Self> {
         let file = OpenOptions::new()
             .create(true)
             .truncate(false)
             .read(true)
             .write(true)
             .open(&path)?;
+        let mut len = file.metadata()?.len();
+        let format = read_format(&file, &path)?;
+        let valid = match format {
+            // An empty file, or one holding a torn header.
+            None => 0,
+            Some(format) => valid_prefix_len(&file, format)?,
+        };
+        if valid < len {
+            file.set_len(valid)?;
+            file.sync_data()?;
+            len = valid;
+        }
+
+        let new_format = WalFormat::for_encoding(encoding);
+        let mut wal = Self {
+            path,
+            file,
+            len,
+            format: format.unwrap_or(new_format),
+            encoding: format.unwrap_or(new_format).encoding(encoding),
+            compression: None,
+        };
+        if format.is_none() {
+            wal.write_header()?;
+        }
+        Ok(wal)
+    }
 
-        Ok(Self { path, file })
+    /// Opens the WAL for replay only, leaving the file exactly as it is; appends fail.
+    pub fn open_read_only(path: PathBuf) -> Result<Self> {
+        let file = OpenOptions::new().read(true).open(&path)?;
+        let len = file.metadata()?.len();
+        let format = read_format(&file, &path)?.unwrap_or(WalFormat::Versioned(WAL_FORMAT_VERSION));
+        Ok(Self {
+            path,
+            file,
+            len,
+            format,
+            encoding: WalEncoding::Json,
+            compression: None,
+        })
     }
 
-    pub fn create_new(path: PathBuf) -> Result<Self> {
+    /// Creates an empty WAL, replacing any file at `path`, that appends records in `encoding`.
+    pub fn create_new(path: PathBuf, encoding: WalEncoding) -> Result<Self> {
         let file = OpenOptions::new()
             .create(true)
             .truncate(true)
             .read(true)
             .write(true)
             .open(&path)?;
-        Ok(Self { path, file })
+        let mut wal = Self {
+            path,
+            file,
+            len: 0,
+            format: WalFormat::for_encoding(encoding),
+            encoding,
+            compression: None,
+        };
+        wal.write_header()?;
+        Ok(wal)
+    }
+
+    /// Compresses records appended from now on with zstd at `level`; `None` stores them as is.
+    /// Has no effect on a version 1 file.
+    pub fn with_compression(mut self, level: Option<i32>) -> Self {
+        self.compression = level;
+        self
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let WalFormat::Versioned(version) = self.format else {
+            return Err(anyhow!("version 1 WAL files have no header"));
+        };
+        let mut header = WAL_MAGIC.to_vec();
+        header.push(version);
+        fault::write_wal(&self.path, &mut self.file, &header)?;
+        self.file.flush()?;
+        self.len = WAL_HEADER_LEN;
+        Ok(())
+    }
+
+    /// Size of the file in bytes, including any torn tail left by a crash.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Cuts the file back to `len` bytes, dropping the records appended after it.
+    pub fn truncate(&mut self, len: u64) -> Result<()> {
+        self.file.set_len(len)?;
+        self.file.sync_data()?;
+        self.len = len;
+        Ok(())
     }
 
     pub fn append(&mut self, record: &WalRecord, sync: bool) -> Result<()> {
-        let data = serde_json::to_vec(record)?;
+        let data = match self.format {
+            WalFormat::Legacy => serde_json::to_vec(record)?,
+            WalFormat::Versioned(_) => encode_record(record, self.encoding, self.compression)?,
+        };
         let mut hasher = Hasher::new();
         hasher.update(&data);
         let checksum = hasher.finalize();
         let len = data.len() as u32;
 
-        self.file.seek(SeekFrom::End(0))?;
-        self.file.write_all(&len.to_le_bytes())?;
-        self.file.write_all(&checksum.to_le_bytes())?;
-        self.file.write_all(&data)?;
+        let mut frame = Vec::with_capacity(8 + data.len());
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&checksum.to_le_bytes());
+        frame.extend_from_slice(&data);
+
+        let end = self.file.seek(SeekFrom::End(0))?;
+        fault::write_wal(&self.path, &mut self.file, &frame)?;
         self.file.flush()?;
+        self.len = end + frame.len() as u64;
         if sync {
-            self.file.sync_data()?;
+            fault::sync_wal(&self.path, &self.file)?;
         }
         Ok(())
     }
 
     pub fn sync(&mut self) -> Result<()> {
         self.file.flush()?;
-        self.file.sync_data()?;
+        fault::sync_wal(&self.path, &self.file)?;
         Ok(())
     }
 
+    /// A second handle onto the file, for syncing it without access to the `Wal`.
+    pub fn sync_handle(&self) -> Result<File> {
+        Ok(self.file.try_clone()?)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     pub fn replay(&self) -> Result<Vec<WalRecord>> {
-        let file = OpenOptions::new().read(true).open(&self.path)?;
+        Self::replay_path(&self.path)
+    }
+
+    /// Reads the records of a WAL file without opening it for writing.
+    pub fn replay_path(path: &Path) -> Result<Vec<WalRecord>> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let Some(format) = read_format(&file, path)? else {
+            return Ok(Vec::new());
+        };
         let mut reader = BufReader::new(file);
+        if format != WalFormat::Legacy {
+            reader.seek(SeekFrom::Start(WAL_HEADER_LEN))?;
+        }
 
         let mut records = Vec::new();
+        let mut open_batch = None;
 
         loop {
             let mut len_buf = [0u8; 4];
@@ -140,8 +503,17 @@ impl Wal {
                 break;
             }
 
-            match serde_json::from_slice::<WalRecord>(&data) {
+            let decoded = match format {
+                WalFormat::Legacy => serde_json::from_slice::<WalRecord>(&data).map_err(Into::into),
+                WalFormat::Versioned(_) => decode_record(&data),
+            };
+            match decoded {
                 Ok(record) => {
+                    match record {
+                        WalRecord::BeginBatch => open_batch = Some(records.len()),
+                        WalRecord::CommitBatch => open_batch = None,
+                        _ => {}
+                    }
                     records.push(record);
                 }
                 Err(_) => {
@@ -150,10 +522,539 @@ impl Wal {
             }
         }
 
+        if let Some(start) = open_batch {
+            records.truncate(start);
+        }
         Ok(records)
     }
 }
 
+/// The WAL files `EmbedDb::open` replays from `data_dir`, in order: the sealed segments, then
+/// `wal.log` (or `wal.prev` when an interrupted checkpoint left only that).
+pub fn replay_files(data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = list_segments(&sealed_dir(data_dir))?;
+    let live = data_dir.join("wal.log");
+    let prev = data_dir.join("wal.prev");
+    if live.exists() {
+        files.push(live);
+    } else if prev.exists() {
+        files.push(prev);
+    }
+    Ok(files)
+}
+
+/// One frame of a WAL file, as read by `inspect`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WalFrame {
+    /// Byte offset of the frame's length prefix.
+    pub offset: u64,
+    /// Length of the framed data, without the 8-byte length and checksum prefix.
+    pub len: u32,
+    pub checksum_ok: bool,
+    /// Whether the data is zstd-compressed.
+    pub compressed: bool,
+    /// Variant name of the decoded record; `None` when the frame does not decode.
+    pub record: Option<&'static str>,
+    pub table: Option<String>,
+    pub row_id: Option<u64>,
+    /// Why the frame cannot be replayed: a checksum mismatch or a record that does not decode.
+    pub error: Option<String>,
+}
+
+/// What replay makes of a WAL file, frame by frame; see `inspect`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WalInspection {
+    pub path: PathBuf,
+    /// `1` for a headerless file of JSON records, `3` for a file that may hold bincode records;
+    /// `None` when the file is empty or its header is torn.
+    pub format_version: Option<u8>,
+    pub file_bytes: u64,
+    pub frames: Vec<WalFrame>,
+    /// Records replay returns.
+    pub records_replayed: usize,
+    /// Offset replay stops at before the end of the file: a torn frame, a checksum mismatch, or a
+    /// record that does not decode.
+    pub first_corrupt_offset: Option<u64>,
+    pub corruption: Option<String>,
+    /// Intact records replay skips: those of a batch still open where replay stops, and the
+    /// checksum-valid frames after `first_corrupt_offset`.
+    pub records_dropped: usize,
+}
+
+impl WalInspection {
+    pub fn is_clean(&self) -> bool {
+        self.first_corrupt_offset.is_none() && self.records_dropped == 0
+    }
+}
+
+/// Reads every frame of a WAL file without replaying it, to find where and why replay would stop.
+/// Unlike replay, it carries on past a bad frame whose length still fits in the file, so the
+/// records lost behind it are counted too.
+pub fn inspect(path: &Path) -> Result<WalInspection> {
+    let file = OpenOptions::new().read(true).open(path)?;
+    let file_bytes = file.metadata()?.len();
+    let format = read_format(&file, path)?;
+    let mut inspection = WalInspection {
+        path: path.to_path_buf(),
+        format_version: format.map(|format| match format {
+            WalFormat::Legacy => 1,
+            WalFormat::Versioned(version) => version,
+        }),
+        file_bytes,
+        frames: Vec::new(),
+        records_replayed: 0,
+        first_corrupt_offset: None,
+        corruption: None,
+        records_dropped: 0,
+    };
+    let Some(format) = format else {
+        if file_bytes > 0 {
+            inspection.first_corrupt_offset = Some(0);
+            inspection.corruption = Some("torn file header".to_string());
+        }
+        return Ok(inspection);
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut offset = 0;
+    if format != WalFormat::Legacy {
+        offset = WAL_HEADER_LEN;
+        reader.seek(SeekFrom::Start(offset))?;
+    }
+    let mut open_batch = None;
+    while offset < file_bytes {
+        let mut header = [0u8; 8];
+        let torn = if offset + 8 > file_bytes {
+            Some("torn frame header".to_string())
+        } else {
+            reader.read_exact(&mut header)?;
+            let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            (offset + 8 + len as u64 > file_bytes)
+                .then(|| format!("frame of {len} bytes runs past the end of the file"))
+        };
+        if let Some(reason) = torn {
+            if inspection.first_corrupt_offset.is_none() {
+                inspection.first_corrupt_offset = Some(offset);
+                inspection.corruption = Some(reason);
+            }
+            break;
+        }
+
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let expected = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut data = vec![0u8; len as usize];
+        reader.read_exact(&mut data)?;
+        let mut hasher = Hasher::new();
+        hasher.update(&data);
+        let actual = hasher.finalize();
+        let mut frame = WalFrame {
+            offset,
+            len,
+            checksum_ok: actual == expected,
+            compressed: format != WalFormat::Legacy
+                && data.first().is_some_and(|kind| kind & KIND_ZSTD != 0),
+            record: None,
+            table: None,
+            row_id: None,
+            error: None,
+        };
+        let mut marker = None;
+        if !frame.checksum_ok {
+            frame.error = Some(format!(
+                "checksum mismatch (stored {expected:08x}, computed {actual:08x})"
+            ));
+        } else {
+            let decoded = match format {
+                WalFormat::Legacy => serde_json::from_slice::<WalRecord>(&data).map_err(Into::into),
+                WalFormat::Versioned(_) => decode_record(&data),
+            };
+            match decoded {
+                Ok(record) => {
+                    frame.record = Some(record.name());
+                    frame.table = record.table().map(str::to_string);
+                    frame.row_id = record.row_id();
+                    marker = Some(record);
+                }
+                Err(err) => frame.error = Some(format!("record does not decode: {err}")),
+            }
+        }
+
+        if inspection.first_corrupt_offset.is_some() {
+            if frame.error.is_none() {
+                inspection.records_dropped += 1;
+            }
+        } else if let Some(error) = &frame.error {
+            inspection.first_corrupt_offset = Some(offset);
+            inspection.corruption = Some(error.clone());
+        } else {
+            match marker {
+                Some(WalRecord::BeginBatch) => open_batch = Some(inspection.records_replayed),
+                Some(WalRecord::CommitBatch) => open_batch = None,
+                _ => {}
+            }
+            inspection.records_replayed += 1;
+        }
+        offset += 8 + len as u64;
+        inspection.frames.push(frame);
+    }
+
+    if let Some(start) = open_batch {
+        inspection.records_dropped += inspection.records_replayed - start;
+        inspection.records_replayed = start;
+    }
+    Ok(inspection)
+}
+
+/// The file's format from its first bytes; `None` for an empty file or a torn header.
+fn read_format(file: &File, path: &Path) -> Result<Option<WalFormat>> {
+    let mut head = Vec::with_capacity(WAL_HEADER_LEN as usize);
+    file.take(WAL_HEADER_LEN).read_to_end(&mut head)?;
+    let mut handle = file;
+    handle.seek(SeekFrom::Start(0))?;
+    if head.len() < WAL_MAGIC.len() {
+        // Anything this short holds no complete record.
+        return Ok((!WAL_MAGIC.starts_with(&head)).then_some(WalFormat::Legacy));
+    }
+    if &head[..WAL_MAGIC.len()] != WAL_MAGIC {
+        return Ok(Some(WalFormat::Legacy));
+    }
+    match head.get(WAL_MAGIC.len()) {
+        None => Ok(None),
+        Some(&version @ (WAL_FORMAT_VERSION | WAL_FORMAT_VERSION_BINCODE)) => {
+            Ok(Some(WalFormat::Versioned(version)))
+        }
+        Some(version) => Err(anyhow!(
+            "unsupported WAL format version {version} in {}",
+            path.display()
+        )),
+    }
+}
+
+/// Whether `record` carries a `TableSchema` or `EmbeddingSpec`. Those leave empty fields out
+/// when serialized, which bincode can't read back, so they are always written as JSON.
+fn carries_schema(record: &WalRecord) -> bool {
+    matches!(
+        record,
+        WalRecord::CreateTable { .. }
+            | WalRecord::SetEmbeddingSpec { .. }
+            | WalRecord::AlterTable { .. }
+            | WalRecord::SchemaChanges { .. }
+    )
+}
+
+fn encode_record(
+    record: &WalRecord,
+    encoding: WalEncoding,
+    compression: Option<i32>,
+) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    match record {
+        WalRecord::StoreEmbedding {
+            table,
+            row_id,
+            vector,
+            norm,
+        } => {
+            data.push(KIND_STORE_EMBEDDING);
+            put_str(&mut data, table);
+            data.extend_from_slice(&row_id.to_le_bytes());
+            match norm {
+                Some(norm) => {
+                    data.push(1);
+                    data.extend_from_slice(&norm.to_le_bytes());
+                }
+                None => data.push(0),
+            }
+            put_vector(&mut data, vector);
+        }
+        WalRecord::StoreNamedEmbedding {
+            table,
+            row_id,
+            name,
+            vector,
+        } => {
+            data.push(KIND_STORE_NAMED_EMBEDDING);
+            put_str(&mut data, table);
+            data.extend_from_slice(&row_id.to_le_bytes());
+            put_str(&mut data, name);
+            put_vector(&mut data, vector);
+        }
+        record if encoding == WalEncoding::Bincode && !carries_schema(record) => {
+            data.push(KIND_BINCODE);
+            bincode::serialize_into(&mut data, record)?;
+        }
+        record => {
+            data.push(KIND_JSON);
+            serde_json::to_writer(&mut data, record)?;
+        }
+    }
+    if let Some(level) = compression.filter(|_| data.len() >= COMPRESS_MIN_BYTES) {
+        let compressed = zstd::bulk::compress(&data[1..], level)?;
+        if compressed.len() + 1 < data.len() {
+            let mut framed = Vec::with_capacity(compressed.len() + 1);
+            framed.push(data[0] | KIND_ZSTD);
+            framed.extend_from_slice(&compressed);
+            return Ok(framed);
+        }
+    }
+    Ok(data)
+}
+
+fn decode_record(data: &[u8]) -> Result<WalRecord> {
+    let (&kind, body) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("empty WAL record"))?;
+    let decompressed;
+    let body = if kind & KIND_ZSTD != 0 {
+        decompressed = zstd::stream::decode_all(body)?;
+        &decompressed[..]
+    } else {
+        body
+    };
+    let mut reader = RecordReader(body);
+    let record = match kind & !KIND_ZSTD {
+        KIND_JSON => return Ok(serde_json::from_slice(body)?),
+        KIND_BINCODE => return Ok(bincode::deserialize(body)?),
+        KIND_STORE_EMBEDDING => WalRecord::StoreEmbedding {
+            table: reader.string()?,
+            row_id: reader.u64()?,
+            norm: match reader.take(1)?[0] {
+                0 => None,
+                _ => Some(reader.f32()?),
+            },
+            vector: reader.vector()?,
+        },
+        KIND_STORE_NAMED_EMBEDDING => WalRecord::StoreNamedEmbedding {
+            table: reader.string()?,
+            row_id: reader.u64()?,
+            name: reader.string()?,
+            vector: reader.vector()?,
+        },
+        other => return Err(anyhow!("unknown WAL record kind {other}")),
+    };
+    if !reader.0.is_empty() {
+        return Err(anyhow!("trailing bytes in WAL record"));
+    }
+    Ok(record)
+}
+
+fn put_str(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn put_vector(data: &mut Vec<u8>, vector: &[f32]) {
+    data.extend_from_slice(&(vector.len() as u32).to_le_bytes());
+    for value in vector {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Reads the fields of a binary record in order.
+struct RecordReader<'a>(&'a [u8]);
+
+impl<'a> RecordReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(anyhow!("truncated WAL record"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+
+    fn vector(&mut self) -> Result<Vec<f32>> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(
+            len.checked_mul(4)
+                .ok_or_else(|| anyhow!("vector too long"))?,
+        )?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect())
+    }
+}
+
+/// Length of the header and the run of complete, checksum-valid records after it, up to the start
+/// of a batch that never committed.
+fn valid_prefix_len(file: &File, format: WalFormat) -> Result<u64> {
+    // Markers are compared as encoded bytes, in either encoding, so other records need not be
+    // decoded.
+    let encode = |record: &WalRecord| -> Result<Vec<Vec<u8>>> {
+        match format {
+            WalFormat::Legacy => Ok(vec![serde_json::to_vec(record)?]),
+            WalFormat::Versioned(_) => Ok(vec![
+                encode_record(record, WalEncoding::Json, None)?,
+                encode_record(record, WalEncoding::Bincode, None)?,
+            ]),
+        }
+    };
+    let begin = encode(&WalRecord::BeginBatch)?;
+    let commit = encode(&WalRecord::CommitBatch)?;
+    let mut reader = BufReader::new(file);
+    let mut valid = 0u64;
+    let mut open_batch = None;
+    if format != WalFormat::Legacy {
+        reader.seek(SeekFrom::Start(WAL_HEADER_LEN))?;
+        valid = WAL_HEADER_LEN;
+    }
+    loop {
+        let mut header = [0u8; 8];
+        if reader.read_exact(&mut header).is_err() {
+            break;
+        }
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let expected = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut data = vec![0u8; len];
+        if reader.read_exact(&mut data).is_err() {
+            break;
+        }
+        let mut hasher = Hasher::new();
+        hasher.update(&data);
+        if hasher.finalize() != expected {
+            break;
+        }
+        if begin.contains(&data) {
+            open_batch = Some(valid);
+        } else if commit.contains(&data) {
+            open_batch = None;
+        }
+        valid += 8 + len as u64;
+    }
+    Ok(open_batch.unwrap_or(valid))
+}
+
+/// Shares WAL syncs between concurrent writers. A writer appends its records without syncing
+/// while it holds the database lock, takes a ticket from `register`, and calls `wait` once the
+/// lock is released. The first waiter becomes the leader: it lets other writers append for up to
+/// `max_latency`, then syncs once for every record registered so far.
+#[derive(Debug)]
+pub struct GroupCommit {
+    max_latency: Duration,
+    state: Mutex<GroupState>,
+    synced: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct GroupState {
+    file: Option<(PathBuf, Arc<File>)>,
+    registered: u64,
+    synced: u64,
+    leader: bool,
+    /// Highest ticket covered by a failed sync, with its error.
+    failed: Option<(u64, String)>,
+    syncs: u64,
+}
+
+impl GroupCommit {
+    pub fn new(max_latency: Duration) -> Self {
+        Self {
+            max_latency,
+            state: Mutex::new(GroupState::default()),
+            synced: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, GroupState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Starts syncing `wal`, the live WAL file.
+    pub fn attach(&self, wal: &Wal) -> Result<()> {
+        let file = wal.sync_handle()?;
+        self.lock().file = Some((wal.path().to_path_buf(), Arc::new(file)));
+        Ok(())
+    }
+
+    /// Settles every registered ticket and drops the file handle. Called with the database lock
+    /// held, after the caller synced the live WAL itself and before it replaces the file.
+    pub fn detach(&self) {
+        let mut state = self.lock();
+        state.file = None;
+        state.synced = state.registered;
+        drop(state);
+        self.synced.notify_all();
+    }
+
+    /// Ticket for the records appended since the previous call; pass it to `wait`.
+    pub fn register(&self) -> u64 {
+        let mut state = self.lock();
+        state.registered += 1;
+        state.registered
+    }
+
+    /// Blocks until the records behind `ticket` are durable.
+    pub fn wait(&self, ticket: u64) -> Result<()> {
+        let mut state = self.lock();
+        loop {
+            if state.synced >= ticket {
+                return Ok(());
+            }
+            if let Some((failed, err)) = &state.failed {
+                if ticket <= *failed {
+                    return Err(anyhow!("WAL sync failed: {err}"));
+                }
+            }
+            if state.leader {
+                state = self
+                    .synced
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                continue;
+            }
+
+            state.leader = true;
+            drop(state);
+            if !self.max_latency.is_zero() {
+                std::thread::sleep(self.max_latency);
+            }
+            let (file, target) = {
+                let state = self.lock();
+                (state.file.clone(), state.registered)
+            };
+            let result = match &file {
+                Some((path, file)) => fault::sync_wal(path, file),
+                None => Ok(()),
+            };
+            state = self.lock();
+            state.leader = false;
+            match result {
+                Ok(()) => {
+                    state.synced = state.synced.max(target);
+                    state.syncs += u64::from(file.is_some());
+                }
+                Err(err) => state.failed = Some((target, err.to_string())),
+            }
+            self.synced.notify_all();
+        }
+    }
+
+    /// Syncs run by group leaders so far.
+    pub fn syncs(&self) -> u64 {
+        self.lock().syncs
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,7 +1064,7 @@ mod tests {
     fn wal_replay_roundtrip() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("wal.log");
-        let mut wal = Wal::open(path.clone()).unwrap();
+        let mut wal = Wal::open(path.clone(), WalEncoding::Json).unwrap();
 
         wal.append(
             &WalRecord::DeleteRow {
@@ -174,16 +1075,208 @@ mod tests {
         )
         .unwrap();
 
-        let wal = Wal::open(path).unwrap();
+        let wal = Wal::open(path, WalEncoding::Json).unwrap();
         let records = wal.replay().unwrap();
         assert_eq!(records.len(), 1);
     }
 
+    #[test]
+    fn binary_and_compressed_records_replay_alongside_legacy_files() {
+        let dir = tempdir().unwrap();
+        let embedding = WalRecord::StoreEmbedding {
+            table: "t".to_string(),
+            row_id: 7,
+            // Full-precision values, repeating so that compression has something to find.
+            vector: (0..256).map(|i| ((i % 4) as f32 + 0.3).sqrt()).collect(),
+            norm: Some(2.0),
+        };
+        let named = WalRecord::StoreNamedEmbedding {
+            table: "t".to_string(),
+            row_id: 7,
+            name: "body".to_string(),
+            vector: vec![1.5, -2.0],
+        };
+        let delete = WalRecord::DeleteRow {
+            table: "t".to_string(),
+            row_id: 8,
+        };
+        let json_len = serde_json::to_vec(&embedding).unwrap().len();
+
+        let mut sizes = Vec::new();
+        for compression in [None, Some(3)] {
+            let path = dir.path().join(format!("wal-{compression:?}.log"));
+            let mut wal = Wal::create_new(path.clone(), WalEncoding::Json)
+                .unwrap()
+                .with_compression(compression);
+            for record in [&embedding, &named, &delete] {
+                wal.append(record, true).unwrap();
+            }
+            sizes.push(wal.len());
+            let records = Wal::open(path, WalEncoding::Json)
+                .unwrap()
+                .replay()
+                .unwrap();
+            assert_eq!(
+                format!("{records:?}"),
+                format!("{:?}", [&embedding, &named, &delete])
+            );
+        }
+        assert!(sizes[0] < json_len as u64);
+        assert!(sizes[1] < sizes[0]);
+
+        // A headerless file from before format versions keeps its JSON framing.
+        let path = dir.path().join("legacy.log");
+        let data = serde_json::to_vec(&delete).unwrap();
+        let mut hasher = Hasher::new();
+        hasher.update(&data);
+        let mut frame = (data.len() as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(&hasher.finalize().to_le_bytes());
+        frame.extend_from_slice(&data);
+        fs::write(&path, &frame).unwrap();
+        let mut wal = Wal::open(path.clone(), WalEncoding::Json)
+            .unwrap()
+            .with_compression(Some(3));
+        wal.append(&embedding, true).unwrap();
+        let records = Wal::replay_path(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(&fs::read(&path).unwrap()[..frame.len()], &frame[..]);
+        assert!(fs::read(&path).unwrap()[frame.len() + 8] == b'{');
+
+        let path = dir.path().join("future.log");
+        fs::write(&path, b"EDBWAL\x09").unwrap();
+        assert!(Wal::open(path.clone(), WalEncoding::Json).is_err());
+        assert!(Wal::replay_path(&path).is_err());
+    }
+
+    #[test]
+    fn bincode_records_replay_alongside_json_ones() {
+        use crate::schema::{Column, DataType, RowData};
+        use crate::Value;
+
+        let dir = tempdir().unwrap();
+        let create = WalRecord::CreateTable {
+            name: "t".to_string(),
+            schema: TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            embedding_spec: Some(EmbeddingSpec::new(vec!["title"])),
+        };
+        let put = WalRecord::PutRow {
+            table: "t".to_string(),
+            row_id: 1,
+            row: RowData {
+                id: 1,
+                version: 1,
+                created_at_ms: 5,
+                updated_at_ms: 5,
+                fields: [("title".to_string(), Value::String("hello".to_string()))].into(),
+            },
+        };
+        let status = WalRecord::UpdateEmbeddingStatus {
+            table: "t".to_string(),
+            row_id: 1,
+            status: EmbeddingStatus::Failed,
+            last_error: Some("timeout".to_string()),
+            attempts: Some(2),
+            next_retry_at_ms: None,
+            model: Some(EmbeddingModel::new("mini").with_version("2")),
+        };
+        let sparse = WalRecord::StoreSparseVector {
+            table: "t".to_string(),
+            row_id: 1,
+            vector: SparseVector::from_pairs([(3, 0.5), (1, 2.0)]).unwrap(),
+        };
+        let ranges = WalRecord::DeleteRanges {
+            table: "t".to_string(),
+            ranges: vec![2..4, 9..10],
+        };
+        let records = [
+            create,
+            WalRecord::BeginBatch,
+            put,
+            status,
+            sparse,
+            WalRecord::CommitBatch,
+            ranges,
+        ];
+
+        let path = dir.path().join("wal.log");
+        let mut wal = Wal::create_new(path.clone(), WalEncoding::Bincode).unwrap();
+        for record in &records {
+            wal.append(record, false).unwrap();
+        }
+        // A batch left open is still cut off when its markers are bincode.
+        wal.append(&WalRecord::BeginBatch, true).unwrap();
+        let len = wal.len();
+        drop(wal);
+        let wal = Wal::open(path.clone(), WalEncoding::Bincode).unwrap();
+        assert!(wal.len() < len);
+        assert_eq!(
+            format!("{:?}", wal.replay().unwrap()),
+            format!("{records:?}")
+        );
+        let inspection = inspect(&path).unwrap();
+        assert_eq!(inspection.format_version, Some(WAL_FORMAT_VERSION_BINCODE));
+        assert!(inspection.is_clean());
+        let data = fs::read(&path).unwrap();
+        // The schema-carrying `CreateTable` stays JSON; the next record is bincode.
+        let first = WAL_HEADER_LEN as usize;
+        assert_eq!(data[first + 8], KIND_JSON);
+        let len = u32::from_le_bytes(data[first..first + 4].try_into().unwrap()) as usize;
+        assert_eq!(data[first + 8 + len + 8], KIND_BINCODE);
+
+        // A version 2 file opened for bincode keeps taking JSON records.
+        let path = dir.path().join("v2.log");
+        drop(Wal::create_new(path.clone(), WalEncoding::Json).unwrap());
+        let mut wal = Wal::open(path.clone(), WalEncoding::Bincode).unwrap();
+        wal.append(&records[6], true).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data[WAL_MAGIC.len()], WAL_FORMAT_VERSION);
+        assert_eq!(data[WAL_HEADER_LEN as usize + 8], KIND_JSON);
+    }
+
+    #[test]
+    fn group_commit_shares_one_sync_between_waiters() {
+        let dir = tempdir().unwrap();
+        let mut wal = Wal::open(dir.path().join("wal.log"), WalEncoding::Json).unwrap();
+        let group = Arc::new(GroupCommit::new(Duration::from_millis(20)));
+        group.attach(&wal).unwrap();
+
+        let record = WalRecord::DeleteRow {
+            table: "t".to_string(),
+            row_id: 1,
+        };
+        let tickets: Vec<u64> = (0..4)
+            .map(|_| {
+                wal.append(&record, false).unwrap();
+                group.register()
+            })
+            .collect();
+        let waiters: Vec<_> = tickets
+            .into_iter()
+            .map(|ticket| {
+                let group = group.clone();
+                std::thread::spawn(move || group.wait(ticket))
+            })
+            .collect();
+        for waiter in waiters {
+            waiter.join().unwrap().unwrap();
+        }
+        assert!(group.syncs() < 4);
+
+        // Detaching settles tickets without another sync.
+        wal.append(&record, false).unwrap();
+        let ticket = group.register();
+        wal.sync().unwrap();
+        group.detach();
+        let syncs = group.syncs();
+        group.wait(ticket).unwrap();
+        assert_eq!(group.syncs(), syncs);
+    }
+
     #[test]
     fn wal_ignores_partial_record() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("wal.log");
-        let mut wal = Wal::open(path.clone()).unwrap();
+        let mut wal = Wal::open(path.clone(), WalEncoding::Json).unwrap();
 
         wal.append(
             &WalRecord::DeleteRow {
@@ -198,8 +1291,108 @@ mod tests {
         file.write_all(&10u32.to_le_bytes()).unwrap();
         file.flush().unwrap();
 
-        let wal = Wal::open(path).unwrap();
+        let wal = Wal::open(path, WalEncoding::Json).unwrap();
         let records = wal.replay().unwrap();
         assert_eq!(records.len(), 1);
     }
+
+    #[test]
+    fn open_cuts_off_an_uncommitted_batch() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wal.log");
+        let mut wal = Wal::open(path.clone(), WalEncoding::Json).unwrap();
+        let delete = |row_id| WalRecord::DeleteRow {
+            table: "t".to_string(),
+            row_id,
+        };
+        for record in [
+            WalRecord::BeginBatch,
+            delete(1),
+            WalRecord::CommitBatch,
+            delete(2),
+        ] {
+            wal.append(&record, true).unwrap();
+        }
+        let committed = wal.len();
+        wal.append(&WalRecord::BeginBatch, true).unwrap();
+        wal.append(&delete(3), true).unwrap();
+
+        // Replay skips the open batch even while the file still holds it.
+        assert_eq!(Wal::replay_path(&path).unwrap().len(), 4);
+        let mut wal = Wal::open(path.clone(), WalEncoding::Json).unwrap();
+        assert_eq!(wal.len(), committed);
+        wal.append(&delete(4), true).unwrap();
+        let records = wal.replay().unwrap();
+        assert_eq!(
+            format!("{records:?}"),
+            format!(
+                "{:?}",
+                [
+                    WalRecord::BeginBatch,
+                    delete(1),
+                    WalRecord::CommitBatch,
+                    delete(2),
+                    delete(4),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn inspect_finds_the_first_corrupt_frame_and_counts_dropped_records() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wal.log");
+        let mut wal = Wal::open(path.clone(), WalEncoding::Json).unwrap();
+        let delete = |row_id| WalRecord::DeleteRow {
+            table: "t".to_string(),
+            row_id,
+        };
+        let mut offsets = Vec::new();
+        for record in [
+            delete(1),
+            WalRecord::BeginBatch,
+            delete(2),
+            delete(3),
+            WalRecord::CommitBatch,
+            delete(4),
+        ] {
+            offsets.push(wal.len());
+            wal.append(&record, true).unwrap();
+        }
+
+        let clean = inspect(&path).unwrap();
+        assert!(clean.is_clean());
+        assert_eq!((clean.format_version, clean.records_replayed), (Some(2), 6));
+        assert_eq!(clean.frames[2].record, Some("DeleteRow"));
+        assert_eq!(clean.frames[2].table.as_deref(), Some("t"));
+        assert_eq!(clean.frames[2].row_id, Some(2));
+        assert_eq!(clean.frames[1].row_id, None);
+
+        // A flipped byte inside the batch: replay keeps the first two records, drops the open
+        // batch's `BeginBatch`, and never reaches the three intact frames behind the bad one.
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[offsets[2] as usize + 10] ^= 0xff;
+        fs::write(&path, &bytes).unwrap();
+        let damaged = inspect(&path).unwrap();
+        assert_eq!(damaged.first_corrupt_offset, Some(offsets[2]));
+        assert!(damaged.corruption.as_deref().unwrap().contains("checksum"));
+        assert!(!damaged.frames[2].checksum_ok);
+        assert_eq!(damaged.records_replayed, 1);
+        assert_eq!(damaged.records_dropped, 4);
+        assert_eq!(Wal::replay_path(&path).unwrap().len(), 1);
+
+        // A length running past the end of the file ends the scan, as does a torn frame header
+        // once the flipped byte is restored.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xff, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+        let torn = inspect(&path).unwrap();
+        assert_eq!(torn.frames.len(), 6);
+        assert_eq!(torn.first_corrupt_offset, Some(offsets[2]));
+        bytes[offsets[2] as usize + 10] ^= 0xff;
+        fs::write(&path, &bytes[..offsets[5] as usize + 3]).unwrap();
+        let torn = inspect(&path).unwrap();
+        assert_eq!(torn.first_corrupt_offset, Some(offsets[5]));
+        assert_eq!(torn.corruption.as_deref(), Some("torn frame header"));
+        assert_eq!((torn.records_replayed, torn.records_dropped), (5, 0));
+    }
 }
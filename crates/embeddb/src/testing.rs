@@ -0,0 +1,232 @@
+//! Fault injection for recovery tests (feature `testing`).
+//!
+//! A [`FaultInjector`] installed for a data directory makes the WAL and SST writers under it
+//! fail the way a crash or a misbehaving disk would: appends cut short after a byte budget,
+//! syncs that report success without reaching the disk, and failing renames. A recovery test
+//! arms a fault, drives the database until an operation fails, drops it, optionally calls
+//! [`FaultInjector::lose_unsynced_writes`] to model power loss, and reopens the directory to
+//! check what survived.
+//!
+//! Injectors are scoped by path, so tests working in different directories can run in
+//! parallel.
+//!
+//! ```no_run
+//! # use embeddb::{testing::FaultInjector, Config, EmbedDb};
+//! let dir = std::path::PathBuf::from("/tmp/embeddb-recovery");
+//! let faults = FaultInjector::install(&dir);
+//! let db = EmbedDb::open(Config::new(dir.clone()))?;
+//! faults.truncate_wal_after(64);
+//! // ... writes until one fails with an injected fault ...
+//! drop(db);
+//! faults.clear();
+//! let db = EmbedDb::open(Config::new(dir))?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// Counts installed injectors so the unarmed I/O path skips the registry lock.
+static INSTALLED: AtomicUsize = AtomicUsize::new(0);
+static INJECTORS: Mutex<Vec<Arc<Injector>>> = Mutex::new(Vec::new());
+
+/// Injects faults into the WAL and SST files under one data directory until dropped. No fault
+/// fires until one is armed.
+#[derive(Debug)]
+pub struct FaultInjector {
+    injector: Arc<Injector>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Injector {
+    root: PathBuf,
+    faults: Mutex<Faults>,
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    wal_bytes: Option<u64>,
+    sst_bytes: Option<u64>,
+    drop_syncs: bool,
+    renames: Option<usize>,
+    // Length of each WAL file as of its last real sync, or when it was first written under the
+    // injector.
+    synced_len: HashMap<PathBuf, u64>,
+    injected: usize,
+}
+
+impl FaultInjector {
+    pub fn install(data_dir: impl AsRef<Path>) -> Self {
+        let injector = Arc::new(Injector {
+            root: data_dir.as_ref().to_path_buf(),
+            faults: Mutex::new(Faults::default()),
+        });
+        registry().push(injector.clone());
+        INSTALLED.fetch_add(1, Ordering::SeqCst);
+        Self { injector }
+    }
+
+    /// Lets `bytes` more bytes of WAL appends through, then writes only part of the next record
+    /// and fails the append, leaving a torn record at the end of the file.
+    pub fn truncate_wal_after(&self, bytes: u64) {
+        self.injector.lock().wal_bytes = Some(bytes);
+    }
+
+    /// Lets `bytes` more bytes of SST files be written, then leaves the next file incomplete and
+    /// fails its write.
+    pub fn truncate_sst_after(&self, bytes: u64) {
+        self.injector.lock().sst_bytes = Some(bytes);
+    }
+
+    /// While set, WAL syncs report success without syncing, so the records behind them count
+    /// as unsynced for `lose_unsynced_writes`.
+    pub fn drop_syncs(&self, drop: bool) {
+        self.injector.lock().drop_syncs = drop;
+    }
+
+    /// Lets `renames` more WAL and SST renames through, then fails every later one.
+    pub fn fail_renames_after(&self, renames: usize) {
+        self.injector.lock().renames = Some(renames);
+    }
+
+    /// Disarms every fault. Unsynced lengths are still tracked.
+    pub fn clear(&self) {
+        let mut faults = self.injector.lock();
+        faults.wal_bytes = None;
+        faults.sst_bytes = None;
+        faults.drop_syncs = false;
+        faults.renames = None;
+    }
+
+    /// Faults fired so far, counting each dropped sync.
+    pub fn injected(&self) -> usize {
+        self.injector.lock().injected
+    }
+
+    /// Models power loss: cuts every WAL file written under the injector back to its length as
+    /// of its last real sync. Call it after dropping the database.
+    pub fn lose_unsynced_writes(&self) -> io::Result<()> {
+        let faults = self.injector.lock();
+        for (path, len) in &faults.synced_len {
+            if !path.exists() || fs::metadata(path)?.len() <= *len {
+                continue;
+            }
+            OpenOptions::new().write(true).open(path)?.set_len(*len)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FaultInjector {
+    fn drop(&mut self) {
+        registry().retain(|injector| !Arc::ptr_eq(injector, &self.injector));
+        INSTALLED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn registry() -> MutexGuard<'static, Vec<Arc<Injector>>> {
+    INJECTORS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The injector covering `path`, if any.
+pub(crate) fn injector_for(path: &Path) -> Option<Arc<Injector>> {
+    if INSTALLED.load(Ordering::SeqCst) == 0 {
+        return None;
+    }
+    registry()
+        .iter()
+        .find(|injector| path.starts_with(&injector.root))
+        .cloned()
+}
+
+fn injected(what: &str) -> io::Error {
+    io::Error::other(format!("injected fault: {what}"))
+}
+
+impl Injector {
+    fn lock(&self) -> MutexGuard<'_, Faults> {
+        self.faults
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn write_wal(&self, path: &Path, file: &mut File, data: &[u8]) -> io::Result<()> {
+        let mut faults = self.lock();
+        if !faults.synced_len.contains_key(path) {
+            let len = file.metadata()?.len();
+            faults.synced_len.insert(path.to_path_buf(), len);
+        }
+        let allowed = take_budget(&mut faults.wal_bytes, data.len());
+        if allowed < data.len() {
+            faults.injected += 1;
+            drop(faults);
+            file.write_all(&data[..allowed])?;
+            file.flush()?;
+            return Err(injected("WAL append cut short"));
+        }
+        drop(faults);
+        file.write_all(data)
+    }
+
+    pub(crate) fn sync_wal(&self, path: &Path, file: &File) -> io::Result<()> {
+        let mut faults = self.lock();
+        if faults.drop_syncs {
+            faults.injected += 1;
+            return Ok(());
+        }
+        file.sync_data()?;
+        let len = file.metadata()?.len();
+        faults.synced_len.insert(path.to_path_buf(), len);
+        Ok(())
+    }
+
+    pub(crate) fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut faults = self.lock();
+        let allowed = take_budget(&mut faults.sst_bytes, data.len());
+        if allowed < data.len() {
+            faults.injected += 1;
+            drop(faults);
+            fs::write(path, &data[..allowed])?;
+            return Err(injected("SST write cut short"));
+        }
+        drop(faults);
+        fs::write(path, data)
+    }
+
+    pub(crate) fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut faults = self.lock();
+        match faults.renames {
+            Some(0) => {
+                faults.injected += 1;
+                return Err(injected("rename failed"));
+            }
+            Some(left) => faults.renames = Some(left - 1),
+            None => {}
+        }
+        fs::rename(from, to)?;
+        // Renames are treated as durable, so the synced length moves with the file.
+        match faults.synced_len.remove(from) {
+            Some(len) => faults.synced_len.insert(to.to_path_buf(), len),
+            None => faults.synced_len.remove(to),
+        };
+        Ok(())
+    }
+}
+
+/// Bytes of a `len`-byte write that fit in `budget`, charging them to it.
+fn take_budget(budget: &mut Option<u64>, len: usize) -> usize {
+    match budget {
+        Some(left) => {
+            let allowed = (*left).min(len as u64);
+            *left -= allowed;
+            allowed as usize
+        }
+        None => len,
+    }
+}
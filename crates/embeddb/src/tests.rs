@@ -46,6 +46,170 @@ fn insert_and_process_embedding_job() {
     assert_eq!(jobs[0].status, EmbeddingStatus::Ready);
 }
 
+#[test]
+fn put_embedding_stores_vector_and_marks_job_ready() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("Hello".to_string()));
+    let first = db.insert_row("notes", fields.clone()).unwrap();
+    let second = db.insert_row("notes", fields).unwrap();
+
+    db.put_embedding("notes", first, vec![0.5, 0.25]).unwrap();
+    let jobs = db.list_embedding_jobs("notes").unwrap();
+    assert_eq!(jobs[0].status, EmbeddingStatus::Ready);
+    assert_eq!(jobs[1].status, EmbeddingStatus::Pending);
+    let page = db.scroll_embeddings("notes", None, 10).unwrap();
+    assert_eq!(page.items[0].vector, Some(vec![0.5, 0.25]));
+
+    let err = db.put_embedding("notes", second, vec![1.0]).unwrap_err();
+    assert!(err.to_string().contains("expects 2"));
+    assert!(db.put_embedding("notes", 99, vec![1.0, 2.0]).is_err());
+    assert!(db
+        .put_embedding("notes", second, vec![1.0, f32::NAN])
+        .is_err());
+
+    let stored = db.get_embedding("notes", first).unwrap().unwrap();
+    assert_eq!(stored.vector, Some(vec![0.5, 0.25]));
+    assert_eq!(stored.status, EmbeddingStatus::Ready);
+    let pending = db.get_embedding("notes", second).unwrap().unwrap();
+    assert_eq!(
+        (pending.vector, pending.status),
+        (None, EmbeddingStatus::Pending)
+    );
+    assert!(db.get_embedding("notes", 99).unwrap().is_none());
+    db.delete_row("notes", first).unwrap();
+    assert!(db.get_embedding("notes", first).unwrap().is_none());
+    assert!(db.get_embedding("missing", first).is_err());
+}
+
+#[test]
+fn insert_with_embedding_skips_the_job_queue() {
+    let dir = tempdir().unwrap();
+    let title = |t: &str| BTreeMap::from([("title".to_string(), Value::String(t.to_string()))]);
+    let (first, second);
+    {
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table(
+            "notes",
+            schema.clone(),
+            Some(EmbeddingSpec::new(vec!["title"]).with_model(EmbeddingModel::new("etl-v2"))),
+        )
+        .unwrap();
+        db.create_table("plain", schema, None).unwrap();
+
+        first = db
+            .insert_row_with_embedding("notes", title("a"), vec![1.0, 0.0])
+            .unwrap();
+        let ids = db
+            .insert_rows_with_embeddings(
+                "notes",
+                vec![(title("b"), vec![0.0, 1.0]), (title("c"), vec![0.5, 0.5])],
+            )
+            .unwrap();
+        second = ids[0];
+        assert_eq!(ids, vec![first + 1, first + 2]);
+
+        let err = db
+            .insert_rows_with_embeddings(
+                "notes",
+                vec![(title("d"), vec![1.0, 1.0]), (title("e"), vec![1.0])],
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("row 1"), "{err}");
+        assert!(db
+            .insert_row_with_embedding("notes", title("f"), vec![f32::INFINITY, 0.0])
+            .is_err());
+        assert!(db
+            .insert_row_with_embedding("plain", title("g"), vec![1.0])
+            .is_err());
+        // Nothing from the failed inserts was written.
+        assert_eq!(db.count_rows("notes", &[]).unwrap(), 3);
+        assert_eq!(db.count_rows("plain", &[]).unwrap(), 0);
+
+        assert_eq!(db.process_pending_jobs("notes", &DummyEmbedder).unwrap(), 0);
+    }
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let jobs = db.list_embedding_jobs("notes").unwrap();
+    assert!(jobs.iter().all(|job| job.status == EmbeddingStatus::Ready));
+    let stored = db.get_embedding("notes", second).unwrap().unwrap();
+    assert_eq!(stored.vector, Some(vec![0.0, 1.0]));
+    assert_eq!(
+        stored.model.as_ref().map(|model| model.name.as_str()),
+        Some("etl-v2")
+    );
+    let hits = db
+        .search_knn("notes", &[1.0, 0.1], 1, DistanceMetric::Cosine)
+        .unwrap();
+    assert_eq!(hits[0].row_id, first);
+}
+
+#[test]
+fn embedding_retry_policy_is_per_table_and_persisted() {
+    let dir = tempdir().unwrap();
+    let schema = || TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let retry = RetryPolicy {
+        max_attempts: 3,
+        base_ms: 1_000,
+        cap_ms: 1_500,
+        jitter: 0.0,
+    };
+    {
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        let bad = RetryPolicy {
+            max_attempts: 0,
+            ..retry
+        };
+        let err = db
+            .create_table(
+                "bad",
+                schema(),
+                Some(EmbeddingSpec::new(vec!["title"]).with_retry(bad)),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("max_attempts"), "{err}");
+        let spec = EmbeddingSpec::new(vec!["title"]).with_retry(retry);
+        db.create_table("flaky", schema(), Some(spec)).unwrap();
+    }
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let spec = db.describe_table("flaky").unwrap().embedding_spec.unwrap();
+    assert_eq!(spec.retry, retry);
+    let fields = BTreeMap::from([("title".to_string(), Value::String("a".into()))]);
+    db.insert_row("flaky", fields).unwrap();
+    let mut now_ms = 1_000_000u64;
+    for expected_delay in [1_000, 1_500] {
+        db.process_pending_jobs_internal_at("flaky", &AlwaysFailEmbedder, None, now_ms)
+            .unwrap();
+        let job = &db.list_embedding_jobs("flaky").unwrap()[0];
+        assert_eq!(job.status, EmbeddingStatus::Pending);
+        assert_eq!(job.next_retry_at_ms, now_ms + expected_delay);
+        now_ms = job.next_retry_at_ms;
+    }
+    db.process_pending_jobs_internal_at("flaky", &AlwaysFailEmbedder, None, now_ms)
+        .unwrap();
+    assert_eq!(
+        db.list_embedding_jobs("flaky").unwrap()[0].status,
+        EmbeddingStatus::Failed
+    );
+
+    let jittered = RetryPolicy {
+        jitter: 0.5,
+        ..retry
+    };
+    let delays: BTreeSet<u64> = (0..64).map(|seed| jittered.backoff_ms(1, seed)).collect();
+    assert!(
+        delays.iter().all(|delay| (500..=1_000).contains(delay)),
+        "{delays:?}"
+    );
+    assert!(delays.len() > 8, "{delays:?}");
+}
+
 #[test]
 fn retry_failed_embedding_job_resets_status_and_error() {
     let dir = tempdir().unwrap();
@@ -66,7 +230,7 @@ fn retry_failed_embedding_job_resets_status_and_error() {
 
     // Drive the job to terminal failure by repeatedly processing it after its backoff expires.
     let mut now_ms = 1_000_000u64;
-    for attempt in 1..EMBEDDING_MAX_ATTEMPTS {
+    for attempt in 1..RetryPolicy::default().max_attempts {
         let processed = db
             .process_pending_jobs_internal_at("notes", &AlwaysFailEmbedder, None, now_ms)
             .unwrap();
@@ -78,7 +242,7 @@ fn retry_failed_embedding_job_resets_status_and_error() {
         assert_eq!(jobs[0].status, EmbeddingStatus::Pending);
         assert_eq!(jobs[0].last_error.as_deref(), Some("boom"));
 
-        let inner = db.inner.lock().unwrap();
+        let inner = db.inner.read().unwrap();
         let meta = inner
             .state
             .tables
@@ -137,7 +301,7 @@ fn embedding_retry_backoff_defers_until_next_retry_time() {
         .unwrap();
     assert_eq!(processed, 1);
 
-    let inner = db.inner.lock().unwrap();
+    let inner = db.inner.read().unwrap();
     let meta = inner
         .state
         .tables
@@ -163,7 +327,7 @@ fn embedding_retry_backoff_defers_until_next_retry_time() {
         .unwrap();
     assert_eq!(processed, 1);
 
-    let inner = db.inner.lock().unwrap();
+    let inner = db.inner.read().unwrap();
     let meta2 = inner
         .state
         .tables
@@ -214,6 +378,149 @@ fn process_pending_jobs_limit_processes_subset() {
     assert_eq!(processed, 1);
 }
 
+#[test]
+fn update_row_skips_reembedding_unchanged_source_fields() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("views", DataType::Int, false),
+    ]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    let fields = |title: &str, views: i64| {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("views".to_string(), Value::Int(views));
+        fields
+    };
+    let row_id = db.insert_row("notes", fields("hello", 0)).unwrap();
+
+    // Still pending: an update must keep the job queued.
+    db.update_row("notes", row_id, fields("hello", 1)).unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().skipped_unchanged, 0);
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let lsn = db.current_lsn().unwrap();
+    db.update_row("notes", row_id, fields("hello", 2)).unwrap();
+    // Only the row itself was logged.
+    assert_eq!(db.current_lsn().unwrap(), lsn + 1);
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!(stats.skipped_unchanged, 1);
+    assert_eq!(stats.embeddings_ready, 1);
+    assert_eq!(stats.embeddings_pending, 0);
+
+    db.update_row("notes", row_id, fields("hello world", 2))
+        .unwrap();
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!(stats.skipped_unchanged, 1);
+    assert_eq!(stats.embeddings_pending, 1);
+}
+
+#[test]
+fn embedding_job_pages_filter_by_status_and_cursor() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let embed_spec = EmbeddingSpec::new(vec!["title"]);
+    db.create_table("notes", schema, Some(embed_spec)).unwrap();
+
+    for i in 0..5 {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(format!("note-{i}")));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs_with_limit("notes", &DummyEmbedder, 2)
+        .unwrap();
+
+    let page = db
+        .list_embedding_jobs_page("notes", Some(EmbeddingStatus::Pending), None, 2)
+        .unwrap();
+    let ids: Vec<u64> = page.items.iter().map(|job| job.row_id).collect();
+    assert_eq!(ids, vec![3, 4]);
+    assert_eq!(page.next_cursor, Some(4));
+
+    let page = db
+        .list_embedding_jobs_page("notes", Some(EmbeddingStatus::Pending), page.next_cursor, 2)
+        .unwrap();
+    let ids: Vec<u64> = page.items.iter().map(|job| job.row_id).collect();
+    assert_eq!(ids, vec![5]);
+    assert_eq!(page.next_cursor, None);
+
+    let page = db
+        .list_embedding_jobs_page("notes", None, Some(1), 10)
+        .unwrap();
+    assert_eq!(page.items.len(), 4);
+    assert!(page.next_cursor.is_none());
+}
+
+#[test]
+fn embedding_job_listings_sort_and_page_by_offset() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for i in 0..5 {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(format!("note-{i}")));
+        db.insert_row("notes", fields).unwrap();
+    }
+    // Rows 1 and 2 fail once and wait for a retry.
+    db.process_pending_jobs_with_limit("notes", &AlwaysFailEmbedder, 2)
+        .unwrap();
+
+    let ids =
+        |page: &EmbeddingJobPage| -> Vec<u64> { page.items.iter().map(|job| job.row_id).collect() };
+    let by_attempts = JobListOptions {
+        sort: JobSort::Attempts,
+        descending: true,
+        offset: 1,
+        limit: Some(2),
+        ..JobListOptions::default()
+    };
+    let page = db
+        .list_embedding_jobs_with_options("notes", &by_attempts)
+        .unwrap();
+    assert_eq!(ids(&page), vec![2, 3]);
+    assert_eq!(page.total, 5);
+    assert_eq!(page.next_cursor, None);
+
+    let newest_first = JobListOptions {
+        cursor: Some(4),
+        descending: true,
+        limit: Some(2),
+        ..JobListOptions::default()
+    };
+    let page = db
+        .list_embedding_jobs_with_options("notes", &newest_first)
+        .unwrap();
+    assert_eq!(ids(&page), vec![3, 2]);
+    assert_eq!(page.total, 3);
+    assert_eq!(page.next_cursor, Some(2));
+
+    let pending_by_retry = JobListOptions {
+        status: Some(EmbeddingStatus::Pending),
+        sort: JobSort::NextRetryAt,
+        ..JobListOptions::default()
+    };
+    let page = db
+        .list_embedding_jobs_with_options("notes", &pending_by_retry)
+        .unwrap();
+    assert_eq!(ids(&page), vec![3, 4, 5, 1, 2]);
+
+    assert!(db
+        .list_embedding_jobs_with_options(
+            "notes",
+            &JobListOptions {
+                cursor: Some(1),
+                ..by_attempts
+            },
+        )
+        .is_err());
+}
+
 #[test]
 fn db_stats_reports_tables_and_wal_bytes() {
     let dir = tempdir().unwrap();
@@ -232,6 +539,123 @@ fn db_stats_reports_tables_and_wal_bytes() {
     assert!(stats.wal_sync_ops >= stats.wal_durable_appends);
 }
 
+#[test]
+fn insert_rows_syncs_once_per_batch_and_rejects_invalid_batches() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+
+    let before = db.db_stats().unwrap();
+    let ids = db
+        .insert_rows("notes", vec![title("a"), title("b"), title("c")])
+        .unwrap();
+    assert_eq!(ids, [1, 2, 3]);
+    let after = db.db_stats().unwrap();
+    assert_eq!(after.wal_sync_ops, before.wal_sync_ops + 1);
+    // A row and an embedding job per inserted row.
+    assert_eq!(after.wal_durable_appends, before.wal_durable_appends + 6);
+
+    let bad = BTreeMap::from([("title".to_string(), Value::Int(1))]);
+    let err = db.insert_rows("notes", vec![title("d"), bad]).unwrap_err();
+    assert!(err.to_string().contains("row 1"));
+    assert_eq!(db.table_stats("notes").unwrap().next_row_id, 4);
+    drop(db);
+
+    let db = EmbedDb::open(Config::new(data_dir)).unwrap();
+    let row = db.get_row("notes", 3).unwrap().unwrap();
+    assert_eq!(row.fields["title"], Value::String("c".to_string()));
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_pending, 3);
+    assert_eq!(db.insert_row("notes", title("d")).unwrap(), 4);
+}
+
+#[test]
+fn relaxed_durability_defers_wal_syncs() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+
+    let config = Config::new(data_dir.clone()).with_durability(Durability::OnCheckpointOnly);
+    let db = EmbedDb::open(config).unwrap();
+    db.create_table("notes", schema, None).unwrap();
+    let before = db.db_stats().unwrap();
+    for text in ["a", "b", "c"] {
+        db.insert_row("notes", title(text)).unwrap();
+    }
+    let after = db.db_stats().unwrap();
+    assert_eq!(after.wal_sync_ops, before.wal_sync_ops);
+    assert_eq!(after.wal_unsynced_records, before.wal_unsynced_records + 3);
+    db.checkpoint().unwrap();
+    let checkpointed = db.db_stats().unwrap();
+    assert_eq!(checkpointed.wal_unsynced_records, 0);
+    assert!(checkpointed.wal_sync_ops > after.wal_sync_ops);
+    db.insert_row("notes", title("d")).unwrap();
+    drop(db);
+
+    // Unsynced records still replay after a clean process exit.
+    let config =
+        Config::new(data_dir).with_durability(Durability::Interval(Duration::from_millis(10)));
+    let db = EmbedDb::open(config).unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().next_row_id, 5);
+    db.insert_row("notes", title("e")).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while db.db_stats().unwrap().wal_unsynced_records > 0 {
+        assert!(Instant::now() < deadline, "interval sync never ran");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn group_commit_shares_wal_syncs_between_concurrent_writers() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let config = Config::new(data_dir.clone()).with_wal_group_commit(Duration::from_millis(5));
+    let db = Arc::new(EmbedDb::open(config).unwrap());
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+    let before = db.db_stats().unwrap();
+
+    let writers: Vec<_> = (0..8)
+        .map(|writer| {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                for i in 0..10 {
+                    let title = Value::String(format!("{writer}-{i}"));
+                    db.insert_row("notes", BTreeMap::from([("title".to_string(), title)]))
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let after = db.db_stats().unwrap();
+    assert_eq!(after.wal_durable_appends - before.wal_durable_appends, 80);
+    assert!(after.wal_sync_ops - before.wal_sync_ops < 80);
+
+    // Checkpoints swap the WAL file out from under the group; later writes still land.
+    db.checkpoint().unwrap();
+    db.insert_row(
+        "notes",
+        BTreeMap::from([("title".to_string(), Value::String("last".to_string()))]),
+    )
+    .unwrap();
+    drop(db);
+
+    let db = EmbedDb::open(Config::new(data_dir)).unwrap();
+    assert_eq!(db.scan_rows("notes", None, 1000).unwrap().items.len(), 81);
+}
+
 #[test]
 fn flush_and_read_from_sst() {
     let dir = tempdir().unwrap();
@@ -329,6 +753,118 @@ fn table_stats_counts_embeddings() {
     assert_eq!(stats.embeddings_pending, 0);
 }
 
+#[test]
+fn table_stats_count_flushed_rows_and_disk_usage() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for text in ["a", "b", "c", "d", "e"] {
+        db.insert_row("notes", title(text)).unwrap();
+    }
+    // Never flushed, so its tombstone hides nothing.
+    db.delete_row("notes", 5).unwrap();
+    db.flush_table("notes").unwrap();
+    db.delete_row("notes", 1).unwrap();
+    db.put_embedding("notes", 2, vec![1.0, 2.0]).unwrap();
+
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!(stats.rows_mem, 0);
+    assert_eq!(stats.total_rows, 3);
+    assert_eq!(stats.total_tombstones, 2);
+    assert_eq!(stats.sst_bytes_per_level.len(), 1);
+    assert!(stats.sst_bytes_per_level[0] > 0);
+    assert_eq!(stats.embedding_memory_bytes, 8);
+
+    db.insert_row("notes", title("f")).unwrap();
+    db.update_row("notes", 6, title("g")).unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().total_rows, 4);
+    db.delete_row("notes", 6).unwrap();
+
+    db.flush_table("notes").unwrap();
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = EmbedDb::open(Config::new(data_dir)).unwrap();
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!(stats.total_rows, 3);
+    assert_eq!(stats.total_tombstones, 3);
+    db.compact_table("notes").unwrap();
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!(stats.total_rows, 3);
+    assert_eq!(stats.total_tombstones, 0);
+}
+
+#[test]
+fn full_memtables_are_flushed_before_writes_or_stall_them() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf()).with_memtable_max_bytes(200);
+    let db = EmbedDb::open(config).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, None).unwrap();
+    // 32 bytes of id, version, and timestamps, 5 of field name, 24 of text.
+    let row = || BTreeMap::from([("title".to_string(), Value::String("x".repeat(24)))]);
+
+    for expected in [61, 122, 183, 244] {
+        db.insert_row("notes", row()).unwrap();
+        assert_eq!(db.table_stats("notes").unwrap().memtable_bytes, expected);
+    }
+    db.update_row("notes", 1, row()).unwrap();
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!((stats.sst_files, stats.memtable_bytes), (1, 61));
+    db.delete_row("notes", 2).unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().memtable_bytes, 69);
+
+    let faults = testing::FaultInjector::install(dir.path());
+    faults.truncate_sst_after(0);
+    for _ in 0..3 {
+        db.insert_row("notes", row()).unwrap();
+    }
+    let err = db.insert_row("notes", row()).unwrap_err();
+    let stall = err.downcast_ref::<WriteStall>().unwrap();
+    assert_eq!((stall.table.as_str(), stall.memtable_bytes), ("notes", 252));
+    assert_eq!(db.table_stats("notes").unwrap().total_rows, 6);
+
+    faults.clear();
+    db.insert_row("notes", row()).unwrap();
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!((stats.memtable_bytes, stats.total_rows), (61, 7));
+}
+
+#[test]
+fn warm_table_reads_flushed_data_and_warms_on_open() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for text in ["red apple", "green pear", "red cherry"] {
+        db.insert_row("notes", title(text)).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.flush_table("notes").unwrap();
+    db.delete_row("notes", 2).unwrap();
+    db.close().unwrap();
+
+    let db = EmbedDb::open(Config::new(data_dir.clone()).with_warm_on_open(true)).unwrap();
+    let stats = db.warm_table("notes").unwrap();
+    assert_eq!(stats.sst_files, db.table_stats("notes").unwrap().sst_files);
+    assert!(stats.bytes_read > 0);
+    assert_eq!((stats.rows, stats.vectors), (2, 2));
+    assert_eq!(db.search_keyword("notes", "red", 5, &[]).unwrap().len(), 2);
+    assert!(db
+        .warm_table("missing")
+        .unwrap_err()
+        .to_string()
+        .contains("table not found"));
+}
+
 #[test]
 fn compacted_rows_survive_reopen_and_tombstones_hide_deleted_rows() {
     let dir = tempdir().unwrap();
@@ -363,6 +899,83 @@ fn compacted_rows_survive_reopen_and_tombstones_hide_deleted_rows() {
     assert!(row.is_none());
 }
 
+#[test]
+fn bulk_deletes_log_range_tombstones_that_survive_flush_and_compaction() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let db = EmbedDb::open(config.clone()).unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("kind", DataType::String, false),
+    ]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for id in 1..=10u64 {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(format!("note {id}")));
+        let kind = if id % 2 == 0 { "even" } else { "odd" };
+        fields.insert("kind".to_string(), Value::String(kind.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.flush_table("notes").unwrap();
+    db.compact_table("notes").unwrap();
+
+    let deleted = 3..7;
+    assert_eq!(db.delete_range("notes", deleted.clone()).unwrap(), 4);
+    let wal = Wal::replay_path(&dir.path().join("wal.log")).unwrap();
+    let deletes: Vec<_> = wal
+        .iter()
+        .filter(|record| {
+            matches!(
+                record,
+                WalRecord::DeleteRanges { .. } | WalRecord::DeleteRow { .. }
+            )
+        })
+        .collect();
+    assert!(matches!(
+        deletes.as_slice(),
+        [WalRecord::DeleteRanges { ranges, .. }] if ranges == &[deleted]
+    ));
+    assert!(db.get_row("notes", 4).unwrap().is_none());
+    assert!(db.get_row("notes", 7).unwrap().is_some());
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_ready, 6);
+
+    db.flush_table("notes").unwrap();
+    drop(db);
+    let db = EmbedDb::open(config.clone()).unwrap();
+    assert!(db.get_row("notes", 5).unwrap().is_none());
+
+    let odd = [FilterCondition {
+        column: "kind".to_string(),
+        op: FilterOp::Eq,
+        value: Value::String("odd".to_string()),
+    }];
+    assert_eq!(db.delete_rows_where("notes", &odd).unwrap(), 3);
+    assert_eq!(db.delete_rows_where("notes", &odd).unwrap(), 0);
+    let ids = |db: &EmbedDb| {
+        db.scan_rows("notes", None, 100)
+            .unwrap()
+            .items
+            .iter()
+            .map(|row| row.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&db), [2, 8, 10]);
+
+    db.flush_table("notes").unwrap();
+    let stats = db.compact_table("notes").unwrap();
+    assert!(stats.merges[0].tombstones_dropped > 0);
+    assert_eq!(stats.files_per_level, [0, 1]);
+    assert_eq!(ids(&db), [2, 8, 10]);
+    drop(db);
+
+    let db = EmbedDb::open(config).unwrap();
+    assert_eq!(ids(&db), [2, 8, 10]);
+    assert_eq!(db.delete_range("notes", 0..u64::MAX).unwrap(), 3);
+    assert!(ids(&db).is_empty());
+}
+
 #[test]
 fn update_row_after_flush_and_compaction() {
     let dir = tempdir().unwrap();
@@ -396,29 +1009,265 @@ fn update_row_after_flush_and_compaction() {
 }
 
 #[test]
-fn process_pending_jobs_after_flush_and_reopen() {
+fn alter_table_upgrades_flushed_rows_on_read() {
     let dir = tempdir().unwrap();
-    let data_dir = dir.path().to_path_buf();
+    let open = || EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let db = open();
     let schema = TableSchema::new(vec![
         Column::new("title", DataType::String, false),
-        Column::new("body", DataType::String, false),
+        Column::new("score", DataType::Float, true),
     ]);
-    let embed_spec = EmbeddingSpec::new(vec!["title", "body"]);
-
-    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
-    db.create_table("notes", schema, Some(embed_spec)).unwrap();
-
-    let mut fields = BTreeMap::new();
-    fields.insert("title".to_string(), Value::String("Hello".to_string()));
-    fields.insert("body".to_string(), Value::String("World".to_string()));
-    let row_id = db.insert_row("notes", fields).unwrap();
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    let fields = |pairs: &[(&str, Value)]| {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let flushed = db
+        .insert_row(
+            "notes",
+            fields(&[
+                ("title", Value::String("a".into())),
+                ("score", Value::Float(1.5)),
+            ]),
+        )
+        .unwrap();
     db.flush_table("notes").unwrap();
-    drop(db);
+    let in_memory = db
+        .insert_row("notes", fields(&[("title", Value::String("b".into()))]))
+        .unwrap();
 
-    let reopened = EmbedDb::open(Config::new(data_dir)).unwrap();
-    let jobs = reopened.list_embedding_jobs("notes").unwrap();
-    assert_eq!(jobs.len(), 1);
-    assert_eq!(jobs[0].status, EmbeddingStatus::Pending);
+    db.alter_table(
+        "notes",
+        AlterTableOp::AddColumn {
+            column: Column::new("tag", DataType::String, false),
+            default: Value::String("none".into()),
+        },
+    )
+    .unwrap();
+    db.alter_table(
+        "notes",
+        AlterTableOp::RenameColumn {
+            from: "score".into(),
+            to: "rating".into(),
+        },
+    )
+    .unwrap();
+    let tagged = db
+        .insert_row(
+            "notes",
+            fields(&[
+                ("title", Value::String("c".into())),
+                ("tag", Value::String("x".into())),
+            ]),
+        )
+        .unwrap();
+    db.flush_table("notes").unwrap();
+    // Re-adding a dropped column must not bring back old values, nor overwrite newer rows.
+    db.alter_table("notes", AlterTableOp::DropColumn { name: "tag".into() })
+        .unwrap();
+    db.alter_table(
+        "notes",
+        AlterTableOp::AddColumn {
+            column: Column::new("tag", DataType::String, true),
+            default: Value::Null,
+        },
+    )
+    .unwrap();
+    let retagged = db
+        .insert_row(
+            "notes",
+            fields(&[
+                ("title", Value::String("d".into())),
+                ("tag", Value::String("y".into())),
+            ]),
+        )
+        .unwrap();
+    db.flush_table("notes").unwrap();
+
+    let check = |db: &EmbedDb| {
+        let row = |row_id| db.get_row("notes", row_id).unwrap().unwrap().fields;
+        assert_eq!(
+            row(flushed),
+            fields(&[
+                ("title", Value::String("a".into())),
+                ("rating", Value::Float(1.5))
+            ])
+        );
+        assert_eq!(
+            row(in_memory),
+            fields(&[("title", Value::String("b".into()))])
+        );
+        assert_eq!(row(tagged), fields(&[("title", Value::String("c".into()))]));
+        assert_eq!(row(retagged)["tag"], Value::String("y".into()));
+        let page = db.scan_rows("notes", None, 10).unwrap();
+        assert_eq!(page.items[0].fields, row(flushed));
+        let schema = db.describe_table("notes").unwrap().schema;
+        let names: Vec<&str> = schema.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["title", "rating", "tag"]);
+    };
+    check(&db);
+    drop(db);
+
+    let db = open();
+    check(&db);
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = open();
+    check(&db);
+    db.compact_table("notes").unwrap();
+    check(&db);
+    assert!(db.read_inner().unwrap().state.tables["notes"]
+        .schema_changes
+        .is_empty());
+
+    for (op, message) in [
+        (
+            AlterTableOp::DropColumn {
+                name: "title".into(),
+            },
+            "embedding source field",
+        ),
+        (
+            AlterTableOp::AddColumn {
+                column: Column::new("n", DataType::Int, false),
+                default: Value::Null,
+            },
+            "needs a default",
+        ),
+        (
+            AlterTableOp::AddColumn {
+                column: Column::new("n", DataType::Int, true),
+                default: Value::String("1".into()),
+            },
+            "type mismatch",
+        ),
+        (
+            AlterTableOp::RenameColumn {
+                from: "rating".into(),
+                to: "title".into(),
+            },
+            "duplicate column",
+        ),
+        (
+            AlterTableOp::DropColumn {
+                name: "missing".into(),
+            },
+            "unknown column",
+        ),
+    ] {
+        let err = db.alter_table("notes", op).unwrap_err();
+        assert!(err.to_string().contains(message), "{err}");
+    }
+}
+
+#[test]
+fn leveled_compaction_pushes_files_down_and_keeps_latest_rows() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    // Level 1 can hold nothing, so every compaction ends with the data on level 2, split into
+    // small files.
+    let policy = CompactionPolicy {
+        l0_trigger_files: 2,
+        l0_trigger_bytes: 0,
+        target_file_bytes: 256,
+        level1_max_bytes: 1,
+        level_size_multiplier: 1_000_000,
+        max_level: 3,
+    };
+    let config = || Config::new(data_dir.clone()).with_compaction_policy(policy.clone());
+    let title = |text: String| BTreeMap::from([("title".to_string(), Value::String(text))]);
+
+    let db = EmbedDb::open(config()).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+    let ids: Vec<u64> = (0..20)
+        .map(|i| db.insert_row("notes", title(format!("row {i}"))).unwrap())
+        .collect();
+    db.flush_table("notes").unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().sst_files_per_level, [1]);
+
+    // The second level-0 file reaches the trigger, so this flush compacts.
+    db.update_row("notes", ids[0], title("row 0 v2".to_string()))
+        .unwrap();
+    db.delete_row("notes", ids[1]).unwrap();
+    db.delete_row("notes", ids[19]).unwrap();
+    db.flush_table("notes").unwrap();
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!(stats.compact_count, 1);
+    assert_eq!(stats.sst_files_per_level[..2], [0, 0]);
+    assert!(stats.sst_files_per_level[2] > 1);
+
+    db.update_row("notes", ids[10], title("row 10 v2".to_string()))
+        .unwrap();
+    db.flush_table("notes").unwrap();
+    drop(db);
+
+    // After reopening, the level-0 update still shadows the level-2 copy.
+    let db = EmbedDb::open(config()).unwrap();
+    let get_title = |db: &EmbedDb, id: u64| {
+        db.get_row("notes", id)
+            .unwrap()
+            .map(|row| row.fields["title"].clone())
+    };
+    assert_eq!(
+        get_title(&db, ids[10]),
+        Some(Value::String("row 10 v2".to_string()))
+    );
+
+    let stats = db.compact_table("notes").unwrap();
+    assert_eq!(stats.merges[0].from_level, 0);
+    assert_eq!(stats.merges[0].to_level, 1);
+    assert!(stats.merges.iter().any(|merge| merge.to_level == 2));
+    assert_eq!(stats.files_per_level[..2], [0, 0]);
+
+    assert_eq!(
+        get_title(&db, ids[0]),
+        Some(Value::String("row 0 v2".to_string()))
+    );
+    assert_eq!(
+        get_title(&db, ids[10]),
+        Some(Value::String("row 10 v2".to_string()))
+    );
+    assert_eq!(get_title(&db, ids[1]), None);
+    assert_eq!(get_title(&db, ids[19]), None);
+    let page = db.scan_rows("notes", None, 100).unwrap();
+    assert_eq!(page.items.len(), 18);
+
+    // Nothing left to merge.
+    assert!(db.compact_table("notes").unwrap().merges.is_empty());
+}
+
+#[test]
+fn process_pending_jobs_after_flush_and_reopen() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("body", DataType::String, false),
+    ]);
+    let embed_spec = EmbeddingSpec::new(vec!["title", "body"]);
+
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+    db.create_table("notes", schema, Some(embed_spec)).unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("Hello".to_string()));
+    fields.insert("body".to_string(), Value::String("World".to_string()));
+    let row_id = db.insert_row("notes", fields).unwrap();
+    db.flush_table("notes").unwrap();
+    drop(db);
+
+    let reopened = EmbedDb::open(Config::new(data_dir)).unwrap();
+    let jobs = reopened.list_embedding_jobs("notes").unwrap();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].status, EmbeddingStatus::Pending);
 
     let processed = reopened
         .process_pending_jobs("notes", &DummyEmbedder)
@@ -565,6 +1414,251 @@ fn search_knn_filtered_applies_scalar_filters() {
         .unwrap();
     assert_eq!(hits.len(), 1);
     assert_eq!(hits[0].row_id, row_a);
+
+    let filters = vec![FilterCondition {
+        column: "title".to_string(),
+        op: FilterOp::Contains,
+        value: Value::String("eet".to_string()),
+    }];
+    let hits = db
+        .search_knn_filtered("notes", &[5.0], 10, DistanceMetric::L2, &filters)
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].row_id, row_b);
+
+    let filters = vec![FilterCondition {
+        column: "age".to_string(),
+        op: FilterOp::Contains,
+        value: Value::String("9".to_string()),
+    }];
+    assert!(db
+        .search_knn_filtered("notes", &[5.0], 10, DistanceMetric::L2, &filters)
+        .is_err());
+}
+
+#[test]
+fn search_pages_by_offset_and_cursor() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("score", DataType::Int, false),
+    ]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    // Pairs of rows share a distance, so pages must break ties by row id.
+    for (i, title) in ["a", "b", "cc", "dd", "eee", "fff", "gggg"]
+        .iter()
+        .enumerate()
+    {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("score".to_string(), Value::Int(i as i64));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    let ids = |hits: &[SearchHit]| hits.iter().map(|hit| hit.row_id).collect::<Vec<_>>();
+    let search = |k: usize, filters: &[FilterCondition], options: SearchOptions| {
+        db.search_knn_with_options("notes", &[0.0], k, DistanceMetric::L2, filters, &options)
+            .unwrap()
+    };
+    let full = search(7, &[], SearchOptions::default());
+    let all = ids(&full);
+    assert_eq!(all, vec![1, 2, 3, 4, 5, 6, 7]);
+
+    let page = search(
+        3,
+        &[],
+        SearchOptions {
+            offset: 3,
+            ..SearchOptions::default()
+        },
+    );
+    assert_eq!(ids(&page), vec![4, 5, 6]);
+    assert!(search(
+        3,
+        &[],
+        SearchOptions {
+            offset: 7,
+            ..SearchOptions::default()
+        }
+    )
+    .is_empty());
+
+    let mut paged = Vec::new();
+    let mut after = None;
+    loop {
+        let page = search(
+            2,
+            &[],
+            SearchOptions {
+                after,
+                ..SearchOptions::default()
+            },
+        );
+        paged.extend(ids(&page));
+        match page.last() {
+            Some(last) if page.len() == 2 => {
+                let cursor = SearchCursor::from(last);
+                assert_eq!(cursor.to_string().parse::<SearchCursor>().unwrap(), cursor);
+                after = Some(cursor);
+            }
+            _ => break,
+        }
+    }
+    assert_eq!(paged, all);
+
+    // A filter that rejects most rows makes the cursor page fetch past its first limit.
+    let late = vec![FilterCondition {
+        column: "score".to_string(),
+        op: FilterOp::Gte,
+        value: Value::Int(4),
+    }];
+    let page = search(
+        2,
+        &late,
+        SearchOptions {
+            after: Some(SearchCursor::from(&full[4])),
+            ..SearchOptions::default()
+        },
+    );
+    assert_eq!(ids(&page), vec![6, 7]);
+    assert!("12".parse::<SearchCursor>().is_err());
+}
+
+#[test]
+fn search_restricts_candidates_to_include_and_exclude_ids() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf()).with_search_cache_capacity(16))
+        .unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("score", DataType::Int, false),
+    ]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for (i, title) in ["a", "b", "cc", "dd", "eee", "fff", "gggg"]
+        .iter()
+        .enumerate()
+    {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("score".to_string(), Value::Int(i as i64));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    let search = |filters: &[FilterCondition], include: Option<&[u64]>, exclude: &[u64]| {
+        let options = SearchOptions {
+            include_ids: include.map(|ids| ids.iter().copied().collect()),
+            exclude_ids: exclude.iter().copied().collect(),
+            ..SearchOptions::default()
+        };
+        db.search_knn_with_options("notes", &[0.0], 10, DistanceMetric::L2, filters, &options)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.row_id)
+            .collect::<Vec<_>>()
+    };
+
+    // Unknown ids are ignored, and cached results of one restriction don't leak into another.
+    assert_eq!(search(&[], Some(&[7, 2, 5, 99]), &[]), vec![2, 5, 7]);
+    assert_eq!(search(&[], None, &[1, 3]), vec![2, 4, 5, 6, 7]);
+    assert_eq!(search(&[], Some(&[2, 5, 7]), &[5]), vec![2, 7]);
+    assert_eq!(search(&[], Some(&[]), &[]), Vec::<u64>::new());
+    assert_eq!(search(&[], None, &[]), vec![1, 2, 3, 4, 5, 6, 7]);
+    let filters = vec![FilterCondition {
+        column: "score".to_string(),
+        op: FilterOp::Gte,
+        value: Value::Int(4),
+    }];
+    assert_eq!(search(&filters, Some(&[1, 2, 5, 6]), &[6]), vec![5]);
+
+    let options = SearchOptions {
+        include_ids: Some(BTreeSet::from([1, 2, 3])),
+        exclude_ids: BTreeSet::from([3]),
+        ..SearchOptions::default()
+    };
+    let explain = db
+        .explain_search("notes", &[0.0], 10, DistanceMetric::L2, &[], &options)
+        .unwrap();
+    assert_eq!(explain.candidates, 2);
+}
+
+#[test]
+fn search_knn_with_rows_returns_fields_from_memtable_and_sst() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+    let flushed = db.insert_row("notes", title("abc")).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.flush_table("notes").unwrap();
+    let in_memory = db.insert_row("notes", title("abcdefgh")).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let hits = db
+        .search_knn_with_rows(
+            "notes",
+            &[4.0],
+            10,
+            DistanceMetric::L2,
+            &[],
+            &SearchOptions::default(),
+        )
+        .unwrap();
+    let plain = db
+        .search_knn("notes", &[4.0], 10, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(
+        hits.iter().map(|hit| hit.row_id).collect::<Vec<_>>(),
+        [flushed, in_memory]
+    );
+    assert_eq!(hits[0].distance, plain[0].distance);
+    assert_eq!(hits[0].fields["title"], Value::String("abc".to_string()));
+    assert_eq!(
+        hits[1].fields["title"],
+        Value::String("abcdefgh".to_string())
+    );
+}
+
+#[test]
+fn row_versions_guard_conditional_updates_and_deletes() {
+    let dir = tempdir().unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+    let row_id = {
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        db.create_table("notes", schema, None).unwrap();
+        let row_id = db.insert_row("notes", title("a")).unwrap();
+        assert_eq!(db.get_row("notes", row_id).unwrap().unwrap().version, 1);
+        assert_eq!(db.update_row("notes", row_id, title("b")).unwrap(), 2);
+        db.flush_table("notes").unwrap();
+        row_id
+    };
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert_eq!(db.get_row("notes", row_id).unwrap().unwrap().version, 2);
+    assert_eq!(
+        db.update_row_if_version("notes", row_id, title("c"), 2)
+            .unwrap(),
+        3
+    );
+
+    let err = db
+        .update_row_if_version("notes", row_id, title("stale"), 2)
+        .unwrap_err();
+    let conflict = err.downcast_ref::<VersionConflict>().unwrap();
+    assert_eq!((conflict.expected, conflict.actual), (2, 3));
+    let err = db.delete_row_if_version("notes", row_id, 2).unwrap_err();
+    assert!(err.downcast_ref::<VersionConflict>().is_some());
+    let row = db.get_row("notes", row_id).unwrap().unwrap();
+    assert_eq!(row.fields["title"], Value::String("c".into()));
+
+    db.delete_row_if_version("notes", row_id, 3).unwrap();
+    assert!(db.get_row("notes", row_id).unwrap().is_none());
 }
 
 #[test]
@@ -592,6 +1686,70 @@ fn wal_autocheckpoint_triggers_before_write() {
     assert!(stats.sst_files > 0);
 }
 
+#[test]
+fn wal_segments_rotate_replay_and_retire_on_checkpoint() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf())
+        .with_wal_segment_bytes(256)
+        .with_wal_archive(true);
+    let db = EmbedDb::open(config.clone()).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+    let insert = |db: &EmbedDb, i: u64| {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(format!("row-{i}")));
+        db.insert_row("notes", fields).unwrap()
+    };
+    for i in 0..20 {
+        insert(&db, i);
+    }
+    let lsn_before_checkpoint = db.current_lsn().unwrap();
+
+    let stats = db.db_stats().unwrap();
+    assert!(stats.wal_segments > 1);
+    assert_eq!(stats.wal_rotations, stats.wal_segments as u64);
+    let live_bytes = fs::metadata(dir.path().join("wal.log")).unwrap().len();
+    assert!(live_bytes < 512);
+    assert!(stats.wal_bytes > live_bytes);
+    drop(db);
+
+    // Sealed segments are replayed ahead of wal.log on open.
+    let db = EmbedDb::open(config.clone()).unwrap();
+    assert_eq!(db.current_lsn().unwrap(), lsn_before_checkpoint);
+    assert!((1..=20).all(|id| db.get_row("notes", id).unwrap().is_some()));
+
+    // A checkpoint supersedes the sealed segments and archives them with the rest of history.
+    db.checkpoint().unwrap();
+    assert_eq!(db.db_stats().unwrap().wal_segments, 0);
+    for i in 20..30 {
+        insert(&db, i);
+    }
+    let view = db.read_at_lsn(lsn_before_checkpoint).unwrap();
+    assert!(view.get_row("notes", 20).unwrap().is_some());
+    assert!(view.get_row("notes", 21).unwrap().is_none());
+    drop(db);
+
+    // Simulate a crash between promoting the checkpoint WAL and retiring a sealed segment.
+    let archived = fs::read_dir(dir.path().join("wal_archive"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .min()
+        .unwrap();
+    let stale = dir
+        .path()
+        .join("wal_segments")
+        .join(archived.file_name().unwrap());
+    fs::copy(&archived, &stale).unwrap();
+
+    let db = EmbedDb::open(config).unwrap();
+    assert!(!stale.exists());
+    assert!((1..=30).all(|id| db.get_row("notes", id).unwrap().is_some()));
+}
+
 #[test]
 fn open_recovers_from_interrupted_checkpoint_wal_rotation() {
     let dir = tempdir().unwrap();
@@ -620,50 +1778,510 @@ fn open_recovers_from_interrupted_checkpoint_wal_rotation() {
 }
 
 #[test]
-fn snapshot_export_and_restore_roundtrip() {
+fn rename_table_moves_rows_embeddings_and_files() {
     let dir = tempdir().unwrap();
-    let data_dir = dir.path().to_path_buf();
-    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
-
+    let config = Config::new(dir.path().to_path_buf());
+    let db = EmbedDb::open(config.clone()).unwrap();
     let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
-    db.create_table("notes", schema, None).unwrap();
+    let spec = EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2);
+    db.create_table("notes", schema.clone(), Some(spec))
+        .unwrap();
+    db.create_table("other", schema, None).unwrap();
+    let flushed = insert_note(&db, "a").unwrap();
+    db.flush_table("notes").unwrap();
+    let fresh = insert_note(&db, "abc").unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
 
+    assert!(db.rename_table("missing", "x").is_err());
+    assert!(db.rename_table("notes", "other").is_err());
+    assert!(db.rename_table("notes", "../escape").is_err());
+    db.rename_table("notes", "docs").unwrap();
+    assert!(db.get_row("notes", flushed).is_err());
+    assert!(!sst::table_dir(dir.path(), "notes").exists());
+    let check = |db: &EmbedDb| {
+        assert!(db.get_row("docs", flushed).unwrap().is_some());
+        assert!(db.get_row("docs", fresh).unwrap().is_some());
+        let hits = db
+            .search_knn("docs", &[3.0], 1, DistanceMetric::L2)
+            .unwrap();
+        assert_eq!(hits[0].row_id, fresh);
+        assert_eq!(db.list_tables().unwrap().len(), 2);
+    };
+    check(&db);
+    insert_note(&db, "after").unwrap_err();
     let mut fields = BTreeMap::new();
-    fields.insert("title".to_string(), Value::String("Hello".to_string()));
-    let row_id = db.insert_row("notes", fields).unwrap();
-
-    let snap_parent = tempdir().unwrap();
-    let snap_dir = snap_parent.path().join("snapshot");
-    let _ = db.export_snapshot(&snap_dir).unwrap();
+    fields.insert("title".to_string(), Value::String("dd".to_string()));
+    db.insert_row("docs", fields).unwrap();
     drop(db);
+    let db = EmbedDb::open(config).unwrap();
+    check(&db);
+    assert_eq!(db.table_stats("docs").unwrap().total_rows, 3);
+}
 
-    let restored_parent = tempdir().unwrap();
-    let restored_dir = restored_parent.path().join("restored");
-    let _ = EmbedDb::restore_snapshot(&snap_dir, &restored_dir).unwrap();
+#[test]
+fn open_finishes_or_rewinds_interrupted_table_renames() {
+    for moved in [false, true] {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let db = EmbedDb::open(config.clone()).unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        let spec = EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2);
+        db.create_table("notes", schema, Some(spec)).unwrap();
+        let row_id = insert_note(&db, "a").unwrap();
+        db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+        // The snapshot loads the embedding from a vector segment under the old name.
+        db.checkpoint().unwrap();
+        drop(db);
 
-    let reopened = EmbedDb::open(Config::new(restored_dir)).unwrap();
-    let row = reopened.get_row("notes", row_id).unwrap().unwrap();
-    assert_eq!(
-        row.fields.get("title"),
-        Some(&Value::String("Hello".to_string()))
-    );
+        // A crash after the rename was logged, before or after the directory moved, and before
+        // the checkpoint that follows it.
+        let mut wal = Wal::open(dir.path().join("wal.log"), WalEncoding::Json).unwrap();
+        let record = WalRecord::RenameTable {
+            table: "notes".to_string(),
+            new_name: "docs".to_string(),
+        };
+        wal.append(&record, true).unwrap();
+        drop(wal);
+        if moved {
+            fs::rename(
+                sst::table_dir(dir.path(), "notes"),
+                sst::table_dir(dir.path(), "docs"),
+            )
+            .unwrap();
+        }
+
+        let err = EmbedDb::open(config.clone().with_read_only(true)).unwrap_err();
+        assert!(err.to_string().contains("interrupted"), "{err}");
+        let db = EmbedDb::open(config).unwrap();
+        assert!(
+            db.get_row("docs", row_id).unwrap().is_some(),
+            "moved: {moved}"
+        );
+        assert!(db.describe_table("notes").is_err());
+        assert!(!sst::table_dir(dir.path(), "notes").exists());
+    }
+}
+
+fn open_notes(config: &Config) -> EmbedDb {
+    let db = EmbedDb::open(config.clone()).unwrap();
+    if db.describe_table("notes").is_err() {
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, None).unwrap();
+    }
+    db
+}
+
+fn insert_note(db: &EmbedDb, title: &str) -> Result<u64> {
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String(title.to_string()));
+    db.insert_row("notes", fields)
+}
+
+fn note_count(config: &Config) -> usize {
+    let db = EmbedDb::open(config.clone()).unwrap();
+    db.scan_rows("notes", None, usize::MAX).unwrap().items.len()
 }
 
 #[test]
-fn table_and_db_stats_track_runtime_operation_metrics() {
-    let dir = tempdir().unwrap();
-    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+fn repair_table_quarantines_corrupt_ssts_and_rebuilds_from_wal_history() {
+    for archive in [true, false] {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf()).with_wal_archive(archive);
+        let db = EmbedDb::open(config.clone()).unwrap();
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table("notes", schema, None).unwrap();
+        let first = insert_note(&db, "a").unwrap();
+        let second = insert_note(&db, "b").unwrap();
+        db.flush_table("notes").unwrap();
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String("a2".to_string()));
+        db.update_row("notes", first, fields).unwrap();
+        let third = insert_note(&db, "c").unwrap();
+        db.flush_table("notes").unwrap();
+        db.checkpoint().unwrap();
+        let report = db.verify_table("notes").unwrap();
+        assert_eq!(report.files.len(), 2);
+        assert!(report.is_clean());
 
-    let schema = TableSchema::new(vec![
-        Column::new("title", DataType::String, false),
-        Column::new("body", DataType::String, false),
-    ]);
-    db.create_table(
-        "notes",
-        schema,
-        Some(EmbeddingSpec::new(vec!["title", "body"])),
-    )
-    .unwrap();
+        // Cut the newer file short.
+        let newest = report.files[1].path.clone();
+        let data = fs::read(&newest).unwrap();
+        fs::write(&newest, &data[..data.len() / 2]).unwrap();
+        let err = db.get_row("notes", third).unwrap_err().to_string();
+        assert!(err.contains("unreadable sst file"), "{err}");
+        let report = db.verify_table("notes").unwrap();
+        assert!(!report.is_clean());
+        assert_eq!(report.unreadable().count(), 1);
+        assert_eq!(report.files[0].entries, 2);
+
+        let report = db.repair_table("notes").unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.quarantined.len(), 1);
+        assert!(report.quarantined[0].exists());
+        assert!(!newest.exists());
+        let title = |row_id| {
+            db.get_row("notes", row_id)
+                .unwrap()
+                .map(|row| row.fields["title"].clone())
+        };
+        if archive {
+            assert_eq!(report.rows_rebuilt, Some(3));
+            assert!(report.rebuild_error.is_none());
+            assert_eq!(title(first), Some(Value::String("a2".to_string())));
+            assert_eq!(title(third), Some(Value::String("c".to_string())));
+        } else {
+            // Only the older file's versions are left.
+            assert_eq!(report.rows_rebuilt, None);
+            assert!(report.rebuild_error.is_some());
+            assert_eq!(title(first), Some(Value::String("a".to_string())));
+            assert_eq!(title(third), None);
+        }
+        assert_eq!(title(second), Some(Value::String("b".to_string())));
+        assert!(db.verify_table("notes").unwrap().is_clean());
+        drop(db);
+
+        assert_eq!(note_count(&config), if archive { 3 } else { 2 });
+    }
+}
+
+#[test]
+fn json_era_wals_open_with_bincode_encoding() {
+    let dir = tempdir().unwrap();
+    let json = Config::new(dir.path().to_path_buf());
+    let db = EmbedDb::open(json.clone()).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let spec = EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2);
+    db.create_table("notes", schema, Some(spec)).unwrap();
+    let first = insert_note(&db, "ab").unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    drop(db);
+
+    let bincode = json.clone().with_wal_encoding(WalEncoding::Bincode);
+    let db = EmbedDb::open(bincode.clone()).unwrap();
+    assert_eq!(
+        db.search_knn("notes", &[2.0], 1, None).unwrap()[0].row_id,
+        first
+    );
+    // Appends go on in the JSON file's format until a checkpoint replaces it.
+    insert_note(&db, "abc").unwrap();
+    let version = || EmbedDb::inspect_wal(dir.path().join("wal.log")).unwrap()[0].format_version;
+    assert_eq!(version(), Some(2));
+    db.checkpoint().unwrap();
+    insert_note(&db, "abcd").unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    assert_eq!(version(), Some(3));
+    drop(db);
+
+    for config in [&bincode, &json] {
+        assert_eq!(note_count(config), 3);
+        let db = EmbedDb::open(config.clone()).unwrap();
+        let hits = db.search_knn("notes", &[4.0], 3, None).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].distance, 0.0);
+    }
+}
+
+#[test]
+fn torn_wal_appends_recover_to_the_last_complete_record() {
+    for budget in [0, 7, 40, 150, 400] {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let faults = testing::FaultInjector::install(dir.path());
+        let db = open_notes(&config);
+        faults.truncate_wal_after(budget);
+        let acknowledged = (0..20)
+            .take_while(|idx| insert_note(&db, &format!("note {idx}")).is_ok())
+            .count();
+        assert!(acknowledged < 20);
+        drop(db);
+        faults.clear();
+
+        assert_eq!(note_count(&config), acknowledged, "budget {budget}");
+        // The torn tail is cut off on open, so later appends are not hidden behind it.
+        let db = open_notes(&config);
+        insert_note(&db, "after").unwrap();
+        drop(db);
+        assert_eq!(note_count(&config), acknowledged + 1, "budget {budget}");
+    }
+}
+
+#[test]
+fn write_batches_apply_atomically_across_tables() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let db = open_notes(&config);
+    let schema = TableSchema::new(vec![
+        Column::new("doc_id", DataType::Int, false),
+        Column::new("text", DataType::String, false),
+    ]);
+    db.create_table("chunks", schema, None).unwrap();
+    let doc = insert_note(&db, "draft").unwrap();
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+    let chunk = |text: &str| {
+        BTreeMap::from([
+            ("doc_id".to_string(), Value::Int(doc as i64)),
+            ("text".to_string(), Value::String(text.into())),
+        ])
+    };
+
+    let results = db
+        .write_batch(vec![
+            WriteOp::Update {
+                table: "notes".into(),
+                row_id: doc,
+                fields: title("final"),
+                expected_version: Some(1),
+            },
+            WriteOp::Insert {
+                table: "chunks".into(),
+                fields: chunk("one"),
+            },
+            WriteOp::Insert {
+                table: "chunks".into(),
+                fields: chunk("two"),
+            },
+            // Later ops see the rows written by earlier ones.
+            WriteOp::Delete {
+                table: "chunks".into(),
+                row_id: 2,
+                expected_version: Some(1),
+            },
+        ])
+        .unwrap();
+    assert_eq!(
+        results,
+        vec![
+            WriteOpResult {
+                row_id: doc,
+                version: Some(2),
+            },
+            WriteOpResult {
+                row_id: 1,
+                version: Some(1),
+            },
+            WriteOpResult {
+                row_id: 2,
+                version: Some(1),
+            },
+            WriteOpResult {
+                row_id: 2,
+                version: None,
+            },
+        ]
+    );
+
+    // A failing op leaves every table untouched.
+    let err = db
+        .write_batch(vec![
+            WriteOp::Insert {
+                table: "chunks".into(),
+                fields: chunk("three"),
+            },
+            WriteOp::Update {
+                table: "notes".into(),
+                row_id: doc,
+                fields: title("stale"),
+                expected_version: Some(1),
+            },
+        ])
+        .unwrap_err();
+    assert!(err.downcast_ref::<VersionConflict>().is_some());
+    assert!(err.to_string().starts_with("op 1"), "{err:#}");
+    assert_eq!(db.scan_rows("chunks", None, 10).unwrap().items.len(), 1);
+
+    // A batch cut short by a failed append is discarded, here and on reopen.
+    let faults = testing::FaultInjector::install(dir.path());
+    faults.truncate_wal_after(60);
+    assert!(db
+        .write_batch(vec![
+            WriteOp::Delete {
+                table: "notes".into(),
+                row_id: doc,
+                expected_version: None,
+            },
+            WriteOp::Insert {
+                table: "chunks".into(),
+                fields: chunk("four"),
+            },
+        ])
+        .is_err());
+    drop(db);
+    faults.clear();
+    let db = EmbedDb::open(config.clone()).unwrap();
+    assert_eq!(
+        db.get_row("notes", doc).unwrap().unwrap().fields["title"],
+        Value::String("final".into())
+    );
+    let chunks = db.scan_rows("chunks", None, 10).unwrap().items;
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].fields["text"], Value::String("one".into()));
+    assert_eq!(insert_note(&db, "after").unwrap(), doc + 1);
+}
+
+#[test]
+fn data_dir_lock_admits_one_writer_and_any_read_only_opens() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    // Read-only opens never create a database.
+    assert!(EmbedDb::open(config.clone().with_read_only(true)).is_err());
+    assert!(fs::read_dir(dir.path()).unwrap().next().is_none());
+
+    let db = open_notes(&config);
+    insert_note(&db, "first").unwrap();
+    let err = EmbedDb::open(config.clone()).unwrap_err();
+    assert!(err.to_string().contains("already in use"), "{err:#}");
+
+    let wal = fs::read(dir.path().join("wal.log")).unwrap();
+    let reader = EmbedDb::open(config.clone().with_read_only(true)).unwrap();
+    assert_eq!(reader.scan_rows("notes", None, 10).unwrap().items.len(), 1);
+    assert!(insert_note(&reader, "second").is_err());
+    assert!(reader.flush_table("notes").is_err());
+    drop(reader);
+    assert_eq!(fs::read(dir.path().join("wal.log")).unwrap(), wal);
+
+    insert_note(&db, "second").unwrap();
+    drop(db);
+    assert_eq!(note_count(&config), 2);
+}
+
+#[test]
+fn dropped_syncs_lose_only_writes_after_the_last_real_sync() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let faults = testing::FaultInjector::install(dir.path());
+    let db = open_notes(&config);
+    for idx in 0..3 {
+        insert_note(&db, &format!("synced {idx}")).unwrap();
+    }
+    faults.drop_syncs(true);
+    for idx in 0..3 {
+        insert_note(&db, &format!("unsynced {idx}")).unwrap();
+    }
+    assert_eq!(faults.injected(), 3);
+    drop(db);
+
+    faults.lose_unsynced_writes().unwrap();
+    faults.clear();
+    let db = open_notes(&config);
+    let titles: Vec<Value> = db
+        .scan_rows("notes", None, 10)
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|row| row.fields["title"].clone())
+        .collect();
+    assert_eq!(
+        titles,
+        (0..3)
+            .map(|idx| Value::String(format!("synced {idx}")))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn checkpoints_interrupted_at_any_rename_recover_every_row() {
+    // A checkpoint renames the flushed SST into place, then `wal.log` to `wal.prev`, then the new
+    // WAL to `wal.log`.
+    for allowed in 0..=3 {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf());
+        let faults = testing::FaultInjector::install(dir.path());
+        let db = open_notes(&config);
+        for idx in 0..3 {
+            insert_note(&db, &format!("note {idx}")).unwrap();
+        }
+        faults.fail_renames_after(allowed);
+        assert_eq!(db.checkpoint().is_err(), allowed < 3, "renames {allowed}");
+        drop(db);
+        faults.clear();
+
+        assert_eq!(note_count(&config), 3, "renames {allowed}");
+        let db = open_notes(&config);
+        insert_note(&db, "after").unwrap();
+        drop(db);
+        assert_eq!(note_count(&config), 4, "renames {allowed}");
+    }
+}
+
+#[test]
+fn torn_sst_writes_leave_no_partial_sst_files() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let faults = testing::FaultInjector::install(dir.path());
+    let db = open_notes(&config);
+    for idx in 0..3 {
+        insert_note(&db, &format!("note {idx}")).unwrap();
+    }
+    faults.truncate_sst_after(10);
+    assert!(db.flush_table("notes").is_err());
+    drop(db);
+    faults.clear();
+
+    let table_dir = sst::table_dir(dir.path(), "notes");
+    assert!(sst::list_sst_files(&table_dir).unwrap().is_empty());
+    let db = open_notes(&config);
+    db.flush_table("notes").unwrap();
+    drop(db);
+    assert_eq!(note_count(&config), 3);
+}
+
+#[test]
+fn snapshot_export_and_restore_roundtrip() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, None).unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("Hello".to_string()));
+    let row_id = db.insert_row("notes", fields).unwrap();
+
+    let snap_parent = tempdir().unwrap();
+    let snap_dir = snap_parent.path().join("snapshot");
+    fs::create_dir(&snap_dir).unwrap();
+    let _ = db.export_snapshot(&snap_dir).unwrap();
+    assert!(!snap_parent.path().join("snapshot.partial").exists());
+    assert!(!snap_dir.join("embeddb.lock").exists());
+    assert!(db.export_snapshot(&snap_dir).is_err());
+
+    // The database stays open and writable; later writes are not in the snapshot.
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("Later".to_string()));
+    let later_id = db.insert_row("notes", fields).unwrap();
+    drop(db);
+
+    let restored_parent = tempdir().unwrap();
+    let restored_dir = restored_parent.path().join("restored");
+    let _ = EmbedDb::restore_snapshot(&snap_dir, &restored_dir).unwrap();
+
+    assert!(EmbedDb::restore_snapshot(&snap_dir, &restored_dir).is_err());
+
+    let reopened = EmbedDb::open(Config::new(restored_dir)).unwrap();
+    let row = reopened.get_row("notes", row_id).unwrap().unwrap();
+    assert_eq!(
+        row.fields.get("title"),
+        Some(&Value::String("Hello".to_string()))
+    );
+    assert!(reopened.get_row("notes", later_id).unwrap().is_none());
+}
+
+#[test]
+fn table_and_db_stats_track_runtime_operation_metrics() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("body", DataType::String, false),
+    ]);
+    db.create_table(
+        "notes",
+        schema,
+        Some(EmbeddingSpec::new(vec!["title", "body"])),
+    )
+    .unwrap();
 
     let mut fields = BTreeMap::new();
     fields.insert("title".to_string(), Value::String("Hello".to_string()));
@@ -719,7 +2337,7 @@ fn table_and_db_stats_track_retry_and_failure_metrics() {
 
     // Drive to failed and retry.
     let mut tick = now_ms;
-    for _ in 0..EMBEDDING_MAX_ATTEMPTS {
+    for _ in 0..RetryPolicy::default().max_attempts {
         let _ = db
             .process_pending_jobs_internal_at("notes", &AlwaysFailEmbedder, None, tick)
             .unwrap();
@@ -733,9 +2351,3165 @@ fn table_and_db_stats_track_retry_and_failure_metrics() {
     assert_eq!(retried, 1);
 
     let table_stats = db.table_stats("notes").unwrap();
-    assert!(table_stats.embeddings_failed_total >= EMBEDDING_MAX_ATTEMPTS as u64);
+    assert!(table_stats.embeddings_failed_total >= RetryPolicy::default().max_attempts as u64);
     assert_eq!(table_stats.embeddings_retried_total, 1);
     let db_stats = db.db_stats().unwrap();
-    assert!(db_stats.embeddings_failed_total >= EMBEDDING_MAX_ATTEMPTS as u64);
+    assert!(db_stats.embeddings_failed_total >= RetryPolicy::default().max_attempts as u64);
     assert_eq!(db_stats.embeddings_retried_total, 1);
 }
+
+#[test]
+fn non_json_row_codecs_survive_flush_compact_and_reopen() {
+    for codec in [RowCodecKind::Bincode, RowCodecKind::MessagePack] {
+        let dir = tempdir().unwrap();
+        let config = Config::new(dir.path().to_path_buf()).with_row_codec(codec);
+        let db = EmbedDb::open(config.clone()).unwrap();
+        db.create_table(
+            "notes",
+            TableSchema::new(vec![
+                Column::new("title", DataType::String, false),
+                Column::new("score", DataType::Float, true),
+            ]),
+            None,
+        )
+        .unwrap();
+
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String("Hello".to_string()));
+        fields.insert("score".to_string(), Value::Float(0.25));
+        let row_id = db.insert_row("notes", fields).unwrap();
+        db.flush_table("notes").unwrap();
+        db.compact_table("notes").unwrap();
+        drop(db);
+
+        // Reopening with the default codec must still read files written with another codec.
+        let db = EmbedDb::open(Config::new(config.data_dir.clone())).unwrap();
+        let row = db.get_row("notes", row_id).unwrap().unwrap();
+        assert_eq!(row.fields.get("score"), Some(&Value::Float(0.25)));
+    }
+}
+
+#[test]
+fn filtered_search_over_dictionary_encoded_compacted_rows() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("source", DataType::String, false),
+        ]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let mut web_rows = Vec::new();
+    for i in 0..8 {
+        let source = if i % 2 == 0 { "web" } else { "mail" };
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(format!("note-{i}")));
+        fields.insert("source".to_string(), Value::String(source.to_string()));
+        let row_id = db.insert_row("notes", fields).unwrap();
+        if source == "web" {
+            web_rows.push(row_id);
+        }
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.flush_table("notes").unwrap();
+    db.compact_table("notes").unwrap();
+
+    let filters = vec![FilterCondition {
+        column: "source".to_string(),
+        op: FilterOp::Eq,
+        value: Value::String("web".to_string()),
+    }];
+    let mut hits: Vec<u64> = db
+        .search_knn_filtered("notes", &[6.0], 10, DistanceMetric::L2, &filters)
+        .unwrap()
+        .into_iter()
+        .map(|hit| hit.row_id)
+        .collect();
+    hits.sort();
+    assert_eq!(hits, web_rows);
+
+    let filters = vec![FilterCondition {
+        column: "source".to_string(),
+        op: FilterOp::Neq,
+        value: Value::String("web".to_string()),
+    }];
+    let hits = db
+        .search_knn_filtered("notes", &[6.0], 10, DistanceMetric::L2, &filters)
+        .unwrap();
+    assert_eq!(hits.len(), 4);
+    assert!(hits.iter().all(|hit| !web_rows.contains(&hit.row_id)));
+}
+
+#[test]
+fn filters_compare_strings_and_bytes_under_column_collation() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let bad = TableSchema::new(vec![
+        Column::new("code", DataType::Bytes, false).with_collation(Collation::CaseInsensitive)
+    ]);
+    let err = db.create_table("bad", bad, None).unwrap_err().to_string();
+    assert!(err.contains("only supported for String"), "{err}");
+    db.create_table(
+        "people",
+        TableSchema::new(vec![
+            Column::new("name", DataType::String, false).with_collation(Collation::CaseInsensitive),
+            Column::new("nick", DataType::String, false),
+            Column::new("code", DataType::Bytes, false),
+            Column::new("active", DataType::Bool, false),
+        ]),
+        Some(EmbeddingSpec::new(vec!["name"])),
+    )
+    .unwrap();
+    for (idx, name) in ["alice", "ALICE", "Bob", "bob", "carol", "Carol"]
+        .iter()
+        .cycle()
+        .take(12)
+        .enumerate()
+    {
+        let fields = BTreeMap::from([
+            ("name".to_string(), Value::String(name.to_string())),
+            ("nick".to_string(), Value::String(name.to_string())),
+            ("code".to_string(), Value::Bytes(vec![idx as u8])),
+            ("active".to_string(), Value::Bool(true)),
+        ]);
+        db.insert_row("people", fields).unwrap();
+    }
+    db.process_pending_jobs("people", &DummyEmbedder).unwrap();
+    // Compaction dictionary-encodes the repeated names.
+    db.flush_table("people").unwrap();
+    db.compact_table("people").unwrap();
+
+    let count = |filters: &[FilterCondition]| {
+        db.search_knn_filtered("people", &[5.0], 20, DistanceMetric::L2, filters)
+            .unwrap()
+            .len()
+    };
+    for (clause, expected) in [
+        (r#"name = "ALICE""#, 4),
+        (r#"name != "alice""#, 8),
+        (r#"nick = "ALICE""#, 2),
+        (r#"name < "BOB""#, 4),
+        (r#"name >= "bob""#, 8),
+        (r#"nick < "a""#, 6),
+        (r#"name contains "AR""#, 4),
+        (r#"nick contains "ar""#, 4),
+    ] {
+        assert_eq!(count(&parse_filter(clause).unwrap()), expected, "{clause}");
+    }
+    let code_below = |op: FilterOp| FilterCondition {
+        column: "code".to_string(),
+        op,
+        value: Value::Bytes(vec![3]),
+    };
+    assert_eq!(count(&[code_below(FilterOp::Lt)]), 3);
+    assert_eq!(count(&[code_below(FilterOp::Gte)]), 9);
+    let err = db
+        .search_knn_filtered(
+            "people",
+            &[5.0],
+            20,
+            DistanceMetric::L2,
+            &parse_filter("active > true").unwrap(),
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("not supported for Bool"), "{err}");
+}
+
+#[test]
+fn rows_track_created_and_updated_times_through_flush_and_reopen() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, None).unwrap();
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+
+    let before = now_epoch_ms();
+    let kept = db.insert_row("notes", title("kept")).unwrap();
+    let edited = db.insert_row("notes", title("edited")).unwrap();
+    let row = db.get_row("notes", kept).unwrap().unwrap();
+    assert!(row.created_at_ms >= before);
+    assert_eq!(row.updated_at_ms, row.created_at_ms);
+    let created = row.created_at_ms;
+    let edited_created = db.get_row("notes", edited).unwrap().unwrap().created_at_ms;
+
+    std::thread::sleep(Duration::from_millis(5));
+    let cutoff = now_epoch_ms();
+    db.update_row("notes", edited, title("edited again"))
+        .unwrap();
+    let row = db.get_row("notes", edited).unwrap().unwrap();
+    assert_eq!(row.created_at_ms, edited_created);
+    assert!(row.updated_at_ms >= cutoff);
+
+    db.flush_table("notes").unwrap();
+    db.compact_table("notes").unwrap();
+    db.close().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let row = db.get_row("notes", kept).unwrap().unwrap();
+    assert_eq!((row.created_at_ms, row.updated_at_ms), (created, created));
+
+    let err = db
+        .delete_rows_where("notes", &parse_filter(r#"updated_at_ms > "x""#).unwrap())
+        .unwrap_err();
+    assert!(err.to_string().contains("updated_at_ms"), "{err}");
+    let recent = parse_filter(&format!("updated_at_ms >= {cutoff}")).unwrap();
+    assert_eq!(db.delete_rows_where("notes", &recent).unwrap(), 1);
+    assert!(db.get_row("notes", edited).unwrap().is_none());
+    assert!(db.get_row("notes", kept).unwrap().is_some());
+}
+
+#[test]
+fn cosine_tables_store_unit_vectors_and_survive_checkpoint() {
+    struct PairEmbedder;
+    impl Embedder for PairEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            Ok(vec![input.len() as f32, 3.0])
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::Cosine)),
+    )
+    .unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("abcd".to_string()));
+    let row_id = db.insert_row("notes", fields).unwrap();
+    db.process_pending_jobs("notes", &PairEmbedder).unwrap();
+
+    {
+        let inner = db.inner.read().unwrap();
+        let table_state = inner.state.tables.get("notes").unwrap();
+        let stored = table_state.embeddings.get(&row_id).unwrap().to_f32();
+        let len: f32 = stored.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((len - 1.0).abs() < 1e-6);
+        assert_eq!(table_state.embedding_norms.get(&row_id), Some(&5.0));
+    }
+
+    let cosine = db
+        .search_knn("notes", &[8.0, 6.0], 1, DistanceMetric::Cosine)
+        .unwrap();
+    assert!(cosine[0].distance.abs() < 1e-6);
+
+    db.checkpoint().unwrap();
+    drop(db);
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    // L2 on a normalized table still measures against the original vector.
+    let options = SearchOptions {
+        allow_metric_mismatch: true,
+        ..SearchOptions::default()
+    };
+    let l2 = db
+        .search_knn_with_options("notes", &[4.0, 3.0], 1, DistanceMetric::L2, &[], &options)
+        .unwrap();
+    assert_eq!(l2[0].row_id, row_id);
+    assert!(l2[0].distance.abs() < 1e-4);
+    let cosine = db
+        .search_knn("notes", &[8.0, 6.0], 1, DistanceMetric::Cosine)
+        .unwrap();
+    assert!(cosine[0].distance.abs() < 1e-6);
+}
+
+#[test]
+fn quantized_tables_search_and_roundtrip_through_checkpoint() {
+    for encoding in [VectorEncoding::F16, VectorEncoding::Int8] {
+        let dir = tempdir().unwrap();
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        db.create_table(
+            "notes",
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(EmbeddingSpec::new(vec!["title"]).with_vector_encoding(encoding)),
+        )
+        .unwrap();
+
+        let mut ids = Vec::new();
+        for title in ["a", "abc", "abcdefgh"] {
+            let mut fields = BTreeMap::new();
+            fields.insert("title".to_string(), Value::String(title.to_string()));
+            ids.push(db.insert_row("notes", fields).unwrap());
+        }
+        db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+        {
+            let inner = db.inner.read().unwrap();
+            let table_state = inner.state.tables.get("notes").unwrap();
+            let stored = table_state.embeddings.get(&ids[0]);
+            match encoding {
+                VectorEncoding::F16 => assert!(matches!(stored, Some(StoredVector::F16(_)))),
+                _ => assert!(matches!(stored, Some(StoredVector::Int8 { .. }))),
+            }
+        }
+
+        let hits = db
+            .search_knn("notes", &[3.0], 1, DistanceMetric::L2)
+            .unwrap();
+        assert_eq!(hits[0].row_id, ids[1], "{encoding:?}");
+
+        db.checkpoint().unwrap();
+        drop(db);
+
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        let hits = db
+            .search_knn("notes", &[8.0], 1, DistanceMetric::L2)
+            .unwrap();
+        assert_eq!(hits[0].row_id, ids[2], "{encoding:?}");
+        assert_eq!(hits[0].distance, 0.0, "{encoding:?}");
+    }
+}
+
+/// Returns one component per input byte, so inputs of different lengths yield different dimensions.
+struct ByteLengthEmbedder;
+
+impl Embedder for ByteLengthEmbedder {
+    fn embed(&self, input: &str) -> Result<Vec<f32>> {
+        Ok(vec![1.0; input.len()])
+    }
+}
+
+#[test]
+fn embeddings_must_match_the_declared_or_learned_dimension() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    assert!(db
+        .create_table(
+            "zero",
+            schema.clone(),
+            Some(EmbeddingSpec::new(vec!["title"]).with_dimensions(0)),
+        )
+        .is_err());
+    db.create_table(
+        "declared",
+        schema.clone(),
+        Some(EmbeddingSpec::new(vec!["title"]).with_dimensions(2)),
+    )
+    .unwrap();
+    db.create_table("learned", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    assert_eq!(db.describe_table("declared").unwrap().dimension, Some(2));
+    assert_eq!(db.describe_table("learned").unwrap().dimension, None);
+
+    for (table, title) in [("declared", "ab"), ("declared", "abc")] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row(table, fields).unwrap();
+    }
+    for title in ["ab", "cd", "abc"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("learned", fields).unwrap();
+    }
+    db.process_pending_jobs("declared", &ByteLengthEmbedder)
+        .unwrap();
+    db.process_pending_jobs("learned", &ByteLengthEmbedder)
+        .unwrap();
+
+    for table in ["declared", "learned"] {
+        let jobs = db.list_embedding_jobs(table).unwrap();
+        let rejected = jobs.last().unwrap();
+        assert_eq!(rejected.status, EmbeddingStatus::Pending);
+        let err = rejected.last_error.as_deref().unwrap();
+        assert!(
+            err.contains("3-dimensional") && err.contains("expects 2"),
+            "{err}"
+        );
+        assert!(jobs[..jobs.len() - 1]
+            .iter()
+            .all(|job| job.status == EmbeddingStatus::Ready));
+        assert_eq!(db.describe_table(table).unwrap().dimension, Some(2));
+    }
+
+    let err = db
+        .search_knn("declared", &[1.0, 0.0, 0.0], 1, DistanceMetric::L2)
+        .unwrap_err();
+    assert!(err.to_string().contains("dimension 2"), "{err}");
+    let err = db
+        .apply_embedding_spec(
+            "learned",
+            EmbeddingSpec::new(vec!["title"]).with_dimensions(3),
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("2-dimensional"), "{err}");
+}
+
+#[test]
+fn search_rejects_dimension_and_metric_mismatches() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2)),
+    )
+    .unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("abc".to_string()));
+    let row_id = db.insert_row("notes", fields).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let err = db
+        .search_knn("notes", &[1.0, 2.0], 1, DistanceMetric::L2)
+        .unwrap_err();
+    assert!(err.to_string().contains("dimension 2"), "{err}");
+
+    let err = db
+        .search_knn("notes", &[3.0], 1, DistanceMetric::Cosine)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("conflicts with table metric"),
+        "{err}"
+    );
+
+    let options = SearchOptions {
+        allow_metric_mismatch: true,
+        ..SearchOptions::default()
+    };
+    let hits = db
+        .search_knn_with_options("notes", &[3.0], 1, DistanceMetric::Cosine, &[], &options)
+        .unwrap();
+    assert_eq!(hits[0].row_id, row_id);
+
+    let hits = db
+        .search_knn("notes", &[3.0], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, row_id);
+}
+
+#[test]
+fn keyword_search_ranks_by_bm25_and_fuses_with_vectors() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("year", DataType::Int, false),
+        ]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2)),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for (title, year) in [
+        ("rust error handling", 2021),
+        ("error budgets", 2023),
+        ("gardening tips for spring", 2023),
+    ] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("year".to_string(), Value::Int(year));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.flush_table("notes").unwrap();
+
+    let order = |hits: Vec<SearchHit>| -> Vec<u64> { hits.iter().map(|hit| hit.row_id).collect() };
+    let hits = db.search_keyword("notes", "Error", 10, &[]).unwrap();
+    assert!(hits.iter().all(|hit| hit.distance < 0.0));
+    // The shorter title matches with the same term frequency, so it scores higher.
+    assert_eq!(order(hits), vec![ids[1], ids[0]]);
+    let recent = [FilterCondition {
+        column: "year".to_string(),
+        op: FilterOp::Gte,
+        value: Value::Int(2022),
+    }];
+    let hits = db.search_keyword("notes", "error", 10, &recent).unwrap();
+    assert_eq!(order(hits), vec![ids[1]]);
+
+    // Writes after the index is built are reflected in it.
+    let mut fields = BTreeMap::new();
+    fields.insert(
+        "title".to_string(),
+        Value::String("spring error".to_string()),
+    );
+    fields.insert("year".to_string(), Value::Int(2024));
+    db.update_row("notes", ids[2], fields).unwrap();
+    db.delete_row("notes", ids[1]).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    let hits = db.search_keyword("notes", "error", 10, &[]).unwrap();
+    assert_eq!(order(hits), vec![ids[2], ids[0]]);
+
+    // The vector ranking alone puts row 1 (19 characters, like the query) first; weighting the
+    // keyword ranking lets the exact match win.
+    let vector_only = db.search_knn("notes", &[19.0], 1, None).unwrap();
+    assert_eq!(order(vector_only), vec![ids[0]]);
+    let fused = db
+        .search_hybrid(
+            "notes",
+            "spring",
+            &[19.0],
+            2,
+            Fusion::WeightedRank {
+                keyword_weight: 2.0,
+                vector_weight: 1.0,
+            },
+            &[],
+        )
+        .unwrap();
+    assert_eq!(order(fused), vec![ids[2], ids[0]]);
+    let fused = db
+        .search_hybrid("notes", "spring", &[19.0], 2, Fusion::ReciprocalRank, &[])
+        .unwrap();
+    assert_eq!(fused.len(), 2);
+
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let hits = db
+        .search_keyword("notes", "spring gardening", 10, &[])
+        .unwrap();
+    assert_eq!(order(hits), vec![ids[2]]);
+}
+
+#[test]
+fn expired_rows_are_deleted_by_flush_and_compaction() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "sessions",
+        TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("expires_at", DataType::Int, true),
+        ])
+        .with_expiry_column("expires_at"),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let row = |title: &str, expires_at: Option<i64>| {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        if let Some(expires_at) = expires_at {
+            fields.insert("expires_at".to_string(), Value::Int(expires_at));
+        }
+        fields
+    };
+    let now_secs = (now_epoch_ms() / 1000) as i64;
+    let kept = db.insert_row("sessions", row("kept", None)).unwrap();
+    let past = db
+        .insert_row("sessions", row("past", Some(now_secs - 60)))
+        .unwrap();
+    let future = db
+        .insert_row("sessions", row("future", Some(now_secs + 3600)))
+        .unwrap();
+    let ttl_ids = db
+        .insert_rows_with_ttl(
+            "sessions",
+            vec![row("ttl-a", None), row("ttl-b", None)],
+            Duration::ZERO,
+        )
+        .unwrap();
+    let long_ttl = db
+        .insert_row_with_ttl("sessions", row("long", None), Duration::from_secs(3600))
+        .unwrap();
+    db.process_pending_jobs("sessions", &DummyEmbedder).unwrap();
+
+    // Expiry times survive a checkpoint and reopen.
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.flush_table("sessions").unwrap();
+
+    let live: Vec<u64> = db
+        .scan_rows("sessions", None, 100)
+        .unwrap()
+        .items
+        .iter()
+        .map(|row| row.id)
+        .collect();
+    assert_eq!(live, vec![kept, future, long_ttl]);
+    for row_id in [past, ttl_ids[0], ttl_ids[1]] {
+        assert!(db.get_row("sessions", row_id).unwrap().is_none());
+    }
+    let hits = db.search_knn("sessions", &[4.0], 10, None).unwrap();
+    assert_eq!(hits.len(), 3);
+    assert_eq!(db.expire_rows("sessions").unwrap(), 0);
+    db.compact_table("sessions").unwrap();
+    assert_eq!(db.scan_rows("sessions", None, 100).unwrap().items.len(), 3);
+
+    // Renaming the expiry column carries the designation along; dropping it is rejected.
+    db.alter_table(
+        "sessions",
+        AlterTableOp::RenameColumn {
+            from: "expires_at".to_string(),
+            to: "deadline".to_string(),
+        },
+    )
+    .unwrap();
+    assert!(db
+        .alter_table(
+            "sessions",
+            AlterTableOp::DropColumn {
+                name: "deadline".to_string(),
+            },
+        )
+        .is_err());
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("stale".to_string()));
+    fields.insert("deadline".to_string(), Value::Int(0));
+    let stale = db.insert_row("sessions", fields).unwrap();
+    assert_eq!(db.expire_rows("sessions").unwrap(), 1);
+    assert!(db.get_row("sessions", stale).unwrap().is_none());
+}
+
+#[test]
+fn expired_rows_are_reported_even_when_the_flush_fails() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "sessions",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+    let title = |title: &str| BTreeMap::from([("title".to_string(), Value::String(title.into()))]);
+    // Gives compaction a level-0 file to rewrite.
+    db.insert_row("sessions", title("kept")).unwrap();
+    db.flush_table("sessions").unwrap();
+    let mut subscription = db.subscribe("sessions").unwrap();
+    let faults = testing::FaultInjector::install(dir.path());
+    faults.truncate_sst_after(0);
+
+    for expire in [
+        (|db: &EmbedDb| db.flush_table("sessions")) as fn(&EmbedDb) -> Result<()>,
+        |db| db.compact_table("sessions").map(|_| ()),
+    ] {
+        let row_id = db
+            .insert_row_with_ttl("sessions", title("short"), Duration::ZERO)
+            .unwrap();
+        assert_eq!(
+            subscription.try_recv().unwrap().unwrap().op,
+            RowChangeKind::Insert
+        );
+        assert!(expire(&db).is_err());
+        assert!(db.get_row("sessions", row_id).unwrap().is_none());
+        assert_eq!(db.count_rows("sessions", &[]).unwrap(), 1);
+        let change = subscription.try_recv().unwrap().unwrap();
+        assert_eq!((change.op, change.row_id), (RowChangeKind::Delete, row_id));
+    }
+}
+
+#[test]
+fn soft_deleted_rows_stay_hidden_until_restored_or_purged() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let db = EmbedDb::open(config.clone()).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("kind", DataType::String, false),
+        ])
+        .with_soft_delete(SoftDelete::default()),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+    for (title, kind) in [("a", "x"), ("bb", "x"), ("ccc", "y"), ("dddd", "y")] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("kind".to_string(), Value::String(kind.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.flush_table("notes").unwrap();
+
+    let ids = |db: &EmbedDb| {
+        db.scan_rows("notes", None, 100)
+            .unwrap()
+            .items
+            .iter()
+            .map(|row| row.id)
+            .collect::<Vec<_>>()
+    };
+    let hit_ids = |db: &EmbedDb| {
+        let mut hits: Vec<u64> = db
+            .search_knn("notes", &[2.0], 10, None)
+            .unwrap()
+            .iter()
+            .map(|hit| hit.row_id)
+            .collect();
+        hits.sort();
+        hits
+    };
+    db.delete_row("notes", 2).unwrap();
+    let kind_y = [FilterCondition {
+        column: "kind".to_string(),
+        op: FilterOp::Eq,
+        value: Value::String("y".to_string()),
+    }];
+    assert_eq!(db.delete_rows_where("notes", &kind_y).unwrap(), 2);
+    assert!(db.get_row("notes", 2).unwrap().is_none());
+    assert_eq!(ids(&db), [1]);
+    assert_eq!(hit_ids(&db), [1]);
+    let stats = db.table_stats("notes").unwrap();
+    assert_eq!((stats.total_rows, stats.hidden_rows), (1, 3));
+    assert!(db.delete_row("notes", 2).is_err());
+    assert!(db.update_row("notes", 2, BTreeMap::new()).is_err());
+
+    // Hidden rows survive a checkpoint and reopen, and come back with their embeddings.
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = EmbedDb::open(config.clone()).unwrap();
+    assert_eq!(ids(&db), [1]);
+    assert_eq!(db.restore_row("notes", 2).unwrap().id, 2);
+    assert_eq!(ids(&db), [1, 2]);
+    assert_eq!(hit_ids(&db), [1, 2]);
+    assert_eq!(
+        db.restore_row("notes", 2).unwrap_err().to_string(),
+        "row is not deleted"
+    );
+    assert_eq!(
+        db.restore_row("notes", 99).unwrap_err().to_string(),
+        "row not found"
+    );
+
+    // Purged rows are deleted for good.
+    assert_eq!(db.purge_hidden_rows("notes").unwrap(), 2);
+    assert!(db.restore_row("notes", 3).is_err());
+    assert_eq!(db.table_stats("notes").unwrap().hidden_rows, 0);
+    db.flush_table("notes").unwrap();
+    let stats = db.compact_table("notes").unwrap();
+    assert!(stats.merges[0].tombstones_dropped > 0);
+    drop(db);
+    let db = EmbedDb::open(config).unwrap();
+    assert_eq!(ids(&db), [1, 2]);
+    assert_eq!(db.purge_hidden_rows("notes").unwrap(), 0);
+
+    // Compaction purges rows hidden for longer than the retention.
+    db.create_table(
+        "drafts",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)])
+            .with_soft_delete(SoftDelete::default().with_retention_secs(0)),
+        None,
+    )
+    .unwrap();
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("draft".to_string()));
+    let draft = db.insert_row("drafts", fields).unwrap();
+    db.delete_row("drafts", draft).unwrap();
+    db.compact_table("drafts").unwrap();
+    assert_eq!(
+        db.restore_row("drafts", draft).unwrap_err().to_string(),
+        "row not found"
+    );
+    assert!(db
+        .restore_row("notes", 1)
+        .unwrap_err()
+        .to_string()
+        .contains("not deleted"));
+}
+
+#[test]
+fn searches_without_a_metric_use_the_table_default() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2)),
+    )
+    .unwrap();
+    db.create_table(
+        "plain",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for title in ["ab", "abcde"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields.clone()).unwrap());
+        db.insert_row("plain", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.process_pending_jobs("plain", &DummyEmbedder).unwrap();
+
+    // DummyEmbedder embeds each title as its length, so L2 distances are differences.
+    let hits = db.search_knn("notes", &[4.0], 2, None).unwrap();
+    assert_eq!(hits[0].row_id, ids[1]);
+    assert_eq!(hits[0].distance, 1.0);
+    let explicit = db
+        .search_knn("notes", &[4.0], 2, DistanceMetric::L2)
+        .unwrap();
+    let batch = db.search_knn_batch("notes", &[vec![4.0]], 2, None).unwrap();
+    for other in [&explicit, &batch[0]] {
+        let pairs = |hits: &[SearchHit]| -> Vec<(u64, f32)> {
+            hits.iter().map(|hit| (hit.row_id, hit.distance)).collect()
+        };
+        assert_eq!(pairs(other), pairs(&hits));
+    }
+
+    // Without a declared metric, tables default to cosine.
+    let hits = db.search_knn("plain", &[4.0], 2, None).unwrap();
+    assert!(hits.iter().all(|hit| hit.distance.abs() < 1e-6));
+}
+
+#[test]
+fn similarity_score_mode_adds_scores_next_to_distances() {
+    let dir = tempdir().unwrap();
+    let db =
+        EmbedDb::open(Config::new(dir.path().to_path_buf()).with_search_cache_capacity(8)).unwrap();
+    for (table, metric) in [
+        ("near", DistanceMetric::L2),
+        ("angle", DistanceMetric::Cosine),
+    ] {
+        db.create_table(
+            table,
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(EmbeddingSpec::new(vec!["title"]).with_metric(metric)),
+        )
+        .unwrap();
+        for title in ["ab", "abcde"] {
+            let mut fields = BTreeMap::new();
+            fields.insert("title".to_string(), Value::String(title.to_string()));
+            db.insert_row(table, fields).unwrap();
+        }
+        db.process_pending_jobs(table, &DummyEmbedder).unwrap();
+    }
+
+    let plain = db.search_knn("near", &[4.0], 2, None).unwrap();
+    assert!(plain.iter().all(|hit| hit.score.is_none()));
+    let options = SearchOptions {
+        score_mode: ScoreMode::Similarity,
+        ..SearchOptions::default()
+    };
+    // Scores are added after the cache, so these are served from the plain search's entry.
+    for _ in 0..2 {
+        let hits = db
+            .search_knn_with_options("near", &[4.0], 2, None, &[], &options)
+            .unwrap();
+        let scored: Vec<(f32, Option<f32>)> =
+            hits.iter().map(|hit| (hit.distance, hit.score)).collect();
+        assert_eq!(scored, vec![(1.0, Some(0.5)), (4.0, Some(0.2))]);
+    }
+    assert_eq!(db.db_stats().unwrap().search_cache_hits, 2);
+
+    // One-dimensional embeddings all point the same way, so cosine scores are 1.
+    let hits = db
+        .search_knn_with_rows("angle", &[4.0], 2, None, &[], &options)
+        .unwrap();
+    assert_eq!(hits.len(), 2);
+    assert!(hits
+        .iter()
+        .all(|hit| hit.score.is_some_and(|score| (score - 1.0).abs() < 1e-6)));
+}
+
+#[test]
+fn named_vectors_are_embedded_searched_and_survive_checkpoint() {
+    let dir = tempdir().unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("body", DataType::String, false),
+    ]);
+    let spec = EmbeddingSpec::new(vec!["title"])
+        .with_metric(DistanceMetric::L2)
+        .with_vector(NamedVectorSpec::new("body", vec!["body"]).with_dimensions(1));
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table("notes", schema, Some(spec)).unwrap();
+
+    let mut ids = Vec::new();
+    for (title, body) in [("a", "bbbbb"), ("aaaaa", "b")] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("body".to_string(), Value::String(body.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    assert_eq!(db.process_pending_jobs("notes", &DummyEmbedder).unwrap(), 2);
+
+    let by_body = SearchOptions {
+        vector: Some("body".to_string()),
+        ..SearchOptions::default()
+    };
+    let search = |db: &EmbedDb, options: &SearchOptions| -> Vec<u64> {
+        db.search_knn_with_options("notes", &[5.0], 2, None, &[], options)
+            .unwrap()
+            .iter()
+            .map(|hit| hit.row_id)
+            .collect()
+    };
+    assert_eq!(search(&db, &SearchOptions::default()), vec![ids[1], ids[0]]);
+    assert_eq!(search(&db, &by_body), vec![ids[0], ids[1]]);
+
+    let unknown = SearchOptions {
+        vector: Some("summary".to_string()),
+        ..SearchOptions::default()
+    };
+    let err = db
+        .search_knn_with_options("notes", &[5.0], 2, None, &[], &unknown)
+        .unwrap_err();
+    assert!(err.to_string().contains("no vector named 'summary'"));
+    let err = db
+        .search_knn_with_options("notes", &[5.0, 1.0], 2, None, &[], &by_body)
+        .unwrap_err();
+    assert!(err.to_string().contains("vector 'body'"));
+    let err = db
+        .alter_table(
+            "notes",
+            AlterTableOp::DropColumn {
+                name: "body".to_string(),
+            },
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("embedding source field"));
+
+    // Editing only a named vector's source field re-embeds the row.
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("a".to_string()));
+    fields.insert("body".to_string(), Value::String("bbbbbbbbbb".to_string()));
+    db.update_row("notes", ids[0], fields).unwrap();
+    assert_eq!(db.process_pending_jobs("notes", &DummyEmbedder).unwrap(), 1);
+    assert_eq!(search(&db, &by_body), vec![ids[1], ids[0]]);
+
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert_eq!(search(&db, &by_body), vec![ids[1], ids[0]]);
+    db.delete_row("notes", ids[1]).unwrap();
+    assert_eq!(search(&db, &by_body), vec![ids[0]]);
+}
+
+#[test]
+fn search_cache_serves_repeats_and_invalidates_on_write() {
+    let dir = tempdir().unwrap();
+    let db =
+        EmbedDb::open(Config::new(dir.path().to_path_buf()).with_search_cache_capacity(8)).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("abc".to_string()));
+    let first = db.insert_row("notes", fields).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let hits = db
+        .search_knn("notes", &[3.0], 5, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    let hits = db
+        .search_knn("notes", &[3.0], 5, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, first);
+    let stats = db.db_stats().unwrap();
+    assert_eq!(stats.search_cache_hits, 1);
+    assert_eq!(stats.search_cache_misses, 1);
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("abd".to_string()));
+    db.insert_row("notes", fields).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    assert_eq!(lock_cache(&db.inner.read().unwrap().search_cache).len(), 0);
+
+    let hits = db
+        .search_knn("notes", &[3.0], 5, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(db.db_stats().unwrap().search_cache_misses, 2);
+}
+
+#[test]
+fn search_similar_uses_stored_embedding_as_query() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2)),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for title in ["abc", "abcd", "abcdefghij"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+
+    let err = db.search_similar("notes", ids[0], 1, true).unwrap_err();
+    assert!(err.to_string().contains("no ready embedding"), "{err}");
+
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let hits = db.search_similar("notes", ids[0], 1, true).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].row_id, ids[1]);
+
+    let hits = db.search_similar("notes", ids[0], 2, false).unwrap();
+    assert_eq!(hits[0].row_id, ids[0]);
+    assert_eq!(hits[0].distance, 0.0);
+    assert_eq!(hits[1].row_id, ids[1]);
+}
+
+#[test]
+fn find_duplicates_clusters_rows_within_threshold() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    for (table, index) in [("flat", IndexSpec::Flat), ("graph", IndexSpec::hnsw())] {
+        db.create_table(
+            table,
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(
+                EmbeddingSpec::new(vec!["title"])
+                    .with_metric(DistanceMetric::L2)
+                    .with_index(index),
+            ),
+        )
+        .unwrap();
+        // DummyEmbedder embeds the title length; 20 equal rows outgrow the first neighbour fetch.
+        let mut titles = vec!["x", "y", "abcde", "abcdf", "abcdeg", "zzzzzzzzzz", "gone"];
+        titles.extend(std::iter::repeat_n("0123456789abcdefghijklmnopqrstu", 20));
+        let ids: Vec<u64> = titles
+            .iter()
+            .map(|title| {
+                let fields =
+                    BTreeMap::from([("title".to_string(), Value::String(title.to_string()))]);
+                db.insert_row(table, fields).unwrap()
+            })
+            .collect();
+        db.process_pending_jobs(table, &DummyEmbedder).unwrap();
+        db.delete_row(table, ids[6]).unwrap();
+
+        let clusters = db.find_duplicates(table, 0.5, None).unwrap();
+        let row_ids: Vec<Vec<u64>> = clusters.iter().map(|c| c.row_ids.clone()).collect();
+        assert_eq!(
+            row_ids,
+            [
+                vec![ids[0], ids[1]],
+                vec![ids[2], ids[3]],
+                ids[7..].to_vec()
+            ],
+            "{table}"
+        );
+        assert!(clusters.iter().all(|c| c.max_distance == 0.0));
+
+        let clusters = db.find_duplicates(table, 1.0, None).unwrap();
+        assert_eq!(clusters[1].row_ids, ids[2..5]);
+        assert_eq!(clusters[1].max_distance, 1.0);
+    }
+    let err = db.find_duplicates("flat", f32::NAN, None).unwrap_err();
+    assert!(err.to_string().contains("finite"), "{err}");
+    assert!(db.find_duplicates("missing", 1.0, None).is_err());
+}
+
+#[test]
+fn recommend_moves_toward_positives_and_away_from_negatives() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2)),
+    )
+    .unwrap();
+
+    // DummyEmbedder embeds each title as its length.
+    let mut ids = Vec::new();
+    for len in [2, 4, 5, 6, 8] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String("x".repeat(len)));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    // Positive 4 alone: the nearest non-example row is 5.
+    let hits = db.recommend("notes", &[ids[1]], &[], 1).unwrap();
+    assert_eq!(hits[0].row_id, ids[2]);
+
+    // Positive 5, negative 2: query = 5 + (5 - 2) = 8.
+    let hits = db.recommend("notes", &[ids[2]], &[ids[0]], 2).unwrap();
+    assert_eq!(hits[0].row_id, ids[4]);
+    assert_eq!(hits[1].row_id, ids[3]);
+    assert!(hits
+        .iter()
+        .all(|h| h.row_id != ids[0] && h.row_id != ids[2]));
+
+    assert!(db.recommend("notes", &[], &[ids[0]], 1).is_err());
+}
+
+#[test]
+fn scroll_embeddings_pages_in_row_id_order() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::Cosine)),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for title in ["a", "bb", "ccc"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs_with_limit("notes", &DummyEmbedder, 2)
+        .unwrap();
+
+    let page = db.scroll_embeddings("notes", None, 2).unwrap();
+    let row_ids: Vec<u64> = page.items.iter().map(|item| item.row_id).collect();
+    assert_eq!(row_ids, vec![ids[0], ids[1]]);
+    // Cosine tables store unit vectors; scrolling returns the original values.
+    assert_eq!(page.items[1].vector, Some(vec![2.0]));
+    assert_eq!(page.next_cursor, Some(ids[1]));
+
+    let page = db.scroll_embeddings("notes", page.next_cursor, 2).unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].row_id, ids[2]);
+    assert_eq!(page.items[0].status, EmbeddingStatus::Pending);
+    assert!(page.items[0].vector.is_none());
+    assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn scan_rows_merges_sst_and_memtable_and_skips_deleted_rows() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for title in ["a", "b", "c", "d"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.flush_table("notes").unwrap();
+    // One deletion lands in an SST tombstone, the other stays in the memtable.
+    db.delete_row("notes", ids[1]).unwrap();
+    db.flush_table("notes").unwrap();
+    db.delete_row("notes", ids[2]).unwrap();
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("A".to_string()));
+    db.update_row("notes", ids[0], fields).unwrap();
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("e".to_string()));
+    ids.push(db.insert_row("notes", fields).unwrap());
+
+    let page = db.scan_rows("notes", None, 2).unwrap();
+    let row_ids: Vec<u64> = page.items.iter().map(|row| row.id).collect();
+    assert_eq!(row_ids, vec![ids[0], ids[3]]);
+    assert_eq!(
+        page.items[0].fields.get("title"),
+        Some(&Value::String("A".to_string()))
+    );
+    assert_eq!(page.next_cursor, Some(ids[3]));
+
+    let page = db.scan_rows("notes", page.next_cursor, 2).unwrap();
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].id, ids[4]);
+    assert_eq!(page.next_cursor, None);
+
+    assert!(db.scan_rows("missing", None, 10).is_err());
+}
+
+#[test]
+fn aggregate_groups_across_memtable_and_sst() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "docs",
+        TableSchema::new(vec![
+            Column::new("source", DataType::String, false),
+            Column::new("score", DataType::Float, true),
+        ]),
+        None,
+    )
+    .unwrap();
+
+    let insert = |source: &str, score: Option<f64>| {
+        let mut fields = BTreeMap::new();
+        fields.insert("source".to_string(), Value::String(source.to_string()));
+        fields.insert(
+            "score".to_string(),
+            score.map(Value::Float).unwrap_or(Value::Null),
+        );
+        db.insert_row("docs", fields).unwrap()
+    };
+    insert("web", Some(1.0));
+    let deleted = insert("web", Some(100.0));
+    insert("pdf", Some(2.0));
+    db.flush_table("docs").unwrap();
+    insert("web", Some(3.0));
+    insert("pdf", None);
+    db.delete_row("docs", deleted).unwrap();
+
+    let aggs = vec![
+        Aggregation::count(),
+        Aggregation::new(AggregateFn::Avg, "score"),
+        Aggregation::new(AggregateFn::Max, "score"),
+        Aggregation::new(AggregateFn::Count, "score"),
+    ];
+    let rows = db
+        .aggregate("docs", &[], &["source".to_string()], &aggs)
+        .unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].group, vec![Value::String("pdf".to_string())]);
+    assert_eq!(
+        rows[0].values,
+        vec![
+            Value::Int(2),
+            Value::Float(2.0),
+            Value::Float(2.0),
+            Value::Int(1)
+        ]
+    );
+    assert_eq!(rows[1].group, vec![Value::String("web".to_string())]);
+    assert_eq!(
+        rows[1].values,
+        vec![
+            Value::Int(2),
+            Value::Float(2.0),
+            Value::Float(3.0),
+            Value::Int(2)
+        ]
+    );
+
+    let filters = vec![FilterCondition {
+        column: "score".to_string(),
+        op: FilterOp::Gte,
+        value: Value::Float(2.0),
+    }];
+    let rows = db
+        .aggregate(
+            "docs",
+            &filters,
+            &[],
+            &[Aggregation::new(AggregateFn::Sum, "score")],
+        )
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].values, vec![Value::Float(5.0)]);
+    assert_eq!(db.count_rows("docs", &[]).unwrap(), 4);
+    assert_eq!(db.count_rows("docs", &filters).unwrap(), 2);
+    let unknown = vec![FilterCondition {
+        column: "nope".to_string(),
+        op: FilterOp::Eq,
+        value: Value::Int(1),
+    }];
+    assert!(db.count_rows("docs", &unknown).is_err());
+
+    assert!(db
+        .aggregate(
+            "docs",
+            &[],
+            &[],
+            &[Aggregation::new(AggregateFn::Sum, "source")]
+        )
+        .is_err());
+}
+
+#[test]
+fn audit_log_records_who_changed_which_rows_and_rotates() {
+    let dir = tempdir().unwrap();
+    let audit = AuditConfig::default().with_max_file_bytes(250);
+    let config = Config::new(dir.path().to_path_buf()).with_audit_log(audit.clone());
+    let db = EmbedDb::open(config.clone()).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+    let title = |title: &str| BTreeMap::from([("title".to_string(), Value::String(title.into()))]);
+    let ids: Vec<u64> = with_audit_actor("alice", || {
+        ["a", "b", "c"]
+            .iter()
+            .map(|t| db.insert_row("notes", title(t)).unwrap())
+            .collect()
+    });
+    with_audit_actor("bob", || {
+        db.update_row("notes", ids[0], title("aa")).unwrap();
+        db.delete_row("notes", ids[1]).unwrap();
+    });
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.delete_range("notes", ids[2]..ids[2] + 1).unwrap();
+
+    let summary = |entries: &[AuditEntry]| {
+        entries
+            .iter()
+            .map(|entry| (entry.op, entry.actor.clone(), entry.row_id))
+            .collect::<Vec<_>>()
+    };
+    let alice = Some("alice".to_string());
+    let bob = Some("bob".to_string());
+    let expected = vec![
+        (AuditOp::CreateTable, None, None),
+        (AuditOp::Insert, alice.clone(), Some(ids[0])),
+        (AuditOp::Insert, alice.clone(), Some(ids[1])),
+        (AuditOp::Insert, alice, Some(ids[2])),
+        (AuditOp::Update, bob.clone(), Some(ids[0])),
+        (AuditOp::Delete, bob, Some(ids[1])),
+        (AuditOp::DeleteRange, None, None),
+    ];
+    let entries = db.audit_log(0).unwrap();
+    assert_eq!(summary(&entries), expected);
+    assert_eq!(entries[6].ranges, vec![ids[2]..ids[2] + 1]);
+    assert!(entries.windows(2).all(|pair| pair[0].lsn < pair[1].lsn));
+    assert!(entries[6].lsn <= db.current_lsn().unwrap());
+    assert!(db.audit_log(u64::MAX).unwrap().is_empty());
+    let files = || fs::read_dir(dir.path().join("audit")).unwrap().count();
+    assert!(files() > 2, "entries past max_file_bytes start new files");
+
+    // Entries survive a checkpoint and reopen; `max_files` prunes the oldest files.
+    db.close().unwrap();
+    drop(db);
+    let db = EmbedDb::open(config.with_audit_log(audit.with_max_files(1))).unwrap();
+    assert_eq!(summary(&db.audit_log(0).unwrap()), expected);
+    db.insert_row("notes", title("d")).unwrap();
+    db.insert_row("notes", title("e")).unwrap();
+    assert_eq!(files(), 2);
+    let entries = db.audit_log(0).unwrap();
+    assert!(entries.len() < expected.len());
+    assert_eq!(entries.last().map(|entry| entry.op), Some(AuditOp::Insert));
+}
+
+#[test]
+fn audit_log_skips_writes_whose_wal_append_failed() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf()).with_audit_log(AuditConfig::default());
+    let db = EmbedDb::open(config.clone()).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+    let title = |title: &str| BTreeMap::from([("title".to_string(), Value::String(title.into()))]);
+    db.insert_row("notes", title("kept")).unwrap();
+
+    // The budget covers an audit entry, which holds no field values, but not the row's record.
+    let faults = testing::FaultInjector::install(dir.path());
+    faults.truncate_wal_after(500);
+    assert!(db.insert_row("notes", title(&"lost".repeat(250))).is_err());
+    let ops = |entries: Vec<AuditEntry>| entries.iter().map(|entry| entry.op).collect::<Vec<_>>();
+    let expected = vec![AuditOp::CreateTable, AuditOp::Insert];
+    assert_eq!(ops(db.audit_log(0).unwrap()), expected);
+    drop(db);
+    faults.clear();
+    let db = EmbedDb::open(config).unwrap();
+    assert_eq!(db.count_rows("notes", &[]).unwrap(), 1);
+    assert_eq!(ops(db.audit_log(0).unwrap()), expected);
+}
+
+#[test]
+fn read_at_lsn_replays_history_across_archived_checkpoints() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf()).with_wal_archive(true);
+    let db = EmbedDb::open(config.clone()).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("first".to_string()));
+    let row_id = db.insert_row("notes", fields).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    let lsn_first = db.current_lsn().unwrap();
+
+    db.checkpoint().unwrap();
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("second".to_string()));
+    db.update_row("notes", row_id, fields).unwrap();
+    db.checkpoint().unwrap();
+    db.delete_row("notes", row_id).unwrap();
+    let lsn_deleted = db.current_lsn().unwrap();
+    assert!(lsn_deleted > lsn_first);
+
+    let view = db.read_at_lsn(lsn_first).unwrap();
+    assert_eq!(view.list_tables(), vec!["notes".to_string()]);
+    let row = view.get_row("notes", row_id).unwrap().unwrap();
+    assert_eq!(
+        row.fields.get("title"),
+        Some(&Value::String("first".to_string()))
+    );
+    let hits = view
+        .search_knn("notes", &[5.0], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, row_id);
+
+    assert!(db
+        .read_at_lsn(lsn_deleted)
+        .unwrap()
+        .get_row("notes", row_id)
+        .unwrap()
+        .is_none());
+    assert!(db.read_at_lsn(0).unwrap().list_tables().is_empty());
+    assert!(db.read_at_lsn(lsn_deleted + 1).is_err());
+
+    // LSNs survive reopen.
+    drop(db);
+    let db = EmbedDb::open(config).unwrap();
+    assert_eq!(db.current_lsn().unwrap(), lsn_deleted);
+}
+
+#[test]
+fn snapshot_is_unaffected_by_later_writes_and_compaction() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+    let title =
+        |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.to_string()))]);
+    let first = db.insert_row("notes", title("a")).unwrap();
+    let second = db.insert_row("notes", title("bb")).unwrap();
+    db.flush_table("notes").unwrap();
+    let third = db.insert_row("notes", title("ccc")).unwrap();
+    db.flush_table("notes").unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let view = db.snapshot().unwrap();
+    assert_eq!(view.lsn(), db.current_lsn().unwrap());
+
+    db.delete_row("notes", first).unwrap();
+    db.update_row("notes", second, title("changed")).unwrap();
+    db.insert_row("notes", title("dddd")).unwrap();
+    db.flush_table("notes").unwrap();
+    db.compact_table("notes").unwrap();
+    db.rename_table("notes", "archive").unwrap();
+
+    let page = view.scan_rows("notes", None, 10).unwrap();
+    let ids: Vec<u64> = page.items.iter().map(|row| row.id).collect();
+    assert_eq!(ids, vec![first, second, third]);
+    assert_eq!(
+        page.items[1].fields.get("title"),
+        Some(&Value::String("bb".to_string()))
+    );
+    assert!(view.get_row("notes", first).unwrap().is_some());
+    let embeddings = view.scroll_embeddings("notes", None, 10).unwrap();
+    assert_eq!(embeddings.items.len(), 3);
+    assert_eq!(embeddings.items[2].vector, Some(vec![3.0]));
+    let hits = view
+        .search_knn("notes", &[2.0], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, second);
+    assert!(view.get_row("archive", first).is_err());
+
+    // The view's links to the compacted files go away with it, and with a restart.
+    let links = dir.path().join("read_snapshots");
+    assert_eq!(fs::read_dir(&links).unwrap().count(), 1);
+    drop(view);
+    assert_eq!(fs::read_dir(&links).unwrap().count(), 0);
+    let leaked = db.snapshot().unwrap();
+    std::mem::forget(leaked);
+    drop(db);
+    EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert!(!links.exists());
+}
+
+#[test]
+fn shipped_wal_records_keep_a_replica_in_step() {
+    let dir = tempdir().unwrap();
+    let primary_dir = dir.path().join("primary");
+    let replica_dir = dir.path().join("replica");
+    let fields = |pairs: &[(&str, Value)]| {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let primary = EmbedDb::open(Config::new(primary_dir).with_wal_archive(true)).unwrap();
+    primary
+        .create_table(
+            "notes",
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(EmbeddingSpec::new(vec!["title"])),
+        )
+        .unwrap();
+    let first = primary
+        .insert_row("notes", fields(&[("title", Value::String("a".into()))]))
+        .unwrap();
+    primary
+        .process_pending_jobs("notes", &DummyEmbedder)
+        .unwrap();
+    primary.export_snapshot(&replica_dir).unwrap();
+    let replica = EmbedDb::open(Config::new(replica_dir.clone())).unwrap();
+    assert_eq!(
+        replica.current_lsn().unwrap(),
+        primary.current_lsn().unwrap()
+    );
+
+    primary
+        .insert_rows(
+            "notes",
+            vec![
+                fields(&[("title", Value::String("bb".into()))]),
+                fields(&[("title", Value::String("ccc".into()))]),
+            ],
+        )
+        .unwrap();
+    primary.delete_row("notes", first).unwrap();
+    primary.flush_table("notes").unwrap();
+    primary
+        .alter_table(
+            "notes",
+            AlterTableOp::AddColumn {
+                column: Column::new("tag", DataType::String, false),
+                default: Value::String("none".into()),
+            },
+        )
+        .unwrap();
+    primary
+        .process_pending_jobs("notes", &DummyEmbedder)
+        .unwrap();
+    primary.rename_table("notes", "archive").unwrap();
+    primary
+        .insert_row(
+            "archive",
+            fields(&[
+                ("title", Value::String("dddd".into())),
+                ("tag", Value::String("new".into())),
+            ]),
+        )
+        .unwrap();
+
+    let entries = primary.wal_since(replica.current_lsn().unwrap()).unwrap();
+    assert_eq!(
+        entries.last().map(|entry| entry.lsn),
+        Some(primary.current_lsn().unwrap())
+    );
+    assert!(replica.apply_wal_records(&entries[2..]).is_err());
+    let lsn = replica.apply_wal_records(&entries).unwrap();
+    assert_eq!(lsn, primary.current_lsn().unwrap());
+    // Entries already applied are skipped.
+    assert_eq!(replica.apply_wal_records(&entries).unwrap(), lsn);
+
+    let check = |db: &EmbedDb| {
+        let rows = db.scan_rows("archive", None, 10).unwrap().items;
+        let titles: Vec<(&Value, &Value)> = rows
+            .iter()
+            .map(|row| (&row.fields["title"], &row.fields["tag"]))
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                (&Value::String("bb".into()), &Value::String("none".into())),
+                (&Value::String("ccc".into()), &Value::String("none".into())),
+                (&Value::String("dddd".into()), &Value::String("new".into())),
+            ]
+        );
+        let hits = db
+            .search_knn("archive", &[3.0], 1, DistanceMetric::L2)
+            .unwrap();
+        assert_eq!(hits[0].row_id, rows[1].id);
+    };
+    check(&primary);
+    check(&replica);
+    assert!(replica.list_tables().unwrap().iter().all(|t| t != "notes"));
+    drop(replica);
+    let replica = EmbedDb::open(Config::new(replica_dir)).unwrap();
+    assert_eq!(replica.current_lsn().unwrap(), lsn);
+    check(&replica);
+
+    // The archive holds the primary's whole history; the replica's starts at its seed.
+    assert_eq!(primary.wal_since(0).unwrap()[0].lsn, 1);
+    assert!(replica.wal_since(0).is_err());
+    assert!(replica.wal_since(lsn).unwrap().is_empty());
+    assert!(primary.wal_since(lsn + 1).is_err());
+}
+
+#[test]
+fn read_at_lsn_requires_retained_history() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+    assert!(db.read_at_lsn(1).is_ok());
+
+    db.checkpoint().unwrap();
+    let err = db.read_at_lsn(1).unwrap_err();
+    assert!(err.to_string().contains("not retained"), "{err}");
+}
+
+#[test]
+fn triggers_see_old_and_new_rows_after_commit() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap());
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        None,
+    )
+    .unwrap();
+
+    let changes: Arc<Mutex<Vec<RowChange>>> = Arc::new(Mutex::new(Vec::new()));
+    let seen = changes.clone();
+    let reader = db.clone();
+    db.register_trigger(Arc::new(move |change: &RowChange| {
+        // The write is committed and the lock released before triggers run.
+        let current = reader.get_row(&change.table, change.row_id).unwrap();
+        assert_eq!(current.is_some(), change.new.is_some());
+        seen.lock().unwrap().push(change.clone());
+    }));
+
+    let title = |t: &str| {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(t.to_string()));
+        fields
+    };
+    let row_id = db.insert_row("notes", title("a")).unwrap();
+    db.flush_table("notes").unwrap();
+    db.update_row("notes", row_id, title("b")).unwrap();
+    db.delete_row("notes", row_id).unwrap();
+    assert!(db.delete_row("notes", row_id).is_err());
+
+    let changes = changes.lock().unwrap();
+    let kinds: Vec<RowChangeKind> = changes.iter().map(|c| c.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            RowChangeKind::Insert,
+            RowChangeKind::Update,
+            RowChangeKind::Delete
+        ]
+    );
+    assert!(changes[0].old.is_none());
+    // The previous row is resolved from the SST after the flush.
+    assert_eq!(
+        changes[1].old.as_ref().unwrap().fields.get("title"),
+        Some(&Value::String("a".to_string()))
+    );
+    assert_eq!(
+        changes[2].old.as_ref().unwrap().fields.get("title"),
+        Some(&Value::String("b".to_string()))
+    );
+    assert!(changes[2].new.is_none());
+}
+
+#[test]
+fn change_feed_numbers_committed_changes_per_table() {
+    let dir = tempdir().unwrap();
+    let db =
+        EmbedDb::open(Config::new(dir.path().to_path_buf()).with_change_feed_capacity(3)).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema.clone(), None).unwrap();
+    db.create_table("other", schema, None).unwrap();
+    assert_eq!(db.last_change_seq(), 0);
+
+    let title = |t: &str| BTreeMap::from([("title".to_string(), Value::String(t.to_string()))]);
+    let row_id = db.insert_row("notes", title("a")).unwrap();
+    let inserted_at = db.current_lsn().unwrap();
+    db.insert_row("other", title("x")).unwrap();
+    db.update_row("notes", row_id, title("b")).unwrap();
+    let updated_at = db.current_lsn().unwrap();
+    assert!(db.update_row("notes", 99, title("c")).is_err());
+
+    // Changes are numbered by the WAL position their write committed at.
+    let page = db.changes_since("notes", 0).unwrap();
+    assert_eq!(page.last_seq, updated_at);
+    let seen: Vec<(u64, RowChangeKind, u64)> = page
+        .events
+        .iter()
+        .map(|event| (event.seq, event.op, event.row_id))
+        .collect();
+    assert_eq!(
+        seen,
+        vec![
+            (inserted_at, RowChangeKind::Insert, row_id),
+            (updated_at, RowChangeKind::Update, row_id)
+        ]
+    );
+    assert!(db
+        .changes_since("notes", updated_at)
+        .unwrap()
+        .events
+        .is_empty());
+
+    db.delete_row("notes", row_id).unwrap();
+    let deleted_at = db.current_lsn().unwrap();
+    let page = db.changes_since("notes", updated_at).unwrap();
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].op, RowChangeKind::Delete);
+    assert_eq!(page.last_seq, deleted_at);
+
+    // Only the last 3 changes are held, and seqs from the future mean a reopened database.
+    for after in [0, deleted_at + 1] {
+        let err = db.changes_since("notes", after).unwrap_err();
+        let lagged = err.downcast_ref::<ChangesLagged>().expect("lagged");
+        assert_eq!((lagged.after, lagged.last_seq), (after, deleted_at));
+    }
+    assert_eq!(
+        db.changes_since("notes", inserted_at).unwrap().events.len(),
+        2
+    );
+
+    // After a reopen, seqs carry on from the WAL's LSN: a reader that saw every change keeps
+    // its place, while older positions are stale however few changes the new feed has.
+    drop(db);
+    let db =
+        EmbedDb::open(Config::new(dir.path().to_path_buf()).with_change_feed_capacity(3)).unwrap();
+    assert_eq!(db.last_change_seq(), deleted_at);
+    for _ in 0..3 {
+        db.insert_row("notes", title("again")).unwrap();
+    }
+    let err = db.changes_since("notes", updated_at).unwrap_err();
+    assert!(err.downcast_ref::<ChangesLagged>().is_some(), "{err:#}");
+    let page = db.changes_since("notes", deleted_at).unwrap();
+    assert_eq!(page.events.len(), 3);
+    assert!(page.events[0].seq > deleted_at);
+}
+
+#[test]
+fn changes_made_by_one_write_share_its_seq() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, None).unwrap();
+    let title = |t: &str| BTreeMap::from([("title".to_string(), Value::String(t.to_string()))]);
+
+    let ids = db
+        .insert_rows("notes", vec![title("a"), title("b"), title("c")])
+        .unwrap();
+    let inserted_at = db.current_lsn().unwrap();
+    // A range delete logs one WAL record however many rows it removes.
+    assert_eq!(db.delete_range("notes", ids[0]..ids[2] + 1).unwrap(), 3);
+    let deleted_at = db.current_lsn().unwrap();
+    assert_eq!(deleted_at, inserted_at + 1);
+
+    let page = db.changes_since("notes", 0).unwrap();
+    let seqs: Vec<u64> = page.events.iter().map(|event| event.seq).collect();
+    assert_eq!(
+        seqs,
+        [[inserted_at; 3], [deleted_at; 3]].concat(),
+        "{:?}",
+        page.events
+    );
+    assert_eq!(page.last_seq, deleted_at);
+    // A reader positioned at a write has seen all of its changes.
+    assert_eq!(
+        db.changes_since("notes", inserted_at).unwrap().events.len(),
+        3
+    );
+    assert!(db
+        .changes_since("notes", deleted_at)
+        .unwrap()
+        .events
+        .is_empty());
+}
+
+#[test]
+fn subscriptions_receive_a_tables_changes_after_commit() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(
+        EmbedDb::open(Config::new(dir.path().to_path_buf()).with_change_feed_capacity(4)).unwrap(),
+    );
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema.clone(), None).unwrap();
+    db.create_table("other", schema, None).unwrap();
+    let title = |t: &str| BTreeMap::from([("title".to_string(), Value::String(t.to_string()))]);
+    db.insert_row("notes", title("before")).unwrap();
+
+    assert!(db.subscribe("missing").is_err());
+    let mut subscription = db.subscribe("notes").unwrap();
+    assert_eq!(subscription.table(), "notes");
+    assert_eq!(subscription.try_recv().unwrap(), None);
+
+    let writer = db.clone();
+    let handle = std::thread::spawn(move || {
+        writer.insert_row("other", title("x")).unwrap();
+        let row_id = writer.insert_row("notes", title("a")).unwrap();
+        writer.delete_row("notes", row_id).unwrap();
+        row_id
+    });
+    let inserted = subscription.recv().unwrap();
+    let deleted = subscription.recv().unwrap();
+    let row_id = handle.join().unwrap();
+    assert_eq!(
+        (inserted.op, inserted.row_id),
+        (RowChangeKind::Insert, row_id)
+    );
+    assert_eq!(deleted.op, RowChangeKind::Delete);
+    assert!(inserted.seq < deleted.seq);
+    assert_eq!(deleted.seq, db.current_lsn().unwrap());
+    // The write is durable by the time its event arrives.
+    assert!(db.get_row("notes", row_id).unwrap().is_none());
+    assert_eq!(
+        subscription
+            .recv_timeout(Duration::from_millis(10))
+            .unwrap(),
+        None
+    );
+
+    // A subscription that falls behind the feed's capacity hears about it once, then carries on.
+    for i in 0..5 {
+        db.insert_row("other", title(&i.to_string())).unwrap();
+    }
+    let err = subscription.try_recv().unwrap_err();
+    let lagged_at = err.downcast_ref::<ChangesLagged>().unwrap().last_seq;
+    assert_eq!(lagged_at, db.current_lsn().unwrap());
+    assert!(db.subscribe_after("notes", deleted.seq).is_err());
+    let row_id = db.insert_row("notes", title("b")).unwrap();
+    assert_eq!(subscription.try_recv().unwrap().unwrap().row_id, row_id);
+
+    let mut resumed = db.subscribe_after("notes", lagged_at).unwrap();
+    assert_eq!(
+        resumed.try_recv().unwrap().unwrap().seq,
+        db.current_lsn().unwrap()
+    );
+    db.close().unwrap();
+    assert!(resumed.recv().is_err());
+}
+
+#[test]
+fn change_feed_order_is_commit_order_under_concurrent_writers() {
+    let dir = tempdir().unwrap();
+    // Group commit waits for the sync after the write lock is released, which is where
+    // concurrent writers could otherwise overtake one another.
+    let config = Config::new(dir.path().to_path_buf())
+        .with_wal_group_commit(Duration::from_millis(1))
+        .with_change_feed_capacity(1024);
+    let db = Arc::new(EmbedDb::open(config).unwrap());
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, None).unwrap();
+    let mut subscription = db.subscribe("notes").unwrap();
+
+    let writers: Vec<_> = (0..8)
+        .map(|_| {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                for _ in 0..25 {
+                    let fields = BTreeMap::from([("title".to_string(), Value::String("t".into()))]);
+                    db.insert_row("notes", fields).unwrap();
+                }
+            })
+        })
+        .collect();
+    // Row ids are allocated under the write lock, so commit order is row id order.
+    let mut events = Vec::new();
+    while events.len() < 200 {
+        events.push(subscription.recv().unwrap());
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert!(events.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+    assert!(events
+        .windows(2)
+        .all(|pair| pair[0].row_id < pair[1].row_id));
+    assert_eq!(subscription.try_recv().unwrap(), None);
+}
+
+#[test]
+fn generated_columns_are_computed_on_write() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("title", DataType::String, false),
+        Column::new("body", DataType::String, true),
+        Column::new("title_lc", DataType::String, true)
+            .with_generated(ColumnExpr::Lowercase("title".to_string())),
+        Column::new("body_len", DataType::Int, true)
+            .with_generated(ColumnExpr::Length("body".to_string())),
+        Column::new("doc", DataType::String, true).with_generated(ColumnExpr::Concat {
+            columns: vec!["title".to_string(), "body".to_string()],
+            separator: " | ".to_string(),
+        }),
+    ]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["doc"])))
+        .unwrap();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("Hello".to_string()));
+    fields.insert("body".to_string(), Value::String("Wörld".to_string()));
+    let row_id = db.insert_row("notes", fields).unwrap();
+    let row = db.get_row("notes", row_id).unwrap().unwrap();
+    assert_eq!(
+        row.fields.get("title_lc"),
+        Some(&Value::String("hello".to_string()))
+    );
+    assert_eq!(row.fields.get("body_len"), Some(&Value::Int(5)));
+    assert_eq!(
+        row.fields.get("doc"),
+        Some(&Value::String("Hello | Wörld".to_string()))
+    );
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("Bye".to_string()));
+    db.update_row("notes", row_id, fields).unwrap();
+    let row = db.get_row("notes", row_id).unwrap().unwrap();
+    assert_eq!(row.fields.get("body_len"), Some(&Value::Null));
+    assert_eq!(
+        row.fields.get("doc"),
+        Some(&Value::String("Bye".to_string()))
+    );
+
+    // A patch keeps the columns it doesn't set and recomputes generated ones from the merge.
+    let mut fields = BTreeMap::new();
+    fields.insert("body".to_string(), Value::String("Hi".to_string()));
+    let patched = db.patch_row("notes", row_id, fields).unwrap();
+    assert_eq!(patched.version, row.version + 1);
+    assert_eq!(
+        patched.fields.get("doc"),
+        Some(&Value::String("Bye | Hi".to_string()))
+    );
+    assert_eq!(
+        db.get_row("notes", row_id).unwrap().unwrap().fields,
+        patched.fields
+    );
+    let mut fields = BTreeMap::new();
+    fields.insert("body".to_string(), Value::Int(1));
+    assert!(db.patch_row("notes", row_id, fields).is_err());
+
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("x".to_string()));
+    fields.insert("title_lc".to_string(), Value::String("y".to_string()));
+    let err = db.insert_row("notes", fields).unwrap_err();
+    assert!(err.to_string().contains("is generated"), "{err}");
+
+    let bad = TableSchema::new(vec![
+        Column::new("n", DataType::Int, false),
+        Column::new("n_lc", DataType::String, true)
+            .with_generated(ColumnExpr::Lowercase("n".to_string())),
+    ]);
+    assert!(db.create_table("bad", bad, None).is_err());
+}
+
+#[test]
+fn column_defaults_fill_in_fields_left_out_of_inserts() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("id", DataType::String, false).with_default(ColumnDefault::UuidV4),
+        Column::new("title", DataType::String, false),
+        Column::new("status", DataType::String, false)
+            .with_default(ColumnDefault::Value(Value::String("draft".to_string()))),
+        Column::new("added_ms", DataType::Int, false).with_default(ColumnDefault::Now),
+        Column::new("status_uc", DataType::String, true)
+            .with_generated(ColumnExpr::Uppercase("status".to_string())),
+    ]);
+    db.create_table("notes", schema, None).unwrap();
+    let title =
+        |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.to_string()))]);
+
+    let before = now_epoch_ms() as i64;
+    let first = db.insert_row("notes", title("a")).unwrap();
+    let row = db.get_row("notes", first).unwrap().unwrap();
+    let Some(Value::String(id)) = row.fields.get("id") else {
+        panic!("expected a generated id: {:?}", row.fields);
+    };
+    assert_eq!(id.len(), 36);
+    assert_eq!(id.as_bytes()[14], b'4');
+    assert_eq!(
+        row.fields.get("status_uc"),
+        Some(&Value::String("DRAFT".to_string()))
+    );
+    let Some(Value::Int(added_ms)) = row.fields.get("added_ms") else {
+        panic!("expected an insert time: {:?}", row.fields);
+    };
+    assert!(*added_ms >= before && *added_ms <= now_epoch_ms() as i64);
+
+    // Supplied values win, and every row gets its own UUID.
+    let mut fields = title("b");
+    fields.insert("status".to_string(), Value::String("live".to_string()));
+    let ids = db.insert_rows("notes", vec![fields, title("c")]).unwrap();
+    let second = db.get_row("notes", ids[0]).unwrap().unwrap();
+    let third = db.get_row("notes", ids[1]).unwrap().unwrap();
+    assert_eq!(
+        second.fields.get("status"),
+        Some(&Value::String("live".to_string()))
+    );
+    assert_ne!(second.fields.get("id"), third.fields.get("id"));
+    assert_ne!(second.fields.get("id"), row.fields.get("id"));
+    let results = db
+        .write_batch(vec![WriteOp::Insert {
+            table: "notes".to_string(),
+            fields: title("d"),
+        }])
+        .unwrap();
+    let written = db.get_row("notes", results[0].row_id).unwrap().unwrap();
+    assert_eq!(
+        written.fields.get("status"),
+        Some(&Value::String("draft".to_string()))
+    );
+
+    // Updates replace the fields as given.
+    let err = db.update_row("notes", first, title("e")).unwrap_err();
+    assert!(err.to_string().contains("missing required column"), "{err}");
+
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = db.describe_table("notes").unwrap().schema;
+    assert_eq!(schema.columns[0].default, Some(ColumnDefault::UuidV4));
+
+    for bad in [
+        Column::new("n", DataType::String, false).with_default(ColumnDefault::Now),
+        Column::new("n", DataType::Int, false).with_default(ColumnDefault::UuidV4),
+        Column::new("n", DataType::Int, true).with_default(ColumnDefault::Value(Value::Null)),
+        Column::new("n", DataType::Int, false)
+            .with_default(ColumnDefault::Value(Value::String("1".to_string()))),
+        Column::new("n", DataType::Int, false)
+            .with_constraints(ColumnConstraints::default().with_range(Some(0.0), Some(1.0)))
+            .with_default(ColumnDefault::Value(Value::Int(5))),
+    ] {
+        assert!(db
+            .create_table("bad", TableSchema::new(vec![bad]), None)
+            .is_err());
+    }
+}
+
+#[test]
+fn column_constraints_reject_bad_rows_with_precise_errors() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![
+        Column::new("slug", DataType::String, false).with_constraints(
+            ColumnConstraints::default()
+                .with_max_length(8)
+                .with_pattern("^[a-z-]+$")
+                .unwrap(),
+        ),
+        Column::new("score", DataType::Float, true)
+            .with_constraints(ColumnConstraints::default().with_range(Some(0.0), Some(1.0))),
+        Column::new("kind", DataType::String, true).with_constraints(
+            ColumnConstraints::default().with_allowed_values(vec![
+                Value::String("post".to_string()),
+                Value::String("page".to_string()),
+            ]),
+        ),
+    ]);
+    db.create_table("docs", schema, None).unwrap();
+
+    let row = |slug: &str, score: Value, kind: Value| {
+        let mut fields = BTreeMap::new();
+        fields.insert("slug".to_string(), Value::String(slug.to_string()));
+        fields.insert("score".to_string(), score);
+        fields.insert("kind".to_string(), kind);
+        fields
+    };
+    let post = || Value::String("post".to_string());
+
+    db.insert_row("docs", row("hello", Value::Float(0.5), post()))
+        .unwrap();
+    db.insert_row("docs", row("nulls-ok", Value::Null, Value::Null))
+        .unwrap();
+
+    let cases = [
+        (
+            row("much-too-long", Value::Null, post()),
+            "exceeds max length 8 (got 13)",
+        ),
+        (row("Hello", Value::Null, post()), "does not match pattern"),
+        (row("a", Value::Float(1.5), post()), "above maximum 1"),
+        (row("a", Value::Float(-0.1), post()), "below minimum 0"),
+        (
+            row("a", Value::Null, Value::String("wiki".to_string())),
+            "not one of the allowed values",
+        ),
+    ];
+    for (fields, expected) in cases {
+        let err = db.insert_row("docs", fields).unwrap_err();
+        assert!(err.to_string().contains(expected), "{err}");
+    }
+
+    let bad = TableSchema::new(vec![Column::new("n", DataType::Int, false)
+        .with_constraints(ColumnConstraints::default().with_max_length(3))]);
+    assert!(db.create_table("bad", bad, None).is_err());
+    assert!(ColumnConstraints::default().with_pattern("(").is_err());
+
+    // Constraints (including compiled patterns) survive a reopen through the WAL.
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert!(db
+        .insert_row("docs", row("UPPER", Value::Null, post()))
+        .is_err());
+}
+
+#[test]
+fn embedding_spec_change_reembeds_rows_whose_hash_changes() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![
+            Column::new("title", DataType::String, false),
+            Column::new("body", DataType::String, true),
+        ]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for (title, body) in [("a", Value::Null), ("bb", Value::String("x".to_string()))] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        fields.insert("body".to_string(), body);
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let same_fields = EmbeddingSpec::new(vec!["title"]).with_metric(DistanceMetric::L2);
+    let plan = db.plan_embedding_spec("notes", &same_fields).unwrap();
+    assert!(plan.affected_rows.is_empty());
+    assert_eq!(plan.unchanged_rows, 2);
+
+    let err = db
+        .plan_embedding_spec("notes", &EmbeddingSpec::new(vec!["missing"]))
+        .unwrap_err();
+    assert!(err.to_string().contains("unknown embedding field"));
+
+    let wider = EmbeddingSpec::new(vec!["title", "body"]);
+    let plan = db.plan_embedding_spec("notes", &wider).unwrap();
+    assert_eq!(plan.affected_rows, ids);
+    assert!(!plan.applied);
+    // A dry run leaves jobs alone.
+    let jobs = db.list_embedding_jobs("notes").unwrap();
+    assert!(jobs.iter().all(|job| job.status == EmbeddingStatus::Ready));
+
+    let plan = db.apply_embedding_spec("notes", wider).unwrap();
+    assert!(plan.applied);
+    let pending: Vec<u64> = db
+        .list_embedding_jobs("notes")
+        .unwrap()
+        .into_iter()
+        .filter(|job| job.status == EmbeddingStatus::Pending)
+        .map(|job| job.row_id)
+        .collect();
+    assert_eq!(pending, ids);
+    drop(db);
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    {
+        let inner = db.inner.read().unwrap();
+        let table_state = inner.state.tables.get("notes").unwrap();
+        let spec = table_state.embedding_spec.as_ref().unwrap();
+        assert_eq!(spec.source_fields, vec!["title", "body"]);
+    }
+    assert_eq!(db.process_pending_jobs("notes", &DummyEmbedder).unwrap(), 2);
+    let plan = db
+        .plan_embedding_spec("notes", &EmbeddingSpec::new(vec!["title", "body"]))
+        .unwrap();
+    assert!(plan.affected_rows.is_empty());
+}
+
+#[test]
+fn reembed_table_switches_rows_to_a_new_model() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let db = EmbedDb::open(config.clone()).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    let insert = |db: &EmbedDb, title: &str| {
+        let fields = BTreeMap::from([("title".to_string(), Value::String(title.to_string()))]);
+        db.insert_row("notes", fields).unwrap()
+    };
+    let model_of = |db: &EmbedDb, row_id| db.get_embedding("notes", row_id).unwrap().unwrap().model;
+
+    let untracked = insert(&db, "a");
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    assert_eq!(model_of(&db, untracked), None);
+
+    // Naming the model the table already uses re-embeds nothing.
+    let v1 = EmbeddingModel::new("mini").with_version("1");
+    let plan = db
+        .apply_embedding_spec(
+            "notes",
+            EmbeddingSpec::new(vec!["title"]).with_model(v1.clone()),
+        )
+        .unwrap();
+    assert!(plan.affected_rows.is_empty());
+    let tracked = insert(&db, "bb");
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    assert_eq!(model_of(&db, tracked), Some(v1.clone()));
+
+    let v2 = EmbeddingModel::new("mini").with_version("2");
+    let plan = db.reembed_table("notes", v2.clone()).unwrap();
+    assert!(plan.applied);
+    assert_eq!(plan.affected_rows, vec![untracked, tracked]);
+    let pending = db.list_embedding_jobs("notes").unwrap();
+    assert!(pending
+        .iter()
+        .all(|job| job.status == EmbeddingStatus::Pending));
+    // Queued rows keep serving their old vectors until they are embedded again.
+    assert!(db
+        .get_embedding("notes", tracked)
+        .unwrap()
+        .unwrap()
+        .vector
+        .is_some());
+    assert_eq!(db.process_pending_jobs("notes", &DummyEmbedder).unwrap(), 2);
+    assert!(db
+        .reembed_table("notes", v2.clone())
+        .unwrap()
+        .affected_rows
+        .is_empty());
+
+    // Going back to an earlier model through the spec re-embeds the rows as well.
+    let plan = db
+        .plan_embedding_spec("notes", &EmbeddingSpec::new(vec!["title"]).with_model(v1))
+        .unwrap();
+    assert_eq!(plan.affected_rows, vec![untracked, tracked]);
+    drop(db);
+
+    // Models survive replay, and the snapshot a checkpoint writes.
+    let db = EmbedDb::open(config.clone()).unwrap();
+    assert_eq!(model_of(&db, untracked), Some(v2.clone()));
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = EmbedDb::open(config).unwrap();
+    assert_eq!(model_of(&db, tracked), Some(v2));
+
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("plain", schema, None).unwrap();
+    let err = db
+        .reembed_table("plain", EmbeddingModel::new("mini"))
+        .unwrap_err();
+    assert!(err.to_string().contains("no embedding spec"));
+}
+
+#[test]
+fn first_embedding_spec_backfills_every_row_in_batched_syncs() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, None).unwrap();
+    let rows = (0..2500)
+        .map(|i| {
+            let mut fields = BTreeMap::new();
+            fields.insert("title".to_string(), Value::String("x".repeat(i % 7 + 1)));
+            fields
+        })
+        .collect();
+    db.insert_rows("notes", rows).unwrap();
+    db.flush_table("notes").unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_total, 0);
+
+    let syncs_before = db.db_stats().unwrap().wal_sync_ops;
+    let plan = db
+        .apply_embedding_spec("notes", EmbeddingSpec::new(vec!["title"]))
+        .unwrap();
+    assert_eq!(plan.affected_rows.len(), 2500);
+    // One sync for the spec and one per batch of jobs, not one per row.
+    assert_eq!(db.db_stats().unwrap().wal_sync_ops - syncs_before, 4);
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_pending, 2500);
+    drop(db);
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert_eq!(
+        db.process_pending_jobs("notes", &DummyEmbedder).unwrap(),
+        2500
+    );
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_ready, 2500);
+}
+
+#[test]
+fn sparse_vectors_search_fuse_and_survive_checkpoint() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for title in ["a", "bbbb", "cccccccc"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let sparse = |pairs: &[(u32, f32)]| SparseVector::from_pairs(pairs.iter().copied()).unwrap();
+    db.put_sparse_vector("notes", ids[0], sparse(&[(1, 1.0), (5, 2.0)]))
+        .unwrap();
+    db.put_sparse_vector("notes", ids[1], sparse(&[(5, 0.5)]))
+        .unwrap();
+    db.put_sparse_vector("notes", ids[2], sparse(&[(9, 3.0)]))
+        .unwrap();
+    assert!(db
+        .put_sparse_vector("notes", 999, sparse(&[(1, 1.0)]))
+        .is_err());
+
+    let query = sparse(&[(5, 1.0)]);
+    let hits = db.search_sparse("notes", &query, 10, &[]).unwrap();
+    let order: Vec<u64> = hits.iter().map(|hit| hit.row_id).collect();
+    assert_eq!(order, vec![ids[0], ids[1]]);
+    assert_eq!(hits[0].distance, -2.0);
+
+    // Dense favors row 2 ("bbbb" has length 4), sparse favors row 1; both beat row 3.
+    let fused = db
+        .search_hybrid_sparse("notes", &[4.0], &query, 3, DistanceMetric::L2, &[])
+        .unwrap();
+    assert_eq!(fused.len(), 3);
+    assert_eq!(fused[2].row_id, ids[2]);
+
+    db.delete_row("notes", ids[1]).unwrap();
+    db.checkpoint().unwrap();
+    drop(db);
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert_eq!(db.get_sparse_vector("notes", ids[1]).unwrap(), None);
+    assert_eq!(
+        db.get_sparse_vector("notes", ids[2]).unwrap(),
+        Some(sparse(&[(9, 3.0)]))
+    );
+    let hits = db.search_sparse("notes", &query, 10, &[]).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].row_id, ids[0]);
+}
+
+#[test]
+fn custom_metrics_are_resolved_by_name_and_as_table_default() {
+    struct PairEmbedder;
+    impl Embedder for PairEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            Ok(vec![input.len() as f32, 1.0])
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let weighted = |query: &[f32], vector: &[f32]| -> f32 {
+        // Only the second component matters.
+        (query[1] - vector[1]).abs() * 10.0 + (query[0] - vector[0]).abs() * 0.001
+    };
+    db.register_metric("weighted", Arc::new(weighted)).unwrap();
+    assert!(db.register_metric("L2", Arc::new(weighted)).is_err());
+
+    assert!(db
+        .create_table(
+            "bad",
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(
+                EmbeddingSpec::new(vec!["title"])
+                    .with_metric(DistanceMetric::L2)
+                    .with_custom_metric("weighted"),
+            ),
+        )
+        .is_err());
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_custom_metric("weighted")),
+    )
+    .unwrap();
+
+    let mut ids = Vec::new();
+    for title in ["a", "abc", "abcdef"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &PairEmbedder).unwrap();
+
+    let options = SearchOptions::default();
+    let hits = db
+        .search_knn_named("notes", &[6.0, 1.0], 3, "weighted", &[], &options)
+        .unwrap();
+    assert_eq!(hits[0].row_id, ids[2]);
+    assert!(hits[0].distance.abs() < 1e-6);
+
+    // Built-in searches conflict with the table's custom default unless overridden.
+    let err = db
+        .search_knn("notes", &[6.0, 1.0], 1, DistanceMetric::L2)
+        .unwrap_err();
+    assert!(err.to_string().contains("'weighted'"));
+    let hits = db.search_knn("notes", &[6.0, 1.0], 1, None).unwrap();
+    assert_eq!(hits[0].row_id, ids[2]);
+    let err = db
+        .search_knn_batch("notes", &[vec![6.0, 1.0]], 1, None)
+        .unwrap_err();
+    assert!(err.to_string().contains("search_knn_named"), "{err}");
+    let overridden = SearchOptions {
+        allow_metric_mismatch: true,
+        ..SearchOptions::default()
+    };
+    let hits = db
+        .search_knn_named("notes", &[3.0, 1.0], 1, "l2", &[], &overridden)
+        .unwrap();
+    assert_eq!(hits[0].row_id, ids[1]);
+
+    let similar = db.search_similar("notes", ids[0], 2, true).unwrap();
+    assert_eq!(similar.len(), 2);
+    assert!(db
+        .search_knn_named("notes", &[1.0, 1.0], 1, "missing", &[], &options)
+        .is_err());
+    drop(db);
+
+    // Registrations are per process: the default is unresolved until registered again.
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let err = db.search_similar("notes", ids[0], 2, true).unwrap_err();
+    assert!(err.to_string().contains("not registered"));
+}
+
+#[test]
+fn batch_search_matches_single_query_search() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(
+        Config::new(dir.path().to_path_buf()).with_scoring_backend(ScoringBackend::Gpu),
+    )
+    .unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+    for title in ["a", "abc", "abcdef", "abcdefghij"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    let queries = vec![vec![2.0], vec![9.0], vec![5.0]];
+    let batch = db
+        .search_knn_batch("notes", &queries, 2, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(batch.len(), queries.len());
+    for (query, hits) in queries.iter().zip(&batch) {
+        let single = db
+            .search_knn("notes", query, 2, DistanceMetric::L2)
+            .unwrap();
+        let expected: Vec<u64> = single.iter().map(|hit| hit.row_id).collect();
+        let actual: Vec<u64> = hits.iter().map(|hit| hit.row_id).collect();
+        assert_eq!(actual, expected);
+    }
+
+    assert!(db
+        .search_knn_batch("notes", &[vec![1.0, 2.0]], 2, DistanceMetric::L2)
+        .is_err());
+    assert!(db
+        .search_knn_batch("notes", &[], 2, DistanceMetric::L2)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn quantized_search_rescores_with_exact_vectors() {
+    struct TableEmbedder;
+    impl Embedder for TableEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            // 2049 is not representable in f16 and rounds down to 2048.
+            Ok(vec![if input == "a" { 2049.0 } else { 2050.0 }])
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_vector_encoding(VectorEncoding::F16)),
+    )
+    .unwrap();
+    let mut ids = Vec::new();
+    for title in ["a", "b"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &TableEmbedder).unwrap();
+
+    let hits = db
+        .search_knn("notes", &[2049.25], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, ids[0]);
+    assert!((hits[0].distance - 0.0625).abs() < 1e-6);
+
+    db.checkpoint().unwrap();
+    drop(db);
+
+    // The checkpoint snapshot keeps exact vectors, so re-scoring still works after reopen.
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let hits = db
+        .search_knn("notes", &[2049.25], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, ids[0]);
+    drop(db);
+
+    let db =
+        EmbedDb::open(Config::new(dir.path().to_path_buf()).with_rescore_oversample(0)).unwrap();
+    let hits = db
+        .search_knn("notes", &[2049.25], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, ids[1]);
+}
+
+#[test]
+fn index_status_and_explain_report_flat_scan() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"]).with_vector_encoding(VectorEncoding::F16)),
+    )
+    .unwrap();
+    for title in ["a", "bb", "ccc"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    db.delete_row("notes", 3).unwrap();
+
+    let status = db.index_status("notes").unwrap();
+    assert_eq!(status.kind, IndexKind::Flat);
+    assert_eq!(status.state, IndexState::Ready);
+    assert_eq!((status.indexed_vectors, status.total_vectors), (2, 2));
+    assert_eq!(db.table_stats("notes").unwrap().index.total_vectors, 2);
+
+    let explain = db
+        .explain_search(
+            "notes",
+            &[1.0],
+            3,
+            DistanceMetric::L2,
+            &[],
+            &SearchOptions::default(),
+        )
+        .unwrap();
+    assert!(!explain.degraded);
+    assert_eq!(explain.candidates, 2);
+    assert_eq!(explain.rescore_candidates, Some(12));
+    assert!(db
+        .explain_search(
+            "notes",
+            &[1.0, 2.0],
+            3,
+            DistanceMetric::L2,
+            &[],
+            &SearchOptions::default(),
+        )
+        .is_err());
+}
+
+#[test]
+fn hnsw_index_matches_exact_search_and_survives_reopen() {
+    struct PointEmbedder;
+
+    impl Embedder for PointEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            input
+                .split(',')
+                .map(|part| part.parse::<f32>().map_err(Into::into))
+                .collect()
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let spec = EmbeddingSpec::new(vec!["point"])
+        .with_metric(DistanceMetric::L2)
+        .with_index(IndexSpec::hnsw());
+    {
+        let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+        db.create_table(
+            "points",
+            TableSchema::new(vec![
+                Column::new("point", DataType::String, false),
+                Column::new("parity", DataType::Int, false),
+            ]),
+            Some(spec),
+        )
+        .unwrap();
+        for i in 0..400 {
+            let mut fields = BTreeMap::new();
+            let point = format!("{},{}", i % 20, i / 20);
+            fields.insert("point".to_string(), Value::String(point));
+            fields.insert("parity".to_string(), Value::Int(i % 2));
+            db.insert_row("points", fields).unwrap();
+        }
+        db.process_pending_jobs("points", &PointEmbedder).unwrap();
+        db.delete_row("points", 1).unwrap();
+    }
+
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let status = db.index_status("points").unwrap();
+    assert_eq!(status.kind, IndexKind::Hnsw);
+    assert_eq!(status.indexed_vectors, 399);
+
+    // Row ids start at 1, so row `id` sits at ((id - 1) % 20, (id - 1) / 20).
+    let exact = |query: [f32; 2], keep: &dyn Fn(u64) -> bool| -> Vec<u64> {
+        let mut rows: Vec<(u64, f32)> = (2..=400u64)
+            .filter(|id| keep(*id))
+            .map(|id| {
+                let (x, y) = (((id - 1) % 20) as f32, ((id - 1) / 20) as f32);
+                (id, (x - query[0]).powi(2) + (y - query[1]).powi(2))
+            })
+            .collect();
+        rows.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        rows.into_iter().take(3).map(|row| row.0).collect()
+    };
+
+    let query = [0.2, 0.1];
+    let hits = db
+        .search_knn("points", &query, 3, DistanceMetric::L2)
+        .unwrap();
+    let ids: Vec<u64> = hits.iter().map(|hit| hit.row_id).collect();
+    assert_eq!(ids, exact(query, &|_| true));
+
+    let query = [7.4, 12.6];
+    let filters = vec![FilterCondition {
+        column: "parity".to_string(),
+        op: FilterOp::Eq,
+        value: Value::Int(1),
+    }];
+    let hits = db
+        .search_knn_filtered("points", &query, 3, DistanceMetric::L2, &filters)
+        .unwrap();
+    let ids: Vec<u64> = hits.iter().map(|hit| hit.row_id).collect();
+    assert_eq!(ids, exact(query, &|id| (id - 1) % 2 == 1));
+
+    let nearest: BTreeSet<u64> = exact(query, &|_| true).into_iter().collect();
+    let options = SearchOptions {
+        exclude_ids: nearest.clone(),
+        ..SearchOptions::default()
+    };
+    let hits = db
+        .search_knn_with_options("points", &query, 3, DistanceMetric::L2, &[], &options)
+        .unwrap();
+    let ids: Vec<u64> = hits.iter().map(|hit| hit.row_id).collect();
+    assert_eq!(ids, exact(query, &|id| !nearest.contains(&id)));
+    let options = SearchOptions {
+        include_ids: Some((300..=400).collect()),
+        ..SearchOptions::default()
+    };
+    let hits = db
+        .search_knn_with_options("points", &query, 3, DistanceMetric::L2, &[], &options)
+        .unwrap();
+    let ids: Vec<u64> = hits.iter().map(|hit| hit.row_id).collect();
+    assert_eq!(ids, exact(query, &|id| id >= 300));
+    let explain = db
+        .explain_search("points", &query, 3, DistanceMetric::L2, &[], &options)
+        .unwrap();
+    assert_eq!(explain.index, IndexKind::Flat);
+    assert!(!explain.degraded);
+    assert_eq!(explain.candidates, 101);
+
+    let explain = db
+        .explain_search(
+            "points",
+            &query,
+            3,
+            DistanceMetric::L2,
+            &[],
+            &SearchOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(explain.index, IndexKind::Hnsw);
+    assert!(!explain.degraded);
+    assert_eq!(explain.candidates, 64);
+
+    // The graph is built for L2, so other metrics fall back to the exact scan.
+    let options = SearchOptions {
+        allow_metric_mismatch: true,
+        ..SearchOptions::default()
+    };
+    let explain = db
+        .explain_search("points", &query, 3, DistanceMetric::Cosine, &[], &options)
+        .unwrap();
+    assert_eq!(explain.index, IndexKind::Flat);
+    assert!(explain.degraded);
+
+    assert!(EmbeddingSpec::new(vec!["point"])
+        .with_custom_metric("weighted")
+        .with_index(IndexSpec::hnsw())
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn hnsw_index_file_is_loaded_when_current_and_rebuilt_when_stale() {
+    let dir = tempdir().unwrap();
+    let config = || Config::new(dir.path().to_path_buf());
+    let table_dir = sst::table_dir(dir.path(), "notes");
+    let insert = |db: &EmbedDb, title: &str| {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("notes", fields).unwrap();
+        db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    };
+    // Replacing an embedding leaves a tombstone in a live graph; a rebuilt graph has none.
+    let tombstones = |db: &EmbedDb| {
+        let inner = db.inner.read().unwrap();
+        inner.state.tables["notes"]
+            .hnsw
+            .as_ref()
+            .unwrap()
+            .tombstones()
+    };
+
+    {
+        let db = EmbedDb::open(config()).unwrap();
+        db.create_table(
+            "notes",
+            TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+            Some(
+                EmbeddingSpec::new(vec!["title"])
+                    .with_metric(DistanceMetric::L2)
+                    .with_index(IndexSpec::hnsw()),
+            ),
+        )
+        .unwrap();
+        for title in ["a", "bb", "ccc"] {
+            insert(&db, title);
+        }
+        db.put_embedding("notes", 1, vec![4.0]).unwrap();
+        assert_eq!(tombstones(&db), 1);
+        db.flush_table("notes").unwrap();
+    }
+
+    {
+        let db = EmbedDb::open(config()).unwrap();
+        assert_eq!(tombstones(&db), 1);
+        let hits = db.search_knn("notes", &[4.0], 1, None).unwrap();
+        assert_eq!(hits[0].row_id, 1);
+        // Stored after the file was written, so the file no longer matches the WAL.
+        insert(&db, "eeeee");
+    }
+
+    let db = EmbedDb::open(config()).unwrap();
+    assert_eq!(tombstones(&db), 0);
+    let hits = db.search_knn("notes", &[5.0], 1, None).unwrap();
+    assert_eq!(hits[0].row_id, 4);
+    // The rebuilt graph is written back for the next open.
+    let file = indexfile::read_index(&table_dir).unwrap().unwrap();
+    assert_eq!(file.generation, db.inner.read().unwrap().lsn);
+
+    // Checkpoints persist the graph along with the snapshot.
+    db.put_embedding("notes", 2, vec![9.0]).unwrap();
+    db.checkpoint().unwrap();
+    drop(db);
+    let db = EmbedDb::open(config()).unwrap();
+    assert_eq!(tombstones(&db), 1);
+    assert_eq!(db.index_status("notes").unwrap().indexed_vectors, 4);
+
+    // A corrupt file is ignored and replaced.
+    drop(db);
+    fs::write(indexfile::index_dir(&table_dir).join("hnsw.bin"), b"junk").unwrap();
+    let db = EmbedDb::open(config()).unwrap();
+    assert_eq!(tombstones(&db), 0);
+    let hits = db.search_knn("notes", &[9.0], 1, None).unwrap();
+    assert_eq!(hits[0].row_id, 2);
+    assert!(indexfile::read_index(&table_dir).unwrap().is_some());
+}
+
+#[test]
+fn pending_jobs_are_embedded_in_batches() {
+    struct BatchEmbedder {
+        calls: Mutex<Vec<usize>>,
+    }
+
+    impl Embedder for BatchEmbedder {
+        fn embed(&self, input: &str) -> Result<Vec<f32>> {
+            Ok(vec![input.len() as f32])
+        }
+
+        fn embed_batch(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+            self.calls.lock().unwrap().push(inputs.len());
+            if inputs.iter().any(|input| input == "bad") {
+                return Err(anyhow!("provider rejected batch"));
+            }
+            inputs.iter().map(|input| self.embed(input)).collect()
+        }
+
+        fn batch_size(&self) -> usize {
+            2
+        }
+    }
+
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for title in ["a", "bb", "ccc", "bad", "e"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+
+    let embedder = BatchEmbedder {
+        calls: Mutex::new(Vec::new()),
+    };
+    assert_eq!(db.process_pending_jobs("notes", &embedder).unwrap(), 5);
+    assert_eq!(*embedder.calls.lock().unwrap(), vec![2, 2, 1]);
+
+    // The failing batch holds rows 3 and 4; both are retried later with the batch error.
+    let jobs = db.list_embedding_jobs("notes").unwrap();
+    let pending: Vec<u64> = jobs
+        .iter()
+        .filter(|job| job.status == EmbeddingStatus::Pending)
+        .map(|job| job.row_id)
+        .collect();
+    assert_eq!(pending, vec![3, 4]);
+    assert_eq!(
+        jobs[2].last_error.as_deref(),
+        Some("provider rejected batch")
+    );
+    assert_eq!(jobs[4].status, EmbeddingStatus::Ready);
+}
+
+#[test]
+fn process_all_pending_takes_turns_between_tables() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let schema = || TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    let title = |text: &str| BTreeMap::from([("title".to_string(), Value::String(text.into()))]);
+    for (table, rows) in [("big", 5), ("small", 2)] {
+        db.create_table(table, schema(), Some(EmbeddingSpec::new(vec!["title"])))
+            .unwrap();
+        for i in 0..rows {
+            db.insert_row(table, title(&format!("{table} {i}")))
+                .unwrap();
+        }
+    }
+    db.create_table("plain", schema(), None).unwrap();
+    db.insert_row("plain", title("no embedding")).unwrap();
+
+    // One job per table per turn: big, small, big, small.
+    assert_eq!(db.process_all_pending(&DummyEmbedder, 4).unwrap(), 4);
+    let pending = db
+        .list_embedding_jobs_all(Some(EmbeddingStatus::Pending))
+        .unwrap();
+    assert_eq!(
+        pending
+            .iter()
+            .map(|job| (job.table.as_str(), job.row_id))
+            .collect::<Vec<_>>(),
+        [("big", 3), ("big", 4), ("big", 5)]
+    );
+
+    assert_eq!(
+        db.process_all_pending(&DummyEmbedder, usize::MAX).unwrap(),
+        3
+    );
+    assert_eq!(
+        db.process_all_pending(&DummyEmbedder, usize::MAX).unwrap(),
+        0
+    );
+    let all = db.list_embedding_jobs_all(None).unwrap();
+    assert_eq!(all.len(), 7);
+    assert!(all.iter().all(|job| job.status == EmbeddingStatus::Ready));
+    assert_eq!(all[0].table, "big");
+}
+
+#[test]
+fn background_worker_drains_jobs_and_stops_on_drop() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf())
+        .with_background_embedding(Arc::new(DummyEmbedder), Duration::from_millis(5));
+    let db = EmbedDb::open(config.clone()).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for title in ["a", "bb"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    // Status is published after the pass, so wait for it rather than for the jobs themselves.
+    let status = loop {
+        let status = db
+            .db_stats()
+            .unwrap()
+            .embedding_worker
+            .expect("worker status");
+        if status.jobs_processed >= 2 {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "worker did not drain jobs");
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_ready, 2);
+    assert_eq!(status.interval_ms, 5);
+    assert!(status.passes >= 1);
+    assert_eq!(status.jobs_processed, 2);
+    assert!(status.last_error.is_none());
+
+    // Dropping joins the worker and releases the directory lock.
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert!(db.db_stats().unwrap().embedding_worker.is_none());
+}
+
+#[test]
+fn background_compaction_compacts_tables_past_their_l0_trigger() {
+    let dir = tempdir().unwrap();
+    let data_dir = dir.path().to_path_buf();
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+    for table in ["notes", "logs"] {
+        let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+        db.create_table(table, schema, None).unwrap();
+        for title in ["a", "b"] {
+            let fields = BTreeMap::from([("title".to_string(), Value::String(title.into()))]);
+            db.insert_row(table, fields).unwrap();
+            db.flush_table(table).unwrap();
+        }
+    }
+    drop(db);
+
+    // Only `notes` has a trigger, so only it is compacted.
+    let notes_policy = CompactionPolicy {
+        l0_trigger_files: 2,
+        ..CompactionPolicy::default()
+    };
+    let db = EmbedDb::open(
+        Config::new(data_dir.clone())
+            .with_table_compaction_policy("notes", notes_policy)
+            .with_background_compaction(Duration::from_millis(5)),
+    )
+    .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        let status = db
+            .db_stats()
+            .unwrap()
+            .compaction_worker
+            .expect("worker status");
+        if status.passes >= 2 {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "worker did not compact");
+        std::thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(status.interval_ms, 5);
+    assert_eq!(status.compactions, 1);
+    assert!(status.last_error.is_none());
+    let notes = db.table_stats("notes").unwrap();
+    assert_eq!(notes.sst_files_per_level, vec![0, 1]);
+    assert!(notes.last_compacted_at_ms.is_some());
+    let logs = db.table_stats("logs").unwrap();
+    assert_eq!(logs.sst_files_per_level, vec![2]);
+    assert_eq!(logs.last_compacted_at_ms, None);
+    drop(db);
+
+    // The byte trigger, checked directly.
+    let db = EmbedDb::open(Config::new(data_dir.clone())).unwrap();
+    assert!(db.db_stats().unwrap().compaction_worker.is_none());
+    assert!(db.compact_table_if_due("logs").unwrap().is_none());
+    drop(db);
+    let db = EmbedDb::open(
+        Config::new(data_dir).with_compaction_policy(CompactionPolicy {
+            l0_trigger_bytes: 1,
+            ..CompactionPolicy::default()
+        }),
+    )
+    .unwrap();
+    let stats = db.compact_table_if_due("logs").unwrap().expect("compacted");
+    assert_eq!(stats.files_per_level, vec![0, 1]);
+    assert!(db.compact_table_if_due("logs").unwrap().is_none());
+    assert_eq!(db.table_stats("logs").unwrap().total_rows, 2);
+}
+
+#[test]
+fn checkpoint_keeps_vectors_in_segments_instead_of_the_wal() {
+    let dir = tempdir().unwrap();
+    let config = Config::new(dir.path().to_path_buf());
+    let db = EmbedDb::open(config.clone()).unwrap();
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    for title in ["a", "bb", "ccc"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        db.insert_row("notes", fields).unwrap();
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    db.flush_table("notes").unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().vector_segments, 1);
+    db.checkpoint().unwrap();
+    let wal = Wal::replay_path(&dir.path().join("wal.log")).unwrap();
+    assert!(!wal
+        .iter()
+        .any(|record| matches!(record, WalRecord::StoreEmbedding { .. })));
+
+    // Removals after the checkpoint reach a new segment on flush and survive reopening.
+    db.delete_row("notes", 2).unwrap();
+    db.flush_table("notes").unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().vector_segments, 2);
+    drop(db);
+
+    let db = EmbedDb::open(config.clone()).unwrap();
+    let hits = db
+        .search_knn("notes", &[3.0], 5, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(
+        hits.iter().map(|hit| hit.row_id).collect::<Vec<_>>(),
+        vec![3, 1]
+    );
+    assert_eq!(db.table_stats("notes").unwrap().embeddings_ready, 2);
+
+    db.compact_table("notes").unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().vector_segments, 1);
+    drop(db);
+    let db = EmbedDb::open(config).unwrap();
+    let hits = db
+        .search_knn("notes", &[3.0], 1, DistanceMetric::L2)
+        .unwrap();
+    assert_eq!(hits[0].row_id, 3);
+    assert_eq!(hits[0].distance, 0.0);
+}
+
+#[test]
+fn searches_run_concurrently_under_a_shared_lock() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap());
+    let schema = TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+    db.create_table("notes", schema, Some(EmbeddingSpec::new(vec!["title"])))
+        .unwrap();
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("hello".to_string()));
+    db.insert_row("notes", fields).unwrap();
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+
+    // Each search blocks inside the metric until both are in flight, which can only happen if
+    // neither excludes the other; a serialized search would time out alone.
+    let rendezvous = Arc::new((Mutex::new(0usize), std::sync::Condvar::new()));
+    let metric_rendezvous = rendezvous.clone();
+    let blocking = move |query: &[f32], vector: &[f32]| -> f32 {
+        let (arrived, cvar) = &*metric_rendezvous;
+        let mut arrived = arrived.lock().unwrap();
+        *arrived += 1;
+        cvar.notify_all();
+        let (arrived, _) = cvar
+            .wait_timeout_while(arrived, Duration::from_secs(5), |arrived| *arrived < 2)
+            .unwrap();
+        if *arrived < 2 {
+            return f32::INFINITY;
+        }
+        (query[0] - vector[0]).abs()
+    };
+    db.register_metric("blocking", Arc::new(blocking)).unwrap();
+
+    let searches: Vec<_> = (0..2)
+        .map(|_| {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                db.search_knn_named(
+                    "notes",
+                    &[5.0],
+                    1,
+                    "blocking",
+                    &[],
+                    &SearchOptions::default(),
+                )
+                .unwrap()
+            })
+        })
+        .collect();
+    for search in searches {
+        let hits = search.join().unwrap();
+        assert_eq!(hits[0].distance, 0.0, "searches were serialized");
+    }
+
+    // Writes still take the lock exclusively and are visible to the next read.
+    db.delete_row("notes", 1).unwrap();
+    assert!(db.get_row("notes", 1).unwrap().is_none());
+}
+
+#[test]
+fn manager_keeps_namespaces_isolated_across_reopens() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("namespaces");
+    let template = Config::new(PathBuf::from("ignored"));
+    let schema = || TableSchema::new(vec![Column::new("title", DataType::String, false)]);
+
+    let manager = EmbedDbManager::new(root.clone(), template.clone());
+    assert!(manager.list_namespaces().unwrap().is_empty());
+    let acme = manager.create_namespace("acme").unwrap();
+    let globex = manager.create_namespace("globex").unwrap();
+    for db in [&acme, &globex] {
+        db.create_table("notes", schema(), None).unwrap();
+    }
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("only acme".to_string()));
+    let row_id = acme.insert_row("notes", fields).unwrap();
+    assert!(globex.get_row("notes", row_id).unwrap().is_none());
+    assert!(Arc::ptr_eq(&acme, &manager.namespace("acme").unwrap()));
+
+    let err = manager.create_namespace("acme").err().unwrap();
+    assert!(err.to_string().contains("already exists"), "{err}");
+    for name in ["", "../escape", "a/b", "dot.dot"] {
+        assert!(manager.namespace(name).is_err(), "{name:?}");
+    }
+    let err = manager.namespace("initech").err().unwrap();
+    assert!(err.to_string().contains("not found"), "{err}");
+    manager.close_all().unwrap();
+    assert!(acme.list_tables().is_err());
+    drop((acme, globex, manager));
+
+    let manager = EmbedDbManager::new(root, template);
+    assert_eq!(manager.list_namespaces().unwrap(), vec!["acme", "globex"]);
+    let acme = manager.namespace("acme").unwrap();
+    assert!(acme.get_row("notes", row_id).unwrap().is_some());
+}
+
+#[test]
+fn close_flushes_checkpoints_and_releases_the_lock() {
+    let dir = tempdir().unwrap();
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    db.create_table(
+        "notes",
+        TableSchema::new(vec![Column::new("title", DataType::String, false)]),
+        Some(EmbeddingSpec::new(vec!["title"])),
+    )
+    .unwrap();
+    let mut ids = Vec::new();
+    for title in ["a", "bb", "ccc"] {
+        let mut fields = BTreeMap::new();
+        fields.insert("title".to_string(), Value::String(title.to_string()));
+        ids.push(db.insert_row("notes", fields).unwrap());
+    }
+    db.process_pending_jobs("notes", &DummyEmbedder).unwrap();
+    let wal_before = db.db_stats().unwrap().wal_bytes;
+
+    db.close().unwrap();
+    db.close().unwrap();
+    let err = db.get_row("notes", ids[0]).unwrap_err();
+    assert_eq!(err.to_string(), "database is closed");
+    assert!(db.list_tables().is_err());
+
+    // The lock is gone while the closed handle is still alive, and nothing is left to replay.
+    let reopened = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    let stats = reopened.table_stats("notes").unwrap();
+    assert_eq!(stats.rows_mem, 0);
+    assert_eq!(stats.embeddings_ready, 3);
+    assert!(reopened.db_stats().unwrap().wal_bytes < wal_before);
+    assert!(reopened.get_row("notes", ids[2]).unwrap().is_some());
+    drop(db);
+
+    // With close_on_drop, dropping the handle does the same.
+    let mut fields = BTreeMap::new();
+    fields.insert("title".to_string(), Value::String("dddd".to_string()));
+    drop(reopened);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf()).with_close_on_drop(true)).unwrap();
+    let row_id = db.insert_row("notes", fields).unwrap();
+    drop(db);
+    let db = EmbedDb::open(Config::new(dir.path().to_path_buf())).unwrap();
+    assert_eq!(db.table_stats("notes").unwrap().rows_mem, 0);
+    assert!(db.get_row("notes", row_id).unwrap().is_some());
+}
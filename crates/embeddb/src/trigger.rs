@@ -0,0 +1,73 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::schema::RowData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RowChangeKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A committed row change. `old` is unset for inserts and `new` is unset for deletes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowChange {
+    pub table: String,
+    pub row_id: u64,
+    pub kind: RowChangeKind,
+    pub old: Option<RowData>,
+    pub new: Option<RowData>,
+}
+
+/// Callback invoked after a row change is durable in the WAL.
+///
+/// Triggers run on the writing thread after the database lock is released, so they may call back
+/// into `EmbedDb`. They cannot veto the write; a slow trigger slows down the writer.
+pub trait RowTrigger: Send + Sync {
+    fn on_row_change(&self, change: &RowChange);
+}
+
+impl<F> RowTrigger for F
+where
+    F: Fn(&RowChange) + Send + Sync,
+{
+    fn on_row_change(&self, change: &RowChange) {
+        self(change)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TriggerSet {
+    triggers: RwLock<Vec<Arc<dyn RowTrigger>>>,
+}
+
+impl TriggerSet {
+    pub(crate) fn register(&self, trigger: Arc<dyn RowTrigger>) {
+        self.triggers
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(trigger);
+    }
+
+    pub(crate) fn fire(&self, change: RowChange) {
+        // Clone the list so a trigger can register another trigger without deadlocking.
+        let triggers = self
+            .triggers
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        for trigger in triggers {
+            trigger.on_row_change(&change);
+        }
+    }
+}
+
+impl fmt::Debug for TriggerSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.triggers.read().map(|t| t.len()).unwrap_or(0);
+        f.debug_struct("TriggerSet").field("count", &count).finish()
+    }
+}
@@ -1,3 +1,10 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use anyhow::{anyhow, Result};
+use half::f16;
+use serde::{Deserialize, Serialize};
+
 use crate::DistanceMetric;
 
 #[derive(Debug, Clone)]
@@ -6,31 +13,200 @@ pub struct SearchResult {
     pub distance: f32,
 }
 
+impl PartialEq for SearchResult {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SearchResult {}
+
+impl PartialOrd for SearchResult {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Nearer first; ties go to the lower row id so results don't depend on hash map order.
+impl Ord for SearchResult {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.row_id.cmp(&other.row_id))
+    }
+}
+
+/// The `k` nearest results seen so far, kept in a bounded max-heap so a scan over `n` rows costs
+/// O(n log k) time and O(k) memory rather than sorting all `n` distances.
+#[derive(Debug)]
+pub struct TopK {
+    k: usize,
+    heap: BinaryHeap<SearchResult>,
+}
+
+impl TopK {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k.saturating_add(1).min(1024)),
+        }
+    }
+
+    /// Whether `push` would keep this result. Scans can call it before doing more expensive work
+    /// (loading a row to check filters), and an index can stop exploring once it returns false for
+    /// every remaining candidate.
+    pub fn accepts(&self, row_id: u64, distance: f32) -> bool {
+        if self.heap.len() < self.k {
+            return true;
+        }
+        self.heap
+            .peek()
+            .is_some_and(|worst| SearchResult { row_id, distance }.cmp(worst) == Ordering::Less)
+    }
+
+    pub fn push(&mut self, row_id: u64, distance: f32) {
+        if !self.accepts(row_id, distance) {
+            return;
+        }
+        if self.heap.len() == self.k {
+            self.heap.pop();
+        }
+        self.heap.push(SearchResult { row_id, distance });
+    }
+
+    /// The kept results, nearest first.
+    pub fn into_sorted(self) -> Vec<SearchResult> {
+        self.heap.into_sorted_vec()
+    }
+}
+
+/// In-memory representation for a table's resident embeddings.
+///
+/// `F16` halves memory use at the cost of ~3 significant decimal digits per component. `Int8`
+/// quarters it by rounding each component to one of 255 steps of a per-vector scale (the largest
+/// magnitude / 127), which costs some recall on vectors with a few dominant components. Distances
+/// are still accumulated in f32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VectorEncoding {
+    #[default]
+    F32,
+    F16,
+    Int8,
+}
+
+#[derive(Debug, Clone)]
+pub enum StoredVector {
+    F32(Vec<f32>),
+    F16(Vec<f16>),
+    /// Component `i` is `values[i] as f32 * scale`.
+    Int8 {
+        values: Vec<i8>,
+        scale: f32,
+    },
+}
+
+impl StoredVector {
+    pub fn encode(vector: Vec<f32>, encoding: VectorEncoding) -> Self {
+        match encoding {
+            VectorEncoding::F32 => StoredVector::F32(vector),
+            VectorEncoding::F16 => {
+                StoredVector::F16(vector.into_iter().map(f16::from_f32).collect())
+            }
+            VectorEncoding::Int8 => {
+                let max = vector.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+                let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+                let values = vector
+                    .iter()
+                    .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8)
+                    .collect();
+                StoredVector::Int8 { values, scale }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            StoredVector::F32(v) => v.len(),
+            StoredVector::F16(v) => v.len(),
+            StoredVector::Int8 { values, .. } => values.len(),
+        }
+    }
+
+    /// Bytes taken by the components, not counting the allocation's bookkeeping.
+    pub fn heap_bytes(&self) -> usize {
+        match self {
+            StoredVector::F32(v) => v.len() * 4,
+            StoredVector::F16(v) => v.len() * 2,
+            StoredVector::Int8 { values, .. } => values.len() + 4,
+        }
+    }
+
+    pub fn to_f32(&self) -> Vec<f32> {
+        match self {
+            StoredVector::F32(v) => v.clone(),
+            StoredVector::F16(v) => v.iter().map(|x| x.to_f32()).collect(),
+            StoredVector::Int8 { values, scale } => {
+                values.iter().map(|x| f32::from(*x) * scale).collect()
+            }
+        }
+    }
+}
+
 pub fn distance(query: &[f32], vector: &[f32], metric: DistanceMetric) -> f32 {
     if query.len() != vector.len() || query.is_empty() {
         return f32::INFINITY;
     }
 
     match metric {
-        DistanceMetric::L2 => l2_distance(query, vector),
-        DistanceMetric::Cosine => cosine_distance(query, vector),
+        DistanceMetric::L2 => l2_distance(query, vector.iter().copied()),
+        DistanceMetric::Cosine => cosine_distance(query, vector.iter().copied()),
+    }
+}
+
+pub fn distance_stored(query: &[f32], vector: &StoredVector, metric: DistanceMetric) -> f32 {
+    match vector {
+        StoredVector::F32(v) => distance(query, v, metric),
+        StoredVector::F16(v) => {
+            if query.len() != v.len() || query.is_empty() {
+                return f32::INFINITY;
+            }
+            let values = v.iter().map(|x| x.to_f32());
+            match metric {
+                DistanceMetric::L2 => l2_distance(query, values),
+                DistanceMetric::Cosine => cosine_distance(query, values),
+            }
+        }
+        StoredVector::Int8 { values, scale } => {
+            if query.len() != values.len() || query.is_empty() {
+                return f32::INFINITY;
+            }
+            match metric {
+                DistanceMetric::L2 => {
+                    l2_distance(query, values.iter().map(|x| f32::from(*x) * scale))
+                }
+                // Cosine distance ignores the scale, so it is applied to neither side.
+                DistanceMetric::Cosine => {
+                    cosine_distance(query, values.iter().map(|x| f32::from(*x)))
+                }
+            }
+        }
     }
 }
 
-fn l2_distance(a: &[f32], b: &[f32]) -> f32 {
+fn l2_distance(a: &[f32], b: impl Iterator<Item = f32>) -> f32 {
     let mut sum = 0.0f32;
-    for (x, y) in a.iter().zip(b.iter()) {
+    for (x, y) in a.iter().zip(b) {
         let diff = x - y;
         sum += diff * diff;
     }
     sum
 }
 
-fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+fn cosine_distance(a: &[f32], b: impl Iterator<Item = f32>) -> f32 {
     let mut dot = 0.0f32;
     let mut norm_a = 0.0f32;
     let mut norm_b = 0.0f32;
-    for (x, y) in a.iter().zip(b.iter()) {
+    for (x, y) in a.iter().zip(b) {
         dot += x * y;
         norm_a += x * x;
         norm_b += y * y;
@@ -41,3 +217,312 @@ fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
     let denom = norm_a.sqrt() * norm_b.sqrt();
     1.0 - (dot / denom)
 }
+
+/// Maps a distance from `distance` to a similarity in `0..=1`, where 1 is an exact match. Cosine
+/// distances lie in `0..=2` and map linearly (`1 - d / 2`); squared L2 distances are unbounded
+/// and map through `inverse_distance`.
+pub fn similarity(distance: f32, metric: DistanceMetric) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => (1.0 - distance / 2.0).clamp(0.0, 1.0),
+        DistanceMetric::L2 => inverse_distance(distance),
+    }
+}
+
+/// `1 / (1 + d)`: a similarity in `0..=1` for a non-negative distance with no upper bound, such
+/// as squared L2 or a custom metric's.
+pub fn inverse_distance(distance: f32) -> f32 {
+    if distance.is_nan() {
+        return 0.0;
+    }
+    1.0 / (1.0 + distance.max(0.0))
+}
+
+/// Scales `vector` to unit length in place and returns its original L2 norm. Zero vectors are
+/// left unchanged.
+pub fn normalize(vector: &mut [f32]) -> f32 {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in vector.iter_mut() {
+            *x /= norm;
+        }
+    }
+    norm
+}
+
+/// Distance between a query and a stored unit vector whose original norm is `norm`.
+/// `query_unit` must be `query` normalized with `normalize`.
+pub fn distance_to_unit(
+    query: &[f32],
+    query_unit: &[f32],
+    unit: &StoredVector,
+    norm: f32,
+    metric: DistanceMetric,
+) -> f32 {
+    if query.len() != unit.len() || query.is_empty() {
+        return f32::INFINITY;
+    }
+
+    match unit {
+        StoredVector::F32(v) => unit_kernel(query, query_unit, v.iter().copied(), norm, metric),
+        StoredVector::F16(v) => unit_kernel(
+            query,
+            query_unit,
+            v.iter().map(|x| x.to_f32()),
+            norm,
+            metric,
+        ),
+        StoredVector::Int8 { values, scale } => unit_kernel(
+            query,
+            query_unit,
+            values.iter().map(|x| f32::from(*x) * scale),
+            norm,
+            metric,
+        ),
+    }
+}
+
+fn unit_kernel(
+    query: &[f32],
+    query_unit: &[f32],
+    unit: impl Iterator<Item = f32>,
+    norm: f32,
+    metric: DistanceMetric,
+) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => {
+            if norm == 0.0 {
+                return 1.0;
+            }
+            let dot: f32 = query_unit.iter().zip(unit).map(|(x, y)| x * y).sum();
+            1.0 - dot
+        }
+        DistanceMetric::L2 => {
+            let mut sum = 0.0f32;
+            for (x, y) in query.iter().zip(unit) {
+                let diff = x - y * norm;
+                sum += diff * diff;
+            }
+            sum
+        }
+    }
+}
+
+/// Sparse embedding (e.g. SPLADE or BM25 term weights) held as parallel index/value arrays
+/// sorted by index. Serialized as `{"indices": [..], "values": [..]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SparseVectorParts")]
+pub struct SparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct SparseVectorParts {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+impl TryFrom<SparseVectorParts> for SparseVector {
+    type Error = anyhow::Error;
+
+    fn try_from(parts: SparseVectorParts) -> Result<Self> {
+        SparseVector::new(parts.indices, parts.values)
+    }
+}
+
+impl SparseVector {
+    /// Builds a sparse vector from unordered index/value arrays. Indices must be unique and
+    /// values finite; explicit zeros are dropped.
+    pub fn new(indices: Vec<u32>, values: Vec<f32>) -> Result<Self> {
+        if indices.len() != values.len() {
+            return Err(anyhow!(
+                "sparse vector has {} indices but {} values",
+                indices.len(),
+                values.len()
+            ));
+        }
+        Self::from_pairs(indices.into_iter().zip(values))
+    }
+
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (u32, f32)>) -> Result<Self> {
+        let mut pairs: Vec<(u32, f32)> = pairs.into_iter().collect();
+        pairs.sort_by_key(|(index, _)| *index);
+        let mut indices = Vec::with_capacity(pairs.len());
+        let mut values = Vec::with_capacity(pairs.len());
+        let mut previous = None;
+        for (index, value) in pairs {
+            if !value.is_finite() {
+                return Err(anyhow!(
+                    "sparse vector value at index {index} is not finite"
+                ));
+            }
+            if previous == Some(index) {
+                return Err(anyhow!(
+                    "sparse vector index {index} appears more than once"
+                ));
+            }
+            previous = Some(index);
+            if value != 0.0 {
+                indices.push(index);
+                values.push(value);
+            }
+        }
+        Ok(Self { indices, values })
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Dot product over shared indices, or `None` when the vectors share no index.
+    pub fn dot(&self, other: &SparseVector) -> Option<f32> {
+        let (mut i, mut j) = (0, 0);
+        let mut sum = 0.0f32;
+        let mut overlap = false;
+        while i < self.indices.len() && j < other.indices.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    sum += self.values[i] * other.values[j];
+                    overlap = true;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        overlap.then_some(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_k_keeps_the_nearest_results_in_order() {
+        let mut top = TopK::new(3);
+        for (row_id, distance) in [(1, 0.9), (2, 0.1), (3, 0.5), (4, 0.7), (5, 0.1), (6, 2.0)] {
+            top.push(row_id, distance);
+        }
+        assert!(!top.accepts(7, 0.7));
+        assert!(top.accepts(7, 0.4));
+        let kept: Vec<(u64, f32)> = top
+            .into_sorted()
+            .into_iter()
+            .map(|res| (res.row_id, res.distance))
+            .collect();
+        assert_eq!(kept, vec![(2, 0.1), (5, 0.1), (3, 0.5)]);
+
+        let mut empty = TopK::new(0);
+        assert!(!empty.accepts(1, 0.0));
+        empty.push(1, 0.0);
+        assert!(empty.into_sorted().is_empty());
+    }
+
+    #[test]
+    fn sparse_vectors_sort_validate_and_dot() {
+        let a = SparseVector::new(vec![7, 2, 4], vec![1.0, 0.5, 0.0]).unwrap();
+        assert_eq!(a.indices(), &[2, 7]);
+        assert_eq!(a.values(), &[0.5, 1.0]);
+
+        let b = SparseVector::from_pairs([(7, 2.0), (3, 9.0), (2, 4.0)]).unwrap();
+        assert_eq!(a.dot(&b), Some(4.0));
+        let disjoint = SparseVector::from_pairs([(1, 1.0)]).unwrap();
+        assert_eq!(a.dot(&disjoint), None);
+
+        assert!(SparseVector::new(vec![1, 1], vec![1.0, 2.0]).is_err());
+        assert!(SparseVector::new(vec![1], vec![]).is_err());
+        assert!(SparseVector::new(vec![1], vec![f32::NAN]).is_err());
+        let parsed: Result<SparseVector, _> =
+            serde_json::from_str(r#"{"indices":[1,2],"values":[1.0]}"#);
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn similarities_fall_in_the_unit_interval() {
+        assert_eq!(similarity(0.0, DistanceMetric::Cosine), 1.0);
+        assert_eq!(similarity(1.0, DistanceMetric::Cosine), 0.5);
+        assert_eq!(similarity(2.0, DistanceMetric::Cosine), 0.0);
+        // Rounding can push cosine distances just outside 0..=2.
+        assert_eq!(similarity(-1e-7, DistanceMetric::Cosine), 1.0);
+        assert_eq!(similarity(2.0 + 1e-6, DistanceMetric::Cosine), 0.0);
+        assert_eq!(similarity(0.0, DistanceMetric::L2), 1.0);
+        assert_eq!(similarity(3.0, DistanceMetric::L2), 0.25);
+        assert_eq!(inverse_distance(f32::INFINITY), 0.0);
+        assert_eq!(inverse_distance(f32::NAN), 0.0);
+    }
+
+    #[test]
+    fn unit_distances_match_raw_distances() {
+        let query = [1.0f32, 2.0, 3.0];
+        let raw = [4.0f32, -1.0, 0.5];
+
+        let mut query_unit = query.to_vec();
+        normalize(&mut query_unit);
+        let mut unit = raw.to_vec();
+        let norm = normalize(&mut unit);
+        let unit = StoredVector::F32(unit);
+
+        for metric in [DistanceMetric::Cosine, DistanceMetric::L2] {
+            let expected = distance(&query, &raw, metric);
+            let actual = distance_to_unit(&query, &query_unit, &unit, norm, metric);
+            assert!((expected - actual).abs() < 1e-4, "{metric:?}");
+        }
+
+        let zero = [0.0f32; 3];
+        assert_eq!(
+            distance_to_unit(&zero, &zero, &unit, norm, DistanceMetric::Cosine),
+            1.0
+        );
+    }
+
+    #[test]
+    fn f16_distances_stay_close_to_f32() {
+        let query = [0.25f32, -1.5, 3.0, 0.125];
+        let raw = vec![1.0f32, 0.5, -2.25, 4.0];
+        let half = StoredVector::encode(raw.clone(), VectorEncoding::F16);
+        assert_eq!(half.len(), 4);
+        assert_eq!(half.to_f32(), raw);
+
+        for metric in [DistanceMetric::Cosine, DistanceMetric::L2] {
+            let expected = distance(&query, &raw, metric);
+            let actual = distance_stored(&query, &half, metric);
+            assert!((expected - actual).abs() < 1e-3, "{metric:?}");
+        }
+    }
+
+    #[test]
+    fn int8_vectors_round_to_a_per_vector_scale() {
+        let query = [0.25f32, -1.5, 3.0, 0.125];
+        let raw = vec![1.0f32, 0.5, -2.25, 4.0];
+        let quantized = StoredVector::encode(raw.clone(), VectorEncoding::Int8);
+        let StoredVector::Int8 { values, scale } = &quantized else {
+            panic!("expected an int8 vector");
+        };
+        assert_eq!(values, &[32, 16, -71, 127]);
+        assert_eq!(*scale, 4.0 / 127.0);
+        for (x, y) in quantized.to_f32().iter().zip(&raw) {
+            assert!((x - y).abs() <= scale / 2.0);
+        }
+
+        for metric in [DistanceMetric::Cosine, DistanceMetric::L2] {
+            let expected = distance(&query, &raw, metric);
+            let actual = distance_stored(&query, &quantized, metric);
+            assert!(
+                (expected - actual).abs() < 0.01 * expected.max(1.0),
+                "{metric:?}"
+            );
+        }
+        let zero = StoredVector::encode(vec![0.0; 4], VectorEncoding::Int8);
+        assert_eq!(zero.to_f32(), vec![0.0; 4]);
+    }
+}
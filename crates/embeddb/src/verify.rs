@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::storage::fault;
+use crate::storage::sst::{self, LoadedSst, SstFile};
+
+/// One SST file as read by `EmbedDb::verify_table`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SstCheck {
+    pub path: PathBuf,
+    pub level: u32,
+    pub seq: u64,
+    pub bytes: u64,
+    /// Rows and tombstones in the file; 0 when it can't be read.
+    pub entries: usize,
+    /// Why the file can't be read, or `None` when every entry decodes.
+    pub error: Option<String>,
+}
+
+/// What `EmbedDb::verify_table` found in a table's SST files, and what `EmbedDb::repair_table`
+/// did about the unreadable ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct TableVerification {
+    pub table: String,
+    /// Oldest first, the order reads apply them in.
+    pub files: Vec<SstCheck>,
+    /// Where the unreadable files were moved to.
+    pub quarantined: Vec<PathBuf>,
+    /// Rows rewritten from the WAL history into the one SST file that replaced all the others;
+    /// `None` when nothing was rebuilt.
+    pub rows_rebuilt: Option<usize>,
+    /// Why the rows of quarantined files couldn't be rebuilt from the WAL history.
+    pub rebuild_error: Option<String>,
+}
+
+impl TableVerification {
+    pub(crate) fn new(table: &str, files: Vec<SstCheck>) -> Self {
+        Self {
+            table: table.to_string(),
+            files,
+            quarantined: Vec::new(),
+            rows_rebuilt: None,
+            rebuild_error: None,
+        }
+    }
+
+    /// The files that can't be read.
+    pub fn unreadable(&self) -> impl Iterator<Item = &SstCheck> {
+        self.files.iter().filter(|check| check.error.is_some())
+    }
+
+    /// Whether every file reads cleanly, or the unreadable ones have all been quarantined.
+    pub fn is_clean(&self) -> bool {
+        self.unreadable().count() == self.quarantined.len()
+    }
+}
+
+/// Reads the whole file and decodes every entry, as a read or compaction reaching it would.
+pub(crate) fn check_file(file: &SstFile) -> SstCheck {
+    let bytes = fs::metadata(&file.path).map_or(0, |meta| meta.len());
+    let read = LoadedSst::load(&file.path).and_then(|loaded| loaded.into_entries());
+    let (entries, error) = match read {
+        Ok(entries) => (entries.len(), None),
+        Err(err) => (0, Some(format!("{err:#}"))),
+    };
+    SstCheck {
+        path: file.path.clone(),
+        level: file.level,
+        seq: file.seq,
+        bytes,
+        entries,
+        error,
+    }
+}
+
+/// Moves `file` into the table's `quarantine/` directory, where `list_sst_files` no longer finds
+/// it, and returns its new path.
+pub(crate) fn quarantine(root: &Path, table: &str, file: &SstFile) -> Result<PathBuf> {
+    let dir = sst::table_dir(root, table).join("quarantine");
+    fs::create_dir_all(&dir)?;
+    let name = file
+        .path
+        .file_name()
+        .ok_or_else(|| anyhow!("sst path has no file name: {}", file.path.display()))?;
+    let dest = dir.join(name);
+    fault::rename(&file.path, &dest)?;
+    Ok(dest)
+}
@@ -0,0 +1,264 @@
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{now_epoch_ms, EmbedDb, Embedder};
+
+/// Settings for the built-in embedding worker enabled by `Config::with_background_embedding`.
+#[derive(Clone)]
+pub struct BackgroundEmbedding {
+    pub embedder: Arc<dyn Embedder>,
+    /// Pause between passes over all tables.
+    pub interval: Duration,
+}
+
+impl fmt::Debug for BackgroundEmbedding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackgroundEmbedding")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Progress of the background embedding worker, reported in `DbStats::embedding_worker`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingWorkerStatus {
+    pub interval_ms: u64,
+    /// Completed passes over all tables.
+    pub passes: u64,
+    /// Jobs attempted, whether they were embedded or failed.
+    pub jobs_processed: u64,
+    pub last_pass_finished_ms: Option<u64>,
+    /// Error from the most recent pass that hit one; cleared by the next clean pass.
+    pub last_error: Option<String>,
+}
+
+/// Progress of the background compaction worker enabled by `Config::with_background_compaction`,
+/// reported in `DbStats::compaction_worker`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactionWorkerStatus {
+    pub interval_ms: u64,
+    /// Completed passes over all tables.
+    pub passes: u64,
+    /// Tables compacted because their level 0 reached the compaction policy's trigger.
+    pub compactions: u64,
+    pub last_pass_finished_ms: Option<u64>,
+    /// Error from the most recent pass that hit one; cleared by the next clean pass.
+    pub last_error: Option<String>,
+}
+
+struct Shared<S> {
+    stop: Mutex<bool>,
+    wake: Condvar,
+    status: Mutex<S>,
+}
+
+impl<S> Shared<S> {
+    fn stopping(&self) -> bool {
+        *lock(&self.stop)
+    }
+
+    /// Sleeps for `interval` or until asked to stop; returns whether to stop.
+    fn wait(&self, interval: Duration) -> bool {
+        let stop = lock(&self.stop);
+        let (stop, _) = self
+            .wake
+            .wait_timeout_while(stop, interval, |stop| !*stop)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *stop
+    }
+}
+
+/// Worker thread draining pending embedding jobs, stopped and joined by `EmbedDb`'s drop.
+pub(crate) struct EmbeddingWorker {
+    shared: Arc<Shared<EmbeddingWorkerStatus>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Worker thread compacting tables whose level 0 reached their `CompactionPolicy` trigger,
+/// stopped and joined by `EmbedDb`'s drop.
+pub(crate) struct CompactionWorker {
+    shared: Arc<Shared<CompactionWorkerStatus>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl fmt::Debug for EmbeddingWorker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddingWorker")
+            .field("status", &self.status())
+            .finish_non_exhaustive()
+    }
+}
+
+impl EmbeddingWorker {
+    /// Starts the worker on `db`, a handle sharing state with the database being opened.
+    pub(crate) fn spawn(db: EmbedDb, settings: BackgroundEmbedding) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            stop: Mutex::new(false),
+            wake: Condvar::new(),
+            status: Mutex::new(EmbeddingWorkerStatus {
+                interval_ms: settings.interval.as_millis() as u64,
+                ..EmbeddingWorkerStatus::default()
+            }),
+        });
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("embeddb-embedding".to_string())
+            .spawn(move || run_embedding(&db, &settings, &thread_shared))?;
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    pub(crate) fn status(&self) -> EmbeddingWorkerStatus {
+        lock(&self.shared.status).clone()
+    }
+
+    /// Asks the thread to exit after its current pass, without waiting for it.
+    pub(crate) fn stop(&self) {
+        *lock(&self.shared.stop) = true;
+        self.shared.wake.notify_all();
+    }
+}
+
+impl Drop for EmbeddingWorker {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            // A panicking pass has already been reported by the panic hook.
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_embedding(
+    db: &EmbedDb,
+    settings: &BackgroundEmbedding,
+    shared: &Shared<EmbeddingWorkerStatus>,
+) {
+    loop {
+        let mut processed = 0u64;
+        let mut error = None;
+        match db.list_tables() {
+            Ok(tables) => {
+                for table in tables {
+                    if shared.stopping() {
+                        return;
+                    }
+                    // Jobs still inside their retry backoff are skipped until a later pass.
+                    match db.process_pending_jobs(&table, settings.embedder.as_ref()) {
+                        Ok(count) => processed += count as u64,
+                        Err(err) => error = Some(format!("{table}: {err}")),
+                    }
+                }
+            }
+            Err(err) => error = Some(err.to_string()),
+        }
+        if let Some(err) = &error {
+            tracing::warn!("background embedding pass failed: {err}");
+        }
+        {
+            let mut status = lock(&shared.status);
+            status.passes += 1;
+            status.jobs_processed += processed;
+            status.last_pass_finished_ms = Some(now_epoch_ms());
+            status.last_error = error;
+        }
+
+        if shared.wait(settings.interval) {
+            return;
+        }
+    }
+}
+
+impl fmt::Debug for CompactionWorker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompactionWorker")
+            .field("status", &self.status())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CompactionWorker {
+    /// Starts the worker on `db`, a handle sharing state with the database being opened.
+    pub(crate) fn spawn(db: EmbedDb, interval: Duration) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            stop: Mutex::new(false),
+            wake: Condvar::new(),
+            status: Mutex::new(CompactionWorkerStatus {
+                interval_ms: interval.as_millis() as u64,
+                ..CompactionWorkerStatus::default()
+            }),
+        });
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("embeddb-compaction".to_string())
+            .spawn(move || run_compaction(&db, interval, &thread_shared))?;
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    pub(crate) fn status(&self) -> CompactionWorkerStatus {
+        lock(&self.shared.status).clone()
+    }
+
+    /// Asks the thread to exit after the table it is compacting, without waiting for it.
+    pub(crate) fn stop(&self) {
+        *lock(&self.shared.stop) = true;
+        self.shared.wake.notify_all();
+    }
+}
+
+impl Drop for CompactionWorker {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_compaction(db: &EmbedDb, interval: Duration, shared: &Shared<CompactionWorkerStatus>) {
+    loop {
+        if shared.wait(interval) {
+            return;
+        }
+        let mut compactions = 0u64;
+        let mut error = None;
+        match db.list_tables() {
+            Ok(tables) => {
+                for table in tables {
+                    if shared.stopping() {
+                        return;
+                    }
+                    match db.compact_table_if_due(&table) {
+                        Ok(Some(_)) => compactions += 1,
+                        Ok(None) => {}
+                        Err(err) => error = Some(format!("{table}: {err}")),
+                    }
+                }
+            }
+            Err(err) => error = Some(err.to_string()),
+        }
+        if let Some(err) = &error {
+            tracing::warn!("background compaction pass failed: {err}");
+        }
+        let mut status = lock(&shared.status);
+        status.passes += 1;
+        status.compactions += compactions;
+        status.last_pass_finished_ms = Some(now_epoch_ms());
+        status.last_error = error;
+    }
+}